@@ -0,0 +1,59 @@
+//! Prepares a local validator for trying the escrow out: two funded wallets holding two fresh
+//! mints and a sample escrow opened by the first one.
+//!
+//! ```text
+//! $ cargo run --example bootstrap --features client -- <PROGRAM_ID> [http://127.0.0.1:8899]
+//! ```
+
+use std::{env, error::Error, str::FromStr};
+
+use paulx_solana_escrow::client::{
+    devtools,
+    rpc::{RpcClient, DEFAULT_RPC_URL},
+};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+const PAYER_AIRDROP_LAMPORTS: u64 = 10_000_000_000;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let program_id = Pubkey::from_str(
+        &args
+            .next()
+            .ok_or("usage: bootstrap <PROGRAM_ID> [RPC_URL]")?,
+    )
+    .map_err(|_| "invalid program id")?;
+    let url = args.next().unwrap_or_else(|| DEFAULT_RPC_URL.to_string());
+    let mut rpc = RpcClient::new(&url)?;
+
+    let payer = Keypair::new();
+    devtools::airdrop(&rpc, &payer.pubkey(), PAYER_AIRDROP_LAMPORTS)?;
+    let maker = Keypair::new();
+    let taker = Keypair::new();
+    let environment = devtools::bootstrap(&mut rpc, &program_id, &payer, &[&maker, &taker]).await?;
+
+    println!("deposit mint {}", environment.deposit_mint);
+    println!("receive mint {}", environment.receive_mint);
+    for (name, keypair, accounts) in [
+        ("maker", &maker, &environment.wallets[0]),
+        ("taker", &taker, &environment.wallets[1]),
+    ]
+    .iter()
+    {
+        println!(
+            "{} {} ({:?}) deposit token account {} receive token account {}",
+            name,
+            accounts.wallet,
+            keypair.to_bytes().to_vec(),
+            accounts.deposit_token_account,
+            accounts.receive_token_account
+        );
+    }
+    println!(
+        "sample escrow {} vault {}",
+        environment.escrow, environment.vault
+    );
+    Ok(())
+}
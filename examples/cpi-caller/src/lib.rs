@@ -0,0 +1,162 @@
+//! A program that opens and takes escrows through cross-program invocations, with a PDA of its
+//! own as the initializer and as the taker. It builds the escrow instructions with
+//! `paulx_solana_escrow::instruction`, the way any program depending on the escrow crate would.
+
+#![forbid(unsafe_code)]
+
+use std::convert::TryInto;
+
+use paulx_solana_escrow::{
+    instruction::{self as escrow_instruction, ExchangeAccounts},
+    state::Escrow,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use spl_token::state::Account as TokenAccount;
+
+solana_program::declare_id!("CpiCa11er1111111111111111111111111111111111");
+
+/// Seed of the PDA that owns the caller's token accounts and signs for them
+pub const AUTHORITY_SEED: &[u8] = b"authority";
+
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CallerInstruction {
+    /// Opens an escrow on a vault the authority owns
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[]` The authority, the PDA of `["authority"]`
+    /// 1. `[writable]` The vault holding the deposit, owned by the authority
+    /// 2. `[]` The authority's token account for the token it will receive
+    /// 3. `[writable]` The escrow account, created for and owned by the escrow program
+    /// 4. `[]` The rent sysvar
+    /// 5. `[]` The token program
+    /// 6. `[]` The escrow's mint list account
+    /// 7. `[]` The clock sysvar
+    /// 8. `[]` The escrow program
+    Open {
+        /// The amount the authority expects to receive
+        amount: u64,
+    },
+    /// Takes an escrow without a fee account, paying from and receiving to the authority's
+    /// token accounts
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The authority, the PDA of `["authority"]`
+    /// 1. `[writable]` The authority's token account for the token it sends
+    /// 2. `[writable]` The authority's token account for the token it will receive
+    /// 3. `[writable]` The escrow's vault
+    /// 4. `[writable]` The initializer's main account
+    /// 5. `[writable]` The initializer's token account that will receive tokens
+    /// 6. `[writable]` The escrow account
+    /// 7. `[]` The token program
+    /// 8. `[]` The escrow's PDA account
+    /// 9. `[]` The escrow's config account
+    /// 10. `[writable]` The escrow's volume account of the deposited mint
+    /// 11. `[]` The escrow program
+    Fill {
+        /// The amount of the deposit the authority expects to be paid
+        amount: u64,
+    },
+}
+
+impl CallerInstruction {
+    pub fn pack(&self) -> Vec<u8> {
+        let (tag, amount) = match self {
+            Self::Open { amount } => (0, amount),
+            Self::Fill { amount } => (1, amount),
+        };
+        let mut data = vec![tag];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data
+    }
+
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (tag, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        let amount = rest
+            .try_into()
+            .map(u64::from_le_bytes)
+            .map_err(|_| ProgramError::InvalidInstructionData)?;
+        match tag {
+            0 => Ok(Self::Open { amount }),
+            1 => Ok(Self::Fill { amount }),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+}
+
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let (authority_key, bump_seed) = Pubkey::find_program_address(&[AUTHORITY_SEED], program_id);
+    if *authority.key != authority_key {
+        msg!("error: expected the authority PDA");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let escrow_program = accounts.last().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    // the authority signs whatever is invoked, so only ever hand its signature to the escrow
+    if *escrow_program.key != paulx_solana_escrow::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let instruction = match CallerInstruction::unpack(instruction_data)? {
+        CallerInstruction::Open { amount } => {
+            let vault = next_account_info(account_info_iter)?;
+            let receive_account = next_account_info(account_info_iter)?;
+            let escrow = next_account_info(account_info_iter)?;
+            msg!("Opening an escrow through the escrow program...");
+            escrow_instruction::init_escrow(
+                escrow_program.key,
+                authority.key,
+                vault.key,
+                receive_account.key,
+                escrow.key,
+                amount,
+            )
+        }
+        CallerInstruction::Fill { amount } => {
+            let sending_account = next_account_info(account_info_iter)?;
+            let receiving_account = next_account_info(account_info_iter)?;
+            let vault = next_account_info(account_info_iter)?;
+            // the initializer, its token account, the escrow and the rest of the accounts are
+            // checked by the escrow program
+            let escrow = accounts.get(6).ok_or(ProgramError::NotEnoughAccountKeys)?;
+            let escrow_info = Escrow::unpack(&escrow.data.borrow())?;
+            let deposit_mint = TokenAccount::unpack(&vault.data.borrow())?.mint;
+            msg!("Filling the escrow through the escrow program...");
+            escrow_instruction::exchange(
+                escrow_program.key,
+                &ExchangeAccounts {
+                    taker: *authority.key,
+                    takers_sending_token_account: *sending_account.key,
+                    takers_token_to_receive_account: *receiving_account.key,
+                    escrow: *escrow.key,
+                    fee_account: None,
+                },
+                &escrow_info,
+                &deposit_mint,
+                amount,
+            )
+        }
+    };
+    invoke_signed(&instruction, accounts, &[&[AUTHORITY_SEED, &[bump_seed]]])
+}
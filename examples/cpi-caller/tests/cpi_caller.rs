@@ -0,0 +1,174 @@
+#![cfg(feature = "test-bpf")]
+
+use escrow_cpi_caller::{CallerInstruction, AUTHORITY_SEED};
+use paulx_solana_escrow::{
+    state::{Config, Escrow, MintList, MintVolume},
+    test_utils::{self, fixture, keypair, token_account, Replay},
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    sysvar,
+};
+use solana_program_test::{processor, ProgramTest};
+use solana_sdk::{account::Account, signature::Signer, transaction::Transaction};
+
+const DEPOSIT_AMOUNT: u64 = 100;
+
+fn program_test() -> ProgramTest {
+    let mut program_test = test_utils::program_test(paulx_solana_escrow::id());
+    program_test.add_program(
+        "escrow_cpi_caller",
+        escrow_cpi_caller::id(),
+        processor!(escrow_cpi_caller::process_instruction),
+    );
+    program_test
+}
+
+fn authority() -> Pubkey {
+    Pubkey::find_program_address(&[AUTHORITY_SEED], &escrow_cpi_caller::id()).0
+}
+
+/// The authority with the lamports for the rebate of a closing fill
+fn authority_account() -> (Pubkey, Account) {
+    (
+        authority(),
+        Account {
+            lamports: Rent::default().minimum_balance(0),
+            ..Account::default()
+        },
+    )
+}
+
+async fn process(replay: &mut Replay, instruction: Instruction) {
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&replay.payer.pubkey()));
+    transaction.sign(&[&replay.payer], replay.recent_blockhash);
+    replay
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_open_through_cpi() {
+    let (deposit_mint, _) = fixture("mint_with_freeze_authority");
+    let mint_y = keypair("mint y").pubkey();
+    let vault = keypair("caller vault").pubkey();
+    let receive_account = keypair("caller y token account").pubkey();
+    let escrow = keypair("caller escrow").pubkey();
+    let program_id = paulx_solana_escrow::id();
+    let mut replay = Replay::start_in(
+        program_test(),
+        program_id,
+        "token_account_delegated",
+        vec![
+            authority_account(),
+            (
+                vault,
+                token_account(deposit_mint, authority(), DEPOSIT_AMOUNT),
+            ),
+            (receive_account, token_account(mint_y, authority(), 0)),
+            (
+                escrow,
+                Account {
+                    lamports: Rent::default().minimum_balance(Escrow::LEN),
+                    owner: program_id,
+                    data: vec![0; Escrow::LEN],
+                    ..Account::default()
+                },
+            ),
+        ],
+    )
+    .await;
+
+    let open = Instruction::new_with_bytes(
+        escrow_cpi_caller::id(),
+        &CallerInstruction::Open { amount: 150 }.pack(),
+        vec![
+            AccountMeta::new_readonly(authority(), false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(receive_account, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(MintList::find_address(&program_id).0, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(program_id, false),
+        ],
+    );
+    process(&mut replay, open).await;
+
+    let account = replay.banks_client.get_account(escrow).await.unwrap();
+    let escrow_info = Escrow::unpack(&account.unwrap().data).unwrap();
+    assert_eq!(escrow_info.initializer_pubkey, authority());
+    assert_eq!(escrow_info.temp_token_account_pubkey, vault);
+    assert_eq!(escrow_info.expected_amount, 150);
+    let pda = replay.pda();
+    assert_eq!(replay.token_account(vault).await.unwrap().owner, pda);
+}
+
+#[tokio::test]
+async fn test_fill_through_cpi() {
+    let (deposit_mint, _) = fixture("mint_with_freeze_authority");
+    let mint_y = keypair("mint y").pubkey();
+    let sending_account = keypair("caller y token account").pubkey();
+    let receiving_account = keypair("caller x token account").pubkey();
+    let program_id = paulx_solana_escrow::id();
+    let mut replay = Replay::start_in(
+        program_test(),
+        program_id,
+        "token_account_delegated",
+        vec![
+            authority_account(),
+            (
+                sending_account,
+                token_account(mint_y, authority(), Replay::EXPECTED_AMOUNT),
+            ),
+            (
+                receiving_account,
+                token_account(deposit_mint, authority(), 0),
+            ),
+        ],
+    )
+    .await;
+    replay.init().await.unwrap();
+
+    let fill = Instruction::new_with_bytes(
+        escrow_cpi_caller::id(),
+        &CallerInstruction::Fill {
+            amount: replay.deposit_amount,
+        }
+        .pack(),
+        vec![
+            AccountMeta::new(authority(), false),
+            AccountMeta::new(sending_account, false),
+            AccountMeta::new(receiving_account, false),
+            AccountMeta::new(replay.vault, false),
+            AccountMeta::new(replay.initializer.pubkey(), false),
+            AccountMeta::new(replay.initializer_y_token_account, false),
+            AccountMeta::new(replay.escrow_account, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(replay.pda(), false),
+            AccountMeta::new_readonly(Config::find_address(&program_id).0, false),
+            AccountMeta::new(
+                MintVolume::find_address(&deposit_mint, &program_id).0,
+                false,
+            ),
+            AccountMeta::new_readonly(program_id, false),
+        ],
+    );
+    process(&mut replay, fill).await;
+
+    let deposit_amount = replay.deposit_amount;
+    let received = replay.token_account(receiving_account).await.unwrap();
+    assert_eq!(received.amount, deposit_amount);
+    let paid = replay
+        .token_account(replay.initializer_y_token_account)
+        .await
+        .unwrap();
+    assert_eq!(paid.amount, Replay::EXPECTED_AMOUNT);
+    assert!(replay.token_account(replay.vault).await.is_none());
+}
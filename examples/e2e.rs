@@ -0,0 +1,155 @@
+//! Opens and fills an escrow on a fresh `solana-test-validator`, through the same JSON-RPC calls,
+//! blockhashes and preflight checks a wallet goes through on a real cluster.
+//!
+//! ```text
+//! $ cargo build-bpf
+//! $ cargo run --example e2e --features client -- [target/deploy/paulx_solana_escrow.so]
+//! ```
+//!
+//! Needs `solana-test-validator` on the `PATH`. The validator runs from a throwaway ledger on its
+//! own ports, so it does not get in the way of one already running, and is killed on exit.
+
+use std::{
+    env,
+    error::Error,
+    fs,
+    path::PathBuf,
+    process::{self, Child, Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use paulx_solana_escrow::{
+    client::{self, devtools, rpc::RpcClient, ExchangeAccounts},
+    state::FillStatus,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+const RPC_PORT: u16 = 18899;
+
+const FAUCET_PORT: u16 = 19900;
+
+const DEFAULT_PROGRAM_PATH: &str = "target/deploy/paulx_solana_escrow.so";
+
+const PAYER_AIRDROP_LAMPORTS: u64 = 10_000_000_000;
+
+const STARTUP_POLLS: usize = 120;
+
+/// A running `solana-test-validator`, killed when dropped
+struct TestValidator {
+    process: Child,
+    ledger: PathBuf,
+}
+
+impl TestValidator {
+    /// Starts a validator with the escrow program at `paulx_solana_escrow::id()` and waits for
+    /// its RPC endpoint to report healthy
+    fn start(program_path: &str) -> Result<(Self, RpcClient), Box<dyn Error>> {
+        let ledger = env::temp_dir().join(format!("escrow-e2e-{}", process::id()));
+        let child = Command::new("solana-test-validator")
+            .arg("--reset")
+            .arg("--quiet")
+            .arg("--ledger")
+            .arg(&ledger)
+            .args(["--rpc-port", &RPC_PORT.to_string()])
+            .args(["--faucet-port", &FAUCET_PORT.to_string()])
+            .arg("--bpf-program")
+            .arg(paulx_solana_escrow::id().to_string())
+            .arg(program_path)
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|error| format!("can not start solana-test-validator: {}", error))?;
+        let validator = Self {
+            process: child,
+            ledger,
+        };
+
+        let rpc = RpcClient::new(&format!("http://127.0.0.1:{}", RPC_PORT))?;
+        for _ in 0..STARTUP_POLLS {
+            // refused until the RPC service is up, then an error until the node is caught up
+            if let Ok(health) = rpc.call("getHealth", serde_json::json!([])) {
+                if health == "ok" {
+                    return Ok((validator, rpc));
+                }
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+        Err("solana-test-validator did not become healthy in time".into())
+    }
+}
+
+impl Drop for TestValidator {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = fs::remove_dir_all(&self.ledger);
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let program_path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_PROGRAM_PATH.to_string());
+    let program_id = paulx_solana_escrow::id();
+    let (_validator, mut rpc) = TestValidator::start(&program_path)?;
+    println!("validator up, escrow program at {}", program_id);
+
+    let payer = Keypair::new();
+    devtools::airdrop(&rpc, &payer.pubkey(), PAYER_AIRDROP_LAMPORTS)?;
+    let maker = Keypair::new();
+    let taker = Keypair::new();
+    // creates the mints and token accounts, then sends InitEscrow partially signed like a
+    // wallet adapter flow would
+    let environment = devtools::bootstrap(&mut rpc, &program_id, &payer, &[&maker, &taker]).await?;
+    println!(
+        "opened escrow {} on vault {}",
+        environment.escrow, environment.vault
+    );
+
+    let view = client::view_escrow(
+        &mut rpc,
+        &program_id,
+        &payer.pubkey(),
+        &environment.escrow,
+        &environment.vault,
+    )
+    .await?;
+    if view.fill_status != FillStatus::Open || view.vault_amount != devtools::SAMPLE_DEPOSIT_AMOUNT
+    {
+        return Err(format!("unexpected escrow after InitEscrow: {:?}", view).into());
+    }
+
+    let taker_accounts = environment.wallets[1];
+    let accounts = ExchangeAccounts {
+        taker: taker.pubkey(),
+        takers_sending_token_account: taker_accounts.receive_token_account,
+        takers_token_to_receive_account: taker_accounts.deposit_token_account,
+        escrow: environment.escrow,
+        fee_account: None,
+    };
+    client::exchange(&mut rpc, &program_id, &taker, &accounts, None).await?;
+    println!("filled escrow {}", environment.escrow);
+
+    if client::fetch_escrow(&mut rpc, &environment.escrow)
+        .await?
+        .is_some()
+    {
+        return Err("the filled escrow was not closed".into());
+    }
+    let received =
+        client::fetch_token_account(&mut rpc, &taker_accounts.deposit_token_account).await?;
+    let paid = client::fetch_token_account(&mut rpc, &environment.wallets[0].receive_token_account)
+        .await?;
+    if received.amount != devtools::WALLET_TOKEN_BALANCE + devtools::SAMPLE_DEPOSIT_AMOUNT
+        || paid.amount != devtools::WALLET_TOKEN_BALANCE + devtools::SAMPLE_EXPECTED_AMOUNT
+    {
+        return Err(format!(
+            "unexpected balances after Exchange: taker received {}, maker holds {}",
+            received.amount, paid.amount
+        )
+        .into());
+    }
+    println!("balances settled, end-to-end run passed");
+    Ok(())
+}
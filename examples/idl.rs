@@ -0,0 +1,23 @@
+//! Writes the Anchor IDL of `anchor_compat::idl` to `idl/paulx_solana_escrow.json`, or to the
+//! given path. Rerun it whenever an instruction or account layout changes, the
+//! `test_checked_in_idl` unit test fails until the checked-in file is regenerated.
+//!
+//! ```text
+//! $ cargo run --example idl --features anchor-compat [PATH]
+//! ```
+
+use std::{env, error::Error, fs};
+
+use paulx_solana_escrow::anchor_compat;
+
+const DEFAULT_IDL_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/idl/paulx_solana_escrow.json");
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_IDL_PATH.to_string());
+    let idl = anchor_compat::idl(&paulx_solana_escrow::id());
+    fs::write(&path, serde_json::to_string_pretty(&idl)? + "\n")?;
+    println!("wrote {}", path);
+    Ok(())
+}
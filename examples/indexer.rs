@@ -0,0 +1,172 @@
+//! Keeps an in-memory order book of open escrows from the program's event logs.
+//!
+//! ```text
+//! $ cargo run --example indexer --features client -- <PROGRAM_ID> [ws://127.0.0.1:8900]
+//! ```
+//!
+//! Subscribes to `logsSubscribe` over the `client` feature's plain `ws://` websocket, which is
+//! what a local test validator serves. Public RPC nodes only speak `wss://`, put a TLS
+//! terminating proxy in front of them.
+
+use std::{collections::BTreeMap, env, error::Error, str::FromStr};
+
+use paulx_solana_escrow::{
+    client::websocket::{WebSocket, DEFAULT_WEBSOCKET_URL},
+    event::{decode, EscrowEvent},
+};
+use serde_json::{json, Value};
+use solana_program::pubkey::Pubkey;
+
+/// What is known of an escrow that is still open
+#[derive(Debug, Default)]
+struct Order {
+    initializer: Option<Pubkey>,
+    /// the mints swapped, unknown for escrows relisted before the indexer started
+    mints: Option<(Pubkey, Pubkey)>,
+    expected_amount: Option<u64>,
+    /// deposit left after the last fill, or when opened
+    remaining_amount: Option<u64>,
+    filled_amount: u64,
+    paid_amount: u64,
+}
+
+#[derive(Debug, Default)]
+struct OrderBook {
+    orders: BTreeMap<Pubkey, Order>,
+}
+
+impl OrderBook {
+    fn apply(&mut self, event: &EscrowEvent) {
+        match *event {
+            EscrowEvent::Initialized {
+                escrow,
+                initializer,
+                expected_amount,
+            } => {
+                let order = self.orders.entry(escrow).or_default();
+                order.initializer = Some(initializer);
+                order.expected_amount = Some(expected_amount);
+            }
+            EscrowEvent::Opened {
+                escrow,
+                initializer,
+                deposit_mint,
+                receive_mint,
+                deposit_amount,
+                expected_amount,
+                ..
+            } => {
+                let order = self.orders.entry(escrow).or_default();
+                order.initializer = Some(initializer);
+                order.mints = Some((deposit_mint, receive_mint));
+                order.expected_amount = Some(expected_amount);
+                order.remaining_amount = Some(deposit_amount);
+            }
+            EscrowEvent::Filled {
+                escrow,
+                fill_amount,
+                paid_amount,
+                remaining_amount,
+                ..
+            } => {
+                if remaining_amount == 0 {
+                    self.orders.remove(&escrow);
+                    return;
+                }
+                let order = self.orders.entry(escrow).or_default();
+                order.remaining_amount = Some(remaining_amount);
+                order.filled_amount += fill_amount;
+                order.paid_amount += paid_amount;
+            }
+            EscrowEvent::Cancelled { escrow, .. }
+            | EscrowEvent::RecoveryExecuted { escrow, .. } => {
+                self.orders.remove(&escrow);
+            }
+            // the escrow stays open until the recovery is executed
+            EscrowEvent::RecoveryProposed { .. } | EscrowEvent::RecoveryCancelled { .. } => {}
+        }
+    }
+
+    fn print(&self) {
+        println!("{} open escrows", self.orders.len());
+        for (escrow, order) in &self.orders {
+            println!(
+                "  {} initializer {} sells {} for {} expects {} remaining {} filled {} paid {}",
+                escrow,
+                display(order.initializer),
+                display(order.mints.map(|(deposit_mint, _)| deposit_mint)),
+                display(order.mints.map(|(_, receive_mint)| receive_mint)),
+                display(order.expected_amount),
+                display(order.remaining_amount),
+                order.filled_amount,
+                order.paid_amount
+            );
+        }
+    }
+}
+
+fn display<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "?".to_string(), |value| value.to_string())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let program_id = Pubkey::from_str(
+        &args
+            .next()
+            .ok_or("usage: indexer <PROGRAM_ID> [WEBSOCKET_URL]")?,
+    )
+    .map_err(|_| "invalid program id")?;
+    let url = args
+        .next()
+        .unwrap_or_else(|| DEFAULT_WEBSOCKET_URL.to_string());
+
+    let mut socket = WebSocket::connect(&url)?;
+    let subscribe = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "logsSubscribe",
+        "params": [{ "mentions": [program_id.to_string()] }, { "commitment": "confirmed" }],
+    });
+    socket.send(WebSocket::OPCODE_TEXT, subscribe.to_string().as_bytes())?;
+    println!("indexing {} from {}", program_id, url);
+
+    let mut order_book = OrderBook::default();
+    while let Some(message) = socket.receive()? {
+        let message: Value = serde_json::from_str(&message)?;
+        if let Some(error) = message.get("error") {
+            return Err(format!("subscription failed: {}", error).into());
+        }
+        let value = &message["params"]["result"]["value"];
+        // a failed transaction's logs describe state changes that were rolled back
+        if !value["err"].is_null() {
+            continue;
+        }
+        let mut events = Vec::new();
+        for log in value["logs"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            match decode::decode_log(log) {
+                Some(Ok(event)) => events.push(event),
+                // skipping an event would leave the order book silently wrong
+                Some(Err(error)) => {
+                    return Err(format!("can not decode {:?}: {:?}", log, error).into())
+                }
+                None => {}
+            }
+        }
+        if events.is_empty() {
+            continue;
+        }
+        println!("transaction {}", value["signature"]);
+        for event in &events {
+            println!("  {:?}", event);
+            order_book.apply(event);
+        }
+        order_book.print();
+    }
+    Ok(())
+}
@@ -0,0 +1,52 @@
+//! Times `Escrow` unpacking and packing on the host, a rough stand-in for what every handler
+//! touching an escrow spends on its state.
+//!
+//! ```text
+//! $ cargo run --release --example pack_bench [ITERATIONS]
+//! ```
+
+use std::{env, hint::black_box, time::Instant};
+
+use paulx_solana_escrow::state::{Escrow, MetadataUri, SplitRecipient, MAX_SPLIT_RECIPIENTS};
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+
+fn main() {
+    let iterations = env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("ITERATIONS must be a number"))
+        .unwrap_or(1_000_000u32);
+
+    let escrow = Escrow {
+        is_initialized: true,
+        initializer_pubkey: Pubkey::new_unique(),
+        temp_token_account_pubkey: Pubkey::new_unique(),
+        initializer_token_to_receive_account_pubkey: Pubkey::new_unique(),
+        expected_amount: 150,
+        metadata_uri: MetadataUri::new("https://example.com/offer.json").unwrap(),
+        split_recipients: [SplitRecipient {
+            token_account: Pubkey::new_unique(),
+            share_bps: 250,
+        }; MAX_SPLIT_RECIPIENTS],
+        ..Escrow::default()
+    };
+    let mut data = vec![0; Escrow::LEN];
+    Escrow::pack(escrow, &mut data).unwrap();
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        black_box(Escrow::unpack(black_box(&data)).unwrap());
+    }
+    let unpack = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        Escrow::pack(black_box(escrow), black_box(&mut data)).unwrap();
+    }
+    let pack = start.elapsed();
+
+    println!(
+        "unpack {:.1} ns, pack {:.1} ns",
+        unpack.as_nanos() as f64 / iterations as f64,
+        pack.as_nanos() as f64 / iterations as f64
+    );
+}
@@ -0,0 +1,156 @@
+//! Serves Solana Pay transaction requests filling escrows, so merchants can accept fills through
+//! wallet deep links and QR codes. Wallets GET and POST `/fill?escrow=<ESCROW>`, the POST being
+//! answered with the Exchange built by `client::pay::post_response`. `/url?escrow=<ESCROW>` gives
+//! the `solana:` URL to show as a QR code. Wallets only talk to https endpoints, so put a TLS
+//! terminating proxy in front of this server and pass the public URL it is reached at.
+//!
+//! ```text
+//! $ cargo run --example pay_server --features client -- <PROGRAM_ID> <PUBLIC_URL> [http://127.0.0.1:8899] [127.0.0.1:8080]
+//! ```
+
+use std::{convert::Infallible, env, error::Error, net::SocketAddr, str::FromStr, sync::Arc};
+
+use hyper::{
+    body,
+    header::{
+        HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_TYPE,
+    },
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use paulx_solana_escrow::{
+    client::{
+        associated_token,
+        offer::Offer,
+        pay,
+        rpc::{RpcClient, DEFAULT_RPC_URL},
+        ClientResult, EscrowRpc,
+    },
+    state::Config,
+};
+use serde_json::{json, Value};
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+
+const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:8080";
+
+const LABEL: &str = "Escrow fill";
+
+const ICON: &str = "https://solana.com/src/img/branding/solanaLogoMark.svg";
+
+struct Settings {
+    program_id: Pubkey,
+    public_url: String,
+    rpc: RpcClient,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let usage = "usage: pay_server <PROGRAM_ID> <PUBLIC_URL> [RPC_URL] [LISTEN_ADDRESS]";
+    let mut args = env::args().skip(1);
+    let program_id =
+        Pubkey::from_str(&args.next().ok_or(usage)?).map_err(|_| "invalid program id")?;
+    let public_url = args.next().ok_or(usage)?;
+    pay::transaction_request_url(&public_url)?;
+    let rpc = RpcClient::new(&args.next().unwrap_or_else(|| DEFAULT_RPC_URL.to_string()))?;
+    let address: SocketAddr = args
+        .next()
+        .unwrap_or_else(|| DEFAULT_LISTEN_ADDRESS.to_string())
+        .parse()?;
+
+    let settings = Arc::new(Settings {
+        program_id,
+        public_url: public_url.trim_end_matches('/').to_string(),
+        rpc,
+    });
+    let make_service = make_service_fn(move |_| {
+        let settings = settings.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let settings = settings.clone();
+                async move { Ok::<_, Infallible>(respond(&settings, request).await) }
+            }))
+        }
+    });
+    println!("serving transaction requests on {}", address);
+    Server::bind(&address).serve(make_service).await?;
+    Ok(())
+}
+
+async fn respond(settings: &Settings, request: Request<Body>) -> Response<Body> {
+    let escrow = request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("escrow="))
+            .and_then(|escrow| Pubkey::from_str(escrow).ok())
+    });
+    let path = request.uri().path().to_string();
+    let (status, body) = match (request.method(), path.as_str(), escrow) {
+        // wallets fetching from a browser context ask first
+        (&Method::OPTIONS, _, _) => (StatusCode::NO_CONTENT, None),
+        (&Method::GET, "/fill", Some(_)) => (StatusCode::OK, Some(pay::get_response(LABEL, ICON))),
+        (&Method::POST, "/fill", Some(escrow)) => {
+            match fill_transaction(settings, &escrow, request.into_body()).await {
+                Ok(response) => (StatusCode::OK, Some(response)),
+                Err(error) => (
+                    StatusCode::BAD_REQUEST,
+                    Some(json!({ "error": error.to_string() })),
+                ),
+            }
+        }
+        (&Method::GET, "/url", Some(escrow)) => {
+            let endpoint = format!("{}/fill?escrow={}", settings.public_url, escrow);
+            match pay::transaction_request_url(&endpoint) {
+                Ok(url) => (StatusCode::OK, Some(json!({ "url": url }))),
+                Err(error) => (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Some(json!({ "error": error.to_string() })),
+                ),
+            }
+        }
+        (_, "/fill", None) | (_, "/url", None) => (
+            StatusCode::BAD_REQUEST,
+            Some(json!({ "error": "missing escrow" })),
+        ),
+        _ => (StatusCode::NOT_FOUND, None),
+    };
+
+    let mut response = Response::new(body.map_or_else(Body::empty, |body| body.to_string().into()));
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+    headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+    headers.insert(
+        ACCESS_CONTROL_ALLOW_HEADERS,
+        HeaderValue::from_static("Content-Type"),
+    );
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+/// The Exchange of the whole deposit of `escrow` by the account the wallet posted
+async fn fill_transaction(
+    settings: &Settings,
+    escrow: &Pubkey,
+    body: Body,
+) -> Result<Value, Box<dyn Error + Send + Sync>> {
+    let body = body::to_bytes(body).await?;
+    let request: Value = serde_json::from_slice(&body)?;
+    let account = request["account"].as_str().ok_or("missing account")?;
+    let account = Pubkey::from_str(account).map_err(|_| "invalid account")?;
+
+    // `RpcClient` blocks, which the multi-threaded runtime can afford for an example
+    let mut rpc = settings.rpc.clone();
+    let mut offer = Offer::fetch(&mut rpc, &settings.program_id, escrow, None).await?;
+    offer.fee_account = fee_account(&mut rpc, &offer).await?;
+    Ok(pay::post_response(&mut rpc, &offer, &account).await?)
+}
+
+/// The fee owner's associated token account for the mint the taker pays in, once the config
+/// exists
+async fn fee_account(rpc: &mut RpcClient, offer: &Offer) -> ClientResult<Option<Pubkey>> {
+    Ok(match rpc.get_account(&offer.config()).await? {
+        Some(account) => Config::unpack(&account.data)
+            .ok()
+            .map(|config| associated_token::find_address(&config.fee_owner, &offer.receive_mint)),
+        None => None,
+    })
+}
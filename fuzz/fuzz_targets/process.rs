@@ -0,0 +1,169 @@
+//! Feeds arbitrary instructions and accounts to `Processor::process`.
+//!
+//! ```text
+//! $ cargo +nightly fuzz run process
+//! ```
+//!
+//! Cross-program invocations are stubbed to succeed without effect, so token balances are out of
+//! reach; the target checks what the runtime would enforce on the escrow program itself. It must
+//! not panic, and a successful run must conserve lamports, leave read-only accounts alone, and
+//! only change the data of, or debit, accounts the program owns.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use paulx_solana_escrow::{fuzz::InstructionData, processor::Processor, state::Config};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+
+struct FuzzSyscallStubs;
+
+impl SyscallStubs for FuzzSyscallStubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_invoke_signed(
+        &self,
+        _instruction: &Instruction,
+        _account_infos: &[AccountInfo],
+        _signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        Ok(())
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+struct FuzzAccount {
+    /// index into the keys the processor compares against, wrapping around
+    key: u8,
+    is_signer: bool,
+    is_writable: bool,
+    lamports: u64,
+    data: Vec<u8>,
+    /// whether the escrow program rather than the token program owns the account
+    program_owned: bool,
+}
+
+#[derive(Arbitrary, Debug)]
+struct FuzzInstruction {
+    /// mostly a known tag followed by data of the tag's length
+    data: InstructionData,
+    accounts: Vec<FuzzAccount>,
+}
+
+fn program_id() -> Pubkey {
+    paulx_solana_escrow::id()
+}
+
+/// Keys the processor checks accounts against, plus a few it knows nothing about
+fn key_pool() -> Vec<Pubkey> {
+    let program_id = program_id();
+    let mut keys = vec![
+        program_id,
+        spl_token::id(),
+        system_program::id(),
+        sysvar::rent::id(),
+        sysvar::clock::id(),
+        sysvar::instructions::id(),
+        Pubkey::find_program_address(&[b"escrow"], &program_id).0,
+        Config::find_address(&program_id).0,
+    ];
+    keys.extend((1..=8).map(|byte| Pubkey::new_from_array([byte; 32])));
+    keys
+}
+
+fuzz_target!(|input: FuzzInstruction| {
+    program_stubs::set_syscall_stubs(Box::new(FuzzSyscallStubs));
+    let program_id = program_id();
+    let key_pool = key_pool();
+
+    // an account passed twice is the same account, as in the runtime
+    let mut storage: Vec<(Pubkey, bool, bool, u64, Vec<u8>, Pubkey)> = vec![];
+    let mut positions = vec![];
+    for account in input.accounts.iter().take(32) {
+        let key = key_pool[account.key as usize % key_pool.len()];
+        let position = match storage.iter().position(|stored| stored.0 == key) {
+            Some(position) => position,
+            None => {
+                let owner = if account.program_owned {
+                    program_id
+                } else {
+                    spl_token::id()
+                };
+                storage.push((
+                    key,
+                    account.is_signer,
+                    account.is_writable,
+                    account.lamports,
+                    account.data.clone(),
+                    owner,
+                ));
+                storage.len() - 1
+            }
+        };
+        positions.push(position);
+    }
+    let before: Vec<(u64, Vec<u8>)> = storage
+        .iter()
+        .map(|(_, _, _, lamports, data, _)| (*lamports, data.clone()))
+        .collect();
+    let before_total = before
+        .iter()
+        .fold(0u128, |total, (lamports, _)| total + *lamports as u128);
+
+    let unique_infos: Vec<AccountInfo> = storage
+        .iter_mut()
+        .map(|(key, is_signer, is_writable, lamports, data, owner)| {
+            AccountInfo::new(
+                key,
+                *is_signer,
+                *is_writable,
+                lamports,
+                data,
+                owner,
+                false,
+                0,
+            )
+        })
+        .collect();
+    let account_infos: Vec<AccountInfo> = positions
+        .iter()
+        .map(|position| unique_infos[*position].clone())
+        .collect();
+
+    if Processor::process(&program_id, &account_infos, &input.data.0).is_err() {
+        return;
+    }
+
+    let mut after_total = 0u128;
+    for (info, (lamports_before, data_before)) in unique_infos.iter().zip(before.iter()) {
+        let lamports = info.lamports();
+        let data = info.data.borrow();
+        after_total += lamports as u128;
+        let changed = lamports != *lamports_before || **data != data_before[..];
+        assert!(
+            !changed || info.is_writable,
+            "read-only account {} changed",
+            info.key
+        );
+        if *info.owner != program_id {
+            assert!(
+                **data == data_before[..],
+                "data of {} changed, the program does not own it",
+                info.key
+            );
+            assert!(
+                lamports >= *lamports_before,
+                "{} debited, the program does not own it",
+                info.key
+            );
+        }
+    }
+    assert_eq!(before_total, after_total, "lamports created or destroyed");
+});
@@ -1,2 +1,3 @@
 #!/bin/bash
 RUST_BACKTRACE=1 cargo test-bpf -- --nocapture
+RUST_BACKTRACE=1 cargo test-bpf --manifest-path examples/cpi-caller/Cargo.toml -- --nocapture
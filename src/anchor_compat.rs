@@ -0,0 +1,522 @@
+//! Anchor tooling support, behind the `anchor-compat` feature: Anchor style account
+//! discriminators and an IDL in Anchor's 0.30 format that `anchor-client`, `anchor-gen` and
+//! `declare_program!` consume. The program is not an Anchor program, so instruction
+//! discriminators are the one byte tags `EscrowInstruction::unpack` reads rather than sighashes,
+//! and only the arguments every client needs are described, the optional trailing terms are not.
+
+use serde_json::{json, Value};
+use solana_program::{
+    hash::hashv,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+    sysvar,
+};
+
+use crate::state::{
+    Config, Discriminator, Escrow, MintList, MintVolume, RelayNonce, CONDITION_VALUE_LEN,
+    MAX_LISTED_MINTS, MAX_MINT_FEE_TIERS, MAX_MINT_MINIMUMS, MAX_SPLIT_RECIPIENTS,
+    MEMO_REFERENCE_LEN, METADATA_URI_LEN,
+};
+
+pub use crate::state::DISCRIMINATOR_LEN;
+
+/// Anchor's discriminator of the account type `name`, the first bytes of
+/// `sha256("account:<name>")`
+pub fn account_discriminator(name: &str) -> [u8; DISCRIMINATOR_LEN] {
+    let mut discriminator = [0; DISCRIMINATOR_LEN];
+    discriminator.copy_from_slice(&hashv(&[b"account:", name.as_bytes()]).to_bytes()[..8]);
+    discriminator
+}
+
+/// A program account as Anchor clients see it. The packed state already starts with Anchor's
+/// discriminator, so the account data is the Borsh serialization Anchor expects as is.
+pub trait AnchorAccount: Pack + IsInitialized + Discriminator {
+    /// the type name in the IDL
+    const NAME: &'static str;
+
+    fn discriminator() -> [u8; DISCRIMINATOR_LEN] {
+        Self::DISCRIMINATOR
+    }
+
+    /// the state in `data`, failing for other account types and for accounts never written
+    fn try_deserialize(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < DISCRIMINATOR_LEN || data[..DISCRIMINATOR_LEN] != Self::discriminator() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::unpack(data)
+    }
+
+    fn try_serialize(&self) -> Vec<u8> {
+        let mut data = vec![0; Self::LEN];
+        Self::pack_into_slice(self, &mut data);
+        data
+    }
+}
+
+impl AnchorAccount for Escrow {
+    const NAME: &'static str = "Escrow";
+}
+
+impl AnchorAccount for Config {
+    const NAME: &'static str = "Config";
+}
+
+impl AnchorAccount for MintList {
+    const NAME: &'static str = "MintList";
+}
+
+impl AnchorAccount for MintVolume {
+    const NAME: &'static str = "MintVolume";
+}
+
+impl AnchorAccount for RelayNonce {
+    const NAME: &'static str = "RelayNonce";
+}
+
+/// The type of a field in the packed layout, which matches Borsh for every type used
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FieldType {
+    Bool,
+    U8,
+    U16,
+    U64,
+    I64,
+    U128,
+    Pubkey,
+    Array(&'static FieldType, usize),
+    Defined(&'static str),
+}
+
+impl FieldType {
+    fn idl(self) -> Value {
+        match self {
+            Self::Bool => json!("bool"),
+            Self::U8 => json!("u8"),
+            Self::U16 => json!("u16"),
+            Self::U64 => json!("u64"),
+            Self::I64 => json!("i64"),
+            Self::U128 => json!("u128"),
+            Self::Pubkey => json!("pubkey"),
+            Self::Array(element, len) => json!({ "array": [element.idl(), len] }),
+            Self::Defined(name) => json!({ "defined": { "name": name } }),
+        }
+    }
+}
+
+type Fields = &'static [(&'static str, FieldType)];
+
+const ESCROW_FIELDS: Fields = &[
+    ("is_initialized", FieldType::Bool),
+    ("initializer_pubkey", FieldType::Pubkey),
+    ("temp_token_account_pubkey", FieldType::Pubkey),
+    (
+        "initializer_token_to_receive_account_pubkey",
+        FieldType::Pubkey,
+    ),
+    ("expected_amount", FieldType::U64),
+    ("oracle_pubkey", FieldType::Pubkey),
+    ("oracle_max_staleness_slots", FieldType::U64),
+    ("oracle_max_confidence_bps", FieldType::U16),
+    ("deposit_mint_decimals", FieldType::U8),
+    ("receive_mint_decimals", FieldType::U8),
+    ("curve_start_price", FieldType::U64),
+    ("curve_slope", FieldType::U64),
+    ("filled_amount", FieldType::U64),
+    ("is_crowdfill", FieldType::Bool),
+    ("cancel_locked_until_slot", FieldType::U64),
+    ("reserve_bond_lamports", FieldType::U64),
+    ("reserve_slots", FieldType::U64),
+    ("reserved_by", FieldType::Pubkey),
+    ("reserved_until_slot", FieldType::U64),
+    ("settle_timeout_slots", FieldType::U64),
+    ("committed_taker", FieldType::Pubkey),
+    ("committed_until_slot", FieldType::U64),
+    ("payment_vault_pubkey", FieldType::Pubkey),
+    ("committed_taker_token_to_receive_pubkey", FieldType::Pubkey),
+    ("exchange_top_level_only", FieldType::Bool),
+    (
+        "metadata_uri",
+        FieldType::Array(&FieldType::U8, METADATA_URI_LEN),
+    ),
+    (
+        "memo_reference",
+        FieldType::Array(&FieldType::U8, MEMO_REFERENCE_LEN),
+    ),
+    ("expires_at_slot", FieldType::U64),
+    ("allowed_taker", FieldType::Pubkey),
+    ("relist_amount", FieldType::U64),
+    ("relist_source_pubkey", FieldType::Pubkey),
+    ("auction_bid_end_slot", FieldType::U64),
+    ("auction_reveal_end_slot", FieldType::U64),
+    ("best_bid_amount", FieldType::U64),
+    ("best_bid_pubkey", FieldType::Pubkey),
+    ("raffle_end_slot", FieldType::U64),
+    ("raffle_ticket_count", FieldType::U64),
+    ("chain_expected_amount", FieldType::U64),
+    ("chain_receive_pubkey", FieldType::Pubkey),
+    ("deposited_amount", FieldType::U64),
+    ("receive_mint", FieldType::Pubkey),
+    ("chain_receive_mint", FieldType::Pubkey),
+    ("operator", FieldType::Pubkey),
+    (
+        "split_recipients",
+        FieldType::Array(&FieldType::Defined("SplitRecipient"), MAX_SPLIT_RECIPIENTS),
+    ),
+    ("release_condition", FieldType::Defined("ReleaseCondition")),
+    ("revised_at_slot", FieldType::U64),
+    ("terms_hash", FieldType::Array(&FieldType::U8, 32)),
+    ("release_after_timestamp", FieldType::I64),
+    ("release_before_timestamp", FieldType::I64),
+    ("market", FieldType::Pubkey),
+    ("tag", FieldType::U64),
+];
+
+const SPLIT_RECIPIENT_FIELDS: Fields = &[
+    ("token_account", FieldType::Pubkey),
+    ("share_bps", FieldType::U16),
+];
+
+const RELEASE_CONDITION_FIELDS: Fields = &[
+    ("account", FieldType::Pubkey),
+    ("owner", FieldType::Pubkey),
+    ("offset", FieldType::U16),
+    ("value_len", FieldType::U8),
+    (
+        "value",
+        FieldType::Array(&FieldType::U8, CONDITION_VALUE_LEN),
+    ),
+    ("gates_cancel", FieldType::Bool),
+];
+
+const CONFIG_FIELDS: Fields = &[
+    ("is_initialized", FieldType::Bool),
+    ("admin", FieldType::Pubkey),
+    ("fee_owner", FieldType::Pubkey),
+    ("default_fee_bps", FieldType::U16),
+    ("fee_tier_count", FieldType::U8),
+    (
+        "fee_tiers",
+        FieldType::Array(&FieldType::Defined("MintFeeTier"), MAX_MINT_FEE_TIERS),
+    ),
+    ("lamport_fee", FieldType::U64),
+    ("market", FieldType::Pubkey),
+    ("is_paused", FieldType::Bool),
+];
+
+const MINT_FEE_TIER_FIELDS: Fields = &[("mint", FieldType::Pubkey), ("fee_bps", FieldType::U16)];
+
+const MINT_MINIMUM_FIELDS: Fields = &[("mint", FieldType::Pubkey), ("min_amount", FieldType::U64)];
+
+const MINT_LIST_FIELDS: Fields = &[
+    ("is_initialized", FieldType::Bool),
+    ("is_allow_list", FieldType::Bool),
+    ("mint_count", FieldType::U8),
+    (
+        "mints",
+        FieldType::Array(&FieldType::Pubkey, MAX_LISTED_MINTS),
+    ),
+    ("min_deposit_amount", FieldType::U64),
+    ("minimum_count", FieldType::U8),
+    (
+        "minimums",
+        FieldType::Array(&FieldType::Defined("MintMinimum"), MAX_MINT_MINIMUMS),
+    ),
+];
+
+const MINT_VOLUME_FIELDS: Fields = &[
+    ("is_initialized", FieldType::Bool),
+    ("mint", FieldType::Pubkey),
+    ("filled_amount", FieldType::U128),
+    ("fill_count", FieldType::U64),
+];
+
+const RELAY_NONCE_FIELDS: Fields = &[
+    ("is_initialized", FieldType::Bool),
+    ("user", FieldType::Pubkey),
+    ("next_nonce", FieldType::U64),
+];
+
+const ESCROW_VIEW_FIELDS: Fields = &[
+    ("initializer_pubkey", FieldType::Pubkey),
+    ("temp_token_account_pubkey", FieldType::Pubkey),
+    (
+        "initializer_token_to_receive_account_pubkey",
+        FieldType::Pubkey,
+    ),
+    ("receive_mint", FieldType::Pubkey),
+    ("expected_amount", FieldType::U64),
+    ("deposited_amount", FieldType::U64),
+    ("filled_amount", FieldType::U64),
+    ("expires_at_slot", FieldType::U64),
+    ("allowed_taker", FieldType::Pubkey),
+    ("market", FieldType::Pubkey),
+    ("terms_hash", FieldType::Array(&FieldType::U8, 32)),
+    ("pda_bump_seed", FieldType::U8),
+    ("vault_amount", FieldType::U64),
+    ("fill_status", FieldType::Defined("FillStatus")),
+];
+
+/// the variants of `state::FillStatus`, a Borsh enum of unit variants packs as its index
+const FILL_STATUS_VARIANTS: &[&str] = &["Open", "PartlyFilled", "Reserved", "Committed", "Expired"];
+
+/// the structs of the IDL, accounts first
+const STRUCTS: &[(&str, Fields)] = &[
+    ("Escrow", ESCROW_FIELDS),
+    ("Config", CONFIG_FIELDS),
+    ("MintList", MINT_LIST_FIELDS),
+    ("MintVolume", MINT_VOLUME_FIELDS),
+    ("RelayNonce", RELAY_NONCE_FIELDS),
+    ("SplitRecipient", SPLIT_RECIPIENT_FIELDS),
+    ("ReleaseCondition", RELEASE_CONDITION_FIELDS),
+    ("MintFeeTier", MINT_FEE_TIER_FIELDS),
+    ("MintMinimum", MINT_MINIMUM_FIELDS),
+    ("EscrowView", ESCROW_VIEW_FIELDS),
+];
+
+/// An account of an instruction, as `(name, writable, signer, fixed address)`
+type InstructionAccount = (&'static str, bool, bool, Option<Pubkey>);
+
+fn instruction(
+    name: &str,
+    tag: u8,
+    accounts: &[InstructionAccount],
+    args: &[(&str, FieldType)],
+) -> Value {
+    let accounts: Vec<Value> = accounts
+        .iter()
+        .map(|(name, writable, signer, address)| {
+            let mut account = json!({ "name": name });
+            if *writable {
+                account["writable"] = json!(true);
+            }
+            if *signer {
+                account["signer"] = json!(true);
+            }
+            if let Some(address) = address {
+                account["address"] = json!(address.to_string());
+            }
+            account
+        })
+        .collect();
+    let args: Vec<Value> = args
+        .iter()
+        .map(|(name, field_type)| json!({ "name": name, "type": field_type.idl() }))
+        .collect();
+    json!({
+        "name": name,
+        "discriminator": [tag],
+        "accounts": accounts,
+        "args": args,
+    })
+}
+
+/// The IDL of the InitEscrow, Exchange, Cancel and GetEscrow instructions and the program
+/// accounts, deployed at `program_id`
+pub fn idl(program_id: &Pubkey) -> Value {
+    let token_program = Some(spl_token::id());
+    let mut get_escrow = instruction(
+        "get_escrow",
+        46,
+        &[
+            ("escrow", false, false, None),
+            ("temp_token_account", false, false, None),
+            ("clock", false, false, Some(sysvar::clock::id())),
+        ],
+        &[],
+    );
+    get_escrow["returns"] = FieldType::Defined("EscrowView").idl();
+    let instructions = vec![
+        instruction(
+            "init_escrow",
+            0,
+            &[
+                ("initializer", false, true, None),
+                ("temp_token_account", true, false, None),
+                ("token_to_receive_account", false, false, None),
+                ("escrow", true, false, None),
+                ("rent", false, false, Some(sysvar::rent::id())),
+                ("token_program", false, false, token_program),
+                ("mint_list", false, false, None),
+                ("clock", false, false, Some(sysvar::clock::id())),
+            ],
+            &[("amount", FieldType::U64)],
+        ),
+        instruction(
+            "exchange",
+            1,
+            &[
+                ("taker", true, true, None),
+                ("takers_sending_token_account", true, false, None),
+                ("takers_token_to_receive_account", true, false, None),
+                ("temp_token_account", true, false, None),
+                ("initializer", true, false, None),
+                ("initializers_token_to_receive_account", true, false, None),
+                ("escrow", true, false, None),
+                ("token_program", false, false, token_program),
+                ("pda", false, false, None),
+                ("config", false, false, None),
+                ("mint_volume", true, false, None),
+            ],
+            &[("amount", FieldType::U64)],
+        ),
+        instruction(
+            "cancel",
+            8,
+            &[
+                ("initializer", true, true, None),
+                ("temp_token_account", true, false, None),
+                ("initializers_token_account", true, false, None),
+                ("escrow", true, false, None),
+                ("token_program", false, false, token_program),
+                ("pda", false, false, None),
+                ("clock", false, false, Some(sysvar::clock::id())),
+            ],
+            &[],
+        ),
+        get_escrow,
+    ];
+
+    let accounts: Vec<Value> = [
+        Escrow::NAME,
+        Config::NAME,
+        MintList::NAME,
+        MintVolume::NAME,
+        RelayNonce::NAME,
+    ]
+    .iter()
+    .map(|name| json!({ "name": name, "discriminator": account_discriminator(name) }))
+    .collect();
+
+    let mut types: Vec<Value> = STRUCTS
+        .iter()
+        .map(|(name, fields)| {
+            let fields: Vec<Value> = fields
+                .iter()
+                .map(|(name, field_type)| json!({ "name": name, "type": field_type.idl() }))
+                .collect();
+            json!({ "name": name, "type": { "kind": "struct", "fields": fields } })
+        })
+        .collect();
+    let variants: Vec<Value> = FILL_STATUS_VARIANTS
+        .iter()
+        .map(|name| json!({ "name": name }))
+        .collect();
+    types.push(json!({ "name": "FillStatus", "type": { "kind": "enum", "variants": variants } }));
+
+    json!({
+        "address": program_id.to_string(),
+        "metadata": {
+            "name": "paulx_solana_escrow",
+            "version": env!("CARGO_PKG_VERSION"),
+            "spec": "0.1.0",
+        },
+        "instructions": instructions,
+        "accounts": accounts,
+        "types": types,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::EscrowView;
+
+    impl FieldType {
+        fn size(self) -> usize {
+            match self {
+                Self::Bool | Self::U8 => 1,
+                Self::U16 => 2,
+                Self::U64 | Self::I64 => 8,
+                Self::U128 => 16,
+                Self::Pubkey => 32,
+                Self::Array(element, len) => element.size() * len,
+                Self::Defined(name) => struct_size(type_fields(name)),
+            }
+        }
+    }
+
+    fn type_fields(name: &str) -> Fields {
+        match name {
+            "FillStatus" => &[("variant", FieldType::U8)],
+            _ => STRUCTS
+                .iter()
+                .find(|(struct_name, _)| *struct_name == name)
+                .map(|(_, fields)| *fields)
+                .unwrap_or_else(|| panic!("no IDL type {}", name)),
+        }
+    }
+
+    fn struct_size(fields: Fields) -> usize {
+        fields.iter().map(|(_, field_type)| field_type.size()).sum()
+    }
+
+    #[test]
+    fn test_idl_matches_layouts() {
+        assert_eq!(DISCRIMINATOR_LEN + struct_size(ESCROW_FIELDS), Escrow::LEN);
+        assert_eq!(DISCRIMINATOR_LEN + struct_size(CONFIG_FIELDS), Config::LEN);
+        assert_eq!(
+            DISCRIMINATOR_LEN + struct_size(MINT_LIST_FIELDS),
+            MintList::LEN
+        );
+        assert_eq!(
+            DISCRIMINATOR_LEN + struct_size(MINT_VOLUME_FIELDS),
+            MintVolume::LEN
+        );
+        assert_eq!(
+            DISCRIMINATOR_LEN + struct_size(RELAY_NONCE_FIELDS),
+            RelayNonce::LEN
+        );
+        assert_eq!(struct_size(ESCROW_VIEW_FIELDS), EscrowView::LEN);
+
+        let idl = idl(&crate::id());
+        assert_eq!(idl["instructions"][1]["discriminator"], json!([1]));
+        assert_eq!(
+            idl["instructions"][3]["returns"],
+            json!({ "defined": { "name": "EscrowView" } })
+        );
+        assert_eq!(
+            idl["accounts"][0]["discriminator"],
+            json!(Escrow::discriminator())
+        );
+    }
+
+    #[test]
+    fn test_checked_in_idl() {
+        let checked_in: Value =
+            serde_json::from_str(include_str!("../idl/paulx_solana_escrow.json")).unwrap();
+        assert!(
+            checked_in == idl(&crate::id()),
+            "idl/paulx_solana_escrow.json is out of date, regenerate it with \
+             `cargo run --example idl --features anchor-compat`"
+        );
+    }
+
+    #[test]
+    fn test_anchor_account() {
+        // the value Anchor derives for an account struct named `Escrow`
+        assert_eq!(
+            Escrow::discriminator(),
+            hashv(&[b"account:Escrow"]).to_bytes()[..8]
+        );
+        assert_ne!(Escrow::discriminator(), Config::discriminator());
+
+        let escrow = Escrow {
+            is_initialized: true,
+            initializer_pubkey: Pubkey::new_unique(),
+            expected_amount: 150,
+            ..Escrow::default()
+        };
+        let data = escrow.try_serialize();
+        assert_eq!(data.len(), Escrow::LEN);
+        assert_eq!(Escrow::try_deserialize(&data).unwrap(), escrow);
+        assert_eq!(
+            Config::try_deserialize(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            Escrow::try_deserialize(&data[DISCRIMINATOR_LEN..]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! Transfers of compressed NFTs through the Metaplex Bubblegum program, whose crate is not a
+//! dependency
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+solana_program::declare_id!("BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY");
+
+/// The SPL account compression program holding the merkle trees
+pub mod compression {
+    solana_program::declare_id!("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK");
+}
+
+/// The SPL noop program the compression program logs changed leaves through
+pub mod noop {
+    solana_program::declare_id!("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+}
+
+// anchor discriminator of the `transfer` instruction
+const TRANSFER_DISCRIMINATOR: [u8; 8] = [163, 52, 200, 231, 140, 3, 69, 186];
+
+/// The parts of a leaf's hash that a transfer leaves unchanged, identifying the compressed NFT
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Leaf {
+    pub data_hash: [u8; 32],
+    pub creator_hash: [u8; 32],
+    pub nonce: u64,
+    pub index: u32,
+}
+
+/// Accounts of a transfer other than the leaf's owner, delegate and new owner
+pub struct TreeAccounts<'a> {
+    pub tree_authority: &'a Pubkey,
+    pub merkle_tree: &'a Pubkey,
+    pub system_program: &'a Pubkey,
+    /// the proof path of the leaf, minus the nodes cached in the tree's canopy
+    pub proof: &'a [Pubkey],
+}
+
+/// Moves `leaf` from `leaf_owner` to `new_leaf_owner`, verified against `root`, the tree's
+/// current root. Signed by the owner, the delegate being only part of the leaf's hash.
+pub fn transfer(
+    tree: &TreeAccounts,
+    leaf_owner: &Pubkey,
+    leaf_delegate: &Pubkey,
+    new_leaf_owner: &Pubkey,
+    root: &[u8; 32],
+    leaf: &Leaf,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*tree.tree_authority, false),
+        AccountMeta::new_readonly(*leaf_owner, true),
+        AccountMeta::new_readonly(*leaf_delegate, false),
+        AccountMeta::new_readonly(*new_leaf_owner, false),
+        AccountMeta::new(*tree.merkle_tree, false),
+        AccountMeta::new_readonly(noop::id(), false),
+        AccountMeta::new_readonly(compression::id(), false),
+        AccountMeta::new_readonly(*tree.system_program, false),
+    ];
+    accounts.extend(
+        tree.proof
+            .iter()
+            .map(|node| AccountMeta::new_readonly(*node, false)),
+    );
+
+    let mut data = Vec::with_capacity(116);
+    data.extend_from_slice(&TRANSFER_DISCRIMINATOR);
+    data.extend_from_slice(root);
+    data.extend_from_slice(&leaf.data_hash);
+    data.extend_from_slice(&leaf.creator_hash);
+    data.extend_from_slice(&leaf.nonce.to_le_bytes());
+    data.extend_from_slice(&leaf.index.to_le_bytes());
+
+    Instruction {
+        program_id: id(),
+        accounts,
+        data,
+    }
+}
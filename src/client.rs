@@ -0,0 +1,279 @@
+//! Off-chain helpers for programs and bots driving the escrow, behind the `client` feature.
+//! They are written against [`EscrowRpc`] so the same code runs in program-test and against a
+//! cluster.
+
+use async_trait::async_trait;
+use solana_banks_client::BanksClient;
+use solana_program::{
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    message::Message,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar,
+};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+};
+
+use self::{
+    compute_budget::ComputeBudget,
+    lookup_table::AddressLookupTable,
+    versioned::{MessageV0, VersionedTransaction},
+};
+use crate::{
+    instruction,
+    state::{Escrow, EscrowAccountData, EscrowView},
+};
+
+pub use self::error::EscrowClientError;
+pub use crate::instruction::ExchangeAccounts;
+
+pub mod associated_token;
+pub mod compute_budget;
+pub mod devtools;
+pub mod error;
+pub mod filters;
+pub mod lookup_table;
+pub mod offer;
+pub mod order_book;
+pub mod pay;
+pub mod preflight;
+pub mod rpc;
+pub mod versioned;
+pub mod wallet;
+pub mod watch;
+pub mod websocket;
+
+pub type ClientResult<T> = Result<T, TransportError>;
+
+fn custom(message: &str) -> TransportError {
+    TransportError::Custom(message.to_string())
+}
+
+/// Outcome of running a transaction without committing it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Simulation {
+    pub error: Option<TransactionError>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+    /// data the transaction's last instruction returned, such as GetEscrow's view
+    pub return_data: Option<Vec<u8>>,
+}
+
+/// The calls the client helpers make, implemented for program-test's `BanksClient` and for the
+/// JSON-RPC [`rpc::RpcClient`]
+#[async_trait]
+pub trait EscrowRpc {
+    async fn get_account(&mut self, address: &Pubkey) -> ClientResult<Option<Account>>;
+
+    async fn latest_blockhash(&mut self) -> ClientResult<Hash>;
+
+    /// sends `transaction`, failing with the transaction's error if it is rejected
+    async fn send_transaction(&mut self, transaction: Transaction) -> ClientResult<()>;
+
+    async fn simulate(&mut self, transaction: &Transaction) -> ClientResult<Simulation>;
+
+    /// lamports the cluster charges to process `message`
+    async fn fee_for_message(&mut self, message: &Message) -> ClientResult<u64>;
+
+    async fn minimum_balance_for_rent_exemption(&mut self, data_len: usize) -> ClientResult<u64>;
+
+    async fn send_versioned_transaction(
+        &mut self,
+        transaction: &VersionedTransaction,
+    ) -> ClientResult<()>;
+}
+
+#[async_trait]
+impl EscrowRpc for BanksClient {
+    async fn get_account(&mut self, address: &Pubkey) -> ClientResult<Option<Account>> {
+        Ok(BanksClient::get_account(self, *address).await?)
+    }
+
+    async fn latest_blockhash(&mut self) -> ClientResult<Hash> {
+        Ok(self.get_recent_blockhash().await?)
+    }
+
+    async fn send_transaction(&mut self, transaction: Transaction) -> ClientResult<()> {
+        self.process_transaction(transaction).await
+    }
+
+    async fn simulate(&mut self, _transaction: &Transaction) -> ClientResult<Simulation> {
+        // the banks server of this Solana version has no simulation call
+        Err(custom("BanksClient can not simulate transactions"))
+    }
+
+    async fn fee_for_message(&mut self, message: &Message) -> ClientResult<u64> {
+        let (fee_calculator, _blockhash, _last_valid_slot) = self.get_fees().await?;
+        Ok(fee_calculator.calculate_fee(message))
+    }
+
+    async fn minimum_balance_for_rent_exemption(&mut self, data_len: usize) -> ClientResult<u64> {
+        Ok(self.get_rent().await?.minimum_balance(data_len))
+    }
+
+    async fn send_versioned_transaction(
+        &mut self,
+        _transaction: &VersionedTransaction,
+    ) -> ClientResult<()> {
+        Err(custom("BanksClient can not process version 0 transactions"))
+    }
+}
+
+/// The escrow at `address`, `None` if there is no such account
+pub async fn fetch_escrow<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    address: &Pubkey,
+) -> ClientResult<Option<Escrow>> {
+    match rpc.get_account(address).await? {
+        Some(account) => Ok(Some(Escrow::unpack(&account.data).map_err(|error| {
+            custom(&format!("{} is not an escrow: {}", address, error))
+        })?)),
+        None => Ok(None),
+    }
+}
+
+/// The program account at `address`, whatever its type, `None` if there is no such account
+pub async fn fetch_program_account<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    address: &Pubkey,
+) -> ClientResult<Option<EscrowAccountData>> {
+    match rpc.get_account(address).await? {
+        Some(account) => Ok(Some(EscrowAccountData::try_unpack(&account.data).map_err(
+            |error| custom(&format!("{} is not a program account: {}", address, error)),
+        )?)),
+        None => Ok(None),
+    }
+}
+
+/// The token account at `address`
+pub async fn fetch_token_account<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    address: &Pubkey,
+) -> ClientResult<spl_token::state::Account> {
+    let account = rpc
+        .get_account(address)
+        .await?
+        .ok_or_else(|| custom(&format!("no token account at {}", address)))?;
+    spl_token::state::Account::unpack(&account.data).map_err(|error| custom(&error.to_string()))
+}
+
+/// GetEscrow of the escrow at `escrow` whose deposit is held by `vault`
+pub fn get_escrow_instruction(program_id: &Pubkey, escrow: &Pubkey, vault: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*escrow, false),
+            AccountMeta::new_readonly(*vault, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+        data: vec![46],
+    }
+}
+
+/// The escrow at `escrow` as GetEscrow returns it, simulated with `payer` as fee payer
+pub async fn view_escrow<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    escrow: &Pubkey,
+    vault: &Pubkey,
+) -> ClientResult<EscrowView> {
+    let instruction = get_escrow_instruction(program_id, escrow, vault);
+    let transaction = Transaction::new_unsigned(Message::new(&[instruction], Some(payer)));
+    let simulation = rpc.simulate(&transaction).await?;
+    if let Some(error) = simulation.error {
+        return Err(TransportError::TransactionError(error));
+    }
+    let return_data = simulation
+        .return_data
+        .ok_or_else(|| custom("GetEscrow returned no data"))?;
+    EscrowView::unpack(&return_data).map_err(|error| custom(&error.to_string()))
+}
+
+/// Exchange of `escrow_info`, expecting `amount` of its deposit, see [`instruction::exchange`]
+pub fn exchange_instruction(
+    program_id: &Pubkey,
+    accounts: &ExchangeAccounts,
+    escrow_info: &Escrow,
+    deposit_mint: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    instruction::exchange(program_id, accounts, escrow_info, deposit_mint, amount)
+}
+
+/// `exchange` with `memo` attached to its settlement through the SPL Memo program, see
+/// [`crate::memo::MAX_SETTLEMENT_MEMO_LEN`]
+pub fn with_settlement_memo(mut exchange: Instruction, memo: &str) -> Instruction {
+    exchange.data.extend_from_slice(memo.as_bytes());
+    exchange
+        .accounts
+        .push(AccountMeta::new_readonly(crate::memo::id(), false));
+    exchange
+}
+
+/// Instructions filling the escrow at `accounts.escrow` in full, preceded by those of
+/// `compute_budget`, see [`compute_budget::estimate`]
+pub async fn exchange_instructions<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    program_id: &Pubkey,
+    accounts: &ExchangeAccounts,
+    compute_budget: Option<ComputeBudget>,
+) -> ClientResult<Vec<Instruction>> {
+    let escrow_info = fetch_escrow(rpc, &accounts.escrow)
+        .await?
+        .ok_or_else(|| custom(&format!("no escrow at {}", accounts.escrow)))?;
+    let vault = fetch_token_account(rpc, &escrow_info.temp_token_account_pubkey).await?;
+
+    let instruction = exchange_instruction(
+        program_id,
+        accounts,
+        &escrow_info,
+        &vault.mint,
+        vault.amount,
+    );
+    Ok(compute_budget
+        .unwrap_or_default()
+        .prepend_to(&[instruction]))
+}
+
+/// Fills the escrow at `accounts.escrow` in full, `taker` paying the transaction fee and the
+/// priority fee of `compute_budget`
+pub async fn exchange<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    program_id: &Pubkey,
+    taker: &Keypair,
+    accounts: &ExchangeAccounts,
+    compute_budget: Option<ComputeBudget>,
+) -> ClientResult<()> {
+    let instructions = exchange_instructions(rpc, program_id, accounts, compute_budget).await?;
+    let blockhash = rpc.latest_blockhash().await?;
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&taker.pubkey()),
+        &[taker],
+        blockhash,
+    );
+    rpc.send_transaction(transaction).await
+}
+
+/// [`exchange`] as a version 0 transaction loading accounts from `lookup_tables`, see
+/// [`lookup_table::static_accounts`]
+pub async fn exchange_v0<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    program_id: &Pubkey,
+    taker: &Keypair,
+    accounts: &ExchangeAccounts,
+    compute_budget: Option<ComputeBudget>,
+    lookup_tables: &[AddressLookupTable],
+) -> ClientResult<()> {
+    let instructions = exchange_instructions(rpc, program_id, accounts, compute_budget).await?;
+    let blockhash = rpc.latest_blockhash().await?;
+    let message = MessageV0::compile(&taker.pubkey(), &instructions, lookup_tables, blockhash)?;
+    let transaction = VersionedTransaction::new(message, &[taker])?;
+    rpc.send_versioned_transaction(&transaction).await
+}
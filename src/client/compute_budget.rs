@@ -0,0 +1,121 @@
+//! Compute budget instructions for the transactions the client builds. The sdk of this Solana
+//! version only knows the deprecated `RequestUnits`, so `SetComputeUnitLimit` and
+//! `SetComputeUnitPrice` are encoded here.
+
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_sdk::{compute_budget, transaction::Transaction};
+
+use super::{custom, preflight, ClientResult, EscrowRpc};
+
+/// Most compute units a transaction can ask for
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Units asked for on top of what the transaction used in simulation, in percent, since the
+/// accounts it reads can change before it lands
+pub const UNIT_LIMIT_HEADROOM_PERCENT: u64 = 20;
+
+pub fn set_compute_unit_limit(units: u32) -> Instruction {
+    let mut data = vec![2];
+    data.extend_from_slice(&units.to_le_bytes());
+    Instruction::new_with_bytes(compute_budget::id(), &data, vec![])
+}
+
+pub fn set_compute_unit_price(micro_lamports: u64) -> Instruction {
+    let mut data = vec![3];
+    data.extend_from_slice(&micro_lamports.to_le_bytes());
+    Instruction::new_with_bytes(compute_budget::id(), &data, vec![])
+}
+
+/// Compute unit limit and priority fee prepended to a transaction's instructions
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ComputeBudget {
+    /// `None` keeps the cluster's default limit
+    pub unit_limit: Option<u32>,
+    /// priority fee in micro-lamports per compute unit, zero for none
+    pub unit_price: u64,
+}
+
+impl ComputeBudget {
+    /// Budget for a transaction that used `units_consumed` in simulation, with headroom
+    pub fn from_units_consumed(units_consumed: u64, unit_price: u64) -> Self {
+        let unit_limit = units_consumed.saturating_mul(100 + UNIT_LIMIT_HEADROOM_PERCENT) / 100;
+        Self {
+            unit_limit: Some(unit_limit.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32),
+            unit_price,
+        }
+    }
+
+    /// `instructions` preceded by the budget's compute budget instructions
+    pub fn prepend_to(&self, instructions: &[Instruction]) -> Vec<Instruction> {
+        let mut budgeted = Vec::with_capacity(instructions.len() + 2);
+        if let Some(unit_limit) = self.unit_limit {
+            budgeted.push(set_compute_unit_limit(unit_limit));
+        }
+        if self.unit_price > 0 {
+            budgeted.push(set_compute_unit_price(self.unit_price));
+        }
+        budgeted.extend_from_slice(instructions);
+        budgeted
+    }
+}
+
+/// Budget for sending `instructions` paid by `payer`, simulating them under the largest limit
+/// to find the units they use
+pub async fn estimate<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    unit_price: u64,
+) -> ClientResult<ComputeBudget> {
+    let unlimited = ComputeBudget {
+        unit_limit: Some(MAX_COMPUTE_UNIT_LIMIT),
+        unit_price: 0,
+    };
+    let mut transaction =
+        Transaction::new_with_payer(&unlimited.prepend_to(instructions), Some(payer));
+    transaction.message.recent_blockhash = rpc.latest_blockhash().await?;
+    let simulation = rpc.simulate(&transaction).await?;
+    if let Some(error) = simulation.error {
+        return Err(custom(&format!("simulation failed: {}", error)));
+    }
+    let units_consumed = simulation
+        .units_consumed
+        .or_else(|| preflight::units_consumed_from_logs(&simulation.logs))
+        .ok_or_else(|| custom("simulation reported no compute units"))?;
+    Ok(ComputeBudget::from_units_consumed(
+        units_consumed,
+        unit_price,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_budget() {
+        let budget = ComputeBudget::from_units_consumed(50_000, 1_000);
+        assert_eq!(budget.unit_limit, Some(60_000));
+        assert_eq!(
+            ComputeBudget::from_units_consumed(1_300_000, 0).unit_limit,
+            Some(MAX_COMPUTE_UNIT_LIMIT)
+        );
+
+        let exchange = Instruction::new_with_bytes(Pubkey::new_unique(), &[1], vec![]);
+        let budgeted = budget.prepend_to(std::slice::from_ref(&exchange));
+        assert_eq!(budgeted.len(), 3);
+        assert_eq!(
+            budgeted[0].data,
+            [&[2][..], &60_000u32.to_le_bytes()].concat()
+        );
+        assert_eq!(
+            budgeted[1].data,
+            [&[3][..], &1_000u64.to_le_bytes()].concat()
+        );
+        assert_eq!(budgeted[2], exchange);
+        assert_eq!(
+            ComputeBudget::default().prepend_to(std::slice::from_ref(&exchange)),
+            vec![exchange]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! Sets up a local validator, devnet or program-test bank for trying the escrow out: funded
+//! wallets, two fresh mints, token balances and an open sample escrow, in one call
+
+use serde_json::json;
+use solana_program::{program_pack::Pack, pubkey::Pubkey, system_instruction};
+use solana_sdk::{
+    instruction::Instruction,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+use super::{
+    custom,
+    rpc::RpcClient,
+    wallet::{InitEscrowParams, PartiallySignedInit},
+    ClientResult, EscrowRpc,
+};
+
+/// Lamports [`bootstrap`] gives every wallet for fees and rent
+pub const WALLET_LAMPORTS: u64 = 1_000_000_000;
+
+/// Base units of each mint [`bootstrap`] gives every wallet
+pub const WALLET_TOKEN_BALANCE: u64 = 1_000_000_000;
+
+pub const MINT_DECIMALS: u8 = 6;
+
+/// Deposit of the sample escrow
+pub const SAMPLE_DEPOSIT_AMOUNT: u64 = 10_000_000;
+
+/// What the sample escrow asks for its deposit
+pub const SAMPLE_EXPECTED_AMOUNT: u64 = 25_000_000;
+
+/// Token accounts of a bootstrapped wallet
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WalletAccounts {
+    pub wallet: Pubkey,
+    pub deposit_token_account: Pubkey,
+    pub receive_token_account: Pubkey,
+}
+
+/// Everything [`bootstrap`] created, the payer being the authority of both mints
+#[derive(Clone, Debug, PartialEq)]
+pub struct Environment {
+    /// the mint the sample escrow deposits
+    pub deposit_mint: Pubkey,
+    /// the mint the sample escrow asks for
+    pub receive_mint: Pubkey,
+    /// in the order of the wallets passed in
+    pub wallets: Vec<WalletAccounts>,
+    /// the sample escrow, opened by the first wallet
+    pub escrow: Pubkey,
+    pub vault: Pubkey,
+}
+
+/// Funds `wallets` from `payer`, gives them balances of two new mints and opens a sample escrow
+/// from the first wallet. `payer` needs a few SOL per wallet, see [`airdrop`].
+pub async fn bootstrap<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    program_id: &Pubkey,
+    payer: &Keypair,
+    wallets: &[&Keypair],
+) -> ClientResult<Environment> {
+    let maker = wallets
+        .first()
+        .ok_or_else(|| custom("bootstrap needs a wallet to open the sample escrow"))?;
+    let deposit_mint = create_mint(rpc, payer, MINT_DECIMALS).await?;
+    let receive_mint = create_mint(rpc, payer, MINT_DECIMALS).await?;
+
+    let mut wallet_accounts = Vec::with_capacity(wallets.len());
+    for wallet in wallets {
+        let transfer =
+            system_instruction::transfer(&payer.pubkey(), &wallet.pubkey(), WALLET_LAMPORTS);
+        send(rpc, &[transfer], payer, &[]).await?;
+        let deposit_token_account =
+            create_token_account(rpc, payer, &deposit_mint, &wallet.pubkey()).await?;
+        let receive_token_account =
+            create_token_account(rpc, payer, &receive_mint, &wallet.pubkey()).await?;
+        mint_to(
+            rpc,
+            payer,
+            &deposit_mint,
+            &deposit_token_account,
+            WALLET_TOKEN_BALANCE,
+        )
+        .await?;
+        mint_to(
+            rpc,
+            payer,
+            &receive_mint,
+            &receive_token_account,
+            WALLET_TOKEN_BALANCE,
+        )
+        .await?;
+        wallet_accounts.push(WalletAccounts {
+            wallet: wallet.pubkey(),
+            deposit_token_account,
+            receive_token_account,
+        });
+    }
+
+    let params = InitEscrowParams {
+        initializer: maker.pubkey(),
+        initializers_sending_token_account: wallet_accounts[0].deposit_token_account,
+        initializers_token_to_receive_account: wallet_accounts[0].receive_token_account,
+        deposit_mint,
+        deposit_amount: SAMPLE_DEPOSIT_AMOUNT,
+        expected_amount: SAMPLE_EXPECTED_AMOUNT,
+    };
+    let mut init = PartiallySignedInit::new(rpc, program_id, &params).await?;
+    let blockhash = init.transaction.message.recent_blockhash;
+    init.transaction
+        .try_partial_sign(&[*maker], blockhash)
+        .map_err(|error| custom(&error.to_string()))?;
+    rpc.send_transaction(init.transaction.clone()).await?;
+
+    Ok(Environment {
+        deposit_mint,
+        receive_mint,
+        wallets: wallet_accounts,
+        escrow: init.escrow(),
+        vault: init.vault(),
+    })
+}
+
+/// Asks the cluster's faucet for `lamports`, on a local validator or devnet
+pub fn airdrop(rpc: &RpcClient, to: &Pubkey, lamports: u64) -> ClientResult<()> {
+    let signature = rpc.call("requestAirdrop", json!([to.to_string(), lamports]))?;
+    let signature = signature
+        .as_str()
+        .ok_or_else(|| custom("missing airdrop signature"))?;
+    rpc.confirm(signature)
+}
+
+/// Creates a mint of `decimals` with `payer` as its mint authority
+pub async fn create_mint<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    payer: &Keypair,
+    decimals: u8,
+) -> ClientResult<Pubkey> {
+    let mint = Keypair::new();
+    let rent = rpc
+        .minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)
+        .await?;
+    let instructions = [
+        system_instruction::create_account(
+            &payer.pubkey(),
+            &mint.pubkey(),
+            rent,
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_mint(
+            &spl_token::id(),
+            &mint.pubkey(),
+            &payer.pubkey(),
+            None,
+            decimals,
+        )
+        .map_err(|error| custom(&error.to_string()))?,
+    ];
+    send(rpc, &instructions, payer, &[&mint]).await?;
+    Ok(mint.pubkey())
+}
+
+/// Creates a token account of `mint` owned by `owner`, `payer` paying its rent
+pub async fn create_token_account<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    payer: &Keypair,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> ClientResult<Pubkey> {
+    let account = Keypair::new();
+    let rent = rpc
+        .minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
+        .await?;
+    let instructions = [
+        system_instruction::create_account(
+            &payer.pubkey(),
+            &account.pubkey(),
+            rent,
+            spl_token::state::Account::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            &account.pubkey(),
+            mint,
+            owner,
+        )
+        .map_err(|error| custom(&error.to_string()))?,
+    ];
+    send(rpc, &instructions, payer, &[&account]).await?;
+    Ok(account.pubkey())
+}
+
+/// Mints `amount` of `mint` into `account`, `mint_authority` paying the fee
+pub async fn mint_to<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    mint_authority: &Keypair,
+    mint: &Pubkey,
+    account: &Pubkey,
+    amount: u64,
+) -> ClientResult<()> {
+    let instruction = spl_token::instruction::mint_to(
+        &spl_token::id(),
+        mint,
+        account,
+        &mint_authority.pubkey(),
+        &[],
+        amount,
+    )
+    .map_err(|error| custom(&error.to_string()))?;
+    send(rpc, &[instruction], mint_authority, &[]).await
+}
+
+async fn send<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&Keypair],
+) -> ClientResult<()> {
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let blockhash = rpc.latest_blockhash().await?;
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &all_signers,
+        blockhash,
+    );
+    rpc.send_transaction(transaction).await
+}
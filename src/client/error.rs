@@ -0,0 +1,205 @@
+//! Typed failures of transactions sent to the escrow, so integrators can match on the error
+//! instead of parsing log strings
+
+use num_traits::FromPrimitive;
+use solana_program::{
+    instruction::InstructionError, message::Message, pubkey::Pubkey,
+    system_instruction::SystemError, system_program,
+};
+use solana_sdk::{transaction::TransactionError, transport::TransportError};
+use spl_token::error::TokenError;
+use thiserror::Error;
+
+use crate::error::EscrowError;
+
+/// Why a transaction failed, attributed to the program that raised the error
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum EscrowClientError {
+    /// the escrow program failed instruction `index` with one of its own errors
+    #[error("instruction {index} failed in the escrow program: {error}")]
+    Escrow { index: u8, error: EscrowError },
+    /// the token program failed instruction `index`, called directly or invoked by the escrow
+    #[error("instruction {index} failed in the token program: {error}")]
+    Token { index: u8, error: TokenError },
+    /// the system program failed instruction `index`, e.g. creating an account that exists
+    #[error("instruction {index} failed in the system program: {error}")]
+    System { index: u8, error: SystemError },
+    /// any other failure of instruction `index`, such as a missing signature or the code of
+    /// another program
+    #[error("instruction {index} failed: {error}")]
+    Instruction { index: u8, error: InstructionError },
+    /// the transaction failed before its instructions ran, on its fee or blockhash for instance
+    #[error("transaction failed: {0}")]
+    Transaction(TransactionError),
+}
+
+impl EscrowClientError {
+    /// Attributes `error`, the failure of a transaction of `message`, to the program that raised
+    /// it. The transaction's `logs` name the failing program when a custom code comes from an
+    /// invoked program, without them the code is read as the top-level instruction's.
+    pub fn from_transaction_error(
+        program_id: &Pubkey,
+        message: &Message,
+        error: TransactionError,
+        logs: &[String],
+    ) -> Self {
+        let (index, error) = match error {
+            TransactionError::InstructionError(index, error) => (index, error),
+            error => return Self::Transaction(error),
+        };
+        let code = match error {
+            InstructionError::Custom(code) => code,
+            error => return Self::Instruction { index, error },
+        };
+        let program = failing_program(logs)
+            .and_then(|program| program.parse::<Pubkey>().ok())
+            .or_else(|| {
+                message
+                    .instructions
+                    .get(index as usize)
+                    .map(|instruction| *instruction.program_id(&message.account_keys))
+            });
+        let typed = match program {
+            Some(program) if program == *program_id => {
+                EscrowError::from_u32(code).map(|error| Self::Escrow { index, error })
+            }
+            Some(program) if program == spl_token::id() => {
+                TokenError::from_u32(code).map(|error| Self::Token { index, error })
+            }
+            Some(program) if program == system_program::id() => {
+                SystemError::from_u32(code).map(|error| Self::System { index, error })
+            }
+            _ => None,
+        };
+        typed.unwrap_or(Self::Instruction { index, error })
+    }
+
+    /// The failure of a transaction of `message` rejected with `error`, `None` when the
+    /// transaction never ran, e.g. because the node could not be reached
+    pub fn from_transport_error(
+        program_id: &Pubkey,
+        message: &Message,
+        error: &TransportError,
+    ) -> Option<Self> {
+        match error {
+            TransportError::TransactionError(error) => Some(Self::from_transaction_error(
+                program_id,
+                message,
+                error.clone(),
+                &[],
+            )),
+            _ => None,
+        }
+    }
+
+    /// the escrow program's error, `None` when another program or the runtime failed the
+    /// transaction
+    pub fn escrow_error(&self) -> Option<EscrowError> {
+        match self {
+            Self::Escrow { error, .. } => Some(*error),
+            _ => None,
+        }
+    }
+}
+
+/// The program that first logged a failure, the one that raised the error
+pub(super) fn failing_program(logs: &[String]) -> Option<String> {
+    logs.iter().find_map(|log| {
+        let (program, _) = log.strip_prefix("Program ")?.split_once(" failed")?;
+        Some(program.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::{instruction::Instruction, system_instruction};
+
+    #[test]
+    fn test_from_transaction_error() {
+        let program_id = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        let message = Message::new(
+            &[
+                system_instruction::transfer(&payer, &Pubkey::new_unique(), 1_000),
+                Instruction::new_with_bytes(program_id, &[1], vec![]),
+            ],
+            Some(&payer),
+        );
+        let custom =
+            |index, code| TransactionError::InstructionError(index, InstructionError::Custom(code));
+        let classify = |error, logs: &[String]| {
+            EscrowClientError::from_transaction_error(&program_id, &message, error, logs)
+        };
+
+        // without logs the code is the top-level instruction's program's
+        let failure = classify(custom(1, EscrowError::ExpectedAmountMismatch as u32), &[]);
+        assert_eq!(
+            failure,
+            EscrowClientError::Escrow {
+                index: 1,
+                error: EscrowError::ExpectedAmountMismatch,
+            }
+        );
+        assert_eq!(
+            failure.escrow_error(),
+            Some(EscrowError::ExpectedAmountMismatch)
+        );
+        assert_eq!(
+            classify(custom(0, 1), &[]),
+            EscrowClientError::System {
+                index: 0,
+                error: SystemError::ResultWithNegativeLamports,
+            }
+        );
+
+        // the token program invoked by the escrow raised this one
+        let logs = vec![
+            format!("Program {} invoke [1]", program_id),
+            format!("Program {} invoke [2]", spl_token::id()),
+            format!(
+                "Program {} failed: custom program error: 0x1",
+                spl_token::id()
+            ),
+            format!("Program {} failed: custom program error: 0x1", program_id),
+        ];
+        assert_eq!(
+            classify(custom(1, 1), &logs),
+            EscrowClientError::Token {
+                index: 1,
+                error: TokenError::InsufficientFunds,
+            }
+        );
+
+        // codes no program defines and other failures keep their raw form
+        assert_eq!(
+            classify(custom(1, u32::MAX), &[]),
+            EscrowClientError::Instruction {
+                index: 1,
+                error: InstructionError::Custom(u32::MAX),
+            }
+        );
+        assert_eq!(
+            classify(
+                TransactionError::InstructionError(1, InstructionError::MissingRequiredSignature),
+                &[]
+            ),
+            EscrowClientError::Instruction {
+                index: 1,
+                error: InstructionError::MissingRequiredSignature,
+            }
+        );
+        assert_eq!(
+            classify(TransactionError::BlockhashNotFound, &[]),
+            EscrowClientError::Transaction(TransactionError::BlockhashNotFound)
+        );
+        assert_eq!(
+            EscrowClientError::from_transport_error(
+                &program_id,
+                &message,
+                &TransportError::Custom("connection refused".to_string())
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! `getProgramAccounts` and `programSubscribe` filters selecting escrows by their fields, built
+//! from the `Escrow::OFFSET_*` constants
+
+use serde_json::{json, Value};
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+
+use crate::state::{Discriminator, Escrow};
+
+/// Matches accounts holding `bytes` at `offset` of their data
+pub fn memcmp(offset: usize, bytes: &[u8]) -> Value {
+    json!({
+        "memcmp": {
+            "offset": offset,
+            "bytes": base64::encode(bytes),
+            "encoding": "base64",
+        }
+    })
+}
+
+/// Every escrow account, whether open or not yet initialized
+pub fn escrows() -> Vec<Value> {
+    vec![
+        json!({ "dataSize": Escrow::LEN }),
+        memcmp(0, &Escrow::DISCRIMINATOR),
+    ]
+}
+
+/// The open escrows of `initializer`
+pub fn escrows_by_initializer(initializer: &Pubkey) -> Vec<Value> {
+    let mut filters = open_escrows();
+    filters.push(memcmp(Escrow::OFFSET_INITIALIZER, initializer.as_ref()));
+    filters
+}
+
+/// The open escrows asking for `mint`
+pub fn escrows_by_receive_mint(mint: &Pubkey) -> Vec<Value> {
+    let mut filters = open_escrows();
+    filters.push(memcmp(Escrow::OFFSET_RECEIVE_MINT, mint.as_ref()));
+    filters
+}
+
+/// The open escrows only `taker` can take
+pub fn escrows_for_taker(taker: &Pubkey) -> Vec<Value> {
+    let mut filters = open_escrows();
+    filters.push(memcmp(Escrow::OFFSET_ALLOWED_TAKER, taker.as_ref()));
+    filters
+}
+
+/// The open escrows of `market`, `Pubkey::default()` for the program-wide market
+pub fn escrows_in_market(market: &Pubkey) -> Vec<Value> {
+    let mut filters = open_escrows();
+    filters.push(memcmp(Escrow::OFFSET_MARKET, market.as_ref()));
+    filters
+}
+
+/// The open escrows initialized with `tag`
+pub fn escrows_with_tag(tag: u64) -> Vec<Value> {
+    let mut filters = open_escrows();
+    filters.push(memcmp(Escrow::OFFSET_TAG, &tag.to_le_bytes()));
+    filters
+}
+
+fn open_escrows() -> Vec<Value> {
+    let mut filters = escrows();
+    filters.push(memcmp(Escrow::OFFSET_IS_INITIALIZED, &[1]));
+    filters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters_match_packed_escrows() {
+        let initializer = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut data = vec![0; Escrow::LEN];
+        Escrow {
+            is_initialized: true,
+            initializer_pubkey: initializer,
+            receive_mint: mint,
+            tag: 7,
+            ..Escrow::default()
+        }
+        .pack_into_slice(&mut data);
+
+        let matches = |filters: Vec<Value>| {
+            filters.iter().all(|filter| match filter.get("memcmp") {
+                Some(memcmp) => {
+                    let offset = memcmp["offset"].as_u64().unwrap() as usize;
+                    let bytes = base64::decode(memcmp["bytes"].as_str().unwrap()).unwrap();
+                    data[offset..offset + bytes.len()] == bytes[..]
+                }
+                None => filter["dataSize"] == data.len(),
+            })
+        };
+        assert!(matches(escrows_by_initializer(&initializer)));
+        assert!(matches(escrows_by_receive_mint(&mint)));
+        assert!(!matches(escrows_by_initializer(&mint)));
+        assert!(!matches(escrows_for_taker(&initializer)));
+        assert!(matches(escrows_for_taker(&Pubkey::default())));
+        assert!(matches(escrows_in_market(&Pubkey::default())));
+        assert!(!matches(escrows_in_market(&initializer)));
+        assert!(matches(escrows_with_tag(7)));
+        assert!(!matches(escrows_with_tag(0)));
+    }
+}
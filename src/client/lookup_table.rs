@@ -0,0 +1,119 @@
+//! Instructions of the address lookup table program, which the sdk of this Solana version
+//! predates, and the escrow's accounts worth putting in a table
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+
+use super::{custom, ClientResult, EscrowRpc};
+use crate::state::Config;
+
+solana_program::declare_id!("AddressLookupTab1e1111111111111111111111111");
+
+/// Bytes of a lookup table account ahead of its addresses
+pub const LOOKUP_TABLE_META_SIZE: usize = 56;
+
+/// Most addresses a lookup table holds
+pub const LOOKUP_TABLE_MAX_ADDRESSES: usize = 256;
+
+/// A lookup table's address and the addresses it holds
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AddressLookupTable {
+    pub key: Pubkey,
+    pub addresses: Vec<Pubkey>,
+}
+
+impl AddressLookupTable {
+    /// The table at `key` from its account data
+    pub fn unpack(key: Pubkey, data: &[u8]) -> Option<Self> {
+        let addresses = data.get(LOOKUP_TABLE_META_SIZE..)?;
+        if addresses.len() % 32 != 0 {
+            return None;
+        }
+        Some(Self {
+            key,
+            addresses: addresses.chunks(32).map(Pubkey::new).collect(),
+        })
+    }
+}
+
+/// The table at `key`
+pub async fn fetch_lookup_table<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    key: &Pubkey,
+) -> ClientResult<AddressLookupTable> {
+    rpc.get_account(key)
+        .await?
+        .filter(|account| account.owner == id())
+        .and_then(|account| AddressLookupTable::unpack(*key, &account.data))
+        .ok_or_else(|| custom(&format!("no lookup table at {}", key)))
+}
+
+/// Address of the table `authority` creates at `recent_slot`
+pub fn find_lookup_table_address(authority: &Pubkey, recent_slot: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[authority.as_ref(), &recent_slot.to_le_bytes()], &id())
+}
+
+/// Creates a table owned by `authority`, `recent_slot` being a slot the cluster has seen in the
+/// last few minutes, returning the instruction and the table's address
+pub fn create_lookup_table(
+    authority: &Pubkey,
+    payer: &Pubkey,
+    recent_slot: u64,
+) -> (Instruction, Pubkey) {
+    let (table, bump_seed) = find_lookup_table_address(authority, recent_slot);
+    let mut data = 0u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&recent_slot.to_le_bytes());
+    data.push(bump_seed);
+    let instruction = Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(table, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    };
+    (instruction, table)
+}
+
+/// Appends `addresses` to `table`, `payer` funding the rent of the larger account
+pub fn extend_lookup_table(
+    table: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    addresses: &[Pubkey],
+) -> Instruction {
+    let mut data = 2u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&(addresses.len() as u64).to_le_bytes());
+    for address in addresses {
+        data.extend_from_slice(address.as_ref());
+    }
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(*table, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    }
+}
+
+/// The accounts every Exchange of `program_id` passes whatever the escrow, plus the fee owner's
+/// token accounts once the config exists
+pub fn static_accounts(program_id: &Pubkey, fee_accounts: &[Pubkey]) -> Vec<Pubkey> {
+    let mut accounts = vec![
+        spl_token::id(),
+        Pubkey::find_program_address(&[b"escrow"], program_id).0,
+        Config::find_address(program_id).0,
+        sysvar::clock::id(),
+        sysvar::instructions::id(),
+    ];
+    accounts.extend_from_slice(fee_accounts);
+    accounts
+}
@@ -0,0 +1,267 @@
+//! Offers makers can paste into a chat as a single string, holding everything a taker's client
+//! needs to build the Exchange, written as `escrow-offer:<version>:<url-safe base64>`
+
+use arrayref::{array_ref, array_refs};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+use super::{
+    custom, exchange_instruction, fetch_escrow, fetch_token_account, ClientResult, EscrowRpc,
+    ExchangeAccounts,
+};
+use crate::state::{Config, Escrow, MintVolume};
+
+/// Prefix of encoded offers, followed by the format version
+pub const OFFER_PREFIX: &str = "escrow-offer:";
+
+/// Version of the layout written by `pack`, bumped whenever it changes
+pub const OFFER_VERSION: u8 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OfferError {
+    /// written by a newer client than this decoder knows about
+    UnsupportedVersion(u8),
+    /// not an offer, or not an offer of the version it claims
+    Malformed,
+}
+
+/// An open escrow as a taker sees it
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Offer {
+    pub program_id: Pubkey,
+    pub escrow: Pubkey,
+    pub initializer: Pubkey,
+    /// the PDA's token account holding the deposit
+    pub vault: Pubkey,
+    /// the initializer's token account the taker pays into
+    pub initializer_token_to_receive_account: Pubkey,
+    pub deposit_mint: Pubkey,
+    pub deposit_amount: u64,
+    /// the mint the taker pays in
+    pub receive_mint: Pubkey,
+    pub expected_amount: u64,
+    /// the fee owner's token account for `receive_mint`, once the config exists
+    pub fee_account: Option<Pubkey>,
+}
+
+impl Offer {
+    const LEN: usize = 273;
+
+    /// The offer of the escrow at `escrow`, `fee_account` being the fee owner's token account
+    /// for the mint it asks for once the config exists
+    pub async fn fetch<R: EscrowRpc + Send>(
+        rpc: &mut R,
+        program_id: &Pubkey,
+        escrow: &Pubkey,
+        fee_account: Option<Pubkey>,
+    ) -> ClientResult<Self> {
+        let escrow_info = fetch_escrow(rpc, escrow)
+            .await?
+            .ok_or_else(|| custom(&format!("no escrow at {}", escrow)))?;
+        let vault = fetch_token_account(rpc, &escrow_info.temp_token_account_pubkey).await?;
+        let receive_account = fetch_token_account(
+            rpc,
+            &escrow_info.initializer_token_to_receive_account_pubkey,
+        )
+        .await?;
+        Ok(Self {
+            program_id: *program_id,
+            escrow: *escrow,
+            initializer: escrow_info.initializer_pubkey,
+            vault: escrow_info.temp_token_account_pubkey,
+            initializer_token_to_receive_account: escrow_info
+                .initializer_token_to_receive_account_pubkey,
+            deposit_mint: vault.mint,
+            deposit_amount: vault.amount,
+            receive_mint: receive_account.mint,
+            expected_amount: escrow_info.expected_amount,
+            fee_account,
+        })
+    }
+
+    /// The `["escrow"]` PDA owning the vault
+    pub fn pda(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"escrow"], &self.program_id).0
+    }
+
+    pub fn config(&self) -> Pubkey {
+        Config::find_address(&self.program_id).0
+    }
+
+    pub fn mint_volume(&self) -> Pubkey {
+        MintVolume::find_address(&self.deposit_mint, &self.program_id).0
+    }
+
+    /// Exchange of the whole deposit by `taker`, paying from `takers_sending_token_account`
+    pub fn exchange_instruction(
+        &self,
+        taker: &Pubkey,
+        takers_sending_token_account: &Pubkey,
+        takers_token_to_receive_account: &Pubkey,
+    ) -> Instruction {
+        let accounts = ExchangeAccounts {
+            taker: *taker,
+            takers_sending_token_account: *takers_sending_token_account,
+            takers_token_to_receive_account: *takers_token_to_receive_account,
+            escrow: self.escrow,
+            fee_account: self.fee_account,
+        };
+        let escrow_info = Escrow {
+            is_initialized: true,
+            initializer_pubkey: self.initializer,
+            temp_token_account_pubkey: self.vault,
+            initializer_token_to_receive_account_pubkey: self.initializer_token_to_receive_account,
+            expected_amount: self.expected_amount,
+            ..Escrow::default()
+        };
+        exchange_instruction(
+            &self.program_id,
+            &accounts,
+            &escrow_info,
+            &self.deposit_mint,
+            self.deposit_amount,
+        )
+    }
+
+    pub fn encode(&self) -> String {
+        format!(
+            "{}{}:{}",
+            OFFER_PREFIX,
+            OFFER_VERSION,
+            base64::encode_config(self.pack(), base64::URL_SAFE_NO_PAD)
+        )
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self, OfferError> {
+        let (version, encoded) = encoded
+            .trim()
+            .strip_prefix(OFFER_PREFIX)
+            .and_then(|rest| rest.split_once(':'))
+            .ok_or(OfferError::Malformed)?;
+        let version = version.parse().map_err(|_| OfferError::Malformed)?;
+        if version != OFFER_VERSION {
+            return Err(OfferError::UnsupportedVersion(version));
+        }
+        let data = base64::decode_config(encoded, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| OfferError::Malformed)?;
+        Self::unpack(&data).ok_or(OfferError::Malformed)
+    }
+
+    fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(Self::LEN);
+        for key in [
+            &self.program_id,
+            &self.escrow,
+            &self.initializer,
+            &self.vault,
+            &self.initializer_token_to_receive_account,
+            &self.deposit_mint,
+        ]
+        .iter()
+        {
+            buf.extend_from_slice(key.as_ref());
+        }
+        buf.extend_from_slice(&self.deposit_amount.to_le_bytes());
+        buf.extend_from_slice(self.receive_mint.as_ref());
+        buf.extend_from_slice(&self.expected_amount.to_le_bytes());
+        match self.fee_account {
+            Some(fee_account) => {
+                buf.push(1);
+                buf.extend_from_slice(fee_account.as_ref());
+            }
+            None => buf.extend_from_slice(&[0; 33]),
+        }
+        buf
+    }
+
+    fn unpack(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::LEN {
+            return None;
+        }
+        let data = array_ref![data, 0, Offer::LEN];
+        let (
+            program_id,
+            escrow,
+            initializer,
+            vault,
+            initializer_token_to_receive_account,
+            deposit_mint,
+            deposit_amount,
+            receive_mint,
+            expected_amount,
+            has_fee_account,
+            fee_account,
+        ) = array_refs![data, 32, 32, 32, 32, 32, 32, 8, 32, 8, 1, 32];
+        Some(Self {
+            program_id: Pubkey::new_from_array(*program_id),
+            escrow: Pubkey::new_from_array(*escrow),
+            initializer: Pubkey::new_from_array(*initializer),
+            vault: Pubkey::new_from_array(*vault),
+            initializer_token_to_receive_account: Pubkey::new_from_array(
+                *initializer_token_to_receive_account,
+            ),
+            deposit_mint: Pubkey::new_from_array(*deposit_mint),
+            deposit_amount: u64::from_le_bytes(*deposit_amount),
+            receive_mint: Pubkey::new_from_array(*receive_mint),
+            expected_amount: u64::from_le_bytes(*expected_amount),
+            fee_account: match has_fee_account {
+                [0] => None,
+                [1] => Some(Pubkey::new_from_array(*fee_account)),
+                _ => return None,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offer_encoding() {
+        let offer = Offer {
+            program_id: Pubkey::new_unique(),
+            escrow: Pubkey::new_unique(),
+            initializer: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            initializer_token_to_receive_account: Pubkey::new_unique(),
+            deposit_mint: Pubkey::new_unique(),
+            deposit_amount: 1_000,
+            receive_mint: Pubkey::new_unique(),
+            expected_amount: 5_000,
+            fee_account: Some(Pubkey::new_unique()),
+        };
+        let encoded = offer.encode();
+        assert!(encoded.starts_with("escrow-offer:1:"));
+        assert_eq!(Offer::decode(&encoded), Ok(offer));
+        let no_fee = Offer {
+            fee_account: None,
+            ..offer
+        };
+        assert_eq!(
+            Offer::decode(&format!(" {}\n", no_fee.encode())),
+            Ok(no_fee)
+        );
+
+        let newer = encoded.replacen(":1:", ":2:", 1);
+        assert_eq!(
+            Offer::decode(&newer),
+            Err(OfferError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            Offer::decode(&encoded[..encoded.len() - 4]),
+            Err(OfferError::Malformed)
+        );
+        assert_eq!(Offer::decode("hello"), Err(OfferError::Malformed));
+
+        let taker = Pubkey::new_unique();
+        let instruction = offer.exchange_instruction(&taker, &Pubkey::new_unique(), &taker);
+        assert_eq!(instruction.program_id, offer.program_id);
+        assert_eq!(instruction.accounts[3].pubkey, offer.vault);
+        assert_eq!(instruction.accounts[8].pubkey, offer.pda());
+        assert_eq!(instruction.accounts[9].pubkey, offer.config());
+        assert_eq!(instruction.accounts[10].pubkey, offer.fee_account.unwrap());
+        assert_eq!(instruction.accounts[11].pubkey, offer.mint_volume());
+        assert_eq!(instruction.data[1..], offer.deposit_amount.to_le_bytes());
+    }
+}
@@ -0,0 +1,343 @@
+//! Order books of the open escrows trading a mint pair, for trading UIs. Escrows only record the
+//! mint they ask for, so [`escrow_addresses`] lists the escrows asking for one side of the pair
+//! with `getProgramAccounts`, slicing their data away, and [`fetch_orders`] reads them
+//! [`PAGE_SIZE`] at a time with the first 32 bytes of each vault, its mint, to keep those selling
+//! the other side.
+
+use std::{cmp::Ordering, str::FromStr};
+
+use serde_json::json;
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+
+use super::{custom, filters, rpc::RpcClient, ClientResult};
+use crate::{
+    math,
+    state::{Escrow, FillStatus},
+};
+
+/// Most accounts `getMultipleAccounts` returns per call
+pub const PAGE_SIZE: usize = 100;
+
+/// An escrow any taker can fill right now at a price known off-chain
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Order {
+    pub escrow: Pubkey,
+    pub escrow_info: Escrow,
+    /// deposit left for sale, in base units of the deposited mint
+    pub amount: u64,
+    /// what buying all of `amount` costs before fees, in base units of the receive mint
+    pub cost: u64,
+}
+
+impl Order {
+    /// The order of `escrow_info` at `slot`, `None` unless an Exchange from any taker can fill it
+    /// at a price the escrow alone tells. Oracle priced escrows, auctions, raffles, private,
+    /// expired, committed and reserved escrows are left out, release windows are not checked.
+    pub fn new(escrow: Pubkey, escrow_info: Escrow, slot: u64) -> Option<Self> {
+        let takeable = matches!(
+            escrow_info.fill_status(slot),
+            FillStatus::Open | FillStatus::PartlyFilled
+        );
+        if !escrow_info.is_initialized
+            || !takeable
+            || escrow_info.is_oracle_priced()
+            || escrow_info.is_auction()
+            || escrow_info.is_raffle()
+            || escrow_info.allowed_taker != Pubkey::default()
+            || escrow_info.deposited_amount == 0
+        {
+            return None;
+        }
+        let amount = escrow_info.deposited_amount;
+        let cost = if escrow_info.is_curve_priced() {
+            math::linear_curve_cost(
+                escrow_info.curve_start_price,
+                escrow_info.curve_slope,
+                escrow_info.filled_amount,
+                amount,
+            )?
+        } else if escrow_info.allows_partial_fills() {
+            math::prorated_cost(
+                escrow_info.expected_amount,
+                escrow_info.filled_amount.checked_add(amount)?,
+                escrow_info.filled_amount,
+                amount,
+            )?
+        } else {
+            escrow_info.expected_amount
+        };
+        Some(Self {
+            escrow,
+            escrow_info,
+            amount,
+            cost,
+        })
+    }
+
+    /// Receive base units per deposited base unit, for display
+    pub fn price(&self) -> f64 {
+        self.cost as f64 / self.amount as f64
+    }
+
+    /// Orders the cheaper of two orders on the same side first, comparing exactly
+    pub fn cmp_price(&self, other: &Self) -> Ordering {
+        (self.cost as u128 * other.amount as u128).cmp(&(other.cost as u128 * self.amount as u128))
+    }
+}
+
+/// The orders on both sides of a mint pair as of `slot`, each side best price first
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrderBook {
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub slot: u64,
+    /// escrows selling the base mint for the quote mint, lowest price first
+    pub asks: Vec<Order>,
+    /// escrows selling the quote mint for the base mint, highest price of the base mint first
+    pub bids: Vec<Order>,
+}
+
+impl OrderBook {
+    /// Sorts `asks` and `bids` into a book. An order's price is in its receive mint per deposited
+    /// unit, so on both sides the lowest comes first, ties keeping their order.
+    pub fn new(
+        base_mint: Pubkey,
+        quote_mint: Pubkey,
+        slot: u64,
+        mut asks: Vec<Order>,
+        mut bids: Vec<Order>,
+    ) -> Self {
+        asks.sort_by(Order::cmp_price);
+        bids.sort_by(Order::cmp_price);
+        Self {
+            base_mint,
+            quote_mint,
+            slot,
+            asks,
+            bids,
+        }
+    }
+
+    /// The lowest ask, in quote base units per base unit
+    pub fn best_ask(&self) -> Option<f64> {
+        self.asks.first().map(Order::price)
+    }
+
+    /// The highest bid, in quote base units per base unit
+    pub fn best_bid(&self) -> Option<f64> {
+        self.bids.first().map(|order| 1.0 / order.price())
+    }
+}
+
+/// The order book of `base_mint` against `quote_mint` among the escrows of `program_id`
+pub fn fetch_order_book(
+    rpc: &RpcClient,
+    program_id: &Pubkey,
+    base_mint: &Pubkey,
+    quote_mint: &Pubkey,
+) -> ClientResult<OrderBook> {
+    let slot = rpc
+        .call("getSlot", json!([{ "commitment": "confirmed" }]))?
+        .as_u64()
+        .ok_or_else(|| custom("missing slot"))?;
+    let asks = fetch_orders(
+        rpc,
+        &escrow_addresses(rpc, program_id, quote_mint)?,
+        base_mint,
+        slot,
+    )?;
+    let bids = fetch_orders(
+        rpc,
+        &escrow_addresses(rpc, program_id, base_mint)?,
+        quote_mint,
+        slot,
+    )?;
+    Ok(OrderBook::new(*base_mint, *quote_mint, slot, asks, bids))
+}
+
+/// Addresses of the open escrows of `program_id` asking for `receive_mint`, listed without
+/// their data
+pub fn escrow_addresses(
+    rpc: &RpcClient,
+    program_id: &Pubkey,
+    receive_mint: &Pubkey,
+) -> ClientResult<Vec<Pubkey>> {
+    let accounts = rpc.call(
+        "getProgramAccounts",
+        json!([program_id.to_string(), {
+            "encoding": "base64",
+            "commitment": "confirmed",
+            "dataSlice": { "offset": 0, "length": 0 },
+            "filters": filters::escrows_by_receive_mint(receive_mint),
+        }]),
+    )?;
+    accounts
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|keyed_account| {
+            let pubkey = keyed_account["pubkey"]
+                .as_str()
+                .ok_or_else(|| custom("pubkey"))?;
+            Pubkey::from_str(pubkey).map_err(|error| custom(&error.to_string()))
+        })
+        .collect()
+}
+
+/// The orders at `slot` among the escrows at `addresses` whose vault holds `deposit_mint`,
+/// unsorted. Reads [`PAGE_SIZE`] escrows per call, so UIs can also pass one page of
+/// [`escrow_addresses`] at a time.
+pub fn fetch_orders(
+    rpc: &RpcClient,
+    addresses: &[Pubkey],
+    deposit_mint: &Pubkey,
+    slot: u64,
+) -> ClientResult<Vec<Order>> {
+    let mut orders = Vec::new();
+    for page in addresses.chunks(PAGE_SIZE) {
+        let escrows = multiple_accounts_data(rpc, page, None)?;
+        let candidates = page
+            .iter()
+            .zip(escrows)
+            .filter_map(|(escrow, data)| {
+                let escrow_info = Escrow::unpack(&data?).ok()?;
+                Order::new(*escrow, escrow_info, slot)
+            })
+            .collect::<Vec<_>>();
+        let vaults = candidates
+            .iter()
+            .map(|order| order.escrow_info.temp_token_account_pubkey)
+            .collect::<Vec<_>>();
+        // a token account starts with its mint
+        let mints = multiple_accounts_data(rpc, &vaults, Some(32))?;
+        orders.extend(
+            candidates
+                .into_iter()
+                .zip(mints)
+                .filter(|(_, mint)| mint.as_deref() == Some(deposit_mint.as_ref()))
+                .map(|(order, _)| order),
+        );
+    }
+    Ok(orders)
+}
+
+/// The data of each account at `addresses`, only its first `prefix_len` bytes if given, `None`
+/// for missing accounts
+fn multiple_accounts_data(
+    rpc: &RpcClient,
+    addresses: &[Pubkey],
+    prefix_len: Option<usize>,
+) -> ClientResult<Vec<Option<Vec<u8>>>> {
+    if addresses.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut config = json!({ "encoding": "base64", "commitment": "confirmed" });
+    if let Some(length) = prefix_len {
+        config["dataSlice"] = json!({ "offset": 0, "length": length });
+    }
+    let addresses = addresses
+        .iter()
+        .map(|address| address.to_string())
+        .collect::<Vec<_>>();
+    let result = rpc.call("getMultipleAccounts", json!([addresses, config]))?;
+    result["value"]
+        .as_array()
+        .ok_or_else(|| custom("missing accounts"))?
+        .iter()
+        .map(|account| {
+            if account.is_null() {
+                return Ok(None);
+            }
+            let data = account["data"][0].as_str().ok_or_else(|| custom("data"))?;
+            base64::decode(data)
+                .map(Some)
+                .map_err(|error| custom(&error.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order_book() {
+        let open = Escrow {
+            is_initialized: true,
+            ..Escrow::default()
+        };
+        let order = |escrow_info| Order::new(Pubkey::new_unique(), escrow_info, 10);
+        // 100 for 250, a crowdfill escrow with 40 of 100 left for 60, and a curve from 2 to 3
+        let fixed = order(Escrow {
+            expected_amount: 250,
+            deposited_amount: 100,
+            ..open
+        })
+        .unwrap();
+        let crowdfill = order(Escrow {
+            expected_amount: 100,
+            deposited_amount: 40,
+            filled_amount: 60,
+            is_crowdfill: true,
+            ..open
+        })
+        .unwrap();
+        assert_eq!(crowdfill.cost, 40);
+        let curve = order(Escrow {
+            curve_start_price: 2 * math::CURVE_PRICE_SCALE as u64,
+            curve_slope: math::CURVE_PRICE_SCALE as u64 / 100,
+            deposited_amount: 100,
+            ..open
+        })
+        .unwrap();
+        assert_eq!(curve.cost, 250);
+        assert_eq!(fixed.cmp_price(&curve), Ordering::Equal);
+
+        for escrow_info in [
+            Escrow::default(),
+            Escrow {
+                deposited_amount: 100,
+                expires_at_slot: 10,
+                ..open
+            },
+            Escrow {
+                deposited_amount: 100,
+                allowed_taker: Pubkey::new_unique(),
+                ..open
+            },
+            Escrow {
+                deposited_amount: 100,
+                oracle_pubkey: Pubkey::new_unique(),
+                ..open
+            },
+            Escrow {
+                deposited_amount: 100,
+                raffle_end_slot: 20,
+                ..open
+            },
+        ]
+        .iter()
+        {
+            assert_eq!(order(*escrow_info), None);
+        }
+
+        let bid = order(Escrow {
+            expected_amount: 50,
+            deposited_amount: 200,
+            ..open
+        })
+        .unwrap();
+        let book = OrderBook::new(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            vec![fixed, crowdfill, curve],
+            vec![bid],
+        );
+        assert_eq!(book.asks, vec![crowdfill, fixed, curve]);
+        assert_eq!(book.best_ask(), Some(1.0));
+        assert_eq!(book.best_bid(), Some(4.0));
+    }
+}
@@ -0,0 +1,80 @@
+//! Solana Pay transaction requests filling an escrow, so mobile wallets can take an offer by
+//! scanning a code. A transfer request can not fill an escrow, the wallet has to sign the
+//! Exchange, so the URL points the wallet to a server that builds it with [`post_response`].
+
+use serde_json::{json, Value};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::transaction::Transaction;
+
+use super::{associated_token, custom, offer::Offer, ClientResult, EscrowRpc};
+
+/// `solana:` URL of the transaction request served at `endpoint`, also what its QR code holds
+pub fn transaction_request_url(endpoint: &str) -> ClientResult<String> {
+    if !endpoint.starts_with("https://") {
+        return Err(custom("transaction requests must be served over https://"));
+    }
+    Ok(format!("solana:{}", percent_encode(endpoint)))
+}
+
+/// Body of the response to the wallet's GET, naming who asks for the transaction
+pub fn get_response(label: &str, icon: &str) -> Value {
+    json!({ "label": label, "icon": icon })
+}
+
+/// Body of the response to the wallet's POST of its `account`, the unsigned transaction
+/// filling `offer` from the wallet's associated token accounts. The account receiving the
+/// deposit is created if need be, the wallet paying the fees.
+pub async fn post_response<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    offer: &Offer,
+    account: &Pubkey,
+) -> ClientResult<Value> {
+    let takers_sending_token_account = associated_token::find_address(account, &offer.receive_mint);
+    let takers_token_to_receive_account =
+        associated_token::find_address(account, &offer.deposit_mint);
+    let instructions = [
+        associated_token::create_idempotent(account, account, &offer.deposit_mint),
+        offer.exchange_instruction(
+            account,
+            &takers_sending_token_account,
+            &takers_token_to_receive_account,
+        ),
+    ];
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(account));
+    transaction.message.recent_blockhash = rpc.latest_blockhash().await?;
+    let transaction =
+        bincode::serialize(&transaction).map_err(|error| custom(&error.to_string()))?;
+    Ok(json!({
+        "transaction": base64::encode(transaction),
+        "message": format!(
+            "{} of {} for {} of {}",
+            offer.deposit_amount, offer.deposit_mint, offer.expected_amount, offer.receive_mint
+        ),
+    }))
+}
+
+/// Percent-encodes everything but RFC 3986's unreserved characters
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transaction_request_url() {
+        assert_eq!(
+            transaction_request_url("https://example.com/fill?escrow=Ab1").unwrap(),
+            "solana:https%3A%2F%2Fexample.com%2Ffill%3Fescrow%3DAb1"
+        );
+        assert!(transaction_request_url("http://example.com").is_err());
+    }
+}
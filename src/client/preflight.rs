@@ -0,0 +1,207 @@
+//! Pre-flight checks, so UIs can show what a transaction costs and why it would fail before the
+//! user signs it
+
+use solana_program::{pubkey::Pubkey, system_instruction::SystemInstruction, system_program};
+use solana_sdk::transaction::Transaction;
+
+use super::{error::failing_program, ClientResult, EscrowClientError, EscrowRpc, Simulation};
+use crate::error::EscrowError;
+
+/// What simulating a transaction revealed about it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preflight {
+    pub simulation: Simulation,
+    /// the error the escrow program fails the transaction with, `None` when the transaction
+    /// succeeds or some other program fails it
+    pub escrow_error: Option<EscrowError>,
+    /// compute units the transaction uses, from the simulation or else its logs
+    pub units_consumed: Option<u64>,
+    /// fee charged for the transaction's signatures
+    pub fee_lamports: u64,
+    /// lamports the transaction moves into the accounts it creates through the system program,
+    /// the rent deposits returned when those accounts are closed
+    pub rent_lamports: u64,
+}
+
+impl Preflight {
+    /// lamports the fee payer needs on top of what the instructions themselves transfer
+    pub fn total_lamports(&self) -> u64 {
+        self.fee_lamports.saturating_add(self.rent_lamports)
+    }
+}
+
+/// Simulates `transaction`, e.g. an assembled InitEscrow or Exchange, and reports its costs and
+/// failure without sending it
+pub async fn preflight<R: EscrowRpc + Send>(
+    rpc: &mut R,
+    program_id: &Pubkey,
+    transaction: &Transaction,
+) -> ClientResult<Preflight> {
+    let simulation = rpc.simulate(transaction).await?;
+    let fee_lamports = rpc.fee_for_message(&transaction.message).await?;
+    Ok(analyze(program_id, transaction, simulation, fee_lamports))
+}
+
+fn analyze(
+    program_id: &Pubkey,
+    transaction: &Transaction,
+    simulation: Simulation,
+    fee_lamports: u64,
+) -> Preflight {
+    let message = &transaction.message;
+    let rent_lamports = message
+        .instructions
+        .iter()
+        .filter(|instruction| {
+            *instruction.program_id(&message.account_keys) == system_program::id()
+        })
+        .filter_map(|instruction| {
+            match bincode::deserialize::<SystemInstruction>(&instruction.data).ok()? {
+                SystemInstruction::CreateAccount { lamports, .. }
+                | SystemInstruction::CreateAccountWithSeed { lamports, .. } => Some(lamports),
+                _ => None,
+            }
+        })
+        .fold(0u64, u64::saturating_add);
+
+    // programs the escrow invokes fail it with their own codes, only trust codes the escrow
+    // program raised itself
+    let escrow_error = match &simulation.error {
+        Some(error) if failing_program(&simulation.logs) == Some(program_id.to_string()) => {
+            EscrowClientError::from_transaction_error(
+                program_id,
+                message,
+                error.clone(),
+                &simulation.logs,
+            )
+            .escrow_error()
+        }
+        _ => None,
+    };
+    let units_consumed = simulation
+        .units_consumed
+        .or_else(|| units_consumed_from_logs(&simulation.logs));
+
+    Preflight {
+        simulation,
+        escrow_error,
+        units_consumed,
+        fee_lamports,
+        rent_lamports,
+    }
+}
+
+/// Sum of the compute units the top-level instructions report consuming, inner invocations
+/// being part of theirs
+pub(super) fn units_consumed_from_logs(logs: &[String]) -> Option<u64> {
+    let mut depth = 0;
+    let mut units_consumed = None;
+    for log in logs {
+        let rest = match log.strip_prefix("Program ") {
+            Some(rest) => rest,
+            None => continue,
+        };
+        let mut words = rest.split(' ');
+        let _program = words.next();
+        match words.next() {
+            Some("invoke") => depth += 1,
+            Some("success") | Some("failed:") => depth -= 1,
+            Some("consumed") if depth == 1 => {
+                let units = words.next().and_then(|units| units.parse::<u64>().ok())?;
+                units_consumed = Some(units_consumed.unwrap_or(0) + units);
+            }
+            _ => {}
+        }
+    }
+    units_consumed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::{
+        instruction::{Instruction, InstructionError},
+        message::Message,
+        system_instruction,
+    };
+    use solana_sdk::transaction::TransactionError;
+
+    #[test]
+    fn test_preflight_analysis() {
+        let program_id = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        let transaction = Transaction::new_unsigned(Message::new(
+            &[
+                system_instruction::create_account(
+                    &payer,
+                    &Pubkey::new_unique(),
+                    2_039_280,
+                    165,
+                    &spl_token::id(),
+                ),
+                system_instruction::transfer(&payer, &Pubkey::new_unique(), 1_000),
+                Instruction::new_with_bytes(program_id, &[1], vec![]),
+            ],
+            Some(&payer),
+        ));
+        let logs: Vec<String> = vec![
+            "Program 11111111111111111111111111111111 invoke [1]".to_string(),
+            "Program 11111111111111111111111111111111 success".to_string(),
+            format!("Program {} invoke [1]", program_id),
+            format!("Program {} invoke [2]", spl_token::id()),
+            format!(
+                "Program {} consumed 2000 of 190000 compute units",
+                spl_token::id()
+            ),
+            format!("Program {} success", spl_token::id()),
+            format!(
+                "Program {} consumed 12000 of 200000 compute units",
+                program_id
+            ),
+            format!("Program {} failed: custom program error: 0x2", program_id),
+        ];
+        let preflight = analyze(
+            &program_id,
+            &transaction,
+            Simulation {
+                error: Some(TransactionError::InstructionError(
+                    2,
+                    InstructionError::Custom(EscrowError::ExpectedAmountMismatch as u32),
+                )),
+                logs: logs.clone(),
+                ..Simulation::default()
+            },
+            5000,
+        );
+        assert_eq!(
+            preflight.escrow_error,
+            Some(EscrowError::ExpectedAmountMismatch)
+        );
+        assert_eq!(preflight.units_consumed, Some(12000));
+        assert_eq!(preflight.rent_lamports, 2_039_280);
+        assert_eq!(preflight.total_lamports(), 2_044_280);
+
+        // the token program's codes are not the escrow's
+        let mut token_logs = logs[..4].to_vec();
+        token_logs.push(format!(
+            "Program {} failed: custom program error: 0x1",
+            spl_token::id()
+        ));
+        let preflight = analyze(
+            &program_id,
+            &transaction,
+            Simulation {
+                error: Some(TransactionError::InstructionError(
+                    2,
+                    InstructionError::Custom(1),
+                )),
+                logs: token_logs,
+                units_consumed: Some(3000),
+                ..Simulation::default()
+            },
+            5000,
+        );
+        assert_eq!(preflight.escrow_error, None);
+        assert_eq!(preflight.units_consumed, Some(3000));
+    }
+}
@@ -0,0 +1,269 @@
+//! A minimal JSON-RPC client for a validator's plain `http://` endpoint. solana-client is not a
+//! dependency, so like the indexer example this speaks HTTP/1.0 over a `TcpStream` itself; put a
+//! TLS terminating proxy in front of public `https://` nodes. Requests block the calling task.
+
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    str::FromStr,
+    thread,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use solana_program::{hash::Hash, message::Message, pubkey::Pubkey};
+use solana_sdk::{
+    account::Account,
+    transaction::{Transaction, TransactionError},
+};
+
+use super::{custom, versioned::VersionedTransaction, ClientResult, EscrowRpc, Simulation};
+
+pub const DEFAULT_RPC_URL: &str = "http://127.0.0.1:8899";
+
+const CONFIRMATION_POLLS: usize = 60;
+
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// JSON-RPC connection details, a fresh connection is opened per request
+#[derive(Clone, Debug, PartialEq)]
+pub struct RpcClient {
+    host: String,
+    path: String,
+}
+
+impl RpcClient {
+    pub fn new(url: &str) -> ClientResult<Self> {
+        let address = url
+            .strip_prefix("http://")
+            .ok_or_else(|| custom("only http:// urls are supported"))?;
+        let (host, path) = match address.find('/') {
+            Some(index) => (&address[..index], &address[index..]),
+            None => (address, "/"),
+        };
+        Ok(Self {
+            host: host.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    /// The `result` of calling `method`, an error for JSON-RPC errors
+    pub fn call(&self, method: &str, params: Value) -> ClientResult<Value> {
+        let body = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+        let body = body.to_string();
+        let mut stream = TcpStream::connect(&self.host)?;
+        write!(
+            stream,
+            "POST {} HTTP/1.0\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host,
+            body.len(),
+            body
+        )?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        let body = response
+            .split_once("\r\n\r\n")
+            .map(|(_, body)| body)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed response"))?;
+        let mut response: Value =
+            serde_json::from_str(body).map_err(|error| custom(&error.to_string()))?;
+        if let Some(error) = response.get("error") {
+            return Err(custom(&error.to_string()));
+        }
+        Ok(response["result"].take())
+    }
+
+    /// sends a base64 encoded wire format transaction and waits for it to be confirmed
+    fn send_encoded(&self, transaction: String) -> ClientResult<()> {
+        let result = self.call(
+            "sendTransaction",
+            json!([transaction, {"encoding": "base64"}]),
+        )?;
+        let signature = result.as_str().ok_or_else(|| custom("missing signature"))?;
+        self.confirm(signature)
+    }
+
+    /// Waits for the transaction of `signature` to be confirmed, failing with its error if it
+    /// failed
+    pub fn confirm(&self, signature: &str) -> ClientResult<()> {
+        for _ in 0..CONFIRMATION_POLLS {
+            let result = self.call("getSignatureStatuses", json!([[signature]]))?;
+            let status = &result["value"][0];
+            let confirmed = matches!(
+                status["confirmationStatus"].as_str(),
+                Some("confirmed") | Some("finalized")
+            );
+            if !status["err"].is_null() {
+                let error = serde_json::from_value::<TransactionError>(status["err"].clone())
+                    .map_err(|error| custom(&error.to_string()))?;
+                return Err(error.into());
+            }
+            if confirmed {
+                return Ok(());
+            }
+            thread::sleep(CONFIRMATION_POLL_INTERVAL);
+        }
+        Err(custom(&format!("{} was not confirmed in time", signature)))
+    }
+}
+
+#[async_trait]
+impl EscrowRpc for RpcClient {
+    async fn get_account(&mut self, address: &Pubkey) -> ClientResult<Option<Account>> {
+        let result = self.call(
+            "getAccountInfo",
+            json!([address.to_string(), {"encoding": "base64"}]),
+        )?;
+        parse_account(&result["value"])
+    }
+
+    async fn latest_blockhash(&mut self) -> ClientResult<Hash> {
+        let result = self.call("getLatestBlockhash", json!([]))?;
+        let blockhash = result["value"]["blockhash"]
+            .as_str()
+            .ok_or_else(|| custom("missing blockhash"))?;
+        Hash::from_str(blockhash).map_err(|error| custom(&error.to_string()))
+    }
+
+    async fn send_transaction(&mut self, transaction: Transaction) -> ClientResult<()> {
+        self.send_encoded(encode_transaction(&transaction)?)
+    }
+
+    async fn simulate(&mut self, transaction: &Transaction) -> ClientResult<Simulation> {
+        let result = self.call(
+            "simulateTransaction",
+            json!([encode_transaction(transaction)?, {"encoding": "base64"}]),
+        )?;
+        parse_simulation(&result["value"])
+    }
+
+    async fn fee_for_message(&mut self, message: &Message) -> ClientResult<u64> {
+        let message = bincode::serialize(message).map_err(|error| custom(&error.to_string()))?;
+        let result = self.call(
+            "getFeeForMessage",
+            json!([base64::encode(message), {"commitment": "confirmed"}]),
+        )?;
+        // null when the message's blockhash has expired
+        result["value"]
+            .as_u64()
+            .ok_or_else(|| custom("blockhash expired"))
+    }
+
+    async fn minimum_balance_for_rent_exemption(&mut self, data_len: usize) -> ClientResult<u64> {
+        self.call("getMinimumBalanceForRentExemption", json!([data_len]))?
+            .as_u64()
+            .ok_or_else(|| custom("missing rent exemption minimum"))
+    }
+
+    async fn send_versioned_transaction(
+        &mut self,
+        transaction: &VersionedTransaction,
+    ) -> ClientResult<()> {
+        self.send_encoded(base64::encode(transaction.serialize()))
+    }
+}
+
+fn encode_transaction(transaction: &Transaction) -> ClientResult<String> {
+    let bytes = bincode::serialize(transaction).map_err(|error| custom(&error.to_string()))?;
+    Ok(base64::encode(bytes))
+}
+
+/// The account of a base64 encoded `getAccountInfo` value, `None` for `null`
+fn parse_account(value: &Value) -> ClientResult<Option<Account>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    let field = |name: &str| value.get(name).ok_or_else(|| custom(name));
+    let data = field("data")?[0].as_str().ok_or_else(|| custom("data"))?;
+    let owner = field("owner")?.as_str().ok_or_else(|| custom("owner"))?;
+    Ok(Some(Account {
+        lamports: field("lamports")?
+            .as_u64()
+            .ok_or_else(|| custom("lamports"))?,
+        data: base64::decode(data).map_err(|error| custom(&error.to_string()))?,
+        owner: Pubkey::from_str(owner).map_err(|error| custom(&error.to_string()))?,
+        executable: field("executable")?.as_bool().unwrap_or(false),
+        rent_epoch: field("rentEpoch")?.as_u64().unwrap_or(0),
+    }))
+}
+
+fn parse_simulation(value: &Value) -> ClientResult<Simulation> {
+    let error = if value["err"].is_null() {
+        None
+    } else {
+        Some(
+            serde_json::from_value::<TransactionError>(value["err"].clone())
+                .map_err(|error| custom(&error.to_string()))?,
+        )
+    };
+    let logs = value["logs"]
+        .as_array()
+        .map(|logs| {
+            logs.iter()
+                .filter_map(|log| log.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    let return_data = match value["returnData"]["data"][0].as_str() {
+        Some(data) => Some(base64::decode(data).map_err(|error| custom(&error.to_string()))?),
+        None => None,
+    };
+    Ok(Simulation {
+        error,
+        logs,
+        units_consumed: value["unitsConsumed"].as_u64(),
+        return_data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::instruction::InstructionError;
+
+    #[test]
+    fn test_parse_rpc_values() {
+        let owner = Pubkey::new_unique();
+        let value = json!({
+            "data": [base64::encode([1, 2, 3]), "base64"],
+            "executable": false,
+            "lamports": 42,
+            "owner": owner.to_string(),
+            "rentEpoch": 7,
+        });
+        let account = parse_account(&value).unwrap().unwrap();
+        assert_eq!(account.data, vec![1, 2, 3]);
+        assert_eq!(account.lamports, 42);
+        assert_eq!(account.owner, owner);
+        assert_eq!(account.rent_epoch, 7);
+        assert_eq!(parse_account(&Value::Null).unwrap(), None);
+
+        let value = json!({
+            "err": {"InstructionError": [0, {"Custom": 3}]},
+            "logs": ["Program log: Instruction: Exchange"],
+            "unitsConsumed": 5000,
+            "returnData": {"programId": owner.to_string(), "data": [base64::encode([4, 5]), "base64"]},
+        });
+        assert_eq!(
+            parse_simulation(&value).unwrap(),
+            Simulation {
+                error: Some(TransactionError::InstructionError(
+                    0,
+                    InstructionError::Custom(3)
+                )),
+                logs: vec!["Program log: Instruction: Exchange".to_string()],
+                units_consumed: Some(5000),
+                return_data: Some(vec![4, 5]),
+            }
+        );
+        assert_eq!(
+            RpcClient::new(DEFAULT_RPC_URL).unwrap(),
+            RpcClient {
+                host: "127.0.0.1:8899".to_string(),
+                path: "/".to_string(),
+            }
+        );
+    }
+}
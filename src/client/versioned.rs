@@ -0,0 +1,316 @@
+//! Version 0 messages, which load accounts from address lookup tables so transactions stay
+//! under the size limit as Exchange's account list grows. The sdk of this Solana version only
+//! has legacy messages, so they are compiled and serialized here.
+
+use solana_program::{
+    hash::Hash,
+    instruction::{CompiledInstruction, Instruction},
+    message::MessageHeader,
+    pubkey::Pubkey,
+    short_vec::ShortU16,
+};
+use solana_sdk::signature::{Signature, Signer};
+
+use super::{custom, lookup_table::AddressLookupTable, ClientResult};
+
+/// Prefix byte of a version 0 message, the high bit telling it from a legacy message
+pub const MESSAGE_VERSION_PREFIX: u8 = 0x80;
+
+/// The accounts a message loads from one lookup table
+#[derive(Clone, Debug, PartialEq)]
+pub struct MessageAddressTableLookup {
+    pub account_key: Pubkey,
+    pub writable_indexes: Vec<u8>,
+    pub readonly_indexes: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MessageV0 {
+    /// counts of the static account keys
+    pub header: MessageHeader,
+    pub account_keys: Vec<Pubkey>,
+    pub recent_blockhash: Hash,
+    /// instructions indexing the static keys, then the writable and then the readonly accounts
+    /// loaded from the tables
+    pub instructions: Vec<CompiledInstruction>,
+    pub address_table_lookups: Vec<MessageAddressTableLookup>,
+}
+
+/// An account of the message with its strictest use
+struct KeyUse {
+    key: Pubkey,
+    is_signer: bool,
+    is_writable: bool,
+    is_invoked: bool,
+}
+
+impl MessageV0 {
+    /// Compiles `instructions` paid by `payer`, loading every account that is neither a signer
+    /// nor an invoked program from the first of `lookup_tables` holding it
+    pub fn compile(
+        payer: &Pubkey,
+        instructions: &[Instruction],
+        lookup_tables: &[AddressLookupTable],
+        recent_blockhash: Hash,
+    ) -> ClientResult<Self> {
+        let mut uses = vec![KeyUse {
+            key: *payer,
+            is_signer: true,
+            is_writable: true,
+            is_invoked: false,
+        }];
+        let mut note =
+            |key: &Pubkey, is_signer: bool, is_writable: bool, is_invoked: bool| match uses
+                .iter_mut()
+                .find(|key_use| key_use.key == *key)
+            {
+                Some(key_use) => {
+                    key_use.is_signer |= is_signer;
+                    key_use.is_writable |= is_writable;
+                    key_use.is_invoked |= is_invoked;
+                }
+                None => uses.push(KeyUse {
+                    key: *key,
+                    is_signer,
+                    is_writable,
+                    is_invoked,
+                }),
+            };
+        for instruction in instructions {
+            note(&instruction.program_id, false, false, true);
+            for meta in instruction.accounts.iter() {
+                note(&meta.pubkey, meta.is_signer, meta.is_writable, false);
+            }
+        }
+
+        let mut lookups: Vec<(MessageAddressTableLookup, Vec<Pubkey>, Vec<Pubkey>)> = lookup_tables
+            .iter()
+            .map(|table| {
+                let lookup = MessageAddressTableLookup {
+                    account_key: table.key,
+                    writable_indexes: vec![],
+                    readonly_indexes: vec![],
+                };
+                (lookup, vec![], vec![])
+            })
+            .collect();
+        let mut static_uses = vec![];
+        'uses: for key_use in uses {
+            if !key_use.is_signer && !key_use.is_invoked {
+                for (table, (lookup, writable, readonly)) in lookup_tables.iter().zip(&mut lookups)
+                {
+                    let index = match table.addresses.iter().position(|a| *a == key_use.key) {
+                        Some(index) if index <= u8::MAX as usize => index as u8,
+                        _ => continue,
+                    };
+                    if key_use.is_writable {
+                        lookup.writable_indexes.push(index);
+                        writable.push(key_use.key);
+                    } else {
+                        lookup.readonly_indexes.push(index);
+                        readonly.push(key_use.key);
+                    }
+                    continue 'uses;
+                }
+            }
+            static_uses.push(key_use);
+        }
+        // the sort is stable, keeping the payer first
+        static_uses.sort_by_key(|key_use| (!key_use.is_signer, !key_use.is_writable));
+
+        let header = MessageHeader {
+            num_required_signatures: count(&static_uses, |u| u.is_signer)?,
+            num_readonly_signed_accounts: count(&static_uses, |u| u.is_signer && !u.is_writable)?,
+            num_readonly_unsigned_accounts: count(&static_uses, |u| {
+                !u.is_signer && !u.is_writable
+            })?,
+        };
+        let account_keys: Vec<Pubkey> = static_uses.iter().map(|key_use| key_use.key).collect();
+        let mut all_keys = account_keys.clone();
+        all_keys.extend(lookups.iter().flat_map(|(_, writable, _)| writable));
+        all_keys.extend(lookups.iter().flat_map(|(_, _, readonly)| readonly));
+        if all_keys.len() > u8::MAX as usize + 1 {
+            return Err(custom("a message can not use more than 256 accounts"));
+        }
+        let index_of = |key: &Pubkey| all_keys.iter().position(|k| k == key).unwrap() as u8;
+
+        Ok(Self {
+            header,
+            account_keys,
+            recent_blockhash,
+            instructions: instructions
+                .iter()
+                .map(|instruction| CompiledInstruction {
+                    program_id_index: index_of(&instruction.program_id),
+                    accounts: instruction
+                        .accounts
+                        .iter()
+                        .map(|meta| index_of(&meta.pubkey))
+                        .collect(),
+                    data: instruction.data.clone(),
+                })
+                .collect(),
+            address_table_lookups: lookups
+                .into_iter()
+                .map(|(lookup, _, _)| lookup)
+                .filter(|lookup| {
+                    !lookup.writable_indexes.is_empty() || !lookup.readonly_indexes.is_empty()
+                })
+                .collect(),
+        })
+    }
+
+    /// The bytes signers sign
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = vec![
+            MESSAGE_VERSION_PREFIX,
+            self.header.num_required_signatures,
+            self.header.num_readonly_signed_accounts,
+            self.header.num_readonly_unsigned_accounts,
+        ];
+        push_len(&mut bytes, self.account_keys.len());
+        for key in self.account_keys.iter() {
+            bytes.extend_from_slice(key.as_ref());
+        }
+        bytes.extend_from_slice(self.recent_blockhash.as_ref());
+        push_len(&mut bytes, self.instructions.len());
+        for instruction in self.instructions.iter() {
+            bytes.push(instruction.program_id_index);
+            push_len(&mut bytes, instruction.accounts.len());
+            bytes.extend_from_slice(&instruction.accounts);
+            push_len(&mut bytes, instruction.data.len());
+            bytes.extend_from_slice(&instruction.data);
+        }
+        push_len(&mut bytes, self.address_table_lookups.len());
+        for lookup in self.address_table_lookups.iter() {
+            bytes.extend_from_slice(lookup.account_key.as_ref());
+            push_len(&mut bytes, lookup.writable_indexes.len());
+            bytes.extend_from_slice(&lookup.writable_indexes);
+            push_len(&mut bytes, lookup.readonly_indexes.len());
+            bytes.extend_from_slice(&lookup.readonly_indexes);
+        }
+        bytes
+    }
+}
+
+fn count(uses: &[KeyUse], predicate: impl Fn(&KeyUse) -> bool) -> ClientResult<u8> {
+    let count = uses.iter().filter(|key_use| predicate(key_use)).count();
+    if count > u8::MAX as usize {
+        return Err(custom("a message can not use more than 255 such accounts"));
+    }
+    Ok(count as u8)
+}
+
+fn push_len(bytes: &mut Vec<u8>, len: usize) {
+    // every length of a message is far below u16::MAX, the packet size caps it
+    bytes.extend(bincode::serialize(&ShortU16(len as u16)).unwrap());
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionedTransaction {
+    pub signatures: Vec<Signature>,
+    pub message: MessageV0,
+}
+
+impl VersionedTransaction {
+    /// Signs `message` with `signers`, which must hold exactly the keys it requires signatures
+    /// of
+    pub fn new(message: MessageV0, signers: &[&dyn Signer]) -> ClientResult<Self> {
+        let bytes = message.serialize();
+        let signer_keys = &message.account_keys[..message.header.num_required_signatures as usize];
+        let signatures = signer_keys
+            .iter()
+            .map(|key| {
+                signers
+                    .iter()
+                    .find(|signer| signer.pubkey() == *key)
+                    .map(|signer| signer.sign_message(&bytes))
+                    .ok_or_else(|| custom(&format!("no signer for {}", key)))
+            })
+            .collect::<ClientResult<Vec<_>>>()?;
+        Ok(Self {
+            signatures,
+            message,
+        })
+    }
+
+    /// The wire format `sendTransaction` takes
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        push_len(&mut bytes, self.signatures.len());
+        for signature in self.signatures.iter() {
+            bytes.extend_from_slice(signature.as_ref());
+        }
+        bytes.extend(self.message.serialize());
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::instruction::AccountMeta;
+    use solana_sdk::signature::Keypair;
+
+    #[test]
+    fn test_compile_v0_message() {
+        let taker = Keypair::new();
+        let program_id = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let escrow = Pubkey::new_unique();
+        let config = Pubkey::new_unique();
+        let table = AddressLookupTable {
+            key: Pubkey::new_unique(),
+            addresses: vec![spl_token::id(), config, vault],
+        };
+        let instruction = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(taker.pubkey(), true),
+                AccountMeta::new(vault, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(config, false),
+            ],
+            data: vec![1],
+        };
+        let message = MessageV0::compile(
+            &taker.pubkey(),
+            &[instruction],
+            std::slice::from_ref(&table),
+            Hash::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            message.header,
+            MessageHeader {
+                num_required_signatures: 1,
+                num_readonly_signed_accounts: 0,
+                num_readonly_unsigned_accounts: 1,
+            }
+        );
+        assert_eq!(
+            message.account_keys,
+            vec![taker.pubkey(), escrow, program_id]
+        );
+        assert_eq!(
+            message.address_table_lookups,
+            vec![MessageAddressTableLookup {
+                account_key: table.key,
+                writable_indexes: vec![2],
+                readonly_indexes: vec![0, 1],
+            }]
+        );
+        // static keys, then the loaded writable vault, then the loaded readonly accounts
+        assert_eq!(message.instructions[0].program_id_index, 2);
+        assert_eq!(message.instructions[0].accounts, vec![0, 3, 1, 4, 5]);
+
+        let transaction = VersionedTransaction::new(message.clone(), &[&taker]).unwrap();
+        let bytes = transaction.serialize();
+        assert_eq!(bytes[0], 1);
+        assert_eq!(&bytes[65..], &message.serialize()[..]);
+        assert_eq!(bytes[65], MESSAGE_VERSION_PREFIX);
+        assert!(transaction.signatures[0].verify(taker.pubkey().as_ref(), &bytes[65..]));
+        assert!(VersionedTransaction::new(message, &[]).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+//! Transactions for wallet-adapter style flows, where the user's wallet signs last. The client
+//! signs for the accounts it creates with throwaway keypairs and hands the rest to the wallet.
+
+use solana_program::{
+    hash::Hash, instruction::Instruction, program_error::ProgramError, program_pack::Pack,
+    pubkey::Pubkey, system_instruction,
+};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+use super::{custom, ClientResult, EscrowRpc};
+use crate::{instruction, state::Escrow};
+
+/// What the initializer puts up and asks for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InitEscrowParams {
+    pub initializer: Pubkey,
+    /// the initializer's token account the deposit is taken from
+    pub initializers_sending_token_account: Pubkey,
+    pub initializers_token_to_receive_account: Pubkey,
+    pub deposit_mint: Pubkey,
+    pub deposit_amount: u64,
+    pub expected_amount: u64,
+}
+
+/// An InitEscrow transaction signed by the new vault and escrow accounts, waiting for the
+/// initializer's wallet, which pays the fees and the rent
+pub struct PartiallySignedInit {
+    pub transaction: Transaction,
+    vault: Keypair,
+    escrow: Keypair,
+}
+
+impl PartiallySignedInit {
+    /// Builds the transaction creating a vault, moving the deposit into it and opening the
+    /// escrow, signed with fresh keypairs for the new accounts
+    pub async fn new<R: EscrowRpc + Send>(
+        rpc: &mut R,
+        program_id: &Pubkey,
+        params: &InitEscrowParams,
+    ) -> ClientResult<Self> {
+        let vault = Keypair::new();
+        let escrow = Keypair::new();
+        let vault_rent = rpc
+            .minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
+            .await?;
+        let escrow_rent = rpc.minimum_balance_for_rent_exemption(Escrow::LEN).await?;
+        let instructions = init_escrow_instructions(
+            program_id,
+            params,
+            (&vault.pubkey(), vault_rent),
+            (&escrow.pubkey(), escrow_rent),
+        )?;
+        let mut init = Self {
+            transaction: Transaction::new_with_payer(&instructions, Some(&params.initializer)),
+            vault,
+            escrow,
+        };
+        init.sign(rpc.latest_blockhash().await?)?;
+        Ok(init)
+    }
+
+    pub fn vault(&self) -> Pubkey {
+        self.vault.pubkey()
+    }
+
+    pub fn escrow(&self) -> Pubkey {
+        self.escrow.pubkey()
+    }
+
+    /// Moves the transaction to the latest blockhash and signs it again, for when the wallet
+    /// took too long and the previous blockhash expired. Clears the wallet's signature.
+    pub async fn refresh_blockhash<R: EscrowRpc + Send>(
+        &mut self,
+        rpc: &mut R,
+    ) -> ClientResult<()> {
+        self.sign(rpc.latest_blockhash().await?)
+    }
+
+    /// The transaction as base64 wire format, what wallet adapters deserialize
+    pub fn encode(&self) -> ClientResult<String> {
+        let bytes =
+            bincode::serialize(&self.transaction).map_err(|error| custom(&error.to_string()))?;
+        Ok(base64::encode(bytes))
+    }
+
+    fn sign(&mut self, blockhash: Hash) -> ClientResult<()> {
+        // signing under a new blockhash clears the signatures made under the old one
+        self.transaction
+            .try_partial_sign(&[&self.vault, &self.escrow], blockhash)
+            .map_err(|error| custom(&error.to_string()))
+    }
+}
+
+fn init_escrow_instructions(
+    program_id: &Pubkey,
+    params: &InitEscrowParams,
+    (vault, vault_rent): (&Pubkey, u64),
+    (escrow, escrow_rent): (&Pubkey, u64),
+) -> ClientResult<Vec<Instruction>> {
+    let token_error = |error: ProgramError| custom(&error.to_string());
+    Ok(vec![
+        system_instruction::create_account(
+            &params.initializer,
+            vault,
+            vault_rent,
+            spl_token::state::Account::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            vault,
+            &params.deposit_mint,
+            &params.initializer,
+        )
+        .map_err(token_error)?,
+        spl_token::instruction::transfer(
+            &spl_token::id(),
+            &params.initializers_sending_token_account,
+            vault,
+            &params.initializer,
+            &[],
+            params.deposit_amount,
+        )
+        .map_err(token_error)?,
+        system_instruction::create_account(
+            &params.initializer,
+            escrow,
+            escrow_rent,
+            Escrow::LEN as u64,
+            program_id,
+        ),
+        instruction::init_escrow(
+            program_id,
+            &params.initializer,
+            vault,
+            &params.initializers_token_to_receive_account,
+            escrow,
+            params.expected_amount,
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partially_signed_init() {
+        let program_id = Pubkey::new_unique();
+        let wallet = Keypair::new();
+        let params = InitEscrowParams {
+            initializer: wallet.pubkey(),
+            initializers_sending_token_account: Pubkey::new_unique(),
+            initializers_token_to_receive_account: Pubkey::new_unique(),
+            deposit_mint: Pubkey::new_unique(),
+            deposit_amount: 1_000,
+            expected_amount: 5_000,
+        };
+        let vault = Keypair::new();
+        let escrow = Keypair::new();
+        let instructions = init_escrow_instructions(
+            &program_id,
+            &params,
+            (&vault.pubkey(), 2_039_280),
+            (&escrow.pubkey(), 3_000_000),
+        )
+        .unwrap();
+        let mut init = PartiallySignedInit {
+            transaction: Transaction::new_with_payer(&instructions, Some(&wallet.pubkey())),
+            vault,
+            escrow,
+        };
+        init.sign(Hash::new_unique()).unwrap();
+
+        // the wallet pays, so it signs first
+        let keys = &init.transaction.message.account_keys;
+        assert_eq!(keys[0], wallet.pubkey());
+        assert_eq!(init.transaction.signatures.len(), 3);
+        assert_eq!(init.transaction.signatures[0], Default::default());
+        assert!(!init.transaction.is_signed());
+        let blockhash = init.transaction.message.recent_blockhash;
+        init.transaction.partial_sign(&[&wallet], blockhash);
+        assert!(init.transaction.verify().is_ok());
+
+        init.sign(Hash::new_unique()).unwrap();
+        assert_eq!(init.transaction.signatures[0], Default::default());
+        let blockhash = init.transaction.message.recent_blockhash;
+        init.transaction.partial_sign(&[&wallet], blockhash);
+        assert!(init.transaction.verify().is_ok());
+    }
+}
@@ -0,0 +1,250 @@
+//! Live order flow from account changes. [`watch`] subscribes to the program's escrow accounts
+//! with `programSubscribe` and turns every change into an [`EscrowEvent`]. Account data can not
+//! tell a full fill from a cancel, both close the escrow, so for closes the watcher reads the
+//! event logs of the closing transaction over JSON-RPC.
+
+use std::{
+    collections::BTreeMap,
+    str::FromStr,
+    sync::mpsc::{self, Receiver},
+    thread::{self, JoinHandle},
+};
+
+use serde_json::{json, Value};
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+
+use super::{custom, filters, rpc::RpcClient, websocket::WebSocket, ClientResult};
+use crate::{event, state::Escrow};
+
+/// A change of an escrow seen by the watcher
+#[derive(Clone, Debug, PartialEq)]
+pub enum EscrowEvent {
+    /// A new escrow was opened
+    Created { escrow: Pubkey, state: Escrow },
+    /// A taker bought `fill_amount` of the deposit, `state` is `None` when the fill closed the
+    /// escrow
+    Filled {
+        escrow: Pubkey,
+        fill_amount: u64,
+        state: Option<Escrow>,
+    },
+    /// The escrow closed without being filled, cancelled or reclaimed once expired
+    Cancelled { escrow: Pubkey },
+}
+
+/// The escrows known to be open, turning their account changes into events
+#[derive(Debug, Default)]
+pub struct EscrowTracker {
+    escrows: BTreeMap<Pubkey, Escrow>,
+}
+
+impl EscrowTracker {
+    /// Starts tracking an escrow that was already open, without an event
+    pub fn insert(&mut self, escrow: Pubkey, state: Escrow) {
+        self.escrows.insert(escrow, state);
+    }
+
+    /// The event of `escrow`'s account now holding `state`, `None` if nothing was filled
+    pub fn update(&mut self, escrow: Pubkey, state: Escrow) -> Option<EscrowEvent> {
+        match self.escrows.insert(escrow, state) {
+            None => Some(EscrowEvent::Created { escrow, state }),
+            Some(previous) => match state.filled_amount.checked_sub(previous.filled_amount) {
+                Some(fill_amount) if fill_amount > 0 => Some(EscrowEvent::Filled {
+                    escrow,
+                    fill_amount,
+                    state: Some(state),
+                }),
+                _ => None,
+            },
+        }
+    }
+
+    /// The event of `escrow` closing, `fill_amount` being what the closing fill bought or
+    /// `None` if no fill closed it, `None` for escrows that were not tracked
+    pub fn close(&mut self, escrow: Pubkey, fill_amount: Option<u64>) -> Option<EscrowEvent> {
+        self.escrows.remove(&escrow)?;
+        Some(match fill_amount {
+            Some(fill_amount) => EscrowEvent::Filled {
+                escrow,
+                fill_amount,
+                state: None,
+            },
+            None => EscrowEvent::Cancelled { escrow },
+        })
+    }
+}
+
+/// Watches the escrows of `program_id` from a thread, delivering their events until the
+/// websocket closes or fails, the error being the last item sent
+pub fn watch(
+    websocket_url: &str,
+    rpc: RpcClient,
+    program_id: Pubkey,
+) -> ClientResult<(Receiver<ClientResult<EscrowEvent>>, JoinHandle<()>)> {
+    let mut socket = WebSocket::connect(websocket_url)?;
+    let subscribe = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "programSubscribe",
+        "params": [program_id.to_string(), escrow_accounts_config()],
+    });
+    socket.send(WebSocket::OPCODE_TEXT, subscribe.to_string().as_bytes())?;
+
+    // escrows opened before the subscription produce no Created event
+    let mut tracker = EscrowTracker::default();
+    let accounts = rpc.call(
+        "getProgramAccounts",
+        json!([program_id.to_string(), escrow_accounts_config()]),
+    )?;
+    for keyed_account in accounts.as_array().into_iter().flatten() {
+        if let (Some(escrow), Some(state)) = parse_escrow_account(keyed_account)? {
+            tracker.insert(escrow, state);
+        }
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let handle = thread::spawn(move || loop {
+        let event = match socket.receive() {
+            Ok(Some(message)) => handle_message(&message, &mut tracker, &rpc),
+            Ok(None) => return,
+            Err(error) => Err(error),
+        };
+        match event {
+            Ok(Some(event)) => {
+                if sender.send(Ok(event)).is_err() {
+                    return;
+                }
+            }
+            Ok(None) => {}
+            Err(error) => {
+                let _ = sender.send(Err(error));
+                return;
+            }
+        }
+    });
+    Ok((receiver, handle))
+}
+
+fn escrow_accounts_config() -> Value {
+    json!({
+        "encoding": "base64",
+        "commitment": "confirmed",
+        "filters": filters::escrows(),
+    })
+}
+
+fn handle_message(
+    message: &str,
+    tracker: &mut EscrowTracker,
+    rpc: &RpcClient,
+) -> ClientResult<Option<EscrowEvent>> {
+    let message: Value =
+        serde_json::from_str(message).map_err(|error| custom(&error.to_string()))?;
+    if let Some(error) = message.get("error") {
+        return Err(custom(&format!("subscription failed: {}", error)));
+    }
+    let keyed_account = &message["params"]["result"]["value"];
+    match parse_escrow_account(keyed_account)? {
+        (Some(escrow), Some(state)) => Ok(tracker.update(escrow, state)),
+        (Some(escrow), None) => {
+            let fill_amount = closing_fill_amount(rpc, &escrow)?;
+            Ok(tracker.close(escrow, fill_amount))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The address and, unless the account was closed, the escrow of a `{pubkey, account}` value
+fn parse_escrow_account(keyed_account: &Value) -> ClientResult<(Option<Pubkey>, Option<Escrow>)> {
+    let escrow = match keyed_account["pubkey"].as_str() {
+        Some(pubkey) => Pubkey::from_str(pubkey).map_err(|error| custom(&error.to_string()))?,
+        None => return Ok((None, None)),
+    };
+    let data = keyed_account["account"]["data"][0]
+        .as_str()
+        .ok_or_else(|| custom("data"))?;
+    let data = base64::decode(data).map_err(|error| custom(&error.to_string()))?;
+    // closed escrows are zeroed, uninitialized ones are not escrows yet
+    Ok((Some(escrow), Escrow::unpack(&data).ok()))
+}
+
+/// What the fill that closed `escrow` bought, read from the events the closing transaction
+/// logged, `None` if it was not a fill
+fn closing_fill_amount(rpc: &RpcClient, escrow: &Pubkey) -> ClientResult<Option<u64>> {
+    let signatures = rpc.call(
+        "getSignaturesForAddress",
+        json!([escrow.to_string(), { "limit": 1, "commitment": "confirmed" }]),
+    )?;
+    let signature = match signatures[0]["signature"].as_str() {
+        Some(signature) => signature,
+        None => return Ok(None),
+    };
+    let transaction = rpc.call(
+        "getTransaction",
+        json!([signature, { "encoding": "json", "commitment": "confirmed" }]),
+    )?;
+    Ok(transaction["meta"]["logMessages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .filter_map(event::EscrowEvent::from_log)
+        .find_map(|logged| match logged {
+            event::EscrowEvent::Filled {
+                escrow: filled,
+                fill_amount,
+                ..
+            } if filled == *escrow => Some(fill_amount),
+            _ => None,
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escrow_tracker() {
+        let escrow = Pubkey::new_unique();
+        let state = Escrow {
+            is_initialized: true,
+            expected_amount: 100,
+            is_crowdfill: true,
+            ..Escrow::default()
+        };
+        let mut tracker = EscrowTracker::default();
+        assert_eq!(
+            tracker.update(escrow, state),
+            Some(EscrowEvent::Created { escrow, state })
+        );
+        assert_eq!(tracker.update(escrow, state), None);
+
+        let filled = Escrow {
+            filled_amount: 30,
+            ..state
+        };
+        assert_eq!(
+            tracker.update(escrow, filled),
+            Some(EscrowEvent::Filled {
+                escrow,
+                fill_amount: 30,
+                state: Some(filled),
+            })
+        );
+        assert_eq!(
+            tracker.close(escrow, Some(70)),
+            Some(EscrowEvent::Filled {
+                escrow,
+                fill_amount: 70,
+                state: None,
+            })
+        );
+        assert_eq!(tracker.close(escrow, None), None);
+
+        tracker.insert(escrow, state);
+        assert_eq!(
+            tracker.close(escrow, None),
+            Some(EscrowEvent::Cancelled { escrow })
+        );
+    }
+}
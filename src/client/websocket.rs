@@ -0,0 +1,127 @@
+//! Bare bones RFC 6455 client, just enough to hold JSON-RPC subscriptions. It speaks plain
+//! `ws://`, which is what a local test validator serves; public RPC nodes only speak `wss://`,
+//! put a TLS terminating proxy in front of them.
+
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{custom, ClientResult};
+
+pub const DEFAULT_WEBSOCKET_URL: &str = "ws://127.0.0.1:8900";
+
+pub struct WebSocket {
+    stream: TcpStream,
+}
+
+impl WebSocket {
+    pub const OPCODE_CONTINUATION: u8 = 0x0;
+    pub const OPCODE_TEXT: u8 = 0x1;
+    pub const OPCODE_CLOSE: u8 = 0x8;
+    pub const OPCODE_PING: u8 = 0x9;
+    pub const OPCODE_PONG: u8 = 0xa;
+
+    pub fn connect(url: &str) -> ClientResult<Self> {
+        let address = url
+            .strip_prefix("ws://")
+            .ok_or_else(|| custom("only ws:// urls are supported"))?;
+        let (host, path) = match address.find('/') {
+            Some(index) => address.split_at(index),
+            None => (address, "/"),
+        };
+        let mut stream = TcpStream::connect(host)?;
+        // the accept key is not checked, so the key from the RFC's example does fine
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            path, host
+        )?;
+
+        let mut response = Vec::new();
+        let mut byte = [0; 1];
+        while !response.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte)?;
+            response.push(byte[0]);
+        }
+        if !response.starts_with(b"HTTP/1.1 101") {
+            return Err(custom(&format!(
+                "websocket handshake refused: {}",
+                String::from_utf8_lossy(&response)
+            )));
+        }
+        Ok(WebSocket { stream })
+    }
+
+    pub fn send(&mut self, opcode: u8, payload: &[u8]) -> ClientResult<()> {
+        let mut frame = vec![0x80 | opcode];
+        // clients must set the mask bit on every frame
+        match payload.len() {
+            len if len < 126 => frame.push(0x80 | len as u8),
+            len if len <= u16::MAX as usize => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(0x80 | 127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        let mask = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|error| custom(&error.to_string()))?
+            .subsec_nanos()
+            .to_be_bytes();
+        frame.extend_from_slice(&mask);
+        frame.extend(
+            payload
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask[i % 4]),
+        );
+        self.stream.write_all(&frame)?;
+        Ok(())
+    }
+
+    /// next text message, answering pings on the way, `None` once the server closes
+    pub fn receive(&mut self) -> ClientResult<Option<String>> {
+        let mut message = Vec::new();
+        loop {
+            let mut header = [0; 2];
+            self.stream.read_exact(&mut header)?;
+            let fin = header[0] & 0x80 != 0;
+            let opcode = header[0] & 0x0f;
+            let len = match header[1] & 0x7f {
+                126 => {
+                    let mut len = [0; 2];
+                    self.stream.read_exact(&mut len)?;
+                    u16::from_be_bytes(len) as usize
+                }
+                127 => {
+                    let mut len = [0; 8];
+                    self.stream.read_exact(&mut len)?;
+                    u64::from_be_bytes(len) as usize
+                }
+                len => len as usize,
+            };
+            let mut payload = vec![0; len];
+            self.stream.read_exact(&mut payload)?;
+
+            match opcode {
+                Self::OPCODE_TEXT | Self::OPCODE_CONTINUATION => {
+                    message.extend_from_slice(&payload);
+                    if fin {
+                        return String::from_utf8(message)
+                            .map(Some)
+                            .map_err(|error| custom(&error.to_string()));
+                    }
+                }
+                Self::OPCODE_PING => self.send(Self::OPCODE_PONG, &payload)?,
+                Self::OPCODE_CLOSE => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
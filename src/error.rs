@@ -1,7 +1,9 @@
-use solana_program::program_error::ProgramError;
+use num_derive::FromPrimitive;
+use solana_program::{decode_error::DecodeError, program_error::ProgramError};
+use std::convert::{TryFrom, TryInto};
 use thiserror::Error;
 
-#[derive(Debug, Error, Copy, Clone)]
+#[derive(Debug, Error, Copy, Clone, FromPrimitive, PartialEq)]
 pub enum EscrowError {
     #[error("Invalid Instruction")]
     InvalidInstruction,
@@ -11,6 +13,106 @@ pub enum EscrowError {
     ExpectedAmountMismatch,
     #[error("Amount overflow")]
     AmountOverflow,
+    #[error("Invalid oracle account")]
+    InvalidOracle,
+    #[error("Oracle price is stale")]
+    OracleStale,
+    #[error("Oracle confidence interval too wide")]
+    OracleConfidenceTooWide,
+    #[error("Invalid curve parameters")]
+    InvalidCurve,
+    #[error("Not enough pool shares")]
+    InsufficientShares,
+    #[error("Escrow can not be cancelled yet")]
+    CancelLocked,
+    #[error("Escrow does not take reservations")]
+    ReservationsDisabled,
+    #[error("Escrow is reserved by another taker")]
+    EscrowReserved,
+    #[error("Escrow does not take two-phase exchanges")]
+    TwoPhaseDisabled,
+    #[error("Escrow has a pending two-phase exchange")]
+    EscrowCommitted,
+    #[error("Two-phase exchange timed out")]
+    CommitExpired,
+    #[error("Exchange must be a top-level instruction")]
+    CpiExchangeForbidden,
+    #[error("Exchange transaction lacks the required memo")]
+    MemoMissing,
+    #[error("Fee above 10000 basis points")]
+    InvalidFee,
+    #[error("No room left in the config")]
+    ConfigFull,
+    #[error("Mint is not allowed by the mint list")]
+    MintNotAllowed,
+    #[error("No room left in the mint list")]
+    MintListFull,
+    #[error("Escrow has expired")]
+    EscrowExpired,
+    #[error("Escrow has not expired")]
+    EscrowNotExpired,
+    #[error("Escrow is private to another taker")]
+    TakerNotAllowed,
+    #[error("Auction escrows can only be bid on")]
+    AuctionEscrow,
+    #[error("Escrow is not an auction")]
+    NotAuction,
+    #[error("Not allowed in the auction's current phase")]
+    AuctionPhase,
+    #[error("Revealed bid does not match its commitment")]
+    CommitmentMismatch,
+    #[error("Bid below the reserve price or above its deposit")]
+    InvalidBid,
+    #[error("Raffle escrows can only be bought tickets of")]
+    RaffleEscrow,
+    #[error("Escrow is not a raffle")]
+    NotRaffle,
+    #[error("Not allowed in the raffle's current phase")]
+    RafflePhase,
+    #[error("Ticket is not the one drawn")]
+    TicketNotDrawn,
+    #[error("Quote offers less than the request's minimum")]
+    QuoteTooLow,
+    #[error("Escrow can not chain its proceeds")]
+    ChainingUnsupported,
+    #[error("Split shares above 10000 basis points or paid to no account")]
+    InvalidSplit,
+    #[error("Escrow's release condition does not hold")]
+    ConditionNotMet,
+    #[error("Asset changed owner or update authority since the escrow was opened")]
+    AssetChanged,
+    #[error("Intent nonce is not the user's next nonce")]
+    RelayNonceMismatch,
+    #[error("Transaction lacks the user's signed intent")]
+    IntentMissing,
+    #[error("Session key has expired")]
+    SessionExpired,
+    #[error("Session key lacks the permission")]
+    SessionNotPermitted,
+    #[error("Escrow is below the minimum size")]
+    EscrowTooSmall,
+    #[error("Escrow violates an invariant")]
+    InvariantViolated,
+    #[error("Recovery is still timelocked")]
+    RecoveryTimelocked,
+    #[error("Taker neither owns the paying account nor is its delegate for the payment")]
+    PaymentNotAuthorized,
+    #[error("Escrow was opened or repriced after the taker's slot")]
+    EscrowRevised,
+    #[error("Exchange landed after the taker's last slot")]
+    ExchangeTooLate,
+    #[error("Escrow's terms changed since the taker saw them")]
+    TermsChanged,
+    #[error("Escrow can not be taken before its release timestamp")]
+    EscrowNotReleased,
+    #[error("Escrow's release window has closed")]
+    ReleaseClosed,
+    #[error("Market is paused")]
+    MarketPaused,
+    #[error("Fill costs more than the taker's maximum payment")]
+    SlippageExceeded,
+    #[error("Oracle priced escrows are only taken with CappedExchange")]
+    MaxPaymentRequired,
 }
 
 impl From<EscrowError> for ProgramError {
@@ -18,3 +120,60 @@ impl From<EscrowError> for ProgramError {
         ProgramError::Custom(e as u32)
     }
 }
+
+impl<T> DecodeError<T> for EscrowError {
+    fn type_of() -> &'static str {
+        "EscrowError"
+    }
+}
+
+/// The return data of a failed instruction, naming the error and the account it is about so
+/// simulations show more than the error's number
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ErrorDetail {
+    /// the error's code, `ProgramError::from` turns it back into the error
+    pub code: u64,
+    /// index of the offending account in the instruction's accounts, `NO_ACCOUNT` when the
+    /// error is about none of them
+    pub account_index: u8,
+}
+
+impl ErrorDetail {
+    pub const LEN: usize = 9;
+    pub const NO_ACCOUNT: u8 = u8::MAX;
+
+    pub fn new(error: ProgramError, account_index: Option<usize>) -> Self {
+        Self {
+            code: error.into(),
+            account_index: account_index
+                .and_then(|index| u8::try_from(index).ok())
+                .unwrap_or(Self::NO_ACCOUNT),
+        }
+    }
+
+    pub fn pack(&self) -> [u8; Self::LEN] {
+        let mut data = [0; Self::LEN];
+        data[..8].copy_from_slice(&self.code.to_le_bytes());
+        data[8] = self.account_index;
+        data
+    }
+
+    /// `None` unless `data` is exactly a packed `ErrorDetail`
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::LEN {
+            return None;
+        }
+        Some(Self {
+            code: u64::from_le_bytes(data[..8].try_into().ok()?),
+            account_index: data[8],
+        })
+    }
+
+    pub fn error(&self) -> ProgramError {
+        ProgramError::from(self.code)
+    }
+
+    pub fn account_index(&self) -> Option<usize> {
+        Some(self.account_index as usize).filter(|_| self.account_index != Self::NO_ACCOUNT)
+    }
+}
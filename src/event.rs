@@ -0,0 +1,536 @@
+use std::{convert::TryInto, fmt};
+
+use solana_program::{msg, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::error::EscrowError::InvalidInstruction;
+
+pub mod decode;
+
+/// Prefix of the program log lines carrying hex encoded events, followed by the schema version
+pub const EVENT_LOG_PREFIX: &str = "escrow-event:";
+
+/// Version of the event layout written by `pack`, bumped whenever an existing layout changes
+pub const EVENT_SCHEMA_VERSION: u8 = 1;
+
+/// Prefix of the program log lines summarizing a fill in whole tokens, for people rather than
+/// indexers
+pub const SUMMARY_LOG_PREFIX: &str = "escrow-settled:";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EscrowEvent {
+    /// A taker bought `fill_amount` of the deposit for `paid_amount` of the other token
+    Filled {
+        escrow: Pubkey,
+        taker: Pubkey,
+        fill_amount: u64,
+        paid_amount: u64,
+        remaining_amount: u64,
+    },
+    /// The initializer took their deposit back
+    Cancelled {
+        escrow: Pubkey,
+        returned_amount: u64,
+    },
+    /// An escrow was opened asking `expected_amount` of the other token for its deposit
+    Initialized {
+        escrow: Pubkey,
+        initializer: Pubkey,
+        expected_amount: u64,
+    },
+    /// An escrow was opened, with everything needed to list it without fetching any account
+    Opened {
+        escrow: Pubkey,
+        initializer: Pubkey,
+        vault: Pubkey,
+        receive_account: Pubkey,
+        deposit_mint: Pubkey,
+        receive_mint: Pubkey,
+        deposit_amount: u64,
+        expected_amount: u64,
+        /// 0 if the escrow never expires
+        expires_at_slot: u64,
+    },
+    /// The admin proposed returning the escrow's deposit to its initializer, executable from the
+    /// unix timestamp `executable_at`
+    RecoveryProposed {
+        escrow: Pubkey,
+        admin: Pubkey,
+        executable_at: i64,
+    },
+    /// The admin withdrew a proposed recovery
+    RecoveryCancelled { escrow: Pubkey },
+    /// The admin returned the escrow's deposit to its initializer and closed the escrow
+    RecoveryExecuted {
+        escrow: Pubkey,
+        returned_amount: u64,
+    },
+}
+
+/// The event's name and escrow on the first line, then one labeled field per line with amounts
+/// in base units, events not knowing the decimals of their mints
+impl fmt::Display for EscrowEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (name, escrow, fields): (_, _, Vec<(_, String)>) = match self {
+            Self::Filled {
+                escrow,
+                taker,
+                fill_amount,
+                paid_amount,
+                remaining_amount,
+            } => (
+                "Filled",
+                escrow,
+                vec![
+                    ("taker", taker.to_string()),
+                    ("fill amount", fill_amount.to_string()),
+                    ("paid amount", paid_amount.to_string()),
+                    ("remaining amount", remaining_amount.to_string()),
+                ],
+            ),
+            Self::Cancelled {
+                escrow,
+                returned_amount,
+            } => (
+                "Cancelled",
+                escrow,
+                vec![("returned amount", returned_amount.to_string())],
+            ),
+            Self::Initialized {
+                escrow,
+                initializer,
+                expected_amount,
+            } => (
+                "Initialized",
+                escrow,
+                vec![
+                    ("initializer", initializer.to_string()),
+                    ("expected amount", expected_amount.to_string()),
+                ],
+            ),
+            Self::Opened {
+                escrow,
+                initializer,
+                vault,
+                receive_account,
+                deposit_mint,
+                receive_mint,
+                deposit_amount,
+                expected_amount,
+                expires_at_slot,
+            } => (
+                "Opened",
+                escrow,
+                vec![
+                    ("initializer", initializer.to_string()),
+                    ("vault", vault.to_string()),
+                    ("receive account", receive_account.to_string()),
+                    ("deposit", format!("{} of {}", deposit_amount, deposit_mint)),
+                    ("asks", format!("{} of {}", expected_amount, receive_mint)),
+                    (
+                        "expires at slot",
+                        match expires_at_slot {
+                            0 => "never".to_string(),
+                            slot => slot.to_string(),
+                        },
+                    ),
+                ],
+            ),
+            Self::RecoveryProposed {
+                escrow,
+                admin,
+                executable_at,
+            } => (
+                "RecoveryProposed",
+                escrow,
+                vec![
+                    ("admin", admin.to_string()),
+                    ("executable at", format!("{} (unix time)", executable_at)),
+                ],
+            ),
+            Self::RecoveryCancelled { escrow } => ("RecoveryCancelled", escrow, vec![]),
+            Self::RecoveryExecuted {
+                escrow,
+                returned_amount,
+            } => (
+                "RecoveryExecuted",
+                escrow,
+                vec![("returned amount", returned_amount.to_string())],
+            ),
+        };
+        write!(f, "{} escrow {}", name, escrow)?;
+        for (label, value) in fields {
+            write!(f, "\n  {}: {}", label, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl EscrowEvent {
+    pub fn emit(&self) {
+        msg!(
+            "{}{}:{}",
+            EVENT_LOG_PREFIX,
+            EVENT_SCHEMA_VERSION,
+            hex::encode(self.pack())
+        );
+    }
+
+    /// decodes the event out of a program log line, `None` if the line is not an event or can
+    /// not be decoded, see [`decode::decode_log`] to tell those apart
+    pub fn from_log(log: &str) -> Option<Self> {
+        decode::decode_log(log)?.ok()
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(217);
+        match self {
+            Self::Filled {
+                escrow,
+                taker,
+                fill_amount,
+                paid_amount,
+                remaining_amount,
+            } => {
+                buf.push(0);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(taker.as_ref());
+                buf.extend_from_slice(&fill_amount.to_le_bytes());
+                buf.extend_from_slice(&paid_amount.to_le_bytes());
+                buf.extend_from_slice(&remaining_amount.to_le_bytes());
+            }
+            Self::Cancelled {
+                escrow,
+                returned_amount,
+            } => {
+                buf.push(1);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(&returned_amount.to_le_bytes());
+            }
+            Self::Initialized {
+                escrow,
+                initializer,
+                expected_amount,
+            } => {
+                buf.push(2);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(initializer.as_ref());
+                buf.extend_from_slice(&expected_amount.to_le_bytes());
+            }
+            Self::Opened {
+                escrow,
+                initializer,
+                vault,
+                receive_account,
+                deposit_mint,
+                receive_mint,
+                deposit_amount,
+                expected_amount,
+                expires_at_slot,
+            } => {
+                buf.push(3);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(initializer.as_ref());
+                buf.extend_from_slice(vault.as_ref());
+                buf.extend_from_slice(receive_account.as_ref());
+                buf.extend_from_slice(deposit_mint.as_ref());
+                buf.extend_from_slice(receive_mint.as_ref());
+                buf.extend_from_slice(&deposit_amount.to_le_bytes());
+                buf.extend_from_slice(&expected_amount.to_le_bytes());
+                buf.extend_from_slice(&expires_at_slot.to_le_bytes());
+            }
+            Self::RecoveryProposed {
+                escrow,
+                admin,
+                executable_at,
+            } => {
+                buf.push(4);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(admin.as_ref());
+                buf.extend_from_slice(&executable_at.to_le_bytes());
+            }
+            Self::RecoveryCancelled { escrow } => {
+                buf.push(5);
+                buf.extend_from_slice(escrow.as_ref());
+            }
+            Self::RecoveryExecuted {
+                escrow,
+                returned_amount,
+            } => {
+                buf.push(6);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(&returned_amount.to_le_bytes());
+            }
+        }
+        buf
+    }
+
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
+        Ok(match tag {
+            0 => Self::Filled {
+                escrow: unpack_pubkey(rest, 0)?,
+                taker: unpack_pubkey(rest, 32)?,
+                fill_amount: unpack_u64(rest, 64)?,
+                paid_amount: unpack_u64(rest, 72)?,
+                remaining_amount: unpack_u64(rest, 80)?,
+            },
+            1 => Self::Cancelled {
+                escrow: unpack_pubkey(rest, 0)?,
+                returned_amount: unpack_u64(rest, 32)?,
+            },
+            2 => Self::Initialized {
+                escrow: unpack_pubkey(rest, 0)?,
+                initializer: unpack_pubkey(rest, 32)?,
+                expected_amount: unpack_u64(rest, 64)?,
+            },
+            3 => Self::Opened {
+                escrow: unpack_pubkey(rest, 0)?,
+                initializer: unpack_pubkey(rest, 32)?,
+                vault: unpack_pubkey(rest, 64)?,
+                receive_account: unpack_pubkey(rest, 96)?,
+                deposit_mint: unpack_pubkey(rest, 128)?,
+                receive_mint: unpack_pubkey(rest, 160)?,
+                deposit_amount: unpack_u64(rest, 192)?,
+                expected_amount: unpack_u64(rest, 200)?,
+                expires_at_slot: unpack_u64(rest, 208)?,
+            },
+            4 => Self::RecoveryProposed {
+                escrow: unpack_pubkey(rest, 0)?,
+                admin: unpack_pubkey(rest, 32)?,
+                executable_at: unpack_u64(rest, 64)? as i64,
+            },
+            5 => Self::RecoveryCancelled {
+                escrow: unpack_pubkey(rest, 0)?,
+            },
+            6 => Self::RecoveryExecuted {
+                escrow: unpack_pubkey(rest, 0)?,
+                returned_amount: unpack_u64(rest, 32)?,
+            },
+            _ => return Err(InvalidInstruction.into()),
+        })
+    }
+}
+
+/// An amount of a token shown in whole tokens, followed by the mint's symbol or else its address
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenAmount {
+    pub amount: u64,
+    pub decimals: u8,
+    pub mint: Pubkey,
+    pub symbol: Option<String>,
+}
+
+/// `amount` base units of a mint with `decimals` in whole tokens, without trailing zeros
+pub fn ui_amount(amount: u64, decimals: u8) -> String {
+    let digits = amount.to_string();
+    let decimals = decimals as usize;
+    let (whole, fraction) = if digits.len() > decimals {
+        let (whole, fraction) = digits.split_at(digits.len() - decimals);
+        (whole.to_string(), fraction.to_string())
+    } else {
+        (
+            "0".to_string(),
+            format!("{:0>width$}", digits, width = decimals),
+        )
+    };
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", ui_amount(self.amount, self.decimals))?;
+        match &self.symbol {
+            Some(symbol) => write!(f, " {}", symbol),
+            None => write!(f, " {}", self.mint),
+        }
+    }
+}
+
+/// A fill as in "sold 1.5 USDC for 10 BONK", from the initializer's side
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SettlementSummary {
+    pub sold: TokenAmount,
+    pub paid: TokenAmount,
+}
+
+impl SettlementSummary {
+    pub fn emit(&self) {
+        msg!("{} {}", SUMMARY_LOG_PREFIX, self);
+    }
+}
+
+impl fmt::Display for SettlementSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "sold {} for {}", self.sold, self.paid)
+    }
+}
+
+fn unpack_pubkey(input: &[u8], offset: usize) -> Result<Pubkey, ProgramError> {
+    input
+        .get(offset..offset + 32)
+        .map(Pubkey::new)
+        .ok_or_else(|| InvalidInstruction.into())
+}
+
+fn unpack_u64(input: &[u8], offset: usize) -> Result<u64, ProgramError> {
+    input
+        .get(offset..offset + 8)
+        .and_then(|slice| slice.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or_else(|| InvalidInstruction.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack_event() {
+        let event = EscrowEvent::Filled {
+            escrow: Pubkey::new_unique(),
+            taker: Pubkey::new_unique(),
+            fill_amount: 40,
+            paid_amount: 60,
+            remaining_amount: 60,
+        };
+        assert_eq!(EscrowEvent::unpack(&event.pack()).unwrap(), event);
+
+        let log = format!(
+            "Program log: {}{}:{}",
+            EVENT_LOG_PREFIX,
+            EVENT_SCHEMA_VERSION,
+            hex::encode(event.pack())
+        );
+        assert_eq!(EscrowEvent::from_log(&log), Some(event));
+        assert_eq!(
+            EscrowEvent::from_log("Program log: Instruction: Exchange"),
+            None
+        );
+        assert!(EscrowEvent::unpack(&event.pack()[..40]).is_err());
+
+        let event = EscrowEvent::Cancelled {
+            escrow: Pubkey::new_unique(),
+            returned_amount: 100,
+        };
+        assert_eq!(EscrowEvent::unpack(&event.pack()).unwrap(), event);
+
+        let event = EscrowEvent::Initialized {
+            escrow: Pubkey::new_unique(),
+            initializer: Pubkey::new_unique(),
+            expected_amount: 150,
+        };
+        assert_eq!(EscrowEvent::unpack(&event.pack()).unwrap(), event);
+
+        let event = EscrowEvent::Opened {
+            escrow: Pubkey::new_unique(),
+            initializer: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            receive_account: Pubkey::new_unique(),
+            deposit_mint: Pubkey::new_unique(),
+            receive_mint: Pubkey::new_unique(),
+            deposit_amount: 100,
+            expected_amount: 150,
+            expires_at_slot: 1_000,
+        };
+        assert_eq!(event.pack().len(), 217);
+        assert_eq!(EscrowEvent::unpack(&event.pack()).unwrap(), event);
+        assert!(EscrowEvent::unpack(&event.pack()[..216]).is_err());
+
+        let escrow = Pubkey::new_unique();
+        for event in [
+            EscrowEvent::RecoveryProposed {
+                escrow,
+                admin: Pubkey::new_unique(),
+                executable_at: 1_000,
+            },
+            EscrowEvent::RecoveryCancelled { escrow },
+            EscrowEvent::RecoveryExecuted {
+                escrow,
+                returned_amount: 100,
+            },
+        ]
+        .iter()
+        {
+            assert_eq!(EscrowEvent::unpack(&event.pack()).unwrap(), *event);
+        }
+    }
+
+    #[test]
+    fn test_display_event() {
+        let escrow = Pubkey::new_unique();
+        let taker = Pubkey::new_unique();
+        let event = EscrowEvent::Filled {
+            escrow,
+            taker,
+            fill_amount: 40,
+            paid_amount: 60,
+            remaining_amount: 60,
+        };
+        assert_eq!(
+            event.to_string(),
+            format!(
+                "Filled escrow {}\n  taker: {}\n  fill amount: 40\n  paid amount: 60\n  \
+                 remaining amount: 60",
+                escrow, taker
+            )
+        );
+        assert_eq!(
+            EscrowEvent::RecoveryCancelled { escrow }.to_string(),
+            format!("RecoveryCancelled escrow {}", escrow)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_event() {
+        let event = EscrowEvent::Cancelled {
+            escrow: Pubkey::new_unique(),
+            returned_amount: 100,
+        };
+        let json = serde_json::to_value(event).unwrap();
+        assert_eq!(json["Cancelled"]["returned_amount"], 100);
+        assert_eq!(serde_json::from_value::<EscrowEvent>(json).unwrap(), event);
+    }
+
+    #[test]
+    fn test_settlement_summary() {
+        let mint = Pubkey::new_unique();
+        let summary = SettlementSummary {
+            sold: TokenAmount {
+                amount: 1_500_000,
+                decimals: 6,
+                mint: Pubkey::new_unique(),
+                symbol: Some("USDC".to_string()),
+            },
+            paid: TokenAmount {
+                amount: 1_000,
+                decimals: 2,
+                mint: Pubkey::new_unique(),
+                symbol: Some("BONK".to_string()),
+            },
+        };
+        assert_eq!(summary.to_string(), "sold 1.5 USDC for 10 BONK");
+
+        let amount = |amount, decimals| TokenAmount {
+            amount,
+            decimals,
+            mint,
+            symbol: None,
+        };
+        assert_eq!(amount(5, 3).to_string(), format!("0.005 {}", mint));
+        assert_eq!(amount(0, 9).to_string(), format!("0 {}", mint));
+        assert_eq!(amount(1, 0).to_string(), format!("1 {}", mint));
+        assert_eq!(
+            amount(u64::MAX, 255).to_string(),
+            format!("0.{:0>255} {}", u64::MAX, mint)
+        );
+    }
+}
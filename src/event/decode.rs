@@ -0,0 +1,72 @@
+//! Decodes event log lines written by any version of the program
+
+use super::{EscrowEvent, EVENT_LOG_PREFIX};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DecodeError {
+    /// written by a newer program than this decoder knows about
+    UnsupportedVersion(u8),
+    /// not valid hex, or not an event of the version it claims
+    Malformed,
+}
+
+/// Decodes the event of a program log line, `None` if the line is not an event
+pub fn decode_log(log: &str) -> Option<Result<EscrowEvent, DecodeError>> {
+    let encoded = log
+        .strip_prefix("Program log: ")
+        .unwrap_or(log)
+        .strip_prefix(EVENT_LOG_PREFIX)?;
+    // the first program logged events without a version
+    let (version, encoded) = match encoded.find(':') {
+        Some(index) => match encoded[..index].parse() {
+            Ok(version) => (version, &encoded[index + 1..]),
+            Err(_) => return Some(Err(DecodeError::Malformed)),
+        },
+        None => (0, encoded),
+    };
+    Some(match hex::decode(encoded) {
+        Ok(data) => decode(version, &data),
+        Err(_) => Err(DecodeError::Malformed),
+    })
+}
+
+/// Decodes an event packed with the layout of schema `version`
+pub fn decode(version: u8, data: &[u8]) -> Result<EscrowEvent, DecodeError> {
+    match version {
+        // both share the current layout
+        0 | 1 => EscrowEvent::unpack(data).map_err(|_| DecodeError::Malformed),
+        _ => Err(DecodeError::UnsupportedVersion(version)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::EVENT_SCHEMA_VERSION;
+    use solana_program::pubkey::Pubkey;
+
+    #[test]
+    fn test_decode_log_versions() {
+        let event = EscrowEvent::Cancelled {
+            escrow: Pubkey::new_unique(),
+            returned_amount: 100,
+        };
+        let encoded = hex::encode(event.pack());
+
+        let current = format!("{}{}:{}", EVENT_LOG_PREFIX, EVENT_SCHEMA_VERSION, encoded);
+        assert_eq!(decode_log(&current), Some(Ok(event)));
+        let unversioned = format!("Program log: {}{}", EVENT_LOG_PREFIX, encoded);
+        assert_eq!(decode_log(&unversioned), Some(Ok(event)));
+
+        let newer = format!("{}{}:{}", EVENT_LOG_PREFIX, 200, encoded);
+        assert_eq!(
+            decode_log(&newer),
+            Some(Err(DecodeError::UnsupportedVersion(200)))
+        );
+        let garbled = format!("{}1:zz", EVENT_LOG_PREFIX);
+        assert_eq!(decode_log(&garbled), Some(Err(DecodeError::Malformed)));
+        let truncated = format!("{}1:{}", EVENT_LOG_PREFIX, &encoded[..20]);
+        assert_eq!(decode_log(&truncated), Some(Err(DecodeError::Malformed)));
+        assert_eq!(decode_log("Program log: Instruction: Cancel"), None);
+    }
+}
@@ -0,0 +1,158 @@
+//! `arbitrary::Arbitrary` for escrows and instructions behind the `fuzz` feature, so fuzz targets
+//! and property tests start from well-formed inputs instead of random bytes
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use solana_program::pubkey::Pubkey;
+
+use crate::{
+    instruction::EscrowInstruction,
+    state::{
+        Escrow, MetadataUri, ReleaseCondition, SplitRecipient, CONDITION_VALUE_LEN,
+        MAX_SPLIT_RECIPIENTS,
+    },
+};
+
+/// Highest instruction tag `EscrowInstruction::unpack` knows
+pub const LAST_TAG: u8 = 68;
+
+/// Data of variable length instructions is drawn up to this many bytes
+pub const MAX_VARIABLE_DATA_LEN: usize = 512;
+
+/// Instruction data with a known tag followed by as many bytes as the tag takes, or now and then
+/// any bytes at all so the unpacking itself stays covered
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstructionData(pub Vec<u8>);
+
+impl<'a> Arbitrary<'a> for InstructionData {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        if u.ratio(1, 8)? {
+            return Ok(Self(u.arbitrary()?));
+        }
+        let tag = u.int_in_range(0..=LAST_TAG)?;
+        let len = match EscrowInstruction::fixed_data_len(tag) {
+            Some(len) => len,
+            None => u.int_in_range(0..=MAX_VARIABLE_DATA_LEN)?,
+        };
+        let mut data = vec![tag];
+        data.extend_from_slice(u.bytes(len)?);
+        Ok(Self(data))
+    }
+}
+
+impl<'a> Arbitrary<'a> for EscrowInstruction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let InstructionData(data) = u.arbitrary()?;
+        EscrowInstruction::unpack(&data).map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
+/// Unset half of the time, as most of an escrow's optional keys are
+fn pubkey(u: &mut Unstructured) -> Result<Pubkey> {
+    Ok(if u.arbitrary()? {
+        Pubkey::default()
+    } else {
+        Pubkey::new_from_array(u.arbitrary()?)
+    })
+}
+
+impl<'a> Arbitrary<'a> for Escrow {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let uri: &str = u.arbitrary()?;
+        let metadata_uri = MetadataUri::new(uri)
+            .filter(|_| !uri.contains('\0'))
+            .unwrap_or_default();
+        let mut split_recipients = [SplitRecipient::default(); MAX_SPLIT_RECIPIENTS];
+        for recipient in split_recipients.iter_mut() {
+            *recipient = SplitRecipient {
+                token_account: pubkey(u)?,
+                share_bps: u.arbitrary()?,
+            };
+        }
+        Ok(Escrow {
+            is_initialized: u.arbitrary()?,
+            initializer_pubkey: pubkey(u)?,
+            temp_token_account_pubkey: pubkey(u)?,
+            initializer_token_to_receive_account_pubkey: pubkey(u)?,
+            expected_amount: u.arbitrary()?,
+            oracle_pubkey: pubkey(u)?,
+            oracle_max_staleness_slots: u.arbitrary()?,
+            oracle_max_confidence_bps: u.arbitrary()?,
+            deposit_mint_decimals: u.arbitrary()?,
+            receive_mint_decimals: u.arbitrary()?,
+            curve_start_price: u.arbitrary()?,
+            curve_slope: u.arbitrary()?,
+            filled_amount: u.arbitrary()?,
+            is_crowdfill: u.arbitrary()?,
+            cancel_locked_until_slot: u.arbitrary()?,
+            reserve_bond_lamports: u.arbitrary()?,
+            reserve_slots: u.arbitrary()?,
+            reserved_by: pubkey(u)?,
+            reserved_until_slot: u.arbitrary()?,
+            settle_timeout_slots: u.arbitrary()?,
+            committed_taker: pubkey(u)?,
+            committed_until_slot: u.arbitrary()?,
+            payment_vault_pubkey: pubkey(u)?,
+            committed_taker_token_to_receive_pubkey: pubkey(u)?,
+            exchange_top_level_only: u.arbitrary()?,
+            metadata_uri,
+            memo_reference: u.arbitrary()?,
+            expires_at_slot: u.arbitrary()?,
+            allowed_taker: pubkey(u)?,
+            relist_amount: u.arbitrary()?,
+            relist_source_pubkey: pubkey(u)?,
+            auction_bid_end_slot: u.arbitrary()?,
+            auction_reveal_end_slot: u.arbitrary()?,
+            best_bid_amount: u.arbitrary()?,
+            best_bid_pubkey: pubkey(u)?,
+            raffle_end_slot: u.arbitrary()?,
+            raffle_ticket_count: u.arbitrary()?,
+            chain_expected_amount: u.arbitrary()?,
+            chain_receive_pubkey: pubkey(u)?,
+            deposited_amount: u.arbitrary()?,
+            receive_mint: pubkey(u)?,
+            chain_receive_mint: pubkey(u)?,
+            operator: pubkey(u)?,
+            split_recipients,
+            release_condition: ReleaseCondition {
+                account: pubkey(u)?,
+                owner: pubkey(u)?,
+                offset: u.arbitrary()?,
+                value_len: u.int_in_range(0..=CONDITION_VALUE_LEN as u8)?,
+                value: u.arbitrary()?,
+                gates_cancel: u.arbitrary()?,
+            },
+            revised_at_slot: u.arbitrary()?,
+            terms_hash: u.arbitrary()?,
+            release_after_timestamp: u.arbitrary()?,
+            release_before_timestamp: u.arbitrary()?,
+            market: pubkey(u)?,
+            tag: u.arbitrary()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::program_pack::Pack;
+
+    #[test]
+    fn test_arbitrary_inputs() {
+        // fixed length tags up to the last one unpack from zeroes of their length
+        for tag in 0..=LAST_TAG {
+            if let Some(len) = EscrowInstruction::fixed_data_len(tag) {
+                let mut data = vec![0; len + 1];
+                data[0] = tag;
+                assert!(EscrowInstruction::unpack(&data).is_ok(), "tag {}", tag);
+            }
+        }
+        assert!(EscrowInstruction::unpack(&[LAST_TAG + 1]).is_err());
+
+        let bytes: Vec<u8> = (0..4096u32).map(|i| (i * 7919 % 251) as u8).collect();
+        let mut u = Unstructured::new(&bytes);
+        let escrow = Escrow::arbitrary(&mut u).unwrap();
+        let mut packed = vec![0; Escrow::LEN];
+        Escrow::pack(escrow, &mut packed).unwrap();
+        assert_eq!(Escrow::unpack_unchecked(&packed).unwrap(), escrow);
+    }
+}
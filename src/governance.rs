@@ -0,0 +1,56 @@
+//! Proposal accounts of the SPL Governance program, whose crate is not a dependency
+
+use solana_program::pubkey::Pubkey;
+
+use crate::state::{ReleaseCondition, CONDITION_VALUE_LEN};
+
+// the mainnet deployment, DAOs running their own instance pass its id instead
+solana_program::declare_id!("GovER5Lthms3bLBqWub97yVrMmEogzX7xNjdXpPPCVZw");
+
+/// Offset of the state in both proposal layouts, after the account type, governance and
+/// governing token mint
+pub const PROPOSAL_STATE_OFFSET: u16 = 65;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+pub enum ProposalState {
+    Draft,
+    SigningOff,
+    Voting,
+    Succeeded,
+    Executing,
+    Completed,
+    Cancelled,
+    Defeated,
+    ExecutingWithErrors,
+    Vetoed,
+}
+
+/// Condition holding while `proposal`, owned by `governance_program`, is in `state`, gating
+/// Cancel rather than Exchange when `gates_cancel`
+pub fn proposal_condition(
+    proposal: &Pubkey,
+    governance_program: &Pubkey,
+    state: ProposalState,
+    gates_cancel: bool,
+) -> ReleaseCondition {
+    let mut value = [0; CONDITION_VALUE_LEN];
+    value[0] = state as u8;
+    ReleaseCondition {
+        account: *proposal,
+        owner: *governance_program,
+        offset: PROPOSAL_STATE_OFFSET,
+        value_len: 1,
+        value,
+        gates_cancel,
+    }
+}
+
+/// Native treasury of `governance`, which signs the instructions of the governance's executed
+/// proposals alongside the governance account itself, so either can be named the config's admin
+pub fn native_treasury_address(governance: &Pubkey, governance_program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"native-treasury", governance.as_ref()],
+        governance_program,
+    )
+}
@@ -1,8 +1,71 @@
 use std::convert::TryInto;
 
-use solana_program::{msg, program_error::ProgramError};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar,
+};
 
-use crate::error::EscrowError::InvalidInstruction;
+use crate::{
+    bubblegum::Leaf,
+    error::EscrowError::InvalidInstruction,
+    math,
+    memo::MAX_SETTLEMENT_MEMO_LEN,
+    state::{
+        Config, Escrow, MetadataUri, MintList, MintVolume, ReleaseCondition, Session,
+        SplitRecipient, CONDITION_VALUE_LEN, MAX_SPLIT_RECIPIENTS, MEMO_REFERENCE_LEN,
+    },
+};
+
+/// Optional terms trailing the InitEscrow amount in field order, each zero when absent
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EscrowTerms {
+    /// How many slots old the oracle price may be at fill time
+    pub oracle_max_staleness_slots: u64,
+    /// Widest oracle confidence interval accepted at fill time, in basis points of the price
+    pub oracle_max_confidence_bps: u16,
+    /// The initializer commits to not cancelling before this slot
+    pub cancel_locked_until_slot: u64,
+    /// Lamports a taker posts to reserve the escrow, forfeited if they do not fill in time
+    pub reserve_bond_lamports: u64,
+    /// How many slots a reservation lasts, zero disables reservations
+    pub reserve_slots: u64,
+    /// How many slots a committed two-phase exchange may wait to be settled, zero disables it
+    pub settle_timeout_slots: u64,
+    /// Refuse Exchanges made through a cross-program invocation
+    pub exchange_top_level_only: bool,
+    /// Zero padded URI of off-chain JSON describing the offer
+    pub metadata_uri: MetadataUri,
+    /// Zero padded string an SPL Memo of the Exchange transaction must contain, all zero when
+    /// no memo is required
+    pub memo_reference: [u8; MEMO_REFERENCE_LEN],
+    /// The escrow can not be taken from this slot on, zero when it never expires
+    pub expires_at_slot: u64,
+    /// The only taker allowed to take the escrow, `Pubkey::default()` for anyone
+    pub allowed_taker: Pubkey,
+    /// Deposit to list the escrow again with once it is fully filled, zero to close it instead
+    pub relist_amount: u64,
+    /// The initializer's token account of the deposited mint that approved the PDA as delegate
+    /// for the relist deposits
+    pub relist_source_pubkey: Pubkey,
+    /// Token accounts paid a share of every payment ahead of the initializer, entries with a
+    /// zero share are unused
+    pub split_recipients: [SplitRecipient; MAX_SPLIT_RECIPIENTS],
+    /// State of another account the escrow can only be taken in, following a full list of
+    /// split recipients
+    pub release_condition: ReleaseCondition,
+    /// Unix timestamp the escrow can be taken from, following a full release condition
+    pub release_after_timestamp: i64,
+    /// Unix timestamp the escrow can not be taken from, zero when its release never closes
+    pub release_before_timestamp: i64,
+    /// Key the escrow's market is seeded by, whose mint list InitEscrow checks and whose config
+    /// Exchange charges fees by, following the release window
+    pub market: Pubkey,
+    /// Label for clients to filter the escrow by, following the market
+    pub tag: u64,
+}
 
 pub enum EscrowInstruction {
     ///
@@ -15,18 +78,29 @@ pub enum EscrowInstruction {
     /// 3. `[writable]` The escrow account, it will hold all necessary info about the trade.
     /// 4. `[]` The rent sysvar
     /// 5. `[]` The token program
+    /// 6. `[]` The mint list account of the market named in the terms, the PDA of `["mint_list"]`
+    ///    for the program-wide market
+    /// 7. `[]` (optional) The clock sysvar, dating the escrow for GuardedExchange
+    /// 8. `[]` (optional) The oracle price account, makes the escrow oracle priced
+    /// 9. `[]` (oracle priced only) The mint of the deposited token
+    /// 10. `[]` (oracle priced only) The mint of the token to receive
     InitEscrow {
-        /// The amount party A expects to receive of token Y
+        /// The amount party A expects to receive of token Y, the minimum price when oracle priced
         amount: u64,
+        terms: Box<EscrowTerms>,
     },
     ///
     /// Accepts a trade
     ///
+    /// The taker can be a PDA of another program signing through `invoke_signed`, filling the
+    /// escrow from token accounts that PDA owns. Top-level only escrows refuse such takers.
+    /// Oracle priced escrows refuse it too, they are only taken with CappedExchange.
+    ///
     ///
     /// Accounts expected:
     ///
-    /// 0. `[signer]` The account of the person taking the trade
-    /// 1. `[writable]` The taker's token account for the token they send
+    /// 0. `[signer, writable]` The account of the person taking the trade, receiving a rebate if the trade closes the escrow
+    /// 1. `[writable]` The taker's token account for the token they send, owned by the taker or delegated to them for the payment
     /// 2. `[writable]` The taker's token account for the token they will receive should the trade go through
     /// 3. `[writable]` The PDA's temp token account to get tokens from and eventually close
     /// 4. `[writable]` The initializer's main account to send their rent fees to
@@ -34,29 +108,1324 @@ pub enum EscrowInstruction {
     /// 6. `[writable]` The escrow account holding the escrow info
     /// 7. `[]` The token program
     /// 8. `[]` The PDA account
+    /// 9. `[]` (oracle priced, reservable, expiring, chained or timelocked only) The clock sysvar
+    /// 10. `[]` (oracle priced only) The oracle price account
+    /// 11. `[]` (top-level only or memo requiring escrows) The instructions sysvar, following whichever of the optional accounts above are present
+    /// 12. `[]` The config account of the escrow's market, the PDA of `["config"]` for the program-wide market, following the optional accounts above
+    /// 13. `[writable]` (once the config exists) The fee owner's token account for the token the taker sends, or with a lamport fee the fee owner and the system program
+    /// 14. `[writable]` The volume account of the deposited mint, the PDA of `["mint_volume", mint]`
+    /// 15. `[writable]` (relisting escrows only) The initializer's relist source token account
+    /// 16. `[writable]` (splitting escrows only) The token account of each split recipient in use, in order
+    /// 17. `[]` (escrows with a condition gating Exchange only) The account the release condition reads
+    /// 18. `[]` (optional) The deposited mint, to log the fill in whole tokens
+    /// 19. `[]` (with the deposited mint) The mint the taker sends
+    /// 20. `[]` (optional) The Metaplex metadata account of the deposited mint, to log its symbol
+    /// 21. `[]` (optional) The Metaplex metadata account of the mint the taker sends
+    /// 22. `[]` (with a memo) The SPL Memo program, always the last account
     Exchange {
         /// the amount the taker expects to be paid in the other token, as a u64 because that's the max possible supply of a token
+        /// for curve priced and crowdfill escrows this is the slice of the deposit the taker buys
+        amount: u64,
+        /// UTF-8 text trailing the amount, at most `memo::MAX_SETTLEMENT_MEMO_LEN` bytes, that
+        /// the settlement passes on to the SPL Memo program
+        memo: Option<String>,
+    },
+    ///
+    /// Starts a trade priced along a linear curve, which takers fill in slices
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person initializing the escrow
+    /// 1. `[writable]` Temporary token account that should be created prior to this instruction and owned by the initializer
+    /// 2. `[]` The initializer's token account for the token they will receive as the escrow is filled
+    /// 3. `[writable]` The escrow account, it will hold all necessary info about the trade.
+    /// 4. `[]` The rent sysvar
+    /// 5. `[]` The token program
+    InitCurveEscrow {
+        /// Price of the first deposited base unit, scaled by `math::CURVE_PRICE_SCALE`
+        start_price: u64,
+        /// Price increase per deposited base unit sold, scaled by `math::CURVE_PRICE_SCALE`
+        slope: u64,
+    },
+    ///
+    /// Starts a fixed price trade that many takers can each fill a slice of
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person initializing the escrow
+    /// 1. `[writable]` Temporary token account that should be created prior to this instruction and owned by the initializer
+    /// 2. `[]` The initializer's token account for the token they will receive as the escrow is filled
+    /// 3. `[writable]` The escrow account, it will hold all necessary info about the trade.
+    /// 4. `[]` The rent sysvar
+    /// 5. `[]` The token program
+    InitCrowdfillEscrow {
+        /// The amount party A expects to receive of token Y for the whole deposit
+        amount: u64,
+    },
+    ///
+    /// Creates the shared pool of a mint pair at a common price
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The account paying for the pool account
+    /// 1. `[writable]` The pool account, the PDA of
+    ///    `["pool", deposit mint, receive mint, price, price exponent]`
+    /// 2. `[]` The token account holding the deposits, owned by the pool
+    /// 3. `[]` The token account collecting the proceeds, owned by the pool
+    /// 4. `[]` The rent sysvar
+    /// 5. `[]` The system program
+    InitPool {
+        /// Mantissa of the price in receive base units per deposited base unit
+        price: u64,
+        /// Exponent of the price, `math::CURVE_PRICE_EXPO` when absent from the data
+        price_expo: i32,
+    },
+    ///
+    /// Adds tokens to a pool in exchange for shares
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The depositor, paying for their share account
+    /// 1. `[writable]` The depositor's token account to deposit from
+    /// 2. `[writable]` The pool account
+    /// 3. `[writable]` The pool's deposit vault
+    /// 4. `[writable]` The depositor's share account, the PDA of `["pool-share", pool, depositor]`
+    /// 5. `[]` The rent sysvar
+    /// 6. `[]` The system program
+    /// 7. `[]` The token program
+    PoolDeposit {
+        /// The amount of the deposit mint to add
+        amount: u64,
+    },
+    ///
+    /// Burns shares for their pro-rata part of the remaining deposits and of the proceeds
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The depositor, receiving the share account rent once emptied
+    /// 1. `[writable]` The depositor's token account for the deposit mint
+    /// 2. `[writable]` The depositor's token account for the receive mint
+    /// 3. `[writable]` The pool account
+    /// 4. `[writable]` The pool's deposit vault
+    /// 5. `[writable]` The pool's proceeds vault
+    /// 6. `[writable]` The depositor's share account
+    /// 7. `[]` The token program
+    PoolWithdraw {
+        /// The number of shares to burn
+        shares: u64,
+    },
+    ///
+    /// Buys deposited tokens from a pool at its price
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person taking the trade
+    /// 1. `[writable]` The taker's token account for the token they send, owned by the taker or delegated to them for the payment
+    /// 2. `[writable]` The taker's token account for the token they will receive
+    /// 3. `[]` The pool account
+    /// 4. `[writable]` The pool's deposit vault
+    /// 5. `[writable]` The pool's proceeds vault
+    /// 6. `[]` The token program
+    PoolExchange {
+        /// The amount of the deposit mint the taker buys
+        amount: u64,
+    },
+    ///
+    /// Cancels a trade, returning the deposit to the initializer
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer of the escrow, receiving the rent of the closed accounts
+    /// 1. `[writable]` The PDA's temp token account to return tokens from and close
+    /// 2. `[writable]` The initializer's token account the deposit is returned to
+    /// 3. `[writable]` The escrow account holding the escrow info
+    /// 4. `[]` The token program
+    /// 5. `[]` The PDA account
+    /// 6. `[]` The clock sysvar
+    /// 7. `[writable]` (chained escrows) The proceeds vault, handed back to the initializer
+    /// 8. `[]` (escrows with a condition gating Cancel only) The account the release condition reads
+    Cancel,
+    ///
+    /// Locks an escrow for the taker for the escrow's reserve period against a lamport bond,
+    /// refunded by their Exchange and forfeited to the initializer once the period lapses.
+    /// A PDA taker posts the bond through the system program, so it must be a system account.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The taker posting the bond
+    /// 1. `[writable]` The escrow account holding the escrow info and the bond
+    /// 2. `[writable]` The initializer's main account, receiving a lapsed reservation's bond
+    /// 3. `[]` The clock sysvar
+    /// 4. `[]` The system program
+    Reserve,
+    ///
+    /// First phase of a two-phase exchange: locks the taker's payment in a second PDA vault
+    /// until the initializer settles or the escrow's settle timeout lets either side unwind.
+    /// Only fixed price escrows filled in one go can be exchanged in two phases.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The account of the person taking the trade
+    /// 1. `[writable]` The taker's token account holding exactly the price, its ownership moves to the PDA
+    /// 2. `[]` The taker's token account for the token they will receive should the trade go through
+    /// 3. `[]` The PDA's temp token account holding the deposit
+    /// 4. `[writable]` The initializer's main account, receiving a lapsed reservation's bond
+    /// 5. `[writable]` The escrow account holding the escrow info
+    /// 6. `[]` The token program
+    /// 7. `[]` The clock sysvar
+    CommitExchange {
+        /// the amount of the deposit the taker expects to receive
+        amount: u64,
+    },
+    ///
+    /// Second phase of a two-phase exchange: releases both legs and closes the escrow
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer, receiving the rent of the escrow and its temp token account
+    /// 1. `[writable]` The PDA's temp token account holding the deposit
+    /// 2. `[writable]` The PDA's payment vault holding the taker's payment
+    /// 3. `[writable]` The committed taker's token account that receives the deposit
+    /// 4. `[writable]` The initializer's token account that receives the payment
+    /// 5. `[writable]` The committed taker's main account, receiving the payment vault's rent
+    /// 6. `[writable]` The escrow account holding the escrow info
+    /// 7. `[]` The token program
+    /// 8. `[]` The PDA account
+    /// 9. `[]` The clock sysvar
+    /// 10. `[]` The config account, the PDA of `["config"]`
+    /// 11. `[writable]` (once the config exists) The fee owner's token account for the token the taker sent
+    /// 12. `[writable]` The volume account of the deposited mint, the PDA of `["mint_volume", mint]`
+    /// 13. `[writable]` (splitting escrows only) The token account of each split recipient in use, in order
+    SettleExchange,
+    ///
+    /// Returns a timed out two-phase exchange's payment to the taker, the escrow stays open
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` Either the initializer or the committed taker
+    /// 1. `[writable]` The PDA's payment vault holding the taker's payment
+    /// 2. `[writable]` The committed taker's token account the payment is returned to
+    /// 3. `[writable]` The committed taker's main account, receiving the payment vault's rent
+    /// 4. `[writable]` The escrow account holding the escrow info
+    /// 5. `[]` The token program
+    /// 6. `[]` The PDA account
+    /// 7. `[]` The clock sysvar
+    UnwindExchange,
+    ///
+    /// Creates the program's config, with no per mint fees yet
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The admin, paying for the config account
+    /// 1. `[writable]` The config account, the PDA of `["config"]`
+    /// 2. `[]` The owner of the token accounts settlement fees are paid into
+    /// 3. `[]` The rent sysvar
+    /// 4. `[]` The system program
+    InitConfig {
+        /// Fee in basis points of the payment for mints without a tier of their own
+        default_fee_bps: u16,
+    },
+    ///
+    /// Sets the fee charged on payments in a mint
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The admin
+    /// 1. `[writable]` The config account
+    /// 2. `[]` The mint
+    SetMintFee {
+        /// Fee in basis points of payments in the mint
+        fee_bps: u16,
+    },
+    ///
+    /// Sets the flat fee in lamports charged to signing takers in place of the fee in basis
+    /// points, zero going back to fees in tokens
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The admin
+    /// 1. `[writable]` The config account
+    SetLamportFee {
+        /// Fee in lamports of every settlement with a signing taker
+        lamport_fee: u64,
+    },
+    ///
+    /// Creates the mint list checked by InitEscrow, empty at first
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The admin, paying for the mint list account
+    /// 1. `[]` The config account
+    /// 2. `[writable]` The mint list account, the PDA of `["mint_list"]` for the program-wide
+    ///    config and of `["mint_list", market]` for a market's
+    /// 3. `[]` The rent sysvar
+    /// 4. `[]` The system program
+    InitMintList {
+        /// Only listed mints may be traded, rather than listed mints being banned
+        is_allow_list: bool,
+    },
+    ///
+    /// Adds a mint to the mint list or removes it
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The admin
+    /// 1. `[]` The config account
+    /// 2. `[writable]` The mint list account
+    /// 3. `[]` The mint
+    SetMintListed {
+        /// Add the mint, rather than remove it
+        listed: bool,
+    },
+    ///
+    /// Creates the account tallying escrow fills selling a mint, which Exchange and
+    /// SettleExchange keep up to date from then on
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The payer of the volume account's rent
+    /// 1. `[writable]` The volume account, the PDA of `["mint_volume", mint]`
+    /// 2. `[]` The mint
+    /// 3. `[]` The rent sysvar
+    /// 4. `[]` The system program
+    InitMintVolume,
+    ///
+    /// Returns an expired escrow's deposit to the initializer and closes the escrow, callable by
+    /// anyone for a bounty out of the escrow account's rent
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The caller, receiving the bounty
+    /// 1. `[writable]` The PDA's temp token account holding the deposit
+    /// 2. `[writable]` The initializer's token account the deposit is returned to
+    /// 3. `[writable]` The initializer's main account, receiving the rest of the rent
+    /// 4. `[writable]` The escrow account
+    /// 5. `[]` The token program
+    /// 6. `[]` The PDA account
+    /// 7. `[]` The clock sysvar
+    /// 8. `[writable]` (chained escrows) The proceeds vault, handed back to the initializer
+    ReclaimExpired,
+    ///
+    /// Hands an expired escrow's vault back to the initializer and closes the escrow, with no
+    /// signer so keepers and automation threads can run it at expiry. Every account follows from
+    /// the escrow's own data.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The PDA's temp token account holding the deposit, owned by the initializer afterwards
+    /// 1. `[writable]` The initializer's main account, receiving the escrow's rent
+    /// 2. `[writable]` The escrow account
+    /// 3. `[]` The token program
+    /// 4. `[]` The PDA account, the PDA of `["escrow"]`
+    /// 5. `[]` The clock sysvar
+    /// 6. `[writable]` (chained escrows) The proceeds vault, handed back to the initializer
+    ProcessExpiry,
+    ///
+    /// Stores default terms to post escrows from with InitFromTemplate
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The trader owning the template
+    /// 1. `[writable]` The template account, created beforehand and owned by the program
+    /// 2. `[]` The mint of the token to deposit
+    /// 3. `[]` The mint of the token to receive
+    /// 4. `[]` The rent sysvar
+    InitTemplate {
+        /// Mantissa of the price in receive base units per deposited base unit
+        price: u64,
+        /// Exponent of the price, after the allowed taker and `math::CURVE_PRICE_EXPO` when
+        /// absent from the data
+        price_expo: i32,
+        /// How many slots escrows stay open for, zero when they never expire
+        expiry_slots: u64,
+        /// The only taker of the escrows, `Pubkey::default()` for anyone
+        allowed_taker: Pubkey,
+    },
+    ///
+    /// Posts an escrow on a template's terms, pricing the whole deposit of the temp token account
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The template's owner, initializing the escrow
+    /// 1. `[writable]` Temporary token account of the template's deposit mint, owned by the initializer
+    /// 2. `[]` The initializer's token account of the template's receive mint
+    /// 3. `[writable]` The escrow account
+    /// 4. `[]` The rent sysvar
+    /// 5. `[]` The token program
+    /// 6. `[]` The mint list account, the PDA of `["mint_list"]`
+    /// 7. `[]` The template account
+    /// 8. `[]` The clock sysvar
+    InitFromTemplate,
+    ///
+    /// Starts a sealed-bid auction of the deposit. Bidders commit to hidden bids until the bid
+    /// phase ends, then reveal them until the reveal phase ends, after which the highest revealed
+    /// bid wins.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0-6. As InitEscrow, without the optional oracle accounts
+    /// 7. `[]` The clock sysvar
+    InitAuctionEscrow {
+        /// The lowest bid accepted, in the token to receive
+        reserve_price: u64,
+        /// How many slots the auction takes bids for
+        bid_slots: u64,
+        /// How many slots bids can be revealed for once bidding ends
+        reveal_slots: u64,
+    },
+    ///
+    /// Commits to a hidden bid on an auction escrow, locking a deposit of at least the bid
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The bidder, paying for the bid account
+    /// 1. `[writable]` The bidder's token account of the token to receive holding the deposit, its ownership moves to the PDA
+    /// 2. `[]` The escrow account
+    /// 3. `[]` The initializer's token account that will receive tokens
+    /// 4. `[writable]` The bid account, the PDA of `["bid", escrow, bidder]`
+    /// 5. `[]` The token program
+    /// 6. `[]` The clock sysvar
+    /// 7. `[]` The rent sysvar
+    /// 8. `[]` The system program
+    CommitBid {
+        /// `state::Bid::commitment` of the bid amount, a secret salt and the bidder
+        commitment: [u8; 32],
+    },
+    ///
+    /// Reveals a committed bid during the reveal phase
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The bidder
+    /// 1. `[writable]` The escrow account
+    /// 2. `[writable]` The bid account
+    /// 3. `[]` The bid's deposit vault
+    /// 4. `[]` The clock sysvar
+    RevealBid {
+        /// The amount committed to
+        amount: u64,
+        /// The salt committed to
+        salt: [u8; 32],
+    },
+    ///
+    /// Ends an auction once the reveal phase is over: the highest revealed bid pays the
+    /// initializer and receives the deposit, the rest of its deposit going back to the bidder.
+    /// Without a winner the deposit goes back to the initializer. Anyone can settle.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The initializer's main account, receiving the escrow's rent
+    /// 1. `[writable]` The PDA's temp token account holding the deposit
+    /// 2. `[writable]` The initializer's token account that will receive tokens
+    /// 3. `[writable]` The escrow account
+    /// 4. `[]` The token program
+    /// 5. `[]` The PDA account
+    /// 6. `[]` The clock sysvar
+    /// 7. `[writable]` (with a winner) The winning bid account
+    /// 8. `[writable]` (with a winner) The winning bid's deposit vault
+    /// 9. `[writable]` (with a winner) The winner's token account for the deposited token
+    /// 10. `[writable]` (with a winner) The winner's main account, receiving the bid account's rent
+    /// 11. `[]` (with a winner) The config account, the PDA of `["config"]`
+    /// 12. `[writable]` (with a winner, once the config exists) The fee owner's token account for the token to receive
+    /// 13. `[writable]` (with a winner) The volume account of the deposited mint
+    SettleAuction,
+    ///
+    /// Hands a losing or unrevealed bid's deposit back to the bidder once the reveal phase is over
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The bidder's main account, receiving the bid account's rent
+    /// 1. `[writable]` The bid account
+    /// 2. `[writable]` The bid's deposit vault
+    /// 3. `[]` The escrow account, closed or not
+    /// 4. `[]` The token program
+    /// 5. `[]` The PDA account
+    /// 6. `[]` The clock sysvar
+    RefundBid,
+    ///
+    /// Starts a raffle of the deposit. Takers buy tickets at a fixed price, paid straight to the
+    /// initializer, until the sale ends, after which one ticket is drawn to receive the deposit.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0-6. As InitEscrow, without the optional oracle accounts
+    /// 7. `[]` The clock sysvar
+    InitRaffleEscrow {
+        /// The price of a ticket, in the token to receive
+        ticket_price: u64,
+        /// How many slots tickets are sold for
+        sale_slots: u64,
+    },
+    ///
+    /// Buys the next ticket of a raffle
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The buyer, paying for the ticket account
+    /// 1. `[writable]` The buyer's token account paying for the ticket, in the token to receive
+    /// 2. `[writable]` The escrow account
+    /// 3. `[writable]` The initializer's token account that will receive tokens
+    /// 4. `[writable]` The ticket account, the PDA of `["ticket", escrow, index]` for the next index
+    /// 5. `[]` The token program
+    /// 6. `[]` The clock sysvar
+    /// 7. `[]` The rent sysvar
+    /// 8. `[]` The system program
+    /// 9. `[]` The config account, the PDA of `["config"]`
+    /// 10. `[writable]` (once the config exists) The fee owner's token account for the token to receive, or with a lamport fee the fee owner and the system program
+    BuyTicket,
+    ///
+    /// Ends a raffle once the sale is over, sending the deposit to the buyer of a ticket picked
+    /// with the most recent slot hash. Without tickets sold the deposit goes back to the
+    /// initializer. Anyone can draw.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The initializer's main account, receiving the escrow's rent
+    /// 1. `[writable]` The PDA's temp token account holding the deposit
+    /// 2. `[writable]` The escrow account
+    /// 3. `[]` The token program
+    /// 4. `[]` The PDA account
+    /// 5. `[]` The clock sysvar
+    /// 6. `[]` The slot hashes sysvar
+    /// 7. `[]` (with tickets sold) The drawn ticket account
+    /// 8. `[writable]` (with tickets sold) The ticket buyer's token account for the deposited token
+    /// 9. `[writable]` (with tickets sold) The volume account of the deposited mint
+    DrawRaffle,
+    ///
+    /// Closes a ticket of a raffle that was drawn, returning its rent to the buyer
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The buyer's main account
+    /// 1. `[writable]` The ticket account
+    /// 2. `[]` The raffle's escrow account, closed
+    CloseTicket,
+    ///
+    /// Posts a request for quote, escrowing the buyer's payment for makers to quote on
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The buyer
+    /// 1. `[writable]` Temporary token account holding the payment, owned by the buyer
+    /// 2. `[]` The buyer's token account for the token they want
+    /// 3. `[writable]` The request account, created beforehand and owned by the program
+    /// 4. `[]` The rent sysvar
+    /// 5. `[]` The token program
+    /// 6. `[]` The mint list account, the PDA of `["mint_list"]`
+    InitRequest {
+        /// The smallest amount of the wanted token a quote can offer
+        min_amount: u64,
+    },
+    ///
+    /// Quotes on a request, offering a whole token account of the wanted token for the payment
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The maker, paying for the quote account
+    /// 1. `[writable]` The maker's token account of the wanted token to deposit, owned by the maker
+    /// 2. `[]` The maker's token account for the payment token
+    /// 3. `[]` The request account
+    /// 4. `[writable]` The quote account, the PDA of `["quote", request, maker]`
+    /// 5. `[]` The token program
+    /// 6. `[]` The rent sysvar
+    /// 7. `[]` The system program
+    PostQuote,
+    ///
+    /// Settles a request with one of its quotes, swapping the payment for the quote's deposit
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The buyer, receiving the request's rent
+    /// 1. `[writable]` The PDA's token account holding the payment
+    /// 2. `[writable]` The buyer's token account for the token they want
+    /// 3. `[writable]` The request account
+    /// 4. `[writable]` The accepted quote account
+    /// 5. `[writable]` The PDA's token account holding the quote's deposit
+    /// 6. `[writable]` The maker's token account for the payment token
+    /// 7. `[writable]` The maker's main account, receiving the quote's rent
+    /// 8. `[]` The token program
+    /// 9. `[]` The PDA account
+    /// 10. `[]` The config account, the PDA of `["config"]`
+    /// 11. `[writable]` (once the config exists) The fee owner's token account for the payment token, or with a lamport fee the fee owner and the system program
+    /// 12. `[writable]` The volume account of the wanted mint
+    AcceptQuote,
+    ///
+    /// Closes a request, handing the payment back to the buyer
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The buyer, receiving the request's rent
+    /// 1. `[writable]` The PDA's token account holding the payment
+    /// 2. `[writable]` The request account
+    /// 3. `[]` The token program
+    /// 4. `[]` The PDA account
+    CancelRequest,
+    ///
+    /// Closes a quote, handing the deposit back to the maker. Quotes on accepted or cancelled
+    /// requests are closed the same way
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The maker, receiving the quote's rent
+    /// 1. `[writable]` The quote account
+    /// 2. `[writable]` The PDA's token account holding the deposit
+    /// 3. `[]` The token program
+    /// 4. `[]` The PDA account
+    WithdrawQuote,
+    ///
+    /// Chains a follow-on offer to a fixed price escrow: once it is fully filled, the escrow is
+    /// listed again in place, offering everything it was paid for the stored terms. The
+    /// initializer's receive token account is handed to the PDA to hold the proceeds until then.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer of the escrow
+    /// 1. `[writable]` The escrow account
+    /// 2. `[writable]` The initializer's token account receiving the escrow's proceeds
+    /// 3. `[]` The initializer's token account to receive the follow-on offer's payment
+    /// 4. `[]` The token program
+    /// 5. `[]` The mint list account of the escrow's market
+    SetChainedOffer {
+        /// The amount of the follow-on offer's receive token asked for all the proceeds
+        expected_amount: u64,
+    },
+    ///
+    /// Escrows a compressed NFT, transferring its leaf to the PDA through the Bubblegum program,
+    /// and offers it for a fixed amount of a token
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer, owner of the compressed NFT
+    /// 1. `[]` The compressed NFT's delegate, the initializer unless delegated
+    /// 2. `[]` The initializer's token account for the token they will receive
+    /// 3. `[writable]` The escrow account, created beforehand and owned by the program
+    /// 4. `[]` The rent sysvar
+    /// 5. `[]` The mint list account, the PDA of `["mint_list"]`
+    /// 6. `[]` The PDA account
+    /// 7. `[]` The tree authority, Bubblegum's PDA of the merkle tree
+    /// 8. `[writable]` The merkle tree
+    /// 9. `[]` The noop log wrapper program
+    /// 10. `[]` The account compression program
+    /// 11. `[]` The system program
+    /// 12. `[]` The Bubblegum program
+    /// 13. `[]` The proof path of the leaf, one node per account, minus the tree's canopy
+    InitCnftEscrow {
+        /// The amount of the token the initializer wants for the compressed NFT
+        expected_amount: u64,
+        /// The tree's current root
+        root: [u8; 32],
+        leaf: Leaf,
+    },
+    ///
+    /// Buys an escrowed compressed NFT, paying the initializer and transferring the leaf to
+    /// the taker
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The taker, new owner of the compressed NFT and payer of a lamport fee
+    /// 1. `[writable]` The taker's token account for the token they send, owned by the taker or delegated to them for the payment
+    /// 2. `[writable]` The initializer's token account receiving the payment
+    /// 3. `[writable]` The initializer's main account, receiving the escrow's rent
+    /// 4. `[writable]` The escrow account
+    /// 5. `[]` The token program
+    /// 6. `[]` The PDA account
+    /// 7. `[]` The config account, the PDA of `["config"]`
+    /// 8. `[writable]` (once the config exists) The fee owner's token account for the token the taker sends, or with a lamport fee the fee owner and the system program
+    /// 9. `[]` The tree authority, following the optional account above
+    /// 10. `[writable]` The merkle tree
+    /// 11. `[]` The noop log wrapper program
+    /// 12. `[]` The account compression program
+    /// 13. `[]` The system program
+    /// 14. `[]` The Bubblegum program
+    /// 15. `[]` The proof path of the leaf, one node per account, minus the tree's canopy
+    ExchangeCnft {
+        /// The tree's current root
+        root: [u8; 32],
+    },
+    ///
+    /// Cancels a compressed NFT escrow, transferring the leaf back to the initializer
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer, receiving the escrow's rent
+    /// 1. `[writable]` The escrow account
+    /// 2. `[]` The PDA account
+    /// 3. `[]` The tree authority
+    /// 4. `[writable]` The merkle tree
+    /// 5. `[]` The noop log wrapper program
+    /// 6. `[]` The account compression program
+    /// 7. `[]` The system program
+    /// 8. `[]` The Bubblegum program
+    /// 9. `[]` The proof path of the leaf, one node per account, minus the tree's canopy
+    CancelCnft {
+        /// The tree's current root
+        root: [u8; 32],
+    },
+    ///
+    /// Escrows a Metaplex Core asset, transferring it to the PDA, and offers it for a fixed
+    /// amount of a token. The asset's update authority is recorded for Exchange to check.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer, owner of the asset
+    /// 1. `[writable]` The asset
+    /// 2. `[]` The asset's collection, or the mpl-core program for assets outside a collection
+    /// 3. `[]` The initializer's token account for the token they will receive
+    /// 4. `[writable]` The escrow account, created beforehand and owned by the program
+    /// 5. `[]` The rent sysvar
+    /// 6. `[]` The mint list account, the PDA of `["mint_list"]`
+    /// 7. `[]` The PDA account
+    /// 8. `[]` The mpl-core program
+    InitCoreEscrow {
+        /// The amount of the token the initializer wants for the asset
+        expected_amount: u64,
+    },
+    ///
+    /// Buys an escrowed Metaplex Core asset, paying the initializer and transferring the asset
+    /// to the taker, provided its update authority has not changed since init
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The taker, new owner of the asset
+    /// 1. `[writable]` The taker's token account for the token they send, owned by the taker or delegated to them for the payment
+    /// 2. `[writable]` The initializer's token account receiving the payment
+    /// 3. `[writable]` The initializer's main account, receiving the escrow's rent
+    /// 4. `[writable]` The escrow account
+    /// 5. `[writable]` The asset
+    /// 6. `[]` The asset's collection, or the mpl-core program for assets outside a collection
+    /// 7. `[]` The token program
+    /// 8. `[]` The PDA account
+    /// 9. `[]` The mpl-core program
+    /// 10. `[]` The config account, the PDA of `["config"]`
+    /// 11. `[writable]` (once the config exists) The fee owner's token account for the token the taker sends, or with a lamport fee the fee owner and the system program
+    ExchangeCore,
+    ///
+    /// Cancels a Metaplex Core asset escrow, transferring the asset back to the initializer
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer, receiving the escrow's rent
+    /// 1. `[writable]` The escrow account
+    /// 2. `[writable]` The asset
+    /// 3. `[]` The asset's collection, or the mpl-core program for assets outside a collection
+    /// 4. `[]` The PDA account
+    /// 5. `[]` The mpl-core program
+    CancelCore,
+    ///
+    /// Exchange submitted by a relayer paying the fees, on behalf of a taker who signed a
+    /// `relay::RelayIntent` off-chain. The transaction carries an Ed25519 program instruction
+    /// verifying the taker's signature of the intent's message, and the taker's token account
+    /// approves the `["escrow"]` PDA as delegate for the payment.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The relayer, paying for the nonce account on the taker's first relayed intent
+    /// 1. `[writable]` The taker's relay nonce account, the PDA of `["relay_nonce", taker]`
+    /// 2. `[]` The instructions sysvar
+    /// 3. `[]` The rent sysvar
+    /// 4. `[]` The system program
+    /// 5. The accounts of Exchange, the taker not signing
+    RelayedExchange {
+        /// the amount the taker expects to be paid in the other token, as in Exchange
+        amount: u64,
+        /// the nonce of the taker's intent
+        nonce: u64,
+    },
+    ///
+    /// Cancel submitted by a relayer paying the fees, on behalf of an initializer who signed a
+    /// `relay::RelayIntent` off-chain, verified like RelayedExchange's
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The relayer, paying for the nonce account on the initializer's first relayed intent
+    /// 1. `[writable]` The initializer's relay nonce account, the PDA of `["relay_nonce", initializer]`
+    /// 2. `[]` The instructions sysvar
+    /// 3. `[]` The rent sysvar
+    /// 4. `[]` The system program
+    /// 5. The accounts of Cancel, the initializer not signing
+    RelayedCancel {
+        /// the nonce of the initializer's intent
+        nonce: u64,
+    },
+    ///
+    /// Changes nothing, returning the escrow's key fields with its PDA bump seed, the deposit left
+    /// in its vault and its fill status as `state::EscrowView` return data, for other programs
+    /// and simulating clients
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[]` The escrow account
+    /// 1. `[]` The PDA's temp token account holding the deposit
+    /// 2. `[]` The clock sysvar
+    GetEscrow,
+    ///
+    /// Points the escrow's proceeds at another token account of the initializer's for the same
+    /// mint, replacing a receive account that was closed or compromised before the escrow is
+    /// filled. Chained escrows can't change theirs, the PDA holds it.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer of the escrow
+    /// 1. `[writable]` The escrow account
+    /// 2. `[]` The initializer's new token account to receive the payment
+    SetReceiveAccount,
+    ///
+    /// Lets `operator` cancel and reprice the escrow on the initializer's behalf, for bots
+    /// managing offers without the initializer's key. The operator can't change where payouts
+    /// go.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer of the escrow
+    /// 1. `[writable]` The escrow account
+    SetOperator {
+        /// the new operator, `Pubkey::default()` to remove it
+        operator: Pubkey,
+    },
+    ///
+    /// Changes the amount a fixed or oracle priced escrow asks for its deposit. Refused while a
+    /// reservation or a two-phase exchange holds the price.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer or the operator of the escrow
+    /// 1. `[writable]` The escrow account
+    /// 2. `[]` The clock sysvar
+    Reprice {
+        /// the amount of the receive token asked for the whole deposit
+        expected_amount: u64,
+    },
+    ///
+    /// Cancel signed by the escrow's operator. The deposit is only returned to a token account
+    /// the initializer owns.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The operator of the escrow
+    /// 1. The accounts of Cancel, the initializer not signing
+    OperatorCancel,
+    ///
+    /// Creates or renews a session, letting `session_key` open and cancel offers for the maker
+    /// until `expires_at_slot` without the maker's signature. A session key opens escrows on
+    /// vaults it owns, funded through a delegate approval on the maker's token account, and
+    /// their proceeds go to the maker.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The maker, paying for the session account
+    /// 1. `[]` The session key
+    /// 2. `[writable]` The session account, the PDA of `["session", maker, session_key]`
+    /// 3. `[]` The clock sysvar
+    /// 4. `[]` The rent sysvar
+    /// 5. `[]` The system program
+    CreateSession {
+        /// first slot the session key is refused at
+        expires_at_slot: u64,
+        /// `SESSION_CAN_CREATE` and `SESSION_CAN_CANCEL` bits
+        permissions: u8,
+    },
+    ///
+    /// Revokes a session before it expires, closing its account
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The maker, receiving the session account's rent
+    /// 1. `[writable]` The session account
+    CloseSession,
+    ///
+    /// InitEscrow signed by a session key, opening the escrow for the session's maker
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[]` The session account
+    /// 1. `[]` The clock sysvar
+    /// 2. The accounts of InitEscrow, the session key signing as the initializer and owning the
+    ///    vault, the receive account owned by the maker
+    SessionInitEscrow {
+        /// The amount the maker expects to receive of the receive token
+        amount: u64,
+        terms: Box<EscrowTerms>,
+    },
+    ///
+    /// Cancel signed by a session key of the escrow's initializer. The deposit is only returned
+    /// to a token account the initializer owns.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The session key
+    /// 1. `[]` The session account
+    /// 2. The accounts of Cancel, the initializer not signing
+    SessionCancel,
+    ///
+    /// Sets the smallest deposit InitEscrow accepts, keeping dust offers off the books
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The admin
+    /// 1. `[]` The config account
+    /// 2. `[writable]` The mint list account
+    SetMinDeposit {
+        /// zero accepts any deposit
+        min_deposit_amount: u64,
+    },
+    ///
+    /// Sets the smallest amount of a mint InitEscrow accepts as the deposit or the expected
+    /// amount, in place of the minimum deposit for that mint
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The admin
+    /// 1. `[]` The config account
+    /// 2. `[writable]` The mint list account
+    /// 3. `[]` The mint
+    SetMintMinimum {
+        /// in the mint's base units
+        min_amount: u64,
+    },
+    ///
+    /// Routes an escrow's residuals out, callable by anyone. Tokens in the vault beyond the
+    /// recorded deposit go to the fee owner's token account, or to the initializer's while the
+    /// program has no config. Lamports of an unreserved escrow account beyond its rent go to the
+    /// initializer. An escrow left with nothing to sell is closed, its rent returned to the
+    /// initializer.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[writable]` The PDA's temp token account holding the deposit
+    /// 2. `[writable]` The initializer's main account
+    /// 3. `[]` The config account of the escrow's market
+    /// 4. `[writable]` The token account of the deposited mint receiving the surplus
+    /// 5. `[]` The token program
+    /// 6. `[]` The PDA account
+    /// 7. `[]` The rent sysvar
+    SweepDust,
+    ///
+    /// Fails unless the escrow is consistent: initialized, its vault the one it records, owned by
+    /// the PDA through SPL Token without a close authority, and holding at least the recorded
+    /// deposit. Changes nothing, for integrators to end their transactions with and for
+    /// monitoring cranks.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[]` The escrow account
+    /// 1. `[]` The PDA's temp token account holding the deposit
+    AssertInvariants,
+    ///
+    /// Proposes returning a bricked escrow's deposit to its initializer, executable by the admin
+    /// seven days later. Only built with the `admin-recovery` feature.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The admin, paying for the recovery account
+    /// 1. `[]` The config account
+    /// 2. `[]` The escrow account
+    /// 3. `[writable]` The recovery account, the PDA of `["recovery", escrow]`
+    /// 4. `[]` The clock sysvar
+    /// 5. `[]` The rent sysvar
+    /// 6. `[]` The system program
+    ProposeRecovery,
+    ///
+    /// Withdraws a proposed recovery. Only built with the `admin-recovery` feature.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The admin, receiving the recovery account's rent
+    /// 1. `[]` The config account
+    /// 2. `[writable]` The recovery account
+    CancelRecovery,
+    ///
+    /// Returns the deposit of an escrow with a recovery past its timelock to the initializer and
+    /// closes the escrow. Only built with the `admin-recovery` feature.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The admin, receiving the recovery account's rent
+    /// 1. `[]` The config account
+    /// 2. `[writable]` The recovery account
+    /// 3. `[writable]` The escrow account
+    /// 4. `[writable]` The PDA's temp token account holding the deposit
+    /// 5. `[writable]` The initializer's token account the deposit is returned to
+    /// 6. `[writable]` The initializer's main account, receiving the rent of the closed accounts
+    /// 7. `[]` The token program
+    /// 8. `[]` The PDA account
+    /// 9. `[]` The clock sysvar
+    /// 10. `[writable]` (chained escrows) The proceeds vault, handed back to the initializer
+    ExecuteRecovery,
+    ///
+    /// Hands the config over to a new admin, which signs nothing here so that a PDA such as a
+    /// governance account can take over and sign later changes through its proposals
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The admin
+    /// 1. `[writable]` The config account
+    /// 2. `[]` The new admin
+    SetAdmin,
+    ///
+    /// Exchange that fails unless the escrow was opened or last repriced at `created_before_slot`
+    /// or earlier and the current slot is at most `not_after_slot`, so the taker fills the offer
+    /// they saw and not one repriced or reopened at the same address since
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[]` The clock sysvar
+    /// 1. .. The accounts of Exchange
+    GuardedExchange {
+        /// the amount of Exchange
+        amount: u64,
+        /// slot the taker saw the escrow at, typically the context slot of the fetch
+        created_before_slot: u64,
+        /// last slot the exchange may land in
+        not_after_slot: u64,
+        /// the memo of Exchange, trailing the slots
+        memo: Option<String>,
+    },
+    ///
+    /// Exchange that fails unless the escrow's `terms_hash` is still `terms_hash`, so a taker
+    /// quoting from a cached offer fills it only while its terms are unchanged
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. .. The accounts of Exchange
+    TermsCheckedExchange {
+        /// the amount of Exchange
+        amount: u64,
+        /// the escrow's `terms_hash` the taker saw
+        terms_hash: [u8; 32],
+        /// the memo of Exchange, trailing the hash
+        memo: Option<String>,
+    },
+    ///
+    /// Returns `amount` of the deposit to the initializer and keeps the escrow open with the
+    /// rest, cutting the expected amount in proportion so the price per token stays the same.
+    /// Refused whenever Cancel would be, and for the whole deposit, which Cancel returns.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer
+    /// 1. `[writable]` The PDA's temp token account holding the deposit
+    /// 2. `[writable]` The initializer's token account the withdrawn tokens go to
+    /// 3. `[writable]` The escrow account
+    /// 4. `[]` The token program
+    /// 5. `[]` The PDA account
+    /// 6. `[]` The clock sysvar
+    /// 7. `[]` The mint list account of the escrow's market, whose minimums the deposit left
+    ///    must meet
+    /// 8. `[]` (escrows with a condition gating Cancel only) The account the release condition reads
+    WithdrawDeposit {
+        /// the amount of the deposit to withdraw
+        amount: u64,
+    },
+    ///
+    /// Creates the config of the market seeded by the signer, who becomes its admin. Escrows
+    /// naming the market in their terms pay its fees and trade the mints its own mint list
+    /// allows. The other config instructions manage it like the program-wide config.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The key the market is seeded by, paying for the config account
+    /// 1. `[writable]` The config account, the PDA of `["config", market]`
+    /// 2. `[]` The owner of the token accounts settlement fees are paid into
+    /// 3. `[]` The rent sysvar
+    /// 4. `[]` The system program
+    InitMarket {
+        /// Fee in basis points of the payment for mints without a tier of their own
+        default_fee_bps: u16,
+    },
+    ///
+    /// Pauses or resumes settlements in a market, the program-wide one included
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The admin
+    /// 1. `[writable]` The config account
+    SetMarketPaused {
+        /// Refuse settlements, rather than allow them again
+        paused: bool,
+    },
+    ///
+    /// Exchange that fails with `SlippageExceeded` when the fill costs more than `max_payment`,
+    /// the only Exchange oracle priced escrows accept
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. .. The accounts of Exchange
+    CappedExchange {
+        /// the amount of Exchange
         amount: u64,
+        /// the most the taker pays for the fill
+        max_payment: u64,
+        /// the memo of Exchange, trailing `max_payment`
+        memo: Option<String>,
     },
 }
 
 impl EscrowInstruction {
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
         let (tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
+        if let Some(len) = Self::fixed_data_len(*tag) {
+            if rest.len() != len {
+                msg!("error: instruction data of the wrong length");
+                return Err(InvalidInstruction.into());
+            }
+        }
 
         Ok(match tag {
             0 => {
                 msg!("InitEscrow instruction!");
                 Self::InitEscrow {
                     amount: Self::unpack_amount(rest)?,
+                    terms: Box::new(Self::unpack_terms(rest.get(8..).unwrap_or_default())?),
                 }
             }
             1 => {
                 msg!("Eschange instruction!");
                 Self::Exchange {
                     amount: Self::unpack_amount(rest)?,
+                    memo: Self::unpack_memo(rest.get(8..).unwrap_or_default())?,
+                }
+            }
+            2 => {
+                msg!("InitCurveEscrow instruction!");
+                Self::InitCurveEscrow {
+                    start_price: Self::unpack_amount(rest)?,
+                    slope: Self::unpack_amount(rest.get(8..).unwrap_or_default())?,
+                }
+            }
+            3 => {
+                msg!("InitCrowdfillEscrow instruction!");
+                Self::InitCrowdfillEscrow {
+                    amount: Self::unpack_amount(rest)?,
                 }
             }
+            4 => Self::InitPool {
+                price: Self::unpack_amount(rest)?,
+                price_expo: Self::unpack_price_expo(rest.get(8..).unwrap_or_default())?,
+            },
+            5 => Self::PoolDeposit {
+                amount: Self::unpack_amount(rest)?,
+            },
+            6 => Self::PoolWithdraw {
+                shares: Self::unpack_amount(rest)?,
+            },
+            7 => Self::PoolExchange {
+                amount: Self::unpack_amount(rest)?,
+            },
+            8 => Self::Cancel,
+            9 => Self::Reserve,
+            10 => Self::CommitExchange {
+                amount: Self::unpack_amount(rest)?,
+            },
+            11 => Self::SettleExchange,
+            12 => Self::UnwindExchange,
+            13 => Self::InitConfig {
+                default_fee_bps: Self::unpack_u16(rest)?,
+            },
+            14 => Self::SetMintFee {
+                fee_bps: Self::unpack_u16(rest)?,
+            },
+            15 => Self::InitMintList {
+                is_allow_list: Self::unpack_bool(rest)?,
+            },
+            16 => Self::SetMintListed {
+                listed: Self::unpack_bool(rest)?,
+            },
+            17 => Self::InitMintVolume,
+            18 => Self::ReclaimExpired,
+            19 => Self::ProcessExpiry,
+            20 => Self::InitTemplate {
+                price: Self::unpack_amount(rest)?,
+                price_expo: Self::unpack_price_expo(rest.get(48..).unwrap_or_default())?,
+                expiry_slots: Self::unpack_amount(rest.get(8..).unwrap_or_default())?,
+                allowed_taker: Self::unpack_optional_pubkey(
+                    rest.get(16..rest.len().min(48)).unwrap_or_default(),
+                )?,
+            },
+            21 => Self::InitFromTemplate,
+            22 => Self::InitAuctionEscrow {
+                reserve_price: Self::unpack_amount(rest)?,
+                bid_slots: Self::unpack_amount(rest.get(8..).unwrap_or_default())?,
+                reveal_slots: Self::unpack_amount(rest.get(16..).unwrap_or_default())?,
+            },
+            23 => Self::CommitBid {
+                commitment: Self::unpack_hash(rest)?,
+            },
+            24 => Self::RevealBid {
+                amount: Self::unpack_amount(rest)?,
+                salt: Self::unpack_hash(rest.get(8..).unwrap_or_default())?,
+            },
+            25 => Self::SettleAuction,
+            26 => Self::RefundBid,
+            27 => Self::InitRaffleEscrow {
+                ticket_price: Self::unpack_amount(rest)?,
+                sale_slots: Self::unpack_amount(rest.get(8..).unwrap_or_default())?,
+            },
+            28 => Self::BuyTicket,
+            29 => Self::DrawRaffle,
+            30 => Self::CloseTicket,
+            31 => Self::InitRequest {
+                min_amount: Self::unpack_amount(rest)?,
+            },
+            32 => Self::PostQuote,
+            33 => Self::AcceptQuote,
+            34 => Self::CancelRequest,
+            35 => Self::WithdrawQuote,
+            36 => Self::SetChainedOffer {
+                expected_amount: Self::unpack_amount(rest)?,
+            },
+            37 => Self::InitCnftEscrow {
+                expected_amount: Self::unpack_amount(rest)?,
+                root: Self::unpack_hash(rest.get(8..).unwrap_or_default())?,
+                leaf: Self::unpack_leaf(rest.get(40..).unwrap_or_default())?,
+            },
+            38 => Self::ExchangeCnft {
+                root: Self::unpack_hash(rest)?,
+            },
+            39 => Self::CancelCnft {
+                root: Self::unpack_hash(rest)?,
+            },
+            40 => Self::InitCoreEscrow {
+                expected_amount: Self::unpack_amount(rest)?,
+            },
+            41 => Self::ExchangeCore,
+            42 => Self::CancelCore,
+            43 => Self::RelayedExchange {
+                amount: Self::unpack_amount(rest)?,
+                nonce: Self::unpack_amount(rest.get(8..).unwrap_or_default())?,
+            },
+            44 => Self::RelayedCancel {
+                nonce: Self::unpack_amount(rest)?,
+            },
+            45 => Self::SetLamportFee {
+                lamport_fee: Self::unpack_amount(rest)?,
+            },
+            46 => Self::GetEscrow,
+            47 => Self::SetReceiveAccount,
+            48 => Self::SetOperator {
+                operator: Self::unpack_optional_pubkey(rest)?,
+            },
+            49 => Self::Reprice {
+                expected_amount: Self::unpack_amount(rest)?,
+            },
+            50 => Self::OperatorCancel,
+            51 => Self::CreateSession {
+                expires_at_slot: Self::unpack_amount(rest)?,
+                permissions: rest[8],
+            },
+            52 => Self::CloseSession,
+            53 => Self::SessionInitEscrow {
+                amount: Self::unpack_amount(rest)?,
+                terms: Box::new(Self::unpack_terms(rest.get(8..).unwrap_or_default())?),
+            },
+            54 => Self::SessionCancel,
+            55 => Self::SetMinDeposit {
+                min_deposit_amount: Self::unpack_amount(rest)?,
+            },
+            56 => Self::SetMintMinimum {
+                min_amount: Self::unpack_amount(rest)?,
+            },
+            57 => Self::SweepDust,
+            58 => Self::AssertInvariants,
+            59 => Self::ProposeRecovery,
+            60 => Self::CancelRecovery,
+            61 => Self::ExecuteRecovery,
+            62 => Self::SetAdmin,
+            63 => Self::GuardedExchange {
+                amount: Self::unpack_amount(rest)?,
+                created_before_slot: Self::unpack_amount(rest.get(8..).unwrap_or_default())?,
+                not_after_slot: Self::unpack_amount(rest.get(16..).unwrap_or_default())?,
+                memo: Self::unpack_memo(rest.get(24..).unwrap_or_default())?,
+            },
+            64 => Self::TermsCheckedExchange {
+                amount: Self::unpack_amount(rest)?,
+                terms_hash: rest
+                    .get(8..40)
+                    .and_then(|slice| slice.try_into().ok())
+                    .ok_or(InvalidInstruction)?,
+                memo: Self::unpack_memo(rest.get(40..).unwrap_or_default())?,
+            },
+            65 => Self::WithdrawDeposit {
+                amount: Self::unpack_amount(rest)?,
+            },
+            66 => Self::InitMarket {
+                default_fee_bps: Self::unpack_u16(rest)?,
+            },
+            67 => Self::SetMarketPaused {
+                paused: Self::unpack_bool(rest)?,
+            },
+            68 => Self::CappedExchange {
+                amount: Self::unpack_amount(rest)?,
+                max_payment: Self::unpack_amount(rest.get(8..).unwrap_or_default())?,
+                memo: Self::unpack_memo(rest.get(16..).unwrap_or_default())?,
+            },
             _ => {
                 msg!("Instruction not implemented!");
                 return Err(InvalidInstruction.into());
@@ -64,6 +1433,44 @@ impl EscrowInstruction {
         })
     }
 
+    /// Length of the data after `tag` for instructions without optional trailing data, whose
+    /// helpers would otherwise ignore whatever follows the fields they read. InitEscrow,
+    /// Exchange, InitPool and InitTemplate bound their trailing data themselves.
+    pub(crate) fn fixed_data_len(tag: u8) -> Option<usize> {
+        Some(match tag {
+            8
+            | 9
+            | 11
+            | 12
+            | 17
+            | 18
+            | 19
+            | 21
+            | 25
+            | 26
+            | 28..=30
+            | 32..=35
+            | 41
+            | 42
+            | 46
+            | 47
+            | 50
+            | 52
+            | 54
+            | 57..=62 => 0,
+            15 | 16 | 67 => 1,
+            13 | 14 | 66 => 2,
+            3 | 5..=7 | 10 | 31 | 36 | 40 | 44 | 45 | 49 | 55 | 56 | 65 => 8,
+            2 | 27 | 43 => 16,
+            51 => 9,
+            22 => 24,
+            23 | 38 | 39 | 48 => 32,
+            24 => 40,
+            37 => 116,
+            _ => return None,
+        })
+    }
+
     pub fn unpack_amount(input: &[u8]) -> Result<u64, ProgramError> {
         let amount = input
             .get(..8)
@@ -72,4 +1479,568 @@ impl EscrowInstruction {
             .ok_or(InvalidInstruction)?;
         Ok(amount)
     }
+
+    fn unpack_memo(input: &[u8]) -> Result<Option<String>, ProgramError> {
+        if input.is_empty() {
+            return Ok(None);
+        }
+        if input.len() > MAX_SETTLEMENT_MEMO_LEN {
+            return Err(InvalidInstruction.into());
+        }
+        let memo = std::str::from_utf8(input).map_err(|_| InvalidInstruction)?;
+        Ok(Some(memo.to_string()))
+    }
+
+    fn unpack_u16(input: &[u8]) -> Result<u16, ProgramError> {
+        let value = input
+            .get(..2)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u16::from_le_bytes)
+            .ok_or(InvalidInstruction)?;
+        Ok(value)
+    }
+
+    /// exactly 8 bytes of a unix timestamp
+    fn unpack_timestamp(input: &[u8]) -> Result<i64, ProgramError> {
+        let bytes = input.try_into().map_err(|_| InvalidInstruction)?;
+        Ok(i64::from_le_bytes(bytes))
+    }
+
+    fn unpack_bool(input: &[u8]) -> Result<bool, ProgramError> {
+        match input.first() {
+            Some(0) => Ok(false),
+            Some(1) => Ok(true),
+            _ => Err(InvalidInstruction.into()),
+        }
+    }
+
+    /// zero when absent from the end of the instruction data
+    fn unpack_optional_u64(input: &[u8]) -> Result<u64, ProgramError> {
+        if input.is_empty() {
+            return Ok(0);
+        }
+        Self::unpack_amount(input)
+    }
+
+    fn unpack_hash(input: &[u8]) -> Result<[u8; 32], ProgramError> {
+        let hash = input
+            .get(..32)
+            .and_then(|slice| slice.try_into().ok())
+            .ok_or(InvalidInstruction)?;
+        Ok(hash)
+    }
+
+    /// data hash, creator hash, nonce and index, as in Bubblegum's own instructions
+    fn unpack_leaf(input: &[u8]) -> Result<Leaf, ProgramError> {
+        Ok(Leaf {
+            data_hash: Self::unpack_hash(input)?,
+            creator_hash: Self::unpack_hash(input.get(32..).unwrap_or_default())?,
+            nonce: Self::unpack_amount(input.get(64..).unwrap_or_default())?,
+            index: input
+                .get(72..76)
+                .and_then(|slice| slice.try_into().ok())
+                .map(u32::from_le_bytes)
+                .ok_or(InvalidInstruction)?,
+        })
+    }
+
+    /// `Pubkey::default()` when absent from the end of the instruction data
+    fn unpack_optional_pubkey(input: &[u8]) -> Result<Pubkey, ProgramError> {
+        if input.is_empty() {
+            return Ok(Pubkey::default());
+        }
+        let bytes = input.try_into().map_err(|_| InvalidInstruction)?;
+        Ok(Pubkey::new_from_array(bytes))
+    }
+
+    /// price exponents are optional trailing data, the curve price scale's when absent
+    fn unpack_price_expo(input: &[u8]) -> Result<i32, ProgramError> {
+        if input.is_empty() {
+            return Ok(math::CURVE_PRICE_EXPO);
+        }
+        let bytes = input.try_into().map_err(|_| InvalidInstruction)?;
+        Ok(i32::from_le_bytes(bytes))
+    }
+
+    /// oracle terms are optional trailing data, both zero when absent
+    fn unpack_oracle_terms(input: &[u8]) -> Result<(u64, u16), ProgramError> {
+        if input.is_empty() {
+            return Ok((0, 0));
+        }
+        let max_staleness_slots = Self::unpack_amount(input)?;
+        let max_confidence_bps = Self::unpack_u16(input.get(8..).unwrap_or_default())?;
+        Ok((max_staleness_slots, max_confidence_bps))
+    }
+
+    fn unpack_terms(input: &[u8]) -> Result<EscrowTerms, ProgramError> {
+        let (oracle_max_staleness_slots, oracle_max_confidence_bps) =
+            Self::unpack_oracle_terms(input)?;
+        Ok(EscrowTerms {
+            oracle_max_staleness_slots,
+            oracle_max_confidence_bps,
+            cancel_locked_until_slot: Self::unpack_optional_u64(
+                input.get(10..).unwrap_or_default(),
+            )?,
+            reserve_bond_lamports: Self::unpack_optional_u64(input.get(18..).unwrap_or_default())?,
+            reserve_slots: Self::unpack_optional_u64(input.get(26..).unwrap_or_default())?,
+            settle_timeout_slots: Self::unpack_optional_u64(input.get(34..).unwrap_or_default())?,
+            exchange_top_level_only: match input.get(42) {
+                None | Some(0) => false,
+                Some(1) => true,
+                _ => return Err(InvalidInstruction.into()),
+            },
+            metadata_uri: match input.get(43..107) {
+                Some(uri) => MetadataUri::unpack(uri).map_err(|_| InvalidInstruction)?,
+                None if input.len() <= 43 => MetadataUri::default(),
+                None => return Err(InvalidInstruction.into()),
+            },
+            memo_reference: match input.get(107..139) {
+                Some(reference) => reference.try_into().map_err(|_| InvalidInstruction)?,
+                None if input.len() <= 107 => [0; MEMO_REFERENCE_LEN],
+                None => return Err(InvalidInstruction.into()),
+            },
+            expires_at_slot: match input.get(139..147) {
+                Some(slot) => Self::unpack_amount(slot)?,
+                None if input.len() <= 139 => 0,
+                None => return Err(InvalidInstruction.into()),
+            },
+            allowed_taker: match input.get(147..179) {
+                Some(taker) => Self::unpack_optional_pubkey(taker)?,
+                None if input.len() <= 147 => Pubkey::default(),
+                None => return Err(InvalidInstruction.into()),
+            },
+            relist_amount: match input.get(179..187) {
+                Some(amount) => Self::unpack_amount(amount)?,
+                None if input.len() <= 179 => 0,
+                None => return Err(InvalidInstruction.into()),
+            },
+            relist_source_pubkey: match input.get(187..219) {
+                Some(source) => Self::unpack_optional_pubkey(source)?,
+                None if input.len() <= 187 => Pubkey::default(),
+                None => return Err(InvalidInstruction.into()),
+            },
+            split_recipients: Self::unpack_split_recipients(
+                input.get(219..input.len().min(355)).unwrap_or_default(),
+            )?,
+            release_condition: Self::unpack_release_condition(
+                input.get(355..input.len().min(455)).unwrap_or_default(),
+            )?,
+            release_after_timestamp: match input.get(455..463) {
+                Some(timestamp) => Self::unpack_timestamp(timestamp)?,
+                None if input.len() <= 455 => 0,
+                None => return Err(InvalidInstruction.into()),
+            },
+            release_before_timestamp: match input.get(463..471) {
+                Some(timestamp) => Self::unpack_timestamp(timestamp)?,
+                None if input.len() <= 463 => 0,
+                None => return Err(InvalidInstruction.into()),
+            },
+            market: match input.get(471..503) {
+                Some(market) => Self::unpack_optional_pubkey(market)?,
+                None if input.len() <= 471 => Pubkey::default(),
+                None => return Err(InvalidInstruction.into()),
+            },
+            tag: match input.get(503..) {
+                Some(tag) if !tag.is_empty() => {
+                    u64::from_le_bytes(tag.try_into().map_err(|_| InvalidInstruction)?)
+                }
+                _ => 0,
+            },
+        })
+    }
+
+    /// account, owner, offset, value length, the value zero padded to `CONDITION_VALUE_LEN` and
+    /// optionally a byte set to 1 for a condition gating Cancel
+    fn unpack_release_condition(input: &[u8]) -> Result<ReleaseCondition, ProgramError> {
+        if input.is_empty() {
+            return Ok(ReleaseCondition::default());
+        }
+        if !(99..=100).contains(&input.len()) || input[66] as usize > CONDITION_VALUE_LEN {
+            return Err(InvalidInstruction.into());
+        }
+        Ok(ReleaseCondition {
+            account: Self::unpack_optional_pubkey(&input[..32])?,
+            owner: Self::unpack_optional_pubkey(&input[32..64])?,
+            offset: Self::unpack_u16(&input[64..66])?,
+            value_len: input[66],
+            value: input[67..99].try_into().map_err(|_| InvalidInstruction)?,
+            gates_cancel: match input.get(99) {
+                None | Some(0) => false,
+                Some(1) => true,
+                Some(_) => return Err(InvalidInstruction.into()),
+            },
+        })
+    }
+
+    /// up to `MAX_SPLIT_RECIPIENTS` pairs of a token account and its share in basis points
+    fn unpack_split_recipients(
+        input: &[u8],
+    ) -> Result<[SplitRecipient; MAX_SPLIT_RECIPIENTS], ProgramError> {
+        let mut split_recipients = [SplitRecipient::default(); MAX_SPLIT_RECIPIENTS];
+        let chunks = input.chunks_exact(34);
+        if !chunks.remainder().is_empty() || chunks.len() > MAX_SPLIT_RECIPIENTS {
+            return Err(InvalidInstruction.into());
+        }
+        for (recipient, input) in split_recipients.iter_mut().zip(chunks) {
+            *recipient = SplitRecipient {
+                token_account: Self::unpack_optional_pubkey(&input[..32])?,
+                share_bps: Self::unpack_u16(&input[32..])?,
+            };
+        }
+        Ok(split_recipients)
+    }
+}
+
+/// InitEscrow without any terms, opening `escrow` on the deposit in `vault` for `amount` of the
+/// token `receive_account` holds
+pub fn init_escrow(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    vault: &Pubkey,
+    receive_account: &Pubkey,
+    escrow: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut data = vec![0];
+    data.extend_from_slice(&amount.to_le_bytes());
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*initializer, true),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new_readonly(*receive_account, false),
+            AccountMeta::new(*escrow, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(MintList::find_address(program_id).0, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+        data,
+    }
+}
+
+/// Accounts of a plain Exchange, for escrows without any of the optional accounts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExchangeAccounts {
+    pub taker: Pubkey,
+    pub takers_sending_token_account: Pubkey,
+    pub takers_token_to_receive_account: Pubkey,
+    pub escrow: Pubkey,
+    /// the fee owner's token account for the token the taker sends, once the config exists
+    pub fee_account: Option<Pubkey>,
+}
+
+/// Exchange of `escrow_info`, expecting `amount` of its deposit
+pub fn exchange(
+    program_id: &Pubkey,
+    accounts: &ExchangeAccounts,
+    escrow_info: &Escrow,
+    deposit_mint: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+    let mut metas = vec![
+        // writable for the rebate paid when the exchange closes the escrow
+        AccountMeta::new(accounts.taker, true),
+        AccountMeta::new(accounts.takers_sending_token_account, false),
+        AccountMeta::new(accounts.takers_token_to_receive_account, false),
+        AccountMeta::new(escrow_info.temp_token_account_pubkey, false),
+        AccountMeta::new(escrow_info.initializer_pubkey, false),
+        AccountMeta::new(
+            escrow_info.initializer_token_to_receive_account_pubkey,
+            false,
+        ),
+        AccountMeta::new(accounts.escrow, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(pda, false),
+        AccountMeta::new_readonly(
+            Config::find_market_address(&escrow_info.market, program_id).0,
+            false,
+        ),
+    ];
+    if let Some(fee_account) = accounts.fee_account {
+        metas.push(AccountMeta::new(fee_account, false));
+    }
+    metas.push(AccountMeta::new(
+        MintVolume::find_address(deposit_mint, program_id).0,
+        false,
+    ));
+
+    let mut data = vec![1];
+    data.extend_from_slice(&amount.to_le_bytes());
+    Instruction {
+        program_id: *program_id,
+        accounts: metas,
+        data,
+    }
+}
+
+/// SetReceiveAccount of `escrow`, paying its proceeds to `receive_account` from now on
+pub fn set_receive_account(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    escrow: &Pubkey,
+    receive_account: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*initializer, true),
+            AccountMeta::new(*escrow, false),
+            AccountMeta::new_readonly(*receive_account, false),
+        ],
+        data: vec![47],
+    }
+}
+
+/// SetOperator of `escrow`, `Pubkey::default()` removing the operator
+pub fn set_operator(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    escrow: &Pubkey,
+    operator: &Pubkey,
+) -> Instruction {
+    let mut data = vec![48];
+    data.extend_from_slice(operator.as_ref());
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*initializer, true),
+            AccountMeta::new(*escrow, false),
+        ],
+        data,
+    }
+}
+
+/// Reprice of `escrow` to `expected_amount`, signed by its initializer or operator `authority`
+pub fn reprice(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    escrow: &Pubkey,
+    expected_amount: u64,
+) -> Instruction {
+    let mut data = vec![49];
+    data.extend_from_slice(&expected_amount.to_le_bytes());
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*escrow, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+        data,
+    }
+}
+
+/// CreateSession of `session_key` for `maker` until `expires_at_slot`
+pub fn create_session(
+    program_id: &Pubkey,
+    maker: &Pubkey,
+    session_key: &Pubkey,
+    expires_at_slot: u64,
+    permissions: u8,
+) -> Instruction {
+    let mut data = vec![51];
+    data.extend_from_slice(&expires_at_slot.to_le_bytes());
+    data.push(permissions);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*maker, true),
+            AccountMeta::new_readonly(*session_key, false),
+            AccountMeta::new(
+                Session::find_address(maker, session_key, program_id).0,
+                false,
+            ),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(solana_program::system_program::id(), false),
+        ],
+        data,
+    }
+}
+
+/// InitEscrow of a vault owned by `session_key`, opened for `maker` and paying `receive_account`
+pub fn session_init_escrow(
+    program_id: &Pubkey,
+    maker: &Pubkey,
+    session_key: &Pubkey,
+    vault: &Pubkey,
+    receive_account: &Pubkey,
+    escrow: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut instruction = init_escrow(
+        program_id,
+        session_key,
+        vault,
+        receive_account,
+        escrow,
+        amount,
+    );
+    instruction.data[0] = 53;
+    instruction.accounts.splice(
+        0..0,
+        vec![
+            AccountMeta::new_readonly(
+                Session::find_address(maker, session_key, program_id).0,
+                false,
+            ),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+    );
+    instruction
+}
+
+/// AssertInvariants of `escrow` and its `vault`
+pub fn assert_invariants(program_id: &Pubkey, escrow: &Pubkey, vault: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*escrow, false),
+            AccountMeta::new_readonly(*vault, false),
+        ],
+        data: vec![58],
+    }
+}
+
+/// SetAdmin handing the config over to `new_admin`, such as a governance account
+pub fn set_admin(program_id: &Pubkey, admin: &Pubkey, new_admin: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(Config::find_address(program_id).0, false),
+            AccountMeta::new_readonly(*new_admin, false),
+        ],
+        data: vec![62],
+    }
+}
+
+/// GuardedExchange of `amount`, the [`exchange`] failing unless `escrow_info` is the revision of
+/// the escrow at `created_before_slot` or earlier and it lands by `not_after_slot`
+pub fn guarded_exchange(
+    program_id: &Pubkey,
+    accounts: &ExchangeAccounts,
+    escrow_info: &Escrow,
+    deposit_mint: &Pubkey,
+    amount: u64,
+    created_before_slot: u64,
+    not_after_slot: u64,
+) -> Instruction {
+    let mut instruction = exchange(program_id, accounts, escrow_info, deposit_mint, amount);
+    instruction
+        .accounts
+        .insert(0, AccountMeta::new_readonly(sysvar::clock::id(), false));
+    instruction.data[0] = 63;
+    instruction
+        .data
+        .extend_from_slice(&created_before_slot.to_le_bytes());
+    instruction
+        .data
+        .extend_from_slice(&not_after_slot.to_le_bytes());
+    instruction
+}
+
+/// TermsCheckedExchange of `amount`, the [`exchange`] failing unless the escrow's terms are still
+/// those of `escrow_info`
+pub fn terms_checked_exchange(
+    program_id: &Pubkey,
+    accounts: &ExchangeAccounts,
+    escrow_info: &Escrow,
+    deposit_mint: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut instruction = exchange(program_id, accounts, escrow_info, deposit_mint, amount);
+    instruction.data[0] = 64;
+    instruction.data.extend_from_slice(&escrow_info.terms_hash);
+    instruction
+}
+
+/// WithdrawDeposit of `amount` of the deposit of `escrow_info` to the initializer's
+/// `token_account`
+pub fn withdraw_deposit(
+    program_id: &Pubkey,
+    escrow: &Pubkey,
+    escrow_info: &Escrow,
+    token_account: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+    let mut data = vec![65];
+    data.extend_from_slice(&amount.to_le_bytes());
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(escrow_info.initializer_pubkey, true),
+            AccountMeta::new(escrow_info.temp_token_account_pubkey, false),
+            AccountMeta::new(*token_account, false),
+            AccountMeta::new(*escrow, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(pda, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(
+                MintList::find_market_address(&escrow_info.market, program_id).0,
+                false,
+            ),
+        ],
+        data,
+    }
+}
+
+/// InitMarket of the market seeded by `market`, paying fees to token accounts of `fee_owner`
+pub fn init_market(
+    program_id: &Pubkey,
+    market: &Pubkey,
+    fee_owner: &Pubkey,
+    default_fee_bps: u16,
+) -> Instruction {
+    let mut data = vec![66];
+    data.extend_from_slice(&default_fee_bps.to_le_bytes());
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*market, true),
+            AccountMeta::new(Config::find_market_address(market, program_id).0, false),
+            AccountMeta::new_readonly(*fee_owner, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(solana_program::system_program::id(), false),
+        ],
+        data,
+    }
+}
+
+/// SetMarketPaused of `market`, `Pubkey::default()` for the program-wide market
+pub fn set_market_paused(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    market: &Pubkey,
+    paused: bool,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(Config::find_market_address(market, program_id).0, false),
+        ],
+        data: vec![67, paused as u8],
+    }
+}
+
+/// CappedExchange of `amount`, the [`exchange`] failing when the fill costs more than
+/// `max_payment`
+pub fn capped_exchange(
+    program_id: &Pubkey,
+    accounts: &ExchangeAccounts,
+    escrow_info: &Escrow,
+    deposit_mint: &Pubkey,
+    amount: u64,
+    max_payment: u64,
+) -> Instruction {
+    let mut instruction = exchange(program_id, accounts, escrow_info, deposit_mint, amount);
+    instruction.data[0] = 68;
+    instruction
+        .data
+        .extend_from_slice(&max_payment.to_le_bytes());
+    instruction
 }
@@ -1,9 +1,36 @@
 #![forbid(unsafe_code)]
 
+// the canonical deployment, `Processor::process` refuses to run under any other address unless
+// built with the `any-program-id` feature
+solana_program::declare_id!("escrow1111111111111111111111111111111111111");
+
+#[cfg(feature = "anchor-compat")]
+pub mod anchor_compat;
+pub mod bubblegum;
+#[cfg(feature = "client")]
+pub mod client;
 pub mod error;
+pub mod event;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+pub mod governance;
 pub mod instruction;
+pub mod math;
+pub mod memo;
+pub mod mpl_core;
+pub mod oracle;
+pub mod prelude;
 pub mod processor;
+pub mod relay;
 pub mod state;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+pub mod token_cpi;
+pub mod token_metadata;
 
 #[cfg(not(feature = "no-entrypoint"))]
 mod entrypoint;
+
+pub use error::EscrowError;
+pub use instruction::EscrowInstruction;
+pub use state::Escrow;
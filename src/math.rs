@@ -0,0 +1,294 @@
+use std::convert::TryInto;
+
+/// Converts `amount` base units of a mint with `from_decimals` into base units of a mint with
+/// `to_decimals`, rounding down
+pub fn normalize_amount(amount: u64, from_decimals: u8, to_decimals: u8) -> Option<u64> {
+    scale(
+        amount as u128,
+        to_decimals as i32 - from_decimals as i32,
+        false,
+    )
+}
+
+/// Amount of the quote mint, in base units, for `base_amount` base units of the base mint at a
+/// price of `price * 10^expo` quote tokens per base token (both in UI units), rounding up so the
+/// party being paid is never short changed
+pub fn quote_amount(
+    base_amount: u64,
+    price: u64,
+    expo: i32,
+    base_decimals: u8,
+    quote_decimals: u8,
+) -> Option<u64> {
+    let value = (base_amount as u128).checked_mul(price as u128)?;
+    let expo = expo
+        .checked_add(quote_decimals as i32)?
+        .checked_sub(base_decimals as i32)?;
+    scale(value, expo, true)
+}
+
+/// Fixed point scale of linear curve prices, in quote base units per base unit
+pub const CURVE_PRICE_SCALE: u128 = 1_000_000_000;
+
+/// Exponent of prices scaled by [`CURVE_PRICE_SCALE`], the one pools and templates default to
+pub const CURVE_PRICE_EXPO: i32 = -9;
+
+/// Cost in quote base units of buying `amount` base units along a linear curve whose price
+/// starts at `start_price` and grows by `slope` per base unit sold, after `filled` units were
+/// already sold. Both prices are scaled by [`CURVE_PRICE_SCALE`] and the cost is rounded up.
+pub fn linear_curve_cost(start_price: u64, slope: u64, filled: u64, amount: u64) -> Option<u64> {
+    let amount = amount as u128;
+    let flat = (start_price as u128).checked_mul(amount)?;
+    // slope * ((filled + amount)^2 - filled^2) / 2
+    let area = amount.checked_mul((filled as u128).checked_mul(2)?.checked_add(amount)?)?;
+    let sloped = (slope as u128).checked_mul(area)?;
+    let scaled = flat
+        .checked_mul(2)?
+        .checked_add(sloped)?
+        .checked_add(2 * CURVE_PRICE_SCALE - 1)?
+        / (2 * CURVE_PRICE_SCALE);
+    scaled.try_into().ok()
+}
+
+/// Cost in quote base units of `amount` base units at a flat `price` scaled by
+/// [`CURVE_PRICE_SCALE`], rounded up
+pub fn fixed_price_cost(price: u64, amount: u64) -> Option<u64> {
+    linear_curve_cost(price, 0, 0, amount)
+}
+
+/// Cost in quote base units of `amount` base units at `price * 10^expo` quote base units per
+/// base unit, rounded up
+pub fn exponent_price_cost(price: u64, expo: i32, amount: u64) -> Option<u64> {
+    quote_amount(amount, price, expo, 0, 0)
+}
+
+/// Mantissa of the price `price * 10^expo` written with the exponent `to_expo`, `None` when it
+/// overflows or would lose precision
+pub fn rescale_price(price: u64, expo: i32, to_expo: i32) -> Option<u64> {
+    if to_expo <= expo {
+        let factor = 10u64.checked_pow(expo.checked_sub(to_expo)?.try_into().ok()?)?;
+        price.checked_mul(factor)
+    } else {
+        let divisor = 10u64.checked_pow(to_expo.checked_sub(expo)?.try_into().ok()?);
+        match divisor {
+            // refuses to drop digits of the mantissa
+            Some(divisor) if price / divisor * divisor == price => Some(price / divisor),
+            // only a zero price survives a divisor past u64::MAX
+            None if price == 0 => Some(0),
+            _ => None,
+        }
+    }
+}
+
+/// `shares / total_shares` of `amount`, rounded down
+pub fn pro_rata(amount: u64, shares: u64, total_shares: u64) -> Option<u64> {
+    if total_shares == 0 {
+        return None;
+    }
+    ((amount as u128 * shares as u128) / total_shares as u128)
+        .try_into()
+        .ok()
+}
+
+/// Largest fee in basis points, the whole amount
+pub const MAX_FEE_BPS: u16 = 10_000;
+
+/// `fee_bps` basis points of `amount`, rounded down
+pub fn fee_amount(amount: u64, fee_bps: u16) -> Option<u64> {
+    pro_rata(amount, fee_bps as u64, MAX_FEE_BPS as u64)
+}
+
+/// Share of `total_price` owed for buying `fill` more units of a `total_amount` deposit of which
+/// `filled` units were already sold. Rounds the running total up, so slices always add up to
+/// exactly `total_price`.
+pub fn prorated_cost(total_price: u64, total_amount: u64, filled: u64, fill: u64) -> Option<u64> {
+    if total_amount == 0 {
+        return None;
+    }
+    let paid = ceil_div(total_price as u128 * filled as u128, total_amount as u128)?;
+    let owed = ceil_div(
+        total_price as u128 * (filled as u128).checked_add(fill as u128)?,
+        total_amount as u128,
+    )?;
+    owed.checked_sub(paid)?.try_into().ok()
+}
+
+fn ceil_div(value: u128, divisor: u128) -> Option<u128> {
+    Some(value.checked_add(divisor.checked_sub(1)?)? / divisor)
+}
+
+/// `value * 10^expo`, rounding up or down when `expo` is negative
+fn scale(value: u128, expo: i32, round_up: bool) -> Option<u64> {
+    let scaled = if expo >= 0 {
+        value.checked_mul(10u128.checked_pow(expo as u32)?)?
+    } else {
+        match 10u128.checked_pow(expo.unsigned_abs()) {
+            Some(divisor) if round_up => value.checked_add(divisor - 1)? / divisor,
+            Some(divisor) => value / divisor,
+            // dividing by more than u128::MAX leaves at most one unit
+            None if round_up && value > 0 => 1,
+            None => 0,
+        }
+    };
+    scaled.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_amount() {
+        assert_eq!(normalize_amount(1_500_000, 6, 6), Some(1_500_000));
+        assert_eq!(normalize_amount(1_500_000, 6, 9), Some(1_500_000_000));
+        assert_eq!(normalize_amount(1_500_000_000, 9, 6), Some(1_500_000));
+        // dust below the target precision is dropped
+        assert_eq!(normalize_amount(1_500_000_999, 9, 6), Some(1_500_000));
+        assert_eq!(normalize_amount(1, 0, 9), Some(1_000_000_000));
+        assert_eq!(normalize_amount(999_999_999, 9, 0), Some(0));
+        assert_eq!(normalize_amount(u64::MAX, 0, 1), None);
+        assert_eq!(normalize_amount(u64::MAX, 255, 0), Some(0));
+    }
+
+    #[test]
+    fn test_quote_amount_same_decimals() {
+        // 1.5 per token
+        assert_eq!(quote_amount(10, 15, -1, 0, 0), Some(15));
+        assert_eq!(quote_amount(10_000_000, 15, -1, 6, 6), Some(15_000_000));
+        // rounds up in favour of the party being paid
+        assert_eq!(quote_amount(3, 15, -1, 0, 0), Some(5));
+        assert_eq!(quote_amount(0, 15, -1, 6, 6), Some(0));
+    }
+
+    #[test]
+    fn test_quote_amount_cross_decimals() {
+        // 1.5 USDC (6 decimals) per TOKEN (9 decimals): 2 TOKEN -> 3 USDC
+        assert_eq!(
+            quote_amount(2_000_000_000, 150_000_000, -8, 9, 6),
+            Some(3_000_000)
+        );
+        // 1.5 TOKEN (9 decimals) per USDC (6 decimals): 2 USDC -> 3 TOKEN
+        assert_eq!(
+            quote_amount(2_000_000, 150_000_000, -8, 6, 9),
+            Some(3_000_000_000)
+        );
+        // 0 decimal NFT priced at 25 SOL (9 decimals)
+        assert_eq!(quote_amount(1, 25, 0, 0, 9), Some(25_000_000_000));
+        // 0.00002 BONK-style price with 5 decimals
+        assert_eq!(quote_amount(1_000_000, 2, -5, 5, 6), Some(200));
+    }
+
+    #[test]
+    fn test_quote_amount_exhaustive_decimals() {
+        // one whole base token at a price of one always yields one whole quote token
+        for base_decimals in 0..=18u8 {
+            for quote_decimals in 0..=18u8 {
+                let one_base = 10u64.pow(base_decimals as u32);
+                let one_quote = 10u64.pow(quote_decimals as u32);
+                assert_eq!(
+                    quote_amount(one_base, 1, 0, base_decimals, quote_decimals),
+                    Some(one_quote),
+                    "base_decimals {} quote_decimals {}",
+                    base_decimals,
+                    quote_decimals
+                );
+                assert_eq!(
+                    normalize_amount(one_base, base_decimals, quote_decimals),
+                    Some(one_quote)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_linear_curve_cost() {
+        let one = CURVE_PRICE_SCALE as u64;
+        // flat price of 2
+        assert_eq!(linear_curve_cost(2 * one, 0, 0, 10), Some(20));
+        assert_eq!(linear_curve_cost(2 * one, 0, 500, 10), Some(20));
+        // price 1 + x: buying 0..10 costs 10 + 50
+        assert_eq!(linear_curve_cost(one, one, 0, 10), Some(60));
+        // buying 10..20 is more expensive
+        assert_eq!(linear_curve_cost(one, one, 10, 10), Some(160));
+        // splitting a fill never makes it cheaper
+        assert_eq!(linear_curve_cost(one, one, 0, 20), Some(60 + 160));
+        // fractional costs round up
+        assert_eq!(linear_curve_cost(one / 2, 0, 0, 1), Some(1));
+        assert_eq!(linear_curve_cost(0, 1, 0, 1), Some(1));
+        assert_eq!(linear_curve_cost(0, 0, 0, 1), Some(0));
+        assert_eq!(
+            linear_curve_cost(u64::MAX, u64::MAX, u64::MAX, u64::MAX),
+            None
+        );
+    }
+
+    #[test]
+    fn test_prorated_cost() {
+        assert_eq!(prorated_cost(150, 100, 0, 100), Some(150));
+        assert_eq!(prorated_cost(150, 100, 0, 40), Some(60));
+        assert_eq!(prorated_cost(150, 100, 40, 60), Some(90));
+        // uneven slices still add up to the full price
+        let slices = [1, 2, 3, 1, 2];
+        let mut filled = 0;
+        let mut paid = 0;
+        for fill in slices.iter() {
+            paid += prorated_cost(10, 9, filled, *fill).unwrap();
+            filled += fill;
+        }
+        assert_eq!(paid, 10);
+        assert_eq!(
+            prorated_cost(u64::MAX, u64::MAX, 0, u64::MAX),
+            Some(u64::MAX)
+        );
+        assert_eq!(prorated_cost(1, 0, 0, 0), None);
+    }
+
+    #[test]
+    fn test_pro_rata() {
+        assert_eq!(pro_rata(100, 25, 100), Some(25));
+        assert_eq!(pro_rata(99, 1, 2), Some(49));
+        assert_eq!(pro_rata(u64::MAX, u64::MAX, u64::MAX), Some(u64::MAX));
+        assert_eq!(pro_rata(u64::MAX, 2, 1), None);
+        assert_eq!(pro_rata(1, 1, 0), None);
+        assert_eq!(fixed_price_cost(CURVE_PRICE_SCALE as u64 * 2, 10), Some(20));
+    }
+
+    #[test]
+    fn test_fee_amount() {
+        assert_eq!(fee_amount(10_000, 30), Some(30));
+        // fees round down in favour of the payer
+        assert_eq!(fee_amount(333, 30), Some(0));
+        assert_eq!(fee_amount(u64::MAX, MAX_FEE_BPS), Some(u64::MAX));
+        assert_eq!(fee_amount(u64::MAX, 0), Some(0));
+    }
+
+    #[test]
+    fn test_quote_amount_overflow() {
+        assert_eq!(quote_amount(u64::MAX, u64::MAX, 0, 0, 0), None);
+        assert_eq!(quote_amount(u64::MAX, 1, 1, 0, 0), None);
+        assert_eq!(quote_amount(u64::MAX, 10, -1, 0, 0), Some(u64::MAX));
+        assert_eq!(quote_amount(1, 1, i32::MAX, 0, 1), None);
+        assert_eq!(quote_amount(1, 1, i32::MIN, 0, 0), Some(1));
+    }
+
+    #[test]
+    fn test_exponent_price_cost() {
+        // a 0 decimals NFT at 120 tokens of 9 decimals, past what a u64 scaled by 10^9 holds
+        assert_eq!(exponent_price_cost(12, 10, 1), Some(120_000_000_000));
+        assert!(rescale_price(12, 10, CURVE_PRICE_EXPO).is_none());
+        // the default exponent prices like the curve scale
+        assert_eq!(
+            exponent_price_cost(CURVE_PRICE_SCALE as u64 / 2, CURVE_PRICE_EXPO, 101),
+            fixed_price_cost(CURVE_PRICE_SCALE as u64 / 2, 101)
+        );
+        // a 9 decimals token at a billionth of a 0 decimals one per base unit, rounded up
+        assert_eq!(exponent_price_cost(1, -18, 1_500_000_000), Some(1));
+        assert_eq!(exponent_price_cost(7, i32::MAX, 1), None);
+
+        assert_eq!(rescale_price(5, -2, -9), Some(50_000_000));
+        assert_eq!(rescale_price(50_000_000, -9, -2), Some(5));
+        assert_eq!(rescale_price(50_000_001, -9, -2), None);
+        assert_eq!(rescale_price(0, i32::MIN, i32::MAX), None);
+        assert_eq!(rescale_price(0, -100, 0), Some(0));
+    }
+}
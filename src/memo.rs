@@ -0,0 +1,25 @@
+//! Program ids of the SPL Memo program, whose crate is not a dependency
+
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+solana_program::declare_id!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+pub mod v1 {
+    solana_program::declare_id!("Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo");
+}
+
+/// Longest memo an Exchange forwards, keeping the memo program's share of the compute budget small
+pub const MAX_SETTLEMENT_MEMO_LEN: usize = 256;
+
+pub fn is_memo_program(program_id: &Pubkey) -> bool {
+    *program_id == id() || *program_id == v1::id()
+}
+
+/// `spl_memo::build_memo` without signers, for the memo program at `program_id`
+pub fn build_memo(program_id: &Pubkey, memo: &[u8]) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![],
+        data: memo.to_vec(),
+    }
+}
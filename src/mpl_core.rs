@@ -0,0 +1,90 @@
+//! Assets of the Metaplex Core program, whose crate is not a dependency
+
+use std::convert::TryInto;
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+solana_program::declare_id!("CoREENxT6tW1HoK8ypY1SxRMZTcVPm7R94rH4PZNhX7d");
+
+const KEY_ASSET_V1: u8 = 1;
+const TRANSFER_V1: u8 = 14;
+
+/// Who can update an asset, an asset in a collection being updated by the collection's authority
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UpdateAuthority {
+    None,
+    Address(Pubkey),
+    Collection(Pubkey),
+}
+
+impl UpdateAuthority {
+    /// the collection the asset belongs to, if any
+    pub fn collection(&self) -> Option<&Pubkey> {
+        match self {
+            UpdateAuthority::Collection(collection) => Some(collection),
+            _ => None,
+        }
+    }
+}
+
+/// The start of an asset account, common to every asset whatever its plugins
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Asset {
+    pub owner: Pubkey,
+    pub update_authority: UpdateAuthority,
+}
+
+impl Asset {
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.first() != Some(&KEY_ASSET_V1) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let owner = read_pubkey(data, 1)?;
+        let update_authority = match data.get(33) {
+            Some(0) => UpdateAuthority::None,
+            Some(1) => UpdateAuthority::Address(read_pubkey(data, 34)?),
+            Some(2) => UpdateAuthority::Collection(read_pubkey(data, 34)?),
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(Asset {
+            owner,
+            update_authority,
+        })
+    }
+}
+
+/// Moves `asset` to `new_owner`, signed by `payer` and by `authority` unless the payer is the
+/// owner. Accounts left out are passed as the program id, as the program expects.
+pub fn transfer(
+    asset: &Pubkey,
+    collection: Option<&Pubkey>,
+    payer: &Pubkey,
+    authority: Option<&Pubkey>,
+    new_owner: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(*asset, false),
+            AccountMeta::new_readonly(*collection.unwrap_or(&id()), false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*authority.unwrap_or(&id()), authority.is_some()),
+            AccountMeta::new_readonly(*new_owner, false),
+            AccountMeta::new_readonly(id(), false),
+            AccountMeta::new_readonly(id(), false),
+        ],
+        // no compression proof
+        data: vec![TRANSFER_V1, 0],
+    }
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey, ProgramError> {
+    data.get(offset..offset + 32)
+        .and_then(|slice| slice.try_into().ok())
+        .map(Pubkey::new_from_array)
+        .ok_or(ProgramError::InvalidAccountData)
+}
@@ -0,0 +1,102 @@
+use std::convert::TryInto;
+
+use solana_program::{clock::Slot, program_error::ProgramError};
+
+use crate::{error::EscrowError, math};
+
+// the mainnet deployment of the Pyth oracle program, owning every price account read
+solana_program::declare_id!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+
+// Pyth v2 price account layout
+const MAGIC: u32 = 0xa1b2_c3d4;
+const VERSION: u32 = 2;
+const ACCOUNT_TYPE_PRICE: u32 = 3;
+const PRICE_STATUS_TRADING: u32 = 1;
+
+const OFFSET_MAGIC: usize = 0;
+const OFFSET_VERSION: usize = 4;
+const OFFSET_ACCOUNT_TYPE: usize = 8;
+const OFFSET_EXPO: usize = 20;
+const OFFSET_AGG_PRICE: usize = 208;
+const OFFSET_AGG_CONF: usize = 216;
+const OFFSET_AGG_STATUS: usize = 224;
+const OFFSET_AGG_PUB_SLOT: usize = 232;
+pub const PRICE_ACCOUNT_MIN_LEN: usize = 240;
+
+/// Aggregate price read from an oracle price account
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OraclePrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_slot: Slot,
+}
+
+impl OraclePrice {
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < PRICE_ACCOUNT_MIN_LEN
+            || read_u32(data, OFFSET_MAGIC) != MAGIC
+            || read_u32(data, OFFSET_VERSION) != VERSION
+            || read_u32(data, OFFSET_ACCOUNT_TYPE) != ACCOUNT_TYPE_PRICE
+        {
+            return Err(EscrowError::InvalidOracle.into());
+        }
+        if read_u32(data, OFFSET_AGG_STATUS) != PRICE_STATUS_TRADING {
+            return Err(EscrowError::InvalidOracle.into());
+        }
+
+        let price = read_u64(data, OFFSET_AGG_PRICE) as i64;
+        if price <= 0 {
+            return Err(EscrowError::InvalidOracle.into());
+        }
+
+        Ok(OraclePrice {
+            price,
+            conf: read_u64(data, OFFSET_AGG_CONF),
+            expo: read_u32(data, OFFSET_EXPO) as i32,
+            publish_slot: read_u64(data, OFFSET_AGG_PUB_SLOT),
+        })
+    }
+
+    /// fails if the price was published more than `max_staleness` slots before `current_slot`
+    pub fn check_staleness(
+        &self,
+        current_slot: Slot,
+        max_staleness: u64,
+    ) -> Result<(), EscrowError> {
+        match current_slot.checked_sub(self.publish_slot) {
+            Some(age) if age > max_staleness => Err(EscrowError::OracleStale),
+            _ => Ok(()),
+        }
+    }
+
+    /// fails if conf / price is wider than `max_confidence_bps`
+    pub fn check_confidence(&self, max_confidence_bps: u16) -> Result<(), EscrowError> {
+        let conf_bps = (self.conf as u128) * 10_000;
+        let max = (self.price as u128) * (max_confidence_bps as u128);
+        if conf_bps > max {
+            return Err(EscrowError::OracleConfidenceTooWide);
+        }
+        Ok(())
+    }
+
+    /// amount of the quote token (in base units) for `base_amount` base units, with the price
+    /// quoted in UI units of each mint
+    pub fn quote(&self, base_amount: u64, base_decimals: u8, quote_decimals: u8) -> Option<u64> {
+        math::quote_amount(
+            base_amount,
+            self.price as u64,
+            self.expo,
+            base_decimals,
+            quote_decimals,
+        )
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
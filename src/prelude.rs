@@ -0,0 +1,20 @@
+//! The types and helpers most integrations use, brought in with
+//! `use paulx_solana_escrow::prelude::*;` without knowing which module each lives in. Program
+//! accounts find their addresses through their `find_address`, client helpers come with the
+//! `client` feature.
+
+pub use crate::{
+    error::EscrowError,
+    instruction::{EscrowInstruction, EscrowTerms, ExchangeAccounts},
+    processor::Processor,
+    state::{
+        Config, Escrow, EscrowAccountData, EscrowView, FillStatus, MintList, MintVolume,
+        RelayNonce, Session,
+    },
+};
+
+#[cfg(feature = "client")]
+pub use crate::client::{
+    exchange_instruction, fetch_escrow, fetch_program_account, get_escrow_instruction, view_escrow,
+    with_settlement_memo, EscrowRpc,
+};
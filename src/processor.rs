@@ -1,36 +1,543 @@
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    ed25519_program,
     entrypoint::ProgramResult,
+    hash::hashv,
+    log::sol_log_64,
     msg,
-    program::{invoke, invoke_signed},
+    program::{get_return_data, invoke, invoke_signed, set_return_data},
     program_error::ProgramError,
+    program_option::COption,
     program_pack::{IsInitialized, Pack},
-    pubkey::Pubkey,
+    pubkey::{Pubkey, PUBKEY_BYTES},
     rent::Rent,
-    sysvar::Sysvar,
+    system_instruction,
+    sysvar::{self, clock::Clock, Sysvar},
 };
 
-use spl_token::state::Account as TokenAccount;
+use spl_token::state::{Account as TokenAccount, Mint};
 
-use crate::{error::EscrowError, instruction::EscrowInstruction, state::Escrow};
+use crate::{
+    bubblegum::{self, Leaf},
+    error::{ErrorDetail, EscrowError},
+    event::{EscrowEvent, SettlementSummary, TokenAmount},
+    instruction::{EscrowInstruction, EscrowTerms},
+    math, memo, mpl_core,
+    oracle::{self, OraclePrice},
+    relay::{self, RelayIntent, RelayedAction},
+    state::{
+        Bid, CnftEscrow, Config, CoreEscrow, Escrow, EscrowView, MintList, MintVolume, Pool,
+        PoolShare, Quote, Recovery, RelayNonce, ReleaseCondition, Request, Session, Template,
+        Ticket, SESSION_CAN_CANCEL, SESSION_CAN_CREATE,
+    },
+    token_cpi::TokenCpi,
+    token_metadata,
+};
+
+/// Lamports of the escrow account's rent paid to the taker whose Exchange closes the escrow
+pub const TAKER_CLOSE_REBATE_LAMPORTS: u64 = 5_000;
+
+/// Lamports of the escrow account's rent paid to whoever reclaims an expired escrow
+pub const RECLAIM_BOUNTY_LAMPORTS: u64 = 5_000;
+
+/// Seconds between an admin proposing a recovery and being able to execute it
+pub const RECOVERY_TIMELOCK_SECONDS: i64 = 7 * 24 * 60 * 60;
+
+/// The fixed accounts of an Exchange, checked against the escrow and each deserialized once
+#[derive(Clone, Copy)]
+struct ExchangeContext<'a, 'b> {
+    taker: &'a AccountInfo<'b>,
+    takers_sending_token_account: &'a AccountInfo<'b>,
+    takers_token_to_receive_account: &'a AccountInfo<'b>,
+    pdas_temp_token_account: &'a AccountInfo<'b>,
+    pdas_temp_token_account_info: TokenAccount,
+    initializers_main_account: &'a AccountInfo<'b>,
+    initializers_token_to_receive_account: &'a AccountInfo<'b>,
+    escrow_account: &'a AccountInfo<'b>,
+    token_program: &'a AccountInfo<'b>,
+    pda_account: &'a AccountInfo<'b>,
+    bump_seed: u8,
+}
+
+impl<'a, 'b> ExchangeContext<'a, 'b> {
+    /// Takes the fixed accounts from `account_info_iter`, the taker only having to sign when the
+    /// exchange is not `relayed`. The escrow's state comes boxed, it being the largest value on
+    /// Exchange's stack.
+    fn new(
+        account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        relayed: bool,
+        program_id: &Pubkey,
+    ) -> Result<(Self, Box<Escrow>), ProgramError> {
+        let taker = next_account_info(account_info_iter)?;
+        if !taker.is_signer && !relayed {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let takers_sending_token_account = next_account_info(account_info_iter)?;
+        let takers_token_to_receive_account = next_account_info(account_info_iter)?;
+
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let pdas_temp_token_account_info =
+            TokenAccount::unpack(&pdas_temp_token_account.data.borrow())?;
+
+        let initializers_main_account = next_account_info(account_info_iter)?;
+        let initializers_token_to_receive_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let escrow_info = Box::new(Processor::unpack_program_account::<Escrow>(
+            escrow_account,
+            program_id,
+        )?);
+
+        Processor::check_key(
+            "error: escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key",
+            &escrow_info.temp_token_account_pubkey,
+            pdas_temp_token_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        Processor::check_key(
+            "error: escrow_info.initializer_pubkey != *initializers_main_account.key",
+            &escrow_info.initializer_pubkey,
+            initializers_main_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        Processor::check_key(
+            "error: escrow_info.initializer_token_to_receive_account_pubkey != *initializers_token_to_receive_account.key",
+            &escrow_info.initializer_token_to_receive_account_pubkey,
+            initializers_token_to_receive_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+
+        let token_program = next_account_info(account_info_iter)?;
+        Processor::check_token_program(token_program)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Processor::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+
+        let context = Self {
+            taker,
+            takers_sending_token_account,
+            takers_token_to_receive_account,
+            pdas_temp_token_account,
+            pdas_temp_token_account_info,
+            initializers_main_account,
+            initializers_token_to_receive_account,
+            escrow_account,
+            token_program,
+            pda_account,
+            bump_seed,
+        };
+        Ok((context, escrow_info))
+    }
+}
 
 pub struct Processor;
 impl Processor {
+    /// Runs the instruction in `instruction_data`. A failed instruction leaves an `ErrorDetail`
+    /// as return data for simulations; the runtime aborts the whole transaction when a CPI fails,
+    /// so calling programs never see it.
     pub fn process(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         instruction_data: &[u8],
     ) -> ProgramResult {
+        let result = Self::dispatch(program_id, accounts, instruction_data);
+        if let Err(error) = &result {
+            // a failed check left the offending account's key as return data, other failures
+            // left nothing since `dispatch` cleared it
+            let account_index = get_return_data()
+                .filter(|(program, key)| program == program_id && key.len() == PUBKEY_BYTES)
+                .and_then(|(_, key)| {
+                    accounts
+                        .iter()
+                        .position(|account| account.key.as_ref() == &key[..])
+                });
+            set_return_data(&ErrorDetail::new(error.clone(), account_index).pack());
+        }
+        result
+    }
+
+    fn dispatch(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction_data: &[u8],
+    ) -> ProgramResult {
+        // an earlier instruction's blame must not name an account of this one
+        set_return_data(&[]);
+        // unit tests run the processor under throwaway program ids
+        if !cfg!(any(test, feature = "any-program-id")) {
+            Self::check_program_id(program_id)?;
+        }
         let instruction = EscrowInstruction::unpack(instruction_data)?;
 
         match instruction {
-            EscrowInstruction::InitEscrow { amount } => {
+            EscrowInstruction::InitEscrow { amount, terms } => {
                 msg!("Instruction: InitEscrow");
-                Self::process_init_escrow(accounts, amount, program_id)
+                Self::process_init_escrow(accounts, amount, &terms, program_id)
             }
-            EscrowInstruction::Exchange { amount } => {
+            EscrowInstruction::Exchange { amount, memo } => {
                 msg!("Instruction: Exchange");
-                Self::process_exchange(accounts, amount, program_id)
+                match memo {
+                    Some(memo) => {
+                        Self::process_exchange_with_memo(accounts, amount, &memo, program_id)
+                    }
+                    None => Self::process_exchange(accounts, amount, program_id),
+                }
+            }
+            EscrowInstruction::InitCurveEscrow { start_price, slope } => {
+                msg!("Instruction: InitCurveEscrow");
+                Self::process_init_curve_escrow(accounts, start_price, slope, program_id)
+            }
+            EscrowInstruction::InitCrowdfillEscrow { amount } => {
+                msg!("Instruction: InitCrowdfillEscrow");
+                Self::process_init_crowdfill_escrow(accounts, amount, program_id)
+            }
+            EscrowInstruction::InitPool { price, price_expo } => {
+                msg!("Instruction: InitPool");
+                Self::process_init_pool(accounts, price, price_expo, program_id)
+            }
+            EscrowInstruction::PoolDeposit { amount } => {
+                msg!("Instruction: PoolDeposit");
+                Self::process_pool_deposit(accounts, amount, program_id)
+            }
+            EscrowInstruction::PoolWithdraw { shares } => {
+                msg!("Instruction: PoolWithdraw");
+                Self::process_pool_withdraw(accounts, shares, program_id)
+            }
+            EscrowInstruction::PoolExchange { amount } => {
+                msg!("Instruction: PoolExchange");
+                Self::process_pool_exchange(accounts, amount, program_id)
+            }
+            EscrowInstruction::Cancel => {
+                msg!("Instruction: Cancel");
+                Self::process_cancel(accounts, program_id)
+            }
+            EscrowInstruction::Reserve => {
+                msg!("Instruction: Reserve");
+                Self::process_reserve(accounts, program_id)
+            }
+            EscrowInstruction::CommitExchange { amount } => {
+                msg!("Instruction: CommitExchange");
+                Self::process_commit_exchange(accounts, amount, program_id)
+            }
+            EscrowInstruction::SettleExchange => {
+                msg!("Instruction: SettleExchange");
+                Self::process_settle_exchange(accounts, program_id)
+            }
+            EscrowInstruction::UnwindExchange => {
+                msg!("Instruction: UnwindExchange");
+                Self::process_unwind_exchange(accounts, program_id)
+            }
+            EscrowInstruction::InitConfig { default_fee_bps } => {
+                msg!("Instruction: InitConfig");
+                Self::process_init_config(accounts, default_fee_bps, program_id)
+            }
+            EscrowInstruction::SetMintFee { fee_bps } => {
+                msg!("Instruction: SetMintFee");
+                Self::process_set_mint_fee(accounts, fee_bps, program_id)
+            }
+            EscrowInstruction::SetLamportFee { lamport_fee } => {
+                msg!("Instruction: SetLamportFee");
+                Self::process_set_lamport_fee(accounts, lamport_fee, program_id)
+            }
+            EscrowInstruction::InitMintList { is_allow_list } => {
+                msg!("Instruction: InitMintList");
+                Self::process_init_mint_list(accounts, is_allow_list, program_id)
+            }
+            EscrowInstruction::SetMintListed { listed } => {
+                msg!("Instruction: SetMintListed");
+                Self::process_set_mint_listed(accounts, listed, program_id)
+            }
+            EscrowInstruction::InitMintVolume => {
+                msg!("Instruction: InitMintVolume");
+                Self::process_init_mint_volume(accounts, program_id)
+            }
+            EscrowInstruction::ReclaimExpired => {
+                msg!("Instruction: ReclaimExpired");
+                Self::process_reclaim_expired(accounts, program_id)
+            }
+            EscrowInstruction::ProcessExpiry => {
+                msg!("Instruction: ProcessExpiry");
+                Self::process_expiry(accounts, program_id)
+            }
+            EscrowInstruction::InitTemplate {
+                price,
+                price_expo,
+                expiry_slots,
+                allowed_taker,
+            } => {
+                msg!("Instruction: InitTemplate");
+                Self::process_init_template(
+                    accounts,
+                    price,
+                    price_expo,
+                    expiry_slots,
+                    allowed_taker,
+                    program_id,
+                )
+            }
+            EscrowInstruction::InitFromTemplate => {
+                msg!("Instruction: InitFromTemplate");
+                Self::process_init_from_template(accounts, program_id)
+            }
+            EscrowInstruction::InitAuctionEscrow {
+                reserve_price,
+                bid_slots,
+                reveal_slots,
+            } => {
+                msg!("Instruction: InitAuctionEscrow");
+                Self::process_init_auction_escrow(
+                    accounts,
+                    reserve_price,
+                    bid_slots,
+                    reveal_slots,
+                    program_id,
+                )
+            }
+            EscrowInstruction::CommitBid { commitment } => {
+                msg!("Instruction: CommitBid");
+                Self::process_commit_bid(accounts, commitment, program_id)
+            }
+            EscrowInstruction::RevealBid { amount, salt } => {
+                msg!("Instruction: RevealBid");
+                Self::process_reveal_bid(accounts, amount, &salt, program_id)
+            }
+            EscrowInstruction::SettleAuction => {
+                msg!("Instruction: SettleAuction");
+                Self::process_settle_auction(accounts, program_id)
+            }
+            EscrowInstruction::RefundBid => {
+                msg!("Instruction: RefundBid");
+                Self::process_refund_bid(accounts, program_id)
+            }
+            EscrowInstruction::InitRaffleEscrow {
+                ticket_price,
+                sale_slots,
+            } => {
+                msg!("Instruction: InitRaffleEscrow");
+                Self::process_init_raffle_escrow(accounts, ticket_price, sale_slots, program_id)
+            }
+            EscrowInstruction::BuyTicket => {
+                msg!("Instruction: BuyTicket");
+                Self::process_buy_ticket(accounts, program_id)
+            }
+            EscrowInstruction::DrawRaffle => {
+                msg!("Instruction: DrawRaffle");
+                Self::process_draw_raffle(accounts, program_id)
+            }
+            EscrowInstruction::CloseTicket => {
+                msg!("Instruction: CloseTicket");
+                Self::process_close_ticket(accounts, program_id)
+            }
+            EscrowInstruction::InitRequest { min_amount } => {
+                msg!("Instruction: InitRequest");
+                Self::process_init_request(accounts, min_amount, program_id)
+            }
+            EscrowInstruction::PostQuote => {
+                msg!("Instruction: PostQuote");
+                Self::process_post_quote(accounts, program_id)
+            }
+            EscrowInstruction::AcceptQuote => {
+                msg!("Instruction: AcceptQuote");
+                Self::process_accept_quote(accounts, program_id)
+            }
+            EscrowInstruction::CancelRequest => {
+                msg!("Instruction: CancelRequest");
+                Self::process_cancel_request(accounts, program_id)
+            }
+            EscrowInstruction::WithdrawQuote => {
+                msg!("Instruction: WithdrawQuote");
+                Self::process_withdraw_quote(accounts, program_id)
+            }
+            EscrowInstruction::SetChainedOffer { expected_amount } => {
+                msg!("Instruction: SetChainedOffer");
+                Self::process_set_chained_offer(accounts, expected_amount, program_id)
+            }
+            EscrowInstruction::InitCnftEscrow {
+                expected_amount,
+                root,
+                leaf,
+            } => {
+                msg!("Instruction: InitCnftEscrow");
+                Self::process_init_cnft_escrow(accounts, expected_amount, &root, leaf, program_id)
+            }
+            EscrowInstruction::ExchangeCnft { root } => {
+                msg!("Instruction: ExchangeCnft");
+                Self::process_exchange_cnft(accounts, &root, program_id)
+            }
+            EscrowInstruction::CancelCnft { root } => {
+                msg!("Instruction: CancelCnft");
+                Self::process_cancel_cnft(accounts, &root, program_id)
+            }
+            EscrowInstruction::InitCoreEscrow { expected_amount } => {
+                msg!("Instruction: InitCoreEscrow");
+                Self::process_init_core_escrow(accounts, expected_amount, program_id)
+            }
+            EscrowInstruction::ExchangeCore => {
+                msg!("Instruction: ExchangeCore");
+                Self::process_exchange_core(accounts, program_id)
+            }
+            EscrowInstruction::CancelCore => {
+                msg!("Instruction: CancelCore");
+                Self::process_cancel_core(accounts, program_id)
+            }
+            EscrowInstruction::RelayedExchange { amount, nonce } => {
+                msg!("Instruction: RelayedExchange");
+                Self::process_relayed_exchange(accounts, amount, nonce, program_id)
+            }
+            EscrowInstruction::RelayedCancel { nonce } => {
+                msg!("Instruction: RelayedCancel");
+                Self::process_relayed_cancel(accounts, nonce, program_id)
+            }
+            EscrowInstruction::SetReceiveAccount => {
+                msg!("Instruction: SetReceiveAccount");
+                Self::process_set_receive_account(accounts, program_id)
+            }
+            EscrowInstruction::SetOperator { operator } => {
+                msg!("Instruction: SetOperator");
+                Self::process_set_operator(accounts, &operator, program_id)
+            }
+            EscrowInstruction::Reprice { expected_amount } => {
+                msg!("Instruction: Reprice");
+                Self::process_reprice(accounts, expected_amount, program_id)
+            }
+            EscrowInstruction::WithdrawDeposit { amount } => {
+                msg!("Instruction: WithdrawDeposit");
+                Self::process_withdraw_deposit(accounts, amount, program_id)
+            }
+            EscrowInstruction::OperatorCancel => {
+                msg!("Instruction: OperatorCancel");
+                Self::process_operator_cancel(accounts, program_id)
+            }
+            EscrowInstruction::CreateSession {
+                expires_at_slot,
+                permissions,
+            } => {
+                msg!("Instruction: CreateSession");
+                Self::process_create_session(accounts, expires_at_slot, permissions, program_id)
+            }
+            EscrowInstruction::CloseSession => {
+                msg!("Instruction: CloseSession");
+                Self::process_close_session(accounts, program_id)
+            }
+            EscrowInstruction::SessionInitEscrow { amount, terms } => {
+                msg!("Instruction: SessionInitEscrow");
+                Self::process_session_init_escrow(accounts, amount, &terms, program_id)
+            }
+            EscrowInstruction::SessionCancel => {
+                msg!("Instruction: SessionCancel");
+                Self::process_session_cancel(accounts, program_id)
+            }
+            EscrowInstruction::SetMinDeposit { min_deposit_amount } => {
+                msg!("Instruction: SetMinDeposit");
+                Self::process_set_min_deposit(accounts, min_deposit_amount, program_id)
+            }
+            EscrowInstruction::SetMintMinimum { min_amount } => {
+                msg!("Instruction: SetMintMinimum");
+                Self::process_set_mint_minimum(accounts, min_amount, program_id)
+            }
+            EscrowInstruction::SweepDust => {
+                msg!("Instruction: SweepDust");
+                Self::process_sweep_dust(accounts, program_id)
+            }
+            EscrowInstruction::AssertInvariants => {
+                msg!("Instruction: AssertInvariants");
+                Self::process_assert_invariants(accounts, program_id)
+            }
+            EscrowInstruction::ProposeRecovery => {
+                msg!("Instruction: ProposeRecovery");
+                Self::check_admin_recovery()?;
+                Self::process_propose_recovery(accounts, program_id)
+            }
+            EscrowInstruction::CancelRecovery => {
+                msg!("Instruction: CancelRecovery");
+                Self::check_admin_recovery()?;
+                Self::process_cancel_recovery(accounts, program_id)
+            }
+            EscrowInstruction::ExecuteRecovery => {
+                msg!("Instruction: ExecuteRecovery");
+                Self::check_admin_recovery()?;
+                Self::process_execute_recovery(accounts, program_id)
+            }
+            EscrowInstruction::SetAdmin => {
+                msg!("Instruction: SetAdmin");
+                Self::process_set_admin(accounts, program_id)
+            }
+            EscrowInstruction::GuardedExchange {
+                amount,
+                created_before_slot,
+                not_after_slot,
+                memo,
+            } => {
+                msg!("Instruction: GuardedExchange");
+                let (clock_sysvar, exchange_accounts) = accounts
+                    .split_first()
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?;
+                Self::check_exchange_guard(
+                    clock_sysvar,
+                    exchange_accounts,
+                    created_before_slot,
+                    not_after_slot,
+                    program_id,
+                )?;
+                match memo {
+                    Some(memo) => Self::process_exchange_with_memo(
+                        exchange_accounts,
+                        amount,
+                        &memo,
+                        program_id,
+                    ),
+                    None => Self::process_exchange(exchange_accounts, amount, program_id),
+                }
+            }
+            EscrowInstruction::TermsCheckedExchange {
+                amount,
+                terms_hash,
+                memo,
+            } => {
+                msg!("Instruction: TermsCheckedExchange");
+                Self::check_terms_hash(accounts, &terms_hash, program_id)?;
+                match memo {
+                    Some(memo) => {
+                        Self::process_exchange_with_memo(accounts, amount, &memo, program_id)
+                    }
+                    None => Self::process_exchange(accounts, amount, program_id),
+                }
+            }
+            EscrowInstruction::InitMarket { default_fee_bps } => {
+                msg!("Instruction: InitMarket");
+                Self::process_init_market(accounts, default_fee_bps, program_id)
+            }
+            EscrowInstruction::CappedExchange {
+                amount,
+                max_payment,
+                memo,
+            } => {
+                msg!("Instruction: CappedExchange");
+                match memo {
+                    Some(memo) => Self::exchange_with_memo(
+                        accounts,
+                        amount,
+                        Some(max_payment),
+                        &memo,
+                        program_id,
+                    ),
+                    None => {
+                        Self::process_capped_exchange(accounts, amount, max_payment, program_id)
+                    }
+                }
+            }
+            EscrowInstruction::SetMarketPaused { paused } => {
+                msg!("Instruction: SetMarketPaused");
+                Self::process_set_market_paused(accounts, paused, program_id)
+            }
+            EscrowInstruction::GetEscrow => {
+                msg!("Instruction: GetEscrow");
+                Self::process_get_escrow(accounts, program_id)
             }
         }
     }
@@ -38,6 +545,22 @@ impl Processor {
     pub fn process_init_escrow(
         accounts: &[AccountInfo],
         amount: u64,
+        terms: &EscrowTerms,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let initializer = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+        Self::init_escrow(accounts, initializer.key, amount, terms, None, program_id)
+    }
+
+    /// Opens an escrow owed to `maker` on the accounts of InitEscrow, whose signing initializer
+    /// owns the vault. The escrow is dated by `slot`, or else by the optional clock sysvar among
+    /// the `accounts`.
+    fn init_escrow(
+        accounts: &[AccountInfo],
+        maker: &Pubkey,
+        amount: u64,
+        terms: &EscrowTerms,
+        slot: Option<u64>,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -63,6 +586,9 @@ impl Processor {
         if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
             return Err(EscrowError::NotRentExempt.into());
         }
+        if escrow_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
 
         // deserialize the data
         let mut escrow_info = Escrow::unpack_unchecked(&escrow_account.data.borrow())?;
@@ -72,26 +598,152 @@ impl Processor {
 
         // write date to escrow state
         escrow_info.is_initialized = true;
-        escrow_info.initializer_pubkey = *initializer.key;
+        escrow_info.initializer_pubkey = *maker;
         escrow_info.temp_token_account_pubkey = *temp_token_account.key;
         escrow_info.initializer_token_to_receive_account_pubkey = *token_to_receive_account.key;
         escrow_info.expected_amount = amount;
+        escrow_info.cancel_locked_until_slot = terms.cancel_locked_until_slot;
+        escrow_info.reserve_bond_lamports = terms.reserve_bond_lamports;
+        escrow_info.reserve_slots = terms.reserve_slots;
+        escrow_info.settle_timeout_slots = terms.settle_timeout_slots;
+        escrow_info.exchange_top_level_only = terms.exchange_top_level_only;
+        escrow_info.metadata_uri = terms.metadata_uri;
+        escrow_info.memo_reference = terms.memo_reference;
+        escrow_info.expires_at_slot = terms.expires_at_slot;
+        escrow_info.allowed_taker = terms.allowed_taker;
+        escrow_info.relist_amount = terms.relist_amount;
+        escrow_info.relist_source_pubkey = terms.relist_source_pubkey;
+        let split_bps = terms
+            .split_recipients
+            .iter()
+            .map(|recipient| recipient.share_bps as u64)
+            .sum::<u64>();
+        if split_bps > math::MAX_FEE_BPS as u64
+            || terms.split_recipients.iter().any(|recipient| {
+                recipient.share_bps > 0 && recipient.token_account == Pubkey::default()
+            })
+        {
+            return Err(EscrowError::InvalidSplit.into());
+        }
+        escrow_info.split_recipients = terms.split_recipients;
+        // only Exchange checks the condition, SettleExchange would release without it
+        if terms.release_condition.is_set() && terms.settle_timeout_slots != 0 {
+            msg!("error: conditional escrows can not be two-phase");
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        escrow_info.release_condition = terms.release_condition;
+        if terms.release_before_timestamp != 0
+            && terms.release_before_timestamp <= terms.release_after_timestamp
+        {
+            msg!("error: the release window closes before it opens");
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        escrow_info.release_after_timestamp = terms.release_after_timestamp;
+        escrow_info.release_before_timestamp = terms.release_before_timestamp;
+
+        let token_program = next_account_info(account_info_iter)?;
+        Self::check_token_program(token_program)?;
+        Self::check_key(
+            "error: the token program does not own the vault",
+            token_program.key,
+            temp_token_account.owner,
+            ProgramError::IncorrectProgramId,
+        )?;
+        escrow_info.receive_mint =
+            TokenAccount::unpack(&token_to_receive_account.data.borrow())?.mint;
+
+        let mint_list_account = next_account_info(account_info_iter)?;
+        Self::check_mint_list(
+            mint_list_account,
+            &[temp_token_account, token_to_receive_account],
+            &terms.market,
+            program_id,
+        )?;
+        escrow_info.market = terms.market;
+        escrow_info.tag = terms.tag;
+
+        // a clock sysvar dates the escrow for GuardedExchange
+        let mut optional_account = account_info_iter.next();
+        if let Some(clock_sysvar) =
+            optional_account.filter(|account| sysvar::clock::check_id(account.key))
+        {
+            escrow_info.revised_at_slot = Clock::from_account_info(clock_sysvar)?.slot;
+            optional_account = account_info_iter.next();
+        }
+        if let Some(slot) = slot {
+            escrow_info.revised_at_slot = slot;
+        }
+
+        // an oracle account makes the escrow oracle priced
+        if let Some(oracle_account) = optional_account {
+            Self::check_oracle_owner(oracle_account)?;
+            OraclePrice::unpack(&oracle_account.data.borrow())?;
+            escrow_info.oracle_pubkey = *oracle_account.key;
+            escrow_info.oracle_max_staleness_slots = terms.oracle_max_staleness_slots;
+            escrow_info.oracle_max_confidence_bps = terms.oracle_max_confidence_bps;
+
+            // the oracle quotes in UI units, so both mints' decimals are needed to settle
+            let deposit_mint = next_account_info(account_info_iter)?;
+            let receive_mint = next_account_info(account_info_iter)?;
+            let temp_token_account_info = TokenAccount::unpack(&temp_token_account.data.borrow())?;
+            let token_to_receive_account_info =
+                TokenAccount::unpack(&token_to_receive_account.data.borrow())?;
+            if temp_token_account_info.mint != *deposit_mint.key
+                || token_to_receive_account_info.mint != *receive_mint.key
+            {
+                msg!("error: mint accounts do not match the token accounts");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            escrow_info.deposit_mint_decimals = Self::unpack_mint(deposit_mint)?.decimals;
+            escrow_info.receive_mint_decimals = Self::unpack_mint(receive_mint)?.decimals;
+        }
+
+        // the deposit is what the vault holds now, changing its owner moves no tokens
+        escrow_info.deposited_amount =
+            TokenAccount::unpack(&temp_token_account.data.borrow())?.amount;
+        Self::check_escrow_size(
+            mint_list_account,
+            &TokenAccount::unpack(&temp_token_account.data.borrow())?,
+            &escrow_info,
+            program_id,
+        )?;
 
         // write date to escrow state/data account
+        escrow_info.terms_hash = escrow_info.hash_terms();
         Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;
 
         // PDA (Program Derived Address) with a static seed
         let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
 
+        // the PDA closes the vault once it is emptied, which a close authority left on the account
+        // would prevent, so clear it while the initializer still owns the account. Another
+        // holder of the close authority makes the token program refuse.
+        let close_authority_ix = spl_token::instruction::set_authority(
+            token_program.key,
+            temp_token_account.key,
+            None,
+            spl_token::instruction::AuthorityType::CloseAccount,
+            initializer.key,
+            &[initializer.key],
+        )?;
+        msg!("Calling the token program to clear the close authority...");
+        invoke(
+            &close_authority_ix,
+            &[
+                temp_token_account.clone(),
+                initializer.clone(),
+                token_program.clone(),
+            ],
+        )?;
+
         // CPI (Cross Program-Invocation)
-        let token_program = next_account_info(account_info_iter)?;
         let owner_change_ix = spl_token::instruction::set_authority(
             token_program.key,
             temp_token_account.key,
             Some(&pda),
             spl_token::instruction::AuthorityType::AccountOwner,
             initializer.key,
-            &[&initializer.key],
+            &[initializer.key],
         )?;
 
         msg!("Calling the token program to transfer ownership...");
@@ -102,7 +754,75 @@ impl Processor {
                 initializer.clone(),
                 token_program.clone(),
             ],
-        )
+        )?;
+
+        let vault_info = TokenAccount::unpack(&temp_token_account.data.borrow())?;
+        EscrowEvent::Opened {
+            escrow: *escrow_account.key,
+            initializer: *maker,
+            vault: *temp_token_account.key,
+            receive_account: *token_to_receive_account.key,
+            deposit_mint: vault_info.mint,
+            receive_mint: escrow_info.receive_mint,
+            deposit_amount: vault_info.amount,
+            expected_amount: amount,
+            expires_at_slot: terms.expires_at_slot,
+        }
+        .emit();
+        Ok(())
+    }
+
+    /// whether InitEscrow `accounts` go on past the mint list and the optional clock sysvar to
+    /// the oracle accounts
+    fn inits_oracle_priced(accounts: &[AccountInfo]) -> bool {
+        accounts
+            .iter()
+            .skip(7)
+            .any(|account| !sysvar::clock::check_id(account.key))
+    }
+
+    pub fn process_init_curve_escrow(
+        accounts: &[AccountInfo],
+        start_price: u64,
+        slope: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if start_price == 0 && slope == 0 {
+            return Err(EscrowError::InvalidCurve.into());
+        }
+        if Self::inits_oracle_priced(accounts) {
+            msg!("error: curve escrows can not be oracle priced");
+            return Err(EscrowError::InvalidCurve.into());
+        }
+
+        // the expected amount is unused, the curve prices every fill
+        Self::process_init_escrow(accounts, 0, &EscrowTerms::default(), program_id)?;
+
+        let escrow_account = &accounts[3];
+        let mut escrow_info = Escrow::unpack(&escrow_account.data.borrow())?;
+        escrow_info.curve_start_price = start_price;
+        escrow_info.curve_slope = slope;
+        escrow_info.terms_hash = escrow_info.hash_terms();
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    pub fn process_init_crowdfill_escrow(
+        accounts: &[AccountInfo],
+        amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if Self::inits_oracle_priced(accounts) {
+            msg!("error: crowdfill escrows can not be oracle priced");
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+
+        Self::process_init_escrow(accounts, amount, &EscrowTerms::default(), program_id)?;
+
+        let escrow_account = &accounts[3];
+        let mut escrow_info = Escrow::unpack(&escrow_account.data.borrow())?;
+        escrow_info.is_crowdfill = true;
+        escrow_info.terms_hash = escrow_info.hash_terms();
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
     }
 
     pub fn process_exchange(
@@ -110,80 +830,455 @@ impl Processor {
         amount_expected_by_taker: u64,
         program_id: &Pubkey,
     ) -> ProgramResult {
-        let account_info_iter = &mut accounts.iter();
-        let taker = next_account_info(account_info_iter)?;
+        Self::exchange(accounts, amount_expected_by_taker, None, false, program_id)
+    }
 
-        if !taker.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
+    /// Exchange failing when the fill costs the taker more than `max_payment`
+    pub fn process_capped_exchange(
+        accounts: &[AccountInfo],
+        amount_expected_by_taker: u64,
+        max_payment: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        Self::exchange(
+            accounts,
+            amount_expected_by_taker,
+            Some(max_payment),
+            false,
+            program_id,
+        )
+    }
 
-        let takers_sending_token_account = next_account_info(account_info_iter)?;
-        let takers_token_to_receive_account = next_account_info(account_info_iter)?;
+    /// Exchange whose settlement carries `memo`, passed on to the SPL Memo program that follows
+    /// the accounts of the Exchange so explorers show it with the transaction
+    pub fn process_exchange_with_memo(
+        accounts: &[AccountInfo],
+        amount_expected_by_taker: u64,
+        memo: &str,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        Self::exchange_with_memo(accounts, amount_expected_by_taker, None, memo, program_id)
+    }
 
-        let pdas_temp_token_account = next_account_info(account_info_iter)?;
-        let pdas_temp_token_account_info =
-            TokenAccount::unpack(&pdas_temp_token_account.data.borrow())?;
-        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+    fn exchange_with_memo(
+        accounts: &[AccountInfo],
+        amount_expected_by_taker: u64,
+        max_payment: Option<u64>,
+        memo: &str,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let (memo_program, exchange_accounts) = accounts
+            .split_last()
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if !memo::is_memo_program(memo_program.key) {
+            msg!("error: expected the SPL Memo program as the last account");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::exchange(
+            exchange_accounts,
+            amount_expected_by_taker,
+            max_payment,
+            false,
+            program_id,
+        )?;
 
-        if amount_expected_by_taker != pdas_temp_token_account_info.amount {
-            msg!("error: amount_expected_by_taker != pdas_temp_token_account_info.amount");
-            return Err(EscrowError::ExpectedAmountMismatch.into());
+        let memo_ix = memo::build_memo(memo_program.key, memo.as_bytes());
+        msg!("Calling the memo program to attach the settlement memo...");
+        invoke(&memo_ix, std::slice::from_ref(memo_program))
+    }
+
+    /// fails unless the escrow of the Exchange `accounts` was opened or repriced at
+    /// `created_before_slot` or earlier and the current slot is at most `not_after_slot`
+    fn check_exchange_guard(
+        clock_sysvar: &AccountInfo,
+        accounts: &[AccountInfo],
+        created_before_slot: u64,
+        not_after_slot: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let clock = Clock::from_account_info(clock_sysvar)?;
+        let escrow_account = accounts.get(6).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        if escrow_info.revised_at_slot > created_before_slot {
+            msg!("error: escrow_info.revised_at_slot > created_before_slot");
+            Self::blame(escrow_account.key);
+            return Err(EscrowError::EscrowRevised.into());
         }
+        if clock.slot > not_after_slot {
+            msg!("error: the current slot is past not_after_slot");
+            return Err(EscrowError::ExchangeTooLate.into());
+        }
+        Ok(())
+    }
 
-        let initializers_main_account = next_account_info(account_info_iter)?;
-        let initializers_token_to_receive_account = next_account_info(account_info_iter)?;
-        let escrow_account = next_account_info(account_info_iter)?;
+    /// fails unless the escrow of the Exchange `accounts` still has the `terms_hash` the taker saw
+    fn check_terms_hash(
+        accounts: &[AccountInfo],
+        terms_hash: &[u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let escrow_account = accounts.get(6).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        if escrow_info.terms_hash != *terms_hash {
+            msg!("error: escrow_info.terms_hash != terms_hash");
+            Self::blame(escrow_account.key);
+            return Err(EscrowError::TermsChanged.into());
+        }
+        Ok(())
+    }
 
-        let escrow_info = Escrow::unpack(&escrow_account.data.borrow())?;
+    /// Exchange, paid by the PDA as delegate of the taker's token account for a `relayed` taker
+    /// whose intent was checked instead of their signature
+    fn exchange(
+        accounts: &[AccountInfo],
+        amount_expected_by_taker: u64,
+        max_payment: Option<u64>,
+        relayed: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let (context, mut escrow_info) =
+            ExchangeContext::new(account_info_iter, relayed, program_id)?;
+        let ExchangeContext {
+            taker,
+            pdas_temp_token_account_info,
+            initializers_main_account,
+            initializers_token_to_receive_account,
+            escrow_account,
+            ..
+        } = context;
 
-        if escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key {
-            msg!("error: escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key");
-            return Err(ProgramError::InvalidAccountData);
+        if escrow_info.is_committed() {
+            msg!("error: escrow has a pending two-phase exchange");
+            return Err(EscrowError::EscrowCommitted.into());
         }
-        if escrow_info.initializer_pubkey != *initializers_main_account.key {
-            msg!("error: escrow_info.initializer_pubkey != *initializers_main_account.key");
-            return Err(ProgramError::InvalidAccountData);
+        if escrow_info.is_auction() {
+            return Err(EscrowError::AuctionEscrow.into());
+        }
+        if escrow_info.is_raffle() {
+            return Err(EscrowError::RaffleEscrow.into());
         }
-        if escrow_info.initializer_token_to_receive_account_pubkey
-            != *initializers_token_to_receive_account.key
+        if !escrow_info.allows_taker(taker.key) {
+            msg!("error: escrow is private to another taker");
+            return Err(EscrowError::TakerNotAllowed.into());
+        }
+
+        // the taker is matched against the recorded deposit, so tokens sent to the vault directly
+        // can not make their expected amount miss
+        let deposited_amount = escrow_info.deposited_amount;
+        let surplus_amount = Self::vault_surplus(&escrow_info, &pdas_temp_token_account_info)?;
+
+        // curve priced and crowdfill escrows are filled in slices, everything else in one go
+        let fill_amount = if escrow_info.allows_partial_fills() {
+            if amount_expected_by_taker == 0 || amount_expected_by_taker > deposited_amount {
+                msg!("error: amount_expected_by_taker exceeds escrow_info.deposited_amount");
+                return Err(EscrowError::ExpectedAmountMismatch.into());
+            }
+            amount_expected_by_taker
+        } else {
+            if amount_expected_by_taker != deposited_amount {
+                msg!("error: amount_expected_by_taker != escrow_info.deposited_amount");
+                return Err(EscrowError::ExpectedAmountMismatch.into());
+            }
+            deposited_amount
+        };
+
+        let remaining_amount = deposited_amount - fill_amount;
+        let fixed_price = if escrow_info.allows_partial_fills() {
+            let total_amount = escrow_info
+                .filled_amount
+                .checked_add(deposited_amount)
+                .ok_or(EscrowError::AmountOverflow)?;
+            math::prorated_cost(
+                escrow_info.expected_amount,
+                total_amount,
+                escrow_info.filled_amount,
+                fill_amount,
+            )
+            .ok_or(EscrowError::AmountOverflow)?
+        } else {
+            escrow_info.expected_amount
+        };
+
+        let clock = if escrow_info.is_oracle_priced()
+            || escrow_info.reserve_slots > 0
+            || escrow_info.expires_at_slot > 0
+            || escrow_info.is_chained()
+            || escrow_info.is_timelocked()
         {
-            msg!("error: escrow_info.initializer_token_to_receive_account_pubkey != *initializers_token_to_receive_account.key");
-            return Err(ProgramError::InvalidAccountData);
+            Clock::from_account_info(next_account_info(account_info_iter)?)?
+        } else {
+            Clock::default()
+        };
+        let current_slot = clock.slot;
+        if escrow_info.is_expired_at(current_slot) {
+            msg!("error: escrow expired");
+            return Err(EscrowError::EscrowExpired.into());
         }
+        Self::check_release_window(&escrow_info, &clock)?;
 
-        let token_program = next_account_info(account_info_iter)?;
+        let (bond_to_taker, bond_to_initializer) =
+            Self::take_reservation(&mut escrow_info, taker.key, current_slot)?;
 
-        let transfer_to_initializer_ix = spl_token::instruction::transfer(
-            token_program.key,
-            takers_sending_token_account.key,
-            initializers_token_to_receive_account.key,
-            taker.key,
-            &[&taker.key],
-            escrow_info.expected_amount,
+        let amount_to_initializer = Self::price_fill(
+            &escrow_info,
+            account_info_iter,
+            fill_amount,
+            fixed_price,
+            current_slot,
         )?;
+        match max_payment {
+            Some(max_payment) if amount_to_initializer > max_payment => {
+                msg!("error: the fill costs more than max_payment");
+                return Err(EscrowError::SlippageExceeded.into());
+            }
+            // the oracle may quote anything up to the fill, so the taker has to cap it
+            None if escrow_info.is_oracle_priced() => {
+                msg!("error: oracle priced escrows need the max_payment of CappedExchange");
+                return Err(EscrowError::MaxPaymentRequired.into());
+            }
+            _ => {}
+        }
+
+        if escrow_info.exchange_top_level_only || escrow_info.requires_memo() {
+            let instructions_sysvar = next_account_info(account_info_iter)?;
+            // makers can opt out of being filled by composing programs
+            if escrow_info.exchange_top_level_only {
+                Self::check_top_level(instructions_sysvar, program_id)?;
+            }
+            if escrow_info.requires_memo() {
+                Self::check_memo(instructions_sysvar, escrow_info.memo_reference())?;
+            }
+        }
+
+        // a relayed taker has not signed for their lamports
+        let lamport_payer = Some(context.taker).filter(|_| !relayed);
+        let fee = Self::settlement_fee(
+            account_info_iter,
+            amount_to_initializer,
+            lamport_payer,
+            &escrow_info.market,
+            program_id,
+        )?;
+        Self::record_fill(
+            account_info_iter,
+            &pdas_temp_token_account_info.mint,
+            fill_amount,
+            program_id,
+        )?;
+        let relist_source = if escrow_info.relists() {
+            let relist_source = next_account_info(account_info_iter)?;
+            Self::check_key(
+                "error: escrow_info.relist_source_pubkey != *relist_source.key",
+                &escrow_info.relist_source_pubkey,
+                relist_source.key,
+                ProgramError::InvalidAccountData,
+            )?;
+            Some(relist_source)
+        } else {
+            None
+        };
+        let net_amount = amount_to_initializer - fee.map_or(0, |(_, fee_amount)| fee_amount);
+        let splits = Self::split_payment(&escrow_info, account_info_iter, net_amount)?;
+        let condition = &escrow_info.release_condition;
+        if condition.is_set() && !condition.gates_cancel {
+            Self::check_release_condition(condition, next_account_info(account_info_iter)?)?;
+        }
+
+        // the closing fill empties the vault, surplus included, so it can be closed
+        let release_amount = if remaining_amount == 0 {
+            fill_amount + surplus_amount
+        } else {
+            fill_amount
+        };
+        let mut token_cpi = TokenCpi::new(context.token_program.key);
+        Self::pay_for_fill(
+            &context,
+            &mut token_cpi,
+            relayed,
+            &splits,
+            fee,
+            net_amount,
+            release_amount,
+        )?;
+
+        EscrowEvent::Filled {
+            escrow: *escrow_account.key,
+            taker: *taker.key,
+            fill_amount,
+            paid_amount: amount_to_initializer,
+            remaining_amount,
+        }
+        .emit();
+        Self::log_settlement_summary(
+            account_info_iter,
+            (&pdas_temp_token_account_info.mint, fill_amount),
+            (initializers_token_to_receive_account, amount_to_initializer),
+        )?;
+
+        Self::move_lamports(escrow_account, taker, bond_to_taker)?;
+        Self::move_lamports(
+            escrow_account,
+            initializers_main_account,
+            bond_to_initializer,
+        )?;
+
+        Self::settle_fill(
+            &context,
+            &mut escrow_info,
+            &mut token_cpi,
+            relist_source,
+            remaining_amount,
+            fill_amount,
+            current_slot,
+        )
+    }
+
+    /// What the taker pays for `fill_amount` of the deposit, at least `fixed_price` for oracle
+    /// priced escrows
+    #[inline(never)]
+    fn price_fill(
+        escrow_info: &Escrow,
+        account_info_iter: &mut std::slice::Iter<AccountInfo>,
+        fill_amount: u64,
+        fixed_price: u64,
+        current_slot: u64,
+    ) -> Result<u64, ProgramError> {
+        let price = if escrow_info.is_oracle_priced() {
+            let oracle_account = next_account_info(account_info_iter)?;
+            Self::check_key(
+                "error: escrow_info.oracle_pubkey != *oracle_account.key",
+                &escrow_info.oracle_pubkey,
+                oracle_account.key,
+                EscrowError::InvalidOracle.into(),
+            )?;
+            Self::check_oracle_owner(oracle_account)?;
+            let oracle_price = OraclePrice::unpack(&oracle_account.data.borrow())?;
+            oracle_price.check_staleness(current_slot, escrow_info.oracle_max_staleness_slots)?;
+            oracle_price.check_confidence(escrow_info.oracle_max_confidence_bps)?;
+            let quoted = oracle_price
+                .quote(
+                    fill_amount,
+                    escrow_info.deposit_mint_decimals,
+                    escrow_info.receive_mint_decimals,
+                )
+                .ok_or(EscrowError::AmountOverflow)?;
+            quoted.max(fixed_price)
+        } else if escrow_info.is_curve_priced() {
+            math::linear_curve_cost(
+                escrow_info.curve_start_price,
+                escrow_info.curve_slope,
+                escrow_info.filled_amount,
+                fill_amount,
+            )
+            .ok_or(EscrowError::AmountOverflow)?
+        } else {
+            fixed_price
+        };
+        Ok(price)
+    }
+
+    /// Pays the split recipients' shares and the fee out of `net_amount` and the rest to the
+    /// initializer, then moves `release_amount` of the vault to the taker
+    #[inline(never)]
+    fn pay_for_fill<'a, 'b>(
+        context: &ExchangeContext<'a, 'b>,
+        token_cpi: &mut TokenCpi,
+        relayed: bool,
+        splits: &[(&'a AccountInfo<'b>, u64)],
+        fee: Option<(&'a AccountInfo<'b>, u64)>,
+        net_amount: u64,
+        release_amount: u64,
+    ) -> ProgramResult {
+        let ExchangeContext {
+            taker,
+            takers_sending_token_account,
+            takers_token_to_receive_account,
+            pdas_temp_token_account,
+            initializers_token_to_receive_account,
+            token_program,
+            pda_account,
+            bump_seed,
+            ..
+        } = *context;
+        let pda_seeds: &[&[&[u8]]] = &[&[&b"escrow"[..], &[bump_seed]]];
+        let (payment_authority, payment_seeds) = if relayed {
+            (pda_account, pda_seeds)
+        } else {
+            (taker, &[][..])
+        };
+        let fee_amount = fee.map_or(0, |(_, fee_amount)| fee_amount);
+        Self::check_payment_authority(
+            takers_sending_token_account,
+            payment_authority.key,
+            net_amount + fee_amount,
+        )?;
+        for (recipient, share) in splits.iter() {
+            let transfer_share_ix = token_cpi.transfer(
+                takers_sending_token_account.key,
+                recipient.key,
+                payment_authority.key,
+                *share,
+            );
+            msg!("Calling the token program to transfer a split recipient's share...");
+            invoke_signed(
+                transfer_share_ix,
+                &[
+                    takers_sending_token_account.clone(),
+                    (*recipient).clone(),
+                    payment_authority.clone(),
+                    token_program.clone(),
+                ],
+                payment_seeds,
+            )?;
+        }
+        if let Some((fee_account, fee_amount)) = fee.filter(|(_, fee_amount)| *fee_amount > 0) {
+            let transfer_fee_ix = token_cpi.transfer(
+                takers_sending_token_account.key,
+                fee_account.key,
+                payment_authority.key,
+                fee_amount,
+            );
+            msg!("Calling the token program to transfer the settlement fee...");
+            invoke_signed(
+                transfer_fee_ix,
+                &[
+                    takers_sending_token_account.clone(),
+                    fee_account.clone(),
+                    payment_authority.clone(),
+                    token_program.clone(),
+                ],
+                payment_seeds,
+            )?;
+        }
+
+        let transfer_to_initializer_ix = token_cpi.transfer(
+            takers_sending_token_account.key,
+            initializers_token_to_receive_account.key,
+            payment_authority.key,
+            net_amount - splits.iter().map(|(_, share)| share).sum::<u64>(),
+        );
         msg!("Calling the token program to transfer tokens to the escrow's initializer...");
-        invoke(
-            &transfer_to_initializer_ix,
+        invoke_signed(
+            transfer_to_initializer_ix,
             &[
                 takers_sending_token_account.clone(),
                 initializers_token_to_receive_account.clone(),
-                taker.clone(),
+                payment_authority.clone(),
                 token_program.clone(),
             ],
+            payment_seeds,
         )?;
 
-        let pda_account = next_account_info(account_info_iter)?;
-        let transfer_to_initializer_ix = spl_token::instruction::transfer(
-            token_program.key,
+        let transfer_to_taker_ix = token_cpi.transfer(
             pdas_temp_token_account.key,
             takers_token_to_receive_account.key,
-            &pda,
-            &[&pda],
-            pdas_temp_token_account_info.amount,
-        )?;
+            pda_account.key,
+            release_amount,
+        );
         msg!("Calling the token program to transfer tokens to the taker...");
         invoke_signed(
-            &transfer_to_initializer_ix,
+            transfer_to_taker_ix,
             &[
                 pdas_temp_token_account.clone(),
                 takers_token_to_receive_account.clone(),
@@ -192,17 +1287,131 @@ impl Processor {
             ],
             &[&[&b"escrow"[..], &[bump_seed]]],
         )?;
+        Ok(())
+    }
 
-        let close_pdas_temp_acc_ix = spl_token::instruction::close_account(
-            token_program.key,
+    /// Keeps a partly filled escrow open, lists a chained or relisting escrow's next offer or
+    /// closes the filled escrow and its vault
+    #[inline(never)]
+    fn settle_fill<'a, 'b>(
+        context: &ExchangeContext<'a, 'b>,
+        escrow_info: &mut Escrow,
+        token_cpi: &mut TokenCpi,
+        relist_source: Option<&'a AccountInfo<'b>>,
+        remaining_amount: u64,
+        fill_amount: u64,
+        current_slot: u64,
+    ) -> ProgramResult {
+        let ExchangeContext {
+            taker,
+            pdas_temp_token_account,
+            initializers_main_account,
+            initializers_token_to_receive_account,
+            escrow_account,
+            token_program,
+            pda_account,
+            bump_seed,
+            ..
+        } = *context;
+        let pda = *pda_account.key;
+
+        if remaining_amount > 0 {
+            msg!("Partial fill, keeping the escrow open...");
+            escrow_info.filled_amount = escrow_info
+                .filled_amount
+                .checked_add(fill_amount)
+                .ok_or(EscrowError::AmountOverflow)?;
+            escrow_info.deposited_amount = remaining_amount;
+            Escrow::pack(*escrow_info, &mut escrow_account.data.borrow_mut())?;
+            return Ok(());
+        }
+
+        if escrow_info.is_chained() {
+            let close_pdas_temp_acc_ix = token_cpi.close_account(
+                pdas_temp_token_account.key,
+                initializers_main_account.key,
+                &pda,
+            );
+            msg!("Calling the token program to close pda's temp account...");
+            invoke_signed(
+                close_pdas_temp_acc_ix,
+                &[
+                    pdas_temp_token_account.clone(),
+                    initializers_main_account.clone(),
+                    pda_account.clone(),
+                    token_program.clone(),
+                ],
+                &[&[&b"escrow"[..], &[bump_seed]]],
+            )?;
+
+            msg!("Listing the proceeds as the follow-on offer...");
+            escrow_info.temp_token_account_pubkey =
+                escrow_info.initializer_token_to_receive_account_pubkey;
+            escrow_info.initializer_token_to_receive_account_pubkey =
+                escrow_info.chain_receive_pubkey;
+            escrow_info.expected_amount = escrow_info.chain_expected_amount;
+            escrow_info.receive_mint = escrow_info.chain_receive_mint;
+            escrow_info.chain_receive_mint = Pubkey::default();
+            escrow_info.filled_amount = 0;
+            // the proceeds account becomes the vault with everything it holds after the payment
+            escrow_info.deposited_amount =
+                Self::unpack_token_account(initializers_token_to_receive_account)?.amount;
+            escrow_info.chain_expected_amount = 0;
+            escrow_info.chain_receive_pubkey = Pubkey::default();
+            escrow_info.revised_at_slot = current_slot;
+            escrow_info.terms_hash = escrow_info.hash_terms();
+            Escrow::pack(*escrow_info, &mut escrow_account.data.borrow_mut())?;
+            EscrowEvent::Initialized {
+                escrow: *escrow_account.key,
+                initializer: escrow_info.initializer_pubkey,
+                expected_amount: escrow_info.expected_amount,
+            }
+            .emit();
+            return Ok(());
+        }
+
+        if let Some(relist_source) = relist_source {
+            if Self::can_relist(escrow_info, relist_source, &pda)? {
+                let relist_ix = token_cpi.transfer(
+                    relist_source.key,
+                    pdas_temp_token_account.key,
+                    &pda,
+                    escrow_info.relist_amount,
+                );
+                msg!("Calling the token program to fund the relisted escrow...");
+                invoke_signed(
+                    relist_ix,
+                    &[
+                        relist_source.clone(),
+                        pdas_temp_token_account.clone(),
+                        pda_account.clone(),
+                        token_program.clone(),
+                    ],
+                    &[&[&b"escrow"[..], &[bump_seed]]],
+                )?;
+
+                escrow_info.filled_amount = 0;
+                escrow_info.deposited_amount = escrow_info.relist_amount;
+                Escrow::pack(*escrow_info, &mut escrow_account.data.borrow_mut())?;
+                EscrowEvent::Initialized {
+                    escrow: *escrow_account.key,
+                    initializer: escrow_info.initializer_pubkey,
+                    expected_amount: escrow_info.expected_amount,
+                }
+                .emit();
+                return Ok(());
+            }
+            msg!("Relist deposit no longer available, closing the escrow...");
+        }
+
+        let close_pdas_temp_acc_ix = token_cpi.close_account(
             pdas_temp_token_account.key,
             initializers_main_account.key,
             &pda,
-            &[&pda],
-        )?;
+        );
         msg!("Calling the token program to close pda's temp account...");
         invoke_signed(
-            &close_pdas_temp_acc_ix,
+            close_pdas_temp_acc_ix,
             &[
                 pdas_temp_token_account.clone(),
                 initializers_main_account.clone(),
@@ -213,231 +1422,9777 @@ impl Processor {
         )?;
 
         msg!("Closing the escrow account...");
-        **initializers_main_account.lamports.borrow_mut() = initializers_main_account
-            .lamports()
-            .checked_add(escrow_account.lamports())
-            .ok_or(EscrowError::AmountOverflow)?;
-        **escrow_account.lamports.borrow_mut() = 0;
-        Ok(())
+        // the taker gets a cut of the escrow's rent for cleaning up in the same transaction
+        let rebate = TAKER_CLOSE_REBATE_LAMPORTS.min(escrow_account.lamports());
+        Self::move_lamports(escrow_account, taker, rebate)?;
+        Self::close_program_account(escrow_account, initializers_main_account)
         // XXX I am exhausted
     }
-}
 
-#[cfg(test)]
-mod tests {
-
-    use std::str::FromStr;
+    pub fn process_init_template(
+        accounts: &[AccountInfo],
+        price: u64,
+        price_expo: i32,
+        expiry_slots: u64,
+        allowed_taker: Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let owner = next_account_info(account_info_iter)?;
+        if !owner.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
 
-    use super::*;
-    use solana_program::{
-        instruction::Instruction, program_pack::Pack, program_stubs, rent::Rent, sysvar,
-    };
+        let template_account = next_account_info(account_info_iter)?;
+        let deposit_mint = next_account_info(account_info_iter)?;
+        let receive_mint = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
 
-    use solana_sdk::account::{
-        create_account_for_test, create_is_signer_account_infos, Account as SolanaAccount,
-        WritableAccount,
-    };
+        if template_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !rent.is_exempt(template_account.lamports(), template_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+        let template_info = Template::unpack_unchecked(&template_account.data.borrow())?;
+        if template_info.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        if price == 0 {
+            msg!("error: templates can not give the deposit away");
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        Self::unpack_mint(deposit_mint)?;
+        Self::unpack_mint(receive_mint)?;
 
-    #[test]
-    fn test_pack_unpack() {
-        // my first Rust test... so proud!
-        let check = Escrow {
-            is_initialized: true,
-            initializer_pubkey: Pubkey::new(&[1; 32]),
-            temp_token_account_pubkey: Pubkey::new(&[2; 32]),
-            initializer_token_to_receive_account_pubkey: Pubkey::new(&[3; 32]),
-            expected_amount: 10,
-        };
-        assert!(check.is_initialized);
+        Template::pack(
+            Template {
+                is_initialized: true,
+                owner: *owner.key,
+                deposit_mint: *deposit_mint.key,
+                receive_mint: *receive_mint.key,
+                price,
+                price_expo,
+                expiry_slots,
+                allowed_taker,
+            },
+            &mut template_account.data.borrow_mut(),
+        )
+    }
 
-        let mut packed = vec![0; Escrow::get_packed_len()];
+    pub fn process_init_from_template(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        // the first accounts are InitEscrow's, without the optional oracle ones
+        if accounts.len() < 9 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let (init_escrow_accounts, template_accounts) = accounts.split_at(7);
+        let initializer = &init_escrow_accounts[0];
+        let temp_token_account = &init_escrow_accounts[1];
+        let token_to_receive_account = &init_escrow_accounts[2];
+        let account_info_iter = &mut template_accounts.iter();
+        let template_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
 
-        let expected = vec![
-            1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
-            1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
-            2, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3,
-            3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 10, 0, 0, 0, 0, 0, 0, 0,
-        ];
-        Escrow::pack(check, &mut packed).unwrap();
-        assert_eq!(packed, expected);
+        let template_info = Self::unpack_program_account::<Template>(template_account, program_id)?;
+        Self::check_key(
+            "error: template_info.owner != *initializer.key",
+            &template_info.owner,
+            initializer.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        let deposit = Self::unpack_token_account(temp_token_account)?;
+        if deposit.mint != template_info.deposit_mint
+            || Self::unpack_token_account(token_to_receive_account)?.mint
+                != template_info.receive_mint
+        {
+            msg!("error: token accounts do not match the template's mints");
+            return Err(ProgramError::InvalidAccountData);
+        }
 
-        let unpacked = Escrow::unpack(&packed).unwrap();
-        assert_eq!(unpacked, check);
+        let expected_amount = math::exponent_price_cost(
+            template_info.price,
+            template_info.price_expo,
+            deposit.amount,
+        )
+        .ok_or(EscrowError::AmountOverflow)?;
+        let expires_at_slot = if template_info.expiry_slots == 0 {
+            0
+        } else {
+            clock
+                .slot
+                .checked_add(template_info.expiry_slots)
+                .ok_or(EscrowError::AmountOverflow)?
+        };
+        let terms = EscrowTerms {
+            expires_at_slot,
+            allowed_taker: template_info.allowed_taker,
+            ..EscrowTerms::default()
+        };
+        Self::init_escrow(
+            init_escrow_accounts,
+            initializer.key,
+            expected_amount,
+            &terms,
+            Some(clock.slot),
+            program_id,
+        )
+    }
 
-        //println!("{:?}", check);
-        // println!("{:?}", unpacked);
+    pub fn process_cancel(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        Self::cancel(accounts, false, program_id)
     }
 
-    struct TestSyscallStubs {}
-    impl program_stubs::SyscallStubs for TestSyscallStubs {
-        fn sol_invoke_signed(
-            &self,
-            _instruction: &Instruction,
-            _account_infos: &[AccountInfo],
-            _signers_seeds: &[&[&[u8]]],
-        ) -> ProgramResult {
-            msg!("TestSyscallStubs::sol_invoke_signed()");
+    /// Cancel, `authorized` by a relayed intent or the operator's signature checked instead of
+    /// the initializer's signature
+    fn cancel(accounts: &[AccountInfo], authorized: bool, program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer && !authorized {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
 
-            // TODO
-            // Stub behaviour of the invoke
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let initializers_token_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
 
-            Ok(())
+        let escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        // bidders' deposits are locked on the auction running its course
+        if escrow_info.is_auction() {
+            return Err(EscrowError::AuctionEscrow.into());
+        }
+        // tickets are paid for, so the deposit is owed to whichever gets drawn
+        if escrow_info.is_raffle() {
+            return Err(EscrowError::RaffleEscrow.into());
+        }
+        if escrow_info.is_committed() {
+            msg!("error: escrow has a pending two-phase exchange");
+            return Err(EscrowError::EscrowCommitted.into());
+        }
+        Self::check_key(
+            "error: escrow_info.initializer_pubkey != *initializer.key",
+            &escrow_info.initializer_pubkey,
+            initializer.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        Self::check_key(
+            "error: escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key",
+            &escrow_info.temp_token_account_pubkey,
+            pdas_temp_token_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        if clock.slot < escrow_info.cancel_locked_until_slot {
+            msg!("error: escrow is locked until a later slot");
+            return Err(EscrowError::CancelLocked.into());
+        }
+        // a lapsed reservation's bond is closed out to the initializer with the escrow
+        if escrow_info.is_reserved_at(clock.slot) {
+            msg!("error: escrow is reserved by a taker");
+            return Err(EscrowError::EscrowReserved.into());
         }
-    }
 
-    fn test_syscall_stubs() {
-        use std::sync::Once;
-        static ONCE: Once = Once::new();
+        Self::check_token_program(token_program)?;
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        let pdas_temp_token_account_info = Self::unpack_token_account(pdas_temp_token_account)?;
 
-        ONCE.call_once(|| {
-            program_stubs::set_syscall_stubs(Box::new(TestSyscallStubs {}));
-        });
-    }
+        let return_deposit_ix = spl_token::instruction::transfer(
+            token_program.key,
+            pdas_temp_token_account.key,
+            initializers_token_account.key,
+            &pda,
+            &[&pda],
+            pdas_temp_token_account_info.amount,
+        )?;
+        msg!("Calling the token program to return the deposit to the initializer...");
+        invoke_signed(
+            &return_deposit_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                initializers_token_account.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
 
-    #[test]
-    fn test_init_escrow() {
-        // println!("-------------------------- test_init_escrow --------------------------");
-        // 0. `[signer]` The account of the person initializing the escrow
-        // 1. `[writable]` Temporary token account that should be created prior to this instruction and owned by the initializer
-        // 2. `[]` The initializer's token account for the token they will receive should the trade go through
-        // 3. `[writable]` The escrow account, it will hold all necessary info about the trade.
-        // 4. `[]` The rent sysvar
-        // 5. `[]` The token program
-        test_syscall_stubs();
+        let close_pdas_temp_acc_ix = spl_token::instruction::close_account(
+            token_program.key,
+            pdas_temp_token_account.key,
+            initializer.key,
+            &pda,
+            &[&pda],
+        )?;
+        msg!("Calling the token program to close pda's temp account...");
+        invoke_signed(
+            &close_pdas_temp_acc_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                initializer.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
 
-        let escrow_program_id =
-            Pubkey::from_str(&"escrow1111111111111111111111111111111111111").unwrap();
+        Self::hand_back_proceeds(
+            &escrow_info,
+            account_info_iter,
+            initializer.key,
+            token_program,
+            pda_account,
+            program_id,
+        )?;
 
-        let escrow_pubkey = Pubkey::new_unique();
+        let condition = &escrow_info.release_condition;
+        if condition.is_set() && condition.gates_cancel {
+            Self::check_release_condition(condition, next_account_info(account_info_iter)?)?;
+        }
 
-        let token_id = spl_token::id();
-        let rent = Rent::default();
-        let mut rent_sysvar = create_account_for_test(&rent);
+        EscrowEvent::Cancelled {
+            escrow: *escrow_account.key,
+            returned_amount: pdas_temp_token_account_info.amount,
+        }
+        .emit();
 
-        let escrow_len = Escrow::get_packed_len();
-        let escrow_account_min_balance = rent.minimum_balance(escrow_len);
+        msg!("Closing the escrow account...");
+        Self::close_program_account(escrow_account, initializer)
+    }
 
-        let mut initializer_account = SolanaAccount::default();
-        let mut temp_token_account = SolanaAccount::default();
-        let mut initializer_token_to_receive_account = SolanaAccount::default();
-        initializer_token_to_receive_account.set_owner(spl_token::id()); // set owner of initializer token account to spl_token
-        let mut escrow_account =
-            SolanaAccount::new(escrow_account_min_balance, escrow_len, &escrow_pubkey);
-        let mut token_account = SolanaAccount::default();
+    /// Logs a `SettlementSummary` when the two mints trail `account_info_iter`, naming each
+    /// mint by the symbol of the metadata account following them, if any
+    fn log_settlement_summary(
+        account_info_iter: &mut std::slice::Iter<AccountInfo>,
+        (sold_mint, sold_amount): (&Pubkey, u64),
+        (paid_token_account, paid_amount): (&AccountInfo, u64),
+    ) -> ProgramResult {
+        let sold_mint_account = match account_info_iter.next() {
+            Some(sold_mint_account) => sold_mint_account,
+            None => return Ok(()),
+        };
+        let paid_mint_account = next_account_info(account_info_iter)?;
+        if *sold_mint_account.key != *sold_mint
+            || *paid_mint_account.key != Self::unpack_token_account(paid_token_account)?.mint
+        {
+            msg!("error: expected the escrow's mints");
+            return Err(ProgramError::InvalidAccountData);
+        }
 
-        let mut accounts = [
-            (&Pubkey::new_unique(), true, &mut initializer_account),
-            (&Pubkey::new_unique(), true, &mut temp_token_account),
-            (
-                &Pubkey::new_unique(),
-                true,
-                &mut initializer_token_to_receive_account,
-            ),
-            (&Pubkey::new_unique(), true, &mut escrow_account),
-            (&sysvar::rent::id(), true, &mut rent_sysvar),
-            (&token_id, true, &mut token_account),
-        ];
+        let mut token_amount = |mint_account: &AccountInfo, amount| {
+            let symbol = match account_info_iter.next() {
+                Some(metadata_account) => {
+                    Self::check_key(
+                        "error: expected the mint's metadata account",
+                        &token_metadata::find_metadata_address(mint_account.key).0,
+                        metadata_account.key,
+                        ProgramError::InvalidSeeds,
+                    )?;
+                    // plenty of mints never had metadata created
+                    if *metadata_account.owner == token_metadata::id() {
+                        token_metadata::unpack_symbol(&metadata_account.data.borrow())
+                    } else {
+                        None
+                    }
+                }
+                None => None,
+            };
+            Ok::<_, ProgramError>(TokenAmount {
+                amount,
+                decimals: Mint::unpack(&mint_account.data.borrow())?.decimals,
+                mint: *mint_account.key,
+                symbol,
+            })
+        };
+        SettlementSummary {
+            sold: token_amount(sold_mint_account, sold_amount)?,
+            paid: token_amount(paid_mint_account, paid_amount)?,
+        }
+        .emit();
+        Ok(())
+    }
 
-        let accounts = create_is_signer_account_infos(&mut accounts);
+    /// fails unless `clock` is within the escrow's window of release timestamps
+    fn check_release_window(escrow_info: &Escrow, clock: &Clock) -> ProgramResult {
+        if !escrow_info.is_released_at(clock.unix_timestamp) {
+            msg!("error: escrow is not released yet");
+            return Err(EscrowError::EscrowNotReleased.into());
+        }
+        if escrow_info.is_release_closed_at(clock.unix_timestamp) {
+            msg!("error: escrow's release window has closed");
+            return Err(EscrowError::ReleaseClosed.into());
+        }
+        Ok(())
+    }
 
-        Processor::process_init_escrow(&accounts, 123, &escrow_program_id)
-            .expect("error: process_init_escrow()");
+    fn check_release_condition(
+        condition: &ReleaseCondition,
+        condition_account: &AccountInfo,
+    ) -> ProgramResult {
+        Self::check_key(
+            "error: expected the account the release condition reads",
+            &condition.account,
+            condition_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        if !condition.holds(condition_account.owner, &condition_account.data.borrow()) {
+            return Err(EscrowError::ConditionNotMet.into());
+        }
+        Ok(())
     }
 
-    #[test]
-    fn test_exchange() {
-        // 0. `[signer]` The account of the person taking the trade
-        // 1. `[writable]` The taker's token account for the token they send
-        // 2. `[writable]` The taker's token account for the token they will receive should the trade go through
-        // 3. `[writable]` The PDA's temp token account to get tokens from and eventually close
-        // 4. `[writable]` The initializer's main account to send their rent fees to
-        // 5. `[writable]` The initializer's token account that will receive tokens
-        // 6. `[writable]` The escrow account holding the escrow info
-        // 7. `[]` The token program
-        // 8. `[]` The PDA account
-        let escrow_program_id = "escrow1111111111111111111111111111111111111";
-        let escrow_program_id = Pubkey::from_str(&escrow_program_id).unwrap();
+    pub fn process_reclaim_expired(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let caller = next_account_info(account_info_iter)?;
+        if !caller.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let initializers_token_account = next_account_info(account_info_iter)?;
+        let initializer = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        Self::check_reclaimable(
+            &escrow_info,
+            pdas_temp_token_account,
+            initializer,
+            clock.slot,
+        )?;
+        // the caller picks where the deposit goes, so it must be the initializer's own account
+        Self::check_key(
+            "error: the deposit can only be returned to a token account of the initializer",
+            initializer.key,
+            &Self::unpack_token_account(initializers_token_account)?.owner,
+            ProgramError::InvalidAccountData,
+        )?;
+        // anyone can reclaim, so a lookalike token program must not get the PDA's signature
+        Self::check_token_program(token_program)?;
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        let pdas_temp_token_account_info = Self::unpack_token_account(pdas_temp_token_account)?;
+
+        let return_deposit_ix = spl_token::instruction::transfer(
+            token_program.key,
+            pdas_temp_token_account.key,
+            initializers_token_account.key,
+            &pda,
+            &[&pda],
+            pdas_temp_token_account_info.amount,
+        )?;
+        msg!("Calling the token program to return the deposit to the initializer...");
+        invoke_signed(
+            &return_deposit_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                initializers_token_account.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
+
+        let close_pdas_temp_acc_ix = spl_token::instruction::close_account(
+            token_program.key,
+            pdas_temp_token_account.key,
+            initializer.key,
+            &pda,
+            &[&pda],
+        )?;
+        msg!("Calling the token program to close pda's temp account...");
+        invoke_signed(
+            &close_pdas_temp_acc_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                initializer.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
+
+        Self::hand_back_proceeds(
+            &escrow_info,
+            account_info_iter,
+            initializer.key,
+            token_program,
+            pda_account,
+            program_id,
+        )?;
+
+        EscrowEvent::Cancelled {
+            escrow: *escrow_account.key,
+            returned_amount: pdas_temp_token_account_info.amount,
+        }
+        .emit();
+
+        msg!("Closing the escrow account...");
+        let bounty = RECLAIM_BOUNTY_LAMPORTS.min(escrow_account.lamports());
+        Self::move_lamports(escrow_account, caller, bounty)?;
+        Self::close_program_account(escrow_account, initializer)
+    }
+
+    pub fn process_sweep_dust(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let initializer = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+        let dust_token_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        if escrow_info.is_committed() {
+            msg!("error: escrow has a pending two-phase exchange");
+            return Err(EscrowError::EscrowCommitted.into());
+        }
+        Self::check_key(
+            "error: escrow_info.initializer_pubkey != *initializer.key",
+            &escrow_info.initializer_pubkey,
+            initializer.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        Self::check_key(
+            "error: escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key",
+            &escrow_info.temp_token_account_pubkey,
+            pdas_temp_token_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        Self::check_token_program(token_program)?;
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        let vault_info = Self::unpack_token_account(pdas_temp_token_account)?;
+        let surplus_amount = Self::vault_surplus(&escrow_info, &vault_info)?;
+
+        if surplus_amount > 0 {
+            Self::check_key(
+                "error: expected the config account of the market",
+                &Config::find_market_address(&escrow_info.market, program_id).0,
+                config_account.key,
+                ProgramError::InvalidSeeds,
+            )?;
+            let dust_owner = if config_account.owner == program_id {
+                Config::unpack(&config_account.data.borrow())?.fee_owner
+            } else {
+                escrow_info.initializer_pubkey
+            };
+            Self::check_key(
+                "error: dust_owner != dust_token_account.owner",
+                &dust_owner,
+                &Self::unpack_token_account(dust_token_account)?.owner,
+                ProgramError::InvalidAccountData,
+            )?;
+            let sweep_ix = spl_token::instruction::transfer(
+                token_program.key,
+                pdas_temp_token_account.key,
+                dust_token_account.key,
+                &pda,
+                &[&pda],
+                surplus_amount,
+            )?;
+            msg!("Calling the token program to sweep the vault's surplus...");
+            invoke_signed(
+                &sweep_ix,
+                &[
+                    pdas_temp_token_account.clone(),
+                    dust_token_account.clone(),
+                    pda_account.clone(),
+                    token_program.clone(),
+                ],
+                &[&[&b"escrow"[..], &[bump_seed]]],
+            )?;
+        }
+
+        // a reservation's bond sits in the escrow account until the reservation is settled
+        if escrow_info.is_reserved() {
+            return Ok(());
+        }
+        // only escrows owing nothing to anyone close: the proceeds vault of a chained escrow is
+        // handed back by Cancel, bids and tickets are settled by their own instructions
+        if escrow_info.deposited_amount > 0
+            || escrow_info.is_chained()
+            || escrow_info.is_auction()
+            || escrow_info.is_raffle()
+        {
+            let excess_lamports = escrow_account
+                .lamports()
+                .saturating_sub(rent.minimum_balance(escrow_account.data_len()));
+            return Self::move_lamports(escrow_account, initializer, excess_lamports);
+        }
+
+        let close_pdas_temp_acc_ix = spl_token::instruction::close_account(
+            token_program.key,
+            pdas_temp_token_account.key,
+            initializer.key,
+            &pda,
+            &[&pda],
+        )?;
+        msg!("Calling the token program to close pda's temp account...");
+        invoke_signed(
+            &close_pdas_temp_acc_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                initializer.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
+        msg!("Closing the drained escrow account...");
+        Self::close_program_account(escrow_account, initializer)
+    }
+
+    pub fn process_expiry(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let initializer = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        Self::check_reclaimable(
+            &escrow_info,
+            pdas_temp_token_account,
+            initializer,
+            clock.slot,
+        )?;
+
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        let pdas_temp_token_account_info = Self::unpack_token_account(pdas_temp_token_account)?;
+
+        // handing the vault back needs no token account of the initializer's to be named
+        Self::hand_back_token_account(
+            pdas_temp_token_account,
+            initializer.key,
+            token_program,
+            pda_account,
+            &pda,
+            bump_seed,
+        )?;
+        Self::hand_back_proceeds(
+            &escrow_info,
+            account_info_iter,
+            initializer.key,
+            token_program,
+            pda_account,
+            program_id,
+        )?;
+
+        EscrowEvent::Cancelled {
+            escrow: *escrow_account.key,
+            returned_amount: pdas_temp_token_account_info.amount,
+        }
+        .emit();
+
+        msg!("Closing the escrow account...");
+        Self::close_program_account(escrow_account, initializer)
+    }
+
+    pub fn process_reserve(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let taker = next_account_info(account_info_iter)?;
+        if !taker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let escrow_account = next_account_info(account_info_iter)?;
+        let initializers_main_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        let mut escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        if escrow_info.reserve_slots == 0 {
+            return Err(EscrowError::ReservationsDisabled.into());
+        }
+        if !escrow_info.allows_taker(taker.key) {
+            msg!("error: escrow is private to another taker");
+            return Err(EscrowError::TakerNotAllowed.into());
+        }
+        if escrow_info.is_committed() {
+            msg!("error: escrow has a pending two-phase exchange");
+            return Err(EscrowError::EscrowCommitted.into());
+        }
+        Self::check_key(
+            "error: escrow_info.initializer_pubkey != *initializers_main_account.key",
+            &escrow_info.initializer_pubkey,
+            initializers_main_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        if escrow_info.is_reserved_at(clock.slot) {
+            msg!("error: escrow is reserved by another taker");
+            return Err(EscrowError::EscrowReserved.into());
+        }
+
+        let lapsed_bond = if escrow_info.is_reserved() {
+            escrow_info.reserve_bond_lamports
+        } else {
+            0
+        };
+        escrow_info.reserved_by = *taker.key;
+        escrow_info.reserved_until_slot = clock
+            .slot
+            .checked_add(escrow_info.reserve_slots)
+            .ok_or(EscrowError::AmountOverflow)?;
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;
+
+        Self::move_lamports(escrow_account, initializers_main_account, lapsed_bond)?;
+
+        let post_bond_ix = system_instruction::transfer(
+            taker.key,
+            escrow_account.key,
+            escrow_info.reserve_bond_lamports,
+        );
+        msg!("Calling the system program to post the bond...");
+        invoke(
+            &post_bond_ix,
+            &[
+                taker.clone(),
+                escrow_account.clone(),
+                system_program.clone(),
+            ],
+        )
+    }
+
+    pub fn process_commit_exchange(
+        accounts: &[AccountInfo],
+        amount_expected_by_taker: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let taker = next_account_info(account_info_iter)?;
+        if !taker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let takers_payment_account = next_account_info(account_info_iter)?;
+        let takers_token_to_receive_account = next_account_info(account_info_iter)?;
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let initializers_main_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let mut escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        if escrow_info.settle_timeout_slots == 0
+            || escrow_info.is_oracle_priced()
+            || escrow_info.allows_partial_fills()
+        {
+            return Err(EscrowError::TwoPhaseDisabled.into());
+        }
+        if !escrow_info.allows_taker(taker.key) {
+            msg!("error: escrow is private to another taker");
+            return Err(EscrowError::TakerNotAllowed.into());
+        }
+        if escrow_info.is_committed() {
+            msg!("error: escrow has a pending two-phase exchange");
+            return Err(EscrowError::EscrowCommitted.into());
+        }
+        if escrow_info.is_expired_at(clock.slot) {
+            msg!("error: escrow expired");
+            return Err(EscrowError::EscrowExpired.into());
+        }
+        Self::check_release_window(&escrow_info, &clock)?;
+        Self::check_key(
+            "error: escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key",
+            &escrow_info.temp_token_account_pubkey,
+            pdas_temp_token_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        Self::check_key(
+            "error: escrow_info.initializer_pubkey != *initializers_main_account.key",
+            &escrow_info.initializer_pubkey,
+            initializers_main_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+
+        let pdas_temp_token_account_info = Self::unpack_token_account(pdas_temp_token_account)?;
+        Self::vault_surplus(&escrow_info, &pdas_temp_token_account_info)?;
+        if amount_expected_by_taker != escrow_info.deposited_amount {
+            msg!("error: amount_expected_by_taker != escrow_info.deposited_amount");
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+        let takers_payment_account_info = Self::unpack_token_account(takers_payment_account)?;
+        Self::check_key(
+            "error: escrow_info.receive_mint != takers_payment_account_info.mint",
+            &escrow_info.receive_mint,
+            &takers_payment_account_info.mint,
+            ProgramError::InvalidAccountData,
+        )?;
+        if takers_payment_account_info.amount != escrow_info.expected_amount {
+            msg!("error: takers_payment_account_info.amount != escrow_info.expected_amount");
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+        Self::check_token_program(token_program)?;
+
+        let (bond_to_taker, bond_to_initializer) =
+            Self::take_reservation(&mut escrow_info, taker.key, clock.slot)?;
+
+        escrow_info.committed_taker = *taker.key;
+        escrow_info.committed_until_slot = clock
+            .slot
+            .checked_add(escrow_info.settle_timeout_slots)
+            .ok_or(EscrowError::AmountOverflow)?;
+        escrow_info.payment_vault_pubkey = *takers_payment_account.key;
+        escrow_info.committed_taker_token_to_receive_pubkey = *takers_token_to_receive_account.key;
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;
+
+        Self::move_lamports(escrow_account, taker, bond_to_taker)?;
+        Self::move_lamports(
+            escrow_account,
+            initializers_main_account,
+            bond_to_initializer,
+        )?;
+
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        let owner_change_ix = spl_token::instruction::set_authority(
+            token_program.key,
+            takers_payment_account.key,
+            Some(&pda),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            taker.key,
+            &[taker.key],
+        )?;
+        msg!("Calling the token program to transfer ownership of the payment...");
+        invoke(
+            &owner_change_ix,
+            &[
+                takers_payment_account.clone(),
+                taker.clone(),
+                token_program.clone(),
+            ],
+        )
+    }
+
+    pub fn process_settle_exchange(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let payment_vault = next_account_info(account_info_iter)?;
+        let takers_token_to_receive_account = next_account_info(account_info_iter)?;
+        let initializers_token_to_receive_account = next_account_info(account_info_iter)?;
+        let takers_main_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        if !escrow_info.is_committed() {
+            msg!("error: escrow has no pending two-phase exchange");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if escrow_info.initializer_pubkey != *initializer.key
+            || escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key
+            || escrow_info.payment_vault_pubkey != *payment_vault.key
+            || escrow_info.committed_taker_token_to_receive_pubkey
+                != *takers_token_to_receive_account.key
+            || escrow_info.initializer_token_to_receive_account_pubkey
+                != *initializers_token_to_receive_account.key
+            || escrow_info.committed_taker != *takers_main_account.key
+        {
+            msg!("error: accounts do not match the committed exchange");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if clock.slot >= escrow_info.committed_until_slot {
+            msg!("error: the two-phase exchange timed out and can only be unwound");
+            return Err(EscrowError::CommitExpired.into());
+        }
+
+        Self::check_token_program(token_program)?;
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        let pdas_temp_token_account_info = Self::unpack_token_account(pdas_temp_token_account)?;
+        let payment_vault_info = Self::unpack_token_account(payment_vault)?;
+
+        let (fee_account, fee_amount) = match Self::settlement_fee(
+            account_info_iter,
+            payment_vault_info.amount,
+            None,
+            &escrow_info.market,
+            program_id,
+        )? {
+            Some((fee_account, fee_amount)) => (Some(fee_account), fee_amount),
+            None => (None, 0),
+        };
+        Self::record_fill(
+            account_info_iter,
+            &pdas_temp_token_account_info.mint,
+            pdas_temp_token_account_info.amount,
+            program_id,
+        )?;
+        let net_amount = payment_vault_info.amount - fee_amount;
+        let splits = Self::split_payment(&escrow_info, account_info_iter, net_amount)?;
+        if let Some(fee_account) = fee_account.filter(|_| fee_amount > 0) {
+            let transfer_fee_ix = spl_token::instruction::transfer(
+                token_program.key,
+                payment_vault.key,
+                fee_account.key,
+                &pda,
+                &[&pda],
+                fee_amount,
+            )?;
+            msg!("Calling the token program to transfer the settlement fee...");
+            invoke_signed(
+                &transfer_fee_ix,
+                &[
+                    payment_vault.clone(),
+                    fee_account.clone(),
+                    pda_account.clone(),
+                    token_program.clone(),
+                ],
+                &[&[&b"escrow"[..], &[bump_seed]]],
+            )?;
+        }
+
+        let mut legs = vec![
+            (
+                pdas_temp_token_account,
+                takers_token_to_receive_account,
+                pdas_temp_token_account_info.amount,
+            ),
+            (
+                payment_vault,
+                initializers_token_to_receive_account,
+                net_amount - splits.iter().map(|(_, share)| share).sum::<u64>(),
+            ),
+        ];
+        legs.extend(
+            splits
+                .iter()
+                .map(|(recipient, share)| (payment_vault, *recipient, *share)),
+        );
+        for (vault, destination, amount) in legs.iter() {
+            let transfer_ix = spl_token::instruction::transfer(
+                token_program.key,
+                vault.key,
+                destination.key,
+                &pda,
+                &[&pda],
+                *amount,
+            )?;
+            msg!("Calling the token program to release a leg of the exchange...");
+            invoke_signed(
+                &transfer_ix,
+                &[
+                    (*vault).clone(),
+                    (*destination).clone(),
+                    pda_account.clone(),
+                    token_program.clone(),
+                ],
+                &[&[&b"escrow"[..], &[bump_seed]]],
+            )?;
+        }
+
+        for (vault, rent_destination) in [
+            (pdas_temp_token_account, initializer),
+            (payment_vault, takers_main_account),
+        ]
+        .iter()
+        {
+            let close_ix = spl_token::instruction::close_account(
+                token_program.key,
+                vault.key,
+                rent_destination.key,
+                &pda,
+                &[&pda],
+            )?;
+            msg!("Calling the token program to close a vault...");
+            invoke_signed(
+                &close_ix,
+                &[
+                    (*vault).clone(),
+                    (*rent_destination).clone(),
+                    pda_account.clone(),
+                    token_program.clone(),
+                ],
+                &[&[&b"escrow"[..], &[bump_seed]]],
+            )?;
+        }
+
+        EscrowEvent::Filled {
+            escrow: *escrow_account.key,
+            taker: escrow_info.committed_taker,
+            fill_amount: pdas_temp_token_account_info.amount,
+            paid_amount: payment_vault_info.amount,
+            remaining_amount: 0,
+        }
+        .emit();
+
+        msg!("Closing the escrow account...");
+        Self::close_program_account(escrow_account, initializer)
+    }
+
+    pub fn process_unwind_exchange(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer = next_account_info(account_info_iter)?;
+        if !signer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let payment_vault = next_account_info(account_info_iter)?;
+        let takers_refund_account = next_account_info(account_info_iter)?;
+        let takers_main_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let mut escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        if !escrow_info.is_committed() {
+            msg!("error: escrow has no pending two-phase exchange");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if *signer.key != escrow_info.initializer_pubkey
+            && *signer.key != escrow_info.committed_taker
+        {
+            msg!("error: only the initializer or the committed taker can unwind");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if escrow_info.payment_vault_pubkey != *payment_vault.key
+            || escrow_info.committed_taker != *takers_main_account.key
+        {
+            msg!("error: accounts do not match the committed exchange");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_key(
+            "error: the refund account is not owned by the committed taker",
+            &escrow_info.committed_taker,
+            &Self::unpack_token_account(takers_refund_account)?.owner,
+            ProgramError::InvalidAccountData,
+        )?;
+        if clock.slot < escrow_info.committed_until_slot {
+            msg!("error: the two-phase exchange can still be settled");
+            return Err(EscrowError::EscrowCommitted.into());
+        }
+
+        Self::check_token_program(token_program)?;
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        let payment_vault_info = Self::unpack_token_account(payment_vault)?;
+
+        let refund_ix = spl_token::instruction::transfer(
+            token_program.key,
+            payment_vault.key,
+            takers_refund_account.key,
+            &pda,
+            &[&pda],
+            payment_vault_info.amount,
+        )?;
+        msg!("Calling the token program to return the payment to the taker...");
+        invoke_signed(
+            &refund_ix,
+            &[
+                payment_vault.clone(),
+                takers_refund_account.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
+
+        let close_ix = spl_token::instruction::close_account(
+            token_program.key,
+            payment_vault.key,
+            takers_main_account.key,
+            &pda,
+            &[&pda],
+        )?;
+        msg!("Calling the token program to close the payment vault...");
+        invoke_signed(
+            &close_ix,
+            &[
+                payment_vault.clone(),
+                takers_main_account.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
+
+        escrow_info.committed_taker = Pubkey::default();
+        escrow_info.committed_until_slot = 0;
+        escrow_info.payment_vault_pubkey = Pubkey::default();
+        escrow_info.committed_taker_token_to_receive_pubkey = Pubkey::default();
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    pub fn process_init_auction_escrow(
+        accounts: &[AccountInfo],
+        reserve_price: u64,
+        bid_slots: u64,
+        reveal_slots: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if bid_slots == 0 || reveal_slots == 0 {
+            msg!("error: both auction phases must last at least a slot");
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        if accounts.len() != 8 {
+            msg!("error: auction escrows can not be oracle priced");
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        let clock = Clock::from_account_info(&accounts[7])?;
+
+        Self::process_init_escrow(accounts, reserve_price, &EscrowTerms::default(), program_id)?;
+
+        let escrow_account = &accounts[3];
+        let mut escrow_info = Escrow::unpack(&escrow_account.data.borrow())?;
+        escrow_info.auction_bid_end_slot = clock
+            .slot
+            .checked_add(bid_slots)
+            .ok_or(EscrowError::AmountOverflow)?;
+        escrow_info.auction_reveal_end_slot = escrow_info
+            .auction_bid_end_slot
+            .checked_add(reveal_slots)
+            .ok_or(EscrowError::AmountOverflow)?;
+        escrow_info.terms_hash = escrow_info.hash_terms();
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    pub fn process_commit_bid(
+        accounts: &[AccountInfo],
+        commitment: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let bidder = next_account_info(account_info_iter)?;
+        if !bidder.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let deposit_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let initializers_token_to_receive_account = next_account_info(account_info_iter)?;
+        let bid_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        let escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        if !escrow_info.is_auction() {
+            return Err(EscrowError::NotAuction.into());
+        }
+        if !escrow_info.allows_taker(bidder.key) {
+            msg!("error: escrow is private to another taker");
+            return Err(EscrowError::TakerNotAllowed.into());
+        }
+        if clock.slot >= escrow_info.auction_bid_end_slot {
+            msg!("error: bidding has ended");
+            return Err(EscrowError::AuctionPhase.into());
+        }
+        Self::check_key(
+            "error: escrow_info.initializer_token_to_receive_account_pubkey != *initializers_token_to_receive_account.key",
+            &escrow_info.initializer_token_to_receive_account_pubkey,
+            initializers_token_to_receive_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        // settlement pays the initializer out of the deposit, so it must be in their token
+        Self::check_key(
+            "error: the deposit is not in the token the initializer receives",
+            &Self::unpack_token_account(deposit_account)?.mint,
+            &Self::unpack_token_account(initializers_token_to_receive_account)?.mint,
+            ProgramError::InvalidAccountData,
+        )?;
+
+        let (bid_address, bump_seed) =
+            Bid::find_address(escrow_account.key, bidder.key, program_id);
+        Self::check_key(
+            "error: bid_address != *bid_account.key",
+            &bid_address,
+            bid_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        Self::create_pda_account(
+            bidder,
+            bid_account,
+            system_program,
+            rent,
+            Bid::LEN,
+            program_id,
+            &[
+                b"bid",
+                escrow_account.key.as_ref(),
+                bidder.key.as_ref(),
+                &[bump_seed],
+            ],
+        )?;
+        Bid::pack(
+            Bid {
+                is_initialized: true,
+                escrow: *escrow_account.key,
+                bidder: *bidder.key,
+                commitment,
+                deposit_vault_pubkey: *deposit_account.key,
+                reveal_end_slot: escrow_info.auction_reveal_end_slot,
+                ..Bid::default()
+            },
+            &mut bid_account.data.borrow_mut(),
+        )?;
+
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        let owner_change_ix = spl_token::instruction::set_authority(
+            token_program.key,
+            deposit_account.key,
+            Some(&pda),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            bidder.key,
+            &[bidder.key],
+        )?;
+        msg!("Calling the token program to transfer ownership of the bid deposit...");
+        invoke(
+            &owner_change_ix,
+            &[
+                deposit_account.clone(),
+                bidder.clone(),
+                token_program.clone(),
+            ],
+        )
+    }
+
+    pub fn process_reveal_bid(
+        accounts: &[AccountInfo],
+        amount: u64,
+        salt: &[u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let bidder = next_account_info(account_info_iter)?;
+        if !bidder.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let escrow_account = next_account_info(account_info_iter)?;
+        let bid_account = next_account_info(account_info_iter)?;
+        let deposit_vault = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let mut escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        let mut bid_info = Self::unpack_program_account::<Bid>(bid_account, program_id)?;
+        if bid_info.escrow != *escrow_account.key
+            || bid_info.bidder != *bidder.key
+            || bid_info.deposit_vault_pubkey != *deposit_vault.key
+        {
+            msg!("error: accounts do not match the bid");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if clock.slot < escrow_info.auction_bid_end_slot
+            || clock.slot >= escrow_info.auction_reveal_end_slot
+            || bid_info.is_revealed
+        {
+            msg!("error: bids can only be revealed once, during the reveal phase");
+            return Err(EscrowError::AuctionPhase.into());
+        }
+        if Bid::commitment(amount, salt, bidder.key) != bid_info.commitment {
+            return Err(EscrowError::CommitmentMismatch.into());
+        }
+        if amount < escrow_info.expected_amount
+            || amount > Self::unpack_token_account(deposit_vault)?.amount
+        {
+            return Err(EscrowError::InvalidBid.into());
+        }
+
+        bid_info.is_revealed = true;
+        bid_info.revealed_amount = amount;
+        Bid::pack(bid_info, &mut bid_account.data.borrow_mut())?;
+        // ties go to the bid revealed first
+        if amount > escrow_info.best_bid_amount {
+            escrow_info.best_bid_amount = amount;
+            escrow_info.best_bid_pubkey = *bid_account.key;
+            Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;
+        }
+        Ok(())
+    }
+
+    pub fn process_settle_auction(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let initializers_token_to_receive_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        if !escrow_info.is_auction() {
+            return Err(EscrowError::NotAuction.into());
+        }
+        if escrow_info.initializer_pubkey != *initializer.key
+            || escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key
+            || escrow_info.initializer_token_to_receive_account_pubkey
+                != *initializers_token_to_receive_account.key
+        {
+            msg!("error: accounts do not match the escrow");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if clock.slot < escrow_info.auction_reveal_end_slot {
+            msg!("error: bids can still be revealed");
+            return Err(EscrowError::AuctionPhase.into());
+        }
+
+        // the PDA signs for both the escrow's vault and the winner's deposit vault
+        Self::check_token_program(token_program)?;
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        let pdas_temp_token_account_info = Self::unpack_token_account(pdas_temp_token_account)?;
+
+        if escrow_info.best_bid_pubkey == Pubkey::default() {
+            Self::hand_back_token_account(
+                pdas_temp_token_account,
+                initializer.key,
+                token_program,
+                pda_account,
+                &pda,
+                bump_seed,
+            )?;
+            EscrowEvent::Cancelled {
+                escrow: *escrow_account.key,
+                returned_amount: pdas_temp_token_account_info.amount,
+            }
+            .emit();
+
+            msg!("No bids, closing the escrow account...");
+            return Self::close_program_account(escrow_account, initializer);
+        }
+
+        let bid_account = next_account_info(account_info_iter)?;
+        let deposit_vault = next_account_info(account_info_iter)?;
+        let winners_token_to_receive_account = next_account_info(account_info_iter)?;
+        let winners_main_account = next_account_info(account_info_iter)?;
+        Self::check_key(
+            "error: escrow_info.best_bid_pubkey != *bid_account.key",
+            &escrow_info.best_bid_pubkey,
+            bid_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        let bid_info = Self::unpack_program_account::<Bid>(bid_account, program_id)?;
+        if bid_info.deposit_vault_pubkey != *deposit_vault.key
+            || bid_info.bidder != *winners_main_account.key
+        {
+            msg!("error: accounts do not match the winning bid");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_key(
+            "error: the deposit can only go to a token account of the winner",
+            &bid_info.bidder,
+            &Self::unpack_token_account(winners_token_to_receive_account)?.owner,
+            ProgramError::InvalidAccountData,
+        )?;
+
+        let payment = escrow_info.best_bid_amount;
+        let (fee_account, fee_amount) = match Self::settlement_fee(
+            account_info_iter,
+            payment,
+            None,
+            &escrow_info.market,
+            program_id,
+        )? {
+            Some((fee_account, fee_amount)) => (Some(fee_account), fee_amount),
+            None => (None, 0),
+        };
+        Self::record_fill(
+            account_info_iter,
+            &pdas_temp_token_account_info.mint,
+            pdas_temp_token_account_info.amount,
+            program_id,
+        )?;
+
+        for (vault, destination, amount) in [
+            (deposit_vault, fee_account, fee_amount),
+            (
+                deposit_vault,
+                Some(initializers_token_to_receive_account),
+                payment - fee_amount,
+            ),
+            (
+                pdas_temp_token_account,
+                Some(winners_token_to_receive_account),
+                pdas_temp_token_account_info.amount,
+            ),
+        ]
+        .iter()
+        {
+            let destination = match destination {
+                Some(destination) if *amount > 0 => destination,
+                _ => continue,
+            };
+            let transfer_ix = spl_token::instruction::transfer(
+                token_program.key,
+                vault.key,
+                destination.key,
+                &pda,
+                &[&pda],
+                *amount,
+            )?;
+            msg!("Calling the token program to pay out the auction...");
+            invoke_signed(
+                &transfer_ix,
+                &[
+                    (*vault).clone(),
+                    (*destination).clone(),
+                    pda_account.clone(),
+                    token_program.clone(),
+                ],
+                &[&[&b"escrow"[..], &[bump_seed]]],
+            )?;
+        }
+
+        let close_pdas_temp_acc_ix = spl_token::instruction::close_account(
+            token_program.key,
+            pdas_temp_token_account.key,
+            initializer.key,
+            &pda,
+            &[&pda],
+        )?;
+        msg!("Calling the token program to close pda's temp account...");
+        invoke_signed(
+            &close_pdas_temp_acc_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                initializer.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
+        // what the winner deposited above their bid stays in the vault they get back
+        Self::hand_back_token_account(
+            deposit_vault,
+            &bid_info.bidder,
+            token_program,
+            pda_account,
+            &pda,
+            bump_seed,
+        )?;
+
+        EscrowEvent::Filled {
+            escrow: *escrow_account.key,
+            taker: bid_info.bidder,
+            fill_amount: pdas_temp_token_account_info.amount,
+            paid_amount: payment,
+            remaining_amount: 0,
+        }
+        .emit();
+
+        msg!("Closing the bid and escrow accounts...");
+        Self::close_program_account(bid_account, winners_main_account)?;
+        Self::close_program_account(escrow_account, initializer)
+    }
+
+    pub fn process_refund_bid(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let bidder = next_account_info(account_info_iter)?;
+        let bid_account = next_account_info(account_info_iter)?;
+        let deposit_vault = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let bid_info = Self::unpack_program_account::<Bid>(bid_account, program_id)?;
+        if bid_info.bidder != *bidder.key
+            || bid_info.deposit_vault_pubkey != *deposit_vault.key
+            || bid_info.escrow != *escrow_account.key
+        {
+            msg!("error: accounts do not match the bid");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if clock.slot < bid_info.reveal_end_slot {
+            msg!("error: bids can still be revealed");
+            return Err(EscrowError::AuctionPhase.into());
+        }
+        // settling closes the winning bid, so only an open escrow can still name it
+        if let Some(escrow_info) = Self::open_escrow(escrow_account, program_id) {
+            if escrow_info.best_bid_pubkey == *bid_account.key {
+                msg!("error: the winning bid is paid out by settling the auction");
+                return Err(EscrowError::AuctionPhase.into());
+            }
+        }
+
+        // handing the vault back checks the token program and the PDA account
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::hand_back_token_account(
+            deposit_vault,
+            bidder.key,
+            token_program,
+            pda_account,
+            &pda,
+            bump_seed,
+        )?;
+        Self::close_program_account(bid_account, bidder)
+    }
+
+    pub fn process_init_raffle_escrow(
+        accounts: &[AccountInfo],
+        ticket_price: u64,
+        sale_slots: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if ticket_price == 0 || sale_slots == 0 {
+            msg!("error: raffles need a ticket price and at least a slot of sales");
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        if accounts.len() != 8 {
+            msg!("error: raffle escrows can not be oracle priced");
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        let clock = Clock::from_account_info(&accounts[7])?;
+
+        Self::process_init_escrow(accounts, ticket_price, &EscrowTerms::default(), program_id)?;
+
+        let escrow_account = &accounts[3];
+        let mut escrow_info = Escrow::unpack(&escrow_account.data.borrow())?;
+        escrow_info.raffle_end_slot = clock
+            .slot
+            .checked_add(sale_slots)
+            .ok_or(EscrowError::AmountOverflow)?;
+        escrow_info.terms_hash = escrow_info.hash_terms();
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    pub fn process_buy_ticket(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let buyer = next_account_info(account_info_iter)?;
+        if !buyer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let buyers_paying_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let initializers_token_to_receive_account = next_account_info(account_info_iter)?;
+        let ticket_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        let mut escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        if !escrow_info.is_raffle() {
+            return Err(EscrowError::NotRaffle.into());
+        }
+        if !escrow_info.allows_taker(buyer.key) {
+            msg!("error: escrow is private to another taker");
+            return Err(EscrowError::TakerNotAllowed.into());
+        }
+        if clock.slot >= escrow_info.raffle_end_slot {
+            msg!("error: ticket sales have ended");
+            return Err(EscrowError::RafflePhase.into());
+        }
+        Self::check_key(
+            "error: escrow_info.initializer_token_to_receive_account_pubkey != *initializers_token_to_receive_account.key",
+            &escrow_info.initializer_token_to_receive_account_pubkey,
+            initializers_token_to_receive_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+
+        let index = escrow_info.raffle_ticket_count;
+        let (ticket_address, bump_seed) =
+            Ticket::find_address(escrow_account.key, index, program_id);
+        Self::check_key(
+            "error: ticket_address != *ticket_account.key",
+            &ticket_address,
+            ticket_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        Self::create_pda_account(
+            buyer,
+            ticket_account,
+            system_program,
+            rent,
+            Ticket::LEN,
+            program_id,
+            &[
+                b"ticket",
+                escrow_account.key.as_ref(),
+                &index.to_le_bytes(),
+                &[bump_seed],
+            ],
+        )?;
+        Ticket::pack(
+            Ticket {
+                is_initialized: true,
+                escrow: *escrow_account.key,
+                buyer: *buyer.key,
+                index,
+            },
+            &mut ticket_account.data.borrow_mut(),
+        )?;
+        escrow_info.raffle_ticket_count =
+            index.checked_add(1).ok_or(EscrowError::AmountOverflow)?;
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;
+
+        let ticket_price = escrow_info.expected_amount;
+        let (fee_account, fee_amount) = match Self::settlement_fee(
+            account_info_iter,
+            ticket_price,
+            Some(buyer),
+            &escrow_info.market,
+            program_id,
+        )? {
+            Some((fee_account, fee_amount)) => (Some(fee_account), fee_amount),
+            None => (None, 0),
+        };
+        for (destination, amount) in [
+            (fee_account, fee_amount),
+            (
+                Some(initializers_token_to_receive_account),
+                ticket_price - fee_amount,
+            ),
+        ]
+        .iter()
+        {
+            let destination = match destination {
+                Some(destination) if *amount > 0 => destination,
+                _ => continue,
+            };
+            let transfer_ix = spl_token::instruction::transfer(
+                token_program.key,
+                buyers_paying_account.key,
+                destination.key,
+                buyer.key,
+                &[buyer.key],
+                *amount,
+            )?;
+            msg!("Calling the token program to pay for the ticket...");
+            invoke(
+                &transfer_ix,
+                &[
+                    buyers_paying_account.clone(),
+                    (*destination).clone(),
+                    buyer.clone(),
+                    token_program.clone(),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn process_draw_raffle(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let slot_hashes = next_account_info(account_info_iter)?;
+
+        let escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        if !escrow_info.is_raffle() {
+            return Err(EscrowError::NotRaffle.into());
+        }
+        if escrow_info.initializer_pubkey != *initializer.key
+            || escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key
+        {
+            msg!("error: accounts do not match the escrow");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if clock.slot < escrow_info.raffle_end_slot {
+            msg!("error: tickets are still on sale");
+            return Err(EscrowError::RafflePhase.into());
+        }
+        Self::check_key(
+            "error: expected the slot hashes sysvar",
+            &sysvar::slot_hashes::id(),
+            slot_hashes.key,
+            ProgramError::InvalidArgument,
+        )?;
+        Self::check_token_program(token_program)?;
+
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        let pdas_temp_token_account_info = Self::unpack_token_account(pdas_temp_token_account)?;
+
+        if escrow_info.raffle_ticket_count == 0 {
+            Self::hand_back_token_account(
+                pdas_temp_token_account,
+                initializer.key,
+                token_program,
+                pda_account,
+                &pda,
+                bump_seed,
+            )?;
+            EscrowEvent::Cancelled {
+                escrow: *escrow_account.key,
+                returned_amount: pdas_temp_token_account_info.amount,
+            }
+            .emit();
+
+            msg!("No tickets sold, closing the escrow account...");
+            return Self::close_program_account(escrow_account, initializer);
+        }
+
+        // the sysvar holds a length prefixed list of (slot, hash), the most recent first
+        let recent_hash = slot_hashes
+            .data
+            .borrow()
+            .get(16..48)
+            .map(|hash| hashv(&[hash, escrow_account.key.as_ref()]))
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let mut index_bytes = [0; 8];
+        index_bytes.copy_from_slice(&recent_hash.as_ref()[..8]);
+        let drawn_index = u64::from_le_bytes(index_bytes) % escrow_info.raffle_ticket_count;
+
+        let ticket_account = next_account_info(account_info_iter)?;
+        let winners_token_to_receive_account = next_account_info(account_info_iter)?;
+        let ticket_info = Self::unpack_program_account::<Ticket>(ticket_account, program_id)?;
+        if ticket_info.escrow != *escrow_account.key || ticket_info.index != drawn_index {
+            return Err(EscrowError::TicketNotDrawn.into());
+        }
+        Self::check_key(
+            "error: the deposit can only go to a token account of the ticket's buyer",
+            &ticket_info.buyer,
+            &Self::unpack_token_account(winners_token_to_receive_account)?.owner,
+            ProgramError::InvalidAccountData,
+        )?;
+        Self::record_fill(
+            account_info_iter,
+            &pdas_temp_token_account_info.mint,
+            pdas_temp_token_account_info.amount,
+            program_id,
+        )?;
+
+        let transfer_to_winner_ix = spl_token::instruction::transfer(
+            token_program.key,
+            pdas_temp_token_account.key,
+            winners_token_to_receive_account.key,
+            &pda,
+            &[&pda],
+            pdas_temp_token_account_info.amount,
+        )?;
+        msg!("Calling the token program to transfer tokens to the winner...");
+        invoke_signed(
+            &transfer_to_winner_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                winners_token_to_receive_account.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
+
+        let close_pdas_temp_acc_ix = spl_token::instruction::close_account(
+            token_program.key,
+            pdas_temp_token_account.key,
+            initializer.key,
+            &pda,
+            &[&pda],
+        )?;
+        msg!("Calling the token program to close pda's temp account...");
+        invoke_signed(
+            &close_pdas_temp_acc_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                initializer.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
+
+        EscrowEvent::Filled {
+            escrow: *escrow_account.key,
+            taker: ticket_info.buyer,
+            fill_amount: pdas_temp_token_account_info.amount,
+            paid_amount: escrow_info
+                .expected_amount
+                .saturating_mul(escrow_info.raffle_ticket_count),
+            remaining_amount: 0,
+        }
+        .emit();
+
+        msg!("Closing the escrow account...");
+        Self::close_program_account(escrow_account, initializer)
+    }
+
+    pub fn process_close_ticket(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let buyer = next_account_info(account_info_iter)?;
+        let ticket_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+
+        let ticket_info = Self::unpack_program_account::<Ticket>(ticket_account, program_id)?;
+        if ticket_info.buyer != *buyer.key || ticket_info.escrow != *escrow_account.key {
+            msg!("error: accounts do not match the ticket");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // the drawn ticket has to be around until the raffle is drawn
+        if Self::open_escrow(escrow_account, program_id).is_some() {
+            msg!("error: the raffle has not been drawn");
+            return Err(EscrowError::RafflePhase.into());
+        }
+        Self::close_program_account(ticket_account, buyer)
+    }
+
+    pub fn process_init_request(
+        accounts: &[AccountInfo],
+        min_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let buyer = next_account_info(account_info_iter)?;
+        if !buyer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let temp_token_account = next_account_info(account_info_iter)?;
+        let token_to_receive_account = next_account_info(account_info_iter)?;
+        let request_account = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        if !rent.is_exempt(request_account.lamports(), request_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+        let token_program = next_account_info(account_info_iter)?;
+        let mint_list_account = next_account_info(account_info_iter)?;
+        Self::check_mint_list(
+            mint_list_account,
+            &[temp_token_account, token_to_receive_account],
+            &Pubkey::default(),
+            program_id,
+        )?;
+
+        if request_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if Request::unpack_unchecked(&request_account.data.borrow())?.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        Request::pack(
+            Request {
+                is_initialized: true,
+                buyer: *buyer.key,
+                payment_vault_pubkey: *temp_token_account.key,
+                buyer_token_to_receive_pubkey: *token_to_receive_account.key,
+                payment_mint: Self::unpack_token_account(temp_token_account)?.mint,
+                wanted_mint: Self::unpack_token_account(token_to_receive_account)?.mint,
+                min_amount,
+            },
+            &mut request_account.data.borrow_mut(),
+        )?;
+
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        let owner_change_ix = spl_token::instruction::set_authority(
+            token_program.key,
+            temp_token_account.key,
+            Some(&pda),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            buyer.key,
+            &[buyer.key],
+        )?;
+        msg!("Calling the token program to transfer ownership of the payment...");
+        invoke(
+            &owner_change_ix,
+            &[
+                temp_token_account.clone(),
+                buyer.clone(),
+                token_program.clone(),
+            ],
+        )
+    }
+
+    pub fn process_post_quote(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let maker = next_account_info(account_info_iter)?;
+        if !maker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let deposit_account = next_account_info(account_info_iter)?;
+        let makers_token_to_receive_account = next_account_info(account_info_iter)?;
+        let request_account = next_account_info(account_info_iter)?;
+        let quote_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        let request_info = Self::unpack_program_account::<Request>(request_account, program_id)?;
+        let deposit_account_info = Self::unpack_token_account(deposit_account)?;
+        if deposit_account_info.mint != request_info.wanted_mint
+            || Self::unpack_token_account(makers_token_to_receive_account)?.mint
+                != request_info.payment_mint
+        {
+            msg!("error: token accounts do not match the request's mints");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if deposit_account_info.amount < request_info.min_amount {
+            return Err(EscrowError::QuoteTooLow.into());
+        }
+
+        let (quote_address, bump_seed) =
+            Quote::find_address(request_account.key, maker.key, program_id);
+        Self::check_key(
+            "error: quote_address != *quote_account.key",
+            &quote_address,
+            quote_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        Self::create_pda_account(
+            maker,
+            quote_account,
+            system_program,
+            rent,
+            Quote::LEN,
+            program_id,
+            &[
+                b"quote",
+                request_account.key.as_ref(),
+                maker.key.as_ref(),
+                &[bump_seed],
+            ],
+        )?;
+        Quote::pack(
+            Quote {
+                is_initialized: true,
+                request: *request_account.key,
+                maker: *maker.key,
+                deposit_vault_pubkey: *deposit_account.key,
+                maker_token_to_receive_pubkey: *makers_token_to_receive_account.key,
+            },
+            &mut quote_account.data.borrow_mut(),
+        )?;
+
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        let owner_change_ix = spl_token::instruction::set_authority(
+            token_program.key,
+            deposit_account.key,
+            Some(&pda),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            maker.key,
+            &[maker.key],
+        )?;
+        msg!("Calling the token program to transfer ownership of the quote deposit...");
+        invoke(
+            &owner_change_ix,
+            &[
+                deposit_account.clone(),
+                maker.clone(),
+                token_program.clone(),
+            ],
+        )
+    }
+
+    pub fn process_accept_quote(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let buyer = next_account_info(account_info_iter)?;
+        if !buyer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let payment_vault = next_account_info(account_info_iter)?;
+        let buyers_token_to_receive_account = next_account_info(account_info_iter)?;
+        let request_account = next_account_info(account_info_iter)?;
+        let quote_account = next_account_info(account_info_iter)?;
+        let deposit_vault = next_account_info(account_info_iter)?;
+        let makers_token_to_receive_account = next_account_info(account_info_iter)?;
+        let makers_main_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+
+        let request_info = Self::unpack_program_account::<Request>(request_account, program_id)?;
+        if request_info.buyer != *buyer.key
+            || request_info.payment_vault_pubkey != *payment_vault.key
+            || request_info.buyer_token_to_receive_pubkey != *buyers_token_to_receive_account.key
+        {
+            msg!("error: accounts do not match the request");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let quote_info = Self::unpack_program_account::<Quote>(quote_account, program_id)?;
+        if quote_info.request != *request_account.key
+            || quote_info.deposit_vault_pubkey != *deposit_vault.key
+            || quote_info.maker_token_to_receive_pubkey != *makers_token_to_receive_account.key
+            || quote_info.maker != *makers_main_account.key
+        {
+            msg!("error: accounts do not match the quote");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_token_program(token_program)?;
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+
+        let payment = Self::unpack_token_account(payment_vault)?.amount;
+        let deposit_vault_info = Self::unpack_token_account(deposit_vault)?;
+        let (fee_account, fee_amount) = match Self::settlement_fee(
+            account_info_iter,
+            payment,
+            Some(buyer),
+            &Pubkey::default(),
+            program_id,
+        )? {
+            Some((fee_account, fee_amount)) => (Some(fee_account), fee_amount),
+            None => (None, 0),
+        };
+        Self::record_fill(
+            account_info_iter,
+            &deposit_vault_info.mint,
+            deposit_vault_info.amount,
+            program_id,
+        )?;
+
+        for (vault, destination, amount) in [
+            (payment_vault, fee_account, fee_amount),
+            (
+                payment_vault,
+                Some(makers_token_to_receive_account),
+                payment - fee_amount,
+            ),
+            (
+                deposit_vault,
+                Some(buyers_token_to_receive_account),
+                deposit_vault_info.amount,
+            ),
+        ]
+        .iter()
+        {
+            let destination = match destination {
+                Some(destination) if *amount > 0 => destination,
+                _ => continue,
+            };
+            let transfer_ix = spl_token::instruction::transfer(
+                token_program.key,
+                vault.key,
+                destination.key,
+                &pda,
+                &[&pda],
+                *amount,
+            )?;
+            msg!("Calling the token program to settle the quote...");
+            invoke_signed(
+                &transfer_ix,
+                &[
+                    (*vault).clone(),
+                    (*destination).clone(),
+                    pda_account.clone(),
+                    token_program.clone(),
+                ],
+                &[&[&b"escrow"[..], &[bump_seed]]],
+            )?;
+        }
+        for (vault, rent_destination) in
+            [(payment_vault, buyer), (deposit_vault, makers_main_account)].iter()
+        {
+            let close_vault_ix = spl_token::instruction::close_account(
+                token_program.key,
+                vault.key,
+                rent_destination.key,
+                &pda,
+                &[&pda],
+            )?;
+            msg!("Calling the token program to close a vault...");
+            invoke_signed(
+                &close_vault_ix,
+                &[
+                    (*vault).clone(),
+                    (*rent_destination).clone(),
+                    pda_account.clone(),
+                    token_program.clone(),
+                ],
+                &[&[&b"escrow"[..], &[bump_seed]]],
+            )?;
+        }
+
+        msg!("Closing the quote and request accounts...");
+        Self::close_program_account(quote_account, makers_main_account)?;
+        Self::close_program_account(request_account, buyer)
+    }
+
+    pub fn process_cancel_request(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let buyer = next_account_info(account_info_iter)?;
+        if !buyer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let payment_vault = next_account_info(account_info_iter)?;
+        let request_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+
+        let request_info = Self::unpack_program_account::<Request>(request_account, program_id)?;
+        if request_info.buyer != *buyer.key
+            || request_info.payment_vault_pubkey != *payment_vault.key
+        {
+            msg!("error: accounts do not match the request");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // handing the vault back checks the token program and the PDA account
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::hand_back_token_account(
+            payment_vault,
+            buyer.key,
+            token_program,
+            pda_account,
+            &pda,
+            bump_seed,
+        )?;
+        Self::close_program_account(request_account, buyer)
+    }
+
+    pub fn process_withdraw_quote(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let maker = next_account_info(account_info_iter)?;
+        if !maker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let quote_account = next_account_info(account_info_iter)?;
+        let deposit_vault = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+
+        let quote_info = Self::unpack_program_account::<Quote>(quote_account, program_id)?;
+        if quote_info.maker != *maker.key || quote_info.deposit_vault_pubkey != *deposit_vault.key {
+            msg!("error: accounts do not match the quote");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // handing the vault back checks the token program and the PDA account
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::hand_back_token_account(
+            deposit_vault,
+            maker.key,
+            token_program,
+            pda_account,
+            &pda,
+            bump_seed,
+        )?;
+        Self::close_program_account(quote_account, maker)
+    }
+
+    pub fn process_set_chained_offer(
+        accounts: &[AccountInfo],
+        expected_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let escrow_account = next_account_info(account_info_iter)?;
+        let initializers_token_to_receive_account = next_account_info(account_info_iter)?;
+        let chain_receive_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let mint_list_account = next_account_info(account_info_iter)?;
+
+        let mut escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        Self::check_key(
+            "error: escrow_info.initializer_pubkey != *initializer.key",
+            &escrow_info.initializer_pubkey,
+            initializer.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        Self::check_key(
+            "error: escrow_info.initializer_token_to_receive_account_pubkey != *initializers_token_to_receive_account.key",
+            &escrow_info.initializer_token_to_receive_account_pubkey,
+            initializers_token_to_receive_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        // the follow-on offer is priced at a flat amount and settled by Exchange alone
+        if expected_amount == 0
+            || escrow_info.is_chained()
+            || escrow_info.is_oracle_priced()
+            || escrow_info.is_curve_priced()
+            || escrow_info.relists()
+            || escrow_info.settle_timeout_slots != 0
+            || escrow_info.is_auction()
+            || escrow_info.is_raffle()
+        {
+            return Err(EscrowError::ChainingUnsupported.into());
+        }
+        Self::check_token_program(token_program)?;
+        Self::check_key(
+            "error: spl_token::id() != *chain_receive_account.owner",
+            &spl_token::id(),
+            chain_receive_account.owner,
+            ProgramError::IncorrectProgramId,
+        )?;
+        escrow_info.chain_receive_mint = Self::unpack_token_account(chain_receive_account)?.mint;
+        Self::check_mint_list(
+            mint_list_account,
+            &[initializers_token_to_receive_account, chain_receive_account],
+            &escrow_info.market,
+            program_id,
+        )?;
+
+        escrow_info.chain_expected_amount = expected_amount;
+        escrow_info.chain_receive_pubkey = *chain_receive_account.key;
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;
+
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        let owner_change_ix = spl_token::instruction::set_authority(
+            token_program.key,
+            initializers_token_to_receive_account.key,
+            Some(&pda),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            initializer.key,
+            &[initializer.key],
+        )?;
+        msg!("Calling the token program to transfer ownership of the proceeds...");
+        invoke(
+            &owner_change_ix,
+            &[
+                initializers_token_to_receive_account.clone(),
+                initializer.clone(),
+                token_program.clone(),
+            ],
+        )
+    }
+
+    /// Points the escrow's proceeds at another of the initializer's token accounts for the
+    /// receive mint, for a receive account that was closed or is no longer trusted
+    pub fn process_set_receive_account(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let escrow_account = next_account_info(account_info_iter)?;
+        let receive_account = next_account_info(account_info_iter)?;
+
+        let mut escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        Self::check_key(
+            "error: escrow_info.initializer_pubkey != *initializer.key",
+            &escrow_info.initializer_pubkey,
+            initializer.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        // the PDA holds a chained escrow's receive account, it is the follow-on offer's deposit
+        if escrow_info.is_chained() {
+            return Err(EscrowError::ChainingUnsupported.into());
+        }
+        Self::check_key(
+            "error: spl_token::id() != *receive_account.owner",
+            &spl_token::id(),
+            receive_account.owner,
+            ProgramError::IncorrectProgramId,
+        )?;
+        let receive_account_info = TokenAccount::unpack(&receive_account.data.borrow())?;
+        Self::check_key(
+            "error: escrow_info.receive_mint != receive_account_info.mint",
+            &escrow_info.receive_mint,
+            &receive_account_info.mint,
+            ProgramError::InvalidAccountData,
+        )?;
+        Self::check_key(
+            "error: escrow_info.initializer_pubkey != receive_account_info.owner",
+            &escrow_info.initializer_pubkey,
+            &receive_account_info.owner,
+            ProgramError::InvalidAccountData,
+        )?;
+
+        escrow_info.initializer_token_to_receive_account_pubkey = *receive_account.key;
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    pub fn process_set_operator(
+        accounts: &[AccountInfo],
+        operator: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let escrow_account = next_account_info(account_info_iter)?;
+
+        let mut escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        Self::check_key(
+            "error: escrow_info.initializer_pubkey != *initializer.key",
+            &escrow_info.initializer_pubkey,
+            initializer.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        escrow_info.operator = *operator;
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    pub fn process_reprice(
+        accounts: &[AccountInfo],
+        expected_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let authority = next_account_info(account_info_iter)?;
+        if !authority.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let escrow_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let mut escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        if !escrow_info.is_managed_by(authority.key) {
+            msg!("error: signer is neither the initializer nor the operator");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // curves price every fill themselves, auctions and raffles sell at the price they opened at
+        if expected_amount == 0
+            || escrow_info.is_curve_priced()
+            || escrow_info.is_auction()
+            || escrow_info.is_raffle()
+        {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        if escrow_info.is_committed() {
+            msg!("error: escrow has a pending two-phase exchange");
+            return Err(EscrowError::EscrowCommitted.into());
+        }
+        if escrow_info.is_reserved_at(clock.slot) {
+            msg!("error: escrow is reserved by a taker");
+            return Err(EscrowError::EscrowReserved.into());
+        }
+
+        escrow_info.expected_amount = expected_amount;
+        escrow_info.revised_at_slot = clock.slot;
+        escrow_info.terms_hash = escrow_info.hash_terms();
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    pub fn process_withdraw_deposit(
+        accounts: &[AccountInfo],
+        amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let initializers_token_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let mint_list_account = next_account_info(account_info_iter)?;
+
+        let mut escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        if escrow_info.is_auction() {
+            return Err(EscrowError::AuctionEscrow.into());
+        }
+        if escrow_info.is_raffle() {
+            return Err(EscrowError::RaffleEscrow.into());
+        }
+        if escrow_info.is_committed() {
+            msg!("error: escrow has a pending two-phase exchange");
+            return Err(EscrowError::EscrowCommitted.into());
+        }
+        Self::check_key(
+            "error: escrow_info.initializer_pubkey != *initializer.key",
+            &escrow_info.initializer_pubkey,
+            initializer.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        Self::check_key(
+            "error: escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key",
+            &escrow_info.temp_token_account_pubkey,
+            pdas_temp_token_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        if clock.slot < escrow_info.cancel_locked_until_slot {
+            msg!("error: escrow is locked until a later slot");
+            return Err(EscrowError::CancelLocked.into());
+        }
+        if escrow_info.is_reserved_at(clock.slot) {
+            msg!("error: escrow is reserved by a taker");
+            return Err(EscrowError::EscrowReserved.into());
+        }
+        // withdrawing everything is a Cancel, which also closes the escrow
+        if amount == 0 || amount >= escrow_info.deposited_amount {
+            msg!("error: amount must leave part of escrow_info.deposited_amount");
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        Self::check_token_program(token_program)?;
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        Self::check_mint_list(mint_list_account, &[], &escrow_info.market, program_id)?;
+        let condition = &escrow_info.release_condition;
+        if condition.is_set() && condition.gates_cancel {
+            Self::check_release_condition(condition, next_account_info(account_info_iter)?)?;
+        }
+
+        // partially filled escrows price the filled slices out of the same expected amount
+        let total_amount = if escrow_info.allows_partial_fills() {
+            escrow_info
+                .filled_amount
+                .checked_add(escrow_info.deposited_amount)
+                .ok_or(EscrowError::AmountOverflow)?
+        } else {
+            escrow_info.deposited_amount
+        };
+        escrow_info.expected_amount = math::prorated_cost(
+            escrow_info.expected_amount,
+            total_amount,
+            0,
+            total_amount - amount,
+        )
+        .ok_or(EscrowError::AmountOverflow)?;
+        escrow_info.deposited_amount -= amount;
+        let vault_info = Self::unpack_token_account(pdas_temp_token_account)?;
+        Self::check_escrow_size(mint_list_account, &vault_info, &escrow_info, program_id)?;
+
+        let withdraw_ix = spl_token::instruction::transfer(
+            token_program.key,
+            pdas_temp_token_account.key,
+            initializers_token_account.key,
+            &pda,
+            &[&pda],
+            amount,
+        )?;
+        msg!("Calling the token program to return part of the deposit to the initializer...");
+        invoke_signed(
+            &withdraw_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                initializers_token_account.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
+
+        escrow_info.revised_at_slot = clock.slot;
+        escrow_info.terms_hash = escrow_info.hash_terms();
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    pub fn process_operator_cancel(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let (operator, cancel_accounts) = accounts
+            .split_first()
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if !operator.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let escrow_account = cancel_accounts
+            .get(3)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        if escrow_info.operator == Pubkey::default() || escrow_info.operator != *operator.key {
+            msg!("error: signer is not the escrow's operator");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_refund_account(&escrow_info, &cancel_accounts[2])?;
+        Self::cancel(cancel_accounts, true, program_id)
+    }
+
+    /// Fails unless `refund_account` is a token account of the escrow's initializer. Cancels
+    /// signed by another key let that key pick the accounts, so the deposit only goes back to
+    /// the initializer's.
+    fn check_refund_account(escrow_info: &Escrow, refund_account: &AccountInfo) -> ProgramResult {
+        let refund_account = Self::unpack_token_account(refund_account)?;
+        Self::check_key(
+            "error: escrow_info.initializer_pubkey != refund_account.owner",
+            &escrow_info.initializer_pubkey,
+            &refund_account.owner,
+            ProgramError::InvalidAccountData,
+        )
+    }
+
+    pub fn process_create_session(
+        accounts: &[AccountInfo],
+        expires_at_slot: u64,
+        permissions: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let maker = next_account_info(account_info_iter)?;
+        if !maker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let session_key = next_account_info(account_info_iter)?;
+        let session_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        if permissions == 0 || permissions & !(SESSION_CAN_CREATE | SESSION_CAN_CANCEL) != 0 {
+            msg!("error: unknown session permissions");
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        if expires_at_slot <= clock.slot {
+            msg!("error: session expires in the past");
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        let (session_address, bump_seed) =
+            Session::find_address(maker.key, session_key.key, program_id);
+        Self::check_key(
+            "error: session_address != *session_account.key",
+            &session_address,
+            session_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        // renewing an existing session only moves its expiry and permissions
+        if session_account.owner != program_id {
+            Self::create_pda_account(
+                maker,
+                session_account,
+                system_program,
+                rent,
+                Session::LEN,
+                program_id,
+                &[
+                    b"session",
+                    maker.key.as_ref(),
+                    session_key.key.as_ref(),
+                    &[bump_seed],
+                ],
+            )?;
+        }
+        Session::pack(
+            Session {
+                is_initialized: true,
+                maker: *maker.key,
+                session_key: *session_key.key,
+                expires_at_slot,
+                permissions,
+            },
+            &mut session_account.data.borrow_mut(),
+        )
+    }
+
+    pub fn process_close_session(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let maker = next_account_info(account_info_iter)?;
+        if !maker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let session_account = next_account_info(account_info_iter)?;
+        let session = Self::unpack_program_account::<Session>(session_account, program_id)?;
+        Self::check_key(
+            "error: session.maker != *maker.key",
+            &session.maker,
+            maker.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        Self::close_program_account(session_account, maker)
+    }
+
+    /// The session of the signing `session_key`, failing unless it grants `permission` at `slot`
+    fn check_session(
+        session_key: &AccountInfo,
+        session_account: &AccountInfo,
+        permission: u8,
+        slot: u64,
+        program_id: &Pubkey,
+    ) -> Result<Session, ProgramError> {
+        if !session_key.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let session = Self::unpack_program_account::<Session>(session_account, program_id)?;
+        Self::check_key(
+            "error: session.session_key != *session_key.key",
+            &session.session_key,
+            session_key.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        if session.is_expired_at(slot) {
+            return Err(EscrowError::SessionExpired.into());
+        }
+        if !session.permits(permission) {
+            return Err(EscrowError::SessionNotPermitted.into());
+        }
+        Ok(session)
+    }
+
+    pub fn process_session_init_escrow(
+        accounts: &[AccountInfo],
+        amount: u64,
+        terms: &EscrowTerms,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if accounts.len() < 5 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let (session_accounts, init_escrow_accounts) = accounts.split_at(2);
+        let clock = Clock::from_account_info(&session_accounts[1])?;
+        let session = Self::check_session(
+            &init_escrow_accounts[0],
+            &session_accounts[0],
+            SESSION_CAN_CREATE,
+            clock.slot,
+            program_id,
+        )?;
+        // the session key owns the vault, the proceeds are the maker's
+        let receive_account = Self::unpack_token_account(&init_escrow_accounts[2])?;
+        Self::check_key(
+            "error: session.maker != receive_account.owner",
+            &session.maker,
+            &receive_account.owner,
+            ProgramError::InvalidAccountData,
+        )?;
+        Self::init_escrow(
+            init_escrow_accounts,
+            &session.maker,
+            amount,
+            terms,
+            Some(clock.slot),
+            program_id,
+        )
+    }
+
+    pub fn process_session_cancel(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        if accounts.len() < 9 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let (session_accounts, cancel_accounts) = accounts.split_at(2);
+        let clock = Clock::from_account_info(&cancel_accounts[6])?;
+        let session = Self::check_session(
+            &session_accounts[0],
+            &session_accounts[1],
+            SESSION_CAN_CANCEL,
+            clock.slot,
+            program_id,
+        )?;
+        let escrow_info = Self::unpack_program_account::<Escrow>(&cancel_accounts[3], program_id)?;
+        Self::check_key(
+            "error: escrow_info.initializer_pubkey != session.maker",
+            &escrow_info.initializer_pubkey,
+            &session.maker,
+            ProgramError::InvalidAccountData,
+        )?;
+        Self::check_refund_account(&escrow_info, &cancel_accounts[2])?;
+        Self::cancel(cancel_accounts, true, program_id)
+    }
+
+    pub fn process_init_cnft_escrow(
+        accounts: &[AccountInfo],
+        expected_amount: u64,
+        root: &[u8; 32],
+        leaf: Leaf,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let leaf_delegate = next_account_info(account_info_iter)?;
+        let token_to_receive_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+        let mint_list_account = next_account_info(account_info_iter)?;
+        Self::check_mint_list(
+            mint_list_account,
+            &[token_to_receive_account],
+            &Pubkey::default(),
+            program_id,
+        )?;
+        let pda_account = next_account_info(account_info_iter)?;
+
+        if escrow_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if CnftEscrow::unpack_unchecked(&escrow_account.data.borrow())?.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        let escrow_info = CnftEscrow {
+            is_initialized: true,
+            initializer_pubkey: *initializer.key,
+            initializer_token_to_receive_account_pubkey: *token_to_receive_account.key,
+            merkle_tree: *account_info_iter
+                .as_slice()
+                .get(1)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?
+                .key,
+            leaf,
+            expected_amount,
+        };
+        CnftEscrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;
+
+        msg!("Calling the Bubblegum program to transfer the compressed NFT to the PDA...");
+        Self::transfer_cnft(
+            &escrow_info,
+            account_info_iter,
+            initializer,
+            leaf_delegate,
+            pda_account,
+            root,
+            &[],
+        )
+    }
+
+    pub fn process_exchange_cnft(
+        accounts: &[AccountInfo],
+        root: &[u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let taker = next_account_info(account_info_iter)?;
+        if !taker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let takers_sending_token_account = next_account_info(account_info_iter)?;
+        let initializers_token_to_receive_account = next_account_info(account_info_iter)?;
+        let initializers_main_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+
+        let escrow_info = Self::unpack_program_account::<CnftEscrow>(escrow_account, program_id)?;
+        if escrow_info.initializer_pubkey != *initializers_main_account.key
+            || escrow_info.initializer_token_to_receive_account_pubkey
+                != *initializers_token_to_receive_account.key
+        {
+            msg!("error: accounts do not match the escrow");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let payment = escrow_info.expected_amount;
+        Self::check_payment_authority(takers_sending_token_account, taker.key, payment)?;
+        let (fee_account, fee_amount) = match Self::settlement_fee(
+            account_info_iter,
+            payment,
+            Some(taker),
+            &Pubkey::default(),
+            program_id,
+        )? {
+            Some((fee_account, fee_amount)) => (Some(fee_account), fee_amount),
+            None => (None, 0),
+        };
+        for (destination, amount) in [
+            (fee_account, fee_amount),
+            (
+                Some(initializers_token_to_receive_account),
+                payment - fee_amount,
+            ),
+        ]
+        .iter()
+        {
+            let destination = match destination {
+                Some(destination) if *amount > 0 => destination,
+                _ => continue,
+            };
+            let transfer_ix = spl_token::instruction::transfer(
+                token_program.key,
+                takers_sending_token_account.key,
+                destination.key,
+                taker.key,
+                &[taker.key],
+                *amount,
+            )?;
+            msg!("Calling the token program to pay for the compressed NFT...");
+            invoke(
+                &transfer_ix,
+                &[
+                    takers_sending_token_account.clone(),
+                    (*destination).clone(),
+                    taker.clone(),
+                    token_program.clone(),
+                ],
+            )?;
+        }
+
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        msg!("Calling the Bubblegum program to transfer the compressed NFT to the taker...");
+        Self::transfer_cnft(
+            &escrow_info,
+            account_info_iter,
+            pda_account,
+            pda_account,
+            taker,
+            root,
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
+
+        msg!("Closing the escrow account...");
+        Self::close_program_account(escrow_account, initializers_main_account)
+    }
+
+    pub fn process_cancel_cnft(
+        accounts: &[AccountInfo],
+        root: &[u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let escrow_account = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+
+        let escrow_info = Self::unpack_program_account::<CnftEscrow>(escrow_account, program_id)?;
+        Self::check_key(
+            "error: escrow_info.initializer_pubkey != *initializer.key",
+            &escrow_info.initializer_pubkey,
+            initializer.key,
+            ProgramError::InvalidAccountData,
+        )?;
+
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        msg!("Calling the Bubblegum program to return the compressed NFT to the initializer...");
+        Self::transfer_cnft(
+            &escrow_info,
+            account_info_iter,
+            pda_account,
+            pda_account,
+            initializer,
+            root,
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
+
+        msg!("Closing the escrow account...");
+        Self::close_program_account(escrow_account, initializer)
+    }
+
+    /// Transfers the escrowed leaf through the tree accounts next in `account_info_iter`, all the
+    /// accounts after them being its proof path
+    fn transfer_cnft<'a>(
+        escrow_info: &CnftEscrow,
+        account_info_iter: &mut std::slice::Iter<AccountInfo<'a>>,
+        leaf_owner: &AccountInfo<'a>,
+        leaf_delegate: &AccountInfo<'a>,
+        new_leaf_owner: &AccountInfo<'a>,
+        root: &[u8; 32],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let tree_authority = next_account_info(account_info_iter)?;
+        let merkle_tree = next_account_info(account_info_iter)?;
+        let log_wrapper = next_account_info(account_info_iter)?;
+        let compression_program = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let bubblegum_program = next_account_info(account_info_iter)?;
+        Self::check_key(
+            "error: escrow_info.merkle_tree != *merkle_tree.key",
+            &escrow_info.merkle_tree,
+            merkle_tree.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        if *bubblegum_program.key != bubblegum::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let proof = account_info_iter.as_slice();
+
+        let proof_keys: Vec<Pubkey> = proof.iter().map(|node| *node.key).collect();
+        let transfer_ix = bubblegum::transfer(
+            &bubblegum::TreeAccounts {
+                tree_authority: tree_authority.key,
+                merkle_tree: merkle_tree.key,
+                system_program: system_program.key,
+                proof: &proof_keys,
+            },
+            leaf_owner.key,
+            leaf_delegate.key,
+            new_leaf_owner.key,
+            root,
+            &escrow_info.leaf,
+        );
+        let mut transfer_accounts = vec![
+            tree_authority.clone(),
+            leaf_owner.clone(),
+            leaf_delegate.clone(),
+            new_leaf_owner.clone(),
+            merkle_tree.clone(),
+            log_wrapper.clone(),
+            compression_program.clone(),
+            system_program.clone(),
+            bubblegum_program.clone(),
+        ];
+        transfer_accounts.extend(proof.iter().cloned());
+        invoke_signed(&transfer_ix, &transfer_accounts, signers_seeds)
+    }
+
+    pub fn process_init_core_escrow(
+        accounts: &[AccountInfo],
+        expected_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let asset_account = next_account_info(account_info_iter)?;
+        let collection_account = next_account_info(account_info_iter)?;
+        let token_to_receive_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+        let mint_list_account = next_account_info(account_info_iter)?;
+        Self::check_mint_list(
+            mint_list_account,
+            &[token_to_receive_account],
+            &Pubkey::default(),
+            program_id,
+        )?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let core_program = next_account_info(account_info_iter)?;
+
+        if escrow_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if CoreEscrow::unpack_unchecked(&escrow_account.data.borrow())?.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        let asset_info = Self::unpack_core_asset(asset_account)?;
+        Self::check_key(
+            "error: asset_info.owner != *initializer.key",
+            initializer.key,
+            &asset_info.owner,
+            ProgramError::InvalidAccountData,
+        )?;
+        let escrow_info = CoreEscrow {
+            is_initialized: true,
+            initializer_pubkey: *initializer.key,
+            initializer_token_to_receive_account_pubkey: *token_to_receive_account.key,
+            asset: *asset_account.key,
+            update_authority: asset_info.update_authority,
+            expected_amount,
+        };
+
+        msg!("Calling the mpl-core program to transfer the asset to the PDA...");
+        Self::transfer_core_asset(
+            &escrow_info,
+            [asset_account, collection_account, core_program],
+            initializer,
+            None,
+            pda_account,
+            &[],
+        )?;
+        CoreEscrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    pub fn process_exchange_core(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let taker = next_account_info(account_info_iter)?;
+        if !taker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let takers_sending_token_account = next_account_info(account_info_iter)?;
+        let initializers_token_to_receive_account = next_account_info(account_info_iter)?;
+        let initializers_main_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let asset_account = next_account_info(account_info_iter)?;
+        let collection_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let core_program = next_account_info(account_info_iter)?;
+
+        let escrow_info = Self::unpack_program_account::<CoreEscrow>(escrow_account, program_id)?;
+        if escrow_info.initializer_pubkey != *initializers_main_account.key
+            || escrow_info.initializer_token_to_receive_account_pubkey
+                != *initializers_token_to_receive_account.key
+        {
+            msg!("error: accounts do not match the escrow");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        // the update authority could have moved the asset to another collection meanwhile
+        let asset_info = Self::unpack_core_asset(asset_account)?;
+        if asset_info.owner != pda || asset_info.update_authority != escrow_info.update_authority {
+            return Err(EscrowError::AssetChanged.into());
+        }
+
+        let payment = escrow_info.expected_amount;
+        Self::check_payment_authority(takers_sending_token_account, taker.key, payment)?;
+        let (fee_account, fee_amount) = match Self::settlement_fee(
+            account_info_iter,
+            payment,
+            Some(taker),
+            &Pubkey::default(),
+            program_id,
+        )? {
+            Some((fee_account, fee_amount)) => (Some(fee_account), fee_amount),
+            None => (None, 0),
+        };
+        for (destination, amount) in [
+            (fee_account, fee_amount),
+            (
+                Some(initializers_token_to_receive_account),
+                payment - fee_amount,
+            ),
+        ]
+        .iter()
+        {
+            let destination = match destination {
+                Some(destination) if *amount > 0 => destination,
+                _ => continue,
+            };
+            let transfer_ix = spl_token::instruction::transfer(
+                token_program.key,
+                takers_sending_token_account.key,
+                destination.key,
+                taker.key,
+                &[taker.key],
+                *amount,
+            )?;
+            msg!("Calling the token program to pay for the asset...");
+            invoke(
+                &transfer_ix,
+                &[
+                    takers_sending_token_account.clone(),
+                    (*destination).clone(),
+                    taker.clone(),
+                    token_program.clone(),
+                ],
+            )?;
+        }
+
+        msg!("Calling the mpl-core program to transfer the asset to the taker...");
+        Self::transfer_core_asset(
+            &escrow_info,
+            [asset_account, collection_account, core_program],
+            taker,
+            Some(pda_account),
+            taker,
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
+
+        msg!("Closing the escrow account...");
+        Self::close_program_account(escrow_account, initializers_main_account)
+    }
+
+    pub fn process_cancel_core(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let escrow_account = next_account_info(account_info_iter)?;
+        let asset_account = next_account_info(account_info_iter)?;
+        let collection_account = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let core_program = next_account_info(account_info_iter)?;
+
+        let escrow_info = Self::unpack_program_account::<CoreEscrow>(escrow_account, program_id)?;
+        Self::check_key(
+            "error: escrow_info.initializer_pubkey != *initializer.key",
+            &escrow_info.initializer_pubkey,
+            initializer.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+
+        msg!("Calling the mpl-core program to return the asset to the initializer...");
+        Self::transfer_core_asset(
+            &escrow_info,
+            [asset_account, collection_account, core_program],
+            initializer,
+            Some(pda_account),
+            initializer,
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
+
+        msg!("Closing the escrow account...");
+        Self::close_program_account(escrow_account, initializer)
+    }
+
+    fn unpack_core_asset(asset_account: &AccountInfo) -> Result<mpl_core::Asset, ProgramError> {
+        if *asset_account.owner != mpl_core::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        mpl_core::Asset::unpack(&asset_account.data.borrow())
+    }
+
+    /// Transfers the escrowed asset, checking the asset, collection and program accounts
+    fn transfer_core_asset<'a>(
+        escrow_info: &CoreEscrow,
+        [asset_account, collection_account, core_program]: [&AccountInfo<'a>; 3],
+        payer: &AccountInfo<'a>,
+        authority: Option<&AccountInfo<'a>>,
+        new_owner: &AccountInfo<'a>,
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        if *core_program.key != mpl_core::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::check_key(
+            "error: escrow_info.asset != *asset_account.key",
+            &escrow_info.asset,
+            asset_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        let collection = escrow_info.update_authority.collection();
+        Self::check_key(
+            "error: expected the asset's collection",
+            collection.unwrap_or(&mpl_core::id()),
+            collection_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+
+        let transfer_ix = mpl_core::transfer(
+            asset_account.key,
+            collection,
+            payer.key,
+            authority.map(|authority| authority.key),
+            new_owner.key,
+        );
+        invoke_signed(
+            &transfer_ix,
+            &[
+                asset_account.clone(),
+                collection_account.clone(),
+                payer.clone(),
+                authority.unwrap_or(core_program).clone(),
+                new_owner.clone(),
+                core_program.clone(),
+            ],
+            signers_seeds,
+        )
+    }
+
+    pub fn process_relayed_exchange(
+        accounts: &[AccountInfo],
+        amount_expected_by_taker: u64,
+        nonce: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let (relay_accounts, exchange_accounts) = Self::split_relay_accounts(accounts)?;
+        let account_key = |index: usize| {
+            exchange_accounts
+                .get(index)
+                .map(|account| account.key)
+                .ok_or(ProgramError::NotEnoughAccountKeys)
+        };
+        let taker = account_key(0)?;
+        let intent = RelayIntent {
+            program_id: *program_id,
+            escrow: *account_key(6)?,
+            nonce,
+            action: RelayedAction::Exchange {
+                amount: amount_expected_by_taker,
+                sending: *account_key(1)?,
+                receiving: *account_key(2)?,
+            },
+        };
+        Self::take_relay_intent(relay_accounts, taker, &intent, program_id)?;
+        // the signed intent carries no cap, so the relayed fill pays whatever the escrow asks
+        Self::exchange(
+            exchange_accounts,
+            amount_expected_by_taker,
+            Some(u64::MAX),
+            true,
+            program_id,
+        )
+    }
+
+    pub fn process_relayed_cancel(
+        accounts: &[AccountInfo],
+        nonce: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let (relay_accounts, cancel_accounts) = Self::split_relay_accounts(accounts)?;
+        let account_key = |index: usize| {
+            cancel_accounts
+                .get(index)
+                .map(|account| account.key)
+                .ok_or(ProgramError::NotEnoughAccountKeys)
+        };
+        let initializer = account_key(0)?;
+        let intent = RelayIntent {
+            program_id: *program_id,
+            escrow: *account_key(3)?,
+            nonce,
+            action: RelayedAction::Cancel {
+                receiving: *account_key(2)?,
+            },
+        };
+        Self::take_relay_intent(relay_accounts, initializer, &intent, program_id)?;
+        Self::cancel(cancel_accounts, true, program_id)
+    }
+
+    pub fn process_get_escrow(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        Self::check_key(
+            "error: escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key",
+            &escrow_info.temp_token_account_pubkey,
+            pdas_temp_token_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        let pdas_temp_token_account_info = Self::unpack_token_account(pdas_temp_token_account)?;
+        let (_pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+
+        let mut view = vec![0; EscrowView::LEN];
+        EscrowView::new(
+            &escrow_info,
+            bump_seed,
+            pdas_temp_token_account_info.amount,
+            clock.slot,
+        )
+        .pack_into_slice(&mut view);
+        set_return_data(&view);
+        Ok(())
+    }
+
+    pub fn process_assert_invariants(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_account = next_account_info(account_info_iter)?;
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+
+        if escrow_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let escrow_info = Escrow::unpack_unchecked(&escrow_account.data.borrow())?;
+        if !escrow_info.is_initialized() {
+            msg!("error: the escrow is not initialized");
+            return Err(EscrowError::InvariantViolated.into());
+        }
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key",
+            &escrow_info.temp_token_account_pubkey,
+            pdas_temp_token_account.key,
+            EscrowError::InvariantViolated.into(),
+        )?;
+        Self::check_key(
+            "error: spl_token::id() != *pdas_temp_token_account.owner",
+            &spl_token::id(),
+            pdas_temp_token_account.owner,
+            EscrowError::InvariantViolated.into(),
+        )?;
+        let vault_info = TokenAccount::unpack(&pdas_temp_token_account.data.borrow())
+            .map_err(|_| EscrowError::InvariantViolated)?;
+        Self::check_key(
+            "error: vault_info.owner != pda",
+            &pda,
+            &vault_info.owner,
+            EscrowError::InvariantViolated.into(),
+        )?;
+        if vault_info.close_authority.is_some() {
+            msg!("error: the vault has a close authority");
+            return Err(EscrowError::InvariantViolated.into());
+        }
+        if vault_info.amount < escrow_info.deposited_amount {
+            msg!("error: the vault holds less than escrow_info.deposited_amount");
+            sol_log_64(vault_info.amount, escrow_info.deposited_amount, 0, 0, 0);
+            return Err(EscrowError::InvariantViolated.into());
+        }
+        Ok(())
+    }
+
+    /// the accounts of the relaying and those of the relayed instruction
+    fn split_relay_accounts<'a, 'b>(
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<(&'a [AccountInfo<'b>], &'a [AccountInfo<'b>]), ProgramError> {
+        if accounts.len() < 5 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        Ok(accounts.split_at(5))
+    }
+
+    /// Uses up `intent`, which must carry `user`'s next nonce and be signed by `user` in an
+    /// Ed25519 program instruction of the transaction. The relayer creates the user's nonce
+    /// account on their first intent.
+    fn take_relay_intent(
+        relay_accounts: &[AccountInfo],
+        user: &Pubkey,
+        intent: &RelayIntent,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut relay_accounts.iter();
+        let relayer = next_account_info(account_info_iter)?;
+        if !relayer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let nonce_account = next_account_info(account_info_iter)?;
+        let instructions_sysvar = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        let (nonce_address, bump_seed) = RelayNonce::find_address(user, program_id);
+        Self::check_key(
+            "error: nonce_address != *nonce_account.key",
+            &nonce_address,
+            nonce_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        let created = nonce_account.owner == program_id;
+        let mut nonce_info = if created {
+            RelayNonce::unpack(&nonce_account.data.borrow())?
+        } else {
+            RelayNonce {
+                is_initialized: true,
+                user: *user,
+                next_nonce: 0,
+            }
+        };
+        if intent.nonce != nonce_info.next_nonce {
+            msg!("error: intent.nonce != nonce_info.next_nonce");
+            sol_log_64(intent.nonce, nonce_info.next_nonce, 0, 0, 0);
+            return Err(EscrowError::RelayNonceMismatch.into());
+        }
+        Self::check_intent(instructions_sysvar, user, &intent.message())?;
+
+        if !created {
+            Self::create_pda_account(
+                relayer,
+                nonce_account,
+                system_program,
+                rent,
+                RelayNonce::LEN,
+                program_id,
+                &[b"relay_nonce", user.as_ref(), &[bump_seed]],
+            )?;
+        }
+        nonce_info.next_nonce = nonce_info
+            .next_nonce
+            .checked_add(1)
+            .ok_or(EscrowError::AmountOverflow)?;
+        RelayNonce::pack(nonce_info, &mut nonce_account.data.borrow_mut())
+    }
+
+    /// fails unless an Ed25519 program instruction of the transaction verifies `user`'s
+    /// signature of `message`
+    fn check_intent(
+        instructions_sysvar: &AccountInfo,
+        user: &Pubkey,
+        message: &[u8],
+    ) -> ProgramResult {
+        for index in 0.. {
+            let instruction =
+                match sysvar::instructions::load_instruction_at_checked(index, instructions_sysvar)
+                {
+                    Ok(instruction) => instruction,
+                    Err(ProgramError::InvalidArgument) => break,
+                    Err(error) => return Err(error),
+                };
+            if instruction.program_id == ed25519_program::id()
+                && relay::unpack_ed25519_instruction(&instruction.data) == Some((*user, message))
+            {
+                return Ok(());
+            }
+        }
+        msg!("error: no signature of the user's intent in the transaction");
+        Err(EscrowError::IntentMissing.into())
+    }
+
+    pub fn process_init_config(
+        accounts: &[AccountInfo],
+        default_fee_bps: u16,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        Self::init_config(accounts, default_fee_bps, false, program_id)
+    }
+
+    pub fn process_init_market(
+        accounts: &[AccountInfo],
+        default_fee_bps: u16,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        Self::init_config(accounts, default_fee_bps, true, program_id)
+    }
+
+    /// InitConfig, or InitMarket creating the config of the market seeded by the signing admin
+    fn init_config(
+        accounts: &[AccountInfo],
+        default_fee_bps: u16,
+        is_market: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin = next_account_info(account_info_iter)?;
+        if !admin.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let config_account = next_account_info(account_info_iter)?;
+        let fee_owner = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        if default_fee_bps > math::MAX_FEE_BPS {
+            return Err(EscrowError::InvalidFee.into());
+        }
+        let market = if is_market {
+            *admin.key
+        } else {
+            Pubkey::default()
+        };
+        let (config_address, bump_seed) = Config::find_market_address(&market, program_id);
+        Self::check_key(
+            "error: config_address != *config_account.key",
+            &config_address,
+            config_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+
+        let bump_seed = [bump_seed];
+        let signer_seeds: &[&[u8]] = if is_market {
+            &[b"config", market.as_ref(), &bump_seed]
+        } else {
+            &[b"config", &bump_seed]
+        };
+        Self::create_pda_account(
+            admin,
+            config_account,
+            system_program,
+            rent,
+            Config::LEN,
+            program_id,
+            signer_seeds,
+        )?;
+
+        Config::pack(
+            Config {
+                is_initialized: true,
+                admin: *admin.key,
+                fee_owner: *fee_owner.key,
+                default_fee_bps,
+                market,
+                ..Config::default()
+            },
+            &mut config_account.data.borrow_mut(),
+        )
+    }
+
+    pub fn process_set_mint_fee(
+        accounts: &[AccountInfo],
+        fee_bps: u16,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+        let mint = next_account_info(account_info_iter)?;
+
+        let mut config = Self::unpack_config_as_admin(admin, config_account, program_id)?;
+        if fee_bps > math::MAX_FEE_BPS {
+            return Err(EscrowError::InvalidFee.into());
+        }
+        Self::unpack_mint(mint)?;
+
+        if !config.set_fee_tier(*mint.key, fee_bps) {
+            return Err(EscrowError::ConfigFull.into());
+        }
+        Config::pack(config, &mut config_account.data.borrow_mut())
+    }
+
+    pub fn process_set_lamport_fee(
+        accounts: &[AccountInfo],
+        lamport_fee: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+
+        let mut config = Self::unpack_config_as_admin(admin, config_account, program_id)?;
+        config.lamport_fee = lamport_fee;
+        Config::pack(config, &mut config_account.data.borrow_mut())
+    }
+
+    pub fn process_set_market_paused(
+        accounts: &[AccountInfo],
+        paused: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+
+        let mut config = Self::unpack_config_as_admin(admin, config_account, program_id)?;
+        config.is_paused = paused;
+        Config::pack(config, &mut config_account.data.borrow_mut())
+    }
+
+    pub fn process_set_admin(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+        let new_admin = next_account_info(account_info_iter)?;
+
+        let mut config = Self::unpack_config_as_admin(admin, config_account, program_id)?;
+        config.admin = *new_admin.key;
+        Config::pack(config, &mut config_account.data.borrow_mut())
+    }
+
+    pub fn process_init_mint_list(
+        accounts: &[AccountInfo],
+        is_allow_list: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+        let mint_list_account = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        let config = Self::unpack_config_as_admin(admin, config_account, program_id)?;
+        let (mint_list_address, bump_seed) =
+            MintList::find_market_address(&config.market, program_id);
+        Self::check_key(
+            "error: mint_list_address != *mint_list_account.key",
+            &mint_list_address,
+            mint_list_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+
+        let bump_seed = [bump_seed];
+        let signer_seeds: &[&[u8]] = if config.market == Pubkey::default() {
+            &[b"mint_list", &bump_seed]
+        } else {
+            &[b"mint_list", config.market.as_ref(), &bump_seed]
+        };
+        Self::create_pda_account(
+            admin,
+            mint_list_account,
+            system_program,
+            rent,
+            MintList::LEN,
+            program_id,
+            signer_seeds,
+        )?;
+
+        MintList::pack(
+            MintList {
+                is_initialized: true,
+                is_allow_list,
+                ..MintList::default()
+            },
+            &mut mint_list_account.data.borrow_mut(),
+        )
+    }
+
+    pub fn process_set_mint_listed(
+        accounts: &[AccountInfo],
+        listed: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+        let mint_list_account = next_account_info(account_info_iter)?;
+        let mint = next_account_info(account_info_iter)?;
+
+        let mut mint_list =
+            Self::unpack_mint_list_as_admin(admin, config_account, mint_list_account, program_id)?;
+        // a banned mint may already be closed, only new listings need a live mint
+        if listed {
+            Self::unpack_mint(mint)?;
+        }
+
+        if !mint_list.set_listed(*mint.key, listed) {
+            return Err(EscrowError::MintListFull.into());
+        }
+        MintList::pack(mint_list, &mut mint_list_account.data.borrow_mut())
+    }
+
+    pub fn process_set_min_deposit(
+        accounts: &[AccountInfo],
+        min_deposit_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+        let mint_list_account = next_account_info(account_info_iter)?;
+
+        let mut mint_list =
+            Self::unpack_mint_list_as_admin(admin, config_account, mint_list_account, program_id)?;
+        mint_list.min_deposit_amount = min_deposit_amount;
+        MintList::pack(mint_list, &mut mint_list_account.data.borrow_mut())
+    }
+
+    pub fn process_set_mint_minimum(
+        accounts: &[AccountInfo],
+        min_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+        let mint_list_account = next_account_info(account_info_iter)?;
+        let mint = next_account_info(account_info_iter)?;
+
+        let mut mint_list =
+            Self::unpack_mint_list_as_admin(admin, config_account, mint_list_account, program_id)?;
+        Self::unpack_mint(mint)?;
+
+        if !mint_list.set_minimum(*mint.key, min_amount) {
+            return Err(EscrowError::MintListFull.into());
+        }
+        MintList::pack(mint_list, &mut mint_list_account.data.borrow_mut())
+    }
+
+    /// Builds without the `admin-recovery` feature leave the admin no way to move a deposit
+    fn check_admin_recovery() -> ProgramResult {
+        if !cfg!(feature = "admin-recovery") {
+            msg!("error: built without admin recovery");
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        Ok(())
+    }
+
+    pub fn process_propose_recovery(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let recovery_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        Self::unpack_program_config_as_admin(admin, config_account, program_id)?;
+        Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        let (recovery_address, bump_seed) = Recovery::find_address(escrow_account.key, program_id);
+        Self::check_key(
+            "error: recovery_address != *recovery_account.key",
+            &recovery_address,
+            recovery_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        if recovery_account.owner == program_id {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        let executable_at = clock
+            .unix_timestamp
+            .checked_add(RECOVERY_TIMELOCK_SECONDS)
+            .ok_or(EscrowError::AmountOverflow)?;
+
+        Self::create_pda_account(
+            admin,
+            recovery_account,
+            system_program,
+            rent,
+            Recovery::LEN,
+            program_id,
+            &[b"recovery", escrow_account.key.as_ref(), &[bump_seed]],
+        )?;
+        Recovery::pack(
+            Recovery {
+                is_initialized: true,
+                escrow: *escrow_account.key,
+                proposer: *admin.key,
+                executable_at,
+            },
+            &mut recovery_account.data.borrow_mut(),
+        )?;
+
+        msg!("warning: the admin proposed recovering the escrow's deposit");
+        EscrowEvent::RecoveryProposed {
+            escrow: *escrow_account.key,
+            admin: *admin.key,
+            executable_at,
+        }
+        .emit();
+        Ok(())
+    }
+
+    pub fn process_cancel_recovery(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+        let recovery_account = next_account_info(account_info_iter)?;
+
+        Self::unpack_program_config_as_admin(admin, config_account, program_id)?;
+        let recovery = Self::unpack_program_account::<Recovery>(recovery_account, program_id)?;
+        Self::close_program_account(recovery_account, admin)?;
+
+        EscrowEvent::RecoveryCancelled {
+            escrow: recovery.escrow,
+        }
+        .emit();
+        Ok(())
+    }
+
+    pub fn process_execute_recovery(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin = next_account_info(account_info_iter)?;
+        let config_account = next_account_info(account_info_iter)?;
+        let recovery_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        let initializers_token_account = next_account_info(account_info_iter)?;
+        let initializer = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        Self::unpack_program_config_as_admin(admin, config_account, program_id)?;
+        let recovery = Self::unpack_program_account::<Recovery>(recovery_account, program_id)?;
+        Self::check_key(
+            "error: recovery.escrow != *escrow_account.key",
+            &recovery.escrow,
+            escrow_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        if clock.unix_timestamp < recovery.executable_at {
+            msg!("error: the recovery is still timelocked");
+            return Err(EscrowError::RecoveryTimelocked.into());
+        }
+
+        let escrow_info = Self::unpack_program_account::<Escrow>(escrow_account, program_id)?;
+        Self::check_key(
+            "error: escrow_info.initializer_pubkey != *initializer.key",
+            &escrow_info.initializer_pubkey,
+            initializer.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        Self::check_key(
+            "error: escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key",
+            &escrow_info.temp_token_account_pubkey,
+            pdas_temp_token_account.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        Self::check_refund_account(&escrow_info, initializers_token_account)?;
+        Self::check_token_program(token_program)?;
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::check_key(
+            "error: expected the PDA account",
+            &pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        let returned_amount = Self::unpack_token_account(pdas_temp_token_account)?.amount;
+
+        let return_deposit_ix = spl_token::instruction::transfer(
+            token_program.key,
+            pdas_temp_token_account.key,
+            initializers_token_account.key,
+            &pda,
+            &[&pda],
+            returned_amount,
+        )?;
+        msg!("Calling the token program to return the deposit to the initializer...");
+        invoke_signed(
+            &return_deposit_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                initializers_token_account.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
+        let close_pdas_temp_acc_ix = spl_token::instruction::close_account(
+            token_program.key,
+            pdas_temp_token_account.key,
+            initializer.key,
+            &pda,
+            &[&pda],
+        )?;
+        msg!("Calling the token program to close pda's temp account...");
+        invoke_signed(
+            &close_pdas_temp_acc_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                initializer.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )?;
+        Self::hand_back_proceeds(
+            &escrow_info,
+            account_info_iter,
+            initializer.key,
+            token_program,
+            pda_account,
+            program_id,
+        )?;
+
+        msg!("warning: the admin recovered the escrow's deposit");
+        EscrowEvent::RecoveryExecuted {
+            escrow: *escrow_account.key,
+            returned_amount,
+        }
+        .emit();
+        Self::close_program_account(escrow_account, initializer)?;
+        Self::close_program_account(recovery_account, admin)
+    }
+
+    pub fn process_init_mint_volume(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer = next_account_info(account_info_iter)?;
+        let mint_volume_account = next_account_info(account_info_iter)?;
+        let mint = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        Self::unpack_mint(mint)?;
+        let (mint_volume_address, bump_seed) = MintVolume::find_address(mint.key, program_id);
+        Self::check_key(
+            "error: mint_volume_address != *mint_volume_account.key",
+            &mint_volume_address,
+            mint_volume_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+
+        Self::create_pda_account(
+            payer,
+            mint_volume_account,
+            system_program,
+            rent,
+            MintVolume::LEN,
+            program_id,
+            &[b"mint_volume", mint.key.as_ref(), &[bump_seed]],
+        )?;
+
+        MintVolume::pack(
+            MintVolume {
+                is_initialized: true,
+                mint: *mint.key,
+                ..MintVolume::default()
+            },
+            &mut mint_volume_account.data.borrow_mut(),
+        )
+    }
+
+    pub fn process_init_pool(
+        accounts: &[AccountInfo],
+        price: u64,
+        price_expo: i32,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let payer = next_account_info(account_info_iter)?;
+        if !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let pool_account = next_account_info(account_info_iter)?;
+        let deposit_vault = next_account_info(account_info_iter)?;
+        let proceeds_vault = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        if price == 0 {
+            return Err(EscrowError::InvalidCurve.into());
+        }
+
+        let deposit_vault_info = Self::unpack_token_account(deposit_vault)?;
+        let proceeds_vault_info = Self::unpack_token_account(proceeds_vault)?;
+        let (pool_address, bump_seed) = Pool::find_address(
+            &deposit_vault_info.mint,
+            &proceeds_vault_info.mint,
+            price,
+            price_expo,
+            program_id,
+        );
+        Self::check_key(
+            "error: pool_address != *pool_account.key",
+            &pool_address,
+            pool_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        if deposit_vault_info.owner != pool_address || proceeds_vault_info.owner != pool_address {
+            msg!("error: the pool vaults must be owned by the pool");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Self::create_pda_account(
+            payer,
+            pool_account,
+            system_program,
+            rent,
+            Pool::LEN,
+            program_id,
+            &[
+                b"pool",
+                deposit_vault_info.mint.as_ref(),
+                proceeds_vault_info.mint.as_ref(),
+                &price.to_le_bytes(),
+                &price_expo.to_le_bytes(),
+                &[bump_seed],
+            ],
+        )?;
+
+        Pool::pack(
+            Pool {
+                is_initialized: true,
+                deposit_mint: deposit_vault_info.mint,
+                receive_mint: proceeds_vault_info.mint,
+                deposit_vault: *deposit_vault.key,
+                proceeds_vault: *proceeds_vault.key,
+                price,
+                price_expo,
+                total_shares: 0,
+                bump_seed,
+            },
+            &mut pool_account.data.borrow_mut(),
+        )
+    }
+
+    pub fn process_pool_deposit(
+        accounts: &[AccountInfo],
+        amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let depositor = next_account_info(account_info_iter)?;
+        if !depositor.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let depositor_token_account = next_account_info(account_info_iter)?;
+        let pool_account = next_account_info(account_info_iter)?;
+        let deposit_vault = next_account_info(account_info_iter)?;
+        let share_account = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+
+        let mut pool_info = Self::unpack_program_account::<Pool>(pool_account, program_id)?;
+        Self::check_key(
+            "error: pool_info.deposit_vault != *deposit_vault.key",
+            &pool_info.deposit_vault,
+            deposit_vault.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        if amount == 0 {
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+
+        let (share_address, share_bump_seed) =
+            PoolShare::find_address(pool_account.key, depositor.key, program_id);
+        Self::check_key(
+            "error: share_address != *share_account.key",
+            &share_address,
+            share_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        let mut share_info = if share_account.data_is_empty() {
+            Self::create_pda_account(
+                depositor,
+                share_account,
+                system_program,
+                rent,
+                PoolShare::LEN,
+                program_id,
+                &[
+                    b"pool-share",
+                    pool_account.key.as_ref(),
+                    depositor.key.as_ref(),
+                    &[share_bump_seed],
+                ],
+            )?;
+            PoolShare {
+                is_initialized: true,
+                pool: *pool_account.key,
+                owner: *depositor.key,
+                shares: 0,
+            }
+        } else {
+            Self::unpack_program_account::<PoolShare>(share_account, program_id)?
+        };
+
+        let deposit_ix = spl_token::instruction::transfer(
+            token_program.key,
+            depositor_token_account.key,
+            deposit_vault.key,
+            depositor.key,
+            &[depositor.key],
+            amount,
+        )?;
+        msg!("Calling the token program to transfer the deposit to the pool...");
+        invoke(
+            &deposit_ix,
+            &[
+                depositor_token_account.clone(),
+                deposit_vault.clone(),
+                depositor.clone(),
+                token_program.clone(),
+            ],
+        )?;
+
+        // fills happen at the pool's price so a share is always worth one deposited base unit
+        share_info.shares = share_info
+            .shares
+            .checked_add(amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+        pool_info.total_shares = pool_info
+            .total_shares
+            .checked_add(amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+        PoolShare::pack(share_info, &mut share_account.data.borrow_mut())?;
+        Pool::pack(pool_info, &mut pool_account.data.borrow_mut())
+    }
+
+    pub fn process_pool_withdraw(
+        accounts: &[AccountInfo],
+        shares: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let depositor = next_account_info(account_info_iter)?;
+        if !depositor.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let depositor_deposit_token_account = next_account_info(account_info_iter)?;
+        let depositor_receive_token_account = next_account_info(account_info_iter)?;
+        let pool_account = next_account_info(account_info_iter)?;
+        let deposit_vault = next_account_info(account_info_iter)?;
+        let proceeds_vault = next_account_info(account_info_iter)?;
+        let share_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+
+        let mut pool_info = Self::unpack_program_account::<Pool>(pool_account, program_id)?;
+        if pool_info.deposit_vault != *deposit_vault.key
+            || pool_info.proceeds_vault != *proceeds_vault.key
+        {
+            msg!("error: vaults do not match the pool");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut share_info = Self::unpack_program_account::<PoolShare>(share_account, program_id)?;
+        if share_info.pool != *pool_account.key || share_info.owner != *depositor.key {
+            msg!("error: share account does not belong to the depositor");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if shares == 0 || shares > share_info.shares {
+            return Err(EscrowError::InsufficientShares.into());
+        }
+        Self::check_token_program(token_program)?;
+
+        let deposit_out = math::pro_rata(
+            Self::unpack_token_account(deposit_vault)?.amount,
+            shares,
+            pool_info.total_shares,
+        )
+        .ok_or(EscrowError::AmountOverflow)?;
+        let proceeds_out = math::pro_rata(
+            Self::unpack_token_account(proceeds_vault)?.amount,
+            shares,
+            pool_info.total_shares,
+        )
+        .ok_or(EscrowError::AmountOverflow)?;
+
+        let pool_seeds: &[&[u8]] = &[
+            b"pool",
+            pool_info.deposit_mint.as_ref(),
+            pool_info.receive_mint.as_ref(),
+            &pool_info.price.to_le_bytes(),
+            &pool_info.price_expo.to_le_bytes(),
+            &[pool_info.bump_seed],
+        ];
+        for (vault, destination, amount) in [
+            (deposit_vault, depositor_deposit_token_account, deposit_out),
+            (
+                proceeds_vault,
+                depositor_receive_token_account,
+                proceeds_out,
+            ),
+        ]
+        .iter()
+        {
+            if *amount == 0 {
+                continue;
+            }
+            let withdraw_ix = spl_token::instruction::transfer(
+                token_program.key,
+                vault.key,
+                destination.key,
+                pool_account.key,
+                &[pool_account.key],
+                *amount,
+            )?;
+            msg!("Calling the token program to pay out the pool share...");
+            invoke_signed(
+                &withdraw_ix,
+                &[
+                    (*vault).clone(),
+                    (*destination).clone(),
+                    pool_account.clone(),
+                    token_program.clone(),
+                ],
+                &[pool_seeds],
+            )?;
+        }
+
+        share_info.shares -= shares;
+        pool_info.total_shares -= shares;
+        Pool::pack(pool_info, &mut pool_account.data.borrow_mut())?;
+        if share_info.shares > 0 {
+            return PoolShare::pack(share_info, &mut share_account.data.borrow_mut());
+        }
+
+        msg!("Closing the share account...");
+        Self::close_program_account(share_account, depositor)
+    }
+
+    pub fn process_pool_exchange(
+        accounts: &[AccountInfo],
+        amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let taker = next_account_info(account_info_iter)?;
+        if !taker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let takers_sending_token_account = next_account_info(account_info_iter)?;
+        let takers_token_to_receive_account = next_account_info(account_info_iter)?;
+        let pool_account = next_account_info(account_info_iter)?;
+        let deposit_vault = next_account_info(account_info_iter)?;
+        let proceeds_vault = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+
+        let pool_info = Self::unpack_program_account::<Pool>(pool_account, program_id)?;
+        if pool_info.deposit_vault != *deposit_vault.key
+            || pool_info.proceeds_vault != *proceeds_vault.key
+        {
+            msg!("error: vaults do not match the pool");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let deposit_vault_info = Self::unpack_token_account(deposit_vault)?;
+        if amount == 0 || amount > deposit_vault_info.amount {
+            msg!("error: amount exceeds the pool's deposits");
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+        let cost = math::exponent_price_cost(pool_info.price, pool_info.price_expo, amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+        Self::check_payment_authority(takers_sending_token_account, taker.key, cost)?;
+        Self::check_token_program(token_program)?;
+
+        let payment_ix = spl_token::instruction::transfer(
+            token_program.key,
+            takers_sending_token_account.key,
+            proceeds_vault.key,
+            taker.key,
+            &[taker.key],
+            cost,
+        )?;
+        msg!("Calling the token program to transfer the payment to the pool...");
+        invoke(
+            &payment_ix,
+            &[
+                takers_sending_token_account.clone(),
+                proceeds_vault.clone(),
+                taker.clone(),
+                token_program.clone(),
+            ],
+        )?;
+
+        let fill_ix = spl_token::instruction::transfer(
+            token_program.key,
+            deposit_vault.key,
+            takers_token_to_receive_account.key,
+            pool_account.key,
+            &[pool_account.key],
+            amount,
+        )?;
+        msg!("Calling the token program to transfer tokens to the taker...");
+        invoke_signed(
+            &fill_ix,
+            &[
+                deposit_vault.clone(),
+                takers_token_to_receive_account.clone(),
+                pool_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[
+                b"pool",
+                pool_info.deposit_mint.as_ref(),
+                pool_info.receive_mint.as_ref(),
+                &pool_info.price.to_le_bytes(),
+                &pool_info.price_expo.to_le_bytes(),
+                &[pool_info.bump_seed],
+            ]],
+        )?;
+
+        EscrowEvent::Filled {
+            escrow: *pool_account.key,
+            taker: *taker.key,
+            fill_amount: amount,
+            paid_amount: cost,
+            remaining_amount: deposit_vault_info.amount - amount,
+        }
+        .emit();
+        Ok(())
+    }
+
+    fn create_pda_account<'a>(
+        payer: &AccountInfo<'a>,
+        new_account: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        rent: &Rent,
+        space: usize,
+        owner: &Pubkey,
+        signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let create_account_ix = system_instruction::create_account(
+            payer.key,
+            new_account.key,
+            rent.minimum_balance(space),
+            space as u64,
+            owner,
+        );
+        msg!("Calling the system program to create a program account...");
+        invoke_signed(
+            &create_account_ix,
+            &[payer.clone(), new_account.clone(), system_program.clone()],
+            &[signer_seeds],
+        )
+    }
+
+    /// Adds a fill of `fill_amount` to the volume account of `mint` next in `account_info_iter`,
+    /// nothing until that account is created
+    fn record_fill(
+        account_info_iter: &mut std::slice::Iter<AccountInfo>,
+        mint: &Pubkey,
+        fill_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let mint_volume_account = next_account_info(account_info_iter)?;
+        Self::check_key(
+            "error: expected the volume account of the deposited mint",
+            &MintVolume::find_address(mint, program_id).0,
+            mint_volume_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        if mint_volume_account.owner != program_id {
+            return Ok(());
+        }
+        let mut mint_volume = MintVolume::unpack(&mint_volume_account.data.borrow())?;
+        mint_volume.filled_amount = mint_volume
+            .filled_amount
+            .checked_add(fill_amount as u128)
+            .ok_or(EscrowError::AmountOverflow)?;
+        mint_volume.fill_count = mint_volume
+            .fill_count
+            .checked_add(1)
+            .ok_or(EscrowError::AmountOverflow)?;
+        MintVolume::pack(mint_volume, &mut mint_volume_account.data.borrow_mut())
+    }
+
+    /// fails unless `authority` can move `amount` out of the taker's `source`, as its owner or as
+    /// the delegate it approved for at least `amount`, e.g. the key of a custodial wallet or the
+    /// PDA of the program managing it
+    fn check_payment_authority(
+        source: &AccountInfo,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> ProgramResult {
+        let source = TokenAccount::unpack(&source.data.borrow())?;
+        if source.owner == *authority
+            || (source.delegate == COption::Some(*authority) && source.delegated_amount >= amount)
+        {
+            return Ok(());
+        }
+        msg!("error: the taker can not spend the payment from the paying account");
+        Err(EscrowError::PaymentNotAuthorized.into())
+    }
+
+    /// whether `relist_source` can still fund another listing of the escrow through `pda`
+    fn can_relist(
+        escrow_info: &Escrow,
+        relist_source: &AccountInfo,
+        pda: &Pubkey,
+    ) -> Result<bool, ProgramError> {
+        let source = Self::unpack_token_account(relist_source)?;
+        Self::check_key(
+            "error: source.owner != escrow_info.initializer_pubkey",
+            &escrow_info.initializer_pubkey,
+            &source.owner,
+            ProgramError::InvalidAccountData,
+        )?;
+        Ok(!source.is_frozen()
+            && source.delegate == COption::Some(*pda)
+            && source.delegated_amount >= escrow_info.relist_amount
+            && source.amount >= escrow_info.relist_amount)
+    }
+
+    /// fails unless the escrow of `vault` and `initializer` expired by `slot` and is free to close
+    fn check_reclaimable(
+        escrow_info: &Escrow,
+        vault: &AccountInfo,
+        initializer: &AccountInfo,
+        slot: u64,
+    ) -> ProgramResult {
+        if !escrow_info.is_expired_at(slot) {
+            msg!("error: escrow has not expired");
+            return Err(EscrowError::EscrowNotExpired.into());
+        }
+        if escrow_info.is_committed() {
+            msg!("error: escrow has a pending two-phase exchange");
+            return Err(EscrowError::EscrowCommitted.into());
+        }
+        Self::check_key(
+            "error: escrow_info.initializer_pubkey != *initializer.key",
+            &escrow_info.initializer_pubkey,
+            initializer.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        Self::check_key(
+            "error: escrow_info.temp_token_account_pubkey != *vault.key",
+            &escrow_info.temp_token_account_pubkey,
+            vault.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        if escrow_info.is_reserved_at(slot) {
+            msg!("error: escrow is reserved by a taker");
+            return Err(EscrowError::EscrowReserved.into());
+        }
+        Ok(())
+    }
+
+    /// Unpacks the config, failing unless `admin` is its admin and signed
+    fn unpack_config_as_admin(
+        admin: &AccountInfo,
+        config_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<Config, ProgramError> {
+        let config = Self::unpack_program_account::<Config>(config_account, program_id)?;
+        if !admin.is_signer || config.admin != *admin.key {
+            msg!("error: only the admin can change the config");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(config)
+    }
+
+    /// Unpacks the mint list of the config's market, failing unless `admin` is the config's admin
+    /// and signed
+    fn unpack_mint_list_as_admin(
+        admin: &AccountInfo,
+        config_account: &AccountInfo,
+        mint_list_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<MintList, ProgramError> {
+        let config = Self::unpack_config_as_admin(admin, config_account, program_id)?;
+        Self::check_key(
+            "error: expected the mint list account of the config's market",
+            &MintList::find_market_address(&config.market, program_id).0,
+            mint_list_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        Self::unpack_program_account::<MintList>(mint_list_account, program_id)
+    }
+
+    /// `unpack_config_as_admin` of the program-wide config, whose admin alone acts on escrows
+    /// of any market
+    fn unpack_program_config_as_admin(
+        admin: &AccountInfo,
+        config_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<Config, ProgramError> {
+        let config = Self::unpack_config_as_admin(admin, config_account, program_id)?;
+        if config.market != Pubkey::default() {
+            msg!("error: expected the program-wide config");
+            Self::blame(config_account.key);
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(config)
+    }
+
+    /// fails if the mint list of `market`, once it exists, does not allow the mints of
+    /// `token_accounts`
+    fn check_mint_list(
+        mint_list_account: &AccountInfo,
+        token_accounts: &[&AccountInfo],
+        market: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        Self::check_key(
+            "error: expected the mint list account of the market",
+            &MintList::find_market_address(market, program_id).0,
+            mint_list_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        if mint_list_account.owner != program_id {
+            return Ok(());
+        }
+        let mint_list = MintList::unpack(&mint_list_account.data.borrow())?;
+        for token_account in token_accounts {
+            let mint = Self::unpack_token_account(token_account)?.mint;
+            if !mint_list.allows(&mint) {
+                // formatting the mint into the message would cost thousands of compute units
+                msg!("error: mint is not allowed");
+                mint.log();
+                return Err(EscrowError::MintNotAllowed.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails for a deposit or an expected amount below the mint list's minimum for its mint,
+    /// any size passing before the mint list exists. `check_mint_list` has checked the account's
+    /// address. Escrows priced per fill open with no expected amount, which isn't checked.
+    fn check_escrow_size(
+        mint_list_account: &AccountInfo,
+        vault: &TokenAccount,
+        escrow_info: &Escrow,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if mint_list_account.owner != program_id {
+            return Ok(());
+        }
+        let mint_list = MintList::unpack(&mint_list_account.data.borrow())?;
+        let min_deposit = mint_list.min_deposit(&vault.mint);
+        if escrow_info.deposited_amount < min_deposit {
+            msg!("error: deposit below the minimum");
+            sol_log_64(escrow_info.deposited_amount, min_deposit, 0, 0, 0);
+            return Err(EscrowError::EscrowTooSmall.into());
+        }
+        let min_expected = mint_list.min_expected(&escrow_info.receive_mint);
+        if escrow_info.expected_amount != 0 && escrow_info.expected_amount < min_expected {
+            msg!("error: expected amount below the minimum");
+            sol_log_64(escrow_info.expected_amount, min_expected, 0, 0, 0);
+            return Err(EscrowError::EscrowTooSmall.into());
+        }
+        Ok(())
+    }
+
+    /// Reads the config account of `market` next in `account_info_iter` and, once the market is
+    /// configured, the fee owner's token account after it. Returns that account with the fee
+    /// owed on `payment`, `None` while there is no config. A paused market refuses to settle.
+    ///
+    /// A config with a lamport fee charges it to `lamport_payer` instead, the fee owner and the
+    /// system program following the config account, and returns `None` as well. Settlements
+    /// without a signing taker to charge pass no payer and keep paying the fee in tokens.
+    fn settlement_fee<'a, 'b>(
+        account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        payment: u64,
+        lamport_payer: Option<&AccountInfo<'b>>,
+        market: &Pubkey,
+        program_id: &Pubkey,
+    ) -> Result<Option<(&'a AccountInfo<'b>, u64)>, ProgramError> {
+        let config_account = next_account_info(account_info_iter)?;
+        Self::check_key(
+            "error: expected the config account of the market",
+            &Config::find_market_address(market, program_id).0,
+            config_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        if config_account.owner != program_id {
+            return Ok(None);
+        }
+        let config = Config::unpack(&config_account.data.borrow())?;
+        if config.is_paused {
+            msg!("error: the market is paused");
+            return Err(EscrowError::MarketPaused.into());
+        }
+
+        if let Some(payer) = lamport_payer.filter(|_| config.lamport_fee > 0) {
+            let fee_owner = next_account_info(account_info_iter)?;
+            Self::check_key(
+                "error: *fee_owner.key != config.fee_owner",
+                &config.fee_owner,
+                fee_owner.key,
+                ProgramError::InvalidAccountData,
+            )?;
+            let system_program = next_account_info(account_info_iter)?;
+            let transfer_fee_ix =
+                system_instruction::transfer(payer.key, fee_owner.key, config.lamport_fee);
+            msg!("Calling the system program to transfer the settlement fee...");
+            invoke(
+                &transfer_fee_ix,
+                &[payer.clone(), fee_owner.clone(), system_program.clone()],
+            )?;
+            return Ok(None);
+        }
+
+        let fee_account = next_account_info(account_info_iter)?;
+        let fee_account_info = Self::unpack_token_account(fee_account)?;
+        Self::check_key(
+            "error: fee_account_info.owner != config.fee_owner",
+            &config.fee_owner,
+            &fee_account_info.owner,
+            ProgramError::InvalidAccountData,
+        )?;
+        let fee = math::fee_amount(payment, config.fee_bps(&fee_account_info.mint))
+            .ok_or(EscrowError::AmountOverflow)?;
+        Ok(Some((fee_account, fee)))
+    }
+
+    /// Each split recipient's share of `net_amount` with its token account, taken in order from
+    /// `account_info_iter`, nothing for escrows paying the initializer alone
+    fn split_payment<'a, 'b>(
+        escrow_info: &Escrow,
+        account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        net_amount: u64,
+    ) -> Result<Vec<(&'a AccountInfo<'b>, u64)>, ProgramError> {
+        escrow_info
+            .split_recipients()
+            .map(|recipient| {
+                let recipient_account = next_account_info(account_info_iter)?;
+                Self::check_key(
+                    "error: expected the token account of the next split recipient",
+                    &recipient.token_account,
+                    recipient_account.key,
+                    ProgramError::InvalidAccountData,
+                )?;
+                let share = math::fee_amount(net_amount, recipient.share_bps)
+                    .ok_or(EscrowError::AmountOverflow)?;
+                Ok((recipient_account, share))
+            })
+            .collect()
+    }
+
+    /// fails unless the instruction being executed is one of the transaction's own, not a CPI
+    fn check_top_level(instructions_sysvar: &AccountInfo, program_id: &Pubkey) -> ProgramResult {
+        let current_index = sysvar::instructions::load_current_index_checked(instructions_sysvar)?;
+        let current_ix = sysvar::instructions::load_instruction_at_checked(
+            current_index as usize,
+            instructions_sysvar,
+        )?;
+        Self::check_key(
+            "error: exchange was invoked by another program",
+            program_id,
+            &current_ix.program_id,
+            EscrowError::CpiExchangeForbidden.into(),
+        )?;
+        Ok(())
+    }
+
+    /// fails unless an SPL Memo instruction of the transaction contains `reference`
+    fn check_memo(instructions_sysvar: &AccountInfo, reference: &[u8]) -> ProgramResult {
+        for index in 0.. {
+            let instruction =
+                match sysvar::instructions::load_instruction_at_checked(index, instructions_sysvar)
+                {
+                    Ok(instruction) => instruction,
+                    Err(ProgramError::InvalidArgument) => break,
+                    Err(error) => return Err(error),
+                };
+            if memo::is_memo_program(&instruction.program_id)
+                && instruction
+                    .data
+                    .windows(reference.len())
+                    .any(|window| window == reference)
+            {
+                return Ok(());
+            }
+        }
+        msg!("error: no memo in the transaction contains the escrow's reference");
+        Err(EscrowError::MemoMissing.into())
+    }
+
+    /// Clears the escrow's reservation for `taker` filling at `slot`. A live reservation shuts
+    /// out every other taker and its bond goes back to the reserver, a lapsed one forfeits its
+    /// bond. Returns the bond owed to the taker and the bond owed to the initializer.
+    fn take_reservation(
+        escrow_info: &mut Escrow,
+        taker: &Pubkey,
+        slot: u64,
+    ) -> Result<(u64, u64), ProgramError> {
+        if !escrow_info.is_reserved() {
+            return Ok((0, 0));
+        }
+        let bonds = if escrow_info.is_reserved_at(slot) {
+            Self::check_key(
+                "error: escrow is reserved by another taker",
+                &escrow_info.reserved_by,
+                taker,
+                EscrowError::EscrowReserved.into(),
+            )?;
+            (escrow_info.reserve_bond_lamports, 0)
+        } else {
+            (0, escrow_info.reserve_bond_lamports)
+        };
+        escrow_info.reserved_by = Pubkey::default();
+        escrow_info.reserved_until_slot = 0;
+        Ok(bonds)
+    }
+
+    /// hands a chained escrow's proceeds vault, next in `account_info_iter`, back to the
+    /// initializer, nothing for escrows without a follow-on offer
+    fn hand_back_proceeds<'a, 'b>(
+        escrow_info: &Escrow,
+        account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        initializer: &Pubkey,
+        token_program: &AccountInfo<'b>,
+        pda_account: &AccountInfo<'b>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if !escrow_info.is_chained() {
+            return Ok(());
+        }
+        let proceeds_vault = next_account_info(account_info_iter)?;
+        Self::check_key(
+            "error: expected the proceeds vault of the chained escrow",
+            &escrow_info.initializer_token_to_receive_account_pubkey,
+            proceeds_vault.key,
+            ProgramError::InvalidAccountData,
+        )?;
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::hand_back_token_account(
+            proceeds_vault,
+            initializer,
+            token_program,
+            pda_account,
+            &pda,
+            bump_seed,
+        )
+    }
+
+    /// makes `owner` the owner of a token account the PDA owns, returning it whole. Callers like
+    /// Expiry need no signer, so the token program and PDA account are checked here for them all.
+    fn hand_back_token_account<'a>(
+        token_account: &AccountInfo<'a>,
+        owner: &Pubkey,
+        token_program: &AccountInfo<'a>,
+        pda_account: &AccountInfo<'a>,
+        pda: &Pubkey,
+        bump_seed: u8,
+    ) -> ProgramResult {
+        Self::check_token_program(token_program)?;
+        Self::check_key(
+            "error: expected the PDA account",
+            pda,
+            pda_account.key,
+            ProgramError::InvalidSeeds,
+        )?;
+        let owner_change_ix = spl_token::instruction::set_authority(
+            token_program.key,
+            token_account.key,
+            Some(owner),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            pda,
+            &[pda],
+        )?;
+        msg!("Calling the token program to hand a token account back...");
+        invoke_signed(
+            &owner_change_ix,
+            &[
+                token_account.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )
+    }
+
+    /// moves lamports out of an account owned by this program
+    fn move_lamports(from: &AccountInfo, to: &AccountInfo, amount: u64) -> ProgramResult {
+        if amount == 0 {
+            return Ok(());
+        }
+        **from.lamports.borrow_mut() = from
+            .lamports()
+            .checked_sub(amount)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        **to.lamports.borrow_mut() = to
+            .lamports()
+            .checked_add(amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+        Ok(())
+    }
+
+    /// Drains an account owned by this program into `destination` and wipes its data, so an
+    /// account refunded later in the same transaction reads as uninitialized instead of
+    /// still holding its old state
+    fn close_program_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+        Self::move_lamports(account, destination, account.lamports())?;
+        account.data.borrow_mut().fill(0);
+        Ok(())
+    }
+
+    fn unpack_program_account<T: Pack + IsInitialized>(
+        account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<T, ProgramError> {
+        if account.owner != program_id {
+            Self::blame(account.key);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let result = T::unpack(&account.data.borrow());
+        if result.is_err() {
+            Self::blame(account.key);
+        }
+        result
+    }
+
+    /// The escrow in `account` if it is still open, without blaming the account otherwise
+    fn open_escrow(account: &AccountInfo, program_id: &Pubkey) -> Option<Escrow> {
+        Some(account)
+            .filter(|account| account.owner == program_id)
+            .and_then(|account| Escrow::unpack(&account.data.borrow()).ok())
+    }
+
+    /// Names `key` as the account an instruction is failing on, `process` turns it into the
+    /// account index of the `ErrorDetail`
+    fn blame(key: &Pubkey) {
+        set_return_data(key.as_ref());
+    }
+
+    /// A build deployed under another address derives other PDAs than the clients using
+    /// `crate::id()` expect, so it fails every instruction instead
+    fn check_program_id(program_id: &Pubkey) -> ProgramResult {
+        if *program_id != crate::id() {
+            msg!("error: *program_id != crate::id()");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Ok(())
+    }
+
+    /// Fails with `error` unless `actual` is the `expected` key, logging `check`, the error's code,
+    /// then the expected and the actual key, so a failed instruction shows which account was wrong
+    fn check_key(
+        check: &str,
+        expected: &Pubkey,
+        actual: &Pubkey,
+        error: ProgramError,
+    ) -> ProgramResult {
+        if expected == actual {
+            return Ok(());
+        }
+        msg!(check);
+        sol_log_64(u64::from(error.clone()), 0, 0, 0, 0);
+        expected.log();
+        actual.log();
+        Self::blame(actual);
+        Err(error)
+    }
+
+    /// Fails unless the Pyth program owns `oracle_account`, anyone else could write a price account
+    /// of the same layout
+    fn check_oracle_owner(oracle_account: &AccountInfo) -> ProgramResult {
+        Self::check_key(
+            "error: oracle::id() != *oracle_account.owner",
+            &oracle::id(),
+            oracle_account.owner,
+            EscrowError::InvalidOracle.into(),
+        )
+    }
+
+    /// CPIs go to whichever program is passed, so a lookalike could fake the transfers
+    fn check_token_program(token_program: &AccountInfo) -> ProgramResult {
+        Self::check_key(
+            "error: expected the token program",
+            &spl_token::id(),
+            token_program.key,
+            ProgramError::IncorrectProgramId,
+        )?;
+        Ok(())
+    }
+
+    fn unpack_token_account(token_account: &AccountInfo) -> Result<TokenAccount, ProgramError> {
+        if *token_account.owner != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        TokenAccount::unpack(&token_account.data.borrow())
+    }
+
+    fn unpack_mint(mint_account: &AccountInfo) -> Result<Mint, ProgramError> {
+        if *mint_account.owner != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Mint::unpack(&mint_account.data.borrow())
+    }
+
+    /// The tokens in the vault beyond the escrow's recorded deposit. Only the program moves
+    /// tokens out of a vault, so one holding less than the deposit is not the escrow's.
+    fn vault_surplus(escrow_info: &Escrow, vault_info: &TokenAccount) -> Result<u64, ProgramError> {
+        vault_info
+            .amount
+            .checked_sub(escrow_info.deposited_amount)
+            .ok_or_else(|| {
+                msg!("error: the vault holds less than escrow_info.deposited_amount");
+                ProgramError::InvalidAccountData
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::governance::{self, ProposalState};
+    use crate::state::{
+        Discriminator, EscrowAccountData, FillStatus, MetadataUri, ReleaseCondition,
+        SplitRecipient, CONDITION_VALUE_LEN, DISCRIMINATOR_LEN, MAX_LISTED_MINTS,
+        MAX_MINT_FEE_TIERS, MAX_SPLIT_RECIPIENTS, MEMO_REFERENCE_LEN, METADATA_URI_LEN,
+    };
+    use solana_program::{
+        instruction::Instruction, program::MAX_RETURN_DATA, program_pack::Pack, program_stubs,
+        rent::Rent, system_program, sysvar,
+    };
+
+    use solana_sdk::account::{
+        create_account_for_test, create_is_signer_account_infos, Account as SolanaAccount,
+    };
+    use solana_sdk::signature::{Keypair, Signer};
+
+    #[test]
+    fn test_pack_unpack() {
+        // my first Rust test... so proud!
+        let check = Escrow {
+            is_initialized: true,
+            initializer_pubkey: Pubkey::new(&[1; 32]),
+            temp_token_account_pubkey: Pubkey::new(&[2; 32]),
+            initializer_token_to_receive_account_pubkey: Pubkey::new(&[3; 32]),
+            expected_amount: 10,
+            oracle_pubkey: Pubkey::new(&[4; 32]),
+            oracle_max_staleness_slots: 150,
+            oracle_max_confidence_bps: 50,
+            deposit_mint_decimals: 9,
+            receive_mint_decimals: 6,
+            curve_start_price: 7,
+            curve_slope: 8,
+            filled_amount: 9,
+            is_crowdfill: true,
+            cancel_locked_until_slot: 11,
+            reserve_bond_lamports: 12,
+            reserve_slots: 13,
+            reserved_by: Pubkey::new(&[5; 32]),
+            reserved_until_slot: 14,
+            settle_timeout_slots: 15,
+            committed_taker: Pubkey::new(&[6; 32]),
+            committed_until_slot: 16,
+            payment_vault_pubkey: Pubkey::new(&[7; 32]),
+            committed_taker_token_to_receive_pubkey: Pubkey::new(&[8; 32]),
+            exchange_top_level_only: true,
+            metadata_uri: MetadataUri::new("ipfs://offer").unwrap(),
+            memo_reference: [9; MEMO_REFERENCE_LEN],
+            expires_at_slot: 17,
+            allowed_taker: Pubkey::new(&[10; 32]),
+            relist_amount: 18,
+            relist_source_pubkey: Pubkey::new(&[11; 32]),
+            auction_bid_end_slot: 19,
+            auction_reveal_end_slot: 20,
+            best_bid_amount: 21,
+            best_bid_pubkey: Pubkey::new(&[12; 32]),
+            raffle_end_slot: 22,
+            raffle_ticket_count: 23,
+            chain_expected_amount: 24,
+            chain_receive_pubkey: Pubkey::new(&[13; 32]),
+            deposited_amount: 27,
+            receive_mint: Pubkey::new(&[30; 32]),
+            chain_receive_mint: Pubkey::new(&[31; 32]),
+            operator: Pubkey::new(&[32; 32]),
+            split_recipients: [
+                SplitRecipient {
+                    token_account: Pubkey::new(&[14; 32]),
+                    share_bps: 25,
+                },
+                SplitRecipient::default(),
+                SplitRecipient::default(),
+                SplitRecipient::default(),
+            ],
+            release_condition: ReleaseCondition {
+                account: Pubkey::new(&[15; 32]),
+                owner: Pubkey::new(&[16; 32]),
+                offset: 26,
+                value_len: 2,
+                value: [17; CONDITION_VALUE_LEN],
+                gates_cancel: true,
+            },
+            revised_at_slot: 33,
+            terms_hash: [34; 32],
+            release_after_timestamp: 35,
+            release_before_timestamp: 36,
+            market: Pubkey::new(&[37; 32]),
+            tag: 38,
+        };
+        assert!(check.is_initialized);
+
+        let mut packed = vec![0; Escrow::get_packed_len()];
+
+        let expected = [
+            Escrow::DISCRIMINATOR.to_vec(),
+            vec![1],
+            vec![1; 32],
+            vec![2; 32],
+            vec![3; 32],
+            vec![10, 0, 0, 0, 0, 0, 0, 0],
+            vec![4; 32],
+            vec![150, 0, 0, 0, 0, 0, 0, 0],
+            vec![50, 0],
+            vec![9],
+            vec![6],
+            vec![7, 0, 0, 0, 0, 0, 0, 0],
+            vec![8, 0, 0, 0, 0, 0, 0, 0],
+            vec![9, 0, 0, 0, 0, 0, 0, 0],
+            vec![1],
+            vec![11, 0, 0, 0, 0, 0, 0, 0],
+            vec![12, 0, 0, 0, 0, 0, 0, 0],
+            vec![13, 0, 0, 0, 0, 0, 0, 0],
+            vec![5; 32],
+            vec![14, 0, 0, 0, 0, 0, 0, 0],
+            vec![15, 0, 0, 0, 0, 0, 0, 0],
+            vec![6; 32],
+            vec![16, 0, 0, 0, 0, 0, 0, 0],
+            vec![7; 32],
+            vec![8; 32],
+            vec![1],
+            b"ipfs://offer".to_vec(),
+            vec![0; METADATA_URI_LEN - 12],
+            vec![9; MEMO_REFERENCE_LEN],
+            vec![17, 0, 0, 0, 0, 0, 0, 0],
+            vec![10; 32],
+            vec![18, 0, 0, 0, 0, 0, 0, 0],
+            vec![11; 32],
+            vec![19, 0, 0, 0, 0, 0, 0, 0],
+            vec![20, 0, 0, 0, 0, 0, 0, 0],
+            vec![21, 0, 0, 0, 0, 0, 0, 0],
+            vec![12; 32],
+            vec![22, 0, 0, 0, 0, 0, 0, 0],
+            vec![23, 0, 0, 0, 0, 0, 0, 0],
+            vec![24, 0, 0, 0, 0, 0, 0, 0],
+            vec![13; 32],
+            vec![27, 0, 0, 0, 0, 0, 0, 0],
+            vec![30; 32],
+            vec![31; 32],
+            vec![32; 32],
+            vec![14; 32],
+            vec![25, 0],
+            vec![0; 34 * (MAX_SPLIT_RECIPIENTS - 1)],
+            vec![15; 32],
+            vec![16; 32],
+            vec![26, 0],
+            vec![2],
+            vec![17; CONDITION_VALUE_LEN],
+            vec![1],
+            vec![33, 0, 0, 0, 0, 0, 0, 0],
+            vec![34; 32],
+            vec![35, 0, 0, 0, 0, 0, 0, 0],
+            vec![36, 0, 0, 0, 0, 0, 0, 0],
+            vec![37; 32],
+            vec![38, 0, 0, 0, 0, 0, 0, 0],
+        ]
+        .concat();
+        Escrow::pack(check, &mut packed).unwrap();
+        assert_eq!(packed, expected);
+
+        let unpacked = Escrow::unpack(&packed).unwrap();
+        assert_eq!(unpacked, check);
+
+        //println!("{:?}", check);
+        // println!("{:?}", unpacked);
+    }
+
+    thread_local! {
+        /// The program the test's instructions run as, the one PDA signer seeds derive from
+        static INVOKING_PROGRAM_ID: std::cell::Cell<Pubkey> = std::cell::Cell::new(Pubkey::default());
+        /// The return data last set by the test's instructions
+        static RETURN_DATA: std::cell::RefCell<Vec<u8>> = const { std::cell::RefCell::new(Vec::new()) };
+    }
+
+    /// Runs token program CPIs against the in-memory accounts, the way the runtime would.
+    /// Other programs' CPIs are skipped.
+    struct TestSyscallStubs {}
+    impl program_stubs::SyscallStubs for TestSyscallStubs {
+        fn sol_set_return_data(&mut self, data: &[u8]) {
+            // the runtime aborts programs returning more
+            assert!(
+                data.len() <= MAX_RETURN_DATA,
+                "return data over MAX_RETURN_DATA"
+            );
+            RETURN_DATA.with(|return_data| *return_data.borrow_mut() = data.to_vec());
+        }
+
+        fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+            let program_id = INVOKING_PROGRAM_ID.with(|program_id| program_id.get());
+            RETURN_DATA
+                .with(|return_data| Some(return_data.borrow().clone()))
+                .filter(|data| !data.is_empty())
+                .map(|data| (program_id, data))
+        }
+
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            msg!("TestSyscallStubs::sol_invoke_signed()");
+            if instruction.program_id != spl_token::id() {
+                return Ok(());
+            }
+            if !account_infos
+                .iter()
+                .any(|account_info| *account_info.key == spl_token::id())
+            {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+
+            let program_id = INVOKING_PROGRAM_ID.with(|program_id| program_id.get());
+            let signers = signers_seeds
+                .iter()
+                .map(|seeds| Pubkey::create_program_address(seeds, &program_id))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| ProgramError::InvalidSeeds)?;
+            let mut instruction_account_infos = vec![];
+            for meta in instruction.accounts.iter() {
+                let mut account_info = account_infos
+                    .iter()
+                    .find(|account_info| *account_info.key == meta.pubkey)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?
+                    .clone();
+                account_info.is_signer |= signers.contains(account_info.key);
+                if meta.is_signer && !account_info.is_signer {
+                    return Err(ProgramError::MissingRequiredSignature);
+                }
+                instruction_account_infos.push(account_info);
+            }
+            spl_token::processor::Processor::process(
+                &instruction.program_id,
+                &instruction_account_infos,
+                &instruction.data,
+            )
+        }
+    }
+
+    /// Installs `TestSyscallStubs`, with PDAs signing for `program_id` on this test's thread
+    fn test_syscall_stubs(program_id: &Pubkey) {
+        use std::sync::Once;
+        static ONCE: Once = Once::new();
+
+        INVOKING_PROGRAM_ID.with(|invoking_program_id| invoking_program_id.set(*program_id));
+        ONCE.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(TestSyscallStubs {}));
+        });
+    }
+
+    #[test]
+    fn test_init_escrow() {
+        // println!("-------------------------- test_init_escrow --------------------------");
+        // 0. `[signer]` The account of the person initializing the escrow
+        // 1. `[writable]` Temporary token account that should be created prior to this instruction and owned by the initializer
+        // 2. `[]` The initializer's token account for the token they will receive should the trade go through
+        // 3. `[writable]` The escrow account, it will hold all necessary info about the trade.
+        // 4. `[]` The rent sysvar
+        // 5. `[]` The token program
+        // 6. `[]` The mint list account
+        // 7. `[]` The clock sysvar
+
+        let escrow_program_id = crate::id();
+        test_syscall_stubs(&escrow_program_id);
+
+        let token_id = spl_token::id();
+        let rent = Rent::default();
+        let mut rent_sysvar = create_account_for_test(&rent);
+
+        let escrow_len = Escrow::get_packed_len();
+        let escrow_account_min_balance = rent.minimum_balance(escrow_len);
+
+        let initializer_pubkey = Pubkey::new_unique();
+        let temp_token_pubkey = Pubkey::new_unique();
+        let mut initializer_account = SolanaAccount::default();
+        let mut temp_token_account =
+            packed_token_account(&Pubkey::new_unique(), &initializer_pubkey, 100);
+        let mut initializer_token_to_receive_account =
+            packed_token_account(&Pubkey::new_unique(), &initializer_pubkey, 0);
+        let mut escrow_account =
+            SolanaAccount::new(escrow_account_min_balance, escrow_len, &escrow_program_id);
+        let mut token_account = SolanaAccount::default();
+        let (mint_list_pubkey, _bump_seed) = MintList::find_address(&escrow_program_id);
+        let mut mint_list_account = SolanaAccount::default();
+        let mut clock_sysvar = create_account_for_test(&Clock {
+            slot: 42,
+            ..Clock::default()
+        });
+
+        let mut accounts = [
+            (&initializer_pubkey, true, &mut initializer_account),
+            (&temp_token_pubkey, true, &mut temp_token_account),
+            (
+                &Pubkey::new_unique(),
+                true,
+                &mut initializer_token_to_receive_account,
+            ),
+            (&Pubkey::new_unique(), true, &mut escrow_account),
+            (&sysvar::rent::id(), true, &mut rent_sysvar),
+            (&token_id, true, &mut token_account),
+            (&mint_list_pubkey, false, &mut mint_list_account),
+            (&sysvar::clock::id(), false, &mut clock_sysvar),
+        ];
+
+        let accounts = create_is_signer_account_infos(&mut accounts);
+
+        let recipient = SplitRecipient {
+            token_account: Pubkey::new_unique(),
+            share_bps: 6_000,
+        };
+        let oversplit = EscrowTerms {
+            split_recipients: [
+                recipient,
+                recipient,
+                SplitRecipient::default(),
+                SplitRecipient::default(),
+            ],
+            ..EscrowTerms::default()
+        };
+        assert_eq!(
+            Processor::process_init_escrow(&accounts, 123, &oversplit, &escrow_program_id),
+            Err(EscrowError::InvalidSplit.into())
+        );
+        // a price account Pyth does not own can not price the escrow
+        let oracle_pubkey = Pubkey::new_unique();
+        let mut oracle_account = SolanaAccount {
+            data: pyth_price_account_data(150_000, 100, -5, 40),
+            owner: Pubkey::new_unique(),
+            ..SolanaAccount::default()
+        };
+        let mut oracle = [(&oracle_pubkey, false, &mut oracle_account)];
+        let mut oracle_priced = accounts.clone();
+        oracle_priced.extend(create_is_signer_account_infos(&mut oracle));
+        assert_eq!(
+            Processor::process_init_escrow(
+                &oracle_priced,
+                123,
+                &EscrowTerms::default(),
+                &escrow_program_id
+            ),
+            Err(EscrowError::InvalidOracle.into())
+        );
+
+        Processor::process_init_escrow(&accounts, 123, &EscrowTerms::default(), &escrow_program_id)
+            .expect("error: process_init_escrow()");
+
+        // the token program handed the temp account over to the PDA
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &escrow_program_id);
+        let temp_token_account = TokenAccount::unpack(&accounts[1].data.borrow()).unwrap();
+        assert_eq!(temp_token_account.owner, pda);
+        assert_eq!(temp_token_account.amount, 100);
+        let escrow_info = Escrow::unpack(&accounts[3].data.borrow()).unwrap();
+        assert_eq!(escrow_info.deposited_amount, 100);
+        assert_eq!(escrow_info.revised_at_slot, 42);
+    }
+
+    #[test]
+    fn test_exchange() {
+        // 0. `[signer]` The account of the person taking the trade
+        // 1. `[writable]` The taker's token account for the token they send
+        // 2. `[writable]` The taker's token account for the token they will receive should the trade go through
+        // 3. `[writable]` The PDA's temp token account to get tokens from and eventually close
+        // 4. `[writable]` The initializer's main account to send their rent fees to
+        // 5. `[writable]` The initializer's token account that will receive tokens
+        // 6. `[writable]` The escrow account holding the escrow info
+        // 7. `[]` The token program
+        // 8. `[]` The PDA account
+        let escrow_program_id = crate::id();
+        test_syscall_stubs(&escrow_program_id);
+        let initializer_pubkey = Pubkey::new_unique();
+        let pdas_temp_token_pubkey = Pubkey::new_unique();
+        let initializer_token_to_receive_account_pubkey = Pubkey::new_unique();
+
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &escrow_program_id); // temp_token_account owner pubkey
+
+        let amount = 123;
+
+        // setup escrow account
+        let mut escrow_account = SolanaAccount {
+            owner: escrow_program_id,
+            data: vec![0; Escrow::get_packed_len()],
+            ..SolanaAccount::default()
+        };
+        Escrow {
+            is_initialized: true,
+            initializer_pubkey,
+            temp_token_account_pubkey: pdas_temp_token_pubkey,
+            initializer_token_to_receive_account_pubkey,
+            expected_amount: amount,
+            deposited_amount: amount,
+            ..Escrow::default()
+        }
+        .pack_into_slice(&mut escrow_account.data);
+
+        // temp_token_account (account that ownership was set in  initialization)
+        let mint_x = Pubkey::new_unique();
+        let mint_y = Pubkey::new_unique();
+        let mut pdas_temp_token_account = packed_token_account(&mint_x, &pda, amount);
+
+        let taker_pubkey = Pubkey::new_unique();
+        let mut taker_account = SolanaAccount::default();
+        let mut taker_token_send_account = packed_token_account(&mint_y, &taker_pubkey, amount);
+        let mut taker_token_receive_account = packed_token_account(&mint_x, &taker_pubkey, 0);
+        let mut initializer_account = SolanaAccount::default();
+        let mut initializer_token_receive_account =
+            packed_token_account(&mint_y, &initializer_pubkey, 0);
+
+        let mut token_account = SolanaAccount::default();
+        let mut pda_temp_account = SolanaAccount::default(); // temp_token_account owner
+        let mut config_account = SolanaAccount::default();
+
+        let taker_token_send_pubkey = Pubkey::new_unique();
+        let taker_token_receive_pubkey = Pubkey::new_unique();
+        let escrow_pubkey = Pubkey::new_unique();
+        let token_pubkey = spl_token::id();
+        let (config_pubkey, _bump_seed) = Config::find_address(&escrow_program_id);
+        let (mint_volume_pubkey, _bump_seed) =
+            MintVolume::find_address(&mint_x, &escrow_program_id);
+        let mut mint_volume_account = SolanaAccount::default();
+
+        let accounts: [solana_program::account_info::AccountInfo; 11] = [
+            (&taker_pubkey, true, &mut taker_account).into(),
+            (
+                &taker_token_send_pubkey,
+                false,
+                &mut taker_token_send_account,
+            )
+                .into(),
+            (
+                &taker_token_receive_pubkey,
+                false,
+                &mut taker_token_receive_account,
+            )
+                .into(),
+            (&pdas_temp_token_pubkey, false, &mut pdas_temp_token_account).into(),
+            (&initializer_pubkey, false, &mut initializer_account).into(),
+            (
+                &initializer_token_to_receive_account_pubkey,
+                false,
+                &mut initializer_token_receive_account,
+            )
+                .into(),
+            (&escrow_pubkey, false, &mut escrow_account).into(),
+            (&token_pubkey, false, &mut token_account).into(),
+            (&pda, false, &mut pda_temp_account).into(),
+            (&config_pubkey, false, &mut config_account).into(),
+            (&mint_volume_pubkey, false, &mut mint_volume_account).into(),
+        ];
+
+        Processor::process_exchange(&accounts, amount, &escrow_program_id)
+            .expect("error: process_exchange()");
+
+        let balance = |index: usize| {
+            TokenAccount::unpack(&accounts[index].data.borrow())
+                .unwrap()
+                .amount
+        };
+        assert_eq!(balance(1), 0);
+        assert_eq!(balance(2), amount);
+        assert_eq!(balance(5), amount);
+        assert_eq!(accounts[3].lamports(), 0);
+    }
+
+    fn pyth_price_account_data(price: i64, conf: u64, expo: i32, publish_slot: u64) -> Vec<u8> {
+        let mut data = vec![0; crate::oracle::PRICE_ACCOUNT_MIN_LEN];
+        data[0..4].copy_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+        data[4..8].copy_from_slice(&2u32.to_le_bytes());
+        data[8..12].copy_from_slice(&3u32.to_le_bytes());
+        data[20..24].copy_from_slice(&expo.to_le_bytes());
+        data[208..216].copy_from_slice(&price.to_le_bytes());
+        data[216..224].copy_from_slice(&conf.to_le_bytes());
+        data[224..228].copy_from_slice(&1u32.to_le_bytes());
+        data[232..240].copy_from_slice(&publish_slot.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_oracle_price() {
+        let oracle_price =
+            OraclePrice::unpack(&pyth_price_account_data(150_000, 100, -5, 1_000)).unwrap();
+        assert_eq!(
+            oracle_price,
+            OraclePrice {
+                price: 150_000,
+                conf: 100,
+                expo: -5,
+                publish_slot: 1_000,
+            }
+        );
+
+        // 1.5 quote tokens per base token, rounded up
+        assert_eq!(oracle_price.quote(10, 0, 0), Some(15));
+        assert_eq!(oracle_price.quote(3, 0, 0), Some(5));
+        // 1.5 USDC (6 decimals) per TOKEN (9 decimals)
+        assert_eq!(oracle_price.quote(2_000_000_000, 9, 6), Some(3_000_000));
+
+        assert!(oracle_price.check_staleness(1_010, 10).is_ok());
+        assert!(matches!(
+            oracle_price.check_staleness(1_011, 10),
+            Err(EscrowError::OracleStale)
+        ));
+
+        // conf is ~6.7 bps of the price
+        assert!(oracle_price.check_confidence(7).is_ok());
+        assert!(matches!(
+            oracle_price.check_confidence(6),
+            Err(EscrowError::OracleConfidenceTooWide)
+        ));
+
+        let mut not_trading = pyth_price_account_data(150_000, 100, -5, 1_000);
+        not_trading[224..228].copy_from_slice(&0u32.to_le_bytes());
+        assert!(OraclePrice::unpack(&not_trading).is_err());
+        assert!(OraclePrice::unpack(&pyth_price_account_data(-1, 0, 0, 0)).is_err());
+        assert!(OraclePrice::unpack(&[0; 16]).is_err());
+    }
+
+    #[test]
+    fn test_init_curve_escrow() {
+        let escrow_program_id = Pubkey::new_unique();
+        test_syscall_stubs(&escrow_program_id);
+        let rent = Rent::default();
+        let mut rent_sysvar = create_account_for_test(&rent);
+        let escrow_len = Escrow::get_packed_len();
+        let mut escrow_account = SolanaAccount::new(
+            rent.minimum_balance(escrow_len),
+            escrow_len,
+            &escrow_program_id,
+        );
+        let initializer_pubkey = Pubkey::new_unique();
+        let mut initializer_account = SolanaAccount::default();
+        let mut temp_token_account =
+            packed_token_account(&Pubkey::new_unique(), &initializer_pubkey, 100);
+        let mut initializer_token_to_receive_account =
+            packed_token_account(&Pubkey::new_unique(), &initializer_pubkey, 0);
+        let mut token_account = SolanaAccount::default();
+        let (mint_list_pubkey, _bump_seed) = MintList::find_address(&escrow_program_id);
+        let mut mint_list_account = SolanaAccount::default();
+
+        let mut accounts = [
+            (&initializer_pubkey, true, &mut initializer_account),
+            (&Pubkey::new_unique(), false, &mut temp_token_account),
+            (
+                &Pubkey::new_unique(),
+                false,
+                &mut initializer_token_to_receive_account,
+            ),
+            (&Pubkey::new_unique(), false, &mut escrow_account),
+            (&sysvar::rent::id(), false, &mut rent_sysvar),
+            (&spl_token::id(), false, &mut token_account),
+            (&mint_list_pubkey, false, &mut mint_list_account),
+        ];
+        let accounts = create_is_signer_account_infos(&mut accounts);
+
+        assert_eq!(
+            Processor::process_init_curve_escrow(&accounts, 0, 0, &escrow_program_id),
+            Err(EscrowError::InvalidCurve.into())
+        );
+        Processor::process_init_curve_escrow(&accounts, 1_000, 10, &escrow_program_id)
+            .expect("error: process_init_curve_escrow()");
+
+        let escrow_info = Escrow::unpack(&accounts[3].data.borrow()).unwrap();
+        assert!(escrow_info.is_curve_priced());
+        assert_eq!(escrow_info.curve_start_price, 1_000);
+        assert_eq!(escrow_info.curve_slope, 10);
+        assert_eq!(escrow_info.filled_amount, 0);
+    }
+
+    /// An initialized token account of `mint` owned by `owner`, holding `amount`
+    fn packed_token_account(mint: &Pubkey, owner: &Pubkey, amount: u64) -> SolanaAccount {
+        let mut account = SolanaAccount::new(0, TokenAccount::get_packed_len(), &spl_token::id());
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..TokenAccount::default()
+        }
+        .pack_into_slice(&mut account.data);
+        account
+    }
+
+    /// runs `instruction` on `accounts`, rolling their lamports and data back if it fails, as
+    /// the runtime would
+    fn process_atomically(
+        accounts: &[AccountInfo],
+        instruction: impl FnOnce(&[AccountInfo]) -> ProgramResult,
+    ) -> ProgramResult {
+        let before = accounts
+            .iter()
+            .map(|account| (account.lamports(), account.data.borrow().to_vec()))
+            .collect::<Vec<_>>();
+        let result = instruction(accounts);
+        if result.is_err() {
+            for (account, (lamports, data)) in accounts.iter().zip(before) {
+                **account.lamports.borrow_mut() = lamports;
+                account.data.borrow_mut().copy_from_slice(&data);
+            }
+        }
+        result
+    }
+
+    /// Accounts of an Exchange, in instruction order, around a packed escrow and vault
+    struct ExchangeFixture {
+        program_id: Pubkey,
+        keys: Vec<Pubkey>,
+        accounts: Vec<SolanaAccount>,
+    }
+
+    impl ExchangeFixture {
+        const TAKER: usize = 0;
+        const TAKER_SENDING: usize = 1;
+        const TAKER_RECEIVING: usize = 2;
+        const VAULT: usize = 3;
+        const INITIALIZER: usize = 4;
+        const INITIALIZER_RECEIVING: usize = 5;
+        const ESCROW: usize = 6;
+        const ESCROW_LAMPORTS: u64 = 1_000_000;
+        const TAKER_BALANCE: u64 = 1_000_000;
+        const DEPOSIT_MINT: Pubkey = Pubkey::new_from_array([1; 32]);
+        const RECEIVE_MINT: Pubkey = Pubkey::new_from_array([2; 32]);
+
+        fn new(escrow: Escrow, vault_amount: u64) -> Self {
+            let program_id = Pubkey::new_unique();
+            test_syscall_stubs(&program_id);
+            let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+            let keys = vec![
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                spl_token::id(),
+                pda,
+            ];
+            let mut accounts = vec![SolanaAccount::default(); keys.len()];
+
+            accounts[Self::TAKER_SENDING] =
+                packed_token_account(&Self::RECEIVE_MINT, &keys[Self::TAKER], Self::TAKER_BALANCE);
+            accounts[Self::TAKER_RECEIVING] =
+                packed_token_account(&Self::DEPOSIT_MINT, &keys[Self::TAKER], 0);
+            accounts[Self::VAULT] = packed_token_account(&Self::DEPOSIT_MINT, &pda, vault_amount);
+            // chained escrows are paid into a vault of the PDA's, offered on once filled
+            let receiver = if escrow.is_chained() {
+                pda
+            } else {
+                keys[Self::INITIALIZER]
+            };
+            accounts[Self::INITIALIZER_RECEIVING] =
+                packed_token_account(&Self::RECEIVE_MINT, &receiver, 0);
+
+            accounts[Self::ESCROW] =
+                SolanaAccount::new(Self::ESCROW_LAMPORTS, Escrow::get_packed_len(), &program_id);
+            Escrow {
+                is_initialized: true,
+                initializer_pubkey: keys[Self::INITIALIZER],
+                temp_token_account_pubkey: keys[Self::VAULT],
+                initializer_token_to_receive_account_pubkey: keys[Self::INITIALIZER_RECEIVING],
+                deposited_amount: vault_amount,
+                ..escrow
+            }
+            .pack_into_slice(&mut accounts[Self::ESCROW].data);
+
+            Self {
+                program_id,
+                keys,
+                accounts,
+            }
+        }
+
+        /// hands the taker's side over to `taker`, token accounts included
+        fn set_taker(&mut self, taker: Pubkey) {
+            self.keys[Self::TAKER] = taker;
+            for index in [Self::TAKER_SENDING, Self::TAKER_RECEIVING].iter() {
+                let mut token_account = self.token_account(*index);
+                token_account.owner = taker;
+                token_account.pack_into_slice(&mut self.accounts[*index].data);
+            }
+        }
+
+        fn token_account(&self, index: usize) -> TokenAccount {
+            TokenAccount::unpack(&self.accounts[index].data).unwrap()
+        }
+
+        /// runs `instruction`, rolling its writes back if it fails, as the runtime would
+        fn atomically(
+            &mut self,
+            instruction: impl FnOnce(&mut Self) -> ProgramResult,
+        ) -> ProgramResult {
+            let before = self.accounts.clone();
+            let result = instruction(self);
+            if result.is_err() {
+                self.accounts = before;
+            }
+            result
+        }
+
+        fn push(&mut self, key: Pubkey, account: SolanaAccount) {
+            self.keys.push(key);
+            self.accounts.push(account);
+        }
+
+        fn account_infos(&mut self) -> Vec<AccountInfo<'_>> {
+            self.keys
+                .iter()
+                .zip(self.accounts.iter_mut())
+                .enumerate()
+                .map(|(i, (key, account))| {
+                    AccountInfo::new(
+                        key,
+                        i == Self::TAKER,
+                        true,
+                        &mut account.lamports,
+                        &mut account.data,
+                        &account.owner,
+                        false,
+                        0,
+                    )
+                })
+                .collect()
+        }
+
+        fn mint_volume_pubkey(&self) -> Pubkey {
+            let vault = TokenAccount::unpack(&self.accounts[Self::VAULT].data).unwrap();
+            MintVolume::find_address(&vault.mint, &self.program_id).0
+        }
+
+        /// runs an Exchange with the config account of an unconfigured program and a volume
+        /// account not created yet appended
+        fn exchange(&mut self, amount: u64) -> ProgramResult {
+            let program_id = self.program_id;
+            self.push(
+                Config::find_address(&program_id).0,
+                SolanaAccount::default(),
+            );
+            self.push(self.mint_volume_pubkey(), SolanaAccount::default());
+            let result = Processor::process_exchange(&self.account_infos(), amount, &program_id);
+            self.keys.truncate(self.keys.len() - 2);
+            self.accounts.truncate(self.accounts.len() - 2);
+            result
+        }
+
+        fn escrow(&self) -> Escrow {
+            Escrow::unpack(&self.accounts[Self::ESCROW].data).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_exchange_stale_oracle() {
+        let oracle_pubkey = Pubkey::new_unique();
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 123,
+                oracle_pubkey,
+                oracle_max_staleness_slots: 25,
+                oracle_max_confidence_bps: 100,
+                ..Escrow::default()
+            },
+            123,
+        );
+        fixture.push(
+            sysvar::clock::id(),
+            create_account_for_test(&Clock {
+                slot: 100,
+                ..Clock::default()
+            }),
+        );
+        fixture.push(
+            oracle_pubkey,
+            SolanaAccount {
+                data: pyth_price_account_data(150_000, 100, -5, 50),
+                owner: oracle::id(),
+                ..SolanaAccount::default()
+            },
+        );
+
+        assert_eq!(fixture.exchange(123), Err(EscrowError::OracleStale.into()));
+        // a price account written by anyone but Pyth is never read
+        fixture.accounts[10].owner = Pubkey::new_unique();
+        assert_eq!(
+            fixture.exchange(123),
+            Err(EscrowError::InvalidOracle.into())
+        );
+    }
+
+    #[test]
+    fn test_capped_exchange() {
+        let oracle_pubkey = Pubkey::new_unique();
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 123,
+                oracle_pubkey,
+                oracle_max_staleness_slots: 25,
+                oracle_max_confidence_bps: 100,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        fixture.push(
+            sysvar::clock::id(),
+            create_account_for_test(&Clock {
+                slot: 60,
+                ..Clock::default()
+            }),
+        );
+        fixture.push(
+            oracle_pubkey,
+            SolanaAccount {
+                data: pyth_price_account_data(150_000, 100, -5, 50),
+                owner: oracle::id(),
+                ..SolanaAccount::default()
+            },
+        );
+        let accounts = fixture.account_infos();
+
+        // the oracle quotes 150 for the deposit, above the fixed price the taker capped it at
+        let capped = [&[68][..], &100u64.to_le_bytes(), &149u64.to_le_bytes()].concat();
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &capped),
+            Err(EscrowError::SlippageExceeded.into())
+        );
+        // an uncapped Exchange never fills an oracle priced escrow
+        let uncapped = [&[1][..], &100u64.to_le_bytes()].concat();
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &uncapped),
+            Err(EscrowError::MaxPaymentRequired.into())
+        );
+    }
+
+    #[test]
+    fn test_error_detail() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        let correct_vault = fixture.keys[ExchangeFixture::VAULT];
+        let exchange_data = |amount: u64| [&[1][..], &amount.to_le_bytes()].concat();
+        let error_detail =
+            || RETURN_DATA.with(|return_data| ErrorDetail::unpack(&return_data.borrow()));
+
+        // the failed check names the vault that is not the escrow's
+        fixture.keys[ExchangeFixture::VAULT] = Pubkey::new_unique();
+        assert_eq!(
+            Processor::process(&program_id, &fixture.account_infos(), &exchange_data(100)),
+            Err(ProgramError::InvalidAccountData)
+        );
+        let detail = error_detail().unwrap();
+        assert_eq!(detail.error(), ProgramError::InvalidAccountData);
+        assert_eq!(detail.account_index(), Some(ExchangeFixture::VAULT));
+
+        // errors about no account in particular name none, not even one an earlier instruction
+        // left blamed
+        fixture.keys[ExchangeFixture::VAULT] = correct_vault;
+        let escrow_key = fixture.keys[ExchangeFixture::ESCROW];
+        RETURN_DATA.with(|return_data| *return_data.borrow_mut() = escrow_key.to_bytes().to_vec());
+        assert_eq!(
+            Processor::process(&program_id, &fixture.account_infos(), &exchange_data(99)),
+            Err(EscrowError::ExpectedAmountMismatch.into())
+        );
+        let detail = error_detail().unwrap();
+        assert_eq!(detail.error(), EscrowError::ExpectedAmountMismatch.into());
+        assert_eq!(detail.account_index, ErrorDetail::NO_ACCOUNT);
+    }
+
+    #[test]
+    fn test_terms_checked_exchange() {
+        let escrow = Escrow {
+            expected_amount: 150,
+            ..Escrow::default()
+        };
+        let seen_terms = escrow.hash_terms();
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                // repriced since the taker saw it
+                expected_amount: 200,
+                terms_hash: Escrow {
+                    expected_amount: 200,
+                    ..escrow
+                }
+                .hash_terms(),
+                ..escrow
+            },
+            100,
+        );
+        assert_ne!(fixture.escrow().terms_hash, seen_terms);
+        let program_id = fixture.program_id;
+        fixture.push(
+            Config::find_address(&program_id).0,
+            SolanaAccount::default(),
+        );
+        fixture.push(fixture.mint_volume_pubkey(), SolanaAccount::default());
+        let mut terms_checked_exchange = |terms_hash: [u8; 32]| {
+            let accounts = fixture.account_infos();
+            let data = [&[64][..], &100u64.to_le_bytes(), &terms_hash].concat();
+            Processor::process(&program_id, &accounts, &data)
+        };
+
+        assert_eq!(
+            terms_checked_exchange(seen_terms),
+            Err(EscrowError::TermsChanged.into())
+        );
+        let detail = RETURN_DATA
+            .with(|return_data| ErrorDetail::unpack(&return_data.borrow()))
+            .unwrap();
+        assert_eq!(detail.account_index(), Some(ExchangeFixture::ESCROW));
+        let current_terms = Escrow {
+            expected_amount: 200,
+            ..escrow
+        }
+        .hash_terms();
+        terms_checked_exchange(current_terms).expect("error: TermsCheckedExchange");
+        assert_eq!(
+            fixture
+                .token_account(ExchangeFixture::INITIALIZER_RECEIVING)
+                .amount,
+            200
+        );
+    }
+
+    #[test]
+    fn test_guarded_exchange() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                revised_at_slot: 50,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        fixture.push(
+            Config::find_address(&program_id).0,
+            SolanaAccount::default(),
+        );
+        fixture.push(fixture.mint_volume_pubkey(), SolanaAccount::default());
+        let clock_pubkey = sysvar::clock::id();
+        let mut clock_sysvar = create_account_for_test(&Clock {
+            slot: 60,
+            ..Clock::default()
+        });
+        let mut guarded_exchange = |created_before_slot: u64, not_after_slot: u64| {
+            let mut clock_account = [(&clock_pubkey, false, &mut clock_sysvar)];
+            let mut accounts = create_is_signer_account_infos(&mut clock_account);
+            accounts.extend(fixture.account_infos());
+            let data = [
+                &[63][..],
+                &100u64.to_le_bytes(),
+                &created_before_slot.to_le_bytes(),
+                &not_after_slot.to_le_bytes(),
+            ]
+            .concat();
+            Processor::process(&program_id, &accounts, &data)
+        };
+
+        // the taker saw the escrow before it was repriced at slot 50
+        assert_eq!(
+            guarded_exchange(49, 100),
+            Err(EscrowError::EscrowRevised.into())
+        );
+        assert_eq!(
+            guarded_exchange(50, 59),
+            Err(EscrowError::ExchangeTooLate.into())
+        );
+        guarded_exchange(50, 60).expect("error: GuardedExchange");
+        assert_eq!(
+            fixture
+                .token_account(ExchangeFixture::INITIALIZER_RECEIVING)
+                .amount,
+            150
+        );
+    }
+
+    #[test]
+    fn test_exchange_delegated_payment() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                ..Escrow::default()
+            },
+            100,
+        );
+        // a custodian owns the paying account and approved the taker's key for part of it
+        let mut sending = fixture.token_account(ExchangeFixture::TAKER_SENDING);
+        sending.owner = Pubkey::new_unique();
+        sending.delegate = COption::Some(fixture.keys[ExchangeFixture::TAKER]);
+        sending.delegated_amount = 149;
+        sending.pack_into_slice(&mut fixture.accounts[ExchangeFixture::TAKER_SENDING].data);
+
+        assert_eq!(
+            fixture.exchange(100),
+            Err(EscrowError::PaymentNotAuthorized.into())
+        );
+        sending.delegated_amount = 150;
+        sending.pack_into_slice(&mut fixture.accounts[ExchangeFixture::TAKER_SENDING].data);
+        fixture.exchange(100).expect("error: process_exchange()");
+
+        let sending = fixture.token_account(ExchangeFixture::TAKER_SENDING);
+        assert_eq!(sending.amount, ExchangeFixture::TAKER_BALANCE - 150);
+        assert_eq!(sending.delegated_amount, 0);
+        assert_eq!(
+            fixture
+                .token_account(ExchangeFixture::INITIALIZER_RECEIVING)
+                .amount,
+            150
+        );
+    }
+
+    #[test]
+    fn test_exchange_curve_partial_fill() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                curve_start_price: math::CURVE_PRICE_SCALE as u64,
+                curve_slope: math::CURVE_PRICE_SCALE as u64,
+                ..Escrow::default()
+            },
+            100,
+        );
+
+        assert_eq!(
+            fixture.exchange(101),
+            Err(EscrowError::ExpectedAmountMismatch.into())
+        );
+        assert_eq!(
+            fixture.exchange(0),
+            Err(EscrowError::ExpectedAmountMismatch.into())
+        );
+        fixture.exchange(40).expect("error: process_exchange()");
+
+        // the escrow stays open and remembers the slice that was sold, no rebate is paid
+        assert_eq!(fixture.escrow().filled_amount, 40);
+        assert_eq!(
+            fixture.accounts[ExchangeFixture::ESCROW].lamports,
+            ExchangeFixture::ESCROW_LAMPORTS
+        );
+        assert_eq!(fixture.accounts[ExchangeFixture::INITIALIZER].lamports, 0);
+        assert_eq!(fixture.accounts[ExchangeFixture::TAKER].lamports, 0);
+    }
+
+    #[test]
+    fn test_exchange_crowdfill() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                is_crowdfill: true,
+                ..Escrow::default()
+            },
+            100,
+        );
+
+        fixture.exchange(40).expect("error: process_exchange()");
+        assert_eq!(fixture.escrow().filled_amount, 40);
+
+        // a different taker takes the rest, which closes the escrow
+        fixture.set_taker(Pubkey::new_unique());
+        let mut vault =
+            TokenAccount::unpack(&fixture.accounts[ExchangeFixture::VAULT].data).unwrap();
+        vault.amount = 60;
+        vault.pack_into_slice(&mut fixture.accounts[ExchangeFixture::VAULT].data);
+        fixture.exchange(60).expect("error: process_exchange()");
+        assert_eq!(fixture.accounts[ExchangeFixture::ESCROW].lamports, 0);
+        assert_eq!(
+            fixture.accounts[ExchangeFixture::INITIALIZER].lamports,
+            ExchangeFixture::ESCROW_LAMPORTS - TAKER_CLOSE_REBATE_LAMPORTS
+        );
+        assert_eq!(
+            fixture.accounts[ExchangeFixture::TAKER].lamports,
+            TAKER_CLOSE_REBATE_LAMPORTS
+        );
+    }
+
+    #[test]
+    fn test_exchange_vault_surplus() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                is_crowdfill: true,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let set_vault_amount = |fixture: &mut ExchangeFixture, amount| {
+            let mut vault = fixture.token_account(ExchangeFixture::VAULT);
+            vault.amount = amount;
+            vault.pack_into_slice(&mut fixture.accounts[ExchangeFixture::VAULT].data);
+        };
+
+        // tokens sent to the vault directly are not for sale
+        set_vault_amount(&mut fixture, 105);
+        assert_eq!(
+            fixture.exchange(105),
+            Err(EscrowError::ExpectedAmountMismatch.into())
+        );
+        fixture.exchange(40).expect("error: process_exchange()");
+        assert_eq!(fixture.escrow().filled_amount, 40);
+        assert_eq!(fixture.escrow().deposited_amount, 60);
+
+        // nor do they make a taker asking for the whole deposit miss
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                ..Escrow::default()
+            },
+            100,
+        );
+        set_vault_amount(&mut fixture, 101);
+        fixture.exchange(100).expect("error: process_exchange()");
+        assert_eq!(fixture.accounts[ExchangeFixture::ESCROW].lamports, 0);
+
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                ..Escrow::default()
+            },
+            100,
+        );
+        set_vault_amount(&mut fixture, 99);
+        assert_eq!(fixture.exchange(100), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn test_sweep_dust() {
+        let program_id = Pubkey::new_unique();
+        test_syscall_stubs(&program_id);
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+        let initializer_pubkey = Pubkey::new_unique();
+        let fee_owner = Pubkey::new_unique();
+        let vault_pubkey = Pubkey::new_unique();
+        let rent = Rent::default();
+        let escrow_rent = rent.minimum_balance(Escrow::LEN);
+
+        let mut escrow_account = SolanaAccount::new(escrow_rent + 500, Escrow::LEN, &program_id);
+        let mut escrow_info = Escrow {
+            is_initialized: true,
+            initializer_pubkey,
+            temp_token_account_pubkey: vault_pubkey,
+            expected_amount: 150,
+            deposited_amount: 100,
+            ..Escrow::default()
+        };
+        escrow_info.pack_into_slice(&mut escrow_account.data);
+        let mint = Pubkey::new_unique();
+        let mut vault = packed_token_account(&mint, &pda, 105);
+        let mut initializer_account = SolanaAccount::default();
+        let mut config_account = SolanaAccount::default();
+        let mut initializers_token_account = packed_token_account(&mint, &initializer_pubkey, 0);
+        let mut fee_token_account = packed_token_account(&mint, &fee_owner, 0);
+        let mut token_program_account = SolanaAccount::default();
+        let mut pda_account = SolanaAccount::default();
+        let mut rent_sysvar = create_account_for_test(&rent);
+        let mut sweep_dust = |escrow_account: &mut SolanaAccount,
+                              config_account: &mut SolanaAccount,
+                              dust_token_account: &mut SolanaAccount| {
+            let mut accounts = [
+                (&Pubkey::new_unique(), false, escrow_account),
+                (&vault_pubkey, false, &mut vault),
+                (&initializer_pubkey, false, &mut initializer_account),
+                (&Config::find_address(&program_id).0, false, config_account),
+                (&Pubkey::new_unique(), false, dust_token_account),
+                (&spl_token::id(), false, &mut token_program_account),
+                (&pda, false, &mut pda_account),
+                (&sysvar::rent::id(), false, &mut rent_sysvar),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_sweep_dust(&accounts, &program_id)
+        };
+
+        // once configured, the surplus is the fee owner's rather than the initializer's
+        let mut configured = SolanaAccount::new(0, Config::LEN, &program_id);
+        Config {
+            is_initialized: true,
+            fee_owner,
+            ..Config::default()
+        }
+        .pack_into_slice(&mut configured.data);
+        assert_eq!(
+            sweep_dust(
+                &mut escrow_account,
+                &mut configured,
+                &mut initializers_token_account
+            ),
+            Err(ProgramError::InvalidAccountData)
+        );
+        sweep_dust(&mut escrow_account, &mut configured, &mut fee_token_account)
+            .expect("error: process_sweep_dust()");
+        assert_eq!(
+            TokenAccount::unpack(&fee_token_account.data)
+                .unwrap()
+                .amount,
+            5
+        );
+        assert_eq!(escrow_account.lamports, escrow_rent);
+        assert_eq!(Escrow::unpack(&escrow_account.data).unwrap(), escrow_info);
+
+        // an escrow with nothing left to sell is closed
+        escrow_info.deposited_amount = 0;
+        escrow_info.pack_into_slice(&mut escrow_account.data);
+        assert_eq!(
+            sweep_dust(
+                &mut escrow_account,
+                &mut config_account,
+                &mut initializers_token_account
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            TokenAccount::unpack(&initializers_token_account.data)
+                .unwrap()
+                .amount,
+            100
+        );
+        assert_eq!(escrow_account.lamports, 0);
+        assert_eq!(initializer_account.lamports, escrow_rent + 500);
+    }
+
+    #[test]
+    fn test_assert_invariants() {
+        let program_id = Pubkey::new_unique();
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+        let vault_pubkey = Pubkey::new_unique();
+        let escrow_info = Escrow {
+            is_initialized: true,
+            temp_token_account_pubkey: vault_pubkey,
+            deposited_amount: 100,
+            ..Escrow::default()
+        };
+        let assert_invariants = |escrow_info: &Escrow, vault: &TokenAccount| {
+            let mut escrow_account = SolanaAccount::new(0, Escrow::LEN, &program_id);
+            escrow_info.pack_into_slice(&mut escrow_account.data);
+            let mut vault_account = SolanaAccount::new(0, TokenAccount::LEN, &spl_token::id());
+            vault.pack_into_slice(&mut vault_account.data);
+            let mut accounts = [
+                (&Pubkey::new_unique(), false, &mut escrow_account),
+                (&vault_pubkey, false, &mut vault_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_assert_invariants(&accounts, &program_id)
+        };
+        let vault = TokenAccount {
+            owner: pda,
+            amount: 100,
+            state: spl_token::state::AccountState::Initialized,
+            ..TokenAccount::default()
+        };
+        assert_invariants(&escrow_info, &vault).expect("error: process_assert_invariants()");
+
+        let violated = Err(EscrowError::InvariantViolated.into());
+        let broken_escrows = [
+            Escrow {
+                is_initialized: false,
+                ..escrow_info
+            },
+            Escrow {
+                temp_token_account_pubkey: Pubkey::new_unique(),
+                ..escrow_info
+            },
+            Escrow {
+                deposited_amount: 101,
+                ..escrow_info
+            },
+        ];
+        for broken_escrow in broken_escrows.iter() {
+            assert_eq!(assert_invariants(broken_escrow, &vault), violated);
+        }
+        let broken_vaults = [
+            TokenAccount {
+                owner: Pubkey::new_unique(),
+                ..vault
+            },
+            TokenAccount {
+                close_authority: COption::Some(pda),
+                ..vault
+            },
+        ];
+        for broken_vault in broken_vaults.iter() {
+            assert_eq!(assert_invariants(&escrow_info, broken_vault), violated);
+        }
+    }
+
+    #[test]
+    fn test_exchange_token_programs() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                ..Escrow::default()
+            },
+            100,
+        );
+        // both legs move through SPL Token, a lookalike never gets the PDA's signature
+        fixture.keys[7] = Pubkey::new_unique();
+        assert_eq!(fixture.exchange(100), Err(ProgramError::IncorrectProgramId));
+    }
+
+    #[test]
+    fn test_exchange_requires_full_fill() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                ..Escrow::default()
+            },
+            100,
+        );
+
+        assert_eq!(
+            fixture.exchange(40),
+            Err(EscrowError::ExpectedAmountMismatch.into())
+        );
+    }
+
+    #[test]
+    fn test_display_escrow() {
+        let initializer = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let receive_account = Pubkey::new_unique();
+        let receive_mint = Pubkey::new_unique();
+        let mut escrow = Escrow {
+            is_initialized: true,
+            initializer_pubkey: initializer,
+            temp_token_account_pubkey: vault,
+            initializer_token_to_receive_account_pubkey: receive_account,
+            receive_mint,
+            expected_amount: 1_500_000,
+            deposited_amount: 2_000_000_000,
+            deposit_mint_decimals: 9,
+            receive_mint_decimals: 6,
+            expires_at_slot: 900,
+            ..Escrow::default()
+        };
+        let accounts = format!(
+            "\n  initializer: {}\n  vault: {}\n  receive account: {}",
+            initializer, vault, receive_account
+        );
+        assert_eq!(
+            escrow.to_string(),
+            format!(
+                "fixed price escrow{}\n  asks: 1500000 base units of {}\n  \
+                 deposit left: 2000000000 base units\n  expires at slot: 900",
+                accounts, receive_mint
+            )
+        );
+
+        // oracle priced escrows know their mints' decimals
+        let oracle = Pubkey::new_unique();
+        escrow.oracle_pubkey = oracle;
+        assert_eq!(
+            escrow.to_string(),
+            format!(
+                "oracle priced escrow{}\n  asks: 1.5 {}\n  deposit left: 2 tokens\n  \
+                 oracle: {}\n  expires at slot: 900",
+                accounts, receive_mint, oracle
+            )
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_escrow() {
+        let escrow = Escrow {
+            is_initialized: true,
+            initializer_pubkey: Pubkey::new_unique(),
+            expected_amount: 150,
+            metadata_uri: MetadataUri::new("https://example.com/offer.json").unwrap(),
+            ..Escrow::default()
+        };
+        let json = serde_json::to_value(escrow).unwrap();
+        // the URI is stored padded but written as the string
+        assert_eq!(json["metadata_uri"], "https://example.com/offer.json");
+        assert_eq!(serde_json::from_value::<Escrow>(json).unwrap(), escrow);
+
+        let too_long = serde_json::json!("x".repeat(METADATA_URI_LEN + 1));
+        assert!(serde_json::from_value::<MetadataUri>(too_long).is_err());
+    }
+
+    #[test]
+    fn test_pool_pack_unpack() {
+        let pool = Pool {
+            is_initialized: true,
+            deposit_mint: Pubkey::new_unique(),
+            receive_mint: Pubkey::new_unique(),
+            deposit_vault: Pubkey::new_unique(),
+            proceeds_vault: Pubkey::new_unique(),
+            price: 2,
+            price_expo: -3,
+            total_shares: 100,
+            bump_seed: 255,
+        };
+        let mut packed = vec![0; Pool::get_packed_len()];
+        Pool::pack(pool, &mut packed).unwrap();
+        assert_eq!(Pool::unpack(&packed).unwrap(), pool);
+
+        let share = PoolShare {
+            is_initialized: true,
+            pool: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            shares: 40,
+        };
+        let mut packed = vec![0; PoolShare::get_packed_len()];
+        PoolShare::pack(share, &mut packed).unwrap();
+        assert_eq!(PoolShare::unpack(&packed).unwrap(), share);
+    }
+
+    #[test]
+    fn test_pool_deposit_and_withdraw() {
+        let program_id = Pubkey::new_unique();
+        test_syscall_stubs(&program_id);
+        let deposit_mint = Pubkey::new_unique();
+        let receive_mint = Pubkey::new_unique();
+        let price = math::CURVE_PRICE_SCALE as u64 * 2;
+        let (pool_pubkey, bump_seed) =
+            Pool::find_address(&deposit_mint, &receive_mint, price, -9, &program_id);
+        let depositor_pubkey = Pubkey::new_unique();
+        let (share_pubkey, _) =
+            PoolShare::find_address(&pool_pubkey, &depositor_pubkey, &program_id);
+        let deposit_vault_pubkey = Pubkey::new_unique();
+        let proceeds_vault_pubkey = Pubkey::new_unique();
+
+        let mut pool_account = SolanaAccount::new(0, Pool::get_packed_len(), &program_id);
+        Pool {
+            is_initialized: true,
+            deposit_mint,
+            receive_mint,
+            deposit_vault: deposit_vault_pubkey,
+            proceeds_vault: proceeds_vault_pubkey,
+            price,
+            price_expo: -9,
+            total_shares: 60,
+            bump_seed,
+        }
+        .pack_into_slice(&mut pool_account.data);
+        let mut share_account = SolanaAccount::new(500, PoolShare::get_packed_len(), &program_id);
+        PoolShare {
+            is_initialized: true,
+            pool: pool_pubkey,
+            owner: depositor_pubkey,
+            shares: 20,
+        }
+        .pack_into_slice(&mut share_account.data);
+        let mut deposit_vault = packed_token_account(&deposit_mint, &pool_pubkey, 50);
+        let mut proceeds_vault = packed_token_account(&receive_mint, &pool_pubkey, 100);
+
+        let mut depositor_account = SolanaAccount::default();
+        let mut depositor_deposit_token_account =
+            packed_token_account(&deposit_mint, &depositor_pubkey, 20);
+        let mut depositor_receive_token_account =
+            packed_token_account(&receive_mint, &depositor_pubkey, 0);
+        let mut rent_sysvar = create_account_for_test(&Rent::default());
+        let mut system_program_account = SolanaAccount::default();
+        let mut token_program_account = SolanaAccount::default();
+
+        {
+            let mut accounts = [
+                (&depositor_pubkey, true, &mut depositor_account),
+                (
+                    &Pubkey::new_unique(),
+                    false,
+                    &mut depositor_deposit_token_account,
+                ),
+                (&pool_pubkey, false, &mut pool_account),
+                (&deposit_vault_pubkey, false, &mut deposit_vault),
+                (&share_pubkey, false, &mut share_account),
+                (&sysvar::rent::id(), false, &mut rent_sysvar),
+                (
+                    &solana_program::system_program::id(),
+                    false,
+                    &mut system_program_account,
+                ),
+                (&spl_token::id(), false, &mut token_program_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_pool_deposit(&accounts, 20, &program_id)
+                .expect("error: process_pool_deposit()");
+        }
+        assert_eq!(PoolShare::unpack(&share_account.data).unwrap().shares, 40);
+        assert_eq!(Pool::unpack(&pool_account.data).unwrap().total_shares, 80);
+
+        let mut accounts = [
+            (&depositor_pubkey, true, &mut depositor_account),
+            (
+                &Pubkey::new_unique(),
+                false,
+                &mut depositor_deposit_token_account,
+            ),
+            (
+                &Pubkey::new_unique(),
+                false,
+                &mut depositor_receive_token_account,
+            ),
+            (&pool_pubkey, false, &mut pool_account),
+            (&deposit_vault_pubkey, false, &mut deposit_vault),
+            (&proceeds_vault_pubkey, false, &mut proceeds_vault),
+            (&share_pubkey, false, &mut share_account),
+            (&spl_token::id(), false, &mut token_program_account),
+        ];
+        let accounts = create_is_signer_account_infos(&mut accounts);
+        assert_eq!(
+            Processor::process_pool_withdraw(&accounts, 41, &program_id),
+            Err(EscrowError::InsufficientShares.into())
+        );
+        // the pool only signs for the token program
+        let lookalike = Pubkey::new_unique();
+        let mut lookalike_accounts = accounts.clone();
+        lookalike_accounts[7].key = &lookalike;
+        assert_eq!(
+            Processor::process_pool_withdraw(&lookalike_accounts, 40, &program_id),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        Processor::process_pool_withdraw(&accounts, 40, &program_id)
+            .expect("error: process_pool_withdraw()");
+        assert_eq!(
+            Pool::unpack(&accounts[3].data.borrow())
+                .unwrap()
+                .total_shares,
+            40
+        );
+        // the emptied share account is closed
+        assert_eq!(accounts[6].lamports(), 0);
+        assert_eq!(accounts[0].lamports(), 500);
+        // half the shares take half of each vault
+        let balance = |index: usize| {
+            TokenAccount::unpack(&accounts[index].data.borrow())
+                .unwrap()
+                .amount
+        };
+        assert_eq!((balance(1), balance(2)), (35, 50));
+        assert_eq!((balance(4), balance(5)), (35, 50));
+    }
+
+    #[test]
+    fn test_exchange_rebate_capped_by_escrow_rent() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                ..Escrow::default()
+            },
+            100,
+        );
+        fixture.accounts[ExchangeFixture::ESCROW].lamports = TAKER_CLOSE_REBATE_LAMPORTS - 1;
+
+        fixture.exchange(100).expect("error: process_exchange()");
+        assert_eq!(
+            fixture.accounts[ExchangeFixture::TAKER].lamports,
+            TAKER_CLOSE_REBATE_LAMPORTS - 1
+        );
+        assert_eq!(fixture.accounts[ExchangeFixture::INITIALIZER].lamports, 0);
+    }
+
+    #[test]
+    fn test_cancel_lock() {
+        let program_id = Pubkey::new_unique();
+        test_syscall_stubs(&program_id);
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+        let initializer_pubkey = Pubkey::new_unique();
+        let vault_pubkey = Pubkey::new_unique();
+
+        let mut escrow_account = SolanaAccount::new(1_000, Escrow::get_packed_len(), &program_id);
+        Escrow {
+            is_initialized: true,
+            initializer_pubkey,
+            temp_token_account_pubkey: vault_pubkey,
+            expected_amount: 150,
+            cancel_locked_until_slot: 200,
+            ..Escrow::default()
+        }
+        .pack_into_slice(&mut escrow_account.data);
+        let mint = Pubkey::new_unique();
+        let mut vault = packed_token_account(&mint, &pda, 100);
+        let mut clock_sysvar = create_account_for_test(&Clock {
+            slot: 199,
+            ..Clock::default()
+        });
+        let mut initializer_account = SolanaAccount::default();
+        let mut initializer_token_account = packed_token_account(&mint, &initializer_pubkey, 0);
+        let mut token_program_account = SolanaAccount::default();
+        let mut pda_account = SolanaAccount::default();
+
+        let mut accounts = [
+            (&initializer_pubkey, true, &mut initializer_account),
+            (&vault_pubkey, false, &mut vault),
+            (&Pubkey::new_unique(), false, &mut initializer_token_account),
+            (&Pubkey::new_unique(), false, &mut escrow_account),
+            (&spl_token::id(), false, &mut token_program_account),
+            (&pda, false, &mut pda_account),
+            (&sysvar::clock::id(), false, &mut clock_sysvar),
+        ];
+        let accounts = create_is_signer_account_infos(&mut accounts);
+
+        assert_eq!(
+            Processor::process_cancel(&accounts, &program_id),
+            Err(EscrowError::CancelLocked.into())
+        );
+
+        Clock {
+            slot: 200,
+            ..Clock::default()
+        }
+        .to_account_info(&mut accounts[6].clone())
+        .unwrap();
+        Processor::process_cancel(&accounts, &program_id).expect("error: process_cancel()");
+        assert_eq!(accounts[3].lamports(), 0);
+        let initializer_token_account = TokenAccount::unpack(&accounts[2].data.borrow()).unwrap();
+        assert_eq!(initializer_token_account.amount, 100);
+        assert_eq!(accounts[0].lamports(), 1_000);
+    }
+
+    #[test]
+    fn test_withdraw_deposit() {
+        let program_id = Pubkey::new_unique();
+        test_syscall_stubs(&program_id);
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+        let initializer_pubkey = Pubkey::new_unique();
+        let vault_pubkey = Pubkey::new_unique();
+
+        // 40 of a crowdfill's 100 tokens sold, at 2 of the receive token each
+        let mut escrow_account = SolanaAccount::new(1_000, Escrow::get_packed_len(), &program_id);
+        Escrow {
+            is_initialized: true,
+            initializer_pubkey,
+            temp_token_account_pubkey: vault_pubkey,
+            expected_amount: 200,
+            is_crowdfill: true,
+            filled_amount: 40,
+            deposited_amount: 60,
+            ..Escrow::default()
+        }
+        .pack_into_slice(&mut escrow_account.data);
+        let mint = Pubkey::new_unique();
+        let mut vault = packed_token_account(&mint, &pda, 60);
+        let mut clock_sysvar = create_account_for_test(&Clock {
+            slot: 80,
+            ..Clock::default()
+        });
+        let mut initializer_account = SolanaAccount::default();
+        let mut initializer_token_account = packed_token_account(&mint, &initializer_pubkey, 0);
+        let mut token_program_account = SolanaAccount::default();
+        let mut pda_account = SolanaAccount::default();
+        let mut mint_list_account = SolanaAccount::default();
+
+        let mut accounts = [
+            (&initializer_pubkey, true, &mut initializer_account),
+            (&vault_pubkey, false, &mut vault),
+            (&Pubkey::new_unique(), false, &mut initializer_token_account),
+            (&Pubkey::new_unique(), false, &mut escrow_account),
+            (&spl_token::id(), false, &mut token_program_account),
+            (&pda, false, &mut pda_account),
+            (&sysvar::clock::id(), false, &mut clock_sysvar),
+            (
+                &MintList::find_address(&program_id).0,
+                false,
+                &mut mint_list_account,
+            ),
+        ];
+        let accounts = create_is_signer_account_infos(&mut accounts);
+        let withdraw = |amount: u64| {
+            Processor::process(
+                &program_id,
+                &accounts,
+                &[&[65][..], &amount.to_le_bytes()].concat(),
+            )
+        };
+
+        // the whole deposit is Cancel's to return
+        assert_eq!(withdraw(60), Err(EscrowError::InvalidInstruction.into()));
+        withdraw(30).expect("error: WithdrawDeposit");
+        let escrow_info = Escrow::unpack(&accounts[3].data.borrow()).unwrap();
+        assert_eq!(escrow_info.deposited_amount, 30);
+        assert_eq!(escrow_info.filled_amount, 40);
+        // 70 tokens in all still at 2 each
+        assert_eq!(escrow_info.expected_amount, 140);
+        assert_eq!(escrow_info.revised_at_slot, 80);
+        assert_eq!(escrow_info.terms_hash, escrow_info.hash_terms());
+        let initializer_token_account = TokenAccount::unpack(&accounts[2].data.borrow()).unwrap();
+        assert_eq!(initializer_token_account.amount, 30);
+        let vault = TokenAccount::unpack(&accounts[1].data.borrow()).unwrap();
+        assert_eq!(vault.amount, 30);
+    }
+
+    #[test]
+    fn test_set_receive_account() {
+        let program_id = Pubkey::new_unique();
+        let initializer_pubkey = Pubkey::new_unique();
+        let receive_mint = Pubkey::new_unique();
+        let escrow = Escrow {
+            is_initialized: true,
+            initializer_pubkey,
+            initializer_token_to_receive_account_pubkey: Pubkey::new_unique(),
+            expected_amount: 150,
+            receive_mint,
+            ..Escrow::default()
+        };
+        let set_receive_account = |escrow: &Escrow, receive_account: &mut SolanaAccount| {
+            let mut escrow_account =
+                SolanaAccount::new(1_000, Escrow::get_packed_len(), &program_id);
+            escrow.pack_into_slice(&mut escrow_account.data);
+            let mut initializer_account = SolanaAccount::default();
+            let receive_pubkey = Pubkey::new_unique();
+            let mut accounts = [
+                (&initializer_pubkey, true, &mut initializer_account),
+                (&Pubkey::new_unique(), false, &mut escrow_account),
+                (&receive_pubkey, false, receive_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_set_receive_account(&accounts, &program_id)?;
+            let escrow_info = Escrow::unpack(&accounts[1].data.borrow()).unwrap();
+            assert_eq!(
+                escrow_info.initializer_token_to_receive_account_pubkey,
+                receive_pubkey
+            );
+            Ok(())
+        };
+
+        let mut receive_account = packed_token_account(&receive_mint, &initializer_pubkey, 0);
+        assert_eq!(set_receive_account(&escrow, &mut receive_account), Ok(()));
+        // another mint, another owner's account or another token program's
+        let mut other_mint = packed_token_account(&Pubkey::new_unique(), &initializer_pubkey, 0);
+        assert_eq!(
+            set_receive_account(&escrow, &mut other_mint),
+            Err(ProgramError::InvalidAccountData)
+        );
+        let mut other_owner = packed_token_account(&receive_mint, &Pubkey::new_unique(), 0);
+        assert_eq!(
+            set_receive_account(&escrow, &mut other_owner),
+            Err(ProgramError::InvalidAccountData)
+        );
+        receive_account.owner = Pubkey::new_unique();
+        assert_eq!(
+            set_receive_account(&escrow, &mut receive_account),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        // the PDA holds a chained escrow's receive account
+        let chained = Escrow {
+            chain_receive_pubkey: Pubkey::new_unique(),
+            ..escrow
+        };
+        let mut receive_account = packed_token_account(&receive_mint, &initializer_pubkey, 0);
+        assert_eq!(
+            set_receive_account(&chained, &mut receive_account),
+            Err(EscrowError::ChainingUnsupported.into())
+        );
+    }
+
+    #[test]
+    fn test_operator() {
+        let program_id = Pubkey::new_unique();
+        test_syscall_stubs(&program_id);
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+        let initializer_pubkey = Pubkey::new_unique();
+        let operator_pubkey = Pubkey::new_unique();
+        let vault_pubkey = Pubkey::new_unique();
+        let escrow_pubkey = Pubkey::new_unique();
+
+        let mut escrow_account = SolanaAccount::new(1_000, Escrow::get_packed_len(), &program_id);
+        Escrow {
+            is_initialized: true,
+            initializer_pubkey,
+            temp_token_account_pubkey: vault_pubkey,
+            expected_amount: 150,
+            ..Escrow::default()
+        }
+        .pack_into_slice(&mut escrow_account.data);
+        let mut initializer_account = SolanaAccount::default();
+        let mut operator_account = SolanaAccount::default();
+        let mut clock_sysvar = create_account_for_test(&Clock::default());
+        let mut reprice =
+            |authority: &Pubkey, escrow_account: &mut SolanaAccount, expected_amount| {
+                let mut authority_account = SolanaAccount::default();
+                let mut accounts = [
+                    (authority, true, &mut authority_account),
+                    (&escrow_pubkey, false, escrow_account),
+                    (&sysvar::clock::id(), false, &mut clock_sysvar),
+                ];
+                let accounts = create_is_signer_account_infos(&mut accounts);
+                Processor::process_reprice(&accounts, expected_amount, &program_id)
+            };
+
+        // only the initializer reprices until they name an operator
+        reprice(&initializer_pubkey, &mut escrow_account, 140).unwrap();
+        assert_eq!(
+            reprice(&operator_pubkey, &mut escrow_account, 130),
+            Err(ProgramError::InvalidAccountData)
+        );
+        let mut accounts = [
+            (&initializer_pubkey, true, &mut initializer_account),
+            (&escrow_pubkey, false, &mut escrow_account),
+        ];
+        let accounts = create_is_signer_account_infos(&mut accounts);
+        Processor::process_set_operator(&accounts, &operator_pubkey, &program_id).unwrap();
+        reprice(&operator_pubkey, &mut escrow_account, 130).unwrap();
+        let escrow_info = Escrow::unpack(&escrow_account.data).unwrap();
+        assert_eq!(escrow_info.operator, operator_pubkey);
+        assert_eq!(escrow_info.expected_amount, 130);
+        assert_eq!(
+            reprice(&operator_pubkey, &mut escrow_account, 0),
+            Err(EscrowError::InvalidInstruction.into())
+        );
+
+        let mint = Pubkey::new_unique();
+        let mut vault = packed_token_account(&mint, &pda, 100);
+        let mut token_program_account = SolanaAccount::default();
+        let mut pda_account = SolanaAccount::default();
+        let mut operators_token_account = packed_token_account(&mint, &operator_pubkey, 0);
+        let mut initializers_token_account = packed_token_account(&mint, &initializer_pubkey, 0);
+        let mut operator_cancel = |refund_account: &mut SolanaAccount| {
+            let mut accounts = [
+                (&operator_pubkey, true, &mut operator_account),
+                (&initializer_pubkey, false, &mut initializer_account),
+                (&vault_pubkey, false, &mut vault),
+                (&Pubkey::new_unique(), false, refund_account),
+                (&escrow_pubkey, false, &mut escrow_account),
+                (&spl_token::id(), false, &mut token_program_account),
+                (&pda, false, &mut pda_account),
+                (&sysvar::clock::id(), false, &mut clock_sysvar),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_operator_cancel(&accounts, &program_id)
+        };
+
+        // the operator can't send the deposit anywhere but back to the initializer
+        assert_eq!(
+            operator_cancel(&mut operators_token_account),
+            Err(ProgramError::InvalidAccountData)
+        );
+        operator_cancel(&mut initializers_token_account).expect("error: process_operator_cancel()");
+        assert_eq!(
+            TokenAccount::unpack(&initializers_token_account.data)
+                .unwrap()
+                .amount,
+            100
+        );
+        assert_eq!(initializer_account.lamports, 1_000);
+    }
+
+    #[test]
+    fn test_session() {
+        let program_id = crate::id();
+        test_syscall_stubs(&program_id);
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+        let maker_pubkey = Pubkey::new_unique();
+        let session_key = Pubkey::new_unique();
+        let session_pubkey = Session::find_address(&maker_pubkey, &session_key, &program_id).0;
+        let vault_pubkey = Pubkey::new_unique();
+        let escrow_pubkey = Pubkey::new_unique();
+
+        let mut session_account = SolanaAccount::new(0, Session::LEN, &program_id);
+        let mut session = Session {
+            is_initialized: true,
+            maker: maker_pubkey,
+            session_key,
+            expires_at_slot: 10,
+            permissions: SESSION_CAN_CREATE,
+        };
+        session.pack_into_slice(&mut session_account.data);
+        let mint = Pubkey::new_unique();
+        let receive_mint = Pubkey::new_unique();
+        let mut vault = packed_token_account(&mint, &session_key, 100);
+        let mut session_receive_account = packed_token_account(&receive_mint, &session_key, 0);
+        let mut makers_receive_account = packed_token_account(&receive_mint, &maker_pubkey, 0);
+        let mut escrow_account = SolanaAccount::new(
+            Rent::default().minimum_balance(Escrow::LEN),
+            Escrow::LEN,
+            &program_id,
+        );
+        let mut session_key_account = SolanaAccount::default();
+        let mut clock_sysvar = create_account_for_test(&Clock::default());
+        let mut rent_sysvar = create_account_for_test(&Rent::default());
+        let mut token_program_account = SolanaAccount::default();
+        let mut mint_list_account = SolanaAccount::default();
+        let mut session_init_escrow = |receive_account: &mut SolanaAccount| {
+            let mut accounts = [
+                (&session_pubkey, false, &mut session_account),
+                (&sysvar::clock::id(), false, &mut clock_sysvar),
+                (&session_key, true, &mut session_key_account),
+                (&vault_pubkey, false, &mut vault),
+                (&Pubkey::new_unique(), false, receive_account),
+                (&escrow_pubkey, false, &mut escrow_account),
+                (&sysvar::rent::id(), false, &mut rent_sysvar),
+                (&spl_token::id(), false, &mut token_program_account),
+                (
+                    &MintList::find_address(&program_id).0,
+                    false,
+                    &mut mint_list_account,
+                ),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_session_init_escrow(
+                &accounts,
+                150,
+                &EscrowTerms::default(),
+                &program_id,
+            )
+        };
+
+        // the proceeds of the maker's escrow can't go to the session key
+        assert_eq!(
+            session_init_escrow(&mut session_receive_account),
+            Err(ProgramError::InvalidAccountData)
+        );
+        session_init_escrow(&mut makers_receive_account)
+            .expect("error: process_session_init_escrow()");
+        let escrow_info = Escrow::unpack(&escrow_account.data).unwrap();
+        assert_eq!(escrow_info.initializer_pubkey, maker_pubkey);
+        assert_eq!(TokenAccount::unpack(&vault.data).unwrap().owner, pda);
+
+        let mut maker_account = SolanaAccount::default();
+        let mut pda_account = SolanaAccount::default();
+        let mut makers_token_account = packed_token_account(&mint, &maker_pubkey, 0);
+        let mut session_cancel = |session_account: &mut SolanaAccount, slot| {
+            let mut clock_sysvar = create_account_for_test(&Clock {
+                slot,
+                ..Clock::default()
+            });
+            let mut accounts = [
+                (&session_key, true, &mut session_key_account),
+                (&session_pubkey, false, session_account),
+                (&maker_pubkey, false, &mut maker_account),
+                (&vault_pubkey, false, &mut vault),
+                (&Pubkey::new_unique(), false, &mut makers_token_account),
+                (&escrow_pubkey, false, &mut escrow_account),
+                (&spl_token::id(), false, &mut token_program_account),
+                (&pda, false, &mut pda_account),
+                (&sysvar::clock::id(), false, &mut clock_sysvar),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_session_cancel(&accounts, &program_id)
+        };
+
+        assert_eq!(
+            session_cancel(&mut session_account, 0),
+            Err(EscrowError::SessionNotPermitted.into())
+        );
+        session.permissions |= SESSION_CAN_CANCEL;
+        session.pack_into_slice(&mut session_account.data);
+        assert_eq!(
+            session_cancel(&mut session_account, 10),
+            Err(EscrowError::SessionExpired.into())
+        );
+        session_cancel(&mut session_account, 9).expect("error: process_session_cancel()");
+        assert_eq!(
+            TokenAccount::unpack(&makers_token_account.data)
+                .unwrap()
+                .amount,
+            100
+        );
+    }
+
+    #[test]
+    fn test_recovery() {
+        let program_id = Pubkey::new_unique();
+        test_syscall_stubs(&program_id);
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+        let admin_pubkey = Pubkey::new_unique();
         let initializer_pubkey = Pubkey::new_unique();
-        let pdas_temp_token_pubkey = Pubkey::new_unique();
-        let initializer_token_to_receive_account_pubkey = Pubkey::new_unique();
+        let vault_pubkey = Pubkey::new_unique();
+        let escrow_pubkey = Pubkey::new_unique();
+        let recovery_pubkey = Recovery::find_address(&escrow_pubkey, &program_id).0;
+
+        let mut config_account = SolanaAccount::new(0, Config::LEN, &program_id);
+        Config {
+            is_initialized: true,
+            admin: admin_pubkey,
+            ..Config::default()
+        }
+        .pack_into_slice(&mut config_account.data);
+        let mut escrow_account = SolanaAccount::new(1_000, Escrow::LEN, &program_id);
+        Escrow {
+            is_initialized: true,
+            initializer_pubkey,
+            temp_token_account_pubkey: vault_pubkey,
+            expected_amount: 150,
+            deposited_amount: 100,
+            ..Escrow::default()
+        }
+        .pack_into_slice(&mut escrow_account.data);
+        // the system program is stubbed out, so the account is allocated up front
+        let mut recovery_account = SolanaAccount::new(0, Recovery::LEN, &Pubkey::default());
+        let mut admin_account = SolanaAccount::default();
+        let mut clock_sysvar = create_account_for_test(&Clock {
+            unix_timestamp: 1_000,
+            ..Clock::default()
+        });
+        let mut rent_sysvar = create_account_for_test(&Rent::default());
+        let mut system_program_account = SolanaAccount::default();
+
+        let mut propose = |admin: &Pubkey| {
+            let mut accounts = [
+                (admin, true, &mut admin_account),
+                (
+                    &Config::find_address(&program_id).0,
+                    false,
+                    &mut config_account,
+                ),
+                (&escrow_pubkey, false, &mut escrow_account),
+                (&recovery_pubkey, false, &mut recovery_account),
+                (&sysvar::clock::id(), false, &mut clock_sysvar),
+                (&sysvar::rent::id(), false, &mut rent_sysvar),
+                (
+                    &solana_program::system_program::id(),
+                    false,
+                    &mut system_program_account,
+                ),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_propose_recovery(&accounts, &program_id)
+        };
+        assert_eq!(
+            propose(&Pubkey::new_unique()),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        propose(&admin_pubkey).expect("error: process_propose_recovery()");
+        recovery_account.owner = program_id;
+        let recovery = Recovery::unpack(&recovery_account.data).unwrap();
+        assert_eq!(recovery.escrow, escrow_pubkey);
+        assert_eq!(recovery.executable_at, 1_000 + RECOVERY_TIMELOCK_SECONDS);
+
+        let mint = Pubkey::new_unique();
+        let mut vault = packed_token_account(&mint, &pda, 100);
+        let mut initializers_token_account = packed_token_account(&mint, &initializer_pubkey, 0);
+        let mut initializer_account = SolanaAccount::default();
+        let mut token_program_account = SolanaAccount::default();
+        let mut pda_account = SolanaAccount::default();
+        let mut execute = |unix_timestamp| {
+            let mut clock_sysvar = create_account_for_test(&Clock {
+                unix_timestamp,
+                ..Clock::default()
+            });
+            let mut accounts = [
+                (&admin_pubkey, true, &mut admin_account),
+                (
+                    &Config::find_address(&program_id).0,
+                    false,
+                    &mut config_account,
+                ),
+                (&recovery_pubkey, false, &mut recovery_account),
+                (&escrow_pubkey, false, &mut escrow_account),
+                (&vault_pubkey, false, &mut vault),
+                (
+                    &Pubkey::new_unique(),
+                    false,
+                    &mut initializers_token_account,
+                ),
+                (&initializer_pubkey, false, &mut initializer_account),
+                (&spl_token::id(), false, &mut token_program_account),
+                (&pda, false, &mut pda_account),
+                (&sysvar::clock::id(), false, &mut clock_sysvar),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_execute_recovery(&accounts, &program_id)
+        };
+        assert_eq!(
+            execute(recovery.executable_at - 1),
+            Err(EscrowError::RecoveryTimelocked.into())
+        );
+        execute(recovery.executable_at).expect("error: process_execute_recovery()");
+        assert_eq!(
+            TokenAccount::unpack(&initializers_token_account.data)
+                .unwrap()
+                .amount,
+            100
+        );
+        assert_eq!(escrow_account.lamports, 0);
+        assert_eq!(initializer_account.lamports, 1_000);
+    }
+
+    #[test]
+    fn test_cancel_governance_condition() {
+        let program_id = Pubkey::new_unique();
+        test_syscall_stubs(&program_id);
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+        let initializer_pubkey = Pubkey::new_unique();
+        let vault_pubkey = Pubkey::new_unique();
+        let proposal_pubkey = Pubkey::new_unique();
+
+        let mut escrow_account = SolanaAccount::new(1_000, Escrow::get_packed_len(), &program_id);
+        Escrow {
+            is_initialized: true,
+            initializer_pubkey,
+            temp_token_account_pubkey: vault_pubkey,
+            expected_amount: 150,
+            release_condition: governance::proposal_condition(
+                &proposal_pubkey,
+                &governance::id(),
+                ProposalState::Defeated,
+                true,
+            ),
+            ..Escrow::default()
+        }
+        .pack_into_slice(&mut escrow_account.data);
+        let mint = Pubkey::new_unique();
+        let mut vault = packed_token_account(&mint, &pda, 100);
+        let mut clock_sysvar = create_account_for_test(&Clock::default());
+        let mut proposal = SolanaAccount::new(0, 100, &governance::id());
+        proposal.data[governance::PROPOSAL_STATE_OFFSET as usize] = ProposalState::Voting as u8;
+        let mut initializer_account = SolanaAccount::default();
+        let mut initializer_token_account = packed_token_account(&mint, &initializer_pubkey, 0);
+        let mut token_program_account = SolanaAccount::default();
+        let mut pda_account = SolanaAccount::default();
+
+        let mut accounts = [
+            (&initializer_pubkey, true, &mut initializer_account),
+            (&vault_pubkey, false, &mut vault),
+            (&Pubkey::new_unique(), false, &mut initializer_token_account),
+            (&Pubkey::new_unique(), false, &mut escrow_account),
+            (&spl_token::id(), false, &mut token_program_account),
+            (&pda, false, &mut pda_account),
+            (&sysvar::clock::id(), false, &mut clock_sysvar),
+            (&proposal_pubkey, false, &mut proposal),
+        ];
+        let accounts = create_is_signer_account_infos(&mut accounts);
+
+        assert_eq!(
+            process_atomically(&accounts[..7], |accounts| {
+                Processor::process_cancel(accounts, &program_id)
+            }),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            process_atomically(&accounts, |accounts| {
+                Processor::process_cancel(accounts, &program_id)
+            }),
+            Err(EscrowError::ConditionNotMet.into())
+        );
+
+        accounts[7].data.borrow_mut()[governance::PROPOSAL_STATE_OFFSET as usize] =
+            ProposalState::Defeated as u8;
+        Processor::process_cancel(&accounts, &program_id).expect("error: process_cancel()");
+        assert_eq!(accounts[3].lamports(), 0);
+        let initializer_token_account = TokenAccount::unpack(&accounts[2].data.borrow()).unwrap();
+        assert_eq!(initializer_token_account.amount, 100);
+    }
+
+    #[test]
+    fn test_reserve() {
+        let program_id = Pubkey::new_unique();
+        test_syscall_stubs(&program_id);
+        let initializer_pubkey = Pubkey::new_unique();
+        let escrow_pubkey = Pubkey::new_unique();
+        let bond = 2_000;
+
+        let mut escrow_account = SolanaAccount::new(1_000, Escrow::get_packed_len(), &program_id);
+        Escrow {
+            is_initialized: true,
+            initializer_pubkey,
+            reserve_bond_lamports: bond,
+            reserve_slots: 10,
+            ..Escrow::default()
+        }
+        .pack_into_slice(&mut escrow_account.data);
+        let mut clock_sysvar = create_account_for_test(&Clock {
+            slot: 100,
+            ..Clock::default()
+        });
+        let mut taker_account = SolanaAccount::default();
+        let mut initializer_account = SolanaAccount::default();
+        let mut system_program_account = SolanaAccount::default();
+
+        let taker_pubkey = Pubkey::new_unique();
+        let mut accounts = [
+            (&taker_pubkey, true, &mut taker_account),
+            (&escrow_pubkey, false, &mut escrow_account),
+            (&initializer_pubkey, false, &mut initializer_account),
+            (&sysvar::clock::id(), false, &mut clock_sysvar),
+            (
+                &solana_program::system_program::id(),
+                false,
+                &mut system_program_account,
+            ),
+        ];
+        let mut accounts = create_is_signer_account_infos(&mut accounts);
+
+        Processor::process_reserve(&accounts, &program_id).expect("error: process_reserve()");
+        let escrow_info = Escrow::unpack(&accounts[1].data.borrow()).unwrap();
+        assert_eq!(escrow_info.reserved_by, taker_pubkey);
+        assert_eq!(escrow_info.reserved_until_slot, 110);
+        // the stubbed system program moves no lamports, post the bond by hand
+        **accounts[1].lamports.borrow_mut() += bond;
+
+        let other_taker = Pubkey::new_unique();
+        accounts[0].key = &other_taker;
+        assert_eq!(
+            Processor::process_reserve(&accounts, &program_id),
+            Err(EscrowError::EscrowReserved.into())
+        );
+
+        // once the reservation lapses the next taker can reserve and the bond is forfeited
+        Clock {
+            slot: 110,
+            ..Clock::default()
+        }
+        .to_account_info(&mut accounts[3].clone())
+        .unwrap();
+        Processor::process_reserve(&accounts, &program_id).expect("error: process_reserve()");
+        let escrow_info = Escrow::unpack(&accounts[1].data.borrow()).unwrap();
+        assert_eq!(escrow_info.reserved_by, other_taker);
+        assert_eq!(escrow_info.reserved_until_slot, 120);
+        assert_eq!(accounts[2].lamports(), bond);
+        assert_eq!(accounts[1].lamports(), 1_000);
+    }
+
+    #[test]
+    fn test_exchange_reserved() {
+        let reserver = Pubkey::new_unique();
+        let bond = 2_000;
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                reserve_bond_lamports: bond,
+                reserve_slots: 10,
+                reserved_by: reserver,
+                reserved_until_slot: 110,
+                ..Escrow::default()
+            },
+            100,
+        );
+        fixture.accounts[ExchangeFixture::ESCROW].lamports += bond;
+        fixture.push(
+            sysvar::clock::id(),
+            create_account_for_test(&Clock {
+                slot: 105,
+                ..Clock::default()
+            }),
+        );
+
+        assert_eq!(
+            fixture.exchange(100),
+            Err(EscrowError::EscrowReserved.into())
+        );
+
+        // the reserver gets their bond back on top of the rebate
+        fixture.set_taker(reserver);
+        fixture.exchange(100).expect("error: process_exchange()");
+        assert_eq!(
+            fixture.accounts[ExchangeFixture::TAKER].lamports,
+            bond + TAKER_CLOSE_REBATE_LAMPORTS
+        );
+        assert_eq!(
+            fixture.accounts[ExchangeFixture::INITIALIZER].lamports,
+            ExchangeFixture::ESCROW_LAMPORTS - TAKER_CLOSE_REBATE_LAMPORTS
+        );
+    }
+
+    #[test]
+    fn test_two_phase_exchange() {
+        let program_id = Pubkey::new_unique();
+        test_syscall_stubs(&program_id);
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+        let taker_pubkey = Pubkey::new_unique();
+        let initializer_pubkey = Pubkey::new_unique();
+        let vault_pubkey = Pubkey::new_unique();
+        let payment_vault_pubkey = Pubkey::new_unique();
+        let takers_receive_pubkey = Pubkey::new_unique();
+        let initializers_receive_pubkey = Pubkey::new_unique();
+        let escrow_pubkey = Pubkey::new_unique();
+
+        let mut escrow_account = SolanaAccount::new(1_000, Escrow::get_packed_len(), &program_id);
+        Escrow {
+            is_initialized: true,
+            initializer_pubkey,
+            temp_token_account_pubkey: vault_pubkey,
+            initializer_token_to_receive_account_pubkey: initializers_receive_pubkey,
+            expected_amount: 150,
+            settle_timeout_slots: 10,
+            deposited_amount: 100,
+            ..Escrow::default()
+        }
+        .pack_into_slice(&mut escrow_account.data);
+        let token_account = |amount, owner| {
+            let mut account =
+                SolanaAccount::new(0, TokenAccount::get_packed_len(), &spl_token::id());
+            TokenAccount {
+                amount,
+                owner,
+                state: spl_token::state::AccountState::Initialized,
+                ..TokenAccount::default()
+            }
+            .pack_into_slice(&mut account.data);
+            account
+        };
+        let mut vault = token_account(100, pda);
+        let mut payment_vault = token_account(150, taker_pubkey);
+        let mut takers_receive_account = token_account(0, taker_pubkey);
+        let mut initializers_receive_account = token_account(0, initializer_pubkey);
+        let mut taker_account = SolanaAccount::default();
+        let mut initializer_account = SolanaAccount::default();
+        let mut token_program_account = SolanaAccount::default();
+        let mut pda_account = SolanaAccount::default();
+        let (config_pubkey, _bump_seed) = Config::find_address(&program_id);
+        let mut config_account = SolanaAccount::default();
+        let (mint_volume_pubkey, _bump_seed) =
+            MintVolume::find_address(&Pubkey::default(), &program_id);
+        let mut mint_volume_account = SolanaAccount::new(0, MintVolume::LEN, &program_id);
+        MintVolume {
+            is_initialized: true,
+            ..MintVolume::default()
+        }
+        .pack_into_slice(&mut mint_volume_account.data);
+        let clock_at = |slot| {
+            create_account_for_test(&Clock {
+                slot,
+                ..Clock::default()
+            })
+        };
+
+        let commit = |escrow_account: &mut SolanaAccount,
+                      vault: &mut SolanaAccount,
+                      payment_vault: &mut SolanaAccount,
+                      takers_receive_account: &mut SolanaAccount,
+                      slot| {
+            let mut taker_account = SolanaAccount::default();
+            let mut initializer_account = SolanaAccount::default();
+            let mut token_program_account = SolanaAccount::default();
+            let mut clock_sysvar = clock_at(slot);
+            let mut accounts = [
+                (&taker_pubkey, true, &mut taker_account),
+                (&payment_vault_pubkey, false, payment_vault),
+                (&takers_receive_pubkey, false, takers_receive_account),
+                (&vault_pubkey, false, vault),
+                (&initializer_pubkey, false, &mut initializer_account),
+                (&escrow_pubkey, false, escrow_account),
+                (&spl_token::id(), false, &mut token_program_account),
+                (&sysvar::clock::id(), false, &mut clock_sysvar),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_commit_exchange(&accounts, 100, &program_id)
+        };
+
+        // the payment has to be in the mint the initializer asked for
+        let mut other_mint_payment = token_account(150, taker_pubkey);
+        let mut payment_info = TokenAccount::unpack(&other_mint_payment.data).unwrap();
+        payment_info.mint = Pubkey::new_unique();
+        payment_info.pack_into_slice(&mut other_mint_payment.data);
+        assert_eq!(
+            commit(
+                &mut escrow_account,
+                &mut vault,
+                &mut other_mint_payment,
+                &mut takers_receive_account,
+                100,
+            ),
+            Err(ProgramError::InvalidAccountData)
+        );
+        commit(
+            &mut escrow_account,
+            &mut vault,
+            &mut payment_vault,
+            &mut takers_receive_account,
+            100,
+        )
+        .expect("error: process_commit_exchange()");
+        let escrow_info = Escrow::unpack(&escrow_account.data).unwrap();
+        assert_eq!(escrow_info.committed_taker, taker_pubkey);
+        assert_eq!(escrow_info.committed_until_slot, 110);
+        assert_eq!(escrow_info.payment_vault_pubkey, payment_vault_pubkey);
+        assert_eq!(
+            commit(
+                &mut escrow_account,
+                &mut vault,
+                &mut payment_vault,
+                &mut takers_receive_account,
+                101,
+            ),
+            Err(EscrowError::EscrowCommitted.into())
+        );
+
+        // the taker can not take their payment back before the timeout, nor through a lookalike
+        // token program
+        let lookalike = Pubkey::new_unique();
+        let token_program = spl_token::id();
+        for (slot, token_program, result) in [
+            (
+                109,
+                &token_program,
+                Err(EscrowError::EscrowCommitted.into()),
+            ),
+            (110, &lookalike, Err(ProgramError::IncorrectProgramId)),
+            (110, &token_program, Ok(())),
+        ]
+        .iter()
+        {
+            let mut clock_sysvar = clock_at(*slot);
+            let mut accounts = [
+                (&taker_pubkey, true, &mut taker_account),
+                (&payment_vault_pubkey, false, &mut payment_vault),
+                (&takers_receive_pubkey, false, &mut takers_receive_account),
+                (&taker_pubkey, false, &mut SolanaAccount::default()),
+                (&escrow_pubkey, false, &mut escrow_account),
+                (token_program, false, &mut token_program_account),
+                (&pda, false, &mut pda_account),
+                (&sysvar::clock::id(), false, &mut clock_sysvar),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            assert_eq!(
+                Processor::process_unwind_exchange(&accounts, &program_id),
+                *result
+            );
+        }
+        assert!(!Escrow::unpack(&escrow_account.data).unwrap().is_committed());
+        let refund = TokenAccount::unpack(&takers_receive_account.data).unwrap();
+        assert_eq!(refund.amount, 150);
+
+        // the unwound payment vault was closed, so the taker pays out of a new one
+        payment_vault = token_account(150, taker_pubkey);
+        commit(
+            &mut escrow_account,
+            &mut vault,
+            &mut payment_vault,
+            &mut takers_receive_account,
+            200,
+        )
+        .expect("error: process_commit_exchange()");
+        for (slot, token_program, result) in [
+            (210, &token_program, Err(EscrowError::CommitExpired.into())),
+            (209, &lookalike, Err(ProgramError::IncorrectProgramId)),
+            (209, &token_program, Ok(())),
+        ]
+        .iter()
+        {
+            let mut clock_sysvar = clock_at(*slot);
+            let mut accounts = [
+                (&initializer_pubkey, true, &mut initializer_account),
+                (&vault_pubkey, false, &mut vault),
+                (&payment_vault_pubkey, false, &mut payment_vault),
+                (&takers_receive_pubkey, false, &mut takers_receive_account),
+                (
+                    &initializers_receive_pubkey,
+                    false,
+                    &mut initializers_receive_account,
+                ),
+                (&taker_pubkey, false, &mut taker_account),
+                (&escrow_pubkey, false, &mut escrow_account),
+                (token_program, false, &mut token_program_account),
+                (&pda, false, &mut pda_account),
+                (&sysvar::clock::id(), false, &mut clock_sysvar),
+                (&config_pubkey, false, &mut config_account),
+                (&mint_volume_pubkey, false, &mut mint_volume_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            assert_eq!(
+                Processor::process_settle_exchange(&accounts, &program_id),
+                *result
+            );
+        }
+        assert_eq!(escrow_account.lamports, 0);
+        assert_eq!(initializer_account.lamports, 1_000);
+        let balance = |account: &SolanaAccount| TokenAccount::unpack(&account.data).unwrap().amount;
+        assert_eq!(balance(&takers_receive_account), 150 + 100);
+        assert_eq!(balance(&initializers_receive_account), 150);
+        let mint_volume = MintVolume::unpack(&mint_volume_account.data).unwrap();
+        assert_eq!(mint_volume.filled_amount, 100);
+        assert_eq!(mint_volume.fill_count, 1);
+    }
+
+    /// instructions sysvar of a transaction executing `instructions[current_index]`
+    fn instructions_sysvar_account(
+        instructions: &[Instruction],
+        current_index: u16,
+    ) -> SolanaAccount {
+        let message = solana_program::message::Message::new(instructions, None);
+        let mut data = message.serialize_instructions(false);
+        data.extend_from_slice(&current_index.to_le_bytes());
+        SolanaAccount {
+            data,
+            ..SolanaAccount::default()
+        }
+    }
+
+    #[test]
+    fn test_exchange_top_level_only() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                exchange_top_level_only: true,
+                ..Escrow::default()
+            },
+            100,
+        );
+        // the transaction's instruction belongs to a composing program
+        fixture.push(
+            sysvar::instructions::id(),
+            instructions_sysvar_account(
+                &[Instruction::new_with_bytes(
+                    Pubkey::new_unique(),
+                    &[1],
+                    vec![],
+                )],
+                0,
+            ),
+        );
+        assert_eq!(
+            fixture.exchange(100),
+            Err(EscrowError::CpiExchangeForbidden.into())
+        );
+
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        *fixture.accounts.last_mut().unwrap() = instructions_sysvar_account(
+            &[Instruction::new_with_bytes(program_id, &[1], vec![])],
+            0,
+        );
+        fixture.exchange(100).expect("error: process_exchange()");
+    }
+
+    #[test]
+    fn test_exchange_pda_taker() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                ..Escrow::default()
+            },
+            100,
+        );
+        // a vault PDA of a composing program, which has no ed25519 key behind it
+        let (taker_pda, _bump_seed) =
+            Pubkey::find_program_address(&[b"vault"], &Pubkey::new_unique());
+        assert!(!taker_pda.is_on_curve());
+        fixture.set_taker(taker_pda);
+
+        fixture.exchange(100).expect("error: process_exchange()");
+        assert_eq!(
+            fixture.accounts[ExchangeFixture::TAKER].lamports,
+            TAKER_CLOSE_REBATE_LAMPORTS
+        );
+    }
+
+    #[test]
+    fn test_exchange_closed_escrow_can_not_be_replayed() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                ..Escrow::default()
+            },
+            100,
+        );
+        fixture.exchange(100).expect("error: process_exchange()");
+        assert!(fixture.accounts[ExchangeFixture::ESCROW]
+            .data
+            .iter()
+            .all(|byte| *byte == 0));
+
+        // refunding the closed escrow later in the transaction does not bring its state back
+        fixture.accounts[ExchangeFixture::ESCROW].lamports = ExchangeFixture::ESCROW_LAMPORTS;
+        assert_eq!(
+            fixture.exchange(100),
+            Err(ProgramError::UninitializedAccount)
+        );
+    }
+
+    #[test]
+    fn test_exchange_rejects_foreign_escrow() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                ..Escrow::default()
+            },
+            100,
+        );
+        // a lookalike escrow written by another program must not unlock the PDA's vault
+        fixture.accounts[ExchangeFixture::ESCROW].owner = Pubkey::new_unique();
+        assert_eq!(fixture.exchange(100), Err(ProgramError::IncorrectProgramId));
+    }
+
+    #[test]
+    fn test_check_program_id() {
+        assert_eq!(Processor::check_program_id(&crate::id()), Ok(()));
+        assert_eq!(
+            Processor::check_program_id(&Pubkey::new_unique()),
+            Err(ProgramError::IncorrectProgramId)
+        );
+    }
+
+    #[test]
+    fn test_unpack_lengths() {
+        let escrow = Escrow {
+            is_initialized: true,
+            ..Escrow::default()
+        };
+        let mut data = vec![0; Escrow::LEN + 1];
+        escrow.pack_into_slice(&mut data[..Escrow::LEN]);
+        for len in [0, 1, Escrow::LEN - 1, Escrow::LEN + 1].iter() {
+            assert_eq!(
+                Escrow::unpack(&data[..*len]),
+                Err(ProgramError::InvalidAccountData)
+            );
+            assert_eq!(
+                Escrow::unpack_from_slice(&data[..*len]),
+                Err(ProgramError::InvalidAccountData)
+            );
+        }
+        assert_eq!(Escrow::unpack(&data[..Escrow::LEN]), Ok(escrow));
+
+        let invalid_instruction = Err(EscrowError::InvalidInstruction.into());
+        let unpack = |data: &[u8]| EscrowInstruction::unpack(data).map(|_| ());
+        assert_eq!(unpack(&[]), invalid_instruction);
+        let mut instruction_data = vec![1];
+        // Exchange without a full amount
+        for len in 0..8 {
+            assert_eq!(unpack(&instruction_data), invalid_instruction);
+            instruction_data.push(len);
+        }
+        assert_eq!(unpack(&instruction_data), Ok(()));
+        // Cancel, CommitExchange and ExchangeCnft with a byte too few and a byte too many
+        for (tag, len) in [(8, 0), (10, 8), (38, 32)].iter() {
+            let mut instruction_data = vec![*tag; len + 2];
+            instruction_data[0] = *tag;
+            assert_eq!(unpack(&instruction_data), invalid_instruction);
+            instruction_data.pop();
+            assert_eq!(unpack(&instruction_data), Ok(()));
+            if *len > 0 {
+                instruction_data.pop();
+                assert_eq!(unpack(&instruction_data), invalid_instruction);
+            }
+        }
+        // InitEscrow's terms end with the release condition, then the release window
+        let mut instruction_data = vec![0; 1 + 8 + 455];
+        instruction_data[1 + 8 + 355 + 66] = 1;
+        assert_eq!(unpack(&instruction_data), Ok(()));
+        instruction_data.push(0);
+        assert_eq!(unpack(&instruction_data), invalid_instruction);
+        instruction_data.resize(1 + 8 + 471, 0);
+        assert_eq!(unpack(&instruction_data), Ok(()));
+        instruction_data.push(0);
+        assert_eq!(unpack(&instruction_data), invalid_instruction);
+        // then the market and the tag
+        instruction_data.resize(1 + 8 + 511, 0);
+        assert_eq!(unpack(&instruction_data), Ok(()));
+        instruction_data.push(0);
+        assert_eq!(unpack(&instruction_data), invalid_instruction);
+    }
+
+    #[test]
+    fn test_account_discriminators() {
+        let discriminators = [
+            ("Escrow", Escrow::DISCRIMINATOR),
+            ("Pool", Pool::DISCRIMINATOR),
+            ("PoolShare", PoolShare::DISCRIMINATOR),
+            ("Config", Config::DISCRIMINATOR),
+            ("MintList", MintList::DISCRIMINATOR),
+            ("MintVolume", MintVolume::DISCRIMINATOR),
+            ("Template", Template::DISCRIMINATOR),
+            ("Bid", Bid::DISCRIMINATOR),
+            ("Ticket", Ticket::DISCRIMINATOR),
+            ("Request", Request::DISCRIMINATOR),
+            ("Quote", Quote::DISCRIMINATOR),
+            ("CnftEscrow", CnftEscrow::DISCRIMINATOR),
+            ("CoreEscrow", CoreEscrow::DISCRIMINATOR),
+            ("RelayNonce", RelayNonce::DISCRIMINATOR),
+            ("Session", Session::DISCRIMINATOR),
+            ("Recovery", Recovery::DISCRIMINATOR),
+        ];
+        for (i, (name, discriminator)) in discriminators.iter().enumerate() {
+            let hash = solana_program::hash::hashv(&[b"account:", name.as_bytes()]);
+            assert_eq!(
+                discriminator[..],
+                hash.to_bytes()[..DISCRIMINATOR_LEN],
+                "{}",
+                name
+            );
+            assert!(discriminators[..i]
+                .iter()
+                .all(|(_, other)| other != discriminator));
+        }
+
+        // accounts of the same size no longer unpack as each other
+        assert_eq!(Recovery::LEN, Ticket::LEN);
+        let mut data = vec![0; Recovery::LEN];
+        Recovery {
+            is_initialized: true,
+            ..Recovery::default()
+        }
+        .pack_into_slice(&mut data);
+        assert_eq!(data[..DISCRIMINATOR_LEN], Recovery::DISCRIMINATOR);
+        assert!(Recovery::unpack(&data).is_ok());
+        assert_eq!(Ticket::unpack(&data), Err(ProgramError::InvalidAccountData));
+        assert_eq!(PoolShare::LEN, Ticket::LEN);
+        let mut data = vec![0; Ticket::LEN];
+        Ticket {
+            is_initialized: true,
+            ..Ticket::default()
+        }
+        .pack_into_slice(&mut data);
+        assert_eq!(
+            PoolShare::unpack(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        // a freshly allocated account is uninitialized, a stray prefix is not
+        let mut data = vec![0; Escrow::LEN];
+        assert_eq!(Escrow::unpack_unchecked(&data), Ok(Escrow::default()));
+        data[DISCRIMINATOR_LEN] = 1;
+        assert_eq!(
+            Escrow::unpack_unchecked(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+        data[DISCRIMINATOR_LEN] = 0;
+        data[0] = 1;
+        assert_eq!(
+            Escrow::unpack_unchecked(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_escrow_account_data() {
+        let escrow = Escrow {
+            is_initialized: true,
+            expected_amount: 150,
+            ..Escrow::default()
+        };
+        let config = Config {
+            is_initialized: true,
+            default_fee_bps: 25,
+            ..Config::default()
+        };
+        let mut data = vec![0; Escrow::LEN];
+        escrow.pack_into_slice(&mut data);
+        assert_eq!(
+            EscrowAccountData::try_unpack(&data),
+            Ok(EscrowAccountData::Escrow(escrow))
+        );
+        let mut data = vec![0; Config::LEN];
+        config.pack_into_slice(&mut data);
+        assert_eq!(
+            EscrowAccountData::try_unpack(&data),
+            Ok(EscrowAccountData::Config(config))
+        );
+
+        // the discriminator picks the type, the type checks the rest
+        data.pop();
+        assert_eq!(
+            EscrowAccountData::try_unpack(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+        for data in [vec![0; Escrow::LEN], vec![1; DISCRIMINATOR_LEN], vec![]].iter() {
+            assert_eq!(
+                EscrowAccountData::try_unpack(data),
+                Err(ProgramError::InvalidAccountData)
+            );
+        }
+    }
+
+    #[test]
+    fn test_metadata_uri() {
+        let uri = MetadataUri::new("https://example.com/offer.json").unwrap();
+        assert_eq!(uri.as_str(), "https://example.com/offer.json");
+        assert_eq!(MetadataUri::unpack(&uri.0), Ok(uri));
+        assert_eq!(MetadataUri::default().as_str(), "");
+        assert_eq!(
+            MetadataUri::new(&"a".repeat(METADATA_URI_LEN))
+                .unwrap()
+                .as_str(),
+            "a".repeat(METADATA_URI_LEN)
+        );
+        assert_eq!(MetadataUri::new(&"a".repeat(METADATA_URI_LEN + 1)), None);
+
+        // bytes after the padding starts and invalid UTF-8 are rejected
+        let mut data = uri.0;
+        data[METADATA_URI_LEN - 1] = b'x';
+        assert!(MetadataUri::unpack(&data).is_err());
+        let mut data = [0; METADATA_URI_LEN];
+        data[0] = 0xff;
+        assert!(MetadataUri::unpack(&data).is_err());
+
+        let mut instruction_data = vec![0];
+        instruction_data.extend_from_slice(&150u64.to_le_bytes());
+        instruction_data.extend_from_slice(&[0; 43]);
+        instruction_data.extend_from_slice(&uri.0);
+        match EscrowInstruction::unpack(&instruction_data).unwrap() {
+            EscrowInstruction::InitEscrow { terms, .. } => assert_eq!(terms.metadata_uri, uri),
+            _ => panic!("expected InitEscrow"),
+        }
+        instruction_data.pop();
+        assert!(EscrowInstruction::unpack(&instruction_data).is_err());
+    }
+
+    #[test]
+    fn test_exchange_requires_memo() {
+        let mut memo_reference = [0; MEMO_REFERENCE_LEN];
+        memo_reference[..9].copy_from_slice(b"invoice-7");
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                memo_reference,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        let exchange = Instruction::new_with_bytes(program_id, &[1], vec![]);
+        let memo = |program_id, text: &str| {
+            Instruction::new_with_bytes(program_id, text.as_bytes(), vec![])
+        };
+
+        for instructions in [
+            vec![exchange.clone()],
+            vec![memo(memo::id(), "invoice-8"), exchange.clone()],
+            // the reference in some other program's data does not count
+            vec![memo(Pubkey::new_unique(), "invoice-7"), exchange.clone()],
+        ]
+        .iter()
+        {
+            fixture.push(
+                sysvar::instructions::id(),
+                instructions_sysvar_account(instructions, instructions.len() as u16 - 1),
+            );
+            assert_eq!(fixture.exchange(100), Err(EscrowError::MemoMissing.into()));
+            fixture.keys.pop();
+            fixture.accounts.pop();
+        }
+
+        fixture.push(
+            sysvar::instructions::id(),
+            instructions_sysvar_account(
+                &[exchange, memo(memo::v1::id(), "paying invoice-7, thanks")],
+                0,
+            ),
+        );
+        fixture.exchange(100).expect("error: process_exchange()");
+    }
+
+    #[test]
+    fn test_exchange_with_memo() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        let exchange_data = |memo: &[u8]| {
+            let mut data = vec![1];
+            data.extend_from_slice(&100u64.to_le_bytes());
+            data.extend_from_slice(memo);
+            data
+        };
+        for memo in [
+            &[0xff, 0xfe][..],
+            &[b'x'; memo::MAX_SETTLEMENT_MEMO_LEN + 1][..],
+        ]
+        .iter()
+        {
+            assert!(matches!(
+                EscrowInstruction::unpack(&exchange_data(memo)),
+                Err(error) if error == EscrowError::InvalidInstruction.into()
+            ));
+        }
+
+        fixture.push(
+            Config::find_address(&program_id).0,
+            SolanaAccount::default(),
+        );
+        fixture.push(fixture.mint_volume_pubkey(), SolanaAccount::default());
+        let data = exchange_data("paying invoice-7, thanks".as_bytes());
+        // with a memo, the memo program has to come last
+        assert_eq!(
+            Processor::process(&program_id, &fixture.account_infos(), &data),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        fixture.push(memo::id(), SolanaAccount::default());
+        Processor::process(&program_id, &fixture.account_infos(), &data)
+            .expect("error: process_exchange_with_memo()");
+        assert_eq!(
+            fixture
+                .token_account(ExchangeFixture::INITIALIZER_RECEIVING)
+                .amount,
+            150
+        );
+    }
+
+    #[test]
+    fn test_get_escrow() {
+        let fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                is_crowdfill: true,
+                filled_amount: 20,
+                expires_at_slot: 200,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        let clock_pubkey = sysvar::clock::id();
+        let get_escrow = |vault_index: usize, slot: u64| {
+            let mut escrow_account = fixture.accounts[ExchangeFixture::ESCROW].clone();
+            let mut vault_account = fixture.accounts[vault_index].clone();
+            let mut clock_account = create_account_for_test(&Clock {
+                slot,
+                ..Clock::default()
+            });
+            let mut accounts = [
+                (
+                    &fixture.keys[ExchangeFixture::ESCROW],
+                    false,
+                    &mut escrow_account,
+                ),
+                (&fixture.keys[vault_index], false, &mut vault_account),
+                (&clock_pubkey, false, &mut clock_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process(&program_id, &accounts, &[46])?;
+            RETURN_DATA.with(|return_data| EscrowView::unpack(&return_data.borrow()))
+        };
+
+        assert_eq!(
+            get_escrow(ExchangeFixture::TAKER_SENDING, 100),
+            Err(ProgramError::InvalidAccountData)
+        );
+        let view = get_escrow(ExchangeFixture::VAULT, 100).unwrap();
+        let escrow = fixture.escrow();
+        assert_eq!(view.initializer_pubkey, escrow.initializer_pubkey);
+        assert_eq!(
+            view.temp_token_account_pubkey,
+            escrow.temp_token_account_pubkey
+        );
+        assert_eq!(view.expected_amount, escrow.expected_amount);
+        assert_eq!(view.terms_hash, escrow.terms_hash);
+        assert_eq!(
+            view.pda_bump_seed,
+            Pubkey::find_program_address(&[b"escrow"], &program_id).1
+        );
+        assert_eq!(view.vault_amount, 100);
+        assert_eq!(view.fill_status, FillStatus::PartlyFilled);
+        assert_eq!(
+            get_escrow(ExchangeFixture::VAULT, 200).unwrap().fill_status,
+            FillStatus::Expired
+        );
+    }
+
+    #[test]
+    fn test_mint_fee_tiers() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        let admin_pubkey = Pubkey::new_unique();
+        let fee_owner = Pubkey::new_unique();
+        let stablecoin = Pubkey::new_unique();
+        let long_tail = Pubkey::new_unique();
+        let (config_pubkey, _bump_seed) = Config::find_address(&program_id);
+        let mut config_account = SolanaAccount::new(0, Config::LEN, &program_id);
+        Config {
+            is_initialized: true,
+            admin: admin_pubkey,
+            fee_owner,
+            default_fee_bps: 30,
+            ..Config::default()
+        }
+        .pack_into_slice(&mut config_account.data);
+
+        let mut admin_account = SolanaAccount::default();
+        let mut mint_account = SolanaAccount::new(0, Mint::LEN, &spl_token::id());
+        Mint {
+            is_initialized: true,
+            ..Mint::default()
+        }
+        .pack_into_slice(&mut mint_account.data);
+        for (signer, fee_bps, result) in [
+            (
+                Pubkey::new_unique(),
+                5,
+                Err(ProgramError::MissingRequiredSignature),
+            ),
+            (admin_pubkey, 10_001, Err(EscrowError::InvalidFee.into())),
+            (admin_pubkey, 5, Ok(())),
+            (admin_pubkey, 1, Ok(())),
+        ]
+        .iter()
+        {
+            let mut accounts = [
+                (signer, true, &mut admin_account),
+                (&config_pubkey, false, &mut config_account),
+                (&stablecoin, false, &mut mint_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            assert_eq!(
+                Processor::process_set_mint_fee(&accounts, *fee_bps, &program_id),
+                *result
+            );
+        }
+        let config = Config::unpack(&config_account.data).unwrap();
+        // setting the fee again replaces the mint's tier
+        assert_eq!(config.fee_tiers().len(), 1);
+        assert_eq!(config.fee_bps(&stablecoin), 1);
+        assert_eq!(config.fee_bps(&long_tail), 30);
+
+        let mut config = Config::default();
+        for _ in 0..MAX_MINT_FEE_TIERS {
+            assert!(config.set_fee_tier(Pubkey::new_unique(), 1));
+        }
+        assert!(!config.set_fee_tier(Pubkey::new_unique(), 1));
+
+        // once configured, Exchange needs the fee owner's token account
+        fixture.push(config_pubkey, config_account);
+        let fee_account = |owner| {
+            let mut account =
+                SolanaAccount::new(0, TokenAccount::get_packed_len(), &spl_token::id());
+            TokenAccount {
+                mint: stablecoin,
+                owner,
+                state: spl_token::state::AccountState::Initialized,
+                ..TokenAccount::default()
+            }
+            .pack_into_slice(&mut account.data);
+            account
+        };
+        let exchange = |fixture: &mut ExchangeFixture| {
+            Processor::process_exchange(&fixture.account_infos(), 100, &program_id)
+        };
+        assert_eq!(
+            exchange(&mut fixture),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        fixture.push(Pubkey::new_unique(), fee_account(Pubkey::new_unique()));
+        assert_eq!(
+            exchange(&mut fixture),
+            Err(ProgramError::InvalidAccountData)
+        );
+        fixture.keys.pop();
+        fixture.accounts.pop();
+        fixture.push(Pubkey::new_unique(), fee_account(fee_owner));
+        fixture.push(fixture.mint_volume_pubkey(), SolanaAccount::default());
+        exchange(&mut fixture).expect("error: process_exchange()");
+    }
+
+    #[test]
+    fn test_lamport_fee() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        let admin_pubkey = Pubkey::new_unique();
+        let fee_owner = Pubkey::new_unique();
+        let (config_pubkey, _bump_seed) = Config::find_address(&program_id);
+        let mut config_account = SolanaAccount::new(0, Config::LEN, &program_id);
+        Config {
+            is_initialized: true,
+            admin: admin_pubkey,
+            fee_owner,
+            default_fee_bps: 30,
+            ..Config::default()
+        }
+        .pack_into_slice(&mut config_account.data);
+
+        let mut admin_account = SolanaAccount::default();
+        for (signer, result) in [
+            (
+                Pubkey::new_unique(),
+                Err(ProgramError::MissingRequiredSignature),
+            ),
+            (admin_pubkey, Ok(())),
+        ]
+        .iter()
+        {
+            let mut accounts = [
+                (signer, true, &mut admin_account),
+                (&config_pubkey, false, &mut config_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            assert_eq!(
+                Processor::process_set_lamport_fee(&accounts, 5_000, &program_id),
+                *result
+            );
+        }
+        assert_eq!(
+            Config::unpack(&config_account.data).unwrap().lamport_fee,
+            5_000
+        );
+
+        // the fee owner and the system program take the place of the fee owner's token account
+        fixture.push(config_pubkey, config_account);
+        let exchange = |fixture: &mut ExchangeFixture| {
+            Processor::process_exchange(&fixture.account_infos(), 100, &program_id)
+        };
+        fixture.push(Pubkey::new_unique(), SolanaAccount::default());
+        fixture.push(system_program::id(), SolanaAccount::default());
+        assert_eq!(
+            exchange(&mut fixture),
+            Err(ProgramError::InvalidAccountData)
+        );
+        fixture.keys.truncate(fixture.keys.len() - 2);
+        fixture.accounts.truncate(fixture.accounts.len() - 2);
+        fixture.push(fee_owner, SolanaAccount::default());
+        fixture.push(system_program::id(), SolanaAccount::default());
+        fixture.push(fixture.mint_volume_pubkey(), SolanaAccount::default());
+        exchange(&mut fixture).expect("error: process_exchange()");
+        // the initializer is paid in full, the fee having been charged in lamports
+        assert_eq!(
+            fixture
+                .token_account(ExchangeFixture::INITIALIZER_RECEIVING)
+                .amount,
+            150
+        );
+    }
+
+    #[test]
+    fn test_markets() {
+        let operator = Pubkey::new_unique();
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 200,
+                market: operator,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        let fee_owner = Pubkey::new_unique();
+        let (market_config_pubkey, _bump_seed) =
+            Config::find_market_address(&operator, &program_id);
+        assert_ne!(market_config_pubkey, Config::find_address(&program_id).0);
+        let mut config_account = SolanaAccount::new(0, Config::LEN, &program_id);
+        Config {
+            is_initialized: true,
+            admin: operator,
+            fee_owner,
+            default_fee_bps: 100,
+            market: operator,
+            ..Config::default()
+        }
+        .pack_into_slice(&mut config_account.data);
+
+        let mut admin_account = SolanaAccount::default();
+        let mut set_paused = |config_account: &mut SolanaAccount, signer, paused| {
+            let mut accounts = [
+                (signer, true, &mut admin_account),
+                (&market_config_pubkey, false, config_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_set_market_paused(&accounts, paused, &program_id)
+        };
+        let stranger = Pubkey::new_unique();
+        assert_eq!(
+            set_paused(&mut config_account, &stranger, true),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        set_paused(&mut config_account, &operator, true)
+            .expect("error: process_set_market_paused()");
+
+        // the market's mint list is its own, the program-wide one can't stand in for it
+        let (mint_list_pubkey, _bump_seed) = MintList::find_address(&program_id);
+        assert_ne!(
+            mint_list_pubkey,
+            MintList::find_market_address(&operator, &program_id).0
+        );
+        let mut mint_list_account = SolanaAccount::new(0, MintList::LEN, &program_id);
+        MintList {
+            is_initialized: true,
+            ..MintList::default()
+        }
+        .pack_into_slice(&mut mint_list_account.data);
+        let mut accounts = [
+            (&operator, true, &mut SolanaAccount::default()),
+            (&market_config_pubkey, false, &mut config_account),
+            (&mint_list_pubkey, false, &mut mint_list_account),
+            (
+                &ExchangeFixture::RECEIVE_MINT,
+                false,
+                &mut SolanaAccount::default(),
+            ),
+        ];
+        let accounts = create_is_signer_account_infos(&mut accounts);
+        assert_eq!(
+            Processor::process_set_mint_listed(&accounts, true, &program_id),
+            Err(ProgramError::InvalidSeeds)
+        );
+
+        let exchange = |fixture: &mut ExchangeFixture| {
+            Processor::process_exchange(&fixture.account_infos(), 100, &program_id)
+        };
+        // the program-wide config doesn't settle the market's escrows
+        fixture.push(
+            Config::find_address(&program_id).0,
+            SolanaAccount::default(),
+        );
+        assert_eq!(exchange(&mut fixture), Err(ProgramError::InvalidSeeds));
+        fixture.keys.pop();
+        fixture.accounts.pop();
+
+        fixture.push(market_config_pubkey, config_account.clone());
+        assert_eq!(
+            exchange(&mut fixture),
+            Err(EscrowError::MarketPaused.into())
+        );
+        fixture.keys.pop();
+        fixture.accounts.pop();
+
+        set_paused(&mut config_account, &operator, false)
+            .expect("error: process_set_market_paused()");
+        fixture.push(market_config_pubkey, config_account);
+        fixture.push(
+            Pubkey::new_unique(),
+            packed_token_account(&ExchangeFixture::RECEIVE_MINT, &fee_owner, 0),
+        );
+        fixture.push(fixture.mint_volume_pubkey(), SolanaAccount::default());
+        exchange(&mut fixture).expect("error: process_exchange()");
+        // the market's fee went to the market's fee owner
+        assert_eq!(fixture.token_account(fixture.keys.len() - 2).amount, 2);
+    }
+
+    #[test]
+    fn test_set_admin() {
+        let program_id = Pubkey::new_unique();
+        test_syscall_stubs(&program_id);
+        let admin_pubkey = Pubkey::new_unique();
+        let (treasury, _bump_seed) =
+            governance::native_treasury_address(&Pubkey::new_unique(), &governance::id());
+        let (config_pubkey, _bump_seed) = Config::find_address(&program_id);
+        let mut config_account = SolanaAccount::new(0, Config::LEN, &program_id);
+        Config {
+            is_initialized: true,
+            admin: admin_pubkey,
+            ..Config::default()
+        }
+        .pack_into_slice(&mut config_account.data);
 
-        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &escrow_program_id); // temp_token_account owner pubkey
+        let mut admin_account = SolanaAccount::default();
+        let mut treasury_account = SolanaAccount::default();
+        let mut accounts = [
+            (&admin_pubkey, true, &mut admin_account),
+            (&config_pubkey, false, &mut config_account),
+            (&treasury, false, &mut treasury_account),
+        ];
+        let accounts = create_is_signer_account_infos(&mut accounts);
+        Processor::process_set_admin(&accounts, &program_id).expect("error: process_set_admin()");
+        // the old admin no longer passes, the treasury does once a proposal signs for it
+        assert_eq!(
+            Processor::process_set_lamport_fee(&accounts[..2], 5_000, &program_id),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        let mut accounts = [
+            (&treasury, true, &mut treasury_account),
+            (&config_pubkey, false, &mut config_account),
+        ];
+        let accounts = create_is_signer_account_infos(&mut accounts);
+        Processor::process_set_lamport_fee(&accounts, 5_000, &program_id)
+            .expect("error: process_set_lamport_fee()");
+        assert_eq!(
+            Config::unpack(&config_account.data).unwrap().admin,
+            treasury
+        );
+    }
 
-        let amount = 123;
+    #[test]
+    fn test_mint_list() {
+        let program_id = Pubkey::new_unique();
+        test_syscall_stubs(&program_id);
+        let admin_pubkey = Pubkey::new_unique();
+        let scam_mint = Pubkey::new_unique();
+        let usdc_mint = Pubkey::new_unique();
+        let (config_pubkey, _bump_seed) = Config::find_address(&program_id);
+        let mut config_account = SolanaAccount::new(0, Config::LEN, &program_id);
+        Config {
+            is_initialized: true,
+            admin: admin_pubkey,
+            ..Config::default()
+        }
+        .pack_into_slice(&mut config_account.data);
+        let (mint_list_pubkey, _bump_seed) = MintList::find_address(&program_id);
+        let mut mint_list_account = SolanaAccount::new(0, MintList::LEN, &program_id);
+        MintList {
+            is_initialized: true,
+            ..MintList::default()
+        }
+        .pack_into_slice(&mut mint_list_account.data);
 
-        // setup escrow account
-        let mut escrow_account = SolanaAccount {
-            owner: pda,
-            data: vec![0; Escrow::get_packed_len()],
-            ..SolanaAccount::default()
+        let mut admin_account = SolanaAccount::default();
+        let mut mint_account = SolanaAccount::new(0, Mint::LEN, &spl_token::id());
+        Mint {
+            is_initialized: true,
+            ..Mint::default()
+        }
+        .pack_into_slice(&mut mint_account.data);
+        let mut set_listed = |signer, mint, listed| {
+            let mut accounts = [
+                (signer, true, &mut admin_account),
+                (&config_pubkey, false, &mut config_account),
+                (&mint_list_pubkey, false, &mut mint_list_account),
+                (mint, false, &mut mint_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_set_mint_listed(&accounts, listed, &program_id)
         };
-        Escrow {
+        let stranger = Pubkey::new_unique();
+        assert_eq!(
+            set_listed(&stranger, &scam_mint, true),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        set_listed(&admin_pubkey, &scam_mint, true).expect("error: process_set_mint_listed()");
+        set_listed(&admin_pubkey, &usdc_mint, true).expect("error: process_set_mint_listed()");
+        set_listed(&admin_pubkey, &usdc_mint, false).expect("error: process_set_mint_listed()");
+        let mint_list = MintList::unpack(&mint_list_account.data).unwrap();
+        assert_eq!(mint_list.mints(), &[scam_mint]);
+
+        let mut full_list = MintList::default();
+        for _ in 0..MAX_LISTED_MINTS {
+            assert!(full_list.set_listed(Pubkey::new_unique(), true));
+        }
+        assert!(!full_list.set_listed(Pubkey::new_unique(), true));
+
+        let init_escrow = |mint_list_account: &mut SolanaAccount, deposit_mint, receive_mint| {
+            let initializer_pubkey = Pubkey::new_unique();
+            let rent = Rent::default();
+            let mut rent_sysvar = create_account_for_test(&rent);
+            let escrow_len = Escrow::get_packed_len();
+            let mut escrow_account =
+                SolanaAccount::new(rent.minimum_balance(escrow_len), escrow_len, &program_id);
+            let mut accounts = [
+                (&initializer_pubkey, true, &mut SolanaAccount::default()),
+                (
+                    &Pubkey::new_unique(),
+                    false,
+                    &mut packed_token_account(&deposit_mint, &initializer_pubkey, 100),
+                ),
+                (
+                    &Pubkey::new_unique(),
+                    false,
+                    &mut packed_token_account(&receive_mint, &initializer_pubkey, 0),
+                ),
+                (&Pubkey::new_unique(), false, &mut escrow_account),
+                (&sysvar::rent::id(), false, &mut rent_sysvar),
+                (&spl_token::id(), false, &mut SolanaAccount::default()),
+                (&mint_list_pubkey, false, mint_list_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_init_escrow(&accounts, 100, &EscrowTerms::default(), &program_id)
+        };
+
+        // banned mints can not be deposited nor asked for
+        let other_mint = Pubkey::new_unique();
+        for (deposit_mint, receive_mint) in
+            [(scam_mint, other_mint), (other_mint, scam_mint)].iter()
+        {
+            assert_eq!(
+                init_escrow(&mut mint_list_account, *deposit_mint, *receive_mint),
+                Err(EscrowError::MintNotAllowed.into())
+            );
+        }
+        init_escrow(&mut mint_list_account, usdc_mint, other_mint)
+            .expect("error: process_init_escrow()");
+
+        // an allow list only lets its own mints through
+        let mut allow_list = MintList {
             is_initialized: true,
-            initializer_pubkey,
-            temp_token_account_pubkey: pdas_temp_token_pubkey,
-            initializer_token_to_receive_account_pubkey,
-            expected_amount: amount,
+            is_allow_list: true,
+            ..MintList::default()
+        };
+        allow_list.set_listed(usdc_mint, true);
+        allow_list.set_listed(other_mint, true);
+        allow_list.pack_into_slice(&mut mint_list_account.data);
+        init_escrow(&mut mint_list_account, usdc_mint, other_mint)
+            .expect("error: process_init_escrow()");
+        assert_eq!(
+            init_escrow(&mut mint_list_account, usdc_mint, Pubkey::new_unique()),
+            Err(EscrowError::MintNotAllowed.into())
+        );
+
+        // dust deposits are refused once the admin sets a minimum
+        for (min_deposit_amount, result) in [
+            (101, Err(EscrowError::EscrowTooSmall.into())),
+            (100, Ok(())),
+        ]
+        .iter()
+        {
+            let mut accounts = [
+                (&admin_pubkey, true, &mut admin_account),
+                (&config_pubkey, false, &mut config_account),
+                (&mint_list_pubkey, false, &mut mint_list_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_set_min_deposit(&accounts, *min_deposit_amount, &program_id)
+                .expect("error: process_set_min_deposit()");
+            assert_eq!(
+                init_escrow(&mut mint_list_account, usdc_mint, other_mint),
+                *result
+            );
         }
-        .pack_into_slice(&mut escrow_account.data);
 
-        // temp_token_account (account that ownership was set in  initialization)
-        let mut pdas_temp_token_account = SolanaAccount {
-            owner: pda,
-            data: vec![0; TokenAccount::get_packed_len()],
-            ..SolanaAccount::default()
+        // a mint's own minimum bounds both the deposit and the expected amount in it
+        for (mint, min_amount, result) in [
+            (&usdc_mint, 101, Err(EscrowError::EscrowTooSmall.into())),
+            (&usdc_mint, 100, Ok(())),
+            (&other_mint, 101, Err(EscrowError::EscrowTooSmall.into())),
+        ]
+        .iter()
+        {
+            let mut accounts = [
+                (&admin_pubkey, true, &mut admin_account),
+                (&config_pubkey, false, &mut config_account),
+                (&mint_list_pubkey, false, &mut mint_list_account),
+                (*mint, false, &mut mint_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_set_mint_minimum(&accounts, *min_amount, &program_id)
+                .expect("error: process_set_mint_minimum()");
+            assert_eq!(
+                init_escrow(&mut mint_list_account, usdc_mint, other_mint),
+                *result
+            );
+        }
+        let mint_list = MintList::unpack(&mint_list_account.data).unwrap();
+        assert_eq!(mint_list.minimums().len(), 2);
+        assert_eq!(mint_list.min_deposit(&other_mint), 101);
+        assert_eq!(mint_list.min_deposit(&Pubkey::new_unique()), 100);
+        assert_eq!(mint_list.min_expected(&Pubkey::new_unique()), 0);
+    }
+
+    #[test]
+    fn test_mint_volume() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 100,
+                curve_start_price: 1,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        let mint_volume_pubkey = fixture.mint_volume_pubkey();
+        let mut mint_volume_account = SolanaAccount::new(0, MintVolume::LEN, &program_id);
+        MintVolume {
+            is_initialized: true,
+            filled_amount: u64::MAX as u128,
+            ..MintVolume::default()
+        }
+        .pack_into_slice(&mut mint_volume_account.data);
+        fixture.push(
+            Config::find_address(&program_id).0,
+            SolanaAccount::default(),
+        );
+
+        // the volume account must be the deposited mint's
+        fixture.push(Pubkey::new_unique(), mint_volume_account.clone());
+        assert_eq!(
+            Processor::process_exchange(&fixture.account_infos(), 40, &program_id),
+            Err(ProgramError::InvalidSeeds)
+        );
+        fixture.keys.pop();
+        fixture.accounts.pop();
+
+        fixture.push(mint_volume_pubkey, mint_volume_account);
+        for fill_amount in [40, 60].iter() {
+            Processor::process_exchange(&fixture.account_infos(), *fill_amount, &program_id)
+                .expect("error: process_exchange()");
+        }
+        let mint_volume = MintVolume::unpack(&fixture.accounts.last().unwrap().data).unwrap();
+        // the running total outgrows a u64
+        assert_eq!(mint_volume.filled_amount, u64::MAX as u128 + 100);
+        assert_eq!(mint_volume.fill_count, 2);
+    }
+
+    #[test]
+    fn test_timelocked_exchange() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                release_after_timestamp: 1_000,
+                release_before_timestamp: 2_000,
+                ..Escrow::default()
+            },
+            100,
+        );
+        const CLOCK: usize = 9;
+        fixture.push(sysvar::clock::id(), SolanaAccount::default());
+        let exchange_at = |fixture: &mut ExchangeFixture, unix_timestamp| {
+            fixture.accounts[CLOCK] = create_account_for_test(&Clock {
+                unix_timestamp,
+                ..Clock::default()
+            });
+            fixture.exchange(100)
         };
-        TokenAccount {
-            amount,
-            state: spl_token::state::AccountState::Initialized,
-            ..TokenAccount::default()
+
+        assert_eq!(
+            exchange_at(&mut fixture, 999),
+            Err(EscrowError::EscrowNotReleased.into())
+        );
+        assert_eq!(
+            exchange_at(&mut fixture, 2_000),
+            Err(EscrowError::ReleaseClosed.into())
+        );
+        exchange_at(&mut fixture, 1_000).expect("error: Exchange");
+        assert_eq!(
+            fixture
+                .token_account(ExchangeFixture::INITIALIZER_RECEIVING)
+                .amount,
+            150
+        );
+    }
+
+    #[test]
+    fn test_reclaim_expired() {
+        let escrow = Escrow {
+            expected_amount: 150,
+            expires_at_slot: 100,
+            ..Escrow::default()
+        };
+        let mut fixture = ExchangeFixture::new(escrow, 100);
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        let clock_at = |slot| {
+            create_account_for_test(&Clock {
+                slot,
+                ..Clock::default()
+            })
+        };
+        fixture.push(sysvar::clock::id(), clock_at(100));
+        assert_eq!(
+            fixture.exchange(100),
+            Err(EscrowError::EscrowExpired.into())
+        );
+
+        let initializer_pubkey = fixture.keys[ExchangeFixture::INITIALIZER];
+        let token_account = |owner| packed_token_account(&ExchangeFixture::DEPOSIT_MINT, &owner, 0);
+        const CLOCK: usize = 9;
+        const DESTINATION: usize = 10;
+        fixture.push(Pubkey::new_unique(), token_account(initializer_pubkey));
+        // the taker's signing account stands in for the third party reclaiming the escrow
+        let reclaim = |fixture: &mut ExchangeFixture| {
+            let account_infos = fixture.account_infos();
+            let accounts = [
+                ExchangeFixture::TAKER,
+                ExchangeFixture::VAULT,
+                DESTINATION,
+                ExchangeFixture::INITIALIZER,
+                ExchangeFixture::ESCROW,
+                7,
+                8,
+                CLOCK,
+            ]
+            .iter()
+            .map(|i| account_infos[*i].clone())
+            .collect::<Vec<_>>();
+            Processor::process_reclaim_expired(&accounts, &program_id)
+        };
+
+        fixture.accounts[CLOCK] = clock_at(99);
+        assert_eq!(
+            reclaim(&mut fixture),
+            Err(EscrowError::EscrowNotExpired.into())
+        );
+        fixture.accounts[CLOCK] = clock_at(100);
+        // a third party can not send the deposit to themselves
+        fixture.accounts[DESTINATION] = token_account(fixture.keys[ExchangeFixture::TAKER]);
+        assert_eq!(reclaim(&mut fixture), Err(ProgramError::InvalidAccountData));
+        fixture.accounts[DESTINATION] = token_account(initializer_pubkey);
+        // nor have a lookalike token program or PDA account signed for
+        let (token_program, pda) = (fixture.keys[7], fixture.keys[8]);
+        fixture.keys[7] = Pubkey::new_unique();
+        assert_eq!(reclaim(&mut fixture), Err(ProgramError::IncorrectProgramId));
+        fixture.keys[7] = token_program;
+        fixture.keys[8] = Pubkey::new_unique();
+        assert_eq!(reclaim(&mut fixture), Err(ProgramError::InvalidSeeds));
+        fixture.keys[8] = pda;
+        reclaim(&mut fixture).expect("error: process_reclaim_expired()");
+        assert_eq!(
+            fixture.accounts[ExchangeFixture::TAKER].lamports,
+            RECLAIM_BOUNTY_LAMPORTS
+        );
+        assert_eq!(fixture.accounts[ExchangeFixture::ESCROW].lamports, 0);
+        assert_eq!(
+            fixture.accounts[ExchangeFixture::INITIALIZER].lamports,
+            ExchangeFixture::ESCROW_LAMPORTS - RECLAIM_BOUNTY_LAMPORTS
+        );
+        assert_eq!(fixture.token_account(DESTINATION).amount, 100);
+    }
+
+    #[test]
+    fn test_process_expiry() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                expires_at_slot: 100,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        const CLOCK: usize = 9;
+        fixture.push(sysvar::clock::id(), SolanaAccount::default());
+        // none of these accounts signs
+        let process_expiry = |fixture: &mut ExchangeFixture, slot| {
+            fixture.accounts[CLOCK] = create_account_for_test(&Clock {
+                slot,
+                ..Clock::default()
+            });
+            let account_infos = fixture.account_infos();
+            let accounts = [
+                ExchangeFixture::VAULT,
+                ExchangeFixture::INITIALIZER,
+                ExchangeFixture::ESCROW,
+                7,
+                8,
+                CLOCK,
+            ]
+            .iter()
+            .map(|i| account_infos[*i].clone())
+            .collect::<Vec<_>>();
+            Processor::process_expiry(&accounts, &program_id)
+        };
+
+        assert_eq!(
+            process_expiry(&mut fixture, 99),
+            Err(EscrowError::EscrowNotExpired.into())
+        );
+        // the PDA only signs for the token program, through the PDA account
+        let (token_program, pda) = (fixture.keys[7], fixture.keys[8]);
+        fixture.keys[7] = Pubkey::new_unique();
+        assert_eq!(
+            process_expiry(&mut fixture, 100),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        fixture.keys[7] = token_program;
+        fixture.keys[8] = Pubkey::new_unique();
+        assert_eq!(
+            process_expiry(&mut fixture, 100),
+            Err(ProgramError::InvalidSeeds)
+        );
+        fixture.keys[8] = pda;
+        process_expiry(&mut fixture, 100).expect("error: process_expiry()");
+        assert_eq!(fixture.accounts[ExchangeFixture::ESCROW].lamports, 0);
+        assert_eq!(
+            fixture.accounts[ExchangeFixture::INITIALIZER].lamports,
+            ExchangeFixture::ESCROW_LAMPORTS
+        );
+        assert_eq!(
+            process_expiry(&mut fixture, 100),
+            Err(ProgramError::UninitializedAccount)
+        );
+    }
+
+    #[test]
+    fn test_templates() {
+        let program_id = Pubkey::new_unique();
+        test_syscall_stubs(&program_id);
+        let owner_pubkey = Pubkey::new_unique();
+        let taker_pubkey = Pubkey::new_unique();
+        let deposit_mint = Pubkey::new_unique();
+        let receive_mint = Pubkey::new_unique();
+        let rent = Rent::default();
+        let mut rent_sysvar = create_account_for_test(&rent);
+        let mut template_account = SolanaAccount::new(
+            rent.minimum_balance(Template::LEN),
+            Template::LEN,
+            &program_id,
+        );
+        let mut mint_account = SolanaAccount::new(0, Mint::LEN, &spl_token::id());
+        Mint {
+            is_initialized: true,
+            ..Mint::default()
         }
-        .pack_into_slice(&mut pdas_temp_token_account.data);
+        .pack_into_slice(&mut mint_account.data);
+        let mut receive_mint_account = mint_account.clone();
 
-        let mut taker_account = SolanaAccount::default();
-        let mut taker_token_send_account = SolanaAccount::default();
-        let mut taker_token_receive_account = SolanaAccount::default();
-        let mut initializer_account = SolanaAccount::default();
-        let mut initializer_token_receive_account = SolanaAccount::default();
+        let mut accounts = [
+            (&owner_pubkey, true, &mut SolanaAccount::default()),
+            (&Pubkey::new_unique(), false, &mut template_account),
+            (&deposit_mint, false, &mut mint_account),
+            (&receive_mint, false, &mut receive_mint_account),
+            (&sysvar::rent::id(), false, &mut rent_sysvar),
+        ];
+        let accounts = create_is_signer_account_infos(&mut accounts);
+        // one receive base unit for every two deposited, 5 * 10^-1
+        let init_template = |accounts: &[AccountInfo]| {
+            Processor::process_init_template(accounts, 5, -1, 50, taker_pubkey, &program_id)
+        };
+        init_template(&accounts).expect("error: process_init_template()");
+        assert_eq!(
+            init_template(&accounts),
+            Err(ProgramError::AccountAlreadyInitialized)
+        );
+        drop(accounts);
 
-        let mut token_account = SolanaAccount::default();
-        let mut pda_temp_account = SolanaAccount::default(); // temp_token_account owner
+        let escrow_len = Escrow::get_packed_len();
+        let mut escrow_account =
+            SolanaAccount::new(rent.minimum_balance(escrow_len), escrow_len, &program_id);
+        let mut init_from_template = |initializer, asked_mint| {
+            let mut clock_sysvar = create_account_for_test(&Clock {
+                slot: 1_000,
+                ..Clock::default()
+            });
+            let mut accounts = [
+                (initializer, true, &mut SolanaAccount::default()),
+                (
+                    &Pubkey::new_unique(),
+                    false,
+                    &mut packed_token_account(&deposit_mint, initializer, 101),
+                ),
+                (
+                    &Pubkey::new_unique(),
+                    false,
+                    &mut packed_token_account(&asked_mint, initializer, 0),
+                ),
+                (&Pubkey::new_unique(), false, &mut escrow_account),
+                (&sysvar::rent::id(), false, &mut rent_sysvar),
+                (&spl_token::id(), false, &mut SolanaAccount::default()),
+                (
+                    &MintList::find_address(&program_id).0,
+                    false,
+                    &mut SolanaAccount::default(),
+                ),
+                (&Pubkey::new_unique(), false, &mut template_account),
+                (&sysvar::clock::id(), false, &mut clock_sysvar),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_init_from_template(&accounts, &program_id)
+        };
+
+        assert_eq!(
+            init_from_template(&taker_pubkey, receive_mint),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            init_from_template(&owner_pubkey, Pubkey::new_unique()),
+            Err(ProgramError::InvalidAccountData)
+        );
+        init_from_template(&owner_pubkey, receive_mint)
+            .expect("error: process_init_from_template()");
+        let escrow_info = Escrow::unpack(&escrow_account.data).unwrap();
+        // the price rounds up in favour of the initializer
+        assert_eq!(escrow_info.expected_amount, 51);
+        assert_eq!(escrow_info.expires_at_slot, 1_050);
+        assert_eq!(escrow_info.allowed_taker, taker_pubkey);
+
+        let mut fixture = ExchangeFixture::new(escrow_info, 101);
+        assert_eq!(
+            fixture.exchange(101),
+            Err(EscrowError::TakerNotAllowed.into())
+        );
+    }
+
+    #[test]
+    fn test_exchange_relists() {
+        let relist_source_pubkey = Pubkey::new_unique();
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                relist_amount: 100,
+                relist_source_pubkey,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        let pda = fixture.keys[8];
+        let initializer_pubkey = fixture.keys[ExchangeFixture::INITIALIZER];
+        fixture.push(
+            Config::find_address(&program_id).0,
+            SolanaAccount::default(),
+        );
+        fixture.push(fixture.mint_volume_pubkey(), SolanaAccount::default());
+        let relist_source = |delegated_amount| {
+            let mut account =
+                SolanaAccount::new(0, TokenAccount::get_packed_len(), &spl_token::id());
+            TokenAccount {
+                mint: ExchangeFixture::DEPOSIT_MINT,
+                owner: initializer_pubkey,
+                amount: 1_000,
+                delegate: COption::Some(pda),
+                delegated_amount,
+                state: spl_token::state::AccountState::Initialized,
+                ..TokenAccount::default()
+            }
+            .pack_into_slice(&mut account.data);
+            account
+        };
+        let exchange = |fixture: &mut ExchangeFixture| {
+            Processor::process_exchange(&fixture.account_infos(), 100, &program_id)
+        };
+
+        fixture.push(Pubkey::new_unique(), relist_source(200));
+        assert_eq!(
+            fixture.atomically(exchange),
+            Err(ProgramError::InvalidAccountData)
+        );
+        fixture.keys.pop();
+        fixture.accounts.pop();
+
+        fixture.push(relist_source_pubkey, relist_source(200));
+        // the escrow stays open, funded again out of the allowance
+        exchange(&mut fixture).expect("error: process_exchange()");
+        assert_eq!(
+            fixture.accounts[ExchangeFixture::ESCROW].lamports,
+            ExchangeFixture::ESCROW_LAMPORTS
+        );
+        assert_eq!(fixture.escrow().filled_amount, 0);
+        assert_eq!(fixture.accounts[ExchangeFixture::TAKER].lamports, 0);
+        assert_eq!(fixture.token_account(ExchangeFixture::VAULT).amount, 100);
+        assert_eq!(
+            fixture
+                .token_account(ExchangeFixture::TAKER_RECEIVING)
+                .amount,
+            100
+        );
+
+        // once the allowance runs out the fill closes the escrow
+        *fixture.accounts.last_mut().unwrap() = relist_source(99);
+        exchange(&mut fixture).expect("error: process_exchange()");
+        assert_eq!(fixture.accounts[ExchangeFixture::ESCROW].lamports, 0);
+        assert_eq!(
+            fixture
+                .token_account(ExchangeFixture::TAKER_RECEIVING)
+                .amount,
+            200
+        );
+    }
+
+    #[test]
+    fn test_sealed_bid_auction() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                auction_bid_end_slot: 100,
+                auction_reveal_end_slot: 200,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        let pda = fixture.keys[8];
+        let bidder_pubkey = fixture.keys[ExchangeFixture::TAKER];
+        assert_eq!(
+            fixture.exchange(100),
+            Err(EscrowError::AuctionEscrow.into())
+        );
+
+        const BID: usize = 9;
+        const DEPOSIT: usize = 10;
+        const CLOCK: usize = 11;
+        const WINNERS_RECEIVE: usize = 12;
+        let salt = [7; 32];
+        let escrow_pubkey = fixture.keys[ExchangeFixture::ESCROW];
+        let mut bid_account = SolanaAccount::new(2_000, Bid::get_packed_len(), &program_id);
+        Bid {
+            is_initialized: true,
+            escrow: escrow_pubkey,
+            bidder: bidder_pubkey,
+            commitment: Bid::commitment(200, &salt, &bidder_pubkey),
+            deposit_vault_pubkey: Pubkey::new_unique(),
+            reveal_end_slot: 200,
+            ..Bid::default()
+        }
+        .pack_into_slice(&mut bid_account.data);
+        let deposit_vault_pubkey = Bid::unpack(&bid_account.data).unwrap().deposit_vault_pubkey;
+        fixture.push(
+            Bid::find_address(&escrow_pubkey, &bidder_pubkey, &program_id).0,
+            bid_account,
+        );
+        fixture.push(
+            deposit_vault_pubkey,
+            packed_token_account(&ExchangeFixture::RECEIVE_MINT, &pda, 300),
+        );
+        fixture.push(sysvar::clock::id(), SolanaAccount::default());
+        fixture.push(
+            Pubkey::new_unique(),
+            packed_token_account(&ExchangeFixture::DEPOSIT_MINT, &bidder_pubkey, 0),
+        );
+        fixture.push(
+            Config::find_address(&program_id).0,
+            SolanaAccount::default(),
+        );
+        fixture.push(fixture.mint_volume_pubkey(), SolanaAccount::default());
+        let run = |fixture: &mut ExchangeFixture, slot, indices: &[usize], amount| {
+            fixture.accounts[CLOCK] = create_account_for_test(&Clock {
+                slot,
+                ..Clock::default()
+            });
+            let account_infos = fixture.account_infos();
+            let accounts = indices
+                .iter()
+                .map(|i| account_infos[*i].clone())
+                .collect::<Vec<_>>();
+            match amount {
+                Some(amount) => {
+                    Processor::process_reveal_bid(&accounts, amount, &salt, &program_id)
+                }
+                None if indices[0] == ExchangeFixture::INITIALIZER => {
+                    Processor::process_settle_auction(&accounts, &program_id)
+                }
+                None => Processor::process_refund_bid(&accounts, &program_id),
+            }
+        };
+        let reveal = [
+            ExchangeFixture::TAKER,
+            ExchangeFixture::ESCROW,
+            BID,
+            DEPOSIT,
+            CLOCK,
+        ];
+        let settle = [
+            ExchangeFixture::INITIALIZER,
+            ExchangeFixture::VAULT,
+            5,
+            ExchangeFixture::ESCROW,
+            7,
+            8,
+            CLOCK,
+            BID,
+            DEPOSIT,
+            WINNERS_RECEIVE,
+            ExchangeFixture::TAKER,
+            13,
+            14,
+        ];
+        let refund = [
+            ExchangeFixture::TAKER,
+            BID,
+            DEPOSIT,
+            ExchangeFixture::ESCROW,
+            7,
+            8,
+            CLOCK,
+        ];
+
+        assert_eq!(
+            run(&mut fixture, 99, &reveal, Some(200)),
+            Err(EscrowError::AuctionPhase.into())
+        );
+        assert_eq!(
+            run(&mut fixture, 100, &reveal, Some(201)),
+            Err(EscrowError::CommitmentMismatch.into())
+        );
+        run(&mut fixture, 100, &reveal, Some(200)).expect("error: process_reveal_bid()");
+        assert_eq!(fixture.escrow().best_bid_amount, 200);
+        assert_eq!(fixture.escrow().best_bid_pubkey, fixture.keys[BID]);
+        assert_eq!(
+            run(&mut fixture, 100, &reveal, Some(200)),
+            Err(EscrowError::AuctionPhase.into())
+        );
+
+        assert_eq!(
+            run(&mut fixture, 199, &settle, None),
+            Err(EscrowError::AuctionPhase.into())
+        );
+        // the winning bid is only paid out by settling
+        assert_eq!(
+            run(&mut fixture, 200, &refund, None),
+            Err(EscrowError::AuctionPhase.into())
+        );
+        // nor through a lookalike token program or PDA account
+        let token_program = fixture.keys[7];
+        fixture.keys[7] = Pubkey::new_unique();
+        assert_eq!(
+            run(&mut fixture, 200, &settle, None),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        fixture.keys[7] = token_program;
+        fixture.keys[8] = Pubkey::new_unique();
+        assert_eq!(
+            run(&mut fixture, 200, &settle, None),
+            Err(ProgramError::InvalidSeeds)
+        );
+        fixture.keys[8] = pda;
+        run(&mut fixture, 200, &settle, None).expect("error: process_settle_auction()");
+        assert_eq!(fixture.accounts[ExchangeFixture::ESCROW].lamports, 0);
+        assert_eq!(fixture.accounts[BID].lamports, 0);
+        assert_eq!(fixture.accounts[ExchangeFixture::TAKER].lamports, 2_000);
+        assert_eq!(
+            fixture.accounts[ExchangeFixture::INITIALIZER].lamports,
+            ExchangeFixture::ESCROW_LAMPORTS
+        );
+        assert_eq!(fixture.token_account(WINNERS_RECEIVE).amount, 100);
+        assert_eq!(
+            fixture
+                .token_account(ExchangeFixture::INITIALIZER_RECEIVING)
+                .amount,
+            200
+        );
+        // the winner gets their deposit vault back with what they put in above the bid
+        let deposit_vault = fixture.token_account(DEPOSIT);
+        assert_eq!(deposit_vault.owner, bidder_pubkey);
+        assert_eq!(deposit_vault.amount, 100);
+    }
+
+    #[test]
+    fn test_raffle() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 10,
+                raffle_end_slot: 100,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        let buyer_pubkey = fixture.keys[ExchangeFixture::TAKER];
+        let escrow_pubkey = fixture.keys[ExchangeFixture::ESCROW];
+        assert_eq!(fixture.exchange(100), Err(EscrowError::RaffleEscrow.into()));
+
+        const TICKET: usize = 9;
+        const CLOCK: usize = 11;
+        // the stubbed system program leaves the ticket account as created here
+        fixture.push(
+            Ticket::find_address(&escrow_pubkey, 0, &program_id).0,
+            SolanaAccount::new(1_500, Ticket::get_packed_len(), &program_id),
+        );
+        fixture.push(
+            Pubkey::new_unique(),
+            packed_token_account(&ExchangeFixture::RECEIVE_MINT, &buyer_pubkey, 100),
+        );
+        fixture.push(sysvar::clock::id(), SolanaAccount::default());
+        fixture.push(
+            sysvar::rent::id(),
+            create_account_for_test(&Rent::default()),
+        );
+        fixture.push(Pubkey::default(), SolanaAccount::default());
+        fixture.push(
+            Config::find_address(&program_id).0,
+            SolanaAccount::default(),
+        );
+        let mut slot_hashes = SolanaAccount::new(0, 48, &sysvar::id());
+        slot_hashes.data[..8].copy_from_slice(&1u64.to_le_bytes());
+        slot_hashes.data[16..].copy_from_slice(&[3; 32]);
+        fixture.push(sysvar::slot_hashes::id(), slot_hashes);
+        fixture.push(
+            Pubkey::new_unique(),
+            packed_token_account(&ExchangeFixture::DEPOSIT_MINT, &buyer_pubkey, 0),
+        );
+        fixture.push(fixture.mint_volume_pubkey(), SolanaAccount::default());
+        let run = |fixture: &mut ExchangeFixture, slot, indices: &[usize]| {
+            fixture.accounts[CLOCK] = create_account_for_test(&Clock {
+                slot,
+                ..Clock::default()
+            });
+            let account_infos = fixture.account_infos();
+            let accounts = indices
+                .iter()
+                .map(|i| account_infos[*i].clone())
+                .collect::<Vec<_>>();
+            match accounts.len() {
+                3 => Processor::process_close_ticket(&accounts, &program_id),
+                10 if indices[0] == ExchangeFixture::TAKER => {
+                    Processor::process_buy_ticket(&accounts, &program_id)
+                }
+                _ => Processor::process_draw_raffle(&accounts, &program_id),
+            }
+        };
+        let buy = [
+            ExchangeFixture::TAKER,
+            10,
+            ExchangeFixture::ESCROW,
+            5,
+            TICKET,
+            7,
+            CLOCK,
+            12,
+            13,
+            14,
+        ];
+        let draw = [
+            ExchangeFixture::INITIALIZER,
+            ExchangeFixture::VAULT,
+            ExchangeFixture::ESCROW,
+            7,
+            8,
+            CLOCK,
+            15,
+            TICKET,
+            16,
+            17,
+        ];
+        let close = [ExchangeFixture::TAKER, TICKET, ExchangeFixture::ESCROW];
+
+        assert_eq!(
+            run(&mut fixture, 100, &buy),
+            Err(EscrowError::RafflePhase.into())
+        );
+        run(&mut fixture, 99, &buy).expect("error: process_buy_ticket()");
+        assert_eq!(fixture.escrow().raffle_ticket_count, 1);
+        assert_eq!(
+            fixture
+                .token_account(ExchangeFixture::INITIALIZER_RECEIVING)
+                .amount,
+            10
+        );
+        assert_eq!(
+            Ticket::unpack(&fixture.accounts[TICKET].data).unwrap(),
+            Ticket {
+                is_initialized: true,
+                escrow: escrow_pubkey,
+                buyer: buyer_pubkey,
+                index: 0,
+            }
+        );
+        // the next ticket lives at the next index
+        assert_eq!(run(&mut fixture, 99, &buy), Err(ProgramError::InvalidSeeds));
+
+        assert_eq!(
+            run(&mut fixture, 99, &draw),
+            Err(EscrowError::RafflePhase.into())
+        );
+        assert_eq!(
+            run(&mut fixture, 100, &close),
+            Err(EscrowError::RafflePhase.into())
+        );
+        // the PDA only signs for the token program and PDA account the escrow expects
+        let (token_program, pda) = (fixture.keys[7], fixture.keys[8]);
+        fixture.keys[7] = Pubkey::new_unique();
+        assert_eq!(
+            run(&mut fixture, 100, &draw),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        fixture.keys[7] = token_program;
+        fixture.keys[8] = Pubkey::new_unique();
+        assert_eq!(
+            run(&mut fixture, 100, &draw),
+            Err(ProgramError::InvalidSeeds)
+        );
+        fixture.keys[8] = pda;
+        run(&mut fixture, 100, &draw).expect("error: process_draw_raffle()");
+        // the only ticket wins the deposit
+        assert_eq!(fixture.token_account(16).amount, 100);
+        assert_eq!(fixture.accounts[ExchangeFixture::ESCROW].lamports, 0);
+        assert_eq!(
+            fixture.accounts[ExchangeFixture::INITIALIZER].lamports,
+            ExchangeFixture::ESCROW_LAMPORTS
+        );
+        run(&mut fixture, 100, &close).expect("error: process_close_ticket()");
+        assert_eq!(fixture.accounts[ExchangeFixture::TAKER].lamports, 1_500);
+    }
 
+    #[test]
+    fn test_request_for_quote() {
+        let program_id = Pubkey::new_unique();
+        test_syscall_stubs(&program_id);
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+        let payment_mint = Pubkey::new_unique();
+        let wanted_mint = Pubkey::new_unique();
+        let token_account = |mint, owner, amount| {
+            let mut account =
+                SolanaAccount::new(0, TokenAccount::get_packed_len(), &spl_token::id());
+            TokenAccount {
+                mint,
+                owner,
+                amount,
+                state: spl_token::state::AccountState::Initialized,
+                ..TokenAccount::default()
+            }
+            .pack_into_slice(&mut account.data);
+            account
+        };
+
+        let buyer_pubkey = Pubkey::new_unique();
+        let maker_pubkey = Pubkey::new_unique();
+        let payment_vault_pubkey = Pubkey::new_unique();
+        let buyers_receive_pubkey = Pubkey::new_unique();
+        let request_pubkey = Pubkey::new_unique();
+        let (quote_pubkey, _bump_seed) =
+            Quote::find_address(&request_pubkey, &maker_pubkey, &program_id);
+        let deposit_vault_pubkey = Pubkey::new_unique();
+        let makers_receive_pubkey = Pubkey::new_unique();
+        let config_pubkey = Config::find_address(&program_id).0;
+        let mint_volume_pubkey = MintVolume::find_address(&wanted_mint, &program_id).0;
+
+        let mut buyer_account = SolanaAccount::default();
+        let mut payment_vault = token_account(payment_mint, pda, 100);
+        let mut buyers_receive_account = token_account(wanted_mint, buyer_pubkey, 0);
+        let mut request_account = SolanaAccount::new(3_000, Request::get_packed_len(), &program_id);
+        Request {
+            is_initialized: true,
+            buyer: buyer_pubkey,
+            payment_vault_pubkey,
+            buyer_token_to_receive_pubkey: buyers_receive_pubkey,
+            payment_mint,
+            wanted_mint,
+            min_amount: 10,
+        }
+        .pack_into_slice(&mut request_account.data);
+        let mut quote_account = SolanaAccount::new(2_000, Quote::get_packed_len(), &program_id);
+        let mut deposit_vault = token_account(wanted_mint, maker_pubkey, 5);
+        let mut makers_receive_account = token_account(payment_mint, maker_pubkey, 0);
+        let mut maker_account = SolanaAccount::default();
+        let mut token_program_account = SolanaAccount::default();
+        let mut pda_account = SolanaAccount::default();
+        let mut rent_account = create_account_for_test(&Rent::default());
+        let mut system_program_account = SolanaAccount::default();
+        let mut config_account = SolanaAccount::default();
+        let mut mint_volume_account = SolanaAccount::default();
+
+        {
+            let mut accounts = [
+                (&maker_pubkey, true, &mut maker_account),
+                (&deposit_vault_pubkey, false, &mut deposit_vault),
+                (&makers_receive_pubkey, false, &mut makers_receive_account),
+                (&request_pubkey, false, &mut request_account),
+                (&quote_pubkey, false, &mut quote_account),
+                (&spl_token::id(), false, &mut token_program_account),
+                (&sysvar::rent::id(), false, &mut rent_account),
+                (&Pubkey::default(), false, &mut system_program_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            assert_eq!(
+                Processor::process_post_quote(&accounts, &program_id),
+                Err(EscrowError::QuoteTooLow.into())
+            );
+        }
+        deposit_vault = token_account(wanted_mint, maker_pubkey, 40);
+        {
+            let mut accounts = [
+                (&maker_pubkey, true, &mut maker_account),
+                (&deposit_vault_pubkey, false, &mut deposit_vault),
+                (&makers_receive_pubkey, false, &mut makers_receive_account),
+                (&request_pubkey, false, &mut request_account),
+                (&quote_pubkey, false, &mut quote_account),
+                (&spl_token::id(), false, &mut token_program_account),
+                (&sysvar::rent::id(), false, &mut rent_account),
+                (&Pubkey::default(), false, &mut system_program_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_post_quote(&accounts, &program_id)
+                .expect("error: process_post_quote()");
+        }
+        assert_eq!(
+            Quote::unpack(&quote_account.data).unwrap(),
+            Quote {
+                is_initialized: true,
+                request: request_pubkey,
+                maker: maker_pubkey,
+                deposit_vault_pubkey,
+                maker_token_to_receive_pubkey: makers_receive_pubkey,
+            }
+        );
+
+        // the PDA only signs for the token program, through the PDA account
+        let lookalike = Pubkey::new_unique();
+        let token_program = spl_token::id();
+        for (token_program, pda, result) in [
+            (&lookalike, &pda, Err(ProgramError::IncorrectProgramId)),
+            (&token_program, &lookalike, Err(ProgramError::InvalidSeeds)),
+            (&token_program, &pda, Ok(())),
+        ]
+        .iter()
+        {
+            let mut accounts = [
+                (&buyer_pubkey, true, &mut buyer_account),
+                (&payment_vault_pubkey, false, &mut payment_vault),
+                (&buyers_receive_pubkey, false, &mut buyers_receive_account),
+                (&request_pubkey, false, &mut request_account),
+                (&quote_pubkey, false, &mut quote_account),
+                (&deposit_vault_pubkey, false, &mut deposit_vault),
+                (&makers_receive_pubkey, false, &mut makers_receive_account),
+                (&maker_pubkey, false, &mut maker_account),
+                (token_program, false, &mut token_program_account),
+                (pda, false, &mut pda_account),
+                (&config_pubkey, false, &mut config_account),
+                (&mint_volume_pubkey, false, &mut mint_volume_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            assert_eq!(
+                Processor::process_accept_quote(&accounts, &program_id),
+                *result
+            );
+        }
+        assert_eq!(request_account.lamports, 0);
+        assert_eq!(quote_account.lamports, 0);
+        assert_eq!(buyer_account.lamports, 3_000);
+        assert_eq!(maker_account.lamports, 2_000);
+    }
+
+    #[test]
+    fn test_exchange_chains_proceeds() {
+        let chain_receive_pubkey = Pubkey::new_unique();
+        let chained = Escrow {
+            expected_amount: 150,
+            chain_expected_amount: 300,
+            chain_receive_pubkey,
+            ..Escrow::default()
+        };
+        let mut fixture = ExchangeFixture::new(chained, 100);
+        let proceeds_pubkey = fixture.keys[5];
+        fixture.push(
+            sysvar::clock::id(),
+            create_account_for_test(&Clock {
+                slot: 70,
+                ..Clock::default()
+            }),
+        );
+        fixture.exchange(100).expect("error: process_exchange()");
+        // the escrow stays open, offering what it was paid
+        assert_eq!(
+            fixture.accounts[ExchangeFixture::ESCROW].lamports,
+            ExchangeFixture::ESCROW_LAMPORTS
+        );
+        let escrow_info = fixture.escrow();
+        assert_eq!(escrow_info.temp_token_account_pubkey, proceeds_pubkey);
+        assert_eq!(
+            escrow_info.initializer_token_to_receive_account_pubkey,
+            chain_receive_pubkey
+        );
+        assert_eq!(escrow_info.expected_amount, 300);
+        assert_eq!(escrow_info.revised_at_slot, 70);
+        assert!(!escrow_info.is_chained());
+
+        // closing a chained escrow hands its proceeds vault back as well
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expires_at_slot: 100,
+                ..chained
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        fixture.push(
+            sysvar::clock::id(),
+            create_account_for_test(&Clock {
+                slot: 100,
+                ..Clock::default()
+            }),
+        );
+        let process_expiry = |fixture: &mut ExchangeFixture, indices: &[usize]| {
+            let account_infos = fixture.account_infos();
+            let accounts = indices
+                .iter()
+                .map(|i| account_infos[*i].clone())
+                .collect::<Vec<_>>();
+            Processor::process_expiry(&accounts, &program_id)
+        };
+        let accounts = [
+            ExchangeFixture::VAULT,
+            ExchangeFixture::INITIALIZER,
+            ExchangeFixture::ESCROW,
+            7,
+            8,
+            9,
+        ];
+        assert_eq!(
+            fixture.atomically(|fixture| process_expiry(fixture, &accounts)),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        process_expiry(&mut fixture, &[&accounts[..], &[5]].concat())
+            .expect("error: process_expiry()");
+        assert_eq!(fixture.accounts[ExchangeFixture::ESCROW].lamports, 0);
+    }
+
+    #[test]
+    fn test_exchange_split_recipients() {
+        let recipients = [
+            SplitRecipient {
+                token_account: Pubkey::new_unique(),
+                share_bps: 1_000,
+            },
+            SplitRecipient::default(),
+            SplitRecipient {
+                token_account: Pubkey::new_unique(),
+                share_bps: 2_500,
+            },
+            SplitRecipient::default(),
+        ];
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                split_recipients: recipients,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        fixture.push(
+            Config::find_address(&program_id).0,
+            SolanaAccount::default(),
+        );
+        fixture.push(fixture.mint_volume_pubkey(), SolanaAccount::default());
+        let exchange = |fixture: &mut ExchangeFixture| {
+            Processor::process_exchange(&fixture.account_infos(), 100, &program_id)
+        };
+
+        let recipient_account =
+            || packed_token_account(&ExchangeFixture::RECEIVE_MINT, &Pubkey::new_unique(), 0);
+        fixture.push(recipients[0].token_account, recipient_account());
+        assert_eq!(
+            exchange(&mut fixture),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        // recipients are paid in the order the escrow lists them
+        fixture.push(Pubkey::new_unique(), recipient_account());
+        assert_eq!(
+            exchange(&mut fixture),
+            Err(ProgramError::InvalidAccountData)
+        );
+        *fixture.keys.last_mut().unwrap() = recipients[2].token_account;
+        exchange(&mut fixture).expect("error: process_exchange()");
+        assert_eq!(fixture.accounts[ExchangeFixture::ESCROW].lamports, 0);
+        let recipient_shares = [fixture.keys.len() - 2, fixture.keys.len() - 1]
+            .iter()
+            .map(|i| fixture.token_account(*i).amount)
+            .collect::<Vec<_>>();
+        assert_eq!(recipient_shares, vec![15, 37]);
+        assert_eq!(
+            fixture
+                .token_account(ExchangeFixture::INITIALIZER_RECEIVING)
+                .amount,
+            150 - 15 - 37
+        );
+    }
+
+    #[test]
+    fn test_exchange_release_condition() {
+        let stake_program = Pubkey::new_unique();
+        let release_condition = ReleaseCondition {
+            account: Pubkey::new_unique(),
+            owner: stake_program,
+            offset: 4,
+            value_len: 2,
+            value: [7; CONDITION_VALUE_LEN],
+            gates_cancel: false,
+        };
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                release_condition,
+                ..Escrow::default()
+            },
+            100,
+        );
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        fixture.push(
+            Config::find_address(&program_id).0,
+            SolanaAccount::default(),
+        );
+        fixture.push(fixture.mint_volume_pubkey(), SolanaAccount::default());
+        let exchange = |fixture: &mut ExchangeFixture| {
+            Processor::process_exchange(&fixture.account_infos(), 100, &program_id)
+        };
+        let condition_account = |owner, data: &[u8]| {
+            let mut account = SolanaAccount::new(0, data.len(), &owner);
+            account.data.copy_from_slice(data);
+            account
+        };
+
+        fixture.push(
+            Pubkey::new_unique(),
+            condition_account(stake_program, &[0, 0, 0, 0, 7, 7]),
+        );
+        assert_eq!(
+            exchange(&mut fixture),
+            Err(ProgramError::InvalidAccountData)
+        );
+        *fixture.keys.last_mut().unwrap() = release_condition.account;
+        for (owner, data) in [
+            (Pubkey::new_unique(), &[0, 0, 0, 0, 7, 7][..]),
+            (stake_program, &[0, 0, 0, 0, 7, 8][..]),
+            // too short to hold the value
+            (stake_program, &[0, 0, 0, 0, 7][..]),
+        ]
+        .iter()
+        {
+            *fixture.accounts.last_mut().unwrap() = condition_account(*owner, data);
+            assert_eq!(
+                exchange(&mut fixture),
+                Err(EscrowError::ConditionNotMet.into())
+            );
+        }
+        *fixture.accounts.last_mut().unwrap() =
+            condition_account(stake_program, &[0, 0, 0, 0, 7, 7]);
+        exchange(&mut fixture).expect("error: process_exchange()");
+        assert_eq!(fixture.accounts[ExchangeFixture::ESCROW].lamports, 0);
+    }
+
+    #[test]
+    fn test_exchange_settlement_summary() {
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 1_000,
+                ..Escrow::default()
+            },
+            1_500_000,
+        );
+        let program_id = fixture.program_id;
+        test_syscall_stubs(&program_id);
+        let sold_mint = ExchangeFixture::DEPOSIT_MINT;
+        let paid_mint = ExchangeFixture::RECEIVE_MINT;
+        let mint_account = |decimals| {
+            let mut account = SolanaAccount::new(0, Mint::get_packed_len(), &spl_token::id());
+            Mint {
+                decimals,
+                is_initialized: true,
+                ..Mint::default()
+            }
+            .pack_into_slice(&mut account.data);
+            account
+        };
+        let mut metadata_account = SolanaAccount::new(0, 679, &token_metadata::id());
+        metadata_account.data[65] = 4;
+        metadata_account.data[69..73].copy_from_slice(b"Bonk");
+        metadata_account.data[73] = 10;
+        metadata_account.data[77..81].copy_from_slice(b"BONK");
+
+        fixture.push(
+            Config::find_address(&program_id).0,
+            SolanaAccount::default(),
+        );
+        fixture.push(fixture.mint_volume_pubkey(), SolanaAccount::default());
+        fixture.push(paid_mint, mint_account(2));
+        fixture.push(sold_mint, mint_account(6));
+        let exchange = |fixture: &mut ExchangeFixture| {
+            Processor::process_exchange(&fixture.account_infos(), 1_500_000, &program_id)
+        };
+        assert_eq!(
+            fixture.atomically(exchange),
+            Err(ProgramError::InvalidAccountData)
+        );
+        fixture.keys.swap(11, 12);
+        fixture.accounts.swap(11, 12);
+        fixture.push(Pubkey::new_unique(), SolanaAccount::default());
+        assert_eq!(
+            fixture.atomically(exchange),
+            Err(ProgramError::InvalidSeeds)
+        );
+
+        *fixture.keys.last_mut().unwrap() = token_metadata::find_metadata_address(&sold_mint).0;
+        fixture.push(
+            token_metadata::find_metadata_address(&paid_mint).0,
+            metadata_account,
+        );
+        exchange(&mut fixture).expect("error: process_exchange()");
+        assert_eq!(
+            token_metadata::unpack_symbol(&fixture.accounts[14].data),
+            Some("BONK".to_string())
+        );
+    }
+
+    /// Ed25519 program instruction verifying `keypair`'s signature of `message`
+    fn ed25519_instruction(keypair: &Keypair, message: &[u8]) -> Instruction {
+        let signature = keypair.sign_message(message);
+        let mut data = vec![1, 0];
+        for offset in [
+            48,
+            u16::MAX,
+            16,
+            u16::MAX,
+            112,
+            message.len() as u16,
+            u16::MAX,
+        ]
+        .iter()
+        {
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+        data.extend_from_slice(keypair.pubkey().as_ref());
+        data.extend_from_slice(signature.as_ref());
+        data.extend_from_slice(message);
+        Instruction {
+            program_id: ed25519_program::id(),
+            accounts: vec![],
+            data,
+        }
+    }
+
+    #[test]
+    fn test_relayed_exchange() {
+        let taker = Keypair::new();
+        let mut fixture = ExchangeFixture::new(
+            Escrow {
+                expected_amount: 150,
+                ..Escrow::default()
+            },
+            100,
+        );
+        fixture.set_taker(taker.pubkey());
+        let program_id = fixture.program_id;
+        // the relayer's PDA pays out of the taker's account, as the delegate the taker approved
+        let mut sending = fixture.token_account(ExchangeFixture::TAKER_SENDING);
+        sending.delegate = COption::Some(fixture.keys[8]);
+        sending.delegated_amount = 150;
+        sending.pack_into_slice(&mut fixture.accounts[ExchangeFixture::TAKER_SENDING].data);
+        fixture.push(
+            Config::find_address(&program_id).0,
+            SolanaAccount::default(),
+        );
+        fixture.push(fixture.mint_volume_pubkey(), SolanaAccount::default());
+
+        let relayer_pubkey = Pubkey::new_unique();
+        let nonce_pubkey = RelayNonce::find_address(&taker.pubkey(), &program_id).0;
+        let mut relayer_account = SolanaAccount::default();
+        let mut nonce_account = SolanaAccount::new(0, RelayNonce::get_packed_len(), &program_id);
+        RelayNonce {
+            is_initialized: true,
+            user: taker.pubkey(),
+            next_nonce: 1,
+        }
+        .pack_into_slice(&mut nonce_account.data);
+        let mut rent_account = create_account_for_test(&Rent::default());
+        let mut system_program_account = SolanaAccount::default();
+        let (escrow_pubkey, sending_pubkey, receiving_pubkey) = (
+            fixture.keys[ExchangeFixture::ESCROW],
+            fixture.keys[1],
+            fixture.keys[2],
+        );
+        let intent = |nonce, receiving| RelayIntent {
+            program_id,
+            escrow: escrow_pubkey,
+            nonce,
+            action: RelayedAction::Exchange {
+                amount: 100,
+                sending: sending_pubkey,
+                receiving,
+            },
+        };
+        let relayed_ix = Instruction::new_with_bytes(program_id, &[43], vec![]);
+        let signed = |intent: RelayIntent| {
+            instructions_sysvar_account(
+                &[
+                    ed25519_instruction(&taker, &intent.message()),
+                    relayed_ix.clone(),
+                ],
+                1,
+            )
+        };
+        let mut instructions_account =
+            instructions_sysvar_account(std::slice::from_ref(&relayed_ix), 0);
+        let mut relayed_exchange = |nonce, instructions_account: &mut SolanaAccount| {
+            let instructions_pubkey = sysvar::instructions::id();
+            let rent_pubkey = sysvar::rent::id();
+            let system_program_pubkey = Pubkey::default();
+            let mut relay_accounts = [
+                (&relayer_pubkey, true, &mut relayer_account),
+                (&nonce_pubkey, false, &mut nonce_account),
+                (&instructions_pubkey, false, instructions_account),
+                (&rent_pubkey, false, &mut rent_account),
+                (&system_program_pubkey, false, &mut system_program_account),
+            ];
+            let mut accounts = create_is_signer_account_infos(&mut relay_accounts);
+            let mut exchange_accounts = fixture.account_infos();
+            exchange_accounts[ExchangeFixture::TAKER].is_signer = false;
+            accounts.extend(exchange_accounts);
+            let result = Processor::process_relayed_exchange(&accounts, 100, nonce, &program_id);
+            let escrow_lamports = accounts[5 + ExchangeFixture::ESCROW].lamports();
+            (result, escrow_lamports)
+        };
+
+        assert_eq!(
+            relayed_exchange(1, &mut instructions_account).0,
+            Err(EscrowError::IntentMissing.into())
+        );
+        instructions_account = signed(intent(1, Pubkey::new_unique()));
+        assert_eq!(
+            relayed_exchange(1, &mut instructions_account).0,
+            Err(EscrowError::IntentMissing.into())
+        );
+        instructions_account = signed(intent(0, receiving_pubkey));
+        assert_eq!(
+            relayed_exchange(0, &mut instructions_account).0,
+            Err(EscrowError::RelayNonceMismatch.into())
+        );
+        instructions_account = signed(intent(1, receiving_pubkey));
+        let (result, escrow_lamports) = relayed_exchange(1, &mut instructions_account);
+        result.expect("error: process_relayed_exchange()");
+        assert_eq!(escrow_lamports, 0);
+        // replaying the same intent finds the nonce used up
+        assert_eq!(
+            relayed_exchange(1, &mut instructions_account).0,
+            Err(EscrowError::RelayNonceMismatch.into())
+        );
+        assert_eq!(
+            RelayNonce::unpack(&nonce_account.data).unwrap().next_nonce,
+            2
+        );
+        let sending = fixture.token_account(ExchangeFixture::TAKER_SENDING);
+        assert_eq!(sending.amount, ExchangeFixture::TAKER_BALANCE - 150);
+        assert_eq!(sending.delegated_amount, 0);
+    }
+
+    #[test]
+    fn test_compressed_nft_escrow() {
+        let program_id = Pubkey::new_unique();
+        test_syscall_stubs(&program_id);
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+        let initializer_pubkey = Pubkey::new_unique();
+        let receive_pubkey = Pubkey::new_unique();
+        let escrow_pubkey = Pubkey::new_unique();
         let taker_pubkey = Pubkey::new_unique();
-        let taker_token_send_pubkey = Pubkey::new_unique();
-        let taker_token_receive_pubkey = Pubkey::new_unique();
+        let merkle_tree_pubkey = Pubkey::new_unique();
+        let tree_authority_pubkey =
+            Pubkey::find_program_address(&[merkle_tree_pubkey.as_ref()], &bubblegum::id()).0;
+        let proof_pubkey = Pubkey::new_unique();
+        let mint_list_pubkey = MintList::find_address(&program_id).0;
+        let config_pubkey = Config::find_address(&program_id).0;
+        let leaf = Leaf {
+            data_hash: [1; 32],
+            creator_hash: [2; 32],
+            nonce: 3,
+            index: 3,
+        };
+        let root = [4; 32];
+
+        let mut initializer_account = SolanaAccount::default();
+        let receive_mint = Pubkey::new_unique();
+        let mut receive_account = packed_token_account(&receive_mint, &initializer_pubkey, 0);
+        let rent = Rent::default();
+        let mut escrow_account = SolanaAccount::new(
+            rent.minimum_balance(CnftEscrow::get_packed_len()),
+            CnftEscrow::get_packed_len(),
+            &program_id,
+        );
+        let mut rent_account = create_account_for_test(&rent);
+        let mut mint_list_account = SolanaAccount::default();
+        let mut pda_account = SolanaAccount::default();
+        let mut tree_authority_account = SolanaAccount::default();
+        let mut merkle_tree_account = SolanaAccount::default();
+        let mut noop_account = SolanaAccount::default();
+        let mut compression_account = SolanaAccount::default();
+        let mut system_program_account = SolanaAccount::default();
+        let mut bubblegum_account = SolanaAccount::default();
+        let mut proof_account = SolanaAccount::default();
+        let mut taker_account = SolanaAccount::default();
+        let mut takers_sending_account = packed_token_account(&receive_mint, &taker_pubkey, 150);
+        let mut token_program_account = SolanaAccount::default();
+        let mut config_account = SolanaAccount::default();
+
+        {
+            let bubblegum_pubkey = bubblegum::id();
+            let noop_pubkey = bubblegum::noop::id();
+            let compression_pubkey = bubblegum::compression::id();
+            let mut accounts = [
+                (&initializer_pubkey, true, &mut initializer_account),
+                (&initializer_pubkey, false, &mut taker_account),
+                (&receive_pubkey, false, &mut receive_account),
+                (&escrow_pubkey, false, &mut escrow_account),
+                (&sysvar::rent::id(), false, &mut rent_account),
+                (&mint_list_pubkey, false, &mut mint_list_account),
+                (&pda, false, &mut pda_account),
+                (&tree_authority_pubkey, false, &mut tree_authority_account),
+                (&merkle_tree_pubkey, false, &mut merkle_tree_account),
+                (&noop_pubkey, false, &mut noop_account),
+                (&compression_pubkey, false, &mut compression_account),
+                (&Pubkey::default(), false, &mut system_program_account),
+                (&bubblegum_pubkey, false, &mut bubblegum_account),
+                (&proof_pubkey, false, &mut proof_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_init_cnft_escrow(&accounts, 150, &root, leaf, &program_id)
+                .expect("error: process_init_cnft_escrow()");
+            assert_eq!(
+                Processor::process_init_cnft_escrow(&accounts, 150, &root, leaf, &program_id),
+                Err(ProgramError::AccountAlreadyInitialized)
+            );
+        }
+        let escrow_info = CnftEscrow::unpack(&escrow_account.data).unwrap();
+        assert_eq!(escrow_info.merkle_tree, merkle_tree_pubkey);
+        assert_eq!(escrow_info.leaf, leaf);
+        assert_eq!(escrow_info.expected_amount, 150);
+
+        {
+            let bubblegum_pubkey = bubblegum::id();
+            let noop_pubkey = bubblegum::noop::id();
+            let compression_pubkey = bubblegum::compression::id();
+            let other_tree_pubkey = Pubkey::new_unique();
+            let mut accounts = [
+                (&taker_pubkey, true, &mut taker_account),
+                (&Pubkey::new_unique(), false, &mut takers_sending_account),
+                (&receive_pubkey, false, &mut receive_account),
+                (&initializer_pubkey, false, &mut initializer_account),
+                (&escrow_pubkey, false, &mut escrow_account),
+                (&spl_token::id(), false, &mut token_program_account),
+                (&pda, false, &mut pda_account),
+                (&config_pubkey, false, &mut config_account),
+                (&tree_authority_pubkey, false, &mut tree_authority_account),
+                (&other_tree_pubkey, false, &mut merkle_tree_account),
+                (&noop_pubkey, false, &mut noop_account),
+                (&compression_pubkey, false, &mut compression_account),
+                (&Pubkey::default(), false, &mut system_program_account),
+                (&bubblegum_pubkey, false, &mut bubblegum_account),
+                (&proof_pubkey, false, &mut proof_account),
+            ];
+            let mut accounts = create_is_signer_account_infos(&mut accounts);
+            assert_eq!(
+                process_atomically(&accounts, |accounts| {
+                    Processor::process_exchange_cnft(accounts, &root, &program_id)
+                }),
+                Err(ProgramError::InvalidAccountData)
+            );
+            accounts[9].key = &merkle_tree_pubkey;
+            Processor::process_exchange_cnft(&accounts, &root, &program_id)
+                .expect("error: process_exchange_cnft()");
+            assert_eq!(accounts[4].lamports(), 0);
+            assert_eq!(
+                accounts[3].lamports(),
+                rent.minimum_balance(CnftEscrow::get_packed_len())
+            );
+            let paid = TokenAccount::unpack(&accounts[2].data.borrow()).unwrap();
+            assert_eq!(paid.amount, 150);
+        }
+
+        escrow_account = SolanaAccount::new(1_000, CnftEscrow::get_packed_len(), &program_id);
+        CnftEscrow::pack(escrow_info, &mut escrow_account.data).unwrap();
+        {
+            let noop_pubkey = bubblegum::noop::id();
+            let compression_pubkey = bubblegum::compression::id();
+            let mut accounts = [
+                (&initializer_pubkey, true, &mut initializer_account),
+                (&escrow_pubkey, false, &mut escrow_account),
+                (&pda, false, &mut pda_account),
+                (&tree_authority_pubkey, false, &mut tree_authority_account),
+                (&merkle_tree_pubkey, false, &mut merkle_tree_account),
+                (&noop_pubkey, false, &mut noop_account),
+                (&compression_pubkey, false, &mut compression_account),
+                (&Pubkey::default(), false, &mut system_program_account),
+                (&spl_token::id(), false, &mut bubblegum_account),
+            ];
+            let mut accounts = create_is_signer_account_infos(&mut accounts);
+            assert_eq!(
+                Processor::process_cancel_cnft(&accounts, &root, &program_id),
+                Err(ProgramError::IncorrectProgramId)
+            );
+            let bubblegum_pubkey = bubblegum::id();
+            accounts[8].key = &bubblegum_pubkey;
+            Processor::process_cancel_cnft(&accounts, &root, &program_id)
+                .expect("error: process_cancel_cnft()");
+            assert_eq!(accounts[1].lamports(), 0);
+        }
+    }
+
+    #[test]
+    fn test_core_asset_escrow() {
+        let program_id = Pubkey::new_unique();
+        test_syscall_stubs(&program_id);
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+        let initializer_pubkey = Pubkey::new_unique();
+        let receive_pubkey = Pubkey::new_unique();
         let escrow_pubkey = Pubkey::new_unique();
-        let token_pubkey = Pubkey::new_unique();
+        let taker_pubkey = Pubkey::new_unique();
+        let asset_pubkey = Pubkey::new_unique();
+        let receive_mint = Pubkey::new_unique();
+        let collection_pubkey = Pubkey::new_unique();
+        let mint_list_pubkey = MintList::find_address(&program_id).0;
+        let config_pubkey = Config::find_address(&program_id).0;
+        let core_pubkey = mpl_core::id();
+        let asset_data = |owner: &Pubkey, update_authority_kind, update_authority: &Pubkey| {
+            let mut account = SolanaAccount::new(0, 120, &mpl_core::id());
+            account.data[0] = 1;
+            account.data[1..33].copy_from_slice(owner.as_ref());
+            account.data[33] = update_authority_kind;
+            account.data[34..66].copy_from_slice(update_authority.as_ref());
+            account
+        };
 
-        let accounts: [solana_program::account_info::AccountInfo; 9] = [
-            (&taker_pubkey, true, &mut taker_account).into(),
-            (
-                &taker_token_send_pubkey,
-                false,
-                &mut taker_token_send_account,
-            )
-                .into(),
-            (
-                &taker_token_receive_pubkey,
-                false,
-                &mut taker_token_receive_account,
-            )
-                .into(),
-            (&pdas_temp_token_pubkey, false, &mut pdas_temp_token_account).into(),
-            (&initializer_pubkey, false, &mut initializer_account).into(),
-            (
-                &initializer_token_to_receive_account_pubkey,
-                false,
-                &mut initializer_token_receive_account,
-            )
-                .into(),
-            (&escrow_pubkey, false, &mut escrow_account).into(),
-            (&token_pubkey, false, &mut token_account).into(),
-            (&pda, false, &mut pda_temp_account).into(),
-        ];
+        let mut initializer_account = SolanaAccount::default();
+        let mut asset_account = asset_data(&initializer_pubkey, 2, &collection_pubkey);
+        let mut collection_account = SolanaAccount::default();
+        let mut receive_account = packed_token_account(&receive_mint, &initializer_pubkey, 0);
+        let rent = Rent::default();
+        let mut escrow_account = SolanaAccount::new(
+            rent.minimum_balance(CoreEscrow::get_packed_len()),
+            CoreEscrow::get_packed_len(),
+            &program_id,
+        );
+        let mut rent_account = create_account_for_test(&rent);
+        let mut mint_list_account = SolanaAccount::default();
+        let mut pda_account = SolanaAccount::default();
+        let mut core_account = SolanaAccount::default();
+        let mut taker_account = SolanaAccount::default();
+        let mut takers_sending_account = packed_token_account(&receive_mint, &taker_pubkey, 150);
+        let mut token_program_account = SolanaAccount::default();
+        let mut config_account = SolanaAccount::default();
 
-        Processor::process_exchange(&accounts, amount, &escrow_program_id)
-            .expect("error: process_exchange()");
+        {
+            let mut accounts = [
+                (&initializer_pubkey, true, &mut initializer_account),
+                (&asset_pubkey, false, &mut asset_account),
+                (&core_pubkey, false, &mut collection_account),
+                (&receive_pubkey, false, &mut receive_account),
+                (&escrow_pubkey, false, &mut escrow_account),
+                (&sysvar::rent::id(), false, &mut rent_account),
+                (&mint_list_pubkey, false, &mut mint_list_account),
+                (&pda, false, &mut pda_account),
+                (&core_pubkey, false, &mut core_account),
+            ];
+            let mut accounts = create_is_signer_account_infos(&mut accounts);
+            // the asset is in a collection
+            assert_eq!(
+                Processor::process_init_core_escrow(&accounts, 150, &program_id),
+                Err(ProgramError::InvalidAccountData)
+            );
+            accounts[2].key = &collection_pubkey;
+            Processor::process_init_core_escrow(&accounts, 150, &program_id)
+                .expect("error: process_init_core_escrow()");
+        }
+        let escrow_info = CoreEscrow::unpack(&escrow_account.data).unwrap();
+        assert_eq!(escrow_info.asset, asset_pubkey);
+        assert_eq!(
+            escrow_info.update_authority,
+            mpl_core::UpdateAuthority::Collection(collection_pubkey)
+        );
+        assert_eq!(escrow_info.expected_amount, 150);
+
+        asset_account = asset_data(&pda, 1, &Pubkey::new_unique());
+        {
+            let mut accounts = [
+                (&taker_pubkey, true, &mut taker_account),
+                (&Pubkey::new_unique(), false, &mut takers_sending_account),
+                (&receive_pubkey, false, &mut receive_account),
+                (&initializer_pubkey, false, &mut initializer_account),
+                (&escrow_pubkey, false, &mut escrow_account),
+                (&asset_pubkey, false, &mut asset_account),
+                (&collection_pubkey, false, &mut collection_account),
+                (&spl_token::id(), false, &mut token_program_account),
+                (&pda, false, &mut pda_account),
+                (&core_pubkey, false, &mut core_account),
+                (&config_pubkey, false, &mut config_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            assert_eq!(
+                process_atomically(&accounts, |accounts| {
+                    Processor::process_exchange_core(accounts, &program_id)
+                }),
+                Err(EscrowError::AssetChanged.into())
+            );
+        }
+        asset_account = asset_data(&pda, 2, &collection_pubkey);
+        {
+            let mut accounts = [
+                (&taker_pubkey, true, &mut taker_account),
+                (&Pubkey::new_unique(), false, &mut takers_sending_account),
+                (&receive_pubkey, false, &mut receive_account),
+                (&initializer_pubkey, false, &mut initializer_account),
+                (&escrow_pubkey, false, &mut escrow_account),
+                (&asset_pubkey, false, &mut asset_account),
+                (&collection_pubkey, false, &mut collection_account),
+                (&spl_token::id(), false, &mut token_program_account),
+                (&pda, false, &mut pda_account),
+                (&core_pubkey, false, &mut core_account),
+                (&config_pubkey, false, &mut config_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            Processor::process_exchange_core(&accounts, &program_id)
+                .expect("error: process_exchange_core()");
+            assert_eq!(
+                TokenAccount::unpack(&accounts[2].data.borrow())
+                    .unwrap()
+                    .amount,
+                150
+            );
+            assert_eq!(accounts[4].lamports(), 0);
+            assert_eq!(
+                accounts[3].lamports(),
+                rent.minimum_balance(CoreEscrow::get_packed_len())
+            );
+        }
 
-        // TODO all the asserts
+        escrow_account = SolanaAccount::new(1_000, CoreEscrow::get_packed_len(), &program_id);
+        CoreEscrow::pack(escrow_info, &mut escrow_account.data).unwrap();
+        {
+            let mut accounts = [
+                (&initializer_pubkey, true, &mut initializer_account),
+                (&escrow_pubkey, false, &mut escrow_account),
+                (&asset_pubkey, false, &mut asset_account),
+                (&collection_pubkey, false, &mut collection_account),
+                (&pda, false, &mut pda_account),
+                (&core_pubkey, false, &mut core_account),
+            ];
+            let accounts = create_is_signer_account_infos(&mut accounts);
+            assert_eq!(
+                Processor::process_cancel_core(&accounts[1..], &program_id),
+                Err(ProgramError::MissingRequiredSignature)
+            );
+            Processor::process_cancel_core(&accounts, &program_id)
+                .expect("error: process_cancel_core()");
+            assert_eq!(accounts[1].lamports(), 0);
+        }
     }
 }
@@ -0,0 +1,89 @@
+//! Intents users sign off-chain for a relayer to submit on their behalf, checked against an
+//! Ed25519 program instruction of the same transaction
+
+use std::convert::TryInto;
+
+use solana_program::pubkey::Pubkey;
+
+/// Prefix of every intent message, so that no other message a wallet signs can pass for one
+pub const RELAY_INTENT_PREFIX: &[u8] = b"escrow-relay:";
+
+// Ed25519 program instruction layout, for a single signature
+const SIGNATURE_OFFSETS_START: usize = 2;
+const SIGNATURE_OFFSETS_LEN: usize = 14;
+const SAME_INSTRUCTION: u16 = u16::MAX;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RelayedAction {
+    /// Exchange paying from `sending`, which delegates to the `["escrow"]` PDA, into `receiving`
+    Exchange {
+        amount: u64,
+        sending: Pubkey,
+        receiving: Pubkey,
+    },
+    /// Cancel returning the deposit to `receiving`
+    Cancel { receiving: Pubkey },
+}
+
+/// What a user authorizes a relayer to do with `escrow`, at most once thanks to `nonce`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RelayIntent {
+    pub program_id: Pubkey,
+    pub escrow: Pubkey,
+    /// the next nonce of the user's relay nonce account
+    pub nonce: u64,
+    pub action: RelayedAction,
+}
+
+impl RelayIntent {
+    /// the bytes the user signs
+    pub fn message(&self) -> Vec<u8> {
+        let mut message = Vec::with_capacity(158);
+        message.extend_from_slice(RELAY_INTENT_PREFIX);
+        message.extend_from_slice(self.program_id.as_ref());
+        message.extend_from_slice(self.escrow.as_ref());
+        message.extend_from_slice(&self.nonce.to_le_bytes());
+        match self.action {
+            RelayedAction::Exchange {
+                amount,
+                sending,
+                receiving,
+            } => {
+                message.push(0);
+                message.extend_from_slice(&amount.to_le_bytes());
+                message.extend_from_slice(sending.as_ref());
+                message.extend_from_slice(receiving.as_ref());
+            }
+            RelayedAction::Cancel { receiving } => {
+                message.push(1);
+                message.extend_from_slice(receiving.as_ref());
+            }
+        }
+        message
+    }
+}
+
+/// The public key and message of an Ed25519 program instruction verifying one signature over
+/// data it carries itself, `None` for any other instruction data
+pub fn unpack_ed25519_instruction(data: &[u8]) -> Option<(Pubkey, &[u8])> {
+    if data.first() != Some(&1) {
+        return None;
+    }
+    let offsets =
+        data.get(SIGNATURE_OFFSETS_START..SIGNATURE_OFFSETS_START + SIGNATURE_OFFSETS_LEN)?;
+    let read_u16 =
+        |index: usize| u16::from_le_bytes(offsets[index * 2..index * 2 + 2].try_into().unwrap());
+    // signature, public key and message must all be read from this instruction
+    if [1, 3, 6]
+        .iter()
+        .any(|index| read_u16(*index) != SAME_INSTRUCTION)
+    {
+        return None;
+    }
+    let public_key_offset = read_u16(2) as usize;
+    let message_offset = read_u16(4) as usize;
+    let message_len = read_u16(5) as usize;
+    let public_key = data.get(public_key_offset..public_key_offset + 32)?;
+    let message = data.get(message_offset..message_offset + message_len)?;
+    Some((Pubkey::new(public_key), message))
+}
@@ -1,77 +1,2776 @@
+use std::fmt;
+
 use solana_program::{
+    hash::hashv,
+    program::MAX_RETURN_DATA,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
 };
 
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use bytemuck::Zeroable;
+
+use crate::{
+    bubblegum::Leaf,
+    event::{ui_amount, TokenAmount},
+    mpl_core::UpdateAuthority,
+};
+
+use layout::{EscrowLayout, ReleaseConditionLayout, SplitRecipientLayout};
 
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Escrow {
     pub is_initialized: bool,
     pub initializer_pubkey: Pubkey,
     pub temp_token_account_pubkey: Pubkey,
     pub initializer_token_to_receive_account_pubkey: Pubkey,
     pub expected_amount: u64,
+    /// price account quoting the receive token per deposited token, `Pubkey::default()` for a fixed price
+    pub oracle_pubkey: Pubkey,
+    /// maximum age in slots of the oracle price accepted at fill time
+    pub oracle_max_staleness_slots: u64,
+    /// maximum confidence interval accepted, in basis points of the price
+    pub oracle_max_confidence_bps: u16,
+    /// decimals of the deposited mint, read at init for oracle priced escrows
+    pub deposit_mint_decimals: u8,
+    /// decimals of the mint the initializer receives, read at init for oracle priced escrows
+    pub receive_mint_decimals: u8,
+    /// starting price of a linear curve escrow, scaled by `math::CURVE_PRICE_SCALE`
+    pub curve_start_price: u64,
+    /// price increase per deposited base unit sold, scaled by `math::CURVE_PRICE_SCALE`
+    pub curve_slope: u64,
+    /// deposited base units already sold by partial fills
+    pub filled_amount: u64,
+    /// lets many takers each buy a slice of a fixed price deposit
+    pub is_crowdfill: bool,
+    /// the initializer can not cancel before this slot
+    pub cancel_locked_until_slot: u64,
+    /// lamports a taker posts to reserve the escrow
+    pub reserve_bond_lamports: u64,
+    /// how many slots a reservation lasts, zero disables reservations
+    pub reserve_slots: u64,
+    /// taker holding the current reservation, `Pubkey::default()` when there is none
+    pub reserved_by: Pubkey,
+    /// first slot at which the current reservation has lapsed
+    pub reserved_until_slot: u64,
+    /// how long a committed two-phase exchange may wait to be settled, zero disables two-phase
+    pub settle_timeout_slots: u64,
+    /// taker of the pending two-phase exchange, `Pubkey::default()` when there is none
+    pub committed_taker: Pubkey,
+    /// first slot at which the pending two-phase exchange can be unwound instead of settled
+    pub committed_until_slot: u64,
+    /// token account owned by the PDA holding the committed taker's payment
+    pub payment_vault_pubkey: Pubkey,
+    /// the committed taker's token account receiving the deposit on settlement
+    pub committed_taker_token_to_receive_pubkey: Pubkey,
+    /// refuses Exchanges made through a cross-program invocation
+    pub exchange_top_level_only: bool,
+    /// where UIs find the off-chain JSON describing the offer
+    pub metadata_uri: MetadataUri,
+    /// zero padded string an SPL Memo of the Exchange transaction must contain, all zero when
+    /// no memo is required
+    pub memo_reference: [u8; MEMO_REFERENCE_LEN],
+    /// first slot at which the escrow can no longer be taken and anyone may reclaim it for the
+    /// initializer, zero when it never expires
+    pub expires_at_slot: u64,
+    /// the only taker the escrow can be taken by, `Pubkey::default()` for anyone
+    pub allowed_taker: Pubkey,
+    /// deposit pulled into the vault to list the escrow again once it is fully filled, zero
+    /// when it closes instead
+    pub relist_amount: u64,
+    /// initializer's token account of the deposited mint that delegated the relist deposits to
+    /// the PDA
+    pub relist_source_pubkey: Pubkey,
+    /// first slot at which a sealed-bid auction escrow stops taking bids, zero when the escrow
+    /// is not an auction
+    pub auction_bid_end_slot: u64,
+    /// first slot at which the auction's bids can no longer be revealed and it can be settled
+    pub auction_reveal_end_slot: u64,
+    /// highest bid revealed so far
+    pub best_bid_amount: u64,
+    /// bid account of the highest bid revealed so far, `Pubkey::default()` when there is none
+    pub best_bid_pubkey: Pubkey,
+    /// first slot at which a raffle escrow stops selling tickets and can be drawn, zero when the
+    /// escrow is not a raffle. Tickets are sold at `expected_amount` each
+    pub raffle_end_slot: u64,
+    /// tickets sold so far, also the index of the next ticket
+    pub raffle_ticket_count: u64,
+    /// amount the follow-on offer asks for the escrow's proceeds, see `chain_receive_pubkey`
+    pub chain_expected_amount: u64,
+    /// initializer's token account receiving the payment of the follow-on offer listed with the
+    /// proceeds once the escrow is fully filled, `Pubkey::default()` when nothing is chained
+    pub chain_receive_pubkey: Pubkey,
+    /// deposit left for sale, recorded as the program moves tokens into and out of the vault.
+    /// Tokens sent to the vault directly are not for sale and go to the taker of the closing fill.
+    pub deposited_amount: u64,
+    /// mint the initializer receives, any new receive account has to hold it
+    pub receive_mint: Pubkey,
+    /// mint the follow-on offer of a chained escrow receives, becoming `receive_mint` once chained
+    pub chain_receive_mint: Pubkey,
+    /// key the initializer lets cancel and reprice the escrow, payouts still go to the
+    /// initializer's accounts. `Pubkey::default()` when there is none
+    pub operator: Pubkey,
+    /// token accounts paid a share of every payment ahead of the initializer, entries with a
+    /// zero share are unused
+    pub split_recipients: [SplitRecipient; MAX_SPLIT_RECIPIENTS],
+    /// state of another account the escrow can only be taken in
+    pub release_condition: ReleaseCondition,
+    /// slot the escrow was opened or last repriced at, which `GuardedExchange` checks so takers
+    /// fill the revision of the offer they saw
+    pub revised_at_slot: u64,
+    /// `hash_terms` of the escrow as of its last change of terms, which `TermsCheckedExchange`
+    /// compares with the hash the taker saw
+    pub terms_hash: [u8; 32],
+    /// unix timestamp the escrow can be taken from, zero when it can be taken right away
+    pub release_after_timestamp: i64,
+    /// unix timestamp the escrow can not be taken from, zero when its release never closes
+    pub release_before_timestamp: i64,
+    /// key the config and mint list of the escrow's market are seeded by, `Pubkey::default()`
+    /// for the program-wide ones
+    pub market: Pubkey,
+    /// label chosen by the initializer, such as a collection, desk or strategy id, for clients
+    /// to filter offers by
+    pub tag: u64,
+}
+
+pub const MEMO_REFERENCE_LEN: usize = 32;
+
+pub const MAX_SPLIT_RECIPIENTS: usize = 4;
+
+/// A token account paid `share_bps` basis points of an escrow's payments
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SplitRecipient {
+    pub token_account: Pubkey,
+    pub share_bps: u16,
+}
+
+impl SplitRecipient {
+    const LEN: usize = 34;
+}
+
+pub const CONDITION_VALUE_LEN: usize = 32;
+
+/// Holds while `account` is owned by `owner` and has the first `value_len` bytes of `value` at
+/// `offset` in its data, e.g. a stake account's state once it is deactivated. Unset while
+/// `account` is `Pubkey::default()`
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReleaseCondition {
+    pub account: Pubkey,
+    pub owner: Pubkey,
+    pub offset: u16,
+    pub value_len: u8,
+    pub value: [u8; CONDITION_VALUE_LEN],
+    /// the condition gates Cancel instead of Exchange
+    pub gates_cancel: bool,
+}
+
+impl ReleaseCondition {
+    const LEN: usize = 100;
+
+    pub fn is_set(&self) -> bool {
+        self.account != Pubkey::default()
+    }
+
+    /// the bytes expected at `offset`
+    pub fn value(&self) -> &[u8] {
+        &self.value[..(self.value_len as usize).min(CONDITION_VALUE_LEN)]
+    }
+
+    /// whether the data of an account owned by `owner` satisfies the condition
+    pub fn holds(&self, owner: &Pubkey, data: &[u8]) -> bool {
+        let offset = self.offset as usize;
+        *owner == self.owner && data.get(offset..offset + self.value().len()) == Some(self.value())
+    }
+}
+
+pub const METADATA_URI_LEN: usize = 64;
+
+/// Zero padded UTF-8 URI, empty when the escrow has no metadata
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MetadataUri(pub [u8; METADATA_URI_LEN]);
+
+impl Default for MetadataUri {
+    fn default() -> Self {
+        MetadataUri([0; METADATA_URI_LEN])
+    }
+}
+
+impl MetadataUri {
+    /// `None` if `uri` does not fit
+    pub fn new(uri: &str) -> Option<Self> {
+        let mut padded = [0; METADATA_URI_LEN];
+        padded.get_mut(..uri.len())?.copy_from_slice(uri.as_bytes());
+        Some(MetadataUri(padded))
+    }
+
+    /// fails unless the bytes are UTF-8 followed by zero padding only
+    pub fn unpack(src: &[u8]) -> Result<Self, ProgramError> {
+        let mut padded = [0; METADATA_URI_LEN];
+        if src.len() != METADATA_URI_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        padded.copy_from_slice(src);
+        let uri = MetadataUri(padded);
+        let len = uri.len();
+        if padded[len..].iter().any(|byte| *byte != 0)
+            || std::str::from_utf8(&padded[..len]).is_err()
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(uri)
+    }
+
+    fn len(&self) -> usize {
+        self.0
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(METADATA_URI_LEN)
+    }
+
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.0[..self.len()]).unwrap_or_default()
+    }
+}
+
+// written as the URI string rather than its padding
+#[cfg(feature = "serde")]
+impl serde::Serialize for MetadataUri {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MetadataUri {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let uri = <String as serde::Deserialize>::deserialize(deserializer)?;
+        if uri.contains('\0') {
+            return Err(serde::de::Error::custom("metadata URI with a NUL byte"));
+        }
+        MetadataUri::new(&uri).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "metadata URI longer than {} bytes",
+                METADATA_URI_LEN
+            ))
+        })
+    }
+}
+
+impl Escrow {
+    // offsets in the account data, discriminator included, for `memcmp` filters
+    pub const OFFSET_IS_INITIALIZED: usize = 8;
+    pub const OFFSET_INITIALIZER: usize = 9;
+    pub const OFFSET_VAULT: usize = 41;
+    pub const OFFSET_RECEIVE_ACCOUNT: usize = 73;
+    pub const OFFSET_EXPECTED_AMOUNT: usize = 105;
+    pub const OFFSET_EXPIRES_AT_SLOT: usize = 455;
+    pub const OFFSET_ALLOWED_TAKER: usize = 463;
+    pub const OFFSET_DEPOSITED_AMOUNT: usize = 647;
+    pub const OFFSET_RECEIVE_MINT: usize = 655;
+    pub const OFFSET_OPERATOR: usize = 719;
+    pub const OFFSET_MARKET: usize = 1043;
+    pub const OFFSET_TAG: usize = 1075;
+
+    pub fn is_oracle_priced(&self) -> bool {
+        self.oracle_pubkey != Pubkey::default()
+    }
+
+    pub fn is_curve_priced(&self) -> bool {
+        self.curve_start_price != 0 || self.curve_slope != 0
+    }
+
+    pub fn allows_partial_fills(&self) -> bool {
+        self.is_crowdfill || self.is_curve_priced()
+    }
+
+    pub fn is_reserved(&self) -> bool {
+        self.reserved_by != Pubkey::default()
+    }
+
+    pub fn is_committed(&self) -> bool {
+        self.committed_taker != Pubkey::default()
+    }
+
+    pub fn requires_memo(&self) -> bool {
+        self.memo_reference[0] != 0
+    }
+
+    /// the memo reference without its padding
+    pub fn memo_reference(&self) -> &[u8] {
+        let len = self
+            .memo_reference
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(MEMO_REFERENCE_LEN);
+        &self.memo_reference[..len]
+    }
+
+    pub fn is_expired_at(&self, slot: u64) -> bool {
+        self.expires_at_slot != 0 && slot >= self.expires_at_slot
+    }
+
+    /// whether the escrow can only be taken within a window of unix timestamps
+    pub fn is_timelocked(&self) -> bool {
+        self.release_after_timestamp != 0 || self.release_before_timestamp != 0
+    }
+
+    pub fn is_released_at(&self, unix_timestamp: i64) -> bool {
+        unix_timestamp >= self.release_after_timestamp
+    }
+
+    pub fn is_release_closed_at(&self, unix_timestamp: i64) -> bool {
+        self.release_before_timestamp != 0 && unix_timestamp >= self.release_before_timestamp
+    }
+
+    pub fn allows_taker(&self, taker: &Pubkey) -> bool {
+        self.allowed_taker == Pubkey::default() || self.allowed_taker == *taker
+    }
+
+    pub fn relists(&self) -> bool {
+        self.relist_amount > 0
+    }
+
+    pub fn is_auction(&self) -> bool {
+        self.auction_reveal_end_slot != 0
+    }
+
+    pub fn is_raffle(&self) -> bool {
+        self.raffle_end_slot != 0
+    }
+
+    pub fn is_chained(&self) -> bool {
+        self.chain_receive_pubkey != Pubkey::default()
+    }
+
+    /// the split recipients in use, in the order their token accounts are passed
+    pub fn split_recipients(&self) -> impl Iterator<Item = &SplitRecipient> {
+        self.split_recipients
+            .iter()
+            .filter(|recipient| recipient.share_bps > 0)
+    }
+
+    /// sha256 of everything a taker agrees to: what is sold and asked, the pricing, the phases,
+    /// the conditions and where the payment is split to. Fills, reservations, the initializer's
+    /// receive account, operator and relisting, and a chained follow-on offer until it is listed,
+    /// leave it unchanged.
+    pub fn hash_terms(&self) -> [u8; 32] {
+        let mut terms = Vec::with_capacity(1024);
+        for key in [
+            &self.initializer_pubkey,
+            &self.temp_token_account_pubkey,
+            &self.receive_mint,
+            &self.oracle_pubkey,
+            &self.allowed_taker,
+            &self.market,
+        ]
+        .iter()
+        {
+            terms.extend_from_slice(key.as_ref());
+        }
+        for value in [
+            self.expected_amount,
+            self.oracle_max_staleness_slots,
+            self.curve_start_price,
+            self.curve_slope,
+            self.cancel_locked_until_slot,
+            self.reserve_bond_lamports,
+            self.reserve_slots,
+            self.settle_timeout_slots,
+            self.expires_at_slot,
+            self.auction_bid_end_slot,
+            self.auction_reveal_end_slot,
+            self.raffle_end_slot,
+        ]
+        .iter()
+        {
+            terms.extend_from_slice(&value.to_le_bytes());
+        }
+        terms.extend_from_slice(&self.release_after_timestamp.to_le_bytes());
+        terms.extend_from_slice(&self.release_before_timestamp.to_le_bytes());
+        terms.extend_from_slice(&self.oracle_max_confidence_bps.to_le_bytes());
+        terms.push(self.is_crowdfill as u8);
+        terms.push(self.exchange_top_level_only as u8);
+        terms.extend_from_slice(self.metadata_uri.as_str().as_bytes());
+        terms.push(0);
+        terms.extend_from_slice(&self.memo_reference);
+        for recipient in self.split_recipients.iter() {
+            terms.extend_from_slice(recipient.token_account.as_ref());
+            terms.extend_from_slice(&recipient.share_bps.to_le_bytes());
+        }
+        let condition = &self.release_condition;
+        terms.extend_from_slice(condition.account.as_ref());
+        terms.extend_from_slice(condition.owner.as_ref());
+        terms.extend_from_slice(&condition.offset.to_le_bytes());
+        terms.extend_from_slice(condition.value());
+        terms.push(condition.gates_cancel as u8);
+        hashv(&[b"escrow terms", &terms]).to_bytes()
+    }
+
+    /// whether `key` is the initializer or the operator, who may both cancel and reprice
+    pub fn is_managed_by(&self, key: &Pubkey) -> bool {
+        *key == self.initializer_pubkey
+            || (self.operator != Pubkey::default() && *key == self.operator)
+    }
+
+    /// whether `slot` falls within the current reservation
+    pub fn is_reserved_at(&self, slot: u64) -> bool {
+        self.is_reserved() && slot < self.reserved_until_slot
+    }
+
+    /// where the escrow stands at `slot`, the first of expired, committed and reserved that
+    /// applies before whether it was partly filled
+    pub fn fill_status(&self, slot: u64) -> FillStatus {
+        if self.is_expired_at(slot) {
+            FillStatus::Expired
+        } else if self.is_committed() {
+            FillStatus::Committed
+        } else if self.is_reserved_at(slot) {
+            FillStatus::Reserved
+        } else if self.filled_amount > 0 {
+            FillStatus::PartlyFilled
+        } else {
+            FillStatus::Open
+        }
+    }
+}
+
+/// A labeled line per field worth reading, amounts in whole tokens when the escrow recorded its
+/// mints' decimals, which oracle priced escrows do, and in base units otherwise
+impl fmt::Display for Escrow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = if self.is_auction() {
+            "sealed-bid auction"
+        } else if self.is_raffle() {
+            "raffle"
+        } else if self.is_oracle_priced() {
+            "oracle priced"
+        } else if self.is_curve_priced() {
+            "linear curve"
+        } else if self.is_crowdfill {
+            "crowdfill"
+        } else {
+            "fixed price"
+        };
+        let decimals = |decimals| Some(decimals).filter(|_| self.is_oracle_priced());
+        let asks = match decimals(self.receive_mint_decimals) {
+            Some(decimals) => TokenAmount {
+                amount: self.expected_amount,
+                decimals,
+                mint: self.receive_mint,
+                symbol: None,
+            }
+            .to_string(),
+            None => format!(
+                "{} base units of {}",
+                self.expected_amount, self.receive_mint
+            ),
+        };
+        let deposit = |amount| match decimals(self.deposit_mint_decimals) {
+            Some(decimals) => format!("{} tokens", ui_amount(amount, decimals)),
+            None => format!("{} base units", amount),
+        };
+
+        write!(f, "{} escrow", kind)?;
+        write!(f, "\n  initializer: {}", self.initializer_pubkey)?;
+        write!(f, "\n  vault: {}", self.temp_token_account_pubkey)?;
+        write!(
+            f,
+            "\n  receive account: {}",
+            self.initializer_token_to_receive_account_pubkey
+        )?;
+        if self.is_curve_priced() {
+            write!(
+                f,
+                "\n  price: {} rising by {} per base unit sold, scaled by {}",
+                self.curve_start_price,
+                self.curve_slope,
+                crate::math::CURVE_PRICE_SCALE
+            )?;
+        } else {
+            write!(f, "\n  asks: {}", asks)?;
+        }
+        write!(f, "\n  deposit left: {}", deposit(self.deposited_amount))?;
+        if self.filled_amount > 0 {
+            write!(f, "\n  filled: {}", deposit(self.filled_amount))?;
+        }
+        if self.is_oracle_priced() {
+            write!(f, "\n  oracle: {}", self.oracle_pubkey)?;
+        }
+        if self.expires_at_slot != 0 {
+            write!(f, "\n  expires at slot: {}", self.expires_at_slot)?;
+        }
+        if self.release_after_timestamp != 0 {
+            write!(
+                f,
+                "\n  released at: {} (unix time)",
+                self.release_after_timestamp
+            )?;
+        }
+        if self.release_before_timestamp != 0 {
+            write!(
+                f,
+                "\n  release closes at: {} (unix time)",
+                self.release_before_timestamp
+            )?;
+        }
+        if self.allowed_taker != Pubkey::default() {
+            write!(f, "\n  allowed taker: {}", self.allowed_taker)?;
+        }
+        if self.operator != Pubkey::default() {
+            write!(f, "\n  operator: {}", self.operator)?;
+        }
+        if self.market != Pubkey::default() {
+            write!(f, "\n  market: {}", self.market)?;
+        }
+        if self.tag != 0 {
+            write!(f, "\n  tag: {}", self.tag)?;
+        }
+        if self.is_reserved() {
+            write!(
+                f,
+                "\n  reserved by: {} until slot {}",
+                self.reserved_by, self.reserved_until_slot
+            )?;
+        }
+        if self.is_committed() {
+            write!(
+                f,
+                "\n  committed to: {} until slot {}",
+                self.committed_taker, self.committed_until_slot
+            )?;
+        }
+        if !self.metadata_uri.as_str().is_empty() {
+            write!(f, "\n  metadata: {}", self.metadata_uri.as_str())?;
+        }
+        Ok(())
+    }
 }
 
 impl Sealed for Escrow {}
 
+impl Discriminator for Escrow {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [31, 213, 123, 187, 186, 22, 218, 155];
+}
+
 impl IsInitialized for Escrow {
     fn is_initialized(&self) -> bool {
         self.is_initialized
     }
 }
 
+// The offsets are the layout's, behind the discriminator: each follows from the one before and
+// the sizes of the layout fields in between. A drift fails to compile as mismatched array
+// lengths, since older toolchains have neither const panics nor a const `offset_of!`
+const fn after(offset: usize, field_sizes: &[usize]) -> usize {
+    let mut end = offset;
+    let mut i = 0;
+    while i < field_sizes.len() {
+        end += field_sizes[i];
+        i += 1;
+    }
+    end
+}
+const _: [(); Escrow::OFFSET_IS_INITIALIZED] = [(); DISCRIMINATOR_LEN];
+const _: [(); Escrow::OFFSET_INITIALIZER] = [(); after(Escrow::OFFSET_IS_INITIALIZED, &[1])];
+const _: [(); Escrow::OFFSET_VAULT] = [(); after(Escrow::OFFSET_INITIALIZER, &[32])];
+const _: [(); Escrow::OFFSET_RECEIVE_ACCOUNT] = [(); after(Escrow::OFFSET_VAULT, &[32])];
+const _: [(); Escrow::OFFSET_EXPECTED_AMOUNT] = [(); after(Escrow::OFFSET_RECEIVE_ACCOUNT, &[32])];
+const _: [(); Escrow::OFFSET_EXPIRES_AT_SLOT] = [(); after(
+    Escrow::OFFSET_EXPECTED_AMOUNT,
+    &[
+        8,                  // expected_amount
+        32,                 // oracle_pubkey
+        8,                  // oracle_max_staleness_slots
+        2,                  // oracle_max_confidence_bps
+        1,                  // deposit_mint_decimals
+        1,                  // receive_mint_decimals
+        8,                  // curve_start_price
+        8,                  // curve_slope
+        8,                  // filled_amount
+        1,                  // is_crowdfill
+        8,                  // cancel_locked_until_slot
+        8,                  // reserve_bond_lamports
+        8,                  // reserve_slots
+        32,                 // reserved_by
+        8,                  // reserved_until_slot
+        8,                  // settle_timeout_slots
+        32,                 // committed_taker
+        8,                  // committed_until_slot
+        32,                 // payment_vault_pubkey
+        32,                 // committed_taker_token_to_receive_pubkey
+        1,                  // exchange_top_level_only
+        METADATA_URI_LEN,   // metadata_uri
+        MEMO_REFERENCE_LEN, // memo_reference
+    ],
+)];
+const _: [(); Escrow::OFFSET_ALLOWED_TAKER] = [(); after(Escrow::OFFSET_EXPIRES_AT_SLOT, &[8])];
+const _: [(); Escrow::OFFSET_DEPOSITED_AMOUNT] = [(); after(
+    Escrow::OFFSET_ALLOWED_TAKER,
+    &[
+        32, // allowed_taker
+        8,  // relist_amount
+        32, // relist_source_pubkey
+        8,  // auction_bid_end_slot
+        8,  // auction_reveal_end_slot
+        8,  // best_bid_amount
+        32, // best_bid_pubkey
+        8,  // raffle_end_slot
+        8,  // raffle_ticket_count
+        8,  // chain_expected_amount
+        32, // chain_receive_pubkey
+    ],
+)];
+const _: [(); Escrow::OFFSET_RECEIVE_MINT] = [(); after(Escrow::OFFSET_DEPOSITED_AMOUNT, &[8])];
+const _: [(); Escrow::OFFSET_OPERATOR] = [(); after(
+    Escrow::OFFSET_RECEIVE_MINT,
+    &[
+        32, // receive_mint
+        32, // chain_receive_mint
+    ],
+)];
+const _: [(); Escrow::OFFSET_MARKET] = [(); after(
+    Escrow::OFFSET_OPERATOR,
+    &[
+        32, // operator
+        std::mem::size_of::<[SplitRecipientLayout; MAX_SPLIT_RECIPIENTS]>(),
+        std::mem::size_of::<ReleaseConditionLayout>(),
+        8,  // revised_at_slot
+        32, // terms_hash
+        8,  // release_after_timestamp
+        8,  // release_before_timestamp
+    ],
+)];
+const _: [(); Escrow::OFFSET_TAG] = [(); after(Escrow::OFFSET_MARKET, &[32])];
+const _: [(); Escrow::LEN] = [(); after(Escrow::OFFSET_TAG, &[8])];
+const _: [(); Escrow::LEN] = [(); DISCRIMINATOR_LEN + std::mem::size_of::<EscrowLayout>()];
+
+/// Byte layouts the Pack implementations view account data as
+mod layout {
+    // bytemuck_derive's generated padding checks read as dead code to newer compilers
+    #![allow(dead_code)]
+
+    use bytemuck::{Pod, Zeroable};
+
+    use super::{CONDITION_VALUE_LEN, MAX_SPLIT_RECIPIENTS, MEMO_REFERENCE_LEN, METADATA_URI_LEN};
+
+    /// Byte layout of a packed `Escrow`. Every field is a byte array, so the layout has no
+    /// padding and an alignment of one, and `Escrow::LEN` bytes of account data can be viewed as
+    /// it in place.
+    #[repr(C)]
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    pub struct EscrowLayout {
+        pub is_initialized: u8,
+        pub initializer_pubkey: [u8; 32],
+        pub temp_token_account_pubkey: [u8; 32],
+        pub initializer_token_to_receive_account_pubkey: [u8; 32],
+        pub expected_amount: [u8; 8],
+        pub oracle_pubkey: [u8; 32],
+        pub oracle_max_staleness_slots: [u8; 8],
+        pub oracle_max_confidence_bps: [u8; 2],
+        pub deposit_mint_decimals: u8,
+        pub receive_mint_decimals: u8,
+        pub curve_start_price: [u8; 8],
+        pub curve_slope: [u8; 8],
+        pub filled_amount: [u8; 8],
+        pub is_crowdfill: u8,
+        pub cancel_locked_until_slot: [u8; 8],
+        pub reserve_bond_lamports: [u8; 8],
+        pub reserve_slots: [u8; 8],
+        pub reserved_by: [u8; 32],
+        pub reserved_until_slot: [u8; 8],
+        pub settle_timeout_slots: [u8; 8],
+        pub committed_taker: [u8; 32],
+        pub committed_until_slot: [u8; 8],
+        pub payment_vault_pubkey: [u8; 32],
+        pub committed_taker_token_to_receive_pubkey: [u8; 32],
+        pub exchange_top_level_only: u8,
+        pub metadata_uri: [u8; METADATA_URI_LEN],
+        pub memo_reference: [u8; MEMO_REFERENCE_LEN],
+        pub expires_at_slot: [u8; 8],
+        pub allowed_taker: [u8; 32],
+        pub relist_amount: [u8; 8],
+        pub relist_source_pubkey: [u8; 32],
+        pub auction_bid_end_slot: [u8; 8],
+        pub auction_reveal_end_slot: [u8; 8],
+        pub best_bid_amount: [u8; 8],
+        pub best_bid_pubkey: [u8; 32],
+        pub raffle_end_slot: [u8; 8],
+        pub raffle_ticket_count: [u8; 8],
+        pub chain_expected_amount: [u8; 8],
+        pub chain_receive_pubkey: [u8; 32],
+        pub deposited_amount: [u8; 8],
+        pub receive_mint: [u8; 32],
+        pub chain_receive_mint: [u8; 32],
+        pub operator: [u8; 32],
+        pub split_recipients: [SplitRecipientLayout; MAX_SPLIT_RECIPIENTS],
+        pub release_condition: ReleaseConditionLayout,
+        pub revised_at_slot: [u8; 8],
+        pub terms_hash: [u8; 32],
+        pub release_after_timestamp: [u8; 8],
+        pub release_before_timestamp: [u8; 8],
+        pub market: [u8; 32],
+        pub tag: [u8; 8],
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    pub struct SplitRecipientLayout {
+        pub token_account: [u8; 32],
+        pub share_bps: [u8; 2],
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    pub struct ReleaseConditionLayout {
+        pub account: [u8; 32],
+        pub owner: [u8; 32],
+        pub offset: [u8; 2],
+        pub value_len: u8,
+        pub value: [u8; CONDITION_VALUE_LEN],
+        pub gates_cancel: u8,
+    }
+}
+
+/// Length of the discriminator every program account starts with
+pub const DISCRIMINATOR_LEN: usize = 8;
+
+/// A program account type, told apart from the others by the bytes its data starts with. The
+/// values are the ones Anchor derives for a struct of the same name, the first bytes of
+/// `sha256("account:<name>")`, so `memcmp` filters and Anchor clients find accounts by type.
+pub trait Discriminator {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN];
+}
+
+/// The packed state of a `T` behind its discriminator. Zeroed data the program has not written
+/// yet unpacks as uninitialized, any other prefix is an account of another type.
+fn unpack_discriminator<T: Discriminator>(src: &[u8]) -> Result<&[u8], ProgramError> {
+    if src.len() < DISCRIMINATOR_LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let (discriminator, state) = src.split_at(DISCRIMINATOR_LEN);
+    let is_blank = discriminator == [0; DISCRIMINATOR_LEN] && state.first() == Some(&0);
+    if discriminator != T::DISCRIMINATOR && !is_blank {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(state)
+}
+
+/// Writes the discriminator of `T` and returns the rest of `dst` for its packed state
+fn pack_discriminator<T: Discriminator>(dst: &mut [u8]) -> &mut [u8] {
+    let (discriminator, state) = dst.split_at_mut(DISCRIMINATOR_LEN);
+    discriminator.copy_from_slice(&T::DISCRIMINATOR);
+    state
+}
+
+fn unpack_flag(byte: u8) -> Result<bool, ProgramError> {
+    match byte {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
 impl Pack for Escrow {
-    const LEN: usize = 105;
+    const LEN: usize = DISCRIMINATOR_LEN
+        + 743
+        + SplitRecipient::LEN * MAX_SPLIT_RECIPIENTS
+        + ReleaseCondition::LEN
+        + 96;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        // `Pack::unpack` checks the length itself, callers slicing by hand may not
+        if src.len() != Escrow::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let src: &EscrowLayout = bytemuck::from_bytes(array_ref![
+            unpack_discriminator::<Escrow>(src)?,
+            0,
+            Escrow::LEN - DISCRIMINATOR_LEN
+        ]);
+        let condition = &src.release_condition;
+        if condition.value_len as usize > CONDITION_VALUE_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut split_recipients = [SplitRecipient::default(); MAX_SPLIT_RECIPIENTS];
+        for (recipient, src) in split_recipients.iter_mut().zip(&src.split_recipients) {
+            *recipient = SplitRecipient {
+                token_account: Pubkey::new_from_array(src.token_account),
+                share_bps: u16::from_le_bytes(src.share_bps),
+            };
+        }
+
+        Ok(Escrow {
+            is_initialized: unpack_flag(src.is_initialized)?,
+            initializer_pubkey: Pubkey::new_from_array(src.initializer_pubkey),
+            temp_token_account_pubkey: Pubkey::new_from_array(src.temp_token_account_pubkey),
+            initializer_token_to_receive_account_pubkey: Pubkey::new_from_array(
+                src.initializer_token_to_receive_account_pubkey,
+            ),
+            expected_amount: u64::from_le_bytes(src.expected_amount),
+            oracle_pubkey: Pubkey::new_from_array(src.oracle_pubkey),
+            oracle_max_staleness_slots: u64::from_le_bytes(src.oracle_max_staleness_slots),
+            oracle_max_confidence_bps: u16::from_le_bytes(src.oracle_max_confidence_bps),
+            deposit_mint_decimals: src.deposit_mint_decimals,
+            receive_mint_decimals: src.receive_mint_decimals,
+            curve_start_price: u64::from_le_bytes(src.curve_start_price),
+            curve_slope: u64::from_le_bytes(src.curve_slope),
+            filled_amount: u64::from_le_bytes(src.filled_amount),
+            is_crowdfill: unpack_flag(src.is_crowdfill)?,
+            cancel_locked_until_slot: u64::from_le_bytes(src.cancel_locked_until_slot),
+            reserve_bond_lamports: u64::from_le_bytes(src.reserve_bond_lamports),
+            reserve_slots: u64::from_le_bytes(src.reserve_slots),
+            reserved_by: Pubkey::new_from_array(src.reserved_by),
+            reserved_until_slot: u64::from_le_bytes(src.reserved_until_slot),
+            settle_timeout_slots: u64::from_le_bytes(src.settle_timeout_slots),
+            committed_taker: Pubkey::new_from_array(src.committed_taker),
+            committed_until_slot: u64::from_le_bytes(src.committed_until_slot),
+            payment_vault_pubkey: Pubkey::new_from_array(src.payment_vault_pubkey),
+            committed_taker_token_to_receive_pubkey: Pubkey::new_from_array(
+                src.committed_taker_token_to_receive_pubkey,
+            ),
+            exchange_top_level_only: unpack_flag(src.exchange_top_level_only)?,
+            metadata_uri: MetadataUri::unpack(&src.metadata_uri)?,
+            memo_reference: src.memo_reference,
+            expires_at_slot: u64::from_le_bytes(src.expires_at_slot),
+            allowed_taker: Pubkey::new_from_array(src.allowed_taker),
+            relist_amount: u64::from_le_bytes(src.relist_amount),
+            relist_source_pubkey: Pubkey::new_from_array(src.relist_source_pubkey),
+            auction_bid_end_slot: u64::from_le_bytes(src.auction_bid_end_slot),
+            auction_reveal_end_slot: u64::from_le_bytes(src.auction_reveal_end_slot),
+            best_bid_amount: u64::from_le_bytes(src.best_bid_amount),
+            best_bid_pubkey: Pubkey::new_from_array(src.best_bid_pubkey),
+            raffle_end_slot: u64::from_le_bytes(src.raffle_end_slot),
+            raffle_ticket_count: u64::from_le_bytes(src.raffle_ticket_count),
+            chain_expected_amount: u64::from_le_bytes(src.chain_expected_amount),
+            chain_receive_pubkey: Pubkey::new_from_array(src.chain_receive_pubkey),
+            deposited_amount: u64::from_le_bytes(src.deposited_amount),
+            receive_mint: Pubkey::new_from_array(src.receive_mint),
+            chain_receive_mint: Pubkey::new_from_array(src.chain_receive_mint),
+            operator: Pubkey::new_from_array(src.operator),
+            split_recipients,
+            release_condition: ReleaseCondition {
+                account: Pubkey::new_from_array(condition.account),
+                owner: Pubkey::new_from_array(condition.owner),
+                offset: u16::from_le_bytes(condition.offset),
+                value_len: condition.value_len,
+                value: condition.value,
+                gates_cancel: unpack_flag(condition.gates_cancel)?,
+            },
+            revised_at_slot: u64::from_le_bytes(src.revised_at_slot),
+            terms_hash: src.terms_hash,
+            release_after_timestamp: i64::from_le_bytes(src.release_after_timestamp),
+            release_before_timestamp: i64::from_le_bytes(src.release_before_timestamp),
+            market: Pubkey::new_from_array(src.market),
+            tag: u64::from_le_bytes(src.tag),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst: &mut EscrowLayout = bytemuck::from_bytes_mut(array_mut_ref![
+            pack_discriminator::<Escrow>(dst),
+            0,
+            Escrow::LEN - DISCRIMINATOR_LEN
+        ]);
+        let mut split_recipients = [SplitRecipientLayout::zeroed(); MAX_SPLIT_RECIPIENTS];
+        for (dst, recipient) in split_recipients.iter_mut().zip(&self.split_recipients) {
+            *dst = SplitRecipientLayout {
+                token_account: recipient.token_account.to_bytes(),
+                share_bps: recipient.share_bps.to_le_bytes(),
+            };
+        }
+        let condition = &self.release_condition;
+        *dst = EscrowLayout {
+            is_initialized: self.is_initialized as u8,
+            initializer_pubkey: self.initializer_pubkey.to_bytes(),
+            temp_token_account_pubkey: self.temp_token_account_pubkey.to_bytes(),
+            initializer_token_to_receive_account_pubkey: self
+                .initializer_token_to_receive_account_pubkey
+                .to_bytes(),
+            expected_amount: self.expected_amount.to_le_bytes(),
+            oracle_pubkey: self.oracle_pubkey.to_bytes(),
+            oracle_max_staleness_slots: self.oracle_max_staleness_slots.to_le_bytes(),
+            oracle_max_confidence_bps: self.oracle_max_confidence_bps.to_le_bytes(),
+            deposit_mint_decimals: self.deposit_mint_decimals,
+            receive_mint_decimals: self.receive_mint_decimals,
+            curve_start_price: self.curve_start_price.to_le_bytes(),
+            curve_slope: self.curve_slope.to_le_bytes(),
+            filled_amount: self.filled_amount.to_le_bytes(),
+            is_crowdfill: self.is_crowdfill as u8,
+            cancel_locked_until_slot: self.cancel_locked_until_slot.to_le_bytes(),
+            reserve_bond_lamports: self.reserve_bond_lamports.to_le_bytes(),
+            reserve_slots: self.reserve_slots.to_le_bytes(),
+            reserved_by: self.reserved_by.to_bytes(),
+            reserved_until_slot: self.reserved_until_slot.to_le_bytes(),
+            settle_timeout_slots: self.settle_timeout_slots.to_le_bytes(),
+            committed_taker: self.committed_taker.to_bytes(),
+            committed_until_slot: self.committed_until_slot.to_le_bytes(),
+            payment_vault_pubkey: self.payment_vault_pubkey.to_bytes(),
+            committed_taker_token_to_receive_pubkey: self
+                .committed_taker_token_to_receive_pubkey
+                .to_bytes(),
+            exchange_top_level_only: self.exchange_top_level_only as u8,
+            metadata_uri: self.metadata_uri.0,
+            memo_reference: self.memo_reference,
+            expires_at_slot: self.expires_at_slot.to_le_bytes(),
+            allowed_taker: self.allowed_taker.to_bytes(),
+            relist_amount: self.relist_amount.to_le_bytes(),
+            relist_source_pubkey: self.relist_source_pubkey.to_bytes(),
+            auction_bid_end_slot: self.auction_bid_end_slot.to_le_bytes(),
+            auction_reveal_end_slot: self.auction_reveal_end_slot.to_le_bytes(),
+            best_bid_amount: self.best_bid_amount.to_le_bytes(),
+            best_bid_pubkey: self.best_bid_pubkey.to_bytes(),
+            raffle_end_slot: self.raffle_end_slot.to_le_bytes(),
+            raffle_ticket_count: self.raffle_ticket_count.to_le_bytes(),
+            chain_expected_amount: self.chain_expected_amount.to_le_bytes(),
+            chain_receive_pubkey: self.chain_receive_pubkey.to_bytes(),
+            deposited_amount: self.deposited_amount.to_le_bytes(),
+            receive_mint: self.receive_mint.to_bytes(),
+            chain_receive_mint: self.chain_receive_mint.to_bytes(),
+            operator: self.operator.to_bytes(),
+            split_recipients,
+            release_condition: ReleaseConditionLayout {
+                account: condition.account.to_bytes(),
+                owner: condition.owner.to_bytes(),
+                offset: condition.offset.to_le_bytes(),
+                value_len: condition.value_len,
+                value: condition.value,
+                gates_cancel: condition.gates_cancel as u8,
+            },
+            revised_at_slot: self.revised_at_slot.to_le_bytes(),
+            terms_hash: self.terms_hash,
+            release_after_timestamp: self.release_after_timestamp.to_le_bytes(),
+            release_before_timestamp: self.release_before_timestamp.to_le_bytes(),
+            market: self.market.to_bytes(),
+            tag: self.tag.to_le_bytes(),
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum FillStatus {
+    Open,
+    PartlyFilled,
+    Reserved,
+    Committed,
+    Expired,
+}
+
+impl FillStatus {
+    fn unpack(byte: u8) -> Result<Self, ProgramError> {
+        Ok(match byte {
+            0 => FillStatus::Open,
+            1 => FillStatus::PartlyFilled,
+            2 => FillStatus::Reserved,
+            3 => FillStatus::Committed,
+            4 => FillStatus::Expired,
+            _ => return Err(ProgramError::InvalidAccountData),
+        })
+    }
+}
+
+/// The key fields of an escrow with what GetEscrow derives from it, the return data of
+/// GetEscrow. The whole escrow is longer than the runtime lets a program return
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EscrowView {
+    pub initializer_pubkey: Pubkey,
+    pub temp_token_account_pubkey: Pubkey,
+    pub initializer_token_to_receive_account_pubkey: Pubkey,
+    pub receive_mint: Pubkey,
+    pub expected_amount: u64,
+    pub deposited_amount: u64,
+    pub filled_amount: u64,
+    pub expires_at_slot: u64,
+    pub allowed_taker: Pubkey,
+    pub market: Pubkey,
+    pub terms_hash: [u8; 32],
+    /// bump seed of the `["escrow"]` PDA owning the vault
+    pub pda_bump_seed: u8,
+    /// deposit left in the vault
+    pub vault_amount: u64,
+    pub fill_status: FillStatus,
+}
+
+impl EscrowView {
+    /// The view of `escrow` at `slot`, whose vault holds `vault_amount`
+    pub fn new(escrow: &Escrow, pda_bump_seed: u8, vault_amount: u64, slot: u64) -> Self {
+        EscrowView {
+            initializer_pubkey: escrow.initializer_pubkey,
+            temp_token_account_pubkey: escrow.temp_token_account_pubkey,
+            initializer_token_to_receive_account_pubkey: escrow
+                .initializer_token_to_receive_account_pubkey,
+            receive_mint: escrow.receive_mint,
+            expected_amount: escrow.expected_amount,
+            deposited_amount: escrow.deposited_amount,
+            filled_amount: escrow.filled_amount,
+            expires_at_slot: escrow.expires_at_slot,
+            allowed_taker: escrow.allowed_taker,
+            market: escrow.market,
+            terms_hash: escrow.terms_hash,
+            pda_bump_seed,
+            vault_amount,
+            fill_status: escrow.fill_status(slot),
+        }
+    }
+}
+
+impl Sealed for EscrowView {}
+
+impl IsInitialized for EscrowView {
+    // GetEscrow only views initialized escrows
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+
+impl Pack for EscrowView {
+    const LEN: usize = 266;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src = array_ref![src, 0, Escrow::LEN];
+        let src = array_ref![src, 0, EscrowView::LEN];
         let (
-            is_initialized,
             initializer_pubkey,
             temp_token_account_pubkey,
             initializer_token_to_receive_account_pubkey,
+            receive_mint,
             expected_amount,
-        ) = array_refs![src, 1, 32, 32, 32, 8];
-        let is_initialized = match is_initialized {
-            [0] => false,
-            [1] => true,
-            _ => return Err(ProgramError::InvalidAccountData),
-        };
-
-        Ok(Escrow {
-            is_initialized,
+            deposited_amount,
+            filled_amount,
+            expires_at_slot,
+            allowed_taker,
+            market,
+            terms_hash,
+            pda_bump_seed,
+            vault_amount,
+            fill_status,
+        ) = array_refs![src, 32, 32, 32, 32, 8, 8, 8, 8, 32, 32, 32, 1, 8, 1];
+        Ok(EscrowView {
             initializer_pubkey: Pubkey::new_from_array(*initializer_pubkey),
             temp_token_account_pubkey: Pubkey::new_from_array(*temp_token_account_pubkey),
             initializer_token_to_receive_account_pubkey: Pubkey::new_from_array(
                 *initializer_token_to_receive_account_pubkey,
             ),
+            receive_mint: Pubkey::new_from_array(*receive_mint),
             expected_amount: u64::from_le_bytes(*expected_amount),
+            deposited_amount: u64::from_le_bytes(*deposited_amount),
+            filled_amount: u64::from_le_bytes(*filled_amount),
+            expires_at_slot: u64::from_le_bytes(*expires_at_slot),
+            allowed_taker: Pubkey::new_from_array(*allowed_taker),
+            market: Pubkey::new_from_array(*market),
+            terms_hash: *terms_hash,
+            pda_bump_seed: pda_bump_seed[0],
+            vault_amount: u64::from_le_bytes(*vault_amount),
+            fill_status: FillStatus::unpack(fill_status[0])?,
         })
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst = array_mut_ref![dst, 0, Escrow::LEN];
+        let dst = array_mut_ref![dst, 0, EscrowView::LEN];
         let (
-            is_initialized_dst,
             initializer_pubkey_dst,
             temp_token_account_pubkey_dst,
             initializer_token_to_receive_account_pubkey_dst,
+            receive_mint_dst,
             expected_amount_dst,
-        ) = mut_array_refs![dst, 1, 32, 32, 32, 8];
-        let Escrow {
+            deposited_amount_dst,
+            filled_amount_dst,
+            expires_at_slot_dst,
+            allowed_taker_dst,
+            market_dst,
+            terms_hash_dst,
+            pda_bump_seed_dst,
+            vault_amount_dst,
+            fill_status_dst,
+        ) = mut_array_refs![dst, 32, 32, 32, 32, 8, 8, 8, 8, 32, 32, 32, 1, 8, 1];
+        initializer_pubkey_dst.copy_from_slice(self.initializer_pubkey.as_ref());
+        temp_token_account_pubkey_dst.copy_from_slice(self.temp_token_account_pubkey.as_ref());
+        initializer_token_to_receive_account_pubkey_dst
+            .copy_from_slice(self.initializer_token_to_receive_account_pubkey.as_ref());
+        receive_mint_dst.copy_from_slice(self.receive_mint.as_ref());
+        *expected_amount_dst = self.expected_amount.to_le_bytes();
+        *deposited_amount_dst = self.deposited_amount.to_le_bytes();
+        *filled_amount_dst = self.filled_amount.to_le_bytes();
+        *expires_at_slot_dst = self.expires_at_slot.to_le_bytes();
+        allowed_taker_dst.copy_from_slice(self.allowed_taker.as_ref());
+        market_dst.copy_from_slice(self.market.as_ref());
+        *terms_hash_dst = self.terms_hash;
+        pda_bump_seed_dst[0] = self.pda_bump_seed;
+        *vault_amount_dst = self.vault_amount.to_le_bytes();
+        fill_status_dst[0] = self.fill_status as u8;
+    }
+}
+
+// GetEscrow returns the view, so it must fit the runtime's return data; an oversized view fails
+// to compile, in a form older toolchains without const panics accept
+const _: [(); 0] = [(); (EscrowView::LEN > MAX_RETURN_DATA) as usize];
+
+/// A shared pool where many initializers deposit the same token at a common price
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Pool {
+    pub is_initialized: bool,
+    pub deposit_mint: Pubkey,
+    pub receive_mint: Pubkey,
+    /// token account owned by the pool holding the deposits
+    pub deposit_vault: Pubkey,
+    /// token account owned by the pool collecting what takers pay
+    pub proceeds_vault: Pubkey,
+    /// mantissa of the price in receive base units per deposited base unit
+    pub price: u64,
+    /// the price is `price * 10^price_expo`
+    pub price_expo: i32,
+    /// one share is minted per deposited base unit
+    pub total_shares: u64,
+    pub bump_seed: u8,
+}
+
+impl Pool {
+    pub fn find_address(
+        deposit_mint: &Pubkey,
+        receive_mint: &Pubkey,
+        price: u64,
+        price_expo: i32,
+        program_id: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                b"pool",
+                deposit_mint.as_ref(),
+                receive_mint.as_ref(),
+                &price.to_le_bytes(),
+                &price_expo.to_le_bytes(),
+            ],
+            program_id,
+        )
+    }
+}
+
+impl Sealed for Pool {}
+
+impl Discriminator for Pool {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [241, 154, 109, 4, 17, 177, 109, 188];
+}
+
+impl IsInitialized for Pool {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Pool {
+    const LEN: usize = DISCRIMINATOR_LEN + 150;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![
+            unpack_discriminator::<Pool>(src)?,
+            0,
+            Pool::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
             is_initialized,
-            initializer_pubkey,
-            temp_token_account_pubkey,
-            initializer_token_to_receive_account_pubkey,
-            expected_amount,
+            deposit_mint,
+            receive_mint,
+            deposit_vault,
+            proceeds_vault,
+            price,
+            price_expo,
+            total_shares,
+            bump_seed,
+        ) = array_refs![src, 1, 32, 32, 32, 32, 8, 4, 8, 1];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(Pool {
+            is_initialized,
+            deposit_mint: Pubkey::new_from_array(*deposit_mint),
+            receive_mint: Pubkey::new_from_array(*receive_mint),
+            deposit_vault: Pubkey::new_from_array(*deposit_vault),
+            proceeds_vault: Pubkey::new_from_array(*proceeds_vault),
+            price: u64::from_le_bytes(*price),
+            price_expo: i32::from_le_bytes(*price_expo),
+            total_shares: u64::from_le_bytes(*total_shares),
+            bump_seed: bump_seed[0],
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![
+            pack_discriminator::<Pool>(dst),
+            0,
+            Pool::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized_dst,
+            deposit_mint_dst,
+            receive_mint_dst,
+            deposit_vault_dst,
+            proceeds_vault_dst,
+            price_dst,
+            price_expo_dst,
+            total_shares_dst,
+            bump_seed_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 32, 8, 4, 8, 1];
+        let Pool {
+            is_initialized,
+            deposit_mint,
+            receive_mint,
+            deposit_vault,
+            proceeds_vault,
+            price,
+            price_expo,
+            total_shares,
+            bump_seed,
         } = self;
         is_initialized_dst[0] = *is_initialized as u8;
-        initializer_pubkey_dst.copy_from_slice(initializer_pubkey.as_ref());
-        temp_token_account_pubkey_dst.copy_from_slice(temp_token_account_pubkey.as_ref());
-        initializer_token_to_receive_account_pubkey_dst
-            .copy_from_slice(initializer_token_to_receive_account_pubkey.as_ref());
-        *expected_amount_dst = expected_amount.to_le_bytes();
+        deposit_mint_dst.copy_from_slice(deposit_mint.as_ref());
+        receive_mint_dst.copy_from_slice(receive_mint.as_ref());
+        deposit_vault_dst.copy_from_slice(deposit_vault.as_ref());
+        proceeds_vault_dst.copy_from_slice(proceeds_vault.as_ref());
+        *price_dst = price.to_le_bytes();
+        *price_expo_dst = price_expo.to_le_bytes();
+        *total_shares_dst = total_shares.to_le_bytes();
+        bump_seed_dst[0] = *bump_seed;
+    }
+}
+
+/// A depositor's stake in a `Pool`
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct PoolShare {
+    pub is_initialized: bool,
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub shares: u64,
+}
+
+impl PoolShare {
+    pub fn find_address(pool: &Pubkey, owner: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"pool-share", pool.as_ref(), owner.as_ref()], program_id)
+    }
+}
+
+impl Sealed for PoolShare {}
+
+impl Discriminator for PoolShare {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [129, 116, 245, 199, 168, 39, 223, 204];
+}
+
+impl IsInitialized for PoolShare {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for PoolShare {
+    const LEN: usize = DISCRIMINATOR_LEN + 73;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![
+            unpack_discriminator::<PoolShare>(src)?,
+            0,
+            PoolShare::LEN - DISCRIMINATOR_LEN
+        ];
+        let (is_initialized, pool, owner, shares) = array_refs![src, 1, 32, 32, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(PoolShare {
+            is_initialized,
+            pool: Pubkey::new_from_array(*pool),
+            owner: Pubkey::new_from_array(*owner),
+            shares: u64::from_le_bytes(*shares),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![
+            pack_discriminator::<PoolShare>(dst),
+            0,
+            PoolShare::LEN - DISCRIMINATOR_LEN
+        ];
+        let (is_initialized_dst, pool_dst, owner_dst, shares_dst) =
+            mut_array_refs![dst, 1, 32, 32, 8];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        pool_dst.copy_from_slice(self.pool.as_ref());
+        owner_dst.copy_from_slice(self.owner.as_ref());
+        *shares_dst = self.shares.to_le_bytes();
+    }
+}
+
+pub const MAX_MINT_FEE_TIERS: usize = 16;
+
+/// Fee charged when settling a payment in `mint`
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct MintFeeTier {
+    pub mint: Pubkey,
+    pub fee_bps: u16,
+}
+
+impl MintFeeTier {
+    const LEN: usize = 34;
+}
+
+/// Settings of a market, the program-wide one held by the PDA of `["config"]` and every other by
+/// the PDA of `["config", market]`
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Config {
+    pub is_initialized: bool,
+    /// the only key allowed to change the config
+    pub admin: Pubkey,
+    /// owner of the token accounts settlement fees are paid into
+    pub fee_owner: Pubkey,
+    /// fee in basis points of the payment for mints without a tier of their own
+    pub default_fee_bps: u16,
+    pub fee_tier_count: u8,
+    /// the first `fee_tier_count` entries are in use
+    pub fee_tiers: [MintFeeTier; MAX_MINT_FEE_TIERS],
+    /// flat fee in lamports charged to signing takers in place of the fee in basis points, zero
+    /// taking fees from the token legs
+    pub lamport_fee: u64,
+    /// key the config's address is seeded by, `Pubkey::default()` for the program-wide config
+    pub market: Pubkey,
+    /// settlements in the market are refused while set
+    pub is_paused: bool,
+}
+
+impl Config {
+    pub fn find_address(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"config"], program_id)
+    }
+
+    /// the config of `market`, the program-wide config for `Pubkey::default()`
+    pub fn find_market_address(market: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        if *market == Pubkey::default() {
+            return Self::find_address(program_id);
+        }
+        Pubkey::find_program_address(&[b"config", market.as_ref()], program_id)
+    }
+
+    /// fee in basis points charged on payments in `mint`
+    pub fn fee_bps(&self, mint: &Pubkey) -> u16 {
+        self.fee_tiers()
+            .iter()
+            .find(|tier| tier.mint == *mint)
+            .map_or(self.default_fee_bps, |tier| tier.fee_bps)
+    }
+
+    pub fn fee_tiers(&self) -> &[MintFeeTier] {
+        &self.fee_tiers[..self.fee_tier_count as usize]
+    }
+
+    /// adds or replaces the tier of `mint`, `false` when the table is full
+    pub fn set_fee_tier(&mut self, mint: Pubkey, fee_bps: u16) -> bool {
+        let count = self.fee_tier_count as usize;
+        if let Some(tier) = self.fee_tiers[..count]
+            .iter_mut()
+            .find(|tier| tier.mint == mint)
+        {
+            tier.fee_bps = fee_bps;
+            return true;
+        }
+        if count == MAX_MINT_FEE_TIERS {
+            return false;
+        }
+        self.fee_tiers[count] = MintFeeTier { mint, fee_bps };
+        self.fee_tier_count += 1;
+        true
+    }
+}
+
+impl Sealed for Config {}
+
+impl Discriminator for Config {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [155, 12, 170, 224, 30, 250, 204, 130];
+}
+
+impl IsInitialized for Config {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Config {
+    const LEN: usize = DISCRIMINATOR_LEN + 109 + MintFeeTier::LEN * MAX_MINT_FEE_TIERS;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![
+            unpack_discriminator::<Config>(src)?,
+            0,
+            Config::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized,
+            admin,
+            fee_owner,
+            default_fee_bps,
+            fee_tier_count,
+            fee_tiers_src,
+            lamport_fee,
+            market,
+            is_paused,
+        ) = array_refs![
+            src,
+            1,
+            32,
+            32,
+            2,
+            1,
+            MintFeeTier::LEN * MAX_MINT_FEE_TIERS,
+            8,
+            32,
+            1
+        ];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        if fee_tier_count[0] as usize > MAX_MINT_FEE_TIERS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut fee_tiers = [MintFeeTier::default(); MAX_MINT_FEE_TIERS];
+        for (tier, src) in fee_tiers
+            .iter_mut()
+            .zip(fee_tiers_src.chunks_exact(MintFeeTier::LEN))
+        {
+            let src = array_ref![src, 0, MintFeeTier::LEN];
+            let (mint, fee_bps) = array_refs![src, 32, 2];
+            *tier = MintFeeTier {
+                mint: Pubkey::new_from_array(*mint),
+                fee_bps: u16::from_le_bytes(*fee_bps),
+            };
+        }
+
+        Ok(Config {
+            is_initialized,
+            admin: Pubkey::new_from_array(*admin),
+            fee_owner: Pubkey::new_from_array(*fee_owner),
+            default_fee_bps: u16::from_le_bytes(*default_fee_bps),
+            fee_tier_count: fee_tier_count[0],
+            fee_tiers,
+            lamport_fee: u64::from_le_bytes(*lamport_fee),
+            market: Pubkey::new_from_array(*market),
+            is_paused: unpack_flag(is_paused[0])?,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![
+            pack_discriminator::<Config>(dst),
+            0,
+            Config::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized_dst,
+            admin_dst,
+            fee_owner_dst,
+            default_fee_bps_dst,
+            fee_tier_count_dst,
+            fee_tiers_dst,
+            lamport_fee_dst,
+            market_dst,
+            is_paused_dst,
+        ) = mut_array_refs![
+            dst,
+            1,
+            32,
+            32,
+            2,
+            1,
+            MintFeeTier::LEN * MAX_MINT_FEE_TIERS,
+            8,
+            32,
+            1
+        ];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        admin_dst.copy_from_slice(self.admin.as_ref());
+        fee_owner_dst.copy_from_slice(self.fee_owner.as_ref());
+        *default_fee_bps_dst = self.default_fee_bps.to_le_bytes();
+        fee_tier_count_dst[0] = self.fee_tier_count;
+        *lamport_fee_dst = self.lamport_fee.to_le_bytes();
+        market_dst.copy_from_slice(self.market.as_ref());
+        is_paused_dst[0] = self.is_paused as u8;
+        for (tier, dst) in self
+            .fee_tiers
+            .iter()
+            .zip(fee_tiers_dst.chunks_exact_mut(MintFeeTier::LEN))
+        {
+            let dst = array_mut_ref![dst, 0, MintFeeTier::LEN];
+            let (mint_dst, fee_bps_dst) = mut_array_refs![dst, 32, 2];
+            mint_dst.copy_from_slice(tier.mint.as_ref());
+            *fee_bps_dst = tier.fee_bps.to_le_bytes();
+        }
+    }
+}
+
+pub const MAX_LISTED_MINTS: usize = 32;
+
+pub const MAX_MINT_MINIMUMS: usize = 16;
+
+/// Smallest amount of `mint` an escrow may deposit or ask for
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct MintMinimum {
+    pub mint: Pubkey,
+    pub min_amount: u64,
+}
+
+impl MintMinimum {
+    const LEN: usize = 40;
+}
+
+/// Mints banned from new escrows, or the only mints they may trade when `is_allow_list`, held by
+/// the PDA of `["mint_list"]` for the program-wide market and of `["mint_list", market]` for
+/// every other, and maintained by the admin of the market's config
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct MintList {
+    pub is_initialized: bool,
+    pub is_allow_list: bool,
+    pub mint_count: u8,
+    /// the first `mint_count` entries are in use
+    pub mints: [Pubkey; MAX_LISTED_MINTS],
+    /// smallest deposit new escrows may open with, zero for any
+    pub min_deposit_amount: u64,
+    pub minimum_count: u8,
+    /// the first `minimum_count` entries are in use
+    pub minimums: [MintMinimum; MAX_MINT_MINIMUMS],
+}
+
+impl MintList {
+    pub fn find_address(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"mint_list"], program_id)
+    }
+
+    /// the mint list of `market`, the program-wide mint list for `Pubkey::default()`
+    pub fn find_market_address(market: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        if *market == Pubkey::default() {
+            return Self::find_address(program_id);
+        }
+        Pubkey::find_program_address(&[b"mint_list", market.as_ref()], program_id)
+    }
+
+    pub fn mints(&self) -> &[Pubkey] {
+        &self.mints[..self.mint_count as usize]
+    }
+
+    /// whether new escrows may trade `mint`
+    pub fn allows(&self, mint: &Pubkey) -> bool {
+        self.mints().contains(mint) == self.is_allow_list
+    }
+
+    /// adds `mint` to the list or removes it, `false` when the list is full
+    pub fn set_listed(&mut self, mint: Pubkey, listed: bool) -> bool {
+        let count = self.mint_count as usize;
+        match self.mints[..count]
+            .iter()
+            .position(|listed_mint| *listed_mint == mint)
+        {
+            Some(index) if !listed => {
+                self.mints[index] = self.mints[count - 1];
+                self.mints[count - 1] = Pubkey::default();
+                self.mint_count -= 1;
+            }
+            None if listed => {
+                if count == MAX_LISTED_MINTS {
+                    return false;
+                }
+                self.mints[count] = mint;
+                self.mint_count += 1;
+            }
+            _ => {}
+        }
+        true
+    }
+
+    pub fn minimums(&self) -> &[MintMinimum] {
+        &self.minimums[..self.minimum_count as usize]
+    }
+
+    /// smallest deposit of `mint`, the minimum of its own in place of `min_deposit_amount`
+    pub fn min_deposit(&self, mint: &Pubkey) -> u64 {
+        self.minimum(mint).unwrap_or(self.min_deposit_amount)
+    }
+
+    /// smallest amount of `mint` an escrow may ask for
+    pub fn min_expected(&self, mint: &Pubkey) -> u64 {
+        self.minimum(mint).unwrap_or_default()
+    }
+
+    fn minimum(&self, mint: &Pubkey) -> Option<u64> {
+        self.minimums()
+            .iter()
+            .find(|minimum| minimum.mint == *mint)
+            .map(|minimum| minimum.min_amount)
+    }
+
+    /// adds or replaces the minimum of `mint`, `false` when the table is full
+    pub fn set_minimum(&mut self, mint: Pubkey, min_amount: u64) -> bool {
+        let count = self.minimum_count as usize;
+        if let Some(minimum) = self.minimums[..count]
+            .iter_mut()
+            .find(|minimum| minimum.mint == mint)
+        {
+            minimum.min_amount = min_amount;
+            return true;
+        }
+        if count == MAX_MINT_MINIMUMS {
+            return false;
+        }
+        self.minimums[count] = MintMinimum { mint, min_amount };
+        self.minimum_count += 1;
+        true
+    }
+}
+
+impl Sealed for MintList {}
+
+impl Discriminator for MintList {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [138, 34, 30, 123, 29, 32, 204, 102];
+}
+
+impl IsInitialized for MintList {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for MintList {
+    const LEN: usize =
+        DISCRIMINATOR_LEN + 12 + 32 * MAX_LISTED_MINTS + MintMinimum::LEN * MAX_MINT_MINIMUMS;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![
+            unpack_discriminator::<MintList>(src)?,
+            0,
+            MintList::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized,
+            is_allow_list,
+            mint_count,
+            mints_src,
+            min_deposit_amount,
+            minimum_count,
+            minimums_src,
+        ) = array_refs![
+            src,
+            1,
+            1,
+            1,
+            32 * MAX_LISTED_MINTS,
+            8,
+            1,
+            MintMinimum::LEN * MAX_MINT_MINIMUMS
+        ];
+        let unpack_bool = |byte: &[u8; 1]| match byte {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(ProgramError::InvalidAccountData),
+        };
+        if mint_count[0] as usize > MAX_LISTED_MINTS
+            || minimum_count[0] as usize > MAX_MINT_MINIMUMS
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut mints = [Pubkey::default(); MAX_LISTED_MINTS];
+        for (mint, src) in mints.iter_mut().zip(mints_src.chunks_exact(32)) {
+            *mint = Pubkey::new_from_array(*array_ref![src, 0, 32]);
+        }
+        let mut minimums = [MintMinimum::default(); MAX_MINT_MINIMUMS];
+        for (minimum, src) in minimums
+            .iter_mut()
+            .zip(minimums_src.chunks_exact(MintMinimum::LEN))
+        {
+            let src = array_ref![src, 0, MintMinimum::LEN];
+            let (mint, min_amount) = array_refs![src, 32, 8];
+            *minimum = MintMinimum {
+                mint: Pubkey::new_from_array(*mint),
+                min_amount: u64::from_le_bytes(*min_amount),
+            };
+        }
+
+        Ok(MintList {
+            is_initialized: unpack_bool(is_initialized)?,
+            is_allow_list: unpack_bool(is_allow_list)?,
+            mint_count: mint_count[0],
+            mints,
+            min_deposit_amount: u64::from_le_bytes(*min_deposit_amount),
+            minimum_count: minimum_count[0],
+            minimums,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![
+            pack_discriminator::<MintList>(dst),
+            0,
+            MintList::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized_dst,
+            is_allow_list_dst,
+            mint_count_dst,
+            mints_dst,
+            min_deposit_amount_dst,
+            minimum_count_dst,
+            minimums_dst,
+        ) = mut_array_refs![
+            dst,
+            1,
+            1,
+            1,
+            32 * MAX_LISTED_MINTS,
+            8,
+            1,
+            MintMinimum::LEN * MAX_MINT_MINIMUMS
+        ];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        is_allow_list_dst[0] = self.is_allow_list as u8;
+        mint_count_dst[0] = self.mint_count;
+        for (mint, dst) in self.mints.iter().zip(mints_dst.chunks_exact_mut(32)) {
+            dst.copy_from_slice(mint.as_ref());
+        }
+        *min_deposit_amount_dst = self.min_deposit_amount.to_le_bytes();
+        minimum_count_dst[0] = self.minimum_count;
+        for (minimum, dst) in self
+            .minimums
+            .iter()
+            .zip(minimums_dst.chunks_exact_mut(MintMinimum::LEN))
+        {
+            let dst = array_mut_ref![dst, 0, MintMinimum::LEN];
+            let (mint_dst, min_amount_dst) = mut_array_refs![dst, 32, 8];
+            mint_dst.copy_from_slice(minimum.mint.as_ref());
+            *min_amount_dst = minimum.min_amount.to_le_bytes();
+        }
+    }
+}
+
+/// Running totals of escrow fills selling `mint`, held by the PDA of `["mint_volume", mint]`
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct MintVolume {
+    pub is_initialized: bool,
+    pub mint: Pubkey,
+    /// sum of every fill, wider than a supply since tokens trade many times over
+    pub filled_amount: u128,
+    pub fill_count: u64,
+}
+
+impl MintVolume {
+    pub fn find_address(mint: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"mint_volume", mint.as_ref()], program_id)
+    }
+}
+
+impl Sealed for MintVolume {}
+
+impl Discriminator for MintVolume {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [149, 27, 84, 109, 241, 88, 129, 127];
+}
+
+impl IsInitialized for MintVolume {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for MintVolume {
+    const LEN: usize = DISCRIMINATOR_LEN + 57;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![
+            unpack_discriminator::<MintVolume>(src)?,
+            0,
+            MintVolume::LEN - DISCRIMINATOR_LEN
+        ];
+        let (is_initialized, mint, filled_amount, fill_count) = array_refs![src, 1, 32, 16, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(MintVolume {
+            is_initialized,
+            mint: Pubkey::new_from_array(*mint),
+            filled_amount: u128::from_le_bytes(*filled_amount),
+            fill_count: u64::from_le_bytes(*fill_count),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![
+            pack_discriminator::<MintVolume>(dst),
+            0,
+            MintVolume::LEN - DISCRIMINATOR_LEN
+        ];
+        let (is_initialized_dst, mint_dst, filled_amount_dst, fill_count_dst) =
+            mut_array_refs![dst, 1, 32, 16, 8];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        mint_dst.copy_from_slice(self.mint.as_ref());
+        *filled_amount_dst = self.filled_amount.to_le_bytes();
+        *fill_count_dst = self.fill_count.to_le_bytes();
+    }
+}
+
+/// Default terms a trader posts escrows from with InitFromTemplate
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Template {
+    pub is_initialized: bool,
+    /// the only initializer allowed to post escrows from the template
+    pub owner: Pubkey,
+    pub deposit_mint: Pubkey,
+    pub receive_mint: Pubkey,
+    /// mantissa of the price in receive base units per deposited base unit
+    pub price: u64,
+    /// the price is `price * 10^price_expo`
+    pub price_expo: i32,
+    /// how many slots escrows stay open for, zero when they never expire
+    pub expiry_slots: u64,
+    /// the only taker of the escrows, `Pubkey::default()` for anyone
+    pub allowed_taker: Pubkey,
+}
+
+impl Sealed for Template {}
+
+impl Discriminator for Template {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [43, 26, 88, 69, 69, 96, 9, 79];
+}
+
+impl IsInitialized for Template {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Template {
+    const LEN: usize = DISCRIMINATOR_LEN + 149;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![
+            unpack_discriminator::<Template>(src)?,
+            0,
+            Template::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized,
+            owner,
+            deposit_mint,
+            receive_mint,
+            price,
+            price_expo,
+            expiry_slots,
+            allowed_taker,
+        ) = array_refs![src, 1, 32, 32, 32, 8, 4, 8, 32];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(Template {
+            is_initialized,
+            owner: Pubkey::new_from_array(*owner),
+            deposit_mint: Pubkey::new_from_array(*deposit_mint),
+            receive_mint: Pubkey::new_from_array(*receive_mint),
+            price: u64::from_le_bytes(*price),
+            price_expo: i32::from_le_bytes(*price_expo),
+            expiry_slots: u64::from_le_bytes(*expiry_slots),
+            allowed_taker: Pubkey::new_from_array(*allowed_taker),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![
+            pack_discriminator::<Template>(dst),
+            0,
+            Template::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized_dst,
+            owner_dst,
+            deposit_mint_dst,
+            receive_mint_dst,
+            price_dst,
+            price_expo_dst,
+            expiry_slots_dst,
+            allowed_taker_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 8, 4, 8, 32];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        owner_dst.copy_from_slice(self.owner.as_ref());
+        deposit_mint_dst.copy_from_slice(self.deposit_mint.as_ref());
+        receive_mint_dst.copy_from_slice(self.receive_mint.as_ref());
+        *price_dst = self.price.to_le_bytes();
+        *price_expo_dst = self.price_expo.to_le_bytes();
+        *expiry_slots_dst = self.expiry_slots.to_le_bytes();
+        allowed_taker_dst.copy_from_slice(self.allowed_taker.as_ref());
+    }
+}
+
+/// A sealed bid on an auction escrow, held by the PDA of `["bid", escrow, bidder]`
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Bid {
+    pub is_initialized: bool,
+    pub escrow: Pubkey,
+    pub bidder: Pubkey,
+    /// `Bid::commitment` of the bid amount, the bidder's salt and the bidder
+    pub commitment: [u8; 32],
+    /// token account owned by the PDA holding the bidder's deposit, at least the bid amount
+    pub deposit_vault_pubkey: Pubkey,
+    /// copied from the escrow, so the deposit can be refunded after the escrow is closed
+    pub reveal_end_slot: u64,
+    pub is_revealed: bool,
+    pub revealed_amount: u64,
+}
+
+impl Bid {
+    pub fn find_address(escrow: &Pubkey, bidder: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"bid", escrow.as_ref(), bidder.as_ref()], program_id)
+    }
+
+    /// hash a bidder commits to, binding the bidder so nobody can replay their reveal
+    pub fn commitment(amount: u64, salt: &[u8; 32], bidder: &Pubkey) -> [u8; 32] {
+        hashv(&[&amount.to_le_bytes(), salt, bidder.as_ref()]).to_bytes()
+    }
+}
+
+impl Sealed for Bid {}
+
+impl Discriminator for Bid {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [143, 246, 48, 245, 42, 145, 180, 88];
+}
+
+impl IsInitialized for Bid {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Bid {
+    const LEN: usize = DISCRIMINATOR_LEN + 146;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![
+            unpack_discriminator::<Bid>(src)?,
+            0,
+            Bid::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized,
+            escrow,
+            bidder,
+            commitment,
+            deposit_vault_pubkey,
+            reveal_end_slot,
+            is_revealed,
+            revealed_amount,
+        ) = array_refs![src, 1, 32, 32, 32, 32, 8, 1, 8];
+        let unpack_bool = |byte: &[u8; 1]| match byte {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(Bid {
+            is_initialized: unpack_bool(is_initialized)?,
+            escrow: Pubkey::new_from_array(*escrow),
+            bidder: Pubkey::new_from_array(*bidder),
+            commitment: *commitment,
+            deposit_vault_pubkey: Pubkey::new_from_array(*deposit_vault_pubkey),
+            reveal_end_slot: u64::from_le_bytes(*reveal_end_slot),
+            is_revealed: unpack_bool(is_revealed)?,
+            revealed_amount: u64::from_le_bytes(*revealed_amount),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![
+            pack_discriminator::<Bid>(dst),
+            0,
+            Bid::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized_dst,
+            escrow_dst,
+            bidder_dst,
+            commitment_dst,
+            deposit_vault_pubkey_dst,
+            reveal_end_slot_dst,
+            is_revealed_dst,
+            revealed_amount_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 32, 8, 1, 8];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        escrow_dst.copy_from_slice(self.escrow.as_ref());
+        bidder_dst.copy_from_slice(self.bidder.as_ref());
+        *commitment_dst = self.commitment;
+        deposit_vault_pubkey_dst.copy_from_slice(self.deposit_vault_pubkey.as_ref());
+        *reveal_end_slot_dst = self.reveal_end_slot.to_le_bytes();
+        is_revealed_dst[0] = self.is_revealed as u8;
+        *revealed_amount_dst = self.revealed_amount.to_le_bytes();
+    }
+}
+
+/// A raffle ticket, held by the PDA of `["ticket", escrow, index]` with the index little endian
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Ticket {
+    pub is_initialized: bool,
+    pub escrow: Pubkey,
+    pub buyer: Pubkey,
+    pub index: u64,
+}
+
+impl Ticket {
+    pub fn find_address(escrow: &Pubkey, index: u64, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[b"ticket", escrow.as_ref(), &index.to_le_bytes()],
+            program_id,
+        )
+    }
+}
+
+impl Sealed for Ticket {}
+
+impl Discriminator for Ticket {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [41, 228, 24, 165, 78, 90, 235, 200];
+}
+
+impl IsInitialized for Ticket {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Ticket {
+    const LEN: usize = DISCRIMINATOR_LEN + 73;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![
+            unpack_discriminator::<Ticket>(src)?,
+            0,
+            Ticket::LEN - DISCRIMINATOR_LEN
+        ];
+        let (is_initialized, escrow, buyer, index) = array_refs![src, 1, 32, 32, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(Ticket {
+            is_initialized,
+            escrow: Pubkey::new_from_array(*escrow),
+            buyer: Pubkey::new_from_array(*buyer),
+            index: u64::from_le_bytes(*index),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![
+            pack_discriminator::<Ticket>(dst),
+            0,
+            Ticket::LEN - DISCRIMINATOR_LEN
+        ];
+        let (is_initialized_dst, escrow_dst, buyer_dst, index_dst) =
+            mut_array_refs![dst, 1, 32, 32, 8];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        escrow_dst.copy_from_slice(self.escrow.as_ref());
+        buyer_dst.copy_from_slice(self.buyer.as_ref());
+        *index_dst = self.index.to_le_bytes();
+    }
+}
+
+/// A request for quote: a buyer's payment held by the PDA, offered for the deposit of whichever
+/// maker's quote the buyer accepts
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Request {
+    pub is_initialized: bool,
+    pub buyer: Pubkey,
+    /// token account owned by the PDA holding the payment
+    pub payment_vault_pubkey: Pubkey,
+    /// buyer's token account for the token they want
+    pub buyer_token_to_receive_pubkey: Pubkey,
+    pub payment_mint: Pubkey,
+    pub wanted_mint: Pubkey,
+    /// smallest amount of the wanted token a quote can offer
+    pub min_amount: u64,
+}
+
+impl Sealed for Request {}
+
+impl Discriminator for Request {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [125, 172, 150, 161, 162, 115, 39, 71];
+}
+
+impl IsInitialized for Request {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Request {
+    const LEN: usize = DISCRIMINATOR_LEN + 169;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![
+            unpack_discriminator::<Request>(src)?,
+            0,
+            Request::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized,
+            buyer,
+            payment_vault_pubkey,
+            buyer_token_to_receive_pubkey,
+            payment_mint,
+            wanted_mint,
+            min_amount,
+        ) = array_refs![src, 1, 32, 32, 32, 32, 32, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(Request {
+            is_initialized,
+            buyer: Pubkey::new_from_array(*buyer),
+            payment_vault_pubkey: Pubkey::new_from_array(*payment_vault_pubkey),
+            buyer_token_to_receive_pubkey: Pubkey::new_from_array(*buyer_token_to_receive_pubkey),
+            payment_mint: Pubkey::new_from_array(*payment_mint),
+            wanted_mint: Pubkey::new_from_array(*wanted_mint),
+            min_amount: u64::from_le_bytes(*min_amount),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![
+            pack_discriminator::<Request>(dst),
+            0,
+            Request::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized_dst,
+            buyer_dst,
+            payment_vault_pubkey_dst,
+            buyer_token_to_receive_pubkey_dst,
+            payment_mint_dst,
+            wanted_mint_dst,
+            min_amount_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 32, 32, 8];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        buyer_dst.copy_from_slice(self.buyer.as_ref());
+        payment_vault_pubkey_dst.copy_from_slice(self.payment_vault_pubkey.as_ref());
+        buyer_token_to_receive_pubkey_dst
+            .copy_from_slice(self.buyer_token_to_receive_pubkey.as_ref());
+        payment_mint_dst.copy_from_slice(self.payment_mint.as_ref());
+        wanted_mint_dst.copy_from_slice(self.wanted_mint.as_ref());
+        *min_amount_dst = self.min_amount.to_le_bytes();
+    }
+}
+
+/// A maker's quote on a request, held by the PDA of `["quote", request, maker]`. The quote
+/// offers the whole deposit for the request's payment
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Quote {
+    pub is_initialized: bool,
+    pub request: Pubkey,
+    pub maker: Pubkey,
+    /// token account owned by the PDA holding the maker's deposit of the wanted token
+    pub deposit_vault_pubkey: Pubkey,
+    /// maker's token account for the payment token
+    pub maker_token_to_receive_pubkey: Pubkey,
+}
+
+impl Quote {
+    pub fn find_address(request: &Pubkey, maker: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"quote", request.as_ref(), maker.as_ref()], program_id)
+    }
+}
+
+impl Sealed for Quote {}
+
+impl Discriminator for Quote {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [167, 202, 20, 198, 228, 66, 105, 208];
+}
+
+impl IsInitialized for Quote {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Quote {
+    const LEN: usize = DISCRIMINATOR_LEN + 129;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![
+            unpack_discriminator::<Quote>(src)?,
+            0,
+            Quote::LEN - DISCRIMINATOR_LEN
+        ];
+        let (is_initialized, request, maker, deposit_vault_pubkey, maker_token_to_receive_pubkey) =
+            array_refs![src, 1, 32, 32, 32, 32];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(Quote {
+            is_initialized,
+            request: Pubkey::new_from_array(*request),
+            maker: Pubkey::new_from_array(*maker),
+            deposit_vault_pubkey: Pubkey::new_from_array(*deposit_vault_pubkey),
+            maker_token_to_receive_pubkey: Pubkey::new_from_array(*maker_token_to_receive_pubkey),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![
+            pack_discriminator::<Quote>(dst),
+            0,
+            Quote::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized_dst,
+            request_dst,
+            maker_dst,
+            deposit_vault_pubkey_dst,
+            maker_token_to_receive_pubkey_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 32];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        request_dst.copy_from_slice(self.request.as_ref());
+        maker_dst.copy_from_slice(self.maker.as_ref());
+        deposit_vault_pubkey_dst.copy_from_slice(self.deposit_vault_pubkey.as_ref());
+        maker_token_to_receive_pubkey_dst
+            .copy_from_slice(self.maker_token_to_receive_pubkey.as_ref());
+    }
+}
+
+/// A compressed NFT owned by the PDA, offered for `expected_amount` of a token
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct CnftEscrow {
+    pub is_initialized: bool,
+    pub initializer_pubkey: Pubkey,
+    pub initializer_token_to_receive_account_pubkey: Pubkey,
+    /// the tree holding the compressed NFT
+    pub merkle_tree: Pubkey,
+    pub leaf: Leaf,
+    pub expected_amount: u64,
+}
+
+impl Sealed for CnftEscrow {}
+
+impl Discriminator for CnftEscrow {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [151, 23, 149, 217, 204, 225, 28, 120];
+}
+
+impl IsInitialized for CnftEscrow {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for CnftEscrow {
+    const LEN: usize = DISCRIMINATOR_LEN + 181;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![
+            unpack_discriminator::<CnftEscrow>(src)?,
+            0,
+            CnftEscrow::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized,
+            initializer_pubkey,
+            initializer_token_to_receive_account_pubkey,
+            merkle_tree,
+            data_hash,
+            creator_hash,
+            nonce,
+            index,
+            expected_amount,
+        ) = array_refs![src, 1, 32, 32, 32, 32, 32, 8, 4, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(CnftEscrow {
+            is_initialized,
+            initializer_pubkey: Pubkey::new_from_array(*initializer_pubkey),
+            initializer_token_to_receive_account_pubkey: Pubkey::new_from_array(
+                *initializer_token_to_receive_account_pubkey,
+            ),
+            merkle_tree: Pubkey::new_from_array(*merkle_tree),
+            leaf: Leaf {
+                data_hash: *data_hash,
+                creator_hash: *creator_hash,
+                nonce: u64::from_le_bytes(*nonce),
+                index: u32::from_le_bytes(*index),
+            },
+            expected_amount: u64::from_le_bytes(*expected_amount),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![
+            pack_discriminator::<CnftEscrow>(dst),
+            0,
+            CnftEscrow::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized_dst,
+            initializer_pubkey_dst,
+            initializer_token_to_receive_account_pubkey_dst,
+            merkle_tree_dst,
+            data_hash_dst,
+            creator_hash_dst,
+            nonce_dst,
+            index_dst,
+            expected_amount_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 32, 32, 8, 4, 8];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        initializer_pubkey_dst.copy_from_slice(self.initializer_pubkey.as_ref());
+        initializer_token_to_receive_account_pubkey_dst
+            .copy_from_slice(self.initializer_token_to_receive_account_pubkey.as_ref());
+        merkle_tree_dst.copy_from_slice(self.merkle_tree.as_ref());
+        *data_hash_dst = self.leaf.data_hash;
+        *creator_hash_dst = self.leaf.creator_hash;
+        *nonce_dst = self.leaf.nonce.to_le_bytes();
+        *index_dst = self.leaf.index.to_le_bytes();
+        *expected_amount_dst = self.expected_amount.to_le_bytes();
+    }
+}
+
+/// A Metaplex Core asset owned by the PDA, offered for `expected_amount` of a token
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CoreEscrow {
+    pub is_initialized: bool,
+    pub initializer_pubkey: Pubkey,
+    pub initializer_token_to_receive_account_pubkey: Pubkey,
+    pub asset: Pubkey,
+    /// the asset's update authority at init, which it must still have when bought
+    pub update_authority: UpdateAuthority,
+    pub expected_amount: u64,
+}
+
+impl Sealed for CoreEscrow {}
+
+impl Discriminator for CoreEscrow {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [82, 56, 254, 38, 174, 115, 36, 38];
+}
+
+impl IsInitialized for CoreEscrow {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for CoreEscrow {
+    const LEN: usize = DISCRIMINATOR_LEN + 138;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![
+            unpack_discriminator::<CoreEscrow>(src)?,
+            0,
+            CoreEscrow::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized,
+            initializer_pubkey,
+            initializer_token_to_receive_account_pubkey,
+            asset,
+            update_authority_kind,
+            update_authority,
+            expected_amount,
+        ) = array_refs![src, 1, 32, 32, 32, 1, 32, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let update_authority = match update_authority_kind {
+            [0] => UpdateAuthority::None,
+            [1] => UpdateAuthority::Address(Pubkey::new_from_array(*update_authority)),
+            [2] => UpdateAuthority::Collection(Pubkey::new_from_array(*update_authority)),
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(CoreEscrow {
+            is_initialized,
+            initializer_pubkey: Pubkey::new_from_array(*initializer_pubkey),
+            initializer_token_to_receive_account_pubkey: Pubkey::new_from_array(
+                *initializer_token_to_receive_account_pubkey,
+            ),
+            asset: Pubkey::new_from_array(*asset),
+            update_authority,
+            expected_amount: u64::from_le_bytes(*expected_amount),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![
+            pack_discriminator::<CoreEscrow>(dst),
+            0,
+            CoreEscrow::LEN - DISCRIMINATOR_LEN
+        ];
+        let (
+            is_initialized_dst,
+            initializer_pubkey_dst,
+            initializer_token_to_receive_account_pubkey_dst,
+            asset_dst,
+            update_authority_kind_dst,
+            update_authority_dst,
+            expected_amount_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 1, 32, 8];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        initializer_pubkey_dst.copy_from_slice(self.initializer_pubkey.as_ref());
+        initializer_token_to_receive_account_pubkey_dst
+            .copy_from_slice(self.initializer_token_to_receive_account_pubkey.as_ref());
+        asset_dst.copy_from_slice(self.asset.as_ref());
+        let (kind, authority) = match self.update_authority {
+            UpdateAuthority::None => (0, Pubkey::default()),
+            UpdateAuthority::Address(authority) => (1, authority),
+            UpdateAuthority::Collection(collection) => (2, collection),
+        };
+        update_authority_kind_dst[0] = kind;
+        update_authority_dst.copy_from_slice(authority.as_ref());
+        *expected_amount_dst = self.expected_amount.to_le_bytes();
+    }
+}
+
+/// Per-user counter of the intents relayed on their behalf, at the PDA of
+/// `["relay_nonce", user]`
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct RelayNonce {
+    pub is_initialized: bool,
+    pub user: Pubkey,
+    /// the nonce the user's next intent must carry
+    pub next_nonce: u64,
+}
+
+impl RelayNonce {
+    pub fn find_address(user: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"relay_nonce", user.as_ref()], program_id)
+    }
+}
+
+impl Sealed for RelayNonce {}
+
+impl Discriminator for RelayNonce {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [8, 4, 238, 138, 204, 40, 192, 8];
+}
+
+impl IsInitialized for RelayNonce {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for RelayNonce {
+    const LEN: usize = DISCRIMINATOR_LEN + 41;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![
+            unpack_discriminator::<RelayNonce>(src)?,
+            0,
+            RelayNonce::LEN - DISCRIMINATOR_LEN
+        ];
+        let (is_initialized, user, next_nonce) = array_refs![src, 1, 32, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(RelayNonce {
+            is_initialized,
+            user: Pubkey::new_from_array(*user),
+            next_nonce: u64::from_le_bytes(*next_nonce),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![
+            pack_discriminator::<RelayNonce>(dst),
+            0,
+            RelayNonce::LEN - DISCRIMINATOR_LEN
+        ];
+        let (is_initialized_dst, user_dst, next_nonce_dst) = mut_array_refs![dst, 1, 32, 8];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        user_dst.copy_from_slice(self.user.as_ref());
+        *next_nonce_dst = self.next_nonce.to_le_bytes();
+    }
+}
+
+/// Session permission to open escrows for the maker
+pub const SESSION_CAN_CREATE: u8 = 1;
+
+/// Session permission to cancel the maker's escrows
+pub const SESSION_CAN_CANCEL: u8 = 2;
+
+/// An ephemeral key allowed to open and cancel offers for `maker` until `expires_at_slot`, at
+/// the PDA of `["session", maker, session_key]`
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Session {
+    pub is_initialized: bool,
+    pub maker: Pubkey,
+    pub session_key: Pubkey,
+    /// first slot the session key is refused at
+    pub expires_at_slot: u64,
+    /// `SESSION_CAN_CREATE` and `SESSION_CAN_CANCEL` bits
+    pub permissions: u8,
+}
+
+impl Session {
+    pub fn find_address(maker: &Pubkey, session_key: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[b"session", maker.as_ref(), session_key.as_ref()],
+            program_id,
+        )
+    }
+
+    pub fn is_expired_at(&self, slot: u64) -> bool {
+        slot >= self.expires_at_slot
+    }
+
+    pub fn permits(&self, permission: u8) -> bool {
+        self.permissions & permission == permission
+    }
+}
+
+impl Sealed for Session {}
+
+impl Discriminator for Session {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [243, 81, 72, 115, 214, 188, 72, 144];
+}
+
+impl IsInitialized for Session {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Session {
+    const LEN: usize = DISCRIMINATOR_LEN + 74;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![
+            unpack_discriminator::<Session>(src)?,
+            0,
+            Session::LEN - DISCRIMINATOR_LEN
+        ];
+        let (is_initialized, maker, session_key, expires_at_slot, permissions) =
+            array_refs![src, 1, 32, 32, 8, 1];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(Session {
+            is_initialized,
+            maker: Pubkey::new_from_array(*maker),
+            session_key: Pubkey::new_from_array(*session_key),
+            expires_at_slot: u64::from_le_bytes(*expires_at_slot),
+            permissions: permissions[0],
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![
+            pack_discriminator::<Session>(dst),
+            0,
+            Session::LEN - DISCRIMINATOR_LEN
+        ];
+        let (is_initialized_dst, maker_dst, session_key_dst, expires_at_slot_dst, permissions_dst) =
+            mut_array_refs![dst, 1, 32, 32, 8, 1];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        maker_dst.copy_from_slice(self.maker.as_ref());
+        session_key_dst.copy_from_slice(self.session_key.as_ref());
+        *expires_at_slot_dst = self.expires_at_slot.to_le_bytes();
+        permissions_dst[0] = self.permissions;
+    }
+}
+
+/// An admin's pending recovery of a bricked escrow's deposit to its initializer, at the PDA of
+/// `["recovery", escrow]`
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Recovery {
+    pub is_initialized: bool,
+    pub escrow: Pubkey,
+    /// the admin who proposed the recovery, refunded the account's rent
+    pub proposer: Pubkey,
+    /// unix timestamp the recovery can be executed from
+    pub executable_at: i64,
+}
+
+impl Recovery {
+    pub fn find_address(escrow: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"recovery", escrow.as_ref()], program_id)
+    }
+}
+
+impl Sealed for Recovery {}
+
+impl Discriminator for Recovery {
+    const DISCRIMINATOR: [u8; DISCRIMINATOR_LEN] = [242, 166, 34, 227, 71, 217, 155, 103];
+}
+
+impl IsInitialized for Recovery {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Recovery {
+    const LEN: usize = DISCRIMINATOR_LEN + 73;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![
+            unpack_discriminator::<Recovery>(src)?,
+            0,
+            Recovery::LEN - DISCRIMINATOR_LEN
+        ];
+        let (is_initialized, escrow, proposer, executable_at) = array_refs![src, 1, 32, 32, 8];
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(Recovery {
+            is_initialized,
+            escrow: Pubkey::new_from_array(*escrow),
+            proposer: Pubkey::new_from_array(*proposer),
+            executable_at: i64::from_le_bytes(*executable_at),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![
+            pack_discriminator::<Recovery>(dst),
+            0,
+            Recovery::LEN - DISCRIMINATOR_LEN
+        ];
+        let (is_initialized_dst, escrow_dst, proposer_dst, executable_at_dst) =
+            mut_array_refs![dst, 1, 32, 32, 8];
+        is_initialized_dst[0] = self.is_initialized as u8;
+        escrow_dst.copy_from_slice(self.escrow.as_ref());
+        proposer_dst.copy_from_slice(self.proposer.as_ref());
+        *executable_at_dst = self.executable_at.to_le_bytes();
+    }
+}
+
+/// Any program account, decoded by the discriminator it starts with. Each variant is unpacked by
+/// its type's own `Pack` impl, the one the processor uses, so clients decoding arbitrary program
+/// accounts read them exactly as the program does.
+// decoded one account at a time off the chain, so the size of the mint list variant costs little
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EscrowAccountData {
+    Escrow(Escrow),
+    Pool(Pool),
+    PoolShare(PoolShare),
+    Config(Config),
+    MintList(MintList),
+    MintVolume(MintVolume),
+    Template(Template),
+    Bid(Bid),
+    Ticket(Ticket),
+    Request(Request),
+    Quote(Quote),
+    CnftEscrow(CnftEscrow),
+    CoreEscrow(CoreEscrow),
+    RelayNonce(RelayNonce),
+    Session(Session),
+    Recovery(Recovery),
+}
+
+impl EscrowAccountData {
+    /// The initialized account in `data`, failing for data without a known discriminator, such
+    /// as an account the program has not written yet
+    pub fn try_unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        let discriminator = data
+            .get(..DISCRIMINATOR_LEN)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        Ok(match *array_ref![discriminator, 0, DISCRIMINATOR_LEN] {
+            Escrow::DISCRIMINATOR => Self::Escrow(Escrow::unpack(data)?),
+            Pool::DISCRIMINATOR => Self::Pool(Pool::unpack(data)?),
+            PoolShare::DISCRIMINATOR => Self::PoolShare(PoolShare::unpack(data)?),
+            Config::DISCRIMINATOR => Self::Config(Config::unpack(data)?),
+            MintList::DISCRIMINATOR => Self::MintList(MintList::unpack(data)?),
+            MintVolume::DISCRIMINATOR => Self::MintVolume(MintVolume::unpack(data)?),
+            Template::DISCRIMINATOR => Self::Template(Template::unpack(data)?),
+            Bid::DISCRIMINATOR => Self::Bid(Bid::unpack(data)?),
+            Ticket::DISCRIMINATOR => Self::Ticket(Ticket::unpack(data)?),
+            Request::DISCRIMINATOR => Self::Request(Request::unpack(data)?),
+            Quote::DISCRIMINATOR => Self::Quote(Quote::unpack(data)?),
+            CnftEscrow::DISCRIMINATOR => Self::CnftEscrow(CnftEscrow::unpack(data)?),
+            CoreEscrow::DISCRIMINATOR => Self::CoreEscrow(CoreEscrow::unpack(data)?),
+            RelayNonce::DISCRIMINATOR => Self::RelayNonce(RelayNonce::unpack(data)?),
+            Session::DISCRIMINATOR => Self::Session(Session::unpack(data)?),
+            Recovery::DISCRIMINATOR => Self::Recovery(Recovery::unpack(data)?),
+            _ => return Err(ProgramError::InvalidAccountData),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::{offset_of, Zeroable};
+
+    #[test]
+    fn test_escrow_offsets() {
+        let layout = EscrowLayout::zeroed();
+        let at = |field_offset: usize| DISCRIMINATOR_LEN + field_offset;
+        assert_eq!(
+            Escrow::OFFSET_IS_INITIALIZED,
+            at(offset_of!(layout, EscrowLayout, is_initialized))
+        );
+        assert_eq!(
+            Escrow::OFFSET_INITIALIZER,
+            at(offset_of!(layout, EscrowLayout, initializer_pubkey))
+        );
+        assert_eq!(
+            Escrow::OFFSET_VAULT,
+            at(offset_of!(layout, EscrowLayout, temp_token_account_pubkey))
+        );
+        assert_eq!(
+            Escrow::OFFSET_RECEIVE_ACCOUNT,
+            at(offset_of!(
+                layout,
+                EscrowLayout,
+                initializer_token_to_receive_account_pubkey
+            ))
+        );
+        assert_eq!(
+            Escrow::OFFSET_EXPECTED_AMOUNT,
+            at(offset_of!(layout, EscrowLayout, expected_amount))
+        );
+        assert_eq!(
+            Escrow::OFFSET_EXPIRES_AT_SLOT,
+            at(offset_of!(layout, EscrowLayout, expires_at_slot))
+        );
+        assert_eq!(
+            Escrow::OFFSET_ALLOWED_TAKER,
+            at(offset_of!(layout, EscrowLayout, allowed_taker))
+        );
+        assert_eq!(
+            Escrow::OFFSET_DEPOSITED_AMOUNT,
+            at(offset_of!(layout, EscrowLayout, deposited_amount))
+        );
+        assert_eq!(
+            Escrow::OFFSET_RECEIVE_MINT,
+            at(offset_of!(layout, EscrowLayout, receive_mint))
+        );
+        assert_eq!(
+            Escrow::OFFSET_OPERATOR,
+            at(offset_of!(layout, EscrowLayout, operator))
+        );
+        assert_eq!(
+            Escrow::OFFSET_MARKET,
+            at(offset_of!(layout, EscrowLayout, market))
+        );
+        assert_eq!(
+            Escrow::OFFSET_TAG,
+            at(offset_of!(layout, EscrowLayout, tag))
+        );
     }
 }
@@ -0,0 +1,342 @@
+//! Program-test setup, fixtures and assertions for the escrow's own integration tests and for
+//! programs that invoke the escrow and want realistic escrow scenarios in their test suites
+
+use serde_json::Value;
+use solana_program::{
+    hash::{hashv, Hash},
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    sysvar,
+};
+use solana_program_test::{processor, BanksClient, ProgramTest};
+use solana_sdk::{
+    account::Account,
+    signature::{keypair_from_seed, Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+use std::str::FromStr;
+
+use crate::{
+    processor::Processor,
+    state::{Config, Escrow, MintList, MintVolume},
+};
+
+/// A bank with the escrow program at `program_id`, built from `paulx_solana_escrow.so` under
+/// `cargo test-bpf` and from the native processor otherwise
+pub fn program_test(program_id: Pubkey) -> ProgramTest {
+    ProgramTest::new(
+        "paulx_solana_escrow",
+        program_id,
+        processor!(Processor::process),
+    )
+}
+
+/// A keypair derived from `label`, so the same label names the same address on every run and
+/// a failing assertion prints an address that can be traced back to the party it belongs to
+pub fn keypair(label: &str) -> Keypair {
+    keypair_from_seed(hashv(&[b"escrow test keypair", label.as_bytes()]).as_ref()).unwrap()
+}
+
+/// A rent exempt account holding `state`
+pub fn packed_account<T: Pack>(state: T, owner: Pubkey) -> Account {
+    let mut data = vec![0; T::LEN];
+    T::pack(state, &mut data).unwrap();
+    Account {
+        lamports: Rent::default().minimum_balance(T::LEN),
+        owner,
+        data,
+        ..Account::default()
+    }
+}
+
+/// An initialized token account, as a [`packed_account`] of the token program
+pub fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> Account {
+    packed_account(
+        TokenAccount {
+            mint,
+            owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        spl_token::id(),
+    )
+}
+
+/// The account of `tests/fixtures/<name>.json`
+pub fn fixture(name: &str) -> (Pubkey, Account) {
+    let path = format!(
+        "{}/tests/fixtures/{}.json",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+    let json: Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let account = &json["account"];
+    (
+        Pubkey::from_str(json["pubkey"].as_str().unwrap()).unwrap(),
+        Account {
+            lamports: account["lamports"].as_u64().unwrap(),
+            data: base64::decode(account["data"][0].as_str().unwrap()).unwrap(),
+            owner: Pubkey::from_str(account["owner"].as_str().unwrap()).unwrap(),
+            executable: account["executable"].as_bool().unwrap(),
+            rent_epoch: account["rentEpoch"].as_u64().unwrap(),
+        },
+    )
+}
+
+/// The token account fixture `name` owned by `owner`, who also replaces the fixture's owner as
+/// close authority
+pub fn token_account_fixture(name: &str, owner: &Pubkey) -> (Pubkey, Account) {
+    let (address, mut account) = fixture(name);
+    let mut state = TokenAccount::unpack(&account.data).unwrap();
+    if state.close_authority == COption::Some(state.owner) {
+        state.close_authority = COption::Some(*owner);
+    }
+    state.owner = *owner;
+    TokenAccount::pack(state, &mut account.data).unwrap();
+    (address, account)
+}
+
+/// An escrow opened on a fixture vault and filled, against accounts in their fixture states
+pub struct Replay {
+    pub program_id: Pubkey,
+    pub initializer: Keypair,
+    pub taker: Keypair,
+    pub deposit_mint: Pubkey,
+    pub vault: Pubkey,
+    pub deposit_amount: u64,
+    pub initializer_x_token_account: Pubkey,
+    pub initializer_y_token_account: Pubkey,
+    pub taker_x_token_account: Pubkey,
+    pub taker_y_token_account: Pubkey,
+    pub escrow_account: Pubkey,
+    pub banks_client: BanksClient,
+    pub payer: Keypair,
+    pub recent_blockhash: Hash,
+}
+
+impl Replay {
+    pub const EXPECTED_AMOUNT: u64 = 150;
+
+    pub async fn start(vault_fixture: &str) -> Self {
+        Self::start_with(vault_fixture, crate::id(), vec![]).await
+    }
+
+    /// `start`, with the escrow program at `program_id` and `accounts` added to the bank. Unless
+    /// built with `any-program-id`, the program refuses to run anywhere but `crate::id()`.
+    pub async fn start_with(
+        vault_fixture: &str,
+        program_id: Pubkey,
+        accounts: Vec<(Pubkey, Account)>,
+    ) -> Self {
+        Self::start_metered(vault_fixture, program_id, accounts, None).await
+    }
+
+    /// `start_with`, limiting each instruction to `bpf_compute_max_units` compute units when the
+    /// program runs as BPF
+    pub async fn start_metered(
+        vault_fixture: &str,
+        program_id: Pubkey,
+        accounts: Vec<(Pubkey, Account)>,
+        bpf_compute_max_units: Option<u64>,
+    ) -> Self {
+        let mut program_test = program_test(program_id);
+        if let Some(max_units) = bpf_compute_max_units {
+            program_test.set_bpf_compute_max_units(max_units);
+        }
+        Self::start_in(program_test, program_id, vault_fixture, accounts).await
+    }
+
+    /// `start_with` in `program_test`, which already holds the escrow program at `program_id`
+    /// next to the caller's own programs
+    pub async fn start_in(
+        mut program_test: ProgramTest,
+        program_id: Pubkey,
+        vault_fixture: &str,
+        accounts: Vec<(Pubkey, Account)>,
+    ) -> Self {
+        let initializer = keypair("initializer");
+        let taker = keypair("taker");
+        let mint_y = keypair("mint y").pubkey();
+        let initializer_x_token_account = keypair("initializer x token account").pubkey();
+        let initializer_y_token_account = keypair("initializer y token account").pubkey();
+        let taker_x_token_account = keypair("taker x token account").pubkey();
+        let taker_y_token_account = keypair("taker y token account").pubkey();
+        let escrow_account = keypair("escrow").pubkey();
+
+        let (deposit_mint, mint) = fixture("mint_with_freeze_authority");
+        program_test.add_account(deposit_mint, mint);
+        program_test.add_account(
+            mint_y,
+            packed_account(
+                Mint {
+                    supply: 1_000,
+                    is_initialized: true,
+                    ..Mint::default()
+                },
+                spl_token::id(),
+            ),
+        );
+        let (vault, vault_account) = token_account_fixture(vault_fixture, &initializer.pubkey());
+        let deposit_amount = TokenAccount::unpack(&vault_account.data).unwrap().amount;
+        program_test.add_account(vault, vault_account);
+        program_test.add_account(
+            initializer_x_token_account,
+            token_account(deposit_mint, initializer.pubkey(), 0),
+        );
+        program_test.add_account(
+            initializer_y_token_account,
+            token_account(mint_y, initializer.pubkey(), 0),
+        );
+        program_test.add_account(
+            taker_x_token_account,
+            token_account(deposit_mint, taker.pubkey(), 0),
+        );
+        program_test.add_account(
+            taker_y_token_account,
+            token_account(mint_y, taker.pubkey(), Self::EXPECTED_AMOUNT),
+        );
+        program_test.add_account(
+            escrow_account,
+            Account {
+                lamports: Rent::default().minimum_balance(Escrow::get_packed_len()),
+                owner: program_id,
+                data: vec![0; Escrow::get_packed_len()],
+                ..Account::default()
+            },
+        );
+        for (address, account) in accounts {
+            program_test.add_account(address, account);
+        }
+        let (banks_client, payer, recent_blockhash) = program_test.start().await;
+
+        Self {
+            program_id,
+            initializer,
+            taker,
+            deposit_mint,
+            vault,
+            deposit_amount,
+            initializer_x_token_account,
+            initializer_y_token_account,
+            taker_x_token_account,
+            taker_y_token_account,
+            escrow_account,
+            banks_client,
+            payer,
+            recent_blockhash,
+        }
+    }
+
+    pub fn pda(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"escrow"], &self.program_id).0
+    }
+
+    pub async fn init(&mut self) -> Result<(), TransportError> {
+        self.process(self.init_instruction()).await
+    }
+
+    pub async fn exchange(&mut self) -> Result<(), TransportError> {
+        self.process(self.exchange_instruction()).await
+    }
+
+    pub fn init_instruction(&self) -> Instruction {
+        let mut init_data = vec![0];
+        init_data.extend_from_slice(&Self::EXPECTED_AMOUNT.to_le_bytes());
+        Instruction::new_with_bytes(
+            self.program_id,
+            &init_data,
+            vec![
+                AccountMeta::new_readonly(self.initializer.pubkey(), true),
+                AccountMeta::new(self.vault, false),
+                AccountMeta::new_readonly(self.initializer_y_token_account, false),
+                AccountMeta::new(self.escrow_account, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(MintList::find_address(&self.program_id).0, false),
+            ],
+        )
+    }
+
+    pub fn exchange_instruction(&self) -> Instruction {
+        let mut exchange_data = vec![1];
+        exchange_data.extend_from_slice(&self.deposit_amount.to_le_bytes());
+        Instruction::new_with_bytes(
+            self.program_id,
+            &exchange_data,
+            vec![
+                AccountMeta::new(self.taker.pubkey(), true),
+                AccountMeta::new(self.taker_y_token_account, false),
+                AccountMeta::new(self.taker_x_token_account, false),
+                AccountMeta::new(self.vault, false),
+                AccountMeta::new(self.initializer.pubkey(), false),
+                AccountMeta::new(self.initializer_y_token_account, false),
+                AccountMeta::new(self.escrow_account, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(self.pda(), false),
+                AccountMeta::new_readonly(Config::find_address(&self.program_id).0, false),
+                AccountMeta::new(
+                    MintVolume::find_address(&self.deposit_mint, &self.program_id).0,
+                    false,
+                ),
+            ],
+        )
+    }
+
+    pub fn cancel_instruction(&self) -> Instruction {
+        Instruction::new_with_bytes(
+            self.program_id,
+            &[8],
+            vec![
+                AccountMeta::new(self.initializer.pubkey(), true),
+                AccountMeta::new(self.vault, false),
+                AccountMeta::new(self.initializer_x_token_account, false),
+                AccountMeta::new(self.escrow_account, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(self.pda(), false),
+                AccountMeta::new_readonly(sysvar::clock::id(), false),
+            ],
+        )
+    }
+
+    /// Sends `instruction`, signed by whichever of the initializer and the taker it names first
+    pub async fn process(&mut self, instruction: Instruction) -> Result<(), TransportError> {
+        let signer = if instruction.accounts[0].pubkey == self.taker.pubkey() {
+            &self.taker
+        } else {
+            &self.initializer
+        };
+        let mut transaction =
+            Transaction::new_with_payer(&[instruction], Some(&self.payer.pubkey()));
+        transaction.sign(&[&self.payer, signer], self.recent_blockhash);
+        self.banks_client.process_transaction(transaction).await
+    }
+
+    /// The lamports of each of `addresses`, zero for closed accounts
+    pub async fn lamports(&mut self, addresses: &[Pubkey]) -> Vec<u64> {
+        let mut lamports = vec![];
+        for address in addresses {
+            let account = self.banks_client.get_account(*address).await.unwrap();
+            lamports.push(account.map_or(0, |account| account.lamports));
+        }
+        lamports
+    }
+
+    pub async fn token_account(&mut self, address: Pubkey) -> Option<TokenAccount> {
+        let account = self.banks_client.get_account(address).await.unwrap()?;
+        Some(TokenAccount::unpack(&account.data).unwrap())
+    }
+}
+
+/// `result` is the escrow instruction failing with `error`
+pub fn assert_instruction_error(result: Result<(), TransportError>, error: InstructionError) {
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, error)
+    );
+}
@@ -0,0 +1,112 @@
+//! Token program instructions for the invocations of the Exchange path, rewritten in place in one
+//! instruction. The `spl_token::instruction` builders allocate the data and account metas of every
+//! instruction anew, which a fill with split recipients and a fee pays for several times.
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+const TRANSFER: u8 = 3;
+const CLOSE_ACCOUNT: u8 = 9;
+
+/// One token program instruction whose data and account metas are reused by every invocation.
+/// It does not check the token program id, the processor does before building it.
+pub struct TokenCpi {
+    instruction: Instruction,
+}
+
+impl TokenCpi {
+    pub fn new(token_program_id: &Pubkey) -> Self {
+        Self {
+            instruction: Instruction {
+                program_id: *token_program_id,
+                accounts: Vec::with_capacity(3),
+                data: Vec::with_capacity(9),
+            },
+        }
+    }
+
+    /// `spl_token::instruction::transfer` of `amount` signed by `authority` alone
+    pub fn transfer(
+        &mut self,
+        source: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> &Instruction {
+        self.rewrite(TRANSFER, source, destination, authority);
+        self.instruction
+            .data
+            .extend_from_slice(&amount.to_le_bytes());
+        &self.instruction
+    }
+
+    /// `spl_token::instruction::close_account` signed by `owner` alone
+    pub fn close_account(
+        &mut self,
+        account: &Pubkey,
+        destination: &Pubkey,
+        owner: &Pubkey,
+    ) -> &Instruction {
+        self.rewrite(CLOSE_ACCOUNT, account, destination, owner);
+        &self.instruction
+    }
+
+    fn rewrite(&mut self, tag: u8, first: &Pubkey, second: &Pubkey, authority: &Pubkey) {
+        let Instruction { accounts, data, .. } = &mut self.instruction;
+        accounts.clear();
+        accounts.push(AccountMeta::new(*first, false));
+        accounts.push(AccountMeta::new(*second, false));
+        accounts.push(AccountMeta::new_readonly(*authority, true));
+        data.clear();
+        data.push(tag);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_spl_token_builders() {
+        let keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let mut cpi = TokenCpi::new(&spl_token::id());
+        assert_eq!(
+            *cpi.transfer(&keys[0], &keys[1], &keys[2], 42),
+            spl_token::instruction::transfer(
+                &spl_token::id(),
+                &keys[0],
+                &keys[1],
+                &keys[2],
+                &[],
+                42
+            )
+            .unwrap()
+        );
+        // nothing of the transfer's amount is left behind
+        assert_eq!(
+            *cpi.close_account(&keys[1], &keys[2], &keys[0]),
+            spl_token::instruction::close_account(
+                &spl_token::id(),
+                &keys[1],
+                &keys[2],
+                &keys[0],
+                &[]
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            *cpi.transfer(&keys[2], &keys[0], &keys[1], u64::MAX),
+            spl_token::instruction::transfer(
+                &spl_token::id(),
+                &keys[2],
+                &keys[0],
+                &keys[1],
+                &[],
+                u64::MAX
+            )
+            .unwrap()
+        );
+    }
+}
@@ -0,0 +1,39 @@
+//! Metadata accounts of the Metaplex Token Metadata program, whose crate is not a dependency
+
+use std::convert::TryInto;
+
+use solana_program::pubkey::Pubkey;
+
+solana_program::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+// key, update authority and mint precede the borsh encoded name and symbol
+const OFFSET_NAME: usize = 65;
+const MAX_SYMBOL_LEN: usize = 10;
+
+pub fn find_metadata_address(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"metadata", id().as_ref(), mint.as_ref()], &id())
+}
+
+/// The symbol of a metadata account, `None` if the data is too short or the symbol empty
+pub fn unpack_symbol(data: &[u8]) -> Option<String> {
+    let name_len = read_len(data, OFFSET_NAME)?;
+    let offset_symbol = OFFSET_NAME + 4 + name_len;
+    let symbol_len = read_len(data, offset_symbol)?;
+    let symbol = data.get(offset_symbol + 4..offset_symbol + 4 + symbol_len.min(MAX_SYMBOL_LEN))?;
+    // symbols are stored padded with zeros to their maximum length
+    let symbol = std::str::from_utf8(symbol)
+        .ok()?
+        .trim_end_matches('\0')
+        .trim();
+    if symbol.is_empty() {
+        return None;
+    }
+    Some(symbol.to_string())
+}
+
+fn read_len(data: &[u8], offset: usize) -> Option<usize> {
+    data.get(offset..offset + 4)
+        .and_then(|slice| slice.try_into().ok())
+        .map(u32::from_le_bytes)
+        .map(|len| len as usize)
+}
@@ -1,12 +1,39 @@
 // FIX this is not a good way to avoid these warnings
 #[cfg(feature = "test-bpf")]
-use paulx_solana_escrow::{processor as p, state::Escrow};
+use paulx_solana_escrow::{processor as p, state::{Config, Escrow, MintList, MintVolume}};
+#[cfg(feature = "test-bpf")]
+use paulx_solana_escrow::test_utils::{
+    assert_instruction_error, fixture, keypair, packed_account, token_account, Replay,
+};
 #[cfg(feature = "test-bpf")]
 use solana_program::{instruction::{AccountMeta, Instruction}, program_pack::Pack, pubkey::Pubkey, rent::Rent, sysvar};
 #[cfg(feature = "test-bpf")]
 use solana_program_test::{ProgramTest, processor};
 #[cfg(feature = "test-bpf")]
-use solana_sdk::{account::Account, signature::{Keypair, Signer}, transaction::Transaction};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+#[cfg(feature = "test-bpf")]
+use solana_program::system_instruction;
+#[cfg(feature = "test-bpf")]
+use spl_token::state::{Account as TokenAccount, Mint};
+#[cfg(feature = "test-bpf")]
+use solana_program::program_option::COption;
+#[cfg(feature = "test-bpf")]
+use solana_sdk::transport::TransportError;
+#[cfg(feature = "test-bpf")]
+use rand::{rngs::StdRng, Rng, SeedableRng};
+#[cfg(feature = "test-bpf")]
+use solana_program_test::ProgramTestContext;
+#[cfg(feature = "test-bpf")]
+use solana_sdk::{
+    account::create_account_for_test, instruction::InstructionError,
+    transaction::TransactionError,
+};
+#[cfg(feature = "test-bpf")]
+use std::collections::BTreeMap;
 
 #[tokio::test]
 #[cfg(feature = "test-bpf")]
@@ -18,22 +45,22 @@ async fn test_success() {
 
     let escrow_amount = 123;
 
-    let program_id = Pubkey::new_unique();
+    let program_id = paulx_solana_escrow::id();
 
     // token x
-    let token_x = Keypair::new();
+    let token_x = keypair("token x");
 
     // token minter
-    let minter = Keypair::new();
+    let minter = keypair("minter");
     
     // 0. `[signer]` The account of the person initializing the escrow
-    let initializer_key = Keypair::new();
+    let initializer_key = keypair("initializer");
     // 1. `[writable]` Temporary token account that should be created prior to this instruction and owned by the initializer
-    let temp_x_token_account = Keypair::new();
+    let temp_x_token_account = keypair("temp x token account");
     // 2. `[]` The initializer's token account for the token they will receive should the trade go through
-    let initializer_y_token_account = Keypair::new();
+    let initializer_y_token_account = keypair("initializer y token account");
     // 3. `[writable]` The escrow account, it will hold all necessary info about the trade.
-    let escrow_account = Keypair::new();
+    let escrow_account = keypair("escrow");
     // 4. `[]` The rent sysvar
     // 5. `[]` The token program
 
@@ -152,6 +179,7 @@ async fn test_success() {
             AccountMeta::new(escrow_account.pubkey(), false),              // escrow account
             AccountMeta::new(sysvar::rent::id(), false),                   // rent sys var
             AccountMeta::new(spl_token::id(), false),                      // token program
+            AccountMeta::new_readonly(MintList::find_address(&program_id).0, false), // mint list
             ],
         )],
         Some(&payer.pubkey()),
@@ -174,3 +202,707 @@ async fn test_success() {
     assert_eq!(escrow_unpacked.initializer_token_to_receive_account_pubkey,initializer_y_token_account.pubkey());
     assert_eq!(escrow_unpacked.expected_amount, escrow_amount);
 }
+
+#[test]
+#[cfg(feature = "test-bpf")]
+fn test_labeled_keypairs_are_stable() {
+    assert_eq!(keypair("initializer").pubkey(), keypair("initializer").pubkey());
+    assert_ne!(keypair("initializer").pubkey(), keypair("taker").pubkey());
+}
+
+#[tokio::test]
+#[cfg(feature = "test-bpf")]
+async fn test_closed_escrow_can_not_be_refunded_and_replayed() {
+    let program_id = paulx_solana_escrow::id();
+    let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+
+    let mint_x = Pubkey::new_unique();
+    let mint_y = Pubkey::new_unique();
+    let initializer = keypair("initializer");
+    let taker = keypair("taker");
+    let temp_x_token_account = Pubkey::new_unique();
+    let initializer_y_token_account = Pubkey::new_unique();
+    let taker_x_token_account = Pubkey::new_unique();
+    let taker_y_token_account = Pubkey::new_unique();
+    let escrow_account = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::new(
+        "paulx_solana_escrow",
+        program_id,
+        processor!(p::Processor::process),
+    );
+    for mint in [mint_x, mint_y].iter() {
+        program_test.add_account(
+            *mint,
+            packed_account(
+                Mint {
+                    supply: 1_000,
+                    is_initialized: true,
+                    ..Mint::default()
+                },
+                spl_token::id(),
+            ),
+        );
+    }
+    program_test.add_account(temp_x_token_account, token_account(mint_x, initializer.pubkey(), 100));
+    program_test.add_account(initializer_y_token_account, token_account(mint_y, initializer.pubkey(), 0));
+    program_test.add_account(taker_x_token_account, token_account(mint_x, taker.pubkey(), 0));
+    // enough to pay for the trade twice, should the replay go through
+    program_test.add_account(taker_y_token_account, token_account(mint_y, taker.pubkey(), 300));
+    program_test.add_account(
+        escrow_account,
+        Account {
+            lamports: Rent::default().minimum_balance(Escrow::get_packed_len()),
+            owner: program_id,
+            data: vec![0; Escrow::get_packed_len()],
+            ..Account::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut init_data = vec![0];
+    init_data.extend_from_slice(&150u64.to_le_bytes());
+    let mut transaction = Transaction::new_with_payer(
+        &[Instruction::new_with_bytes(
+            program_id,
+            &init_data,
+            vec![
+                AccountMeta::new_readonly(initializer.pubkey(), true),
+                AccountMeta::new(temp_x_token_account, false),
+                AccountMeta::new_readonly(initializer_y_token_account, false),
+                AccountMeta::new(escrow_account, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(MintList::find_address(&program_id).0, false),
+            ],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &initializer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let mut exchange_data = vec![1];
+    exchange_data.extend_from_slice(&100u64.to_le_bytes());
+    let exchange = Instruction::new_with_bytes(
+        program_id,
+        &exchange_data,
+        vec![
+            AccountMeta::new(taker.pubkey(), true),
+            AccountMeta::new(taker_y_token_account, false),
+            AccountMeta::new(taker_x_token_account, false),
+            AccountMeta::new(temp_x_token_account, false),
+            AccountMeta::new(initializer.pubkey(), false),
+            AccountMeta::new(initializer_y_token_account, false),
+            AccountMeta::new(escrow_account, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(pda, false),
+            AccountMeta::new_readonly(Config::find_address(&program_id).0, false),
+            AccountMeta::new(MintVolume::find_address(&mint_x, &program_id).0, false),
+        ],
+    );
+
+    // close the escrow, refund its rent and try to fill it a second time in one transaction
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            exchange.clone(),
+            system_instruction::transfer(
+                &payer.pubkey(),
+                &escrow_account,
+                Rent::default().minimum_balance(Escrow::get_packed_len()),
+            ),
+            exchange.clone(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &taker], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
+    // the failed replay rolled back, the escrow still fills exactly once
+    let mut transaction = Transaction::new_with_payer(&[exchange], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &taker], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert!(banks_client.get_account(escrow_account).await.unwrap().is_none());
+    let taker_x = banks_client.get_account(taker_x_token_account).await.unwrap().unwrap();
+    assert_eq!(TokenAccount::unpack(&taker_x.data).unwrap().amount, 100);
+}
+
+
+#[tokio::test]
+#[cfg(all(feature = "test-bpf", feature = "client"))]
+async fn test_bootstrapped_sample_escrow_fills() {
+    use paulx_solana_escrow::client::{self, devtools};
+
+    let program_id = paulx_solana_escrow::id();
+    let program_test = ProgramTest::new(
+        "paulx_solana_escrow",
+        program_id,
+        processor!(p::Processor::process),
+    );
+    let (mut banks_client, payer, _recent_blockhash) = program_test.start().await;
+
+    let maker = keypair("maker");
+    let taker = keypair("taker");
+    let environment = devtools::bootstrap(&mut banks_client, &program_id, &payer, &[&maker, &taker])
+        .await
+        .unwrap();
+    let vault = banks_client.get_account(environment.vault).await.unwrap().unwrap();
+    assert_eq!(TokenAccount::unpack(&vault.data).unwrap().amount, devtools::SAMPLE_DEPOSIT_AMOUNT);
+
+    let takers = environment.wallets[1];
+    let accounts = client::ExchangeAccounts {
+        taker: taker.pubkey(),
+        takers_sending_token_account: takers.receive_token_account,
+        takers_token_to_receive_account: takers.deposit_token_account,
+        escrow: environment.escrow,
+        fee_account: None,
+    };
+    client::exchange(&mut banks_client, &program_id, &taker, &accounts, None)
+        .await
+        .unwrap();
+    assert!(banks_client.get_account(environment.escrow).await.unwrap().is_none());
+    let taker_deposit = banks_client.get_account(takers.deposit_token_account).await.unwrap().unwrap();
+    assert_eq!(
+        TokenAccount::unpack(&taker_deposit.data).unwrap().amount,
+        devtools::WALLET_TOKEN_BALANCE + devtools::SAMPLE_DEPOSIT_AMOUNT
+    );
+}
+
+#[tokio::test]
+#[cfg(feature = "test-bpf")]
+async fn test_fixture_delegated_vault_fills() {
+    let mut replay = Replay::start("token_account_delegated").await;
+    replay.init().await.unwrap();
+    // the delegate's leftover allowance must not reach the deposit
+    let vault = replay.token_account(replay.vault).await.unwrap();
+    assert_eq!(vault.owner, replay.pda());
+    assert_eq!(vault.delegate, COption::None);
+    assert_eq!(vault.delegated_amount, 0);
+
+    replay.exchange().await.unwrap();
+    let taker_x = replay.token_account(replay.taker_x_token_account).await.unwrap();
+    assert_eq!(taker_x.amount, replay.deposit_amount);
+}
+
+#[tokio::test]
+#[cfg(feature = "test-bpf")]
+async fn test_fixture_owner_close_authority_is_cleared() {
+    let mut replay = Replay::start("token_account_owner_close_authority").await;
+    replay.init().await.unwrap();
+    // left in place, the initializer's close authority would keep the PDA from closing the vault
+    let vault = replay.token_account(replay.vault).await.unwrap();
+    assert_eq!(vault.close_authority, COption::None);
+
+    replay.exchange().await.unwrap();
+    assert!(replay.token_account(replay.vault).await.is_none());
+}
+
+#[tokio::test]
+#[cfg(feature = "test-bpf")]
+async fn test_fixture_unusable_vaults_are_refused() {
+    for vault_fixture in ["token_account_foreign_close_authority", "token_account_frozen"].iter() {
+        let mut replay = Replay::start(vault_fixture).await;
+        assert!(replay.init().await.is_err(), "{}", vault_fixture);
+        let vault = replay.token_account(replay.vault).await.unwrap();
+        assert_eq!(vault.owner, replay.initializer.pubkey());
+    }
+}
+
+#[tokio::test]
+#[cfg(feature = "test-bpf")]
+async fn test_exchange_refunds_vault_and_escrow_rent_to_initializer() {
+    let mut replay = Replay::start("token_account_delegated").await;
+    replay.init().await.unwrap();
+    let accounts = [
+        replay.initializer.pubkey(),
+        replay.taker.pubkey(),
+        replay.vault,
+        replay.escrow_account,
+    ];
+    let before = replay.lamports(&accounts).await;
+    assert!(before[2] > 0 && before[3] > 0);
+
+    replay.exchange().await.unwrap();
+    // the payer covers the fee, so the taker only gains the close rebate out of the escrow's rent
+    // and the initializer gets the rest
+    let after = replay.lamports(&accounts).await;
+    let rebate = p::TAKER_CLOSE_REBATE_LAMPORTS;
+    assert_eq!(
+        after,
+        vec![before[0] + before[2] + before[3] - rebate, before[1] + rebate, 0, 0]
+    );
+}
+
+#[tokio::test]
+#[cfg(feature = "test-bpf")]
+async fn test_cancel_refunds_vault_and_escrow_rent_to_initializer() {
+    let mut replay = Replay::start("token_account_delegated").await;
+    replay.init().await.unwrap();
+    let accounts = [
+        replay.initializer.pubkey(),
+        replay.taker.pubkey(),
+        replay.vault,
+        replay.escrow_account,
+    ];
+    let before = replay.lamports(&accounts).await;
+
+    replay.process(replay.cancel_instruction()).await.unwrap();
+    let after = replay.lamports(&accounts).await;
+    assert_eq!(after, vec![before[0] + before[2] + before[3], before[1], 0, 0]);
+    let initializer_x = replay.token_account(replay.initializer_x_token_account).await.unwrap();
+    assert_eq!(initializer_x.amount, replay.deposit_amount);
+}
+
+#[tokio::test]
+#[cfg(feature = "test-bpf")]
+async fn test_spoofed_pda_is_refused() {
+    let mut replay = Replay::start("token_account_delegated").await;
+    replay.init().await.unwrap();
+    let mut exchange = replay.exchange_instruction();
+    exchange.accounts[8].pubkey = Pubkey::new_unique();
+    assert_instruction_error(replay.process(exchange).await, InstructionError::InvalidSeeds);
+    let vault = replay.token_account(replay.vault).await.unwrap();
+    assert_eq!(vault.amount, replay.deposit_amount);
+}
+
+#[tokio::test]
+#[cfg(feature = "test-bpf")]
+async fn test_vault_of_another_escrow_is_refused() {
+    let program_id = paulx_solana_escrow::id();
+    let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+    let (deposit_mint, _) = fixture("mint_with_freeze_authority");
+    let (_, vault) = fixture("token_account_delegated");
+    // holding what this escrow's vault holds, so only the vault check tells them apart
+    let deposit_amount = TokenAccount::unpack(&vault.data).unwrap().amount;
+    let other_vault = Pubkey::new_unique();
+    let mut replay = Replay::start_with(
+        "token_account_delegated",
+        program_id,
+        vec![(other_vault, token_account(deposit_mint, pda, deposit_amount))],
+    )
+    .await;
+    replay.init().await.unwrap();
+
+    let mut exchange = replay.exchange_instruction();
+    exchange.accounts[3].pubkey = other_vault;
+    assert_instruction_error(replay.process(exchange).await, InstructionError::InvalidAccountData);
+    let other_vault = replay.token_account(other_vault).await.unwrap();
+    assert_eq!(other_vault.amount, deposit_amount);
+}
+
+#[tokio::test]
+#[cfg(feature = "test-bpf")]
+async fn test_escrow_state_owned_by_another_program_is_refused() {
+    let foreign_escrow = Pubkey::new_unique();
+    let mut replay = Replay::start_with(
+        "token_account_delegated",
+        paulx_solana_escrow::id(),
+        vec![(
+            foreign_escrow,
+            Account {
+                lamports: Rent::default().minimum_balance(Escrow::get_packed_len()),
+                owner: Pubkey::new_unique(),
+                data: vec![0; Escrow::get_packed_len()],
+                ..Account::default()
+            },
+        )],
+    )
+    .await;
+    let mut init = replay.init_instruction();
+    init.accounts[3].pubkey = foreign_escrow;
+    assert_instruction_error(replay.process(init).await, InstructionError::IncorrectProgramId);
+    let vault = replay.token_account(replay.vault).await.unwrap();
+    assert_eq!(vault.owner, replay.initializer.pubkey());
+}
+
+#[tokio::test]
+#[cfg(feature = "test-bpf")]
+async fn test_fake_rent_sysvar_is_refused() {
+    let fake_rent = Pubkey::new_unique();
+    // a rent sysvar lookalike under which any balance is rent exempt
+    let free_rent = Rent {
+        lamports_per_byte_year: 0,
+        exemption_threshold: 0.0,
+        burn_percent: 0,
+    };
+    let mut replay = Replay::start_with(
+        "token_account_delegated",
+        paulx_solana_escrow::id(),
+        vec![(fake_rent, create_account_for_test(&free_rent))],
+    )
+    .await;
+    let mut init = replay.init_instruction();
+    init.accounts[4].pubkey = fake_rent;
+    assert_instruction_error(replay.process(init).await, InstructionError::InvalidArgument);
+    let vault = replay.token_account(replay.vault).await.unwrap();
+    assert_eq!(vault.owner, replay.initializer.pubkey());
+}
+
+#[tokio::test]
+#[cfg(feature = "test-bpf")]
+async fn test_fake_token_program_is_refused() {
+    let mut replay = Replay::start("token_account_delegated").await;
+    replay.init().await.unwrap();
+    let mut exchange = replay.exchange_instruction();
+    exchange.accounts[7].pubkey = Pubkey::new_unique();
+    assert_instruction_error(replay.process(exchange).await, InstructionError::IncorrectProgramId);
+    let vault = replay.token_account(replay.vault).await.unwrap();
+    assert_eq!(vault.amount, replay.deposit_amount);
+}
+
+/// An escrow the property test opened and has not seen closed
+#[cfg(feature = "test-bpf")]
+struct OpenEscrow {
+    escrow: Pubkey,
+    vault: Pubkey,
+    initializer: usize,
+    deposit: u64,
+    expected_amount: u64,
+}
+
+/// Token balances the property test expects, by token account
+#[cfg(feature = "test-bpf")]
+type Ledger = BTreeMap<Pubkey, (Pubkey, u64)>;
+
+#[cfg(feature = "test-bpf")]
+async fn send(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> bool {
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let mut transaction = Transaction::new_with_payer(instructions, Some(&context.payer.pubkey()));
+    transaction.sign(&all_signers, context.last_blockhash);
+    context.banks_client.process_transaction(transaction).await.is_ok()
+}
+
+/// Every token account holds what the ledger says, every closed vault is gone and every mint's
+/// supply is still spread over the ledger's accounts
+#[cfg(feature = "test-bpf")]
+async fn check_ledger(
+    context: &mut ProgramTestContext,
+    ledger: &Ledger,
+    closed: &[Pubkey],
+    mints: &[Pubkey],
+) {
+    for (address, (mint, amount)) in ledger.iter() {
+        let account = context.banks_client.get_account(*address).await.unwrap().unwrap();
+        let account = TokenAccount::unpack(&account.data).unwrap();
+        assert_eq!((account.mint, account.amount), (*mint, *amount), "{}", address);
+    }
+    for vault in closed {
+        assert!(context.banks_client.get_account(*vault).await.unwrap().is_none());
+    }
+    for mint in mints {
+        let account = context.banks_client.get_account(*mint).await.unwrap().unwrap();
+        let held: u64 = ledger
+            .values()
+            .filter(|(account_mint, _)| account_mint == mint)
+            .map(|(_, amount)| amount)
+            .sum();
+        assert_eq!(Mint::unpack(&account.data).unwrap().supply, held);
+    }
+}
+
+/// Runs a random sequence of InitEscrow, Exchange and Cancel and checks after every step that
+/// only the parties of the step saw their balances move, by exactly what the step moves
+#[cfg(feature = "test-bpf")]
+async fn run_random_sequence(seed: u64) {
+    const STEPS: u64 = 16;
+    let mut rng = StdRng::seed_from_u64(seed);
+    let program_id = paulx_solana_escrow::id();
+    let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+    let mint_x = Pubkey::new_unique();
+    let mint_y = Pubkey::new_unique();
+    let parties: Vec<Keypair> = (0..4)
+        .map(|party| keypair(&format!("party {}", party)))
+        .collect();
+
+    let mut program_test = ProgramTest::new(
+        "paulx_solana_escrow",
+        program_id,
+        processor!(p::Processor::process),
+    );
+    let mut ledger = Ledger::new();
+    let mut token_accounts = vec![];
+    // the bystander's accounts never take part in a step, so they must never move
+    let bystander = Pubkey::new_unique();
+    for owner in parties.iter().map(|party| party.pubkey()).chain(Some(bystander)) {
+        let x_account = Pubkey::new_unique();
+        let y_account = Pubkey::new_unique();
+        for (address, mint) in [(x_account, mint_x), (y_account, mint_y)].iter() {
+            let amount = rng.gen_range(0, 1_000);
+            program_test.add_account(*address, token_account(*mint, owner, amount));
+            ledger.insert(*address, (*mint, amount));
+        }
+        token_accounts.push((x_account, y_account));
+    }
+    for mint in [mint_x, mint_y].iter() {
+        let supply = ledger
+            .values()
+            .filter(|(account_mint, _)| account_mint == mint)
+            .map(|(_, amount)| amount)
+            .sum();
+        program_test.add_account(
+            *mint,
+            packed_account(
+                Mint {
+                    supply,
+                    is_initialized: true,
+                    ..Mint::default()
+                },
+                spl_token::id(),
+            ),
+        );
+    }
+    let mut context = program_test.start_with_context().await;
+    let rent = Rent::default();
+
+    let mut open: Vec<OpenEscrow> = vec![];
+    let mut closed = vec![];
+    for step in 0..STEPS {
+        // a fresh blockhash, so a retried step is not rejected as a duplicate
+        context.warp_to_slot(step + 2).unwrap();
+        context.last_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+
+        match rng.gen_range(0, 3) {
+            0 => {
+                let initializer = rng.gen_range(0, parties.len());
+                let (x_account, y_account) = token_accounts[initializer];
+                // sometimes more than the initializer holds
+                let deposit = rng.gen_range(1, ledger[&x_account].1 + 50);
+                let expected_amount = rng.gen_range(1, 300);
+                let vault = keypair(&format!("seed {} step {} vault", seed, step));
+                let escrow = keypair(&format!("seed {} step {} escrow", seed, step));
+                let mut init_data = vec![0];
+                init_data.extend_from_slice(&expected_amount.to_le_bytes());
+                let instructions = [
+                    system_instruction::create_account(
+                        &context.payer.pubkey(),
+                        &vault.pubkey(),
+                        rent.minimum_balance(TokenAccount::LEN),
+                        TokenAccount::LEN as u64,
+                        &spl_token::id(),
+                    ),
+                    spl_token::instruction::initialize_account(
+                        &spl_token::id(),
+                        &vault.pubkey(),
+                        &mint_x,
+                        &parties[initializer].pubkey(),
+                    )
+                    .unwrap(),
+                    spl_token::instruction::transfer(
+                        &spl_token::id(),
+                        &x_account,
+                        &vault.pubkey(),
+                        &parties[initializer].pubkey(),
+                        &[],
+                        deposit,
+                    )
+                    .unwrap(),
+                    system_instruction::create_account(
+                        &context.payer.pubkey(),
+                        &escrow.pubkey(),
+                        rent.minimum_balance(Escrow::LEN),
+                        Escrow::LEN as u64,
+                        &program_id,
+                    ),
+                    Instruction::new_with_bytes(
+                        program_id,
+                        &init_data,
+                        vec![
+                            AccountMeta::new_readonly(parties[initializer].pubkey(), true),
+                            AccountMeta::new(vault.pubkey(), false),
+                            AccountMeta::new_readonly(y_account, false),
+                            AccountMeta::new(escrow.pubkey(), false),
+                            AccountMeta::new_readonly(sysvar::rent::id(), false),
+                            AccountMeta::new_readonly(spl_token::id(), false),
+                            AccountMeta::new_readonly(MintList::find_address(&program_id).0, false),
+                        ],
+                    ),
+                ];
+                let signers = [&parties[initializer], &vault, &escrow];
+                let succeeded = send(&mut context, &instructions, &signers).await;
+                assert_eq!(succeeded, deposit <= ledger[&x_account].1, "seed {} step {}", seed, step);
+                if succeeded {
+                    ledger.get_mut(&x_account).unwrap().1 -= deposit;
+                    ledger.insert(vault.pubkey(), (mint_x, deposit));
+                    open.push(OpenEscrow {
+                        escrow: escrow.pubkey(),
+                        vault: vault.pubkey(),
+                        initializer,
+                        deposit,
+                        expected_amount,
+                    });
+                }
+            }
+            1 if !open.is_empty() => {
+                let index = rng.gen_range(0, open.len());
+                let taker = rng.gen_range(0, parties.len());
+                let offer = &open[index];
+                let (_, initializer_y) = token_accounts[offer.initializer];
+                let (taker_x, taker_y) = token_accounts[taker];
+                let mut exchange_data = vec![1];
+                exchange_data.extend_from_slice(&offer.deposit.to_le_bytes());
+                let exchange = Instruction::new_with_bytes(
+                    program_id,
+                    &exchange_data,
+                    vec![
+                        AccountMeta::new(parties[taker].pubkey(), true),
+                        AccountMeta::new(taker_y, false),
+                        AccountMeta::new(taker_x, false),
+                        AccountMeta::new(offer.vault, false),
+                        AccountMeta::new(parties[offer.initializer].pubkey(), false),
+                        AccountMeta::new(initializer_y, false),
+                        AccountMeta::new(offer.escrow, false),
+                        AccountMeta::new_readonly(spl_token::id(), false),
+                        AccountMeta::new_readonly(pda, false),
+                        AccountMeta::new_readonly(Config::find_address(&program_id).0, false),
+                        AccountMeta::new(MintVolume::find_address(&mint_x, &program_id).0, false),
+                    ],
+                );
+                let succeeded = send(&mut context, &[exchange], &[&parties[taker]]).await;
+                assert_eq!(
+                    succeeded,
+                    ledger[&taker_y].1 >= offer.expected_amount,
+                    "seed {} step {}",
+                    seed,
+                    step
+                );
+                if succeeded {
+                    let offer = open.remove(index);
+                    ledger.get_mut(&taker_y).unwrap().1 -= offer.expected_amount;
+                    ledger.get_mut(&initializer_y).unwrap().1 += offer.expected_amount;
+                    ledger.get_mut(&taker_x).unwrap().1 += offer.deposit;
+                    ledger.remove(&offer.vault);
+                    closed.push(offer.vault);
+                }
+            }
+            2 if !open.is_empty() => {
+                let offer = open.remove(rng.gen_range(0, open.len()));
+                let (initializer_x, _) = token_accounts[offer.initializer];
+                let cancel = Instruction::new_with_bytes(
+                    program_id,
+                    &[8],
+                    vec![
+                        AccountMeta::new(parties[offer.initializer].pubkey(), true),
+                        AccountMeta::new(offer.vault, false),
+                        AccountMeta::new(initializer_x, false),
+                        AccountMeta::new(offer.escrow, false),
+                        AccountMeta::new_readonly(spl_token::id(), false),
+                        AccountMeta::new_readonly(pda, false),
+                        AccountMeta::new_readonly(sysvar::clock::id(), false),
+                    ],
+                );
+                assert!(
+                    send(&mut context, &[cancel], &[&parties[offer.initializer]]).await,
+                    "seed {} step {}",
+                    seed,
+                    step
+                );
+                ledger.get_mut(&initializer_x).unwrap().1 += offer.deposit;
+                ledger.remove(&offer.vault);
+                closed.push(offer.vault);
+            }
+            _ => continue,
+        }
+        check_ledger(&mut context, &ledger, &closed, &[mint_x, mint_y]).await;
+    }
+}
+
+#[tokio::test]
+#[cfg(feature = "test-bpf")]
+async fn test_random_sequences_conserve_tokens() {
+    for seed in 0..8 {
+        run_random_sequence(seed).await;
+    }
+}
+
+/// Compute units each instruction may use, checked in to `tests/compute_units.json` so a change
+/// that costs programs composing with the escrow more units shows up in review
+#[cfg(feature = "test-bpf")]
+fn compute_unit_ceiling(instruction: &str) -> u64 {
+    /// Units an instruction may use above its checked in count before the guard fails, in percent
+    const TOLERANCE_PERCENT: u64 = 5;
+    let path = format!("{}/tests/compute_units.json", env!("CARGO_MANIFEST_DIR"));
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let units = json[instruction].as_u64().unwrap();
+    units + units * TOLERANCE_PERCENT / 100
+}
+
+#[cfg(feature = "test-bpf")]
+fn assert_within_compute_units(instruction: &str, result: Result<(), TransportError>) {
+    if let Err(TransportError::TransactionError(TransactionError::InstructionError(
+        _,
+        InstructionError::ComputationalBudgetExceeded,
+    ))) = result
+    {
+        panic!(
+            "{} used more than {} compute units; if the cost is intended, raise its count in tests/compute_units.json",
+            instruction,
+            compute_unit_ceiling(instruction)
+        );
+    }
+    result.unwrap();
+}
+
+/// A replay whose escrow is already open on a plain vault, as `InitEscrow` leaves it, so the
+/// instruction under test is the only one its compute limit applies to
+#[cfg(feature = "test-bpf")]
+async fn open_replay(bpf_compute_max_units: u64) -> Replay {
+    let program_id = paulx_solana_escrow::id();
+    let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+    let (deposit_mint, _mint) = fixture("mint_with_freeze_authority");
+    let (vault, vault_account) = fixture("token_account_delegated");
+    let deposit_amount = TokenAccount::unpack(&vault_account.data).unwrap().amount;
+    let escrow = Escrow {
+        is_initialized: true,
+        initializer_pubkey: keypair("initializer").pubkey(),
+        temp_token_account_pubkey: vault,
+        initializer_token_to_receive_account_pubkey: keypair("initializer y token account").pubkey(),
+        expected_amount: Replay::EXPECTED_AMOUNT,
+        ..Escrow::default()
+    };
+    let mut escrow_data = vec![0; Escrow::get_packed_len()];
+    Escrow::pack(escrow, &mut escrow_data).unwrap();
+    let escrow_account = Account {
+        lamports: Rent::default().minimum_balance(Escrow::get_packed_len()),
+        owner: program_id,
+        data: escrow_data,
+        ..Account::default()
+    };
+    Replay::start_metered(
+        "token_account_delegated",
+        program_id,
+        vec![
+            (vault, token_account(deposit_mint, pda, deposit_amount)),
+            (keypair("escrow").pubkey(), escrow_account),
+        ],
+        Some(bpf_compute_max_units),
+    )
+    .await
+}
+
+/// Under `cargo test-bpf` each instruction runs with its checked in compute units as the limit.
+/// The native processor is not metered, so without BPF this only checks that they succeed.
+#[tokio::test]
+#[cfg(feature = "test-bpf")]
+async fn test_instructions_stay_within_compute_units() {
+    let mut replay = Replay::start_metered(
+        "token_account_delegated",
+        paulx_solana_escrow::id(),
+        vec![],
+        Some(compute_unit_ceiling("InitEscrow")),
+    )
+    .await;
+    assert_within_compute_units("InitEscrow", replay.init().await);
+
+    let mut replay = open_replay(compute_unit_ceiling("Exchange")).await;
+    assert_within_compute_units("Exchange", replay.exchange().await);
+
+    let mut replay = open_replay(compute_unit_ceiling("Cancel")).await;
+    let cancel = replay.cancel_instruction();
+    assert_within_compute_units("Cancel", replay.process(cancel).await);
+}
@@ -0,0 +1,30 @@
+//! The BPF build of the program, checked for functions whose stack frames outgrow the 4KB the
+//! runtime gives each of them. The toolchain only prints these as errors while still producing the
+//! program, which then fails at runtime in whichever instruction reaches the function.
+
+#[cfg(feature = "test-bpf")]
+use std::process::Command;
+
+#[test]
+#[cfg(feature = "test-bpf")]
+fn test_bpf_build_fits_the_stack_frame() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let output = Command::new(env!("CARGO"))
+        .args(&["build-bpf", "--manifest-path"])
+        .arg(format!("{}/Cargo.toml", manifest_dir))
+        // a target directory of its own, the one of `cargo test-bpf` being locked by it
+        .env("CARGO_TARGET_DIR", format!("{}/target/stack-check", manifest_dir))
+        .output()
+        .expect("error: cargo build-bpf");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    let overflows: Vec<_> = stderr
+        .lines()
+        .filter(|line| line.contains("exceeded max offset"))
+        .collect();
+    assert!(
+        overflows.is_empty(),
+        "functions outgrowing the BPF stack frame:\n{}",
+        overflows.join("\n")
+    );
+}